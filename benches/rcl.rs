@@ -0,0 +1,184 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Benchmarks for parsing, formatting, evaluation, and pretty-printing.
+//!
+//! Run with `cargo bench`. To run only some benchmarks, pass a substring of
+//! their name, e.g. `cargo bench -- wide`. The inputs are synthetic documents
+//! that stress one aspect each: wide collections, deep nesting, many strings,
+//! and comprehensions with function calls.
+//!
+//! This is a small self-contained harness rather than a benchmark framework,
+//! to keep RCL free of dependencies. It reports the minimum and median time
+//! per iteration; the minimum is the most stable number to compare between
+//! two versions.
+
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use rcl::cmd_fmt::FormatStyle;
+use rcl::loader::Loader;
+use rcl::pprint::Config;
+use rcl::runtime::Value;
+use rcl::source::{DocId, Span};
+use rcl::tracer::VoidTracer;
+
+/// A list of many small dicts, which exercises wide collections.
+fn make_wide_list(n: usize) -> String {
+    let mut out = String::from("[\n");
+    for i in 0..n {
+        writeln!(
+            out,
+            "  {{ id = {i}, enabled = {}, port = {} }},",
+            i % 3 == 0,
+            8000 + i
+        )
+        .unwrap();
+    }
+    out.push_str("]\n");
+    out
+}
+
+/// Deeply nested dicts and lists, which exercises nesting and indentation.
+fn make_deep_nesting(depth: usize, copies: usize) -> String {
+    let mut out = String::from("[\n");
+    for i in 0..copies {
+        for _ in 0..depth {
+            out.push_str("{ inner = [");
+        }
+        write!(out, "{i}").unwrap();
+        for _ in 0..depth {
+            out.push_str("] }");
+        }
+        out.push_str(",\n");
+    }
+    out.push_str("]\n");
+    out
+}
+
+/// A dict with many string keys and values, which exercises strings.
+fn make_many_strings(n: usize) -> String {
+    let mut out = String::from("{\n");
+    for i in 0..n {
+        writeln!(
+            out,
+            "  \"key-{i}\": \"A string value with an escape\\t and some length, number {i}.\","
+        )
+        .unwrap();
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Comprehensions and function calls, which exercises the evaluator.
+fn make_comprehensions(n: usize) -> String {
+    format!(
+        r#"
+let square = x => x * x;
+let items = [for i in std.range(0, {n}): {{ id = i, name = f"item-{{i}}" }}];
+{{
+  squares = [for item in items: square(item.id)],
+  names = {{for item in items: if item.id > 100: item.name}},
+  by_name = {{for item in items: item.name: item.id}},
+  total = items.map(item => item.id).sum(),
+}}
+"#
+    )
+}
+
+fn parse(input: &str) {
+    let mut loader = Loader::new();
+    let id = loader.load_string(input.to_string());
+    loader.get_cst(id).expect("Benchmark input should parse.");
+}
+
+fn format(input: &str) {
+    let style = FormatStyle::default();
+    rcl::cmd_fmt::format_doc(DocId(0), input, &style).expect("Benchmark input should parse.");
+}
+
+fn evaluate(input: &str) -> (Span, Value) {
+    let mut loader = Loader::new();
+    let id = loader.load_string(input.to_string());
+    let mut type_env = rcl::typecheck::prelude();
+    let mut value_env = rcl::runtime::prelude();
+    let value = loader
+        .evaluate(&mut type_env, &mut value_env, id, &mut VoidTracer)
+        .expect("Benchmark input should evaluate.");
+    (loader.get_span(id), value)
+}
+
+fn print_rcl(value: &Value) {
+    let cfg = Config::default();
+    rcl::fmt_rcl::format_rcl(value).println(&cfg);
+}
+
+fn print_json(span: Span, value: &Value) {
+    let cfg = Config::default();
+    rcl::fmt_json::format_json(span, value)
+        .expect("Benchmark value should be json-compatible.")
+        .println(&cfg);
+}
+
+/// Run `f` repeatedly for roughly a second, and print the timings.
+fn bench(name: &str, filter: &[String], mut f: impl FnMut()) {
+    if !filter.is_empty() && !filter.iter().any(|pattern| name.contains(pattern.as_str())) {
+        return;
+    }
+
+    // Warm up caches and the allocator before we start measuring.
+    f();
+
+    let budget = Duration::from_secs(1);
+    let start = Instant::now();
+    let mut samples = Vec::new();
+    while samples.len() < 5 || (start.elapsed() < budget && samples.len() < 1000) {
+        let t0 = Instant::now();
+        f();
+        samples.push(t0.elapsed());
+    }
+    samples.sort();
+
+    let min = samples[0];
+    let median = samples[samples.len() / 2];
+    println!(
+        "{name:<30} min {:>10.3} ms   median {:>10.3} ms   ({} iterations)",
+        min.as_secs_f64() * 1e3,
+        median.as_secs_f64() * 1e3,
+        samples.len(),
+    );
+}
+
+fn main() {
+    // Cargo passes `--bench` to benchmarks, we only take the other arguments
+    // as filters.
+    let filter: Vec<String> = std::env::args()
+        .skip(1)
+        .filter(|arg| !arg.starts_with("--"))
+        .collect();
+
+    let inputs = [
+        ("wide_list", make_wide_list(5_000)),
+        ("deep_nesting", make_deep_nesting(30, 200)),
+        ("many_strings", make_many_strings(5_000)),
+        ("comprehensions", make_comprehensions(5_000)),
+    ];
+
+    for (name, input) in inputs.iter() {
+        bench(&format!("parse/{name}"), &filter, || parse(input));
+        bench(&format!("format/{name}"), &filter, || format(input));
+        bench(&format!("evaluate/{name}"), &filter, || {
+            evaluate(input);
+        });
+
+        let (span, value) = evaluate(input);
+        bench(&format!("print_rcl/{name}"), &filter, || print_rcl(&value));
+        bench(&format!("print_json/{name}"), &filter, || {
+            print_json(span, &value)
+        });
+    }
+}
@@ -0,0 +1,66 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+// C interface to the RCL interpreter, implemented by librcl_capi.
+//
+// All strings are NUL-terminated UTF-8. Functions that produce output return
+// a string that the caller owns, or NULL on failure. On failure, when
+// `error_out` is not NULL, it is set to an error that the caller owns, and on
+// success it is set to NULL. Release strings with `rcl_string_free` and errors
+// with `rcl_error_free`, not with `free`: they are allocated by Rust.
+//
+// Imports are resolved relative to the working directory, and cannot escape
+// it, like `rcl evaluate --sandbox=workdir`. `trace` messages go to stderr.
+// The functions do not share state, so they can be called from any thread.
+// An internal panic is reported as an `RCL_ERROR_RUNTIME` error whose message
+// starts with "Internal error:", when built with the `release-capi` profile.
+
+#ifndef RCL_H
+#define RCL_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+// The kind of an error, equal to the exit code of the command-line tool.
+enum rcl_error_kind {
+  RCL_ERROR_RUNTIME = 1,
+  RCL_ERROR_USAGE = 2,
+  RCL_ERROR_PARSE = 3,
+  RCL_ERROR_TYPE = 4,
+};
+
+typedef struct rcl_error {
+  // One of `enum rcl_error_kind`.
+  int32_t kind;
+  // The error message, without source location.
+  char *message;
+  // The full report with the source location, as `rcl evaluate` prints it,
+  // but without colors.
+  char *report;
+} rcl_error;
+
+// Evaluate an RCL document, and format the result as pretty-printed JSON that
+// tries to not exceed `width` columns.
+char *rcl_evaluate_json(const char *source, uint32_t width, rcl_error **error_out);
+
+// Format RCL source code, like `rcl format` does.
+char *rcl_format(const char *source, uint32_t width, rcl_error **error_out);
+
+// Release a string returned by this library. Passing NULL is allowed.
+void rcl_string_free(char *s);
+
+// Release an error and its strings. Passing NULL is allowed.
+void rcl_error_free(rcl_error *err);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,295 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! C bindings to the RCL interpreter.
+//!
+//! See `rcl.h` for the interface and the rules for who owns which memory. All
+//! strings that cross the boundary are NUL-terminated UTF-8. Strings that we
+//! return are allocated by Rust, so the caller must release them with the
+//! matching `rcl_*_free` function, and not with `free`.
+
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use rcl::error::{Error, ErrorKind, Result};
+use rcl::loader::{Loader, SandboxMode};
+use rcl::pprint::{self, Doc};
+use rcl::runtime;
+use rcl::source::DocId;
+use rcl::tracer::StderrTracer;
+use rcl::typecheck;
+
+/// An error, see `rcl_error` in `rcl.h`.
+#[repr(C)]
+pub struct RclError {
+    /// The kind of error, equal to the exit code of the command-line tool.
+    pub kind: i32,
+
+    /// The error message, without source location.
+    pub message: *mut c_char,
+
+    /// The full report, as `rcl evaluate` would print it, without colors.
+    pub report: *mut c_char,
+}
+
+/// Move the string into a C string that the caller owns.
+fn into_c_string(s: String) -> *mut c_char {
+    // RCL strings can contain NUL, but our outputs escape it, so this only
+    // happens in degenerate cases. Replace it rather than truncating.
+    let s = match s.contains('\0') {
+        true => s.replace('\0', "\u{fffd}"),
+        false => s,
+    };
+    CString::new(s)
+        .expect("NUL characters have been replaced.")
+        .into_raw()
+}
+
+fn print_plain(doc: Doc, width: u32) -> String {
+    let cfg = pprint::Config {
+        width,
+        ..pprint::Config::default()
+    };
+    doc.println(&cfg).to_string_no_markup()
+}
+
+/// Describe the payload of a panic, which is usually a string.
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> &str {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s
+    } else {
+        "unknown panic"
+    }
+}
+
+/// Load the source, run `f` on it, and return either the output or the error.
+///
+/// A panic must not unwind into the caller, that is undefined behavior for an
+/// `extern "C"` function, so we turn it into an internal error. This only works
+/// when the library is built with `panic = "unwind"`, see `c_bindings.md`.
+///
+/// # Safety
+///
+/// `source` must be null or point to a NUL-terminated string, and `error_out`
+/// must be null or valid for writes.
+unsafe fn run<F>(
+    source: *const c_char,
+    width: u32,
+    error_out: *mut *mut RclError,
+    f: F,
+) -> *mut c_char
+where
+    F: FnOnce(&mut Loader, DocId) -> Result<String>,
+{
+    if !error_out.is_null() {
+        *error_out = ptr::null_mut();
+    }
+    let mut loader = Loader::new();
+    // The loader is not used again after a panic, apart from rendering the
+    // error report from the documents it holds, so it is fine if a panic left
+    // it in an inconsistent state.
+    let result = panic::catch_unwind(AssertUnwindSafe(|| load_and_run(&mut loader, source, f)))
+        .unwrap_or_else(|payload| {
+            Error::new(format!("Internal error: {}", panic_message(&*payload)))
+                .with_kind(ErrorKind::Runtime)
+                .err()
+        });
+    match result {
+        Ok(output) => into_c_string(output),
+        Err(err) => {
+            if !error_out.is_null() {
+                let kind = err.kind.exit_code();
+                let message = print_plain(err.message.clone(), width);
+                let report = print_plain(err.report(&loader.as_inputs()), width);
+                *error_out = Box::into_raw(Box::new(RclError {
+                    kind,
+                    message: into_c_string(message.trim_end().to_string()),
+                    report: into_c_string(report),
+                }));
+            }
+            ptr::null_mut()
+        }
+    }
+}
+
+/// The part of [`run`] that may panic.
+///
+/// # Safety
+///
+/// `source` must be null or point to a NUL-terminated string.
+unsafe fn load_and_run<F>(loader: &mut Loader, source: *const c_char, f: F) -> Result<String>
+where
+    F: FnOnce(&mut Loader, DocId) -> Result<String>,
+{
+    if source.is_null() {
+        Error::new("The source must not be null.")
+            .with_kind(ErrorKind::Usage)
+            .err()
+    } else {
+        match CStr::from_ptr(source).to_str() {
+            Ok(src) => loader
+                .initialize_filesystem(SandboxMode::Workdir, None)
+                .and_then(|()| {
+                    let doc = loader.load_string(src.to_string());
+                    f(loader, doc)
+                }),
+            Err(..) => Error::new("The source is not valid UTF-8.")
+                .with_kind(ErrorKind::Usage)
+                .err(),
+        }
+    }
+}
+
+/// Evaluate an RCL document, and format the result as pretty-printed JSON.
+///
+/// # Safety
+///
+/// See `rcl_evaluate_json` in `rcl.h`.
+#[no_mangle]
+pub unsafe extern "C" fn rcl_evaluate_json(
+    source: *const c_char,
+    width: u32,
+    error_out: *mut *mut RclError,
+) -> *mut c_char {
+    run(source, width, error_out, |loader, doc| {
+        let mut tracer = StderrTracer::new(None);
+        let mut type_env = typecheck::prelude();
+        let mut value_env = runtime::prelude();
+        let value = loader.evaluate(&mut type_env, &mut value_env, doc, &mut tracer)?;
+        let json = rcl::fmt_json::format_json(loader.get_span(doc), &value)?;
+        Ok(print_plain(json, width))
+    })
+}
+
+/// Format RCL source code, like `rcl format` does.
+///
+/// # Safety
+///
+/// See `rcl_format` in `rcl.h`.
+#[no_mangle]
+pub unsafe extern "C" fn rcl_format(
+    source: *const c_char,
+    width: u32,
+    error_out: *mut *mut RclError,
+) -> *mut c_char {
+    run(source, width, error_out, |loader, doc| {
+        let cst = loader.get_cst(doc)?;
+        let data = loader.get_doc(doc).data;
+        Ok(print_plain(rcl::fmt_cst::format_expr(data, &cst), width))
+    })
+}
+
+/// Release a string returned by `rcl_evaluate_json` or `rcl_format`.
+///
+/// # Safety
+///
+/// `s` must be null or a string returned by this library, not yet released.
+#[no_mangle]
+pub unsafe extern "C" fn rcl_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Release an error, including its strings.
+///
+/// # Safety
+///
+/// `err` must be null or an error returned by this library, not yet released.
+#[no_mangle]
+pub unsafe extern "C" fn rcl_error_free(err: *mut RclError) {
+    if !err.is_null() {
+        let err = Box::from_raw(err);
+        rcl_string_free(err.message);
+        rcl_string_free(err.report);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{rcl_error_free, rcl_evaluate_json, rcl_format, rcl_string_free, run, RclError};
+    use std::ffi::{c_char, CStr, CString};
+    use std::ptr;
+
+    /// Call the function, return the output or the kind and message of the error.
+    fn call(
+        f: unsafe extern "C" fn(*const c_char, u32, *mut *mut RclError) -> *mut c_char,
+        source: &str,
+    ) -> std::result::Result<String, (i32, String)> {
+        let source = CString::new(source).unwrap();
+        let mut err: *mut RclError = ptr::null_mut();
+        unsafe {
+            let out = f(source.as_ptr(), 80, &mut err);
+            if out.is_null() {
+                assert!(!err.is_null());
+                let result = (
+                    (*err).kind,
+                    CStr::from_ptr((*err).message).to_str().unwrap().to_string(),
+                );
+                let report = CStr::from_ptr((*err).report).to_str().unwrap();
+                assert!(report.contains("Error: "));
+                rcl_error_free(err);
+                Err(result)
+            } else {
+                assert!(err.is_null());
+                let result = CStr::from_ptr(out).to_str().unwrap().to_string();
+                rcl_string_free(out);
+                Ok(result)
+            }
+        }
+    }
+
+    #[test]
+    fn evaluate_json_returns_output_or_error() {
+        assert_eq!(
+            call(rcl_evaluate_json, "{ a = [1, 2 * 3] }"),
+            Ok("{\"a\": [1, 6]}\n".to_string()),
+        );
+        assert_eq!(
+            call(rcl_evaluate_json, "let f = x => x; f"),
+            Err((1, "Functions cannot be exported as json.".to_string())),
+        );
+        assert!(matches!(call(rcl_evaluate_json, "{"), Err((3, _))));
+    }
+
+    #[test]
+    fn format_formats_source() {
+        assert_eq!(
+            call(rcl_format, "{a=1,\n}"),
+            Ok("{\n  a = 1,\n}\n".to_string()),
+        );
+        // A null source is an error, not undefined behavior.
+        let mut err: *mut RclError = ptr::null_mut();
+        unsafe {
+            assert!(rcl_format(ptr::null(), 80, &mut err).is_null());
+            assert_eq!((*err).kind, 2);
+            rcl_error_free(err);
+        }
+    }
+
+    #[test]
+    fn run_turns_panic_into_internal_error() {
+        let source = CString::new("null").unwrap();
+        let mut err: *mut RclError = ptr::null_mut();
+        unsafe {
+            let out = run(source.as_ptr(), 80, &mut err, |_loader, _doc| {
+                panic!("Something is broken.")
+            });
+            assert!(out.is_null());
+            assert!(!err.is_null());
+            assert_eq!((*err).kind, 1);
+            let message = CStr::from_ptr((*err).message).to_str().unwrap();
+            assert_eq!(message, "Internal error: Something is broken.");
+            let report = CStr::from_ptr((*err).report).to_str().unwrap();
+            assert!(report.contains("Error: Internal error"));
+            rcl_error_free(err);
+        }
+    }
+}
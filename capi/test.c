@@ -0,0 +1,39 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+// This file tests the C interface. Build the library and run it with:
+//
+//   cargo build --manifest-path capi/Cargo.toml
+//   cc capi/test.c -Icapi -Ltarget/debug -lrcl_capi -o target/test_capi
+//   LD_LIBRARY_PATH=target/debug target/test_capi
+
+#include <assert.h>
+#include <stddef.h>
+#include <string.h>
+
+#include "rcl.h"
+
+int main(void) {
+  rcl_error *err = NULL;
+
+  char *out = rcl_evaluate_json("{ answer = 6 * 7 }", 80, &err);
+  assert(err == NULL);
+  assert(strcmp(out, "{\"answer\": 42}\n") == 0);
+  rcl_string_free(out);
+
+  out = rcl_evaluate_json("{ answer = 6 * }", 80, &err);
+  assert(out == NULL);
+  assert(err->kind == RCL_ERROR_PARSE);
+  assert(strstr(err->report, err->message) != NULL);
+  rcl_error_free(err);
+
+  out = rcl_format("[1,2,\n]", 80, NULL);
+  assert(strcmp(out, "[\n  1,\n  2,\n]\n") == 0);
+  rcl_string_free(out);
+
+  return 0;
+}
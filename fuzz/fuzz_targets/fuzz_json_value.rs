@@ -0,0 +1,16 @@
+#![no_main]
+
+//! This fuzzer tests that json output of random values means the same value to
+//! `serde_json`. See also the [`rcl_fuzz::json_value`] module.
+
+use libfuzzer_sys::fuzz_target;
+
+use rcl_fuzz::json_value::{check_json_roundtrip, random_value};
+use rcl_fuzz::random::WyRand;
+
+fuzz_target!(|input: (u64, u8)| {
+    let (seed, width) = input;
+    let mut rng = WyRand::new(seed);
+    let value = random_value(&mut rng, 5);
+    check_json_roundtrip(&value, width as u32);
+});
@@ -0,0 +1,187 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Differential testing of the json formatter against `serde_json`.
+//!
+//! The über fuzzer already checks that json output of evaluated programs can
+//! be parsed, but programs that the fuzzer generates rarely contain unusual
+//! strings or extreme integers. Here we generate random values directly,
+//! format them as json, parse the result with `serde_json`, and check that we
+//! get back the same value. This catches escaping mistakes, integers that
+//! don't round-trip, and output that is not valid json at some width.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::rc::Rc;
+
+use rcl::pprint;
+use rcl::runtime::Value;
+use rcl::source::{DocId, Span};
+
+use crate::random::WyRand;
+
+/// Characters that need escaping, or that are easy to get wrong.
+const SPECIAL_CHARS: &[char] = &[
+    '"',
+    '\\',
+    '/',
+    '\n',
+    '\r',
+    '\t',
+    '\x00',
+    '\x08',
+    '\x0c',
+    '\x1b',
+    '\x1f',
+    '\x7f',
+    '\u{80}',
+    '\u{2028}',
+    '\u{2029}',
+    '\u{feff}',
+    '\u{fffd}',
+    '\u{10ffff}',
+    'é',
+    '😀',
+];
+
+/// Integers at the boundaries, where serializers tend to break.
+const SPECIAL_INTS: &[i64] = &[
+    0,
+    1,
+    -1,
+    i64::MIN,
+    i64::MAX,
+    i32::MIN as i64,
+    i32::MAX as i64,
+    // Beyond 2^53, the integer is no longer exact in a double.
+    (1 << 53) + 1,
+    -(1 << 53) - 1,
+];
+
+fn random_char(rng: &mut WyRand) -> char {
+    match rng.next_range_u8(0..4) {
+        0 => SPECIAL_CHARS[rng.next_range_usize(0..SPECIAL_CHARS.len())],
+        // Any code point, surrogates are not chars so we substitute those.
+        1 => char::from_u32((rng.next_u64() % 0x11_0000) as u32).unwrap_or('\u{fffd}'),
+        // Printable ascii.
+        _ => (b' ' + rng.next_range_u8(0..95)) as char,
+    }
+}
+
+fn random_string(rng: &mut WyRand) -> Rc<str> {
+    let len = rng.next_range_usize(0..12);
+    (0..len)
+        .map(|_| random_char(rng))
+        .collect::<String>()
+        .into()
+}
+
+fn random_int(rng: &mut WyRand) -> i64 {
+    match rng.next_range_u8(0..3) {
+        0 => SPECIAL_INTS[rng.next_range_usize(0..SPECIAL_INTS.len())],
+        1 => rng.next_u64() as i64,
+        _ => rng.next_range_usize(0..2000) as i64 - 1000,
+    }
+}
+
+/// Generate a random json-compatible value, nested at most `depth` levels.
+pub fn random_value(rng: &mut WyRand, depth: u32) -> Value {
+    let n_kinds = if depth == 0 { 4 } else { 7 };
+    match rng.next_range_u8(0..n_kinds) {
+        0 => Value::Null,
+        1 => Value::Bool(rng.next_u8() & 1 == 1),
+        2 => Value::Int(random_int(rng).into()),
+        3 => Value::String(random_string(rng)),
+        4 => {
+            let len = rng.next_range_usize(0..5);
+            let elems = (0..len).map(|_| random_value(rng, depth - 1)).collect();
+            Value::List(Rc::new(elems))
+        }
+        5 => {
+            let len = rng.next_range_usize(0..5);
+            let elems: BTreeSet<Value> = (0..len).map(|_| random_value(rng, depth - 1)).collect();
+            Value::Set(Rc::new(elems))
+        }
+        _ => {
+            let len = rng.next_range_usize(0..5);
+            let elems: BTreeMap<Value, Value> = (0..len)
+                .map(|_| {
+                    let key = Value::String(random_string(rng));
+                    (key, random_value(rng, depth - 1))
+                })
+                .collect();
+            Value::Dict(Rc::new(elems))
+        }
+    }
+}
+
+/// Return whether a value parsed by `serde_json` is the same as the RCL value.
+///
+/// Sets are formatted as json arrays, in the order of the set.
+fn json_equals(v: &Value, j: &serde_json::Value) -> bool {
+    use serde_json::Value as Json;
+    match (v, j) {
+        (Value::Null, Json::Null) => true,
+        (Value::Bool(x), Json::Bool(y)) => x == y,
+        (Value::Int(x), Json::Number(y)) => {
+            y.as_i64().map(i128::from).or(y.as_u64().map(i128::from)) == Some(*x)
+        }
+        (Value::String(x), Json::String(y)) => x.as_ref() == y.as_str(),
+        (Value::List(xs), Json::Array(ys)) => {
+            xs.len() == ys.len() && xs.iter().zip(ys).all(|(x, y)| json_equals(x, y))
+        }
+        (Value::Set(xs), Json::Array(ys)) => {
+            xs.len() == ys.len() && xs.iter().zip(ys).all(|(x, y)| json_equals(x, y))
+        }
+        (Value::Dict(xs), Json::Object(ys)) => {
+            xs.len() == ys.len()
+                && xs.iter().all(|(k, x)| match k {
+                    Value::String(k) => ys.get(k.as_ref()).map_or(false, |y| json_equals(x, y)),
+                    _ => false,
+                })
+        }
+        _ => false,
+    }
+}
+
+/// Format the value as json, and check that `serde_json` parses it back.
+///
+/// Panics if the output is not valid json, or if it means a different value.
+pub fn check_json_roundtrip(value: &Value, width: u32) {
+    let span = Span::new(DocId(0), 0, 0);
+    let doc = rcl::fmt_json::format_json(span, value)
+        .expect("Generated values are json-compatible, formatting should succeed.");
+    let json_str = doc
+        .println(&pprint::Config {
+            width,
+            ..pprint::Config::default()
+        })
+        .to_string_no_markup();
+    let parsed = match serde_json::from_str::<serde_json::Value>(&json_str[..]) {
+        Ok(parsed) => parsed,
+        Err(err) => panic!("RCL output should be parseable, but got {err:?}:\n{json_str}"),
+    };
+    assert!(
+        json_equals(value, &parsed),
+        "Json output should mean the same value, but got:\n{json_str}",
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_json_roundtrip, random_value};
+    use crate::random::WyRand;
+
+    #[test]
+    fn json_output_round_trips_through_serde_json() {
+        for seed in 0..2_000 {
+            let mut rng = WyRand::new(seed);
+            let value = random_value(&mut rng, 4);
+            let width = rng.next_range_usize(0..100) as u32;
+            check_json_roundtrip(&value, width);
+        }
+    }
+}
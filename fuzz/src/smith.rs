@@ -59,9 +59,58 @@ const BUILTINS: &[&str] = &[
     "to_uppercase",
     "values",
     // Stdlib and its functions
+    "abs",
+    "base64",
+    "base64_decode",
+    "ceil",
+    "contains_key",
+    "datetime",
+    "day",
+    "dict",
+    "encode",
+    "encode_pretty",
+    "float",
+    "floor",
+    "format",
+    "from_pairs",
+    "hash",
+    "hex",
+    "hex_decode",
+    "hour",
+    "int",
+    "is_subset_of",
+    "is_superset_of",
+    "json",
+    "list",
+    "max",
+    "min",
+    "minute",
+    "month",
+    "pad_left",
+    "pad_right",
+    "parse",
+    "parse_duration",
+    "parse_iso8601",
     "range",
     "read_file_utf8",
+    "round",
+    "second",
+    "set",
+    "sha256",
     "std",
+    "string",
+    "to_pairs",
+    "to_sorted_list",
+    "to_lower",
+    "to_upper",
+    "trim",
+    "try_parse",
+    "unique",
+    "url_decode",
+    "url_encode",
+    "weekday",
+    "year",
+    "zip",
 ];
 
 const BUILTIN_TYPES: &[&str] = &[
@@ -64,13 +64,13 @@ pub fn fuzz_main(mode: Mode, input: &str) {
     if let Err(err) = result {
         let inputs = loader.as_inputs();
         let err_doc = err.report(&inputs);
-        let cfg = pprint::Config { width: 80 };
+        let cfg = pprint::Config::default();
         let _ = err_doc.println(&cfg);
     }
 }
 
 fn fuzz_main_impl(loader: &mut Loader, mode: Mode, input: &str) -> Result<()> {
-    let mut cfg = pprint::Config { width: 80 };
+    let mut cfg = pprint::Config::default();
 
     match mode {
         Mode::Eval => {
@@ -19,6 +19,9 @@ and then prints whether they match. Inputs and expectations are separated by a
 line that contains "# output:". Blank lines preceding it are not considered part
 of the input.
 
+For `rcl check`, the expected output ends in a "# exit code: N" line with the
+exit code of the command.
+
 SYNOPSIS
 
   golden/run.py [--rewrite-output] [<file>...]
@@ -38,8 +41,10 @@ OPTIONS
 
 """
 
+import configparser
 import difflib
 import os
+import plistlib
 import re
 import subprocess
 import sys
@@ -82,11 +87,18 @@ def test_one(fname: str, fname_friendly: str, *, rewrite_output: bool) -> Option
     # Allow overriding the binary that we run.
     rcl_bin = os.getenv("RCL_BIN", default="target/debug/rcl")
 
+    # For some commands, the exit code is part of the expected output.
+    check_exit_code = False
+
     # Decide which subcommand to test based on the test directory.
     match os.path.basename(os.path.dirname(fname)):
         case "build":
             cmd = ["build", "--dry-run"]
 
+        case "check":
+            cmd = ["check"]
+            check_exit_code = True
+
         case "error" | "types":
             cmd = ["eval"]
 
@@ -96,21 +108,87 @@ def test_one(fname: str, fname_friendly: str, *, rewrite_output: bool) -> Option
         case "error_raw":
             cmd = ["eval", "--format=raw"]
 
+        case "csv":
+            cmd = ["eval", "--format=csv"]
+
+        case "csv_same_keys":
+            cmd = ["eval", "--format=csv", "--require-same-keys"]
+
         case "fmt":
             cmd = ["fmt"]
 
+        case "fmt_ambiguous_wide":
+            # This directory has an `rcl-format.rcl` that counts ambiguous
+            # characters as wide.
+            cmd = ["fmt"]
+
+        case "fmt_tabs":
+            # This directory has an `rcl-format.rcl` that selects tabs.
+            cmd = ["fmt"]
+
+        case "hcl":
+            cmd = ["eval", "--format=hcl"]
+
+        case "ini":
+            cmd = ["eval", "--format=ini"]
+            # Like for TOML, confirm that Python can parse the expected output.
+            # Python does not accept keys before the first section, so we put
+            # those in a section of their own.
+            if not os.path.basename(fname).startswith("error_"):
+                try:
+                    parser = configparser.ConfigParser(interpolation=None)
+                    parser.read_string("[global]\n" + "".join(golden_lines))
+                except Exception as err:
+                    raise Exception(f"Invalid INI in {fname}") from err
+
         case "json":
             cmd = ["eval", "--format=json"]
 
+        case "textproto":
+            cmd = ["eval", "--format=textproto"]
+
+        case "json_canonical":
+            cmd = ["eval", "--format=json-canonical"]
+
+        case "json_compact":
+            cmd = ["eval", "--format=json-compact"]
+
+        case "json_lines":
+            cmd = ["eval", "--format=json-lines"]
+
+        case "json_input":
+            cmd = ["eval", "--input-format=json", "--format=rcl"]
+
         case "html":
             cmd = ["format", "--color=html"]
 
+        case "html_rcl":
+            cmd = ["format", "--color=html-rcl"]
+
+        case "nix":
+            cmd = ["eval", "--format=nix"]
+
+        case "plist":
+            cmd = ["eval", "--format=plist"]
+            # Like for TOML, confirm that Python can parse the expected output.
+            if not os.path.basename(fname).startswith("error_"):
+                try:
+                    plistlib.loads("".join(golden_lines).encode("utf-8"))
+                except Exception as err:
+                    raise Exception(f"Invalid plist in {fname}") from err
+
+        case "properties":
+            cmd = ["eval", "--format=properties"]
+
         case "raw":
             cmd = ["eval", "--format=raw"]
 
         case "rcl":
             cmd = ["eval", "--format=rcl"]
 
+        case "schema":
+            cmd = ["schema"]
+
         case "toml":
             cmd = ["eval", "--format=toml"]
             # For TOML, when the test case is not an error, we additionally test
@@ -122,9 +200,18 @@ def test_one(fname: str, fname_friendly: str, *, rewrite_output: bool) -> Option
                 except Exception as err:
                     raise Exception(f"Invalid TOML in {fname}") from err
 
+        case "tsv":
+            cmd = ["eval", "--format=tsv"]
+
+        case "validate_schema":
+            cmd = ["eval", "--validate-schema=_schema.json"]
+
         case "yaml_stream":
             cmd = ["eval", "--format=yaml-stream"]
 
+        case "yaml_stream_sources":
+            cmd = ["eval", "--format=yaml-stream", "--source-comments"]
+
         case unknown:
             raise ValueError(f"No command-line known for {unknown}.")
 
@@ -141,6 +228,8 @@ def test_one(fname: str, fname_friendly: str, *, rewrite_output: bool) -> Option
         STRIP_ESCAPES.sub("", line).replace(common_root, "/WORKDIR")
         for line in result.stdout.splitlines() + result.stderr.splitlines()
     ]
+    if check_exit_code:
+        output_lines.append(f"# exit code: {result.returncode}")
 
     report_lines: List[str] = []
 
@@ -66,8 +66,9 @@ expr_unop: expr_not_op | UNOP expr_unop;
 // This rule for binop is simplified here. In reality, there should be a
 // dedicated one for every binop, so you can repeat the same binop without
 // parens, but you cannot mix multiple different ones. To keep this file simple,
-// we don't expand it out here and we have just one generic binop.
-expr_binop: expr_not_op | expr_not_op BINOP expr_binop;
+// we don't expand it out here and we have just one generic binop. The final
+// operand may have a unary operator, e.g. "a - -1" or "a and not b".
+expr_binop: expr_not_op | UNOP expr_unop | expr_not_op BINOP expr_binop;
 
 // Because we disallow confusing operator combinations without parens, the nodes
 // of an operator are "not binary operator", "not binop" for short.
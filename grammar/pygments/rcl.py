@@ -106,6 +106,7 @@ _root_base = [
                 "Int",
                 "List",
                 "Null",
+                "Number",
                 "Set",
                 "String",
                 "Union",
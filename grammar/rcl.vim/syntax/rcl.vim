@@ -1,77 +1,76 @@
-" RCL -- A reasonable configuration language.
-" Copyright 2023 Ruud van Asseldonk
-"
-" Licensed under the Apache License, Version 2.0 (the "License");
-" you may not use this file except in compliance with the License.
-" A copy of the License has been included in the root of the repository.
+" Vim syntax file for RCL, generated by 'rcl generate-grammar vim'.
+
+if exists('b:current_syntax')
+  finish
+endif
 
 syn keyword rclNull         null
-syn keyword rclBoolean      true false
-syn keyword rclConditional  if else
+syn keyword rclBoolean      false true
+syn keyword rclConditional  else if
 syn keyword rclRepeat       for
 syn keyword rclOperator     and not or
-syn keyword rclKeyword      in let import
+syn keyword rclKeyword      import in let
 syn keyword rclException    assert trace
-syn cluster rclKeyword      contains=rclBoolean,rclConditional,rclRepeat,rclOperator,rclKeyword,rclException
+syn cluster rclKeywords     contains=rclNull,rclBoolean,rclConditional,rclRepeat,rclOperator,rclKeyword,rclException
 
 syn match rclOperator '<='
 syn match rclOperator '>='
 syn match rclOperator '=='
 syn match rclOperator '!='
-syn match rclOperator '=>'
 syn match rclOperator '->'
-syn match rclOperator '|'
+syn match rclOperator '=>'
+syn match rclOperator '<'
+syn match rclOperator '>'
+syn match rclOperator '!'
+syn match rclOperator '*'
 syn match rclOperator '+'
 syn match rclOperator '-'
-syn match rclOperator '*'
 syn match rclOperator '/'
+syn match rclOperator '|'
 
 syn keyword rclTodo FIXME NOTE TODO HACK contained
 syn match   rclComment '//.*' contains=rclTodo,@Spell
+syn match   rclShebang '\%^#!.*'
 
-" TODO: Something in the float is not yet working right.
-syn match   rclFloat       '\<[0-9_]\+\.[0-9_]\+\([eE][+-]\?[0-9_]\+\)\?\>'
 syn match   rclHexadecimal '\<0x[0-9a-fA-F_]\+\>'
 syn match   rclBinary      '\<0b[01_]\+\>'
-syn match   rclDecimal     '\<[0-9_]\+\>'
-syn cluster rclNumber      contains=rclFloat,rclHexadecimal,rclBinary,rclDecimal
+syn match   rclDecimal     '\<\(0\|[1-9][0-9_]*\)\(\.[0-9][0-9_]*\)\?\([eE][+-]\?[0-9][0-9_]*\)\?\>'
+syn cluster rclNumber      contains=rclHexadecimal,rclBinary,rclDecimal
 
-syn region  rclStringDouble start='"' end='"' skip='\\"'
-syn region  rclStringTriple start='"""' end='"""' skip='\\"'
+syn match   rclEscape '\\\(u{[0-9a-fA-F_]*}\|u[0-9a-fA-F]\{4}\|.\)' contained
+syn region  rclStringDouble start='"' end='"' skip='\\\\\|\\"' contains=rclEscape
+syn region  rclStringTriple start='"""' end='"""' skip='\\\\\|\\"' contains=rclEscape
 
 syn region  rclInterpolation matchgroup=rclInterpolationDelimiter start="{" end="}" contained contains=@rclExpr
-syn region  rclFormatDouble  start='f"'   end='"'   skip='\\"\|\\{' contains=rclInterpolation
-syn region  rclFormatTriple  start='f"""' end='"""' skip='\\"\|\\{' contains=rclInterpolation
-
-" See also https://vi.stackexchange.com/questions/5966/ for why the `contains`
-" needs to end in `[]`.
-syn keyword rclBuiltin all any chars contains[] empty_set ends_with except filter flat_map fold get group_by join key_by keys len map parse_int remove_prefix remove_suffix replace reverse sort split split_lines starts_with std sum to_lowercase to_uppercase values
-
-syn match   rclType '\<\(Any\|Bool\|Dict\|Int\|List\|Null\|Set\|String\|Void\)\>'
-
+syn region  rclFormatDouble  start='f"'   end='"'   skip='\\\\\|\\"\|\\{' contains=rclEscape,rclInterpolation
+syn region  rclFormatTriple  start='f"""' end='"""' skip='\\\\\|\\"\|\\{' contains=rclEscape,rclInterpolation
 syn cluster rclString contains=rclStringDouble,rclStringTriple,rclFormatDouble,rclFormatTriple
-highlight link rclStringDouble rclString
-highlight link rclStringTriple rclString
-highlight link rclFormatDouble rclString
-highlight link rclFormatTriple rclString
 
-syn cluster rclExpr contains=@rclKeyword,rclOperator,@rclNumber,rclComment,rclBuiltin,rclType,@rclString
+syn keyword rclBuiltin all any chars contains[] empty_set ends_with enumerate except filter flat_map fold[] get group_by join key_by keys len map parse_int remove_prefix remove_suffix replace reverse sort split split_lines starts_with std sum to_lowercase to_uppercase values
+syn keyword rclType    Any Bool Dict Int List Null Number Set String Union Void
+
+syn cluster rclExpr contains=@rclKeywords,rclOperator,@rclNumber,rclComment,rclBuiltin,rclType,@rclString
 
-highlight link rclBoolean     Boolean
-highlight link rclConditional Conditional
-highlight link rclRepeat      Repeat
-highlight link rclOperator    Operator
-highlight link rclNull        Keyword
-highlight link rclKeyword     Keyword
-highlight link rclException   Keyword
-highlight link rclComment     Comment
-highlight link rclTodo        Todo
-highlight link rclBuiltin     Function
-highlight link rclType        Type
-highlight link rclString      String
-highlight link rclFloat       Float
-highlight link rclHexadecimal Number
-highlight link rlcBinary      Number
-highlight link rclDecimal     Number
+highlight default link rclBoolean     Boolean
+highlight default link rclConditional Conditional
+highlight default link rclRepeat      Repeat
+highlight default link rclOperator    Operator
+highlight default link rclNull        Constant
+highlight default link rclKeyword     Keyword
+highlight default link rclException   Keyword
+highlight default link rclComment     Comment
+highlight default link rclShebang     PreProc
+highlight default link rclTodo        Todo
+highlight default link rclBuiltin     Function
+highlight default link rclType        Type
+highlight default link rclStringDouble String
+highlight default link rclStringTriple String
+highlight default link rclFormatDouble String
+highlight default link rclFormatTriple String
+highlight default link rclEscape      SpecialChar
+highlight default link rclHexadecimal Number
+highlight default link rclBinary      Number
+highlight default link rclDecimal     Number
+highlight default link rclInterpolationDelimiter Delimiter
 
-highlight link rclInterpolationDelimiter Delimiter
+let b:current_syntax = 'rcl'
@@ -165,7 +165,14 @@ module.exports = grammar({
     // implemented in the parser, it is more akin to a logic error, and it's
     // only disallowed to force code to be unambiguous for humans. For this
     // parser, nothing prevents us from just parsing it.
-    expr_binop: $ => seq($._expr_not_op, repeat1(seq($.binop, $._expr_not_op))),
+    // The final operand may have a unary operator, as in `a - -1`, but
+    // operands before it cannot.
+    expr_binop: $ => seq(
+      $._expr_not_op,
+      repeat(seq($.binop, $._expr_not_op)),
+      $.binop,
+      choice($._expr_not_op, $.expr_unop),
+    ),
 
     _expr_not_op: $ => choice(
       $._expr_term,
@@ -5,7 +5,9 @@
 # you may not use this file except in compliance with the License.
 # A copy of the License has been included in the root of the repository.
 
-from typing import Any, Union
+from typing import Any, Dict, Optional, Union
 
 def load_file(path: str) -> Any: ...
 def loads(src: str) -> Any: ...
+def evaluate(src: str, vars: Optional[Dict[str, Any]] = None) -> Any: ...
+def format(src: str) -> str: ...
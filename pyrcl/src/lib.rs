@@ -5,10 +5,15 @@
 // you may not use this file except in compliance with the License.
 // A copy of the License has been included in the root of the repository.
 
+use std::collections::{BTreeMap, BTreeSet};
+use std::rc::Rc;
+
 use pyo3::prelude::*;
 use rcl::cli::Target;
-use rcl::error::Result;
+use rcl::decimal::Decimal;
+use rcl::error::{Error, Result};
 use rcl::loader::{Loader, SandboxMode};
+use rcl::pprint;
 use rcl::runtime::{self, Value};
 use rcl::source::DocId;
 use rcl::tracer::StderrTracer;
@@ -28,12 +33,22 @@ fn runtime_error(message: &'static str) -> PyErr {
     PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(message)
 }
 
+/// Turn the error into an exception whose message is the full report.
+fn report_error(loader: &Loader, err: Error) -> PyErr {
+    let report = err
+        .report(&loader.as_inputs())
+        .println(&pprint::Config::default())
+        .to_string_no_markup();
+    PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(report)
+}
+
 fn build_python_value(py: Python, v: &Value) -> PyResult<PyObject> {
     use pyo3::types::{PyDict, PyList, PyNone, PySet};
     let result = match v {
         Value::Null => PyNone::get(py).into(),
         Value::Bool(b) => b.to_object(py),
         Value::Int(i) => i.to_object(py),
+        Value::Number(d) => d.to_f64().to_object(py),
         Value::String(s) => s.to_object(py),
         Value::List(xs) => {
             let values = xs
@@ -63,6 +78,107 @@ fn build_python_value(py: Python, v: &Value) -> PyResult<PyObject> {
     Ok(result)
 }
 
+fn build_rcl_value(v: &PyAny) -> PyResult<Value> {
+    use pyo3::types::{
+        PyBool, PyDict, PyFloat, PyFrozenSet, PyList, PyLong, PySet, PyString, PyTuple,
+    };
+    // Bool is a subclass of int in Python, so it has to go first.
+    let result = if v.is_none() {
+        Value::Null
+    } else if let Ok(b) = v.downcast::<PyBool>() {
+        Value::Bool(b.is_true())
+    } else if let Ok(i) = v.downcast::<PyLong>() {
+        Value::Int(i.extract()?)
+    } else if let Ok(f) = v.downcast::<PyFloat>() {
+        match Decimal::from_f64(f.value()) {
+            Some(d) => Value::Number(d),
+            None => {
+                let message = "Infinity and NaN cannot be converted to RCL.";
+                return Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(message));
+            }
+        }
+    } else if let Ok(s) = v.downcast::<PyString>() {
+        Value::String(s.to_str()?.into())
+    } else if let Ok(xs) = v.downcast::<PyList>() {
+        let values = xs.iter().map(build_rcl_value).collect::<PyResult<_>>()?;
+        Value::List(Rc::new(values))
+    } else if let Ok(xs) = v.downcast::<PyTuple>() {
+        let values = xs.iter().map(build_rcl_value).collect::<PyResult<_>>()?;
+        Value::List(Rc::new(values))
+    } else if let Ok(xs) = v.downcast::<PySet>() {
+        let values = xs
+            .iter()
+            .map(build_rcl_value)
+            .collect::<PyResult<BTreeSet<_>>>()?;
+        Value::Set(Rc::new(values))
+    } else if let Ok(xs) = v.downcast::<PyFrozenSet>() {
+        let values = xs
+            .iter()
+            .map(build_rcl_value)
+            .collect::<PyResult<BTreeSet<_>>>()?;
+        Value::Set(Rc::new(values))
+    } else if let Ok(xs) = v.downcast::<PyDict>() {
+        let mut values = BTreeMap::new();
+        for (k, v) in xs.iter() {
+            values.insert(build_rcl_value(k)?, build_rcl_value(v)?);
+        }
+        Value::Dict(Rc::new(values))
+    } else {
+        let message = format!(
+            "Values of type {} cannot be converted to RCL.",
+            v.get_type().name()?
+        );
+        return Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(message));
+    };
+    Ok(result)
+}
+
+/// Evaluate an RCL expression, with the given variables in scope.
+#[pyfunction]
+#[pyo3(name = "evaluate", signature = (src, vars = None))]
+fn evaluate_with_vars(py: Python, src: String, vars: Option<&PyAny>) -> PyResult<PyObject> {
+    let mut type_env = typecheck::prelude();
+    let mut value_env = runtime::prelude();
+    if let Some(vars) = vars {
+        let vars = vars.downcast::<pyo3::types::PyDict>()?;
+        for (name, value) in vars.iter() {
+            let name: &str = name.extract()?;
+            type_env.push(name.into(), typecheck::type_any().clone());
+            value_env.push(name.into(), build_rcl_value(value)?);
+        }
+    }
+
+    let mut loader = Loader::new();
+    let result = loader
+        .initialize_filesystem(SandboxMode::Workdir, None)
+        .and_then(|()| {
+            let doc = loader.load_string(src);
+            let mut tracer = StderrTracer::new(None);
+            loader.evaluate(&mut type_env, &mut value_env, doc, &mut tracer)
+        });
+    match result {
+        Ok(v) => build_python_value(py, &v),
+        Err(err) => Err(report_error(&loader, *err)),
+    }
+}
+
+/// Format RCL source code, like `rcl format` does.
+#[pyfunction]
+fn format(src: String) -> PyResult<String> {
+    let mut loader = Loader::new();
+    let doc = loader.load_string(src);
+    match loader.get_cst(doc) {
+        Ok(cst) => {
+            let data = loader.get_doc(doc).data;
+            let result = rcl::fmt_cst::format_expr(data, &cst);
+            Ok(result
+                .println(&pprint::Config::default())
+                .to_string_no_markup())
+        }
+        Err(err) => Err(report_error(&loader, *err)),
+    }
+}
+
 /// Load an RCL expression from the file at the given path.
 #[pyfunction]
 fn load_file(py: Python, path: String) -> PyResult<PyObject> {
@@ -90,5 +206,7 @@ fn loads(py: Python, src: String) -> PyResult<PyObject> {
 fn pyrcl(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(load_file, m)?)?;
     m.add_function(wrap_pyfunction!(loads, m)?)?;
+    m.add_function(wrap_pyfunction!(evaluate_with_vars, m)?)?;
+    m.add_function(wrap_pyfunction!(format, m)?)?;
     Ok(())
 }
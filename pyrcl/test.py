@@ -24,3 +24,24 @@ assert rcl.load_file("test.rcl") == {
     "name": "Import Test Data",
     "description": "This is only here to test `rcl.load_file` in Python.",
 }
+
+# Test that variables get converted to RCL values and are in scope.
+assert rcl.evaluate("x + 1", vars={"x": 41}) == 42
+assert rcl.evaluate("[for k, v in d: [k, v]]", vars={"d": {1: None}}) == [[1, None]]
+assert rcl.evaluate("xs.len()", vars={"xs": (True, "a", frozenset({1}))}) == 3
+assert rcl.evaluate("{2, 1}", vars=None) == {1, 2}
+
+try:
+    rcl.evaluate("x", vars={"x": 1.5})
+    assert False, "Floats are not valid RCL values."
+except TypeError:
+    pass
+
+# Errors carry the full report.
+try:
+    rcl.evaluate("1 +")
+    assert False, "The input is invalid."
+except RuntimeError as err:
+    assert "Error:" in str(err)
+
+assert rcl.format("{a=1,\n}") == "{\n  a = 1,\n}\n"
@@ -16,7 +16,10 @@
 use crate::ast::{
     CallArg, Expr as AExpr, Expr, FormatFragment, Seq as ASeq, Stmt as AStmt, Type as AType, Yield,
 };
-use crate::cst::{Chain, Expr as CExpr, Seq as CSeq, Stmt as CStmt, StringPart, Type as CType};
+use crate::cst::{
+    Chain, Expr as CExpr, Seq as CSeq, Stmt as CStmt, StringPart, Type as CType, UnOp,
+};
+use crate::decimal::Decimal;
 use crate::error::{IntoError, Result};
 use crate::lexer::QuoteStyle;
 use crate::source::Span;
@@ -37,6 +40,44 @@ impl<'a> Abstractor<'a> {
         Self { input }
     }
 
+    /// Parse a number literal, optionally negated, into its value.
+    ///
+    /// The `span` is the span to report overflow errors at. It includes the
+    /// minus sign for negated literals. Decimal literals with a decimal point
+    /// or exponent become numbers, all other literals become integers.
+    fn number_lit(&self, literal: &CExpr, span: Span, negate: bool) -> Result<AExpr> {
+        let (digits_span, radix) = match literal {
+            // Cut off the 0x or 0b, then parse the rest.
+            CExpr::NumHexadecimal(span) => (span.trim_start(2), 16),
+            CExpr::NumBinary(span) => (span.trim_start(2), 2),
+            CExpr::NumDecimal(span) => (*span, 10),
+            _ => unreachable!("Should only be called on number literals."),
+        };
+        let mut num_str = String::with_capacity(digits_span.len() + 1);
+        if negate {
+            num_str.push('-');
+        }
+        num_str.extend(
+            digits_span
+                .resolve(self.input)
+                .chars()
+                .filter(|ch| *ch != '_'),
+        );
+        if radix == 10 && num_str.contains(['.', 'e', 'E']) {
+            return match Decimal::parse(&num_str) {
+                Some(d) => Ok(AExpr::NumberLit(d)),
+                None => span
+                    .error("Overflow in number literal.")
+                    .with_help("Numbers can have at most 38 significant digits.")
+                    .err(),
+            };
+        }
+        match i128::from_str_radix(&num_str, radix) {
+            Ok(i) => Ok(AExpr::IntegerLit(i)),
+            Err(..) => span.error("Overflow in integer literal.").err(),
+        }
+    }
+
     /// Abstract a string or format string.
     ///
     /// If possible we return `Expr::StringLit`, but if the string has holes, we
@@ -199,40 +240,8 @@ impl<'a> Abstractor<'a> {
 
             CExpr::StringLit { style, parts, .. } => self.string(*style, parts)?,
 
-            CExpr::NumHexadecimal(span) => {
-                // Cut off the 0x, then parse the rest.
-                let num_str = span.trim_start(2).resolve(self.input).replace('_', "");
-                match i64::from_str_radix(&num_str, 16) {
-                    Ok(i) => AExpr::IntegerLit(i),
-                    Err(..) => {
-                        let err = span.error("Overflow in integer literal.");
-                        return Err(err.into());
-                    }
-                }
-            }
-
-            CExpr::NumBinary(span) => {
-                // Cut off the 0b, then parse the rest.
-                let num_str = span.trim_start(2).resolve(self.input).replace('_', "");
-                match i64::from_str_radix(&num_str, 2) {
-                    Ok(i) => AExpr::IntegerLit(i),
-                    Err(..) => {
-                        let err = span.error("Overflow in integer literal.");
-                        return Err(err.into());
-                    }
-                }
-            }
-
-            CExpr::NumDecimal(span) => {
-                // TODO: Handle floats.
-                let num_str = span.resolve(self.input).replace('_', "");
-                match i64::from_str_radix(&num_str, 10) {
-                    Ok(i) => AExpr::IntegerLit(i),
-                    Err(..) => {
-                        let err = span.error("Overflow in integer literal.");
-                        return Err(err.into());
-                    }
-                }
+            CExpr::NumHexadecimal(span) | CExpr::NumBinary(span) | CExpr::NumDecimal(span) => {
+                self.number_lit(expr, *span, false)?
             }
 
             CExpr::IfThenElse {
@@ -272,6 +281,22 @@ impl<'a> Abstractor<'a> {
                 body: Box::new(self.expr(body)?),
             },
 
+            // A minus directly applied to a literal is folded into the literal,
+            // such that the most negative integer can be written, even though
+            // its absolute value does not fit.
+            CExpr::UnOp {
+                op_span,
+                op: UnOp::Neg,
+                body_span,
+                body,
+            } if matches!(
+                body.as_ref(),
+                CExpr::NumHexadecimal(..) | CExpr::NumBinary(..) | CExpr::NumDecimal(..)
+            ) =>
+            {
+                self.number_lit(body, op_span.union(*body_span), true)?
+            }
+
             CExpr::UnOp {
                 op_span,
                 op,
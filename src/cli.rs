@@ -11,10 +11,11 @@ use std::str::FromStr;
 
 use crate::cli_utils::{match_option, parse_option, Arg, ArgIter};
 use crate::cmd_build::BuildMode;
+use crate::cmd_eval::FormatOptions;
 use crate::error::{Error, Result};
 use crate::loader::SandboxMode;
-use crate::markup::{Markup, MarkupMode};
-use crate::pprint::{concat, Doc};
+use crate::markup::{Markup, MarkupMode, Theme};
+use crate::pprint::{self, concat, Doc};
 
 const USAGE_MAIN: &str = r#"
 RCL -- A reasonable configuration language.
@@ -24,10 +25,17 @@ Usage:
 
 Commands:
   build        Write formatted evaluation results to files.
+  check        Parse and typecheck documents without evaluating them.
+  completion   Print a shell completion script.
+  diff         Compare the values of two documents.
   evaluate     Evaluate a document to an output format.
   format       Auto-format an RCL document.
+  generate-grammar
+               Print a syntax highlighting grammar for editors.
   highlight    Print a document with syntax highlighting.
+  lsp          Run a language server over stdio.
   query        Evaluate an expression against an input document.
+  schema       Print a JSON Schema for the output of a document.
 
 Command shorthands:
   e, eval      Alias for 'evaluate'.
@@ -38,17 +46,42 @@ Command shorthands:
   q            Alias for 'query'.
 
 Global options:
-  -h --help             Show this screen, or command-specific help.
-  --version             Show version.
-  --color <mode>        Set how output is colored, see modes below.
-  -C --directory <dir>  Change the working directory.
+  -h --help                Show this screen, or command-specific help.
+  --version                Show version.
+  --color <mode>           Set how output is colored, see modes below.
+  -C --directory <dir>     Change the working directory.
+  --error-format <format>  Set how errors are reported, see formats below.
+  --no-pager               Do not pipe output that does not fit on the terminal
+                           through a pager.
+  --stdin-filename <name>  Treat stdin as if it were the file <name>, in error
+                           messages and to resolve imports relative to it.
 
 Color modes:
-  ansi    Always color output using ANSI escape codes.
+  ansi    Always color output using ANSI escape codes, with the basic 16
+          colors, for terminals with a dark background.
+  ansi-light
+          Like 'ansi', but with 256 colors for a light background.
+  ansi-truecolor
+          Like 'ansi', but with 24-bit colors for a dark background.
   auto    Use ANSI if the output file is a TTY and the NO_COLOR environment
-          variable is not set to a non-empty string. This is the default.
+          variable is not set to a non-empty string, or if CLICOLOR_FORCE or
+          FORCE_COLOR is set to a value other than 0. This is the default.
   html    Output HTML tags in the same style as Pandoc.
+  html-rcl
+          Output HTML spans with 'rcl-' prefixed class names, for embedding.
   none    Do not color output at all.
+
+The modes 'always' and 'never' are accepted as aliases for 'ansi' and 'none'.
+An explicit mode applies to both stdout and stderr, and it overrides terminal
+detection and the environment variables.
+The RCL_COLORS environment variable overrides the styles of the ANSI modes,
+for example RCL_COLORS='string=32:comment=3;90'. See the manual for details.
+
+Error formats:
+  human   Report errors with source snippets, meant to be read. This is the
+          default.
+  json    Report every error as a json object on a single line, meant for
+          tools. See the documentation for the fields.
 "#;
 
 const USAGE_BUILD: &str = r##"
@@ -68,11 +101,29 @@ Arguments:
                     Defaults to 'build.rcl' when no file is specified.
 
 Options:
-  --dry-run         Print what files we would write to stdout, instead of
-                    writing to the file system, which would overwrite existing
-                    files.
-  --sandbox <mode>  Sandboxing mode, see 'rcl evaluate --help' for an
-                    explanation of the modes. Defaults to 'workdir'.
+  --deterministic          Guarantee that the output depends only on the input
+                           files, see 'rcl evaluate --help'.
+  --dry-run                Print what files we would write to stdout, instead
+                           of writing to the file system, which would overwrite
+                           existing files.
+  -j --jobs <n>            Pretty-print up to <n> targets in parallel. Defaults
+                           to the number of logical CPUs.
+  --output-depfile <file>  Write the output paths and all dependencies that
+                           were loaded during evaluation to <file> in Makefile
+                           syntax. This can be used by e.g. the Ninja build
+                           system.
+  --sandbox <mode>         Sandboxing mode, see 'rcl evaluate --help' for an
+                           explanation of the modes. Defaults to 'workdir'.
+  --time                   Print the time spent reading, lexing, parsing,
+                           typechecking, evaluating, and serializing every
+                           file to stderr.
+  --trace-file <file>      Write the duration of parsing, typechecking, imports,
+                           and function calls to <file>, in the Chrome trace
+                           event format.
+  --validate-schema <file> Check the contents of every target against the JSON
+                           Schema in <file> before writing anything.
+  --watch                  Keep running, and rebuild whenever the build file or
+                           any of its imports change.
 
 See also --help for global options.
 
@@ -102,10 +153,138 @@ Build target fields:
   format: String    The output format, must be one of the formats supported by
                     'rcl evaluate --format', see 'rcl evaluate --help'.
 
+  key_separator:    The separator for flattened keys in the 'ini' and
+    String          'properties' formats, as for 'rcl evaluate
+                    --key-separator'. Optional, defaults to '.'.
+
+  require_same_keys: Whether records must have the same keys in the 'csv' and
+    Bool            'tsv' formats, as for 'rcl evaluate --require-same-keys'.
+                    Optional, defaults to false.
+
   width: Int        Target width for formatting, as for 'rcl evaluate --width'.
                     Optional, defaults to 80.
 "##;
 
+const USAGE_CHECK: &str = r#"
+RCL -- A reasonable configuration language.
+
+Usage:
+  rcl [<options>] check [<options>] [<file>...]
+
+The 'check' command parses and typechecks one or more documents, and all the
+documents they import, without evaluating them. It reports the errors in all
+files, and it fails if any file has an error. Errors that can only be found by
+evaluating, such as failing assertions, are not reported. This makes the
+command suitable as a fast check before committing or in CI.
+
+Arguments:
+  <file>...        The input files to check, or '-' for stdin. Defaults to stdin
+                   when no file is specified.
+
+Options:
+  --sandbox <mode>         Sandboxing mode, see 'rcl evaluate --help' for an
+                           explanation of the modes. Defaults to 'workdir'.
+
+See also --help for global options.
+"#;
+
+const USAGE_COMPLETION: &str = r#"
+RCL -- A reasonable configuration language.
+
+Usage:
+  rcl [<options>] completion <shell>
+
+The 'completion' command prints a script that adds tab completion for 'rcl' to
+the given shell. The completions cover commands, options, and option values.
+
+Arguments:
+  <shell>      The shell to print the script for, see below.
+
+Shells:
+  bash         Source the script from '~/.bashrc'.
+  fish         Save the script as '~/.config/fish/completions/rcl.fish'.
+  zsh          Save the script as '_rcl' in a directory in '$fpath'.
+
+See also --help for global options.
+"#;
+
+const USAGE_GENERATE_GRAMMAR: &str = r#"
+RCL -- A reasonable configuration language.
+
+Usage:
+  rcl [<options>] generate-grammar <grammar>
+
+The 'generate-grammar' command prints syntax highlighting definitions for
+editors. The keywords, operators, builtins, and types in the grammar are taken
+from RCL itself, so the grammar matches the version of 'rcl' that printed it.
+
+Arguments:
+  <grammar>    The grammar format to print, see below.
+
+Grammar formats:
+  textmate     A TextMate grammar in json, for editors that support those.
+  vim          A Vim syntax file, to save as '~/.vim/syntax/rcl.vim'.
+
+See also --help for global options.
+"#;
+
+const USAGE_DIFF: &str = r#"
+RCL -- A reasonable configuration language.
+
+Usage:
+  rcl [<options>] diff [<options>] <old-file> <new-file>
+  rcl [<options>] diff [<options>] --git <old-rev> <new-rev> <file>
+
+The 'diff' command evaluates two documents and prints the differences between
+the resulting values: dict keys and list elements that were added or removed,
+and values that changed, together with their path in the value. Unlike a
+textual diff, this ignores differences in formatting, comments, and how values
+are computed.
+
+Arguments:
+  <old-file>   The first file to compare, or '-' for stdin.
+  <new-file>   The second file to compare, or '-' for stdin.
+  <old-rev>    With --git, the Git revision to read the old document from.
+  <new-rev>    With --git, the Git revision to read the new document from.
+  <file>       With --git, the file to compare, relative to the working
+               directory. Imports are read from the same revision.
+
+Options:
+  -f --format <format>     Output format, 'rcl' for the text report described
+                           below, or 'json' for a list of changes as objects.
+                           Defaults to 'rcl'.
+  --git                    Compare <file> at two Git revisions, instead of
+                           comparing two files.
+  --input-format <format>  How to read the input files, see 'rcl evaluate
+                           --help' for the formats. Defaults to 'rcl'.
+  -o --output <outfile>    Write to the given file instead of stdout.
+  --sandbox <mode>         Sandboxing mode, see 'rcl evaluate --help' for an
+                           explanation of the modes. Defaults to 'workdir'.
+  --time                   Print the time spent reading, lexing, parsing,
+                           typechecking, evaluating, and serializing every
+                           file to stderr.
+  --trace-file <file>      Write the duration of parsing, typechecking, imports,
+                           and function calls to <file>, in the Chrome trace
+                           event format.
+  -w --width <width>       Target width for pretty-printing values, must be an
+                           integer. Defaults to 80.
+
+Output:
+  + <path>: <value>          The key or element at <path> was added.
+  - <path>: <value>          The key or element at <path> was removed.
+  ~ <path>: <old> -> <new>   The value at <path> changed.
+
+Paths are written as in 'jq': '.' is the root, '.key' and '["key"]' select a
+dict key, '[i]' selects a list element, and '{x}' a set element. Indices of
+removed elements refer to the old list, other indices to the new list.
+
+With --format=json, the output is a list with an object per change, with the
+'path', the 'change' (added, removed, or changed), and the 'value', or 'old'
+and 'new' values for changes.
+
+See also --help for global options.
+"#;
+
 const USAGE_EVAL_QUERY: &str = r#"
 RCL -- A reasonable configuration language.
 
@@ -126,27 +305,79 @@ Arguments:
 Options:
   --banner <message>       Prepend the message to the output. This can be useful
                            to add headings or comments to generated files.
+  --deterministic          Guarantee that the output depends only on the input
+                           files and options, not on the platform. Line endings
+                           in loaded files are normalized to '\n', and
+                           '--sandbox=unrestricted' is not allowed.
   -f --format <format>     Output format, see below for the available formats.
                            Defaults to 'rcl'.
+  --input-format <format>  How to read the input file, see below. Defaults to
+                           'rcl'.
+  --key-separator <sep>    For the 'ini' and 'properties' formats, join the keys
+                           of nested dicts with <sep>. Defaults to '.'.
   -o --output <outfile>    Write to the given file instead of stdout.
   --output-depfile <file>  Write all dependencies that were loaded during
                            evaluation to <file> in Makefile syntax. This can be
                            used by e.g. the Ninja build system.
+  --require-same-keys      For the 'csv' and 'tsv' formats, require all records
+                           to have the same keys.
   --sandbox <mode>         Sandboxing mode, see below. Defaults to 'workdir'.
+  --source-comments        For the 'yaml-stream' format, precede every document
+                           with a '# Source:' comment that names the file that
+                           the element was imported from.
+  --time                   Print the time spent reading, lexing, parsing,
+                           typechecking, evaluating, and serializing every
+                           file to stderr.
+  --trace-file <file>      Write the duration of parsing, typechecking, imports,
+                           and function calls to <file>, in the Chrome trace
+                           event format.
+  --validate-schema <file> Check the result against the JSON Schema in <file>
+                           before printing it.
   -w --width <width>       Target width for pretty-printing, must be an integer.
                            Defaults to 80.
+  --watch                  Keep running, and evaluate again whenever the input
+                           file or any of its imports change.
 
 Output format:
+  csv           Output a CSV table. The document must be a list of rows, which
+                are either dicts or lists. For dicts, the header is the union
+                of their keys, see also --require-same-keys.
+  hcl           Output HCL attributes, as in a Terraform '.tfvars' file. The
+                top-level value must be a dict.
+  ini           Output an INI file. The top-level value must be a dict, nested
+                dicts become sections, and dicts below those are flattened,
+                see --key-separator.
   json          Output pretty-printed JSON.
+  json-canonical
+                Output canonical JSON as specified by RFC 8785 (JCS), with
+                sorted keys and without whitespace, for hashing and signing.
+  json-compact  Output JSON on a single line, without whitespace.
+  json-lines    If the document is a list, output every element as JSON on a
+                line of its own. Top-level values other than lists are not
+                valid for this format.
+  nix           Output a Nix expression.
+  plist         Output an Apple property list in the XML format, as for launchd.
+  properties    Output a Java properties file. The top-level value must be a
+                dict, nested dicts are flattened, see --key-separator.
   raw           If the document is a string, output the string itself. If the
                 document is a list or set of strings, output each string on its
                 own line.
   rcl           Output pretty-printed RCL.
+  textproto     Output Protobuf text format. The top-level value must be a
+                dict, nested dicts become messages, and lists become repeated
+                fields.
   toml          Output TOML.
+  tsv           Output a TSV table, like 'csv', but separated by tabs.
   yaml-stream   If the document is a list, output every element as a JSON
                 document, prefixed by the '---' YAML document separator.
                 Top-level values other than lists are not valid for this format.
 
+Input format:
+  json          Parse the input file as JSON data. Unlike 'rcl', this accepts
+                only JSON, and it does not evaluate anything.
+  rcl           Evaluate the input file as an RCL expression. Because RCL is a
+                superset of JSON, this accepts JSON documents too.
+
 Sandboxing modes:
   workdir       Only allow importing files inside the working directory and
                 subdirectories.
@@ -165,17 +396,92 @@ The 'format' command formats one or more input documents in standard style.
 
 Arguments:
   <file>...        The input files to process, or '-' for stdin. When --in-place
-                   is used, there can be multiple input files. Defaults to stdin
-                   when no file is specified.
+                   is used, there can be multiple input files, directories,
+                   which are searched for '.rcl' files, and quoted glob patterns
+                   such as 'conf/**/*.rcl'. Defaults to stdin when no file is
+                   specified.
 
 Options:
+  --files-from <file>    Also process the files listed in <file>, or in stdin
+                         for '-', separated by NUL bytes. Requires --in-place
+                         or --check. See below for the output.
   -i --in-place          Rewrite files in-place instead of writing to stdout.
                          By default the formatted result is written to stdout.
+  -j --jobs <n>          Format up to <n> files in parallel. Defaults to the
+                         number of logical CPUs.
+  --no-ignore            When searching directories, do not skip files that
+                         '.gitignore' or '.rclignore' files exclude.
   -o --output <outfile>  Write to the given file instead of stdout. This is
                          incompatible with --in-place.
+  --staged               Also process the '.rcl' files that are staged in Git.
+                         Requires --in-place or --check. See below for the
+                         output.
   -w --width <width>     Target width in number of columns, must be an integer.
                          Defaults to 80.
 
+With --files-from or --staged, the output is one line per file, with a status
+and the path separated by a tab. The status is one of 'unchanged',
+'reformatted', 'would-reformat', or 'failed'. Errors are reported on stderr.
+
+Settings are read from '.editorconfig' files, and from the nearest
+'rcl-format.rcl' in the directory of the input file or its parents, which takes
+precedence. Options on the command line take precedence over both.
+
+See also --help for global options.
+"#;
+
+const USAGE_HIGHLIGHT: &str = r#"
+RCL -- A reasonable configuration language.
+
+Usage:
+  rcl [<options>] highlight [<file>]
+
+The 'highlight' command prints an input document with syntax highlighting.
+Unlike other commands, it defaults to '--color=ansi' even when the output is
+not a TTY, because producing markup is the purpose of the command.
+
+Arguments:
+  <file>       The input file to highlight, or '-' for stdin. Defaults to stdin
+               when no file is specified.
+
+See also --help for global options.
+"#;
+
+const USAGE_SCHEMA: &str = r#"
+RCL -- A reasonable configuration language.
+
+Usage:
+  rcl [<options>] schema [<options>] [<file>]
+
+The 'schema' command typechecks a document without evaluating it, and prints a
+JSON Schema that describes the json output of the document. The schema follows
+from the type that the typechecker infers for the document, including from type
+annotations. Where the type is not known statically, the schema accepts any
+value.
+
+Arguments:
+  <file>       The input file, or '-' for stdin. Defaults to stdin when no file
+               is specified.
+
+Options:
+  -o --output <outfile>    Write to the given file instead of stdout.
+  -w --width <width>       Target width for pretty-printing, must be an integer.
+                           Defaults to 80.
+
+See also --help for global options.
+"#;
+
+const USAGE_LSP: &str = r#"
+RCL -- A reasonable configuration language.
+
+Usage:
+  rcl [<options>] lsp
+
+The 'lsp' command runs a server for the Language Server Protocol, that
+communicates over stdin and stdout. It is meant to be started by an editor,
+not by a user. The server reports parse and type errors as diagnostics, and
+supports formatting documents.
+
 See also --help for global options.
 "#;
 
@@ -191,25 +497,86 @@ pub struct GlobalOptions {
 
     /// Alter the working directory for filesystem access.
     pub workdir: Option<String>,
+
+    /// The name to use for a document read from stdin.
+    pub stdin_filename: Option<String>,
+
+    /// How to report errors.
+    pub error_format: ErrorFormat,
+
+    /// Whether to write long output to the terminal directly, see [`crate::pager`].
+    pub no_pager: bool,
+}
+
+/// The available formats for reporting errors.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum ErrorFormat {
+    /// Report errors with source snippets, see [`crate::error::Error::report`].
+    #[default]
+    Human,
+    /// Report errors as json lines, see [`crate::error::Error::report_json`].
+    Json,
 }
 
 /// The available output formats (JSON, RCL).
 #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
 pub enum OutputFormat {
+    Csv,
+    Hcl,
+    Ini,
     Json,
+    JsonCanonical,
+    JsonCompact,
+    JsonLines,
+    Nix,
+    Plist,
+    Properties,
     Raw,
     #[default]
     Rcl,
+    Textproto,
     Toml,
+    Tsv,
     YamlStream,
 }
 
+/// The names of the output formats, for `--format` and the `format` of build targets.
+pub const OUTPUT_FORMATS: &[(&str, OutputFormat)] = &[
+    ("csv", OutputFormat::Csv),
+    ("hcl", OutputFormat::Hcl),
+    ("ini", OutputFormat::Ini),
+    ("json", OutputFormat::Json),
+    ("json-canonical", OutputFormat::JsonCanonical),
+    ("json-compact", OutputFormat::JsonCompact),
+    ("json-lines", OutputFormat::JsonLines),
+    ("nix", OutputFormat::Nix),
+    ("plist", OutputFormat::Plist),
+    ("properties", OutputFormat::Properties),
+    ("raw", OutputFormat::Raw),
+    ("rcl", OutputFormat::Rcl),
+    ("textproto", OutputFormat::Textproto),
+    ("toml", OutputFormat::Toml),
+    ("tsv", OutputFormat::Tsv),
+    ("yaml-stream", OutputFormat::YamlStream),
+];
+
+/// The available input formats for the document to evaluate.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum InputFormat {
+    Json,
+    #[default]
+    Rcl,
+}
+
 /// Options for commands that evaluate expressions.
-#[derive(Debug, Default, Eq, PartialEq)]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct EvalOptions {
     /// The format to output in.
     pub format: OutputFormat,
 
+    /// The format of the input document.
+    pub input_format: InputFormat,
+
     /// Policy for what files can be imported.
     pub sandbox: SandboxMode,
 
@@ -221,23 +588,86 @@ pub struct EvalOptions {
 
     /// A banner message to prepend to the output.
     pub banner: Option<String>,
+
+    /// Whether to rule out output that depends on the platform.
+    ///
+    /// See also [`crate::loader::Loader::set_deterministic`].
+    pub deterministic: bool,
+
+    /// The separator for flattened keys in the `ini` and `properties` formats.
+    pub key_separator: Option<String>,
+
+    /// Whether records must have the same keys in the `csv` and `tsv` formats.
+    pub require_same_keys: bool,
+
+    /// Whether to add `# Source:` comments to the documents in `yaml-stream`.
+    pub source_comments: bool,
+
+    /// File to write a Chrome trace of the evaluation to.
+    pub trace_file: Option<String>,
+
+    /// JSON Schema file to validate the output against.
+    pub validate_schema: Option<String>,
+
+    /// Whether to print the time spent per phase and document to stderr.
+    pub time: bool,
+
+    /// Whether to re-run the command when any of its input files change.
+    pub watch: bool,
+}
+
+impl EvalOptions {
+    /// Return the settings for the output format, with defaults applied.
+    pub fn format_options(&self) -> FormatOptions {
+        let mut opts = FormatOptions {
+            require_same_keys: self.require_same_keys,
+            ..FormatOptions::default()
+        };
+        if let Some(sep) = &self.key_separator {
+            opts.key_separator = sep.clone();
+        }
+        opts
+    }
 }
 
 /// Options for commands that pretty-print their output.
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct StyleOptions {
     /// Target width (number of columns) to try to not exceed.
-    pub width: u32,
+    ///
+    /// When not specified, this defaults to 80, or for `rcl format`, to the
+    /// width in the formatter configuration file.
+    pub width: Option<u32>,
 }
 
-impl Default for StyleOptions {
-    fn default() -> Self {
-        Self { width: 80 }
+impl StyleOptions {
+    /// Return the pretty-printer configuration with these options applied.
+    pub fn pprint_config(&self) -> pprint::Config {
+        let mut config = pprint::Config::default();
+        if let Some(width) = self.width {
+            config.width = width;
+        }
+        config
     }
 }
 
+/// An editor grammar format to generate.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum GrammarFormat {
+    TextMate,
+    Vim,
+}
+
+/// A shell to generate completions for.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Shell {
+    Bash,
+    Fish,
+    Zsh,
+}
+
 /// Input to act on.
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Target {
     /// A file, selected explicitly.
     File(String),
@@ -255,15 +685,24 @@ pub enum Target {
 }
 
 /// For the `fmt` command, which documents to format, and in what mode.
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum FormatTarget {
     Stdout { fname: Target },
     InPlace { fnames: Vec<Target> },
     Check { fnames: Vec<Target> },
 }
 
+/// For the `fmt` command, where to read a list of files to format from.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum FileList {
+    /// Read NUL-separated paths from a file or stdin, for `--files-from`.
+    FilesFrom(Target),
+    /// Ask Git for the files that are staged, for `--staged`.
+    GitStaged,
+}
+
 /// An output file to write results to.
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum OutputTarget {
     /// Write to the given file.
     File(String),
@@ -272,12 +711,18 @@ pub enum OutputTarget {
 }
 
 /// The different subcommands supported by the main program.
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Cmd {
     Build {
         eval_opts: EvalOptions,
         build_mode: BuildMode,
         fname: Target,
+        /// The number of threads to use, or `None` for one per logical CPU.
+        jobs: Option<usize>,
+    },
+    Check {
+        eval_opts: EvalOptions,
+        fnames: Vec<Target>,
     },
     Evaluate {
         eval_opts: EvalOptions,
@@ -292,14 +737,48 @@ pub enum Cmd {
         query: String,
         output: OutputTarget,
     },
+    Schema {
+        eval_opts: EvalOptions,
+        style_opts: StyleOptions,
+        fname: Target,
+        output: OutputTarget,
+    },
     Format {
         style_opts: StyleOptions,
         target: FormatTarget,
         output: OutputTarget,
+        no_ignore: bool,
+        /// The number of threads to use, or `None` for one per logical CPU.
+        jobs: Option<usize>,
+        /// Files to format in addition to the targets.
+        file_list: Option<FileList>,
     },
     Highlight {
         fname: Target,
     },
+    Completion {
+        shell: Shell,
+    },
+    GenerateGrammar {
+        format: GrammarFormat,
+    },
+    Diff {
+        eval_opts: EvalOptions,
+        style_opts: StyleOptions,
+        old: Target,
+        new: Target,
+        /// With `--git`, the revisions to load `old` and `new` from.
+        git_revs: Option<(String, String)>,
+        output: OutputTarget,
+    },
+    Lsp,
+    /// Check the formatter on the given files, see [`crate::cmd_fmt::self_test`].
+    ///
+    /// This command is not listed in the usage, it is intended for development.
+    SelfTestFmt {
+        style_opts: StyleOptions,
+        fnames: Vec<Target>,
+    },
     Help {
         usage: &'static str,
     },
@@ -324,6 +803,10 @@ pub fn parse(args: Vec<String>) -> Result<(GlobalOptions, Cmd)> {
     let mut targets: Vec<Target> = Vec::new();
     let mut output = OutputTarget::Stdout;
     let mut build_mode = BuildMode::WriteFilesystem;
+    let mut git = false;
+    let mut no_ignore = false;
+    let mut jobs: Option<usize> = None;
+    let mut file_list: Option<FileList> = None;
 
     while let Some(arg) = args.next() {
         match arg.as_ref() {
@@ -333,6 +816,21 @@ pub fn parse(args: Vec<String>) -> Result<(GlobalOptions, Cmd)> {
                     |x: &str| Ok::<_, std::convert::Infallible>(Some(x.to_string()))
                 };
             }
+            Arg::Long("deterministic") => {
+                eval_opts.deterministic = true;
+            }
+            Arg::Long("key-separator") => {
+                eval_opts.key_separator = parse_option! {
+                    args: arg,
+                    |x: &str| Ok::<_, std::convert::Infallible>(Some(x.to_string()))
+                };
+            }
+            Arg::Long("require-same-keys") => {
+                eval_opts.require_same_keys = true;
+            }
+            Arg::Long("source-comments") => {
+                eval_opts.source_comments = true;
+            }
             Arg::Long("check") => {
                 check = true;
                 build_mode = BuildMode::Check;
@@ -341,9 +839,15 @@ pub fn parse(args: Vec<String>) -> Result<(GlobalOptions, Cmd)> {
                 global_opts.markup = match_option! {
                     args: arg,
                     "auto" => None,
-                    "ansi" => Some(MarkupMode::Ansi),
+                    "ansi" => Some(MarkupMode::Ansi(Theme::DARK)),
+                    "ansi-light" => Some(MarkupMode::Ansi(Theme::LIGHT)),
+                    "ansi-truecolor" => Some(MarkupMode::Ansi(Theme::TRUECOLOR)),
                     "html" => Some(MarkupMode::HtmlPandoc),
+                    "html-rcl" => Some(MarkupMode::Html),
                     "none" => Some(MarkupMode::None),
+                    // Aliases for compatibility with other tools' --color.
+                    "always" => Some(MarkupMode::Ansi(Theme::DARK)),
+                    "never" => Some(MarkupMode::None),
                 }
             }
             Arg::Long("directory") | Arg::Short("C") => {
@@ -352,17 +856,39 @@ pub fn parse(args: Vec<String>) -> Result<(GlobalOptions, Cmd)> {
                     |x: &str| Ok::<_, std::convert::Infallible>(Some(x.to_string()))
                 };
             }
+            Arg::Long("error-format") => {
+                global_opts.error_format = match_option! {
+                    args: arg,
+                    "human" => ErrorFormat::Human,
+                    "json" => ErrorFormat::Json,
+                }
+            }
+            Arg::Long("no-pager") => {
+                global_opts.no_pager = true;
+            }
+            Arg::Long("stdin-filename") => {
+                global_opts.stdin_filename = parse_option! {
+                    args: arg,
+                    |x: &str| Ok::<_, std::convert::Infallible>(Some(x.to_string()))
+                };
+            }
             Arg::Long("dry-run") => {
                 build_mode = BuildMode::DryRun;
             }
+            Arg::Long("git") => {
+                git = true;
+            }
             Arg::Long("format") | Arg::Short("f") => {
                 eval_opts.format = match_option! {
                     args: arg,
-                    "json" => OutputFormat::Json,
-                    "raw" => OutputFormat::Raw,
-                    "rcl" => OutputFormat::Rcl,
-                    "toml" => OutputFormat::Toml,
-                    "yaml-stream" => OutputFormat::YamlStream,
+                    in OUTPUT_FORMATS
+                }
+            }
+            Arg::Long("input-format") => {
+                eval_opts.input_format = match_option! {
+                    args: arg,
+                    "json" => InputFormat::Json,
+                    "rcl" => InputFormat::Rcl,
                 }
             }
             Arg::Long("output") | Arg::Short("o") => {
@@ -370,6 +896,27 @@ pub fn parse(args: Vec<String>) -> Result<(GlobalOptions, Cmd)> {
                     args: arg,
                     |x: &str| Ok::<_, std::convert::Infallible>(OutputTarget::File(x.to_string()))
                 };
+                // Some other tools use -o to select the output format. In RCL
+                // that is --format, catch the mistake early rather than writing
+                // to a file named e.g. "json".
+                if let OutputTarget::File(fname) = &output {
+                    if OUTPUT_FORMATS.iter().any(|(name, _)| name == fname) {
+                        let err = concat! {
+                            Doc::highlight(&arg.to_string()).into_owned()
+                            " expects a file name, but '"
+                            Doc::highlight(fname).into_owned()
+                            "' looks like an output format."
+                        };
+                        let help = concat! {
+                            "Use '"
+                            Doc::highlight(&format!("--format={fname}")).into_owned()
+                            "' to select the output format, or '"
+                            Doc::highlight(&format!("./{fname}")).into_owned()
+                            "' to write to a file with that name."
+                        };
+                        return Error::new(err).with_help(help).err();
+                    }
+                }
             }
             Arg::Long("output-depfile") => {
                 eval_opts.output_depfile = parse_option! {
@@ -377,6 +924,21 @@ pub fn parse(args: Vec<String>) -> Result<(GlobalOptions, Cmd)> {
                     |x: &str| Ok::<_, std::convert::Infallible>(Some(x.to_string()))
                 };
             }
+            Arg::Long("time") => {
+                eval_opts.time = true;
+            }
+            Arg::Long("trace-file") => {
+                eval_opts.trace_file = parse_option! {
+                    args: arg,
+                    |x: &str| Ok::<_, std::convert::Infallible>(Some(x.to_string()))
+                };
+            }
+            Arg::Long("validate-schema") => {
+                eval_opts.validate_schema = parse_option! {
+                    args: arg,
+                    |x: &str| Ok::<_, std::convert::Infallible>(Some(x.to_string()))
+                };
+            }
             Arg::Long("sandbox") => {
                 eval_opts.sandbox = match_option! {
                     args: arg,
@@ -384,12 +946,50 @@ pub fn parse(args: Vec<String>) -> Result<(GlobalOptions, Cmd)> {
                     "unrestricted" => SandboxMode::Unrestricted,
                 }
             }
+            Arg::Long("watch") => {
+                eval_opts.watch = true;
+            }
             Arg::Long("width") | Arg::Short("w") => {
-                style_opts.width = parse_option! { args: arg, u32::from_str };
+                style_opts.width = Some(parse_option! { args: arg, u32::from_str });
             }
             Arg::Long("in-place") | Arg::Short("i") => {
                 in_place = true;
             }
+            Arg::Long("files-from") => {
+                // Unlike other options, this one accepts '-' for stdin.
+                file_list = match args.next() {
+                    Some(Arg::Plain(fname)) if fname == "-" => {
+                        Some(FileList::FilesFrom(Target::Stdin))
+                    }
+                    Some(Arg::Plain(fname)) => Some(FileList::FilesFrom(Target::File(fname))),
+                    Some(Arg::StdInOut) => Some(FileList::FilesFrom(Target::Stdin)),
+                    _ => {
+                        return Error::new(concat! {
+                            "Expected a value after "
+                            Doc::from("--files-from").with_markup(Markup::Highlight)
+                            ". See --help for usage."
+                        })
+                        .err();
+                    }
+                };
+            }
+            Arg::Long("staged") => {
+                file_list = Some(FileList::GitStaged);
+            }
+            Arg::Long("jobs") | Arg::Short("j") => {
+                // Zero jobs would never get anything done, so we reject it.
+                jobs = parse_option! {
+                    args: arg,
+                    |x: &str| match usize::from_str(x) {
+                        Ok(0) => Err(()),
+                        Ok(n) => Ok(Some(n)),
+                        Err(..) => Err(()),
+                    }
+                };
+            }
+            Arg::Long("no-ignore") => {
+                no_ignore = true;
+            }
             Arg::Long("help") | Arg::Short("h") => {
                 is_version = false;
                 cmd_help = match cmd {
@@ -404,6 +1004,9 @@ pub fn parse(args: Vec<String>) -> Result<(GlobalOptions, Cmd)> {
             Arg::Plain("build") if cmd.is_none() => {
                 cmd = Some("build");
             }
+            Arg::Plain("check") if cmd.is_none() => {
+                cmd = Some("check");
+            }
             Arg::Plain("evaluate") | Arg::Plain("eval") | Arg::Plain("e") if cmd.is_none() => {
                 cmd = Some("evaluate");
             }
@@ -426,12 +1029,30 @@ pub fn parse(args: Vec<String>) -> Result<(GlobalOptions, Cmd)> {
                 cmd = Some("query");
                 eval_opts.format = OutputFormat::Raw;
             }
+            Arg::Plain("schema") if cmd.is_none() => {
+                cmd = Some("schema");
+            }
             Arg::Plain("format") | Arg::Plain("fmt") | Arg::Plain("f") if cmd.is_none() => {
                 cmd = Some("format");
             }
             Arg::Plain("highlight") | Arg::Plain("h") if cmd.is_none() => {
                 cmd = Some("highlight");
             }
+            Arg::Plain("generate-grammar") if cmd.is_none() => {
+                cmd = Some("generate-grammar");
+            }
+            Arg::Plain("completion") if cmd.is_none() => {
+                cmd = Some("completion");
+            }
+            Arg::Plain("diff") if cmd.is_none() => {
+                cmd = Some("diff");
+            }
+            Arg::Plain("lsp") if cmd.is_none() => {
+                cmd = Some("lsp");
+            }
+            Arg::Plain("self-test-fmt") if cmd.is_none() => {
+                cmd = Some("self-test-fmt");
+            }
             Arg::Plain(fname) if cmd.is_some() => {
                 targets.push(Target::File(fname.to_string()));
             }
@@ -461,22 +1082,9 @@ pub fn parse(args: Vec<String>) -> Result<(GlobalOptions, Cmd)> {
         return Ok((global_opts, Cmd::Version));
     }
 
-    let help_opt = match cmd_help {
-        Some("build") => Some(Cmd::Help { usage: USAGE_BUILD }),
-        Some("evaluate") => Some(Cmd::Help {
-            usage: USAGE_EVAL_QUERY,
-        }),
-        Some("format") => Some(Cmd::Help {
-            usage: USAGE_FORMAT,
-        }),
-        // TODO: Add usage for highlight.
-        Some("highlight") => Some(Cmd::Help { usage: USAGE_MAIN }),
-        Some("main") => Some(Cmd::Help { usage: USAGE_MAIN }),
-        Some("query") => Some(Cmd::Help {
-            usage: USAGE_EVAL_QUERY,
-        }),
-        _ => None,
-    };
+    let help_opt = cmd_help
+        .and_then(get_usage)
+        .map(|usage| Cmd::Help { usage });
     if let Some(help) = help_opt {
         return Ok((global_opts, help));
     }
@@ -492,6 +1100,16 @@ pub fn parse(args: Vec<String>) -> Result<(GlobalOptions, Cmd)> {
                 eval_opts,
                 build_mode,
                 fname: get_unique_target(targets)?,
+                jobs,
+            }
+        }
+        Some("check") => {
+            if targets.is_empty() {
+                targets.push(Target::StdinDefault);
+            }
+            Cmd::Check {
+                eval_opts,
+                fnames: targets,
             }
         }
         Some("evaluate") => Cmd::Evaluate {
@@ -540,6 +1158,22 @@ pub fn parse(args: Vec<String>) -> Result<(GlobalOptions, Cmd)> {
                 output,
             }
         }
+        Some("schema") => Cmd::Schema {
+            eval_opts,
+            style_opts,
+            fname: get_unique_target(targets)?,
+            output,
+        },
+        Some("format") if file_list.is_some() && !in_place && !check => {
+            return Error::new(concat! {
+                "Reading the files from a list requires "
+                Doc::highlight("--in-place")
+                " or "
+                Doc::highlight("--check")
+                ". See --help for usage."
+            })
+            .err();
+        }
         Some("format") => Cmd::Format {
             style_opts,
             target: if in_place {
@@ -552,16 +1186,114 @@ pub fn parse(args: Vec<String>) -> Result<(GlobalOptions, Cmd)> {
                 }
             },
             output,
+            no_ignore,
+            jobs,
+            file_list,
         },
         Some("highlight") => Cmd::Highlight {
             fname: get_unique_target(targets)?,
         },
+        Some("completion") => {
+            let shell = match &targets[..] {
+                [Target::File(name)] if name == "bash" => Shell::Bash,
+                [Target::File(name)] if name == "fish" => Shell::Fish,
+                [Target::File(name)] if name == "zsh" => Shell::Zsh,
+                _ => {
+                    return Error::new(
+                        "Expected one of 'bash', 'fish', or 'zsh'. See --help for usage.",
+                    )
+                    .err()
+                }
+            };
+            Cmd::Completion { shell }
+        }
+        Some("generate-grammar") => {
+            let format = match &targets[..] {
+                [Target::File(name)] if name == "textmate" => GrammarFormat::TextMate,
+                [Target::File(name)] if name == "vim" => GrammarFormat::Vim,
+                _ => {
+                    return Error::new("Expected 'textmate' or 'vim'. See --help for usage.").err()
+                }
+            };
+            Cmd::GenerateGrammar { format }
+        }
+        Some("diff") => {
+            if !matches!(eval_opts.format, OutputFormat::Rcl | OutputFormat::Json) {
+                return Error::new(
+                    "The 'diff' command supports only the 'rcl' and 'json' formats.",
+                )
+                .err();
+            }
+            let (old, new, git_revs) = match &targets[..] {
+                [Target::File(old_rev), Target::File(new_rev), Target::File(fname)] if git => {
+                    if old_rev.starts_with('-') || new_rev.starts_with('-') {
+                        return Error::new("A revision must not start with '-'.").err();
+                    }
+                    let target = Target::File(fname.clone());
+                    let revs = (old_rev.clone(), new_rev.clone());
+                    (target.clone(), target, Some(revs))
+                }
+                _ if git => {
+                    return Error::new("Expected two revisions and a file. See --help for usage.")
+                        .err();
+                }
+                [Target::Stdin, Target::Stdin] => {
+                    return Error::new("Only one of the inputs can be stdin.").err();
+                }
+                [old, new] => (old.clone(), new.clone(), None),
+                _ => {
+                    return Error::new("Expected two input files. See --help for usage.").err();
+                }
+            };
+            Cmd::Diff {
+                eval_opts,
+                style_opts,
+                old,
+                new,
+                git_revs,
+                output,
+            }
+        }
+        Some("lsp") => {
+            if !targets.is_empty() {
+                return Error::new(
+                    "The 'lsp' command does not take arguments. See --help for usage.",
+                )
+                .err();
+            }
+            Cmd::Lsp
+        }
+        Some("self-test-fmt") => Cmd::SelfTestFmt {
+            style_opts,
+            fnames: targets,
+        },
         None => Cmd::Help { usage: USAGE_MAIN },
         _ => panic!("Should have returned an error before getting here."),
     };
     Ok((global_opts, result))
 }
 
+/// Return the usage text for the given command, or `"main"` for the main usage.
+///
+/// This is also the source of truth for shell completions, see
+/// [`crate::cmd_completion`].
+pub fn get_usage(cmd: &str) -> Option<&'static str> {
+    match cmd {
+        "build" => Some(USAGE_BUILD),
+        "check" => Some(USAGE_CHECK),
+        "completion" => Some(USAGE_COMPLETION),
+        "diff" => Some(USAGE_DIFF),
+        "evaluate" | "query" => Some(USAGE_EVAL_QUERY),
+        "format" => Some(USAGE_FORMAT),
+        "generate-grammar" => Some(USAGE_GENERATE_GRAMMAR),
+        "highlight" => Some(USAGE_HIGHLIGHT),
+        "lsp" => Some(USAGE_LSP),
+        "main" => Some(USAGE_MAIN),
+        "schema" => Some(USAGE_SCHEMA),
+        _ => None,
+    }
+}
+
 fn get_unique_target(mut targets: Vec<Target>) -> Result<Target> {
     match targets.pop() {
         None => Ok(Target::StdinDefault),
@@ -575,17 +1307,17 @@ fn get_unique_target(mut targets: Vec<Target>) -> Result<Target> {
 #[cfg(test)]
 mod test {
     use crate::cli::{
-        Cmd, EvalOptions, FormatTarget, GlobalOptions, OutputFormat, OutputTarget, SandboxMode,
-        StyleOptions, Target,
+        Cmd, ErrorFormat, EvalOptions, FileList, FormatTarget, GlobalOptions, GrammarFormat,
+        InputFormat, OutputFormat, OutputTarget, SandboxMode, Shell, StyleOptions, Target,
     };
     use crate::cmd_build::BuildMode;
-    use crate::markup::MarkupMode;
+    use crate::markup::{MarkupMode, Theme};
     use crate::pprint::Config;
 
     fn fail_parse(args: &[&'static str]) -> String {
         let args_vec: Vec<_> = args.iter().map(|a| a.to_string()).collect();
         let err = super::parse(args_vec).err().unwrap();
-        let cfg = Config { width: 80 };
+        let cfg = Config::default();
         err.report(&[]).println(&cfg).to_string_no_markup()
     }
 
@@ -599,6 +1331,9 @@ mod test {
         let expected_opt = GlobalOptions {
             markup: None,
             workdir: None,
+            stdin_filename: None,
+            error_format: ErrorFormat::Human,
+            no_pager: false,
         };
         let expected_cmd = Cmd::Evaluate {
             eval_opts: EvalOptions::default(),
@@ -619,7 +1354,19 @@ mod test {
         assert_eq!(parse(&["rcl", "--color=none", "e", "infile"]), expected);
         expected.0.markup = Some(MarkupMode::HtmlPandoc);
         assert_eq!(parse(&["rcl", "--color=html", "e", "infile"]), expected);
-        expected.0.markup = Some(MarkupMode::Ansi);
+        expected.0.markup = Some(MarkupMode::Html);
+        assert_eq!(parse(&["rcl", "--color=html-rcl", "e", "infile"]), expected);
+        expected.0.markup = Some(MarkupMode::Ansi(Theme::LIGHT));
+        assert_eq!(
+            parse(&["rcl", "--color=ansi-light", "e", "infile"]),
+            expected
+        );
+        expected.0.markup = Some(MarkupMode::Ansi(Theme::TRUECOLOR));
+        assert_eq!(
+            parse(&["rcl", "--color=ansi-truecolor", "e", "infile"]),
+            expected
+        );
+        expected.0.markup = Some(MarkupMode::Ansi(Theme::DARK));
         assert_eq!(parse(&["rcl", "--color=ansi", "e", "infile"]), expected);
 
         // We should be able to pass --color in any place.
@@ -634,6 +1381,12 @@ mod test {
             expected
         );
 
+        // The aliases common in other tools work too.
+        assert_eq!(parse(&["rcl", "--color=always", "e", "infile"]), expected);
+        expected.0.markup = Some(MarkupMode::None);
+        assert_eq!(parse(&["rcl", "--color=never", "e", "infile"]), expected);
+        expected.0.markup = Some(MarkupMode::Ansi(Theme::DARK));
+
         // If we specify an option twice, the last one takes precedence.
         assert_eq!(
             parse(&["rcl", "e", "infile", "--color=none", "--color=ansi"]),
@@ -643,7 +1396,7 @@ mod test {
         // Test that --width works, in any location, last option wins.
         expected.0.markup = None;
         if let Cmd::Evaluate { style_opts, .. } = &mut expected.1 {
-            style_opts.width = 42;
+            style_opts.width = Some(42);
         }
         assert_eq!(parse(&["rcl", "e", "--width=42", "infile"]), expected);
         assert_eq!(parse(&["rcl", "e", "--width", "42", "infile"]), expected);
@@ -664,7 +1417,7 @@ mod test {
             ..
         } = &mut expected.1
         {
-            style_opts.width = 80;
+            style_opts.width = None;
             eval_opts.format = OutputFormat::Json;
         }
         assert_eq!(parse(&["rcl", "e", "infile", "-fjson"]), expected);
@@ -704,6 +1457,82 @@ mod test {
         }
         assert_eq!(parse(&["rcl", "e", "infile", "--banner=prefix"]), expected);
 
+        // Test --watch
+        if let Cmd::Evaluate { eval_opts, .. } = &mut expected.1 {
+            eval_opts.watch = true;
+        }
+        assert_eq!(
+            parse(&["rcl", "e", "infile", "--banner=prefix", "--watch"]),
+            expected
+        );
+        if let Cmd::Evaluate { eval_opts, .. } = &mut expected.1 {
+            eval_opts.watch = false;
+        }
+
+        // Test --validate-schema
+        if let Cmd::Evaluate { eval_opts, .. } = &mut expected.1 {
+            eval_opts.validate_schema = Some("schema.json".to_string());
+        }
+        assert_eq!(
+            parse(&[
+                "rcl",
+                "e",
+                "infile",
+                "--banner=prefix",
+                "--validate-schema",
+                "schema.json"
+            ]),
+            expected
+        );
+        if let Cmd::Evaluate { eval_opts, .. } = &mut expected.1 {
+            eval_opts.validate_schema = None;
+        }
+
+        // Test --deterministic
+        if let Cmd::Evaluate { eval_opts, .. } = &mut expected.1 {
+            eval_opts.deterministic = true;
+        }
+        assert_eq!(
+            parse(&["rcl", "e", "infile", "--banner=prefix", "--deterministic"]),
+            expected
+        );
+        if let Cmd::Evaluate { eval_opts, .. } = &mut expected.1 {
+            eval_opts.deterministic = false;
+        }
+
+        // Test --key-separator
+        if let Cmd::Evaluate { eval_opts, .. } = &mut expected.1 {
+            eval_opts.banner = None;
+            eval_opts.key_separator = Some("_".to_string());
+        }
+        assert_eq!(
+            parse(&["rcl", "e", "infile", "--key-separator=_"]),
+            expected
+        );
+        if let Cmd::Evaluate { eval_opts, .. } = &mut expected.1 {
+            assert_eq!(eval_opts.format_options().key_separator, "_");
+            eval_opts.key_separator = None;
+            assert_eq!(eval_opts.format_options().key_separator, ".");
+            eval_opts.require_same_keys = true;
+        }
+        assert_eq!(
+            parse(&["rcl", "e", "infile", "--require-same-keys"]),
+            expected
+        );
+        if let Cmd::Evaluate { eval_opts, .. } = &mut expected.1 {
+            assert!(eval_opts.format_options().require_same_keys);
+            eval_opts.require_same_keys = false;
+            eval_opts.source_comments = true;
+        }
+        assert_eq!(
+            parse(&["rcl", "e", "infile", "--source-comments"]),
+            expected
+        );
+        if let Cmd::Evaluate { eval_opts, .. } = &mut expected.1 {
+            eval_opts.source_comments = false;
+            eval_opts.banner = Some("prefix".to_string());
+        }
+
         // Test that defaulting to stdin works. If '-' is there we get it
         // explicitly, if it's not, we get it implicitly.
         if let Cmd::Evaluate {
@@ -739,7 +1568,13 @@ mod test {
         );
         assert_eq!(
             fail_parse(&["rcl", "eval", "infile", "--format=yamr"]),
-            "Error: Expected --format to be followed by one of json, raw, rcl, toml, yaml-stream. See --help for usage.\n"
+            "Error: Expected --format to be followed by one of csv, hcl, ini, json, json-canonical, json-compact, json-lines, nix, plist, properties, raw, rcl, textproto, toml, tsv, yaml-stream. See --help for usage.\n"
+        );
+        assert_eq!(
+            fail_parse(&["rcl", "eval", "infile", "-o", "json"]),
+            "Error: -o expects a file name, but 'json' looks like an output format.\n\n\
+            Help: Use '--format=json' to select the output format, \
+            or './json' to write to a file with that name.\n"
         );
         assert_eq!(
             fail_parse(&["rcl", "frobnicate", "infile"]),
@@ -756,6 +1591,9 @@ mod test {
         let expected_opt = GlobalOptions {
             markup: None,
             workdir: None,
+            stdin_filename: None,
+            error_format: ErrorFormat::Human,
+            no_pager: false,
         };
         let expected_cmd = Cmd::Format {
             style_opts: StyleOptions::default(),
@@ -763,6 +1601,9 @@ mod test {
                 fname: Target::File("infile".into()),
             },
             output: OutputTarget::Stdout,
+            no_ignore: false,
+            jobs: None,
+            file_list: None,
         };
         let mut expected = (expected_opt, expected_cmd);
 
@@ -791,6 +1632,62 @@ mod test {
             };
         }
         assert_eq!(parse(&["rcl", "f", "--check", "f1", "f2"]), expected);
+
+        if let Cmd::Format {
+            ref mut no_ignore, ..
+        } = &mut expected.1
+        {
+            *no_ignore = true;
+        }
+        assert_eq!(
+            parse(&["rcl", "f", "--check", "--no-ignore", "f1", "f2"]),
+            expected
+        );
+
+        if let Cmd::Format { ref mut jobs, .. } = &mut expected.1 {
+            *jobs = Some(4);
+        }
+        assert_eq!(
+            parse(&["rcl", "f", "--check", "--no-ignore", "-j4", "f1", "f2"]),
+            expected
+        );
+        assert_eq!(
+            parse(&["rcl", "f", "--check", "--no-ignore", "--jobs=4", "f1", "f2"]),
+            expected
+        );
+        assert_eq!(
+            fail_parse(&["rcl", "f", "--check", "-j0", "f1"]),
+            "Error: '0' is not valid for -j. See --help for usage.\n",
+        );
+    }
+
+    #[test]
+    fn parse_cmd_fmt_file_list() {
+        let file_list = |args: &[&'static str]| match parse(args).1 {
+            Cmd::Format { file_list, .. } => file_list,
+            _ => panic!("Expected a format command."),
+        };
+        assert_eq!(
+            file_list(&["rcl", "f", "--check", "--files-from", "-"]),
+            Some(FileList::FilesFrom(Target::Stdin)),
+        );
+        assert_eq!(
+            file_list(&["rcl", "f", "-i", "--files-from=-"]),
+            Some(FileList::FilesFrom(Target::Stdin)),
+        );
+        assert_eq!(
+            file_list(&["rcl", "f", "-i", "--files-from", "list"]),
+            Some(FileList::FilesFrom(Target::File("list".into()))),
+        );
+        assert_eq!(
+            file_list(&["rcl", "f", "--check", "--staged"]),
+            Some(FileList::GitStaged),
+        );
+        assert_eq!(
+            fail_parse(&["rcl", "f", "--staged"]),
+            "Error: Reading the files from a list requires --in-place or --check. \
+            See --help for usage.\n",
+        );
     }
 
     #[test]
@@ -806,6 +1703,7 @@ mod test {
             parse(&["rcl", "highlight", "-h"]).1,
             Cmd::Help { .. }
         ));
+        assert!(matches!(parse(&["rcl", "lsp", "-h"]).1, Cmd::Help { .. }));
         assert!(matches!(parse(&["rcl", "query", "-h"]).1, Cmd::Help { .. }));
         // Missing subcommand also triggers help.
         assert!(matches!(parse(&["rcl"]).1, Cmd::Help { .. }));
@@ -816,6 +1714,9 @@ mod test {
         let expected_opt = GlobalOptions {
             markup: None,
             workdir: None,
+            stdin_filename: None,
+            error_format: ErrorFormat::Human,
+            no_pager: false,
         };
         let expected_cmd = Cmd::Highlight {
             fname: Target::File("infile".into()),
@@ -824,11 +1725,191 @@ mod test {
         assert_eq!(parse(&["rcl", "highlight", "infile"]), expected);
     }
 
+    #[test]
+    fn parse_cmd_completion() {
+        assert_eq!(
+            parse(&["rcl", "completion", "zsh"]).1,
+            Cmd::Completion { shell: Shell::Zsh }
+        );
+        assert_eq!(
+            fail_parse(&["rcl", "completion", "tcsh"]),
+            "Error: Expected one of 'bash', 'fish', or 'zsh'. See --help for usage.\n"
+        );
+        assert!(super::parse(vec!["rcl".into(), "completion".into()]).is_err());
+    }
+
+    #[test]
+    fn parse_cmd_generate_grammar() {
+        assert_eq!(
+            parse(&["rcl", "generate-grammar", "vim"]).1,
+            Cmd::GenerateGrammar {
+                format: GrammarFormat::Vim
+            }
+        );
+        assert_eq!(
+            fail_parse(&["rcl", "generate-grammar", "emacs"]),
+            "Error: Expected 'textmate' or 'vim'. See --help for usage.\n"
+        );
+    }
+
+    #[test]
+    fn parse_stdin_filename() {
+        let (global_opts, cmd) = parse(&["rcl", "eval", "--stdin-filename=conf/a.rcl", "-"]);
+        assert_eq!(global_opts.stdin_filename.as_deref(), Some("conf/a.rcl"));
+        assert!(matches!(
+            cmd,
+            Cmd::Evaluate {
+                fname: Target::Stdin,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn parse_trace_file() {
+        let (_, cmd) = parse(&["rcl", "build", "--trace-file", "trace.json"]);
+        match cmd {
+            Cmd::Build { eval_opts, .. } => {
+                assert_eq!(eval_opts.trace_file.as_deref(), Some("trace.json"))
+            }
+            _ => panic!("Expected a build command."),
+        }
+    }
+
+    #[test]
+    fn parse_time() {
+        let (_, cmd) = parse(&["rcl", "eval", "--time", "a.rcl"]);
+        match cmd {
+            Cmd::Evaluate { eval_opts, .. } => assert!(eval_opts.time),
+            _ => panic!("Expected an evaluate command."),
+        }
+    }
+
+    #[test]
+    fn parse_no_pager() {
+        let (global_opts, _) = parse(&["rcl", "eval", "a.rcl", "--no-pager"]);
+        assert!(global_opts.no_pager);
+    }
+
+    #[test]
+    fn parse_error_format() {
+        let (global_opts, _) = parse(&["rcl", "--error-format=json", "eval", "a.rcl"]);
+        assert_eq!(global_opts.error_format, ErrorFormat::Json);
+        let (global_opts, _) = parse(&["rcl", "eval", "a.rcl"]);
+        assert_eq!(global_opts.error_format, ErrorFormat::Human);
+    }
+
+    #[test]
+    fn parse_cmd_check() {
+        assert_eq!(
+            parse(&["rcl", "check"]).1,
+            Cmd::Check {
+                eval_opts: EvalOptions::default(),
+                fnames: vec![Target::StdinDefault],
+            }
+        );
+        assert_eq!(
+            parse(&["rcl", "check", "a.rcl", "b.rcl", "--sandbox=unrestricted"]).1,
+            Cmd::Check {
+                eval_opts: EvalOptions {
+                    sandbox: SandboxMode::Unrestricted,
+                    ..EvalOptions::default()
+                },
+                fnames: vec![Target::File("a.rcl".into()), Target::File("b.rcl".into())],
+            }
+        );
+    }
+
+    #[test]
+    fn parse_cmd_schema() {
+        assert_eq!(
+            parse(&["rcl", "schema", "a.rcl", "-o", "a.json", "-w", "60"]).1,
+            Cmd::Schema {
+                eval_opts: EvalOptions::default(),
+                style_opts: StyleOptions { width: Some(60) },
+                fname: Target::File("a.rcl".into()),
+                output: OutputTarget::File("a.json".into()),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_cmd_diff() {
+        assert_eq!(
+            parse(&["rcl", "diff", "a.rcl", "-", "--sandbox=unrestricted"]).1,
+            Cmd::Diff {
+                eval_opts: EvalOptions {
+                    sandbox: SandboxMode::Unrestricted,
+                    ..EvalOptions::default()
+                },
+                style_opts: StyleOptions::default(),
+                old: Target::File("a.rcl".into()),
+                new: Target::Stdin,
+                git_revs: None,
+                output: OutputTarget::Stdout,
+            }
+        );
+        assert_eq!(
+            fail_parse(&["rcl", "diff", "a.rcl"]),
+            "Error: Expected two input files. See --help for usage.\n"
+        );
+        assert_eq!(
+            fail_parse(&["rcl", "diff", "-", "-"]),
+            "Error: Only one of the inputs can be stdin.\n"
+        );
+        assert_eq!(
+            parse(&["rcl", "diff", "--git", "HEAD~", "HEAD", "a.rcl", "-fjson"]).1,
+            Cmd::Diff {
+                eval_opts: EvalOptions {
+                    format: OutputFormat::Json,
+                    ..EvalOptions::default()
+                },
+                style_opts: StyleOptions::default(),
+                old: Target::File("a.rcl".into()),
+                new: Target::File("a.rcl".into()),
+                git_revs: Some(("HEAD~".into(), "HEAD".into())),
+                output: OutputTarget::Stdout,
+            }
+        );
+        assert_eq!(
+            fail_parse(&["rcl", "diff", "--git", "HEAD", "a.rcl"]),
+            "Error: Expected two revisions and a file. See --help for usage.\n"
+        );
+        assert_eq!(
+            fail_parse(&["rcl", "diff", "--git", "--", "--output=x", "HEAD", "a.rcl"]),
+            "Error: A revision must not start with '-'.\n"
+        );
+        assert_eq!(
+            fail_parse(&["rcl", "diff", "a.rcl", "b.rcl", "--format=toml"]),
+            "Error: The 'diff' command supports only the 'rcl' and 'json' formats.\n"
+        );
+    }
+
+    #[test]
+    fn parse_cmd_lsp() {
+        assert_eq!(parse(&["rcl", "lsp"]).1, Cmd::Lsp);
+        assert!(super::parse(vec!["rcl".into(), "lsp".into(), "infile".into()]).is_err());
+    }
+
+    #[test]
+    fn parse_cmd_self_test_fmt() {
+        assert_eq!(
+            parse(&["rcl", "self-test-fmt", "a.rcl", "-w80", "b.rcl"]).1,
+            Cmd::SelfTestFmt {
+                style_opts: StyleOptions { width: Some(80) },
+                fnames: vec![Target::File("a.rcl".into()), Target::File("b.rcl".into())],
+            }
+        );
+    }
+
     #[test]
     fn parse_cmd_query() {
         let expected_opt = GlobalOptions {
             markup: None,
             workdir: None,
+            stdin_filename: None,
+            error_format: ErrorFormat::Human,
+            no_pager: false,
         };
         let expected_cmd = Cmd::Query {
             eval_opts: EvalOptions::default(),
@@ -851,6 +1932,18 @@ mod test {
         };
         assert_eq!(parse(&["rcl", "rq", "infile", "input.name"]), expected);
 
+        if let Cmd::Query { eval_opts, .. } = &mut expected.1 {
+            eval_opts.format = OutputFormat::Json;
+            eval_opts.input_format = InputFormat::Json;
+        };
+        assert_eq!(
+            parse(&["rcl", "jq", "--input-format=json", "infile", "input.name"]),
+            expected
+        );
+        if let Cmd::Query { eval_opts, .. } = &mut expected.1 {
+            eval_opts.input_format = InputFormat::Rcl;
+        };
+
         if let Cmd::Query {
             eval_opts,
             fname,
@@ -870,11 +1963,15 @@ mod test {
         let expected_opt = GlobalOptions {
             markup: None,
             workdir: None,
+            stdin_filename: None,
+            error_format: ErrorFormat::Human,
+            no_pager: false,
         };
         let expected_cmd = Cmd::Build {
             eval_opts: EvalOptions::default(),
             build_mode: BuildMode::WriteFilesystem,
             fname: Target::File("build.rcl".to_string()),
+            jobs: None,
         };
         let mut expected = (expected_opt, expected_cmd);
         assert_eq!(parse(&["rcl", "build"]), expected);
@@ -928,4 +2025,24 @@ mod test {
             }
         );
     }
+
+    #[test]
+    fn usage_describes_every_output_format() {
+        let usage = super::USAGE_EVAL_QUERY;
+        let start = usage.find("Output format:\n").unwrap();
+        let section = &usage[start..];
+        let section = &section[..section.find("\n\n").unwrap()];
+        let names: Vec<&str> = section
+            .lines()
+            .skip(1)
+            .filter_map(|line| line.strip_prefix("  "))
+            .filter(|line| !line.starts_with(' '))
+            .filter_map(|line| line.split_whitespace().next())
+            .collect();
+        let formats: Vec<&str> = super::OUTPUT_FORMATS
+            .iter()
+            .map(|(name, _)| *name)
+            .collect();
+        assert_eq!(names, formats);
+    }
 }
@@ -145,6 +145,32 @@ macro_rules! match_option {
             None => return Error::new(err).err(),
         }
     }};
+    {
+        $args_iter:ident: $option:expr,
+        in $table:expr
+    } => {{
+        let mut err = vec![
+            "Expected ".into(),
+            Doc::from($option.to_string()).with_markup(Markup::Highlight),
+            " to be followed by one of ".into(),
+        ];
+        for (name, _) in $table {
+            err.push(Doc::from(*name).with_markup(Markup::Highlight));
+            err.push(", ".into());
+        }
+        err.pop();
+        err.push(". See --help for usage.".into());
+        let err = Doc::Concat(err);
+
+        let value = match $args_iter.next() {
+            Some(Arg::Plain(value)) => $table.iter().find(|(name, _)| *name == value),
+            _ => None,
+        };
+        match value {
+            Some((_, val)) => *val,
+            None => return Error::new(err).err(),
+        }
+    }};
 }
 pub(crate) use match_option;
 
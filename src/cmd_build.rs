@@ -7,13 +7,15 @@
 
 //! Implementation of the `rcl build` subcommand.
 
-use std::io::Read;
+use std::io::{Read, Write};
 use std::rc::Rc;
 
-use crate::cli::OutputFormat;
-use crate::error::{Error, PathElement, Result};
+use crate::cli::{OutputFormat, OUTPUT_FORMATS};
+use crate::cmd_eval::FormatOptions;
+use crate::error::{Error, ErrorKind, PathElement, Result};
 use crate::fmt_rcl::format_rcl;
 use crate::loader::{Loader, OpenMode};
+use crate::parallel;
 use crate::pprint::{concat, Config, Doc};
 use crate::runtime::Value;
 use crate::source::{DocId, Span};
@@ -65,20 +67,15 @@ struct Target {
     banner: Option<Rc<str>>,
     contents: Value,
     format: OutputFormat,
+    format_opts: FormatOptions,
     width: u32,
 }
 
 fn parse_format(format: &str) -> Option<OutputFormat> {
-    // Note, this is duplicated between the CLI parser.
-    let f = match format {
-        "json" => OutputFormat::Json,
-        "raw" => OutputFormat::Raw,
-        "rcl" => OutputFormat::Rcl,
-        "toml" => OutputFormat::Toml,
-        "yaml-stream" => OutputFormat::YamlStream,
-        _ => return None,
-    };
-    Some(f)
+    OUTPUT_FORMATS
+        .iter()
+        .find(|(name, _)| *name == format)
+        .map(|(_, f)| *f)
 }
 
 fn parse_targets(doc_span: Span, targets_value: Value) -> Result<Vec<Target>> {
@@ -103,6 +100,7 @@ fn parse_targets(doc_span: Span, targets_value: Value) -> Result<Vec<Target>> {
             banner: banner.clone(),
             contents: Value::Null,
             format: OutputFormat::Rcl,
+            format_opts: FormatOptions::default(),
             width: 80,
         };
         for (k, v) in target_value.expect_dict().iter() {
@@ -130,8 +128,18 @@ fn parse_targets(doc_span: Span, targets_value: Value) -> Result<Vec<Target>> {
                         .with_help("See 'rcl evaluate --help' for supported output formats.")
                         .err();
                 }
+                "key_separator" => match v {
+                    Value::String(sep) => target.format_opts.key_separator = sep.to_string(),
+                    _not_str => return make_error("Key separator must be a string.".into()).err(),
+                },
+                "require_same_keys" => match v {
+                    Value::Bool(b) => target.format_opts.require_same_keys = *b,
+                    _not_bool => {
+                        return make_error("Require same keys must be a boolean.".into()).err()
+                    }
+                },
                 "width" => match v {
-                    Value::Int(w) if *w > 0 && *w <= u32::MAX as i64 => target.width = *w as u32,
+                    Value::Int(w) if *w > 0 && *w <= u32::MAX as i128 => target.width = *w as u32,
                     _not_int => {
                         return make_error("Width must be a positive integer.".into()).err()
                     }
@@ -175,24 +183,61 @@ fn parse_targets(doc_span: Span, targets_value: Value) -> Result<Vec<Target>> {
 }
 
 /// Take a build specification and write the outputs to files.
+///
+/// If a depfile path is given, write the outputs and the files that were loaded
+/// to produce them there, except in dry-run mode, which does not write files.
+///
+/// The outputs are pretty-printed on at most `jobs` threads. Converting the
+/// values into documents, and writing the files, happens on the main thread.
 pub fn execute_build(
     loader: &Loader,
+    jobs: usize,
     mode: BuildMode,
     buildfile: DocId,
-    doc_span: Span,
     targets_value: Value,
+    schema: Option<&Value>,
+    depfile_path: Option<&str>,
 ) -> Result<()> {
+    let doc_span = loader.get_span(buildfile);
     let targets = parse_targets(doc_span, targets_value).map_err(|mut err| {
         err.origin = Some(err.origin.unwrap_or(doc_span));
         err
     })?;
 
-    let mut n_changed = 0;
+    // Validate all targets before we write any of them, so a violation does
+    // not leave a partially updated tree behind.
+    if let Some(schema) = schema {
+        for target in targets.iter() {
+            crate::json_schema::validate(doc_span, schema, &target.contents).map_err(
+                |mut err| {
+                    let mut path = vec![
+                        PathElement::Key(target.out_path.as_ref().into()),
+                        PathElement::Key("contents".into()),
+                    ];
+                    path.append(&mut err.path);
+                    err.path = path;
+                    err
+                },
+            )?;
+        }
+    }
 
-    for (i, target) in targets.iter().enumerate() {
-        println!("[{}/{}] {}", i + 1, targets.len(), target.out_path);
+    if let (Some(depfile_path), false) = (depfile_path, mode == BuildMode::DryRun) {
+        let out_paths: Vec<&str> = targets.iter().map(|t| t.out_path.as_ref()).collect();
+        loader.write_build_depfile(&out_paths, buildfile, depfile_path)?;
+    }
 
-        let mut doc = crate::cmd_eval::format_value(target.format, doc_span, &target.contents)?;
+    let mut n_changed = 0;
+
+    // Values can't cross threads, so we convert them into documents first.
+    let mut docs = Vec::with_capacity(targets.len());
+    for target in targets.iter() {
+        let mut doc = crate::cmd_eval::format_value(
+            target.format,
+            &target.format_opts,
+            doc_span,
+            &target.contents,
+        )?;
 
         if let Some(banner) = target.banner.as_ref() {
             doc = concat! {
@@ -204,18 +249,47 @@ pub fn execute_build(
 
         let print_cfg = Config {
             width: target.width,
+            ..Config::default()
         };
-        let result = doc.println(&print_cfg);
+        docs.push((doc, print_cfg));
+    }
+
+    let outputs = parallel::map(jobs, &docs, |(doc, print_cfg)| {
+        let mut contents = Vec::new();
+        doc.println(print_cfg)
+            .write_bytes_no_markup(&mut contents)
+            .expect("Writing in memory does not fail.");
+        contents
+    });
+
+    for (i, (target, contents)) in targets.iter().zip(outputs).enumerate() {
+        println!("[{}/{}] {}", i + 1, targets.len(), target.out_path);
 
         match mode {
             BuildMode::WriteFilesystem => {
                 // coverage:off -- We don't test writing to the file system in tests.
+                // Leave files that are already up to date untouched. This
+                // preserves their modification time, so build tools that
+                // depend on them do not need to redo work.
+                let is_up_to_date = loader
+                    .open_build_output(target.out_path.as_ref(), buildfile, OpenMode::Read)
+                    .ok()
+                    .and_then(|mut f| {
+                        let mut actual = Vec::with_capacity(contents.len());
+                        f.read_to_end(&mut actual).ok()?;
+                        Some(actual == contents)
+                    })
+                    .unwrap_or(false);
+                if is_up_to_date {
+                    continue;
+                }
+
                 let mut out_file = loader.open_build_output(
                     target.out_path.as_ref(),
                     buildfile,
                     OpenMode::Write,
                 )?;
-                match result.write_bytes_no_markup(&mut out_file) {
+                match out_file.write_all(&contents) {
                     Ok(()) => continue,
                     Err(err) => {
                         return Error::new(concat! {
@@ -233,14 +307,10 @@ pub fn execute_build(
                     buildfile,
                     OpenMode::Read,
                 )?;
-                let mut expected = Vec::new();
-                result
-                    .write_bytes_no_markup(&mut expected)
-                    .expect("Writing in memory does not fail.");
-                let mut actual = Vec::with_capacity(expected.len());
+                let mut actual = Vec::with_capacity(contents.len());
                 match out_file.read_to_end(&mut actual) {
                     Ok(_) => {
-                        if actual != expected {
+                        if actual != contents {
                             // coverage:off -- All files in the repo should exist and be compliant.
                             println!("Would rewrite {}", target.out_path.as_ref());
                             n_changed += 1;
@@ -260,7 +330,7 @@ pub fn execute_build(
                 let mut stdout = std::io::stdout().lock();
                 // Ignore the result here, if we fail to write to stdout,
                 // then we have no good way of reporting the error anyway.
-                let _ = result.write_bytes_no_markup(&mut stdout);
+                let _ = stdout.write_all(&contents);
             }
         }
     }
@@ -275,7 +345,9 @@ pub fn execute_build(
                 targets.len().to_string().into(),
                 Doc::str(" files would be rewritten."),
             ];
-            Error::new(Doc::Concat(parts)).err()
+            Error::new(Doc::Concat(parts))
+                .with_kind(ErrorKind::Check)
+                .err()
             // coverage:on
         }
         BuildMode::Check => {
@@ -0,0 +1,199 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Parsing and typechecking documents without evaluating them, for `rcl check`.
+//!
+//! Because import paths are string literals, the import graph is known
+//! statically, so we can follow imports without evaluating anything. Every
+//! document is checked once, in a clean environment, like it would be when it
+//! gets imported during evaluation. Data documents such as TOML and CSV are
+//! parsed with the same parser that imports use, but not typechecked.
+
+use std::collections::{BTreeSet, VecDeque};
+
+use crate::ast::{Expr, Seq, Stmt, Yield};
+use crate::error::{Error, IntoError};
+use crate::loader::Loader;
+use crate::source::{DocId, Span};
+use crate::typecheck;
+
+/// The outcome of checking documents and their imports.
+pub struct CheckResult {
+    /// The number of documents that we checked, including imported ones.
+    pub n_checked: usize,
+
+    /// The errors that we found, at most one per document.
+    pub errors: Vec<Error>,
+}
+
+/// Parse and typecheck the documents, and all documents that they import.
+pub fn check_docs(loader: &mut Loader, docs: &[DocId]) -> CheckResult {
+    let mut visited: BTreeSet<DocId> = docs.iter().copied().collect();
+    let mut queue: VecDeque<DocId> = docs.iter().copied().collect();
+    let mut result = CheckResult {
+        n_checked: 0,
+        errors: Vec::new(),
+    };
+
+    while let Some(doc) = queue.pop_front() {
+        result.n_checked += 1;
+
+        // Data documents do not get typechecked, and they cannot import
+        // anything, but they should still parse.
+        if let Some(parse_data) = loader.get_data_parser(doc) {
+            if let Err(err) = parse_data(loader, doc) {
+                result.errors.push(*err);
+            }
+            continue;
+        }
+
+        let mut env = typecheck::prelude();
+        let ast = match loader.get_typechecked_ast(&mut env, doc) {
+            Ok(ast) => ast,
+            Err(err) => {
+                result.errors.push(*err);
+                continue;
+            }
+        };
+
+        let mut imports = Vec::new();
+        collect_imports_expr(&ast, &mut imports);
+
+        for (path_span, path_expr) in imports {
+            // These are the same restrictions that the evaluator enforces.
+            let path = match path_expr {
+                Expr::StringLit(path) => path,
+                Expr::Format(..) => {
+                    let err =
+                        path_span.error("Import path must be a string literal without holes.");
+                    result.errors.push(err);
+                    continue;
+                }
+                _ => {
+                    let err = path_span.error("Import path must be a string literal.");
+                    result.errors.push(err);
+                    continue;
+                }
+            };
+            match loader.load_path(path.as_ref(), Some(doc)) {
+                Ok(imported) => {
+                    if visited.insert(imported) {
+                        queue.push_back(imported);
+                    }
+                }
+                Err(mut err) => {
+                    if err.origin.is_none() {
+                        err.origin = Some(path_span);
+                    }
+                    result.errors.push(*err);
+                }
+            }
+        }
+    }
+
+    result
+}
+
+/// Collect the path expressions of all imports in the expression.
+fn collect_imports_expr<'a>(expr: &'a Expr, out: &mut Vec<(Span, &'a Expr)>) {
+    match expr {
+        Expr::Stmt { stmt, body, .. } => {
+            collect_imports_stmt(stmt, out);
+            collect_imports_expr(body, out);
+        }
+        Expr::Import { path_span, path } => out.push((*path_span, path)),
+        Expr::BraceLit { elements, .. }
+        | Expr::BracketLit { elements, .. }
+        | Expr::SetLit { elements, .. }
+        | Expr::DictLit { elements, .. } => {
+            for seq in elements {
+                collect_imports_seq(seq, out);
+            }
+        }
+        Expr::NullLit
+        | Expr::BoolLit(..)
+        | Expr::StringLit(..)
+        | Expr::IntegerLit(..)
+        | Expr::NumberLit(..)
+        | Expr::Var { .. } => {}
+        Expr::Format(fragments) => {
+            for fragment in fragments {
+                collect_imports_expr(&fragment.body, out);
+            }
+        }
+        Expr::IfThenElse {
+            condition,
+            body_then,
+            body_else,
+            ..
+        } => {
+            collect_imports_expr(condition, out);
+            collect_imports_expr(body_then, out);
+            collect_imports_expr(body_else, out);
+        }
+        Expr::Field { inner, .. } => collect_imports_expr(inner, out),
+        Expr::Function { body, .. }
+        | Expr::TypedFunction { body, .. }
+        | Expr::UnOp { body, .. }
+        | Expr::CheckType { body, .. } => collect_imports_expr(body, out),
+        Expr::Call { function, args, .. } => {
+            collect_imports_expr(function, out);
+            for arg in args {
+                collect_imports_expr(&arg.value, out);
+            }
+        }
+        Expr::Index {
+            collection, index, ..
+        } => {
+            collect_imports_expr(collection, out);
+            collect_imports_expr(index, out);
+        }
+        Expr::BinOp { lhs, rhs, .. } => {
+            collect_imports_expr(lhs, out);
+            collect_imports_expr(rhs, out);
+        }
+    }
+}
+
+fn collect_imports_stmt<'a>(stmt: &'a Stmt, out: &mut Vec<(Span, &'a Expr)>) {
+    match stmt {
+        Stmt::Let { value, .. } => collect_imports_expr(value, out),
+        Stmt::Assert {
+            condition, message, ..
+        } => {
+            collect_imports_expr(condition, out);
+            collect_imports_expr(message, out);
+        }
+        Stmt::Trace { message, .. } => collect_imports_expr(message, out),
+    }
+}
+
+fn collect_imports_seq<'a>(seq: &'a Seq, out: &mut Vec<(Span, &'a Expr)>) {
+    match seq {
+        Seq::Yield(Yield::Elem { value, .. }) => collect_imports_expr(value, out),
+        Seq::Yield(Yield::Assoc { key, value, .. }) => {
+            collect_imports_expr(key, out);
+            collect_imports_expr(value, out);
+        }
+        Seq::Stmt { stmt, body } => {
+            collect_imports_stmt(stmt, out);
+            collect_imports_seq(body, out);
+        }
+        Seq::For {
+            collection, body, ..
+        } => {
+            collect_imports_expr(collection, out);
+            collect_imports_seq(body, out);
+        }
+        Seq::If {
+            condition, body, ..
+        } => {
+            collect_imports_expr(condition, out);
+            collect_imports_seq(body, out);
+        }
+    }
+}
@@ -0,0 +1,598 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Generating shell completion scripts.
+//!
+//! Rather than maintaining a separate list of commands and options, we extract
+//! them from the usage texts in [`crate::cli`]. That way the completions cannot
+//! drift from the documented command line interface, and a test checks that
+//! everything we extract is accepted by the parser.
+
+use crate::cli::{get_usage, Shell};
+
+/// The sections of the usage texts that list the values for an option or argument.
+const VALUE_SECTIONS: &[(&str, &str)] = &[
+    ("--color", "Color modes:"),
+    ("--error-format", "Error formats:"),
+    ("--format", "Output format:"),
+    ("--input-format", "Input format:"),
+    ("--sandbox", "Sandboxing modes:"),
+    ("<grammar>", "Grammar formats:"),
+    ("<shell>", "Shells:"),
+];
+
+/// What to complete for an option argument or positional argument.
+#[derive(Debug, Eq, PartialEq)]
+enum Complete {
+    Nothing,
+    File,
+    Directory,
+    Values(Vec<&'static str>),
+}
+
+/// An option, as documented in the usage text.
+#[derive(Debug)]
+struct Opt {
+    short: Option<&'static str>,
+    long: &'static str,
+    /// The placeholder name and what to complete for it, `None` for flags.
+    arg: Option<(&'static str, Complete)>,
+    help: String,
+}
+
+/// A command, as documented in the usage text.
+#[derive(Debug)]
+struct Command {
+    name: &'static str,
+    /// The name and shorthands that select this command.
+    names: Vec<&'static str>,
+    help: String,
+    options: Vec<Opt>,
+    args: Complete,
+}
+
+#[derive(Debug)]
+struct Cli {
+    global_options: Vec<Opt>,
+    commands: Vec<Command>,
+}
+
+/// Return the entries of the section with the given header.
+///
+/// An entry is a line indented by two spaces, followed by zero or more lines
+/// that are indented further. The result contains the term at the start of the
+/// entry, and its description.
+fn get_section(usage: &'static str, header: &str) -> Vec<(&'static str, String)> {
+    let mut result: Vec<(&'static str, String)> = Vec::new();
+    let mut lines = usage.lines().skip_while(|line| *line != header);
+    if lines.next().is_none() {
+        return result;
+    }
+    for line in lines.take_while(|line| !line.is_empty()) {
+        let content = line.trim_start();
+        match result.last_mut() {
+            Some((_term, description)) if line.len() - content.len() > 2 => {
+                // A long term can be on a line of its own, then the
+                // description starts on the next line.
+                if !description.is_empty() {
+                    description.push(' ');
+                }
+                description.push_str(content);
+            }
+            _ => match content.find("  ") {
+                Some(i) => result.push((&content[..i], content[i..].trim().to_string())),
+                None => result.push((content, String::new())),
+            },
+        }
+    }
+    result
+}
+
+/// Return the first sentence of a description.
+fn get_summary(description: &str) -> String {
+    match description.find(". ") {
+        Some(i) => description[..i + 1].to_string(),
+        None => description.to_string(),
+    }
+}
+
+/// Return the values listed for an option or argument, if there is a section for it.
+fn get_values(name: &str) -> Option<Vec<&'static str>> {
+    let (_, header) = VALUE_SECTIONS.iter().find(|(n, _)| *n == name)?;
+    let usages = get_section(get_usage("main")?, "Commands:")
+        .into_iter()
+        .filter_map(|(cmd, _)| get_usage(cmd))
+        .chain(get_usage("main"));
+    for usage in usages {
+        let section = get_section(usage, header);
+        if !section.is_empty() {
+            return Some(section.into_iter().map(|(value, _)| value).collect());
+        }
+    }
+    None
+}
+
+/// Return what to complete for an argument with the given placeholder.
+fn get_complete(option: &str, placeholder: &str) -> Complete {
+    match get_values(option) {
+        Some(values) => Complete::Values(values),
+        None if placeholder.ends_with("file>") || placeholder.ends_with("file>...") => {
+            Complete::File
+        }
+        None if placeholder == "<dir>" => Complete::Directory,
+        None => Complete::Nothing,
+    }
+}
+
+fn get_options(usage: &'static str, header: &str) -> Vec<Opt> {
+    let mut result = Vec::new();
+    for (term, description) in get_section(usage, header) {
+        let mut short = None;
+        let mut long = "";
+        let mut arg = None;
+        for part in term.split_whitespace() {
+            if part.starts_with("--") {
+                long = part;
+            } else if part.starts_with('-') {
+                short = Some(part);
+            } else {
+                arg = Some((part, get_complete(long, part)));
+            }
+        }
+        assert!(!long.is_empty(), "Every option should have a long form.");
+        result.push(Opt {
+            short,
+            long,
+            arg,
+            help: get_summary(&description),
+        });
+    }
+    result
+}
+
+/// Extract the commands and options from the usage texts.
+fn get_cli() -> Cli {
+    let main = get_usage("main").expect("The main usage exists.");
+
+    // Shorthands are documented as "Alias for 'x'", or for multiple shorthands
+    // at once as "Alias for 'x' and 'y' respectively".
+    let mut aliases: Vec<(&'static str, String)> = Vec::new();
+    for (term, description) in get_section(main, "Command shorthands:") {
+        let targets: Vec<&str> = description
+            .split('\'')
+            .skip(1)
+            .step_by(2)
+            .filter(|target| !target.starts_with('-'))
+            .collect();
+        for (i, alias) in term.split(", ").enumerate() {
+            aliases.push((alias, targets[i.min(targets.len() - 1)].to_string()));
+        }
+    }
+
+    let commands = get_section(main, "Commands:")
+        .into_iter()
+        .map(|(name, description)| {
+            let usage = get_usage(name).expect("Every listed command has a usage.");
+            let mut names = vec![name];
+            for (alias, target) in aliases.iter() {
+                // A shorthand can refer to another shorthand, e.g. 'je' to 'eval'.
+                let target = aliases
+                    .iter()
+                    .find(|(a, _)| a == target)
+                    .map_or(target, |(_, t)| t);
+                if target == name {
+                    names.push(alias);
+                }
+            }
+            let args = match get_section(usage, "Arguments:").first() {
+                Some((placeholder, _)) => get_complete(placeholder, placeholder),
+                None => Complete::Nothing,
+            };
+            Command {
+                name,
+                names,
+                help: get_summary(&description),
+                options: get_options(usage, "Options:"),
+                args,
+            }
+        })
+        .collect();
+
+    Cli {
+        global_options: get_options(main, "Global options:"),
+        commands,
+    }
+}
+
+/// Return all options that occur anywhere, without duplicates.
+fn get_all_options(cli: &Cli) -> Vec<&Opt> {
+    let mut result: Vec<&Opt> = Vec::new();
+    let all = cli
+        .global_options
+        .iter()
+        .chain(cli.commands.iter().flat_map(|cmd| cmd.options.iter()));
+    for opt in all {
+        if !result.iter().any(|o| o.long == opt.long) {
+            result.push(opt);
+        }
+    }
+    result
+}
+
+fn get_option_names(options: &[Opt]) -> impl Iterator<Item = &'static str> + '_ {
+    options
+        .iter()
+        .flat_map(|opt| opt.short.into_iter().chain(Some(opt.long)))
+}
+
+/// Generate the completion script for the given shell.
+pub fn generate(shell: Shell) -> String {
+    let cli = get_cli();
+    match shell {
+        Shell::Bash => generate_bash(&cli),
+        Shell::Fish => generate_fish(&cli),
+        Shell::Zsh => generate_zsh(&cli),
+    }
+}
+
+fn generate_bash(cli: &Cli) -> String {
+    let mut out = String::new();
+    out.push_str("# Bash completion for rcl, generated by 'rcl completion bash'.\n\n");
+    out.push_str("_rcl() {\n");
+    out.push_str("    local cur prev cmd opts args i\n");
+    out.push_str("    cur=\"${COMP_WORDS[COMP_CWORD]}\"\n");
+    out.push_str("    prev=\"${COMP_WORDS[COMP_CWORD-1]}\"\n");
+    out.push_str("    cmd=\"\"\n");
+    out.push_str("    for ((i = 1; i < COMP_CWORD; i++)); do\n");
+    out.push_str("        case \"${COMP_WORDS[i]}\" in\n");
+    for cmd in cli.commands.iter() {
+        let names = cmd.names.join("|");
+        out.push_str(&format!(
+            "            {names}) cmd=\"{}\"; break ;;\n",
+            cmd.name
+        ));
+    }
+    out.push_str("        esac\n");
+    out.push_str("    done\n\n");
+
+    // When the previous word is an option that takes an argument, complete
+    // the argument. If we produce no completions, `-o default` falls back to
+    // file names.
+    out.push_str("    case \"$prev\" in\n");
+    for opt in get_all_options(cli) {
+        let Some((_, complete)) = &opt.arg else {
+            continue;
+        };
+        let names: Vec<&str> = opt.short.into_iter().chain(Some(opt.long)).collect();
+        let action = match complete {
+            Complete::Nothing | Complete::File => "return".to_string(),
+            Complete::Directory => "COMPREPLY=($(compgen -d -- \"$cur\")); return".to_string(),
+            Complete::Values(values) => format!(
+                "COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")); return",
+                values.join(" ")
+            ),
+        };
+        out.push_str(&format!("        {}) {action} ;;\n", names.join("|")));
+    }
+    out.push_str("    esac\n\n");
+
+    let global: Vec<&str> = get_option_names(&cli.global_options).collect();
+    let commands: Vec<&str> = cli.commands.iter().map(|cmd| cmd.name).collect();
+    out.push_str("    case \"$cmd\" in\n");
+    out.push_str(&format!(
+        "        \"\") opts=\"{}\"; args=\"{}\" ;;\n",
+        global.join(" "),
+        commands.join(" ")
+    ));
+    for cmd in cli.commands.iter() {
+        let opts: Vec<&str> = get_option_names(&cmd.options)
+            .chain(global.iter().copied())
+            .collect();
+        let args = match &cmd.args {
+            Complete::Values(values) => values.join(" "),
+            _ => String::new(),
+        };
+        out.push_str(&format!(
+            "        {}) opts=\"{}\"; args=\"{args}\" ;;\n",
+            cmd.name,
+            opts.join(" ")
+        ));
+    }
+    out.push_str("    esac\n\n");
+
+    out.push_str("    if [[ \"$cur\" == -* ]]; then\n");
+    out.push_str("        COMPREPLY=($(compgen -W \"$opts\" -- \"$cur\"))\n");
+    out.push_str("    elif [[ -n \"$args\" ]]; then\n");
+    out.push_str("        COMPREPLY=($(compgen -W \"$args\" -- \"$cur\"))\n");
+    out.push_str("    fi\n");
+    out.push_str("}\n\n");
+    out.push_str("complete -o default -F _rcl rcl\n");
+    out
+}
+
+/// Escape a description for use inside brackets in a single-quoted zsh string.
+fn escape_zsh(description: &str) -> String {
+    description
+        .replace('\'', "'\\''")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+        .replace(':', "\\:")
+}
+
+fn get_zsh_option_spec(opt: &Opt) -> String {
+    let help = escape_zsh(&opt.help);
+    // Long options that take an argument accept it after '=' or as a separate
+    // word, which is what the trailing '=' means to `_arguments`.
+    let eq = if opt.arg.is_some() { "=" } else { "" };
+    let mut spec = match opt.short {
+        Some(short) => format!(
+            "'({short} {})'{{{short},{}{eq}}}'[{help}]",
+            opt.long, opt.long
+        ),
+        None => format!("'{}{eq}[{help}]", opt.long),
+    };
+    if let Some((placeholder, complete)) = &opt.arg {
+        let message = placeholder.trim_start_matches('<').trim_end_matches('>');
+        let action = match complete {
+            Complete::Nothing => " ".to_string(),
+            Complete::File => "_files".to_string(),
+            Complete::Directory => "_files -/".to_string(),
+            Complete::Values(values) => format!("({})", values.join(" ")),
+        };
+        spec.push_str(&format!(":{message}:{action}"));
+    }
+    spec.push('\'');
+    spec
+}
+
+fn generate_zsh(cli: &Cli) -> String {
+    let mut out = String::new();
+    out.push_str("#compdef rcl\n\n");
+    out.push_str("# Zsh completion for rcl, generated by 'rcl completion zsh'.\n\n");
+    out.push_str("_rcl() {\n");
+    out.push_str("    local curcontext=\"$curcontext\" state line\n");
+    out.push_str("    local -a global_opts commands\n");
+    out.push_str("    global_opts=(\n");
+    for opt in cli.global_options.iter() {
+        out.push_str(&format!("        {}\n", get_zsh_option_spec(opt)));
+    }
+    out.push_str("    )\n");
+    out.push_str("    commands=(\n");
+    for cmd in cli.commands.iter() {
+        out.push_str(&format!(
+            "        '{}:{}'\n",
+            cmd.name,
+            escape_zsh(&cmd.help)
+        ));
+    }
+    out.push_str("    )\n\n");
+    out.push_str(
+        "    _arguments -C $global_opts '1:command:->command' '*::argument:->argument'\n\n",
+    );
+    out.push_str("    case $state in\n");
+    out.push_str("        command)\n");
+    out.push_str("            _describe command commands\n");
+    out.push_str("            ;;\n");
+    out.push_str("        argument)\n");
+    out.push_str("            case $words[1] in\n");
+    for cmd in cli.commands.iter() {
+        out.push_str(&format!("                {})\n", cmd.names.join("|")));
+        out.push_str("                    _arguments $global_opts");
+        for opt in cmd.options.iter() {
+            out.push_str(" \\\n                        ");
+            out.push_str(&get_zsh_option_spec(opt));
+        }
+        match &cmd.args {
+            Complete::Nothing => {}
+            Complete::File => out.push_str(" \\\n                        '*:file:_files'"),
+            Complete::Directory => out.push_str(" \\\n                        '*:dir:_files -/'"),
+            Complete::Values(values) => out.push_str(&format!(
+                " \\\n                        '1:value:({})'",
+                values.join(" ")
+            )),
+        }
+        out.push_str("\n                    ;;\n");
+    }
+    out.push_str("            esac\n");
+    out.push_str("            ;;\n");
+    out.push_str("    esac\n");
+    out.push_str("}\n\n");
+    out.push_str("_rcl \"$@\"\n");
+    out
+}
+
+/// Quote a string for fish.
+fn quote_fish(s: &str) -> String {
+    format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+fn push_fish_option(out: &mut String, condition: &str, opt: &Opt) {
+    out.push_str("complete -c rcl");
+    out.push_str(condition);
+    if let Some(short) = opt.short {
+        out.push_str(&format!(" -s {}", &short[1..]));
+    }
+    out.push_str(&format!(" -l {}", &opt.long[2..]));
+    match &opt.arg {
+        None => {}
+        Some((_, Complete::Nothing)) => out.push_str(" -x"),
+        Some((_, Complete::File)) => out.push_str(" -r -F"),
+        Some((_, Complete::Directory)) => {
+            out.push_str(" -x -a '(__fish_complete_directories)'");
+        }
+        Some((_, Complete::Values(values))) => {
+            out.push_str(&format!(" -x -a {}", quote_fish(&values.join(" "))));
+        }
+    }
+    out.push_str(&format!(" -d {}\n", quote_fish(&opt.help)));
+}
+
+fn generate_fish(cli: &Cli) -> String {
+    let mut out = String::new();
+    out.push_str("# Fish completion for rcl, generated by 'rcl completion fish'.\n\n");
+    // Do not complete file names unless a command or option takes files.
+    out.push_str("complete -c rcl -f\n\n");
+    for opt in cli.global_options.iter() {
+        push_fish_option(&mut out, "", opt);
+    }
+    out.push('\n');
+    for cmd in cli.commands.iter() {
+        out.push_str(&format!(
+            "complete -c rcl -n __fish_use_subcommand -a {} -d {}\n",
+            cmd.name,
+            quote_fish(&cmd.help)
+        ));
+    }
+    for cmd in cli.commands.iter() {
+        out.push('\n');
+        let condition = format!(" -n '__fish_seen_subcommand_from {}'", cmd.names.join(" "));
+        for opt in cmd.options.iter() {
+            push_fish_option(&mut out, &condition, opt);
+        }
+        match &cmd.args {
+            Complete::Nothing => {}
+            Complete::File => out.push_str(&format!("complete -c rcl{condition} -F\n")),
+            Complete::Directory => out.push_str(&format!(
+                "complete -c rcl{condition} -a '(__fish_complete_directories)'\n"
+            )),
+            Complete::Values(values) => out.push_str(&format!(
+                "complete -c rcl{condition} -a {}\n",
+                quote_fish(&values.join(" "))
+            )),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::{get_cli, Complete, Opt};
+    use crate::cli::{get_usage, parse, Cmd, Shell};
+
+    /// Return example invocations of the option, with a valid argument if needed.
+    fn get_examples(opt: &Opt) -> Vec<Vec<&'static str>> {
+        let names = opt.short.into_iter().chain(Some(opt.long));
+        let values = match &opt.arg {
+            None => vec![],
+            Some((_, Complete::Values(values))) => values.clone(),
+            Some(_) => vec!["1"],
+        };
+        let mut result = Vec::new();
+        for name in names {
+            if values.is_empty() {
+                result.push(vec![name]);
+            }
+            for value in values.iter() {
+                result.push(vec![name, value]);
+            }
+        }
+        result
+    }
+
+    fn assert_parses_to_help(args: &[&str], usage: &'static str) {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        match parse(args.clone()) {
+            Ok((_, Cmd::Help { usage: u })) if u == usage => {}
+            _ => panic!("Expected {args:?} to parse as a help command."),
+        }
+    }
+
+    #[test]
+    fn completions_are_accepted_by_the_parser() {
+        let cli = get_cli();
+        let main = get_usage("main").unwrap();
+        assert!(cli.commands.len() > 5);
+        assert!(cli.global_options.iter().any(|opt| opt.long == "--color"));
+
+        for opt in cli.global_options.iter() {
+            for example in get_examples(opt) {
+                let mut args = vec!["rcl"];
+                args.extend(example);
+                args.push("--help");
+                assert_parses_to_help(&args, main);
+            }
+        }
+
+        for cmd in cli.commands.iter() {
+            let usage = get_usage(cmd.name).unwrap();
+            for name in cmd.names.iter() {
+                assert_parses_to_help(&["rcl", name, "--help"], usage);
+            }
+            for opt in cmd.options.iter() {
+                for example in get_examples(opt) {
+                    let mut args = vec!["rcl", cmd.name];
+                    args.extend(example);
+                    args.push("--help");
+                    assert_parses_to_help(&args, usage);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn get_cli_extracts_shorthands_and_values() {
+        let cli = get_cli();
+        let eval = cli.commands.iter().find(|c| c.name == "evaluate").unwrap();
+        assert_eq!(eval.names, ["evaluate", "e", "eval", "je", "re"]);
+        assert_eq!(eval.args, Complete::File);
+        let format = eval.options.iter().find(|o| o.long == "--format").unwrap();
+        assert_eq!(format.short, Some("-f"));
+        assert_eq!(
+            format.arg,
+            Some((
+                "<format>",
+                Complete::Values(vec![
+                    "csv",
+                    "hcl",
+                    "ini",
+                    "json",
+                    "json-canonical",
+                    "json-compact",
+                    "json-lines",
+                    "nix",
+                    "plist",
+                    "properties",
+                    "raw",
+                    "rcl",
+                    "textproto",
+                    "toml",
+                    "tsv",
+                    "yaml-stream"
+                ])
+            ))
+        );
+
+        let completion = cli
+            .commands
+            .iter()
+            .find(|c| c.name == "completion")
+            .unwrap();
+        assert_eq!(
+            completion.args,
+            Complete::Values(vec!["bash", "fish", "zsh"])
+        );
+    }
+
+    #[test]
+    fn generate_covers_all_commands() {
+        for shell in [Shell::Bash, Shell::Fish, Shell::Zsh] {
+            let script = super::generate(shell);
+            for cmd in [
+                "build",
+                "evaluate",
+                "format",
+                "highlight",
+                "query",
+                "schema",
+            ] {
+                assert!(script.contains(cmd), "{shell:?} script lacks {cmd}.");
+            }
+            assert!(script.contains("yaml-stream"));
+            assert!(script.contains("output-depfile"));
+        }
+    }
+}
@@ -0,0 +1,522 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Structural diffs between values, for `rcl diff`.
+//!
+//! Unlike a textual diff, this compares values, so changes in formatting,
+//! comments, or how a value is computed do not show up, only changes in the
+//! resulting value do.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::rc::Rc;
+
+use crate::fmt_rcl::format_rcl;
+use crate::markup::Markup;
+use crate::pprint::{concat, indent, Config, Doc};
+use crate::runtime::Value;
+use crate::string::is_identifier;
+
+/// A difference between two values, at some path inside the values.
+enum Change<'a> {
+    Added(&'a Value),
+    Removed(&'a Value),
+    Changed(&'a Value, &'a Value),
+    /// A set element was added, the path ends in the element itself.
+    AddedElement,
+    /// A set element was removed, the path ends in the element itself.
+    RemovedElement,
+}
+
+struct Differ<'a> {
+    changes: Vec<(String, Change<'a>)>,
+}
+
+/// Format a value on a single line, for use in a path.
+fn format_inline(value: &Value) -> String {
+    let cfg = Config {
+        width: u32::MAX,
+        ..Config::default()
+    };
+    let result = format_rcl(value).println(&cfg).to_string_no_markup();
+    result.trim_end().to_string()
+}
+
+fn get_key_path(path: &str, key: &Value) -> String {
+    match key {
+        Value::String(s) if is_identifier(s) => format!("{path}.{s}"),
+        _ => format!("{path}[{}]", format_inline(key)),
+    }
+}
+
+impl<'a> Differ<'a> {
+    fn diff(&mut self, path: String, old: &'a Value, new: &'a Value) {
+        if old == new {
+            return;
+        }
+        match (old, new) {
+            (Value::Dict(old), Value::Dict(new)) => {
+                let keys: BTreeSet<&Value> = old.keys().chain(new.keys()).collect();
+                for key in keys {
+                    let key_path = get_key_path(&path, key);
+                    match (old.get(key), new.get(key)) {
+                        (Some(v0), Some(v1)) => self.diff(key_path, v0, v1),
+                        (Some(v0), None) => self.changes.push((key_path, Change::Removed(v0))),
+                        (None, Some(v1)) => self.changes.push((key_path, Change::Added(v1))),
+                        (None, None) => unreachable!("The key comes from one of the dicts."),
+                    }
+                }
+            }
+            (Value::Set(old), Value::Set(new)) => {
+                for elem in old.union(new) {
+                    let elem_path = format!("{path}{{{}}}", format_inline(elem));
+                    match (old.contains(elem), new.contains(elem)) {
+                        (true, false) => self.changes.push((elem_path, Change::RemovedElement)),
+                        (false, true) => self.changes.push((elem_path, Change::AddedElement)),
+                        _ => {}
+                    }
+                }
+            }
+            (Value::List(old), Value::List(new)) => self.diff_list(&path, old, new),
+            _ => self.changes.push((path, Change::Changed(old, new))),
+        }
+    }
+
+    /// Diff two lists by aligning their longest common subsequence.
+    ///
+    /// This way, inserting an element at the start of a list reports one
+    /// addition, rather than a change for every element.
+    fn diff_list(&mut self, path: &str, old: &'a [Value], new: &'a [Value]) {
+        let mut edits = Vec::new();
+        align(old, new, 0, 0, &mut edits);
+
+        let mut removed = Vec::new();
+        let mut added = Vec::new();
+        for edit in edits {
+            match edit {
+                Edit::Keep => self.flush_list(path, old, new, &mut removed, &mut added),
+                Edit::Remove(i) => removed.push(i),
+                Edit::Add(j) => added.push(j),
+            }
+        }
+        self.flush_list(path, old, new, &mut removed, &mut added);
+    }
+
+    /// Report a run of removed and added list elements between common elements.
+    ///
+    /// Within a run, an element that takes the place of a removed one was most
+    /// likely modified, so we diff those pairs recursively. Paths of removed
+    /// elements refer to indices in the old list, other paths to the new list.
+    fn flush_list(
+        &mut self,
+        path: &str,
+        old: &'a [Value],
+        new: &'a [Value],
+        removed: &mut Vec<usize>,
+        added: &mut Vec<usize>,
+    ) {
+        let n_pairs = removed.len().min(added.len());
+        for (&i, &j) in removed.iter().zip(added.iter()) {
+            self.diff(format!("{path}[{j}]"), &old[i], &new[j]);
+        }
+        for &i in &removed[n_pairs..] {
+            let change = Change::Removed(&old[i]);
+            self.changes.push((format!("{path}[{i}]"), change));
+        }
+        for &j in &added[n_pairs..] {
+            let change = Change::Added(&new[j]);
+            self.changes.push((format!("{path}[{j}]"), change));
+        }
+        removed.clear();
+        added.clear();
+    }
+}
+
+/// One step in the alignment of two lists, indices refer to the full lists.
+enum Edit {
+    /// The next elements of both lists are equal.
+    Keep,
+    /// The element at this index of the old list is not in the new list.
+    Remove(usize),
+    /// The element at this index of the new list is not in the old list.
+    Add(usize),
+}
+
+/// The furthest reaching `x` per diagonal `k = x - y`, for [`middle_snake`].
+///
+/// Diagonals can be negative, so this offsets the index.
+struct Frontier {
+    offset: isize,
+    xs: Vec<isize>,
+}
+
+impl Frontier {
+    fn new(max_d: isize) -> Frontier {
+        Frontier {
+            offset: max_d + 1,
+            xs: vec![0; 2 * max_d as usize + 3],
+        }
+    }
+}
+
+impl std::ops::Index<isize> for Frontier {
+    type Output = isize;
+    fn index(&self, k: isize) -> &isize {
+        &self.xs[(k + self.offset) as usize]
+    }
+}
+
+impl std::ops::IndexMut<isize> for Frontier {
+    fn index_mut(&mut self, k: isize) -> &mut isize {
+        &mut self.xs[(k + self.offset) as usize]
+    }
+}
+
+/// Find a point `(x, y)` on a shortest edit path from `old` to `new`.
+///
+/// This is the middle snake of Myers' _An O(ND) Difference Algorithm and Its
+/// Variations_ (1986): we search for the shortest edit path from both ends at
+/// once, until the searches meet. The returned point splits the problem into
+/// two smaller ones. Both lists must be non-empty, with different first
+/// elements and different last elements, so the point is strictly inside.
+fn middle_snake(old: &[Value], new: &[Value]) -> (usize, usize) {
+    let n = old.len() as isize;
+    let m = new.len() as isize;
+    let delta = n - m;
+    let is_odd = delta % 2 != 0;
+    let max_d = (n + m + 1) / 2;
+    let mut fwd = Frontier::new(max_d);
+    let mut bwd = Frontier::new(max_d);
+
+    for d in 0..=max_d {
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && fwd[k - 1] < fwd[k + 1]) {
+                fwd[k + 1]
+            } else {
+                fwd[k - 1] + 1
+            };
+            let (x0, y0) = (x, x - k);
+            let mut y = y0;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            fwd[k] = x;
+            if is_odd && (delta - k).abs() < d && fwd[k] + bwd[delta - k] >= n {
+                return (x0 as usize, y0 as usize);
+            }
+        }
+        // The backward search counts `x` and `y` from the end of the lists.
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && bwd[k - 1] < bwd[k + 1]) {
+                bwd[k + 1]
+            } else {
+                bwd[k - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[(n - x - 1) as usize] == new[(m - y - 1) as usize] {
+                x += 1;
+                y += 1;
+            }
+            bwd[k] = x;
+            if !is_odd && (delta - k).abs() <= d && bwd[k] + fwd[delta - k] >= n {
+                return ((n - x) as usize, (m - y) as usize);
+            }
+        }
+    }
+
+    unreachable!("The searches meet after at most (n + m) / 2 steps.")
+}
+
+/// Append the edits that turn `old` into `new` along a longest common subsequence.
+///
+/// This takes time proportional to the length of the lists times the number
+/// of edits, and linear memory, so large lists that are mostly equal are
+/// cheap to diff. `i0` and `j0` are the offsets of `old` and `new` in the full
+/// lists.
+fn align(old: &[Value], new: &[Value], i0: usize, j0: usize, edits: &mut Vec<Edit>) {
+    let n_prefix = old.iter().zip(new).take_while(|(x, y)| x == y).count();
+    let n_suffix = old[n_prefix..]
+        .iter()
+        .rev()
+        .zip(new[n_prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let old_mid = &old[n_prefix..old.len() - n_suffix];
+    let new_mid = &new[n_prefix..new.len() - n_suffix];
+    let (i0, j0) = (i0 + n_prefix, j0 + n_prefix);
+
+    edits.extend((0..n_prefix).map(|_| Edit::Keep));
+    if old_mid.is_empty() {
+        edits.extend((0..new_mid.len()).map(|j| Edit::Add(j0 + j)));
+    } else if new_mid.is_empty() {
+        edits.extend((0..old_mid.len()).map(|i| Edit::Remove(i0 + i)));
+    } else {
+        let (x, y) = middle_snake(old_mid, new_mid);
+        align(&old_mid[..x], &new_mid[..y], i0, j0, edits);
+        align(&old_mid[x..], &new_mid[y..], i0 + x, j0 + y, edits);
+    }
+    edits.extend((0..n_suffix).map(|_| Edit::Keep));
+}
+
+impl<'a> Differ<'a> {
+    /// Diff the two values, and return the changes with their normalized path.
+    fn run(old: &'a Value, new: &'a Value) -> Vec<(String, Change<'a>)> {
+        let mut differ = Differ {
+            changes: Vec::new(),
+        };
+        differ.diff(String::new(), old, new);
+        differ
+            .changes
+            .into_iter()
+            .map(|(path, change)| {
+                let path = match path.chars().next() {
+                    None => ".".to_string(),
+                    Some('.') => path,
+                    Some(_) => format!(".{path}"),
+                };
+                (path, change)
+            })
+            .collect()
+    }
+}
+
+/// Diff two values and format the differences, one document per change.
+///
+/// Paths are written like in `jq`, with `.` for the root, `.key` for dict
+/// keys that are identifiers, `[key]` for other keys and for list indices,
+/// and `{element}` for set elements. The result is empty if the values are
+/// equal.
+pub fn diff_values<'a>(old: &'a Value, new: &'a Value) -> Vec<Doc<'a>> {
+    let changes = Differ::run(old, new);
+    let mut result = Vec::with_capacity(changes.len());
+    for (path, change) in changes {
+        let doc = match change {
+            Change::Added(v) => concat! {
+                Doc::string(format!("+ {path}")).with_markup(Markup::DiffAdd)
+                ": "
+                indent! { format_rcl(v) }
+            },
+            Change::Removed(v) => concat! {
+                Doc::string(format!("- {path}")).with_markup(Markup::DiffRemove)
+                ": "
+                indent! { format_rcl(v) }
+            },
+            Change::Changed(v0, v1) => concat! {
+                Doc::string(format!("~ {path}")).with_markup(Markup::DiffChange)
+                ": "
+                indent! { format_rcl(v0) " -> " format_rcl(v1) }
+            },
+            Change::AddedElement => Doc::string(format!("+ {path}")).with_markup(Markup::DiffAdd),
+            Change::RemovedElement => {
+                Doc::string(format!("- {path}")).with_markup(Markup::DiffRemove)
+            }
+        };
+        result.push(doc);
+    }
+    result
+}
+
+/// Diff two values and return the differences as a list of dicts.
+///
+/// This is the machine-readable counterpart to [`diff_values`], for
+/// `rcl diff --format=json`. Every change has a `change` of `"added"`,
+/// `"removed"`, or `"changed"`, and a `path` in the same form as for
+/// [`diff_values`]. Added and removed changes have a `value`, changed values
+/// have an `old` and `new`. For set elements, the element is part of the path,
+/// so there is no value.
+pub fn diff_values_report(old: &Value, new: &Value) -> Value {
+    let changes = Differ::run(old, new);
+    let mut result = Vec::with_capacity(changes.len());
+    for (path, change) in changes {
+        let mut fields: Vec<(&str, Value)> = vec![("path", path[..].into())];
+        match change {
+            Change::Added(v) => {
+                fields.push(("change", "added".into()));
+                fields.push(("value", v.clone()));
+            }
+            Change::Removed(v) => {
+                fields.push(("change", "removed".into()));
+                fields.push(("value", v.clone()));
+            }
+            Change::Changed(v0, v1) => {
+                fields.push(("change", "changed".into()));
+                fields.push(("old", v0.clone()));
+                fields.push(("new", v1.clone()));
+            }
+            Change::AddedElement => fields.push(("change", "added".into())),
+            Change::RemovedElement => fields.push(("change", "removed".into())),
+        }
+        let dict: BTreeMap<Value, Value> = fields
+            .into_iter()
+            .map(|(k, v)| (Value::from(k), v))
+            .collect();
+        result.push(Value::Dict(Rc::new(dict)));
+    }
+    Value::List(Rc::new(result))
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use super::{align, diff_values, diff_values_report, Edit};
+    use crate::pprint::{Config, Doc};
+    use crate::runtime::Value;
+    use crate::tracer::VoidTracer;
+    use crate::{loader::Loader, runtime, typecheck};
+
+    fn eval(loader: &mut Loader, input: &str) -> Value {
+        let id = loader.load_string(input.to_string());
+        let mut type_env = typecheck::prelude();
+        let mut value_env = runtime::prelude();
+        loader
+            .evaluate(&mut type_env, &mut value_env, id, &mut VoidTracer)
+            .unwrap()
+    }
+
+    fn diff(old: &str, new: &str) -> String {
+        let mut loader = Loader::new();
+        let v0 = eval(&mut loader, old);
+        let v1 = eval(&mut loader, new);
+        let docs = diff_values(&v0, &v1);
+        let doc = Doc::join(docs.into_iter(), Doc::HardBreak);
+        doc.println(&Config::default()).to_string_no_markup()
+    }
+
+    #[test]
+    fn diff_values_reports_nested_changes() {
+        let old = r#"{ name = "a", port = 80, tags = {"x"}, "a b": 1 }"#;
+        let new = r#"{ name = "a", port = 81, tags = {"y"}, extra = null }"#;
+        assert_eq!(
+            diff(old, new),
+            concat!(
+                "- .[\"a b\"]: 1\n",
+                "+ .extra: null\n",
+                "~ .port: 80 -> 81\n",
+                "- .tags{\"x\"}\n",
+                "+ .tags{\"y\"}\n",
+            ),
+        );
+    }
+
+    #[test]
+    fn diff_values_aligns_lists() {
+        assert_eq!(
+            diff("[1, 2, 3]", "[0, 1, 2, 4]"),
+            "+ .[0]: 0\n~ .[3]: 3 -> 4\n"
+        );
+        assert_eq!(diff("[[1, 2], 3]", "[[1], 3]"), "- .[0][1]: 2\n");
+        assert_eq!(diff("1", "\"1\""), "~ .: 1 -> \"1\"\n");
+    }
+
+    fn diff_ints(old: impl Iterator<Item = i128>, new: impl Iterator<Item = i128>) -> String {
+        let v0 = Value::List(Rc::new(old.map(Value::Int).collect()));
+        let v1 = Value::List(Rc::new(new.map(Value::Int).collect()));
+        let docs = diff_values(&v0, &v1);
+        let doc = Doc::join(docs.into_iter(), Doc::HardBreak);
+        doc.println(&Config::default()).to_string_no_markup()
+    }
+
+    #[test]
+    fn diff_values_aligns_large_lists() {
+        // The full table of the textbook algorithm would take 400 MB here.
+        let n = 10_000;
+        let new = (0..n)
+            .filter(|i| *i != 5000)
+            .map(|i| if i == 7000 { -3 } else { i })
+            .chain([-1]);
+        assert_eq!(
+            diff_ints(0..n, new),
+            concat!(
+                "- .[5000]: 5000\n",
+                "~ .[6999]: 7000 -> -3\n",
+                "+ .[9999]: -1\n",
+            ),
+        );
+        // Interleaved edits in the middle go through the recursive alignment.
+        let old = (0..1000).map(|i| i % 7);
+        let new = (0..1000).filter(|i| i % 150 != 0).map(|i| i % 7);
+        assert_eq!(diff_ints(old, new).lines().count(), 7);
+    }
+
+    #[test]
+    fn align_finds_longest_common_subsequence() {
+        // Compare against the textbook quadratic algorithm, on small lists of
+        // few distinct elements, so there are many common subsequences.
+        let mut seed: u64 = 1;
+        let mut next = |n: u64| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) % n
+        };
+        for _ in 0..2000 {
+            let old: Vec<Value> = (0..next(12)).map(|_| Value::Int(next(3) as i128)).collect();
+            let new: Vec<Value> = (0..next(12)).map(|_| Value::Int(next(3) as i128)).collect();
+
+            let mut lcs = vec![vec![0; new.len() + 1]; old.len() + 1];
+            for i in (0..old.len()).rev() {
+                for j in (0..new.len()).rev() {
+                    lcs[i][j] = if old[i] == new[j] {
+                        lcs[i + 1][j + 1] + 1
+                    } else {
+                        lcs[i + 1][j].max(lcs[i][j + 1])
+                    };
+                }
+            }
+
+            let mut edits = Vec::new();
+            align(&old, &new, 0, 0, &mut edits);
+            let (mut i, mut j, mut n_keep) = (0, 0, 0);
+            for edit in edits {
+                match edit {
+                    Edit::Keep => {
+                        assert_eq!(old[i], new[j]);
+                        i += 1;
+                        j += 1;
+                        n_keep += 1;
+                    }
+                    Edit::Remove(k) => {
+                        assert_eq!(k, i);
+                        i += 1;
+                    }
+                    Edit::Add(k) => {
+                        assert_eq!(k, j);
+                        j += 1;
+                    }
+                }
+            }
+            assert_eq!((i, j), (old.len(), new.len()));
+            assert_eq!(n_keep, lcs[0][0], "Not optimal for {old:?} -> {new:?}.");
+        }
+    }
+
+    #[test]
+    fn diff_values_report_lists_changes() {
+        let mut loader = Loader::new();
+        let v0 = eval(&mut loader, r#"{ a = 1, b = [1], c = {"x"} }"#);
+        let v1 = eval(&mut loader, r#"{ a = 2, b = [], c = {"x", "y"} }"#);
+        let report = diff_values_report(&v0, &v1);
+        let doc = crate::fmt_rcl::format_rcl(&report);
+        assert_eq!(
+            doc.println(&Config::default()).to_string_no_markup(),
+            concat!(
+                "[\n",
+                "  { change = \"changed\", new = 2, old = 1, path = \".a\" },\n",
+                "  { change = \"removed\", path = \".b[0]\", value = 1 },\n",
+                "  { change = \"added\", path = \".c{\\\"y\\\"}\" },\n",
+                "]\n",
+            ),
+        );
+    }
+
+    #[test]
+    fn diff_values_is_empty_for_equal_values() {
+        let v = Value::List(Rc::new(vec![Value::Int(1)]));
+        assert!(diff_values(&v, &v).is_empty());
+    }
+}
@@ -9,17 +9,180 @@
 
 use crate::cli::OutputFormat;
 use crate::error::Result;
+use crate::fmt_ini::DEFAULT_KEY_SEPARATOR;
 use crate::pprint::Doc;
 use crate::runtime::Value;
 use crate::source::Span;
 
-pub fn format_value(format: OutputFormat, value_span: Span, value: &Value) -> Result<Doc> {
+/// Settings for the output formats that have any.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FormatOptions {
+    /// For `ini` and `properties`, the separator to join nested keys with.
+    pub key_separator: String,
+
+    /// For `csv` and `tsv`, whether all records must have the same keys.
+    pub require_same_keys: bool,
+
+    /// For `yaml-stream`, the name for the `# Source:` comment of every document.
+    ///
+    /// When this is `None`, the documents have no comments.
+    pub yaml_sources: Option<Vec<String>>,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            key_separator: DEFAULT_KEY_SEPARATOR.to_string(),
+            require_same_keys: false,
+            yaml_sources: None,
+        }
+    }
+}
+
+/// Format the value in the given output format.
+pub fn format_value<'a>(
+    format: OutputFormat,
+    opts: &FormatOptions,
+    value_span: Span,
+    value: &'a Value,
+) -> Result<Doc<'a>> {
+    let key_separator = &opts.key_separator[..];
+    let require_same_keys = opts.require_same_keys;
     let result = match format {
+        OutputFormat::Csv => crate::fmt_csv::format_csv(value_span, require_same_keys, value)?,
+        OutputFormat::Hcl => crate::fmt_hcl::format_hcl(value_span, value)?,
+        OutputFormat::Ini => crate::fmt_ini::format_ini(value_span, key_separator, value)?,
         OutputFormat::Json => crate::fmt_json::format_json(value_span, value)?,
+        OutputFormat::JsonCanonical => crate::fmt_json::format_json_canonical(value_span, value)?,
+        OutputFormat::JsonCompact => crate::fmt_json::format_json_compact(value_span, value)?,
+        OutputFormat::JsonLines => crate::fmt_json_lines::format_json_lines(value_span, value)?,
+        OutputFormat::Nix => crate::fmt_nix::format_nix(value_span, value)?,
+        OutputFormat::Plist => crate::fmt_plist::format_plist(value_span, value)?,
+        OutputFormat::Properties => {
+            crate::fmt_ini::format_properties(value_span, key_separator, value)?
+        }
         OutputFormat::Raw => crate::fmt_raw::format_raw(value_span, value)?,
         OutputFormat::Rcl => crate::fmt_rcl::format_rcl(value),
+        OutputFormat::Textproto => crate::fmt_textproto::format_textproto(value_span, value)?,
         OutputFormat::Toml => crate::fmt_toml::format_toml(value_span, value)?,
-        OutputFormat::YamlStream => crate::fmt_yaml_stream::format_yaml_stream(value_span, value)?,
+        OutputFormat::Tsv => crate::fmt_csv::format_tsv(value_span, require_same_keys, value)?,
+        OutputFormat::YamlStream => {
+            let sources = opts.yaml_sources.as_deref();
+            crate::fmt_yaml_stream::format_yaml_stream(value_span, sources, value)?
+        }
     };
     Ok(result)
 }
+
+#[cfg(test)]
+mod test {
+    use super::{format_value, FormatOptions};
+    use crate::cli::OutputFormat;
+    use crate::loader::Loader;
+    use crate::pprint::Config;
+    use crate::runtime;
+    use crate::tracer::VoidTracer;
+    use crate::typecheck;
+
+    /// All output formats.
+    ///
+    /// The match has no wildcard, so adding a format without adding it here
+    /// fails to compile, and the new format gets covered by the tests below.
+    fn all_formats() -> Vec<OutputFormat> {
+        use OutputFormat::*;
+        let formats = [
+            Csv,
+            Hcl,
+            Ini,
+            Json,
+            JsonCanonical,
+            JsonCompact,
+            JsonLines,
+            Nix,
+            Plist,
+            Properties,
+            Raw,
+            Rcl,
+            Textproto,
+            Toml,
+            Tsv,
+            YamlStream,
+        ];
+        for format in formats {
+            match format {
+                Csv | Hcl | Ini | Json | JsonCanonical | JsonCompact | JsonLines | Nix | Plist
+                | Properties | Raw | Rcl | Textproto | Toml | Tsv | YamlStream => {}
+            }
+        }
+        formats.to_vec()
+    }
+
+    /// Evaluate the source, and format the result, or the error message.
+    fn eval_format(loader: &mut Loader, format: OutputFormat, src: &str) -> Result<String, String> {
+        let doc = loader.load_string(src.to_string());
+        let mut type_env = typecheck::prelude();
+        let mut value_env = runtime::prelude();
+        let value = loader
+            .evaluate(&mut type_env, &mut value_env, doc, &mut VoidTracer)
+            .expect("Test inputs should evaluate.");
+        let span = loader.get_span(doc);
+        match format_value(format, &FormatOptions::default(), span, &value) {
+            Ok(out) => Ok(out.println(&Config::default()).to_string_no_markup()),
+            Err(err) => Err(err
+                .message
+                .println(&Config::default())
+                .to_string_no_markup()),
+        }
+    }
+
+    #[test]
+    fn format_value_does_not_depend_on_construction_order() {
+        // Pairs of documents that evaluate to the same value, but that build
+        // their dicts and sets in a different order. Between them, they are
+        // valid for every format.
+        let inputs = [
+            (
+                r#"{ section = { z = 1, a = "x", flag = true }, top = { k = "v" } }"#,
+                r#"{ top = { k = "v" }, section = { for k in ["flag", "a", "z"]: k: { flag = true, a = "x", z = 1 }[k] } }"#,
+            ),
+            (
+                r#"[{ b = 1, a = "x", c = true }, { a = "y", b = 2, c = false }]"#,
+                r#"[{ c = true, a = "x", b = 1 }, { c = false, b = 2, a = "y" }]"#,
+            ),
+            (
+                r#"{"b", "a", "c"}"#,
+                r#"{ for s in ["c", "a", "b", "a"]: s }"#,
+            ),
+        ];
+        let mut loader = Loader::new();
+        for format in all_formats() {
+            let mut any_ok = false;
+            for (src1, src2) in inputs {
+                let out1 = eval_format(&mut loader, format, src1);
+                // Formatting the same value again must produce the same bytes.
+                assert_eq!(out1, eval_format(&mut loader, format, src1));
+                assert_eq!(
+                    out1,
+                    eval_format(&mut loader, format, src2),
+                    "Output for {format:?} depends on construction order.",
+                );
+                any_ok = any_ok || out1.is_ok();
+            }
+            assert!(any_ok, "No test input is valid for {format:?}.");
+        }
+    }
+
+    #[test]
+    fn deterministic_loader_normalizes_line_endings() {
+        let src_lf = "// Comment.\nlet s = \"\"\"\n  a\n  b\n  \"\"\";\n[s]\n";
+        let src_crlf = src_lf.replace('\n', "\r\n");
+        let mut loader = Loader::new();
+        let expected = eval_format(&mut loader, OutputFormat::Json, src_lf);
+        assert_eq!(expected, Ok("[\"a\\nb\\n\"]\n".to_string()));
+        loader.set_deterministic(true);
+        assert_eq!(
+            eval_format(&mut loader, OutputFormat::Json, &src_crlf),
+            expected
+        );
+    }
+}
@@ -0,0 +1,1169 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Functions for implementing parts of `rcl format`.
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::ast::{Expr, Seq, Stmt, Type, Yield};
+use crate::cli::Target;
+use crate::error::{Error, ErrorKind, IntoError, PathElement, Result};
+use crate::fmt_cst::TrailingComma;
+use crate::fmt_rcl::format_rcl;
+use crate::loader::Loader;
+use crate::pprint::{concat, AmbiguousWidth, Config, Doc, IndentStyle, LineEnding};
+use crate::runtime::Value;
+use crate::source::{DocId, Span};
+use crate::tracer::VoidTracer;
+use crate::{editorconfig, fmt_cst, lexer, parallel, parser, runtime, typecheck};
+
+/// The name of the formatter configuration file, see [`find_config`].
+pub const CONFIG_FILE_NAME: &str = "rcl-format.rcl";
+
+/// The style to format documents in.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct FormatStyle {
+    /// Settings for the pretty-printer: width, indentation, and line endings.
+    pub print: Config,
+
+    /// When to add a comma after the last element of a collection.
+    pub trailing_comma: TrailingComma,
+}
+
+/// Settings that override parts of a [`FormatStyle`].
+///
+/// These come from configuration files, where every setting is optional.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct StyleOverrides {
+    pub width: Option<u32>,
+    pub indent_width: Option<u32>,
+    pub indent_style: Option<IndentStyle>,
+    pub tab_width: Option<u32>,
+    pub line_ending: Option<LineEnding>,
+    pub ambiguous_width: Option<AmbiguousWidth>,
+    pub trailing_comma: Option<TrailingComma>,
+}
+
+impl StyleOverrides {
+    /// Map the `.editorconfig` properties that apply to a file onto a style.
+    ///
+    /// Properties and values that we do not support are ignored, as the
+    /// EditorConfig specification prescribes.
+    pub fn from_editorconfig(properties: &BTreeMap<String, String>) -> StyleOverrides {
+        let get = |key: &str| properties.get(key).map(|v| v.to_ascii_lowercase());
+        let get_int = |key: &str| {
+            get(key)
+                .and_then(|v| v.parse::<u32>().ok())
+                .filter(|n| *n > 0)
+        };
+        StyleOverrides {
+            width: get_int("max_line_length"),
+            indent_width: match get("indent_size").as_deref() {
+                Some("tab") => get_int("tab_width"),
+                _ => get_int("indent_size"),
+            },
+            indent_style: match get("indent_style").as_deref() {
+                Some("space") => Some(IndentStyle::Spaces),
+                Some("tab") => Some(IndentStyle::Tabs),
+                _ => None,
+            },
+            // When `tab_width` is not set, it defaults to `indent_size`.
+            tab_width: get_int("tab_width").or_else(|| get_int("indent_size")),
+            line_ending: match get("end_of_line").as_deref() {
+                Some("lf") => Some(LineEnding::Lf),
+                Some("crlf") => Some(LineEnding::CrLf),
+                _ => None,
+            },
+            ambiguous_width: None,
+            trailing_comma: None,
+        }
+    }
+
+    /// Replace the settings in `style` that these overrides set.
+    pub fn apply(&self, style: &mut FormatStyle) {
+        if let Some(width) = self.width {
+            style.print.width = width;
+        }
+        if let Some(indent_width) = self.indent_width {
+            style.print.indent_width = indent_width;
+        }
+        if let Some(indent_style) = self.indent_style {
+            style.print.indent_style = indent_style;
+        }
+        if let Some(tab_width) = self.tab_width {
+            style.print.tab_width = tab_width;
+        }
+        if let Some(line_ending) = self.line_ending {
+            style.print.line_ending = line_ending;
+        }
+        if let Some(ambiguous_width) = self.ambiguous_width {
+            style.print.ambiguous_width = ambiguous_width;
+        }
+        if let Some(trailing_comma) = self.trailing_comma {
+            style.trailing_comma = trailing_comma;
+        }
+    }
+}
+
+/// Determines the style for documents, from the configuration files that apply.
+///
+/// Documents in the same directory tree share configuration files, this caches
+/// them so we load every file only once.
+#[derive(Default)]
+pub struct StyleResolver {
+    configs: BTreeMap<PathBuf, StyleOverrides>,
+    editorconfig: editorconfig::Resolver,
+}
+
+impl StyleResolver {
+    /// Return the style for the document at the absolute `path`.
+    ///
+    /// Settings in [`CONFIG_FILE_NAME`] take precedence over `.editorconfig`.
+    pub fn get_style(&mut self, loader: &mut Loader, path: &Path) -> Result<FormatStyle> {
+        let mut style = FormatStyle::default();
+        let properties = self.editorconfig.properties(path)?;
+        StyleOverrides::from_editorconfig(&properties).apply(&mut style);
+
+        if let Some(config_path) = path.parent().and_then(find_config) {
+            if !self.configs.contains_key(&config_path) {
+                let overrides = load_config(loader, &config_path)?;
+                self.configs.insert(config_path.clone(), overrides);
+            }
+            self.configs[&config_path].apply(&mut style);
+        }
+
+        Ok(style)
+    }
+}
+
+/// Find the configuration file that applies to a document in `dir`.
+///
+/// This is the first [`CONFIG_FILE_NAME`] in `dir` or any of its ancestors.
+pub fn find_config(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|ancestor| ancestor.join(CONFIG_FILE_NAME))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Evaluate a formatter configuration file and return the settings it specifies.
+///
+/// The file should evaluate to a dict where every field is optional.
+pub fn load_config(loader: &mut Loader, path: &Path) -> Result<StyleOverrides> {
+    let target = Target::File(path.to_string_lossy().into_owned());
+    let doc = loader.load_cli_target(&target)?;
+    let mut type_env = typecheck::prelude();
+    let mut value_env = runtime::prelude();
+    let value = loader.evaluate(&mut type_env, &mut value_env, doc, &mut VoidTracer)?;
+    let doc_span = loader.get_span(doc);
+    parse_config(doc_span, &value).map_err(|mut err| {
+        err.origin = Some(err.origin.unwrap_or(doc_span));
+        err
+    })
+}
+
+fn parse_config(doc_span: Span, value: &Value) -> Result<StyleOverrides> {
+    let fields = match value {
+        Value::Dict(fields) => fields,
+        _ => {
+            return doc_span
+                .error("The formatter configuration must be a dict.")
+                .err()
+        }
+    };
+    let mut overrides = StyleOverrides::default();
+    for (k, v) in fields.iter() {
+        let make_error = |message: Doc<'static>| {
+            Error::new(message).with_path_element(PathElement::Key(k.clone()))
+        };
+        let positive_int = |v: &Value| match v {
+            Value::Int(n) if *n > 0 && *n <= u32::MAX as i128 => Some(*n as u32),
+            _ => None,
+        };
+        let key = match k {
+            Value::String(key) => key.as_ref(),
+            _ => return make_error("Configuration keys must be strings.".into()).err(),
+        };
+        let invalid_value = |expected: &'static str| {
+            make_error(concat! {
+                "Invalid value for '" Doc::highlight(key).into_owned() "': "
+                format_rcl(v).into_owned() "."
+            })
+            .with_help(expected)
+            .err()
+        };
+        match key {
+            "width" => match positive_int(v) {
+                Some(width) => overrides.width = Some(width),
+                None => return invalid_value("The width must be a positive integer."),
+            },
+            "indent" => match positive_int(v) {
+                Some(indent) => overrides.indent_width = Some(indent),
+                None => return invalid_value("The indent must be a positive integer."),
+            },
+            "indent_style" => match v {
+                Value::String(s) if s.as_ref() == "spaces" => {
+                    overrides.indent_style = Some(IndentStyle::Spaces)
+                }
+                Value::String(s) if s.as_ref() == "tabs" => {
+                    overrides.indent_style = Some(IndentStyle::Tabs)
+                }
+                _ => return invalid_value("Expected \"spaces\" or \"tabs\"."),
+            },
+            "tab_width" => match positive_int(v) {
+                Some(tab_width) => overrides.tab_width = Some(tab_width),
+                None => return invalid_value("The tab width must be a positive integer."),
+            },
+            "trailing_comma" => match v {
+                Value::String(s) if s.as_ref() == "multiline" => {
+                    overrides.trailing_comma = Some(TrailingComma::Multiline)
+                }
+                Value::String(s) if s.as_ref() == "never" => {
+                    overrides.trailing_comma = Some(TrailingComma::Never)
+                }
+                _ => return invalid_value("Expected \"multiline\" or \"never\"."),
+            },
+            "line_ending" => match v {
+                Value::String(s) if s.as_ref() == "lf" => {
+                    overrides.line_ending = Some(LineEnding::Lf)
+                }
+                Value::String(s) if s.as_ref() == "crlf" => {
+                    overrides.line_ending = Some(LineEnding::CrLf)
+                }
+                _ => return invalid_value("Expected \"lf\" or \"crlf\"."),
+            },
+            "ambiguous_width" => match v {
+                Value::String(s) if s.as_ref() == "narrow" => {
+                    overrides.ambiguous_width = Some(AmbiguousWidth::Narrow)
+                }
+                Value::String(s) if s.as_ref() == "wide" => {
+                    overrides.ambiguous_width = Some(AmbiguousWidth::Wide)
+                }
+                _ => return invalid_value("Expected \"narrow\" or \"wide\"."),
+            },
+            unknown => {
+                return make_error(concat! {
+                    "Unknown formatter configuration field: '"
+                    Doc::highlight(unknown).into_owned()
+                    "'."
+                })
+                .with_help(
+                    "The supported fields are width, indent, indent_style, tab_width, \
+                    trailing_comma, line_ending, and ambiguous_width.",
+                )
+                .err()
+            }
+        }
+    }
+    Ok(overrides)
+}
+
+/// Replace `\r\n` line endings with `\n`.
+///
+/// The formatter works with `\n` internally, and emits the line ending that
+/// the style asks for. Normalizing the input ensures that formatting a file
+/// with `\r\n` line endings does not leave stray `\r` in comments.
+pub fn normalize_line_endings(input: &str) -> Cow<str> {
+    if input.contains("\r\n") {
+        Cow::Owned(input.replace("\r\n", "\n"))
+    } else {
+        Cow::Borrowed(input)
+    }
+}
+
+/// Format a document, or return `None` if it fails to lex or parse.
+///
+/// This does not return the error, because errors are not `Send`, and we want
+/// to be able to call this from a worker thread. To report the error, parse the
+/// document again through the loader on the main thread.
+pub fn format_doc(id: DocId, input: &str, style: &FormatStyle) -> Option<String> {
+    let input = normalize_line_endings(input);
+    let tokens = lexer::lex(id, &input).ok()?;
+    let (_doc_span, cst) = parser::parse(id, &input, &tokens).ok()?;
+    let doc = fmt_cst::format_expr_with(&input, &cst, style.trailing_comma);
+    Some(doc.println(&style.print).to_string_no_markup())
+}
+
+/// The outcome of formatting multiple documents.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct FormatSummary {
+    /// Documents that were already formatted correctly.
+    pub n_unchanged: u32,
+    /// Documents that were reformatted, or would be with `--check`.
+    pub n_changed: u32,
+    /// Documents that could not be formatted because they failed to parse.
+    pub n_failed: u32,
+}
+
+impl FormatSummary {
+    /// Describe the outcome in one line, for `--in-place` or `--check`.
+    pub fn describe(&self, is_check: bool) -> String {
+        let n_total = self.n_unchanged + self.n_changed + self.n_failed;
+        let files = if n_total == 1 { "file" } else { "files" };
+        if is_check {
+            format!(
+                "Checked {} {}: {} formatted correctly, {} would be reformatted, {} failed to parse.",
+                n_total, files, self.n_unchanged, self.n_changed, self.n_failed,
+            )
+        } else {
+            format!(
+                "Formatted {} {}: {} unchanged, {} reformatted, {} failed to parse.",
+                n_total, files, self.n_unchanged, self.n_changed, self.n_failed,
+            )
+        }
+    }
+}
+
+/// Return the 1-based number of the first line where `a` and `b` differ.
+pub fn first_difference_line(a: &str, b: &str) -> usize {
+    let n_common = a.bytes().zip(b.bytes()).take_while(|(x, y)| x == y).count();
+    1 + a.as_bytes()[..n_common]
+        .iter()
+        .filter(|b| **b == b'\n')
+        .count()
+}
+
+/// Format multiple documents, spread out over at most `jobs` threads.
+///
+/// Every document is formatted in its own style. The results are in the same
+/// order as the inputs, regardless of the order in which the threads finish.
+pub fn format_docs(jobs: usize, docs: &[(DocId, &str, &FormatStyle)]) -> Vec<Option<String>> {
+    parallel::map(jobs, docs, |(id, input, style)| {
+        format_doc(*id, input, style)
+    })
+}
+
+/// Return whether two ASTs are equal, ignoring their spans.
+///
+/// The documents differ in layout, so all spans differ, but everything else
+/// must be the same for the formatter to preserve the meaning.
+fn ast_eq(a: &Expr, b: &Expr) -> bool {
+    match (a, b) {
+        (
+            Expr::Stmt {
+                stmt: s1, body: b1, ..
+            },
+            Expr::Stmt {
+                stmt: s2, body: b2, ..
+            },
+        ) => stmt_eq(s1, s2) && ast_eq(b1, b2),
+        (Expr::Import { path: p1, .. }, Expr::Import { path: p2, .. }) => ast_eq(p1, p2),
+        (Expr::BraceLit { elements: e1, .. }, Expr::BraceLit { elements: e2, .. })
+        | (Expr::BracketLit { elements: e1, .. }, Expr::BracketLit { elements: e2, .. })
+        | (Expr::SetLit { elements: e1, .. }, Expr::SetLit { elements: e2, .. })
+        | (Expr::DictLit { elements: e1, .. }, Expr::DictLit { elements: e2, .. }) => {
+            all_eq(e1, e2, seq_eq)
+        }
+        (Expr::NullLit, Expr::NullLit) => true,
+        (Expr::BoolLit(x1), Expr::BoolLit(x2)) => x1 == x2,
+        (Expr::StringLit(x1), Expr::StringLit(x2)) => x1 == x2,
+        (Expr::IntegerLit(x1), Expr::IntegerLit(x2)) => x1 == x2,
+        (Expr::NumberLit(x1), Expr::NumberLit(x2)) => x1 == x2,
+        (Expr::Format(f1), Expr::Format(f2)) => all_eq(f1, f2, |x1, x2| ast_eq(&x1.body, &x2.body)),
+        (
+            Expr::IfThenElse {
+                condition: c1,
+                body_then: t1,
+                body_else: e1,
+                ..
+            },
+            Expr::IfThenElse {
+                condition: c2,
+                body_then: t2,
+                body_else: e2,
+                ..
+            },
+        ) => ast_eq(c1, c2) && ast_eq(t1, t2) && ast_eq(e1, e2),
+        (Expr::Var { ident: i1, .. }, Expr::Var { ident: i2, .. }) => i1 == i2,
+        (
+            Expr::Field {
+                inner: x1,
+                field: f1,
+                ..
+            },
+            Expr::Field {
+                inner: x2,
+                field: f2,
+                ..
+            },
+        ) => f1 == f2 && ast_eq(x1, x2),
+        (
+            Expr::Function {
+                args: a1, body: b1, ..
+            },
+            Expr::Function {
+                args: a2, body: b2, ..
+            },
+        ) => all_eq(a1, a2, |(_, x1), (_, x2)| x1 == x2) && ast_eq(b1, b2),
+        (
+            Expr::Call {
+                function: f1,
+                args: a1,
+                ..
+            },
+            Expr::Call {
+                function: f2,
+                args: a2,
+                ..
+            },
+        ) => ast_eq(f1, f2) && all_eq(a1, a2, |x1, x2| ast_eq(&x1.value, &x2.value)),
+        (
+            Expr::Index {
+                collection: c1,
+                index: i1,
+                ..
+            },
+            Expr::Index {
+                collection: c2,
+                index: i2,
+                ..
+            },
+        ) => ast_eq(c1, c2) && ast_eq(i1, i2),
+        (
+            Expr::UnOp {
+                op: o1, body: b1, ..
+            },
+            Expr::UnOp {
+                op: o2, body: b2, ..
+            },
+        ) => o1 == o2 && ast_eq(b1, b2),
+        (
+            Expr::BinOp {
+                op: o1,
+                lhs: l1,
+                rhs: r1,
+                ..
+            },
+            Expr::BinOp {
+                op: o2,
+                lhs: l2,
+                rhs: r2,
+                ..
+            },
+        ) => o1 == o2 && ast_eq(l1, l2) && ast_eq(r1, r2),
+        // These nodes only exist after typechecking, and their types carry
+        // sources, so we can only compare them exactly.
+        (
+            Expr::CheckType {
+                type_: t1,
+                body: b1,
+                ..
+            },
+            Expr::CheckType {
+                type_: t2,
+                body: b2,
+                ..
+            },
+        ) => t1 == t2 && ast_eq(b1, b2),
+        (
+            Expr::TypedFunction {
+                type_: t1,
+                body: b1,
+                ..
+            },
+            Expr::TypedFunction {
+                type_: t2,
+                body: b2,
+                ..
+            },
+        ) => t1 == t2 && ast_eq(b1, b2),
+        _ => false,
+    }
+}
+
+/// Return whether the slices have equal length and pairwise equal elements.
+fn all_eq<T>(xs: &[T], ys: &[T], eq: impl Fn(&T, &T) -> bool) -> bool {
+    xs.len() == ys.len() && xs.iter().zip(ys).all(|(x, y)| eq(x, y))
+}
+
+/// Like [`ast_eq`], but for statements.
+fn stmt_eq(a: &Stmt, b: &Stmt) -> bool {
+    match (a, b) {
+        (
+            Stmt::Let {
+                ident: i1,
+                type_: t1,
+                value: v1,
+                ..
+            },
+            Stmt::Let {
+                ident: i2,
+                type_: t2,
+                value: v2,
+                ..
+            },
+        ) => {
+            let types_eq = match (t1, t2) {
+                (None, None) => true,
+                (Some(t1), Some(t2)) => type_eq(t1, t2),
+                _ => false,
+            };
+            i1 == i2 && types_eq && ast_eq(v1, v2)
+        }
+        (
+            Stmt::Assert {
+                condition: c1,
+                message: m1,
+                ..
+            },
+            Stmt::Assert {
+                condition: c2,
+                message: m2,
+                ..
+            },
+        ) => ast_eq(c1, c2) && ast_eq(m1, m2),
+        (Stmt::Trace { message: m1, .. }, Stmt::Trace { message: m2, .. }) => ast_eq(m1, m2),
+        _ => false,
+    }
+}
+
+/// Like [`ast_eq`], but for elements of collection literals.
+fn seq_eq(a: &Seq, b: &Seq) -> bool {
+    match (a, b) {
+        (Seq::Yield(Yield::Elem { value: v1, .. }), Seq::Yield(Yield::Elem { value: v2, .. })) => {
+            ast_eq(v1, v2)
+        }
+        (
+            Seq::Yield(Yield::Assoc {
+                key: k1, value: v1, ..
+            }),
+            Seq::Yield(Yield::Assoc {
+                key: k2, value: v2, ..
+            }),
+        ) => ast_eq(k1, k2) && ast_eq(v1, v2),
+        (Seq::Stmt { stmt: s1, body: b1 }, Seq::Stmt { stmt: s2, body: b2 }) => {
+            stmt_eq(s1, s2) && seq_eq(b1, b2)
+        }
+        (
+            Seq::For {
+                idents: i1,
+                collection: c1,
+                body: b1,
+                ..
+            },
+            Seq::For {
+                idents: i2,
+                collection: c2,
+                body: b2,
+                ..
+            },
+        ) => i1 == i2 && ast_eq(c1, c2) && seq_eq(b1, b2),
+        (
+            Seq::If {
+                condition: c1,
+                body: b1,
+                ..
+            },
+            Seq::If {
+                condition: c2,
+                body: b2,
+                ..
+            },
+        ) => ast_eq(c1, c2) && seq_eq(b1, b2),
+        _ => false,
+    }
+}
+
+/// Like [`ast_eq`], but for type annotations.
+fn type_eq(a: &Type, b: &Type) -> bool {
+    match (a, b) {
+        (Type::Term { name: n1, .. }, Type::Term { name: n2, .. }) => n1 == n2,
+        (
+            Type::Apply {
+                name: n1, args: a1, ..
+            },
+            Type::Apply {
+                name: n2, args: a2, ..
+            },
+        ) => n1 == n2 && all_eq(a1, a2, type_eq),
+        (
+            Type::Function {
+                args: a1,
+                result: r1,
+                ..
+            },
+            Type::Function {
+                args: a2,
+                result: r2,
+                ..
+            },
+        ) => all_eq(a1, a2, type_eq) && type_eq(r1, r2),
+        _ => false,
+    }
+}
+
+/// Check the formatter on a document, for `rcl self-test-fmt`.
+///
+/// This confirms that the formatted document parses to the same AST as the
+/// input, and that formatting the formatted document does not change it.
+pub fn self_test(loader: &mut Loader, id: DocId, style: &FormatStyle) -> Result<()> {
+    let name = loader.get_doc(id).name.to_string();
+    let ast = loader.get_unchecked_ast(id)?;
+    let formatted = format_doc(id, loader.get_doc(id).data, style)
+        .expect("We already parsed this document above.");
+
+    // If the formatted document fails to parse, we report the parse error in
+    // the formatted document, which is the most useful place to point at.
+    let id_formatted = loader.load_named_string(&format!("{name} (formatted)"), formatted);
+    let ast_formatted = loader.get_unchecked_ast(id_formatted)?;
+
+    if !ast_eq(&ast, &ast_formatted) {
+        return Error::new(concat! {
+            "Formatting '" Doc::highlight(&name).into_owned() "' changes its meaning."
+        })
+        .with_kind(ErrorKind::Check)
+        .err();
+    }
+
+    let formatted = loader.get_doc(id_formatted).data;
+    let formatted_again =
+        format_doc(id_formatted, formatted, style).expect("We already parsed this document above.");
+    if formatted != formatted_again {
+        let line = formatted
+            .lines()
+            .zip(formatted_again.lines())
+            .position(|(a, b)| a != b)
+            .unwrap_or(
+                formatted
+                    .lines()
+                    .count()
+                    .min(formatted_again.lines().count()),
+            );
+        let line_number = (line + 1).to_string();
+        return Error::new(concat! {
+            "Formatting '" Doc::highlight(&name).into_owned() "' is not idempotent, "
+            "formatting a second time changes line " line_number "."
+        })
+        .with_kind(ErrorKind::Check)
+        .err();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        ast_eq, first_difference_line, format_doc, format_docs, parse_config, self_test,
+        FormatStyle, FormatSummary, StyleOverrides,
+    };
+    use crate::fmt_cst::TrailingComma;
+    use crate::loader::Loader;
+    use crate::pprint::{AmbiguousWidth, Config, IndentStyle, LineEnding};
+    use crate::source::DocId;
+    use crate::tracer::VoidTracer;
+    use std::collections::BTreeMap;
+
+    fn ast_eq_str(a: &str, b: &str) -> bool {
+        let mut loader = Loader::new();
+        let a = loader.load_string(a.to_string());
+        let b = loader.load_string(b.to_string());
+        ast_eq(
+            &loader.get_unchecked_ast(a).unwrap(),
+            &loader.get_unchecked_ast(b).unwrap(),
+        )
+    }
+
+    #[test]
+    fn ast_eq_ignores_spans() {
+        assert!(ast_eq_str(
+            "let x=\"d0[1..2]\";[x,x]",
+            "let x = \"d0[1..2]\";\n[x, x]\n"
+        ));
+        assert!(ast_eq_str(
+            "let f=(a,b)=>{a:b};f(1,2)",
+            "let f = (a, b) => { a: b };\nf(1, 2)\n"
+        ));
+        assert!(ast_eq_str(
+            "[for x in xs: if x: x]",
+            "[\n  for x in xs:\n  if x:\n  x,\n]"
+        ));
+    }
+
+    #[test]
+    fn ast_eq_compares_string_contents() {
+        // Strings that look like spans used to be erased by comparing the
+        // `Debug` output, these must still be different.
+        assert!(!ast_eq_str("\"d0[1..2]\"", "\"d0[5..9]\""));
+        assert!(!ast_eq_str("[\"a\", \"b\"]", "[\"a\", \"c\"]"));
+        assert!(!ast_eq_str("1 + 2", "1 - 2"));
+        assert!(!ast_eq_str("[1, 2]", "[1, 2, 3]"));
+        assert!(!ast_eq_str("let x: Int = 1; x", "let x: String = 1; x"));
+        assert!(!ast_eq_str("1.5", "1.50001"));
+    }
+
+    /// A small deterministic PRNG (SplitMix64), so the property test below is reproducible.
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: u64) -> u64 {
+            self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+            let mut z = self.0;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            (z ^ (z >> 31)) % n
+        }
+
+        fn pick<'a>(&mut self, options: &[&'a str]) -> &'a str {
+            options[self.below(options.len() as u64) as usize]
+        }
+    }
+
+    /// Optional whitespace, possibly with a line break.
+    fn ws(rng: &mut Rng, out: &mut String) {
+        out.push_str(rng.pick(&["", "", " ", "  ", "\n", "\n  "]));
+    }
+
+    /// Mandatory whitespace between tokens that would otherwise merge.
+    fn ws1(rng: &mut Rng, out: &mut String) {
+        out.push_str(rng.pick(&[" ", " ", "\n", "\n    "]));
+    }
+
+    /// Whitespace before a collection element, where comments and blank lines are allowed.
+    fn ws_elem(rng: &mut Rng, out: &mut String) {
+        out.push_str(rng.pick(&["", " ", "\n", "\n\n", "\n// c\n", " // c\n"]));
+    }
+
+    /// Append a random, syntactically valid expression to `out`.
+    fn gen_expr(rng: &mut Rng, depth: u32, out: &mut String) {
+        let n_choices = if depth == 0 { 3 } else { 14 };
+        match rng.below(n_choices) {
+            0 => out.push_str(rng.pick(&[
+                "0", "42", "-7", "0xff", "0b101", "1.5", "-0.25", "2e3", "1.0e-7",
+            ])),
+            1 => out.push_str(rng.pick(&[
+                "null",
+                "true",
+                "false",
+                "\"\"",
+                "\"a b\"",
+                "\"d0[1..2]\"",
+                "\"\\n\\u{1f600}\"",
+            ])),
+            2 => out.push_str(rng.pick(&["x", "y", "my-var", "std"])),
+            3 | 4 => {
+                let (open, close) = if rng.below(2) == 0 {
+                    ("[", "]")
+                } else {
+                    ("{", "}")
+                };
+                out.push_str(open);
+                let n = rng.below(4);
+                for i in 0..n {
+                    ws_elem(rng, out);
+                    gen_seq(rng, depth - 1, open == "{", out);
+                    ws(rng, out);
+                    if i + 1 < n || rng.below(2) == 0 {
+                        out.push(',');
+                    }
+                }
+                ws(rng, out);
+                out.push_str(close);
+            }
+            5 => {
+                out.push_str("let");
+                ws1(rng, out);
+                out.push_str(rng.pick(&["x", "y", "z"]));
+                if rng.below(3) == 0 {
+                    out.push_str(rng.pick(&[": Int", ": List[String]", ": (Int) -> Bool"]));
+                }
+                ws(rng, out);
+                out.push('=');
+                ws(rng, out);
+                gen_expr(rng, depth - 1, out);
+                ws(rng, out);
+                out.push(';');
+                ws(rng, out);
+                gen_expr(rng, depth - 1, out);
+            }
+            6 => {
+                out.push_str("if");
+                ws1(rng, out);
+                gen_operand(rng, depth - 1, out);
+                ws(rng, out);
+                out.push(':');
+                ws(rng, out);
+                gen_expr(rng, depth - 1, out);
+                ws1(rng, out);
+                out.push_str("else");
+                ws1(rng, out);
+                gen_expr(rng, depth - 1, out);
+            }
+            7 | 8 => {
+                out.push('(');
+                gen_expr(rng, depth - 1, out);
+                out.push(')');
+                ws1(rng, out);
+                out.push_str(rng.pick(&["+", "-", "*", "and", "or", "==", "<", "|"]));
+                ws1(rng, out);
+                out.push('(');
+                gen_expr(rng, depth - 1, out);
+                out.push(')');
+            }
+            9 => {
+                out.push_str(rng.pick(&["f", "x.map", "std.range"]));
+                out.push('(');
+                let n = rng.below(3);
+                for i in 0..n {
+                    ws(rng, out);
+                    gen_expr(rng, depth - 1, out);
+                    if i + 1 < n {
+                        out.push(',');
+                    }
+                }
+                ws(rng, out);
+                out.push(')');
+            }
+            10 => {
+                // Inside parentheses, the parser's lookahead mistakes
+                // `((a) => a)` for a lambda with arguments `((a)`, so we only
+                // generate lambdas without parenthesized arguments.
+                out.push_str("x.map(a =>");
+                ws1(rng, out);
+                gen_expr(rng, depth - 1, out);
+                out.push(')');
+            }
+            11 => {
+                out.push_str("f\"a {");
+                gen_expr(rng, depth - 1, out);
+                out.push_str("} b\"");
+            }
+            12 => {
+                let is_assert = rng.below(2) == 0;
+                out.push_str(if is_assert { "assert" } else { "trace" });
+                ws1(rng, out);
+                gen_expr(rng, depth - 1, out);
+                if is_assert {
+                    out.push(',');
+                    ws(rng, out);
+                    gen_expr(rng, depth - 1, out);
+                }
+                out.push(';');
+                ws(rng, out);
+                gen_expr(rng, depth - 1, out);
+            }
+            _ => {
+                out.push('-');
+                out.push('(');
+                gen_expr(rng, depth - 1, out);
+                out.push(')');
+            }
+        }
+    }
+
+    /// Append an expression that can be used as an operand without parentheses.
+    fn gen_operand(rng: &mut Rng, depth: u32, out: &mut String) {
+        if depth == 0 || rng.below(2) == 0 {
+            gen_expr(rng, 0, out);
+        } else {
+            out.push('(');
+            gen_expr(rng, depth, out);
+            out.push(')');
+        }
+    }
+
+    /// Append a random element of a collection literal to `out`.
+    fn gen_seq(rng: &mut Rng, depth: u32, is_brace: bool, out: &mut String) {
+        match rng.below(5) {
+            0 if depth > 0 => {
+                out.push_str("for");
+                ws1(rng, out);
+                out.push_str(rng.pick(&["x", "k, v"]));
+                ws1(rng, out);
+                out.push_str("in");
+                ws1(rng, out);
+                gen_operand(rng, depth - 1, out);
+                ws(rng, out);
+                out.push(':');
+                ws(rng, out);
+                gen_seq(rng, depth - 1, is_brace, out);
+            }
+            1 if depth > 0 => {
+                out.push_str("if");
+                ws1(rng, out);
+                gen_operand(rng, depth - 1, out);
+                ws(rng, out);
+                out.push(':');
+                ws(rng, out);
+                gen_seq(rng, depth - 1, is_brace, out);
+            }
+            2 if is_brace => {
+                out.push_str(rng.pick(&["a", "k-1", "\"k\":", "(x):"]));
+                if !out.ends_with(':') {
+                    // A blank line or comment between key and `=` is an error.
+                    out.push_str(rng.pick(&["", " "]));
+                    out.push('=');
+                }
+                ws(rng, out);
+                gen_expr(rng, depth, out);
+            }
+            _ => {
+                // In a collection, `if` starts a comprehension, so an if-else
+                // expression needs parentheses.
+                let mut elem = String::new();
+                gen_expr(rng, depth, &mut elem);
+                if elem.starts_with("if") {
+                    elem = format!("({elem})");
+                }
+                out.push_str(&elem);
+            }
+        }
+    }
+
+    /// Property test: every generated document survives `self_test`.
+    ///
+    /// We generate random documents with random layout, and check that the
+    /// formatter preserves their AST and is idempotent, at various widths and
+    /// indentation styles.
+    #[test]
+    fn self_test_random_documents() {
+        let styles = [
+            FormatStyle::default(),
+            FormatStyle {
+                print: Config {
+                    width: 12,
+                    indent_style: IndentStyle::Tabs,
+                    ..Config::default()
+                },
+                trailing_comma: TrailingComma::Never,
+            },
+        ];
+        for seed in 0..500 {
+            let mut rng = Rng(seed);
+            let mut input = String::new();
+            gen_expr(&mut rng, 4, &mut input);
+            let style = &styles[seed as usize % styles.len()];
+            let mut loader = Loader::new();
+            let id = loader.load_string(input.clone());
+            let result = loader
+                .get_unchecked_ast(id)
+                .and_then(|_| self_test(&mut loader, id, style));
+            if let Err(err) = result {
+                let inputs = loader.as_inputs();
+                let report = err.report(&inputs);
+                panic!(
+                    "Seed {seed} failed on input:\n{input}\n\n{}",
+                    report.println(&style.print).to_string_no_markup()
+                );
+            }
+        }
+    }
+
+    /// Run the self-test over the examples and the formatter golden tests.
+    #[test]
+    fn self_test_corpus() {
+        let root = env!("CARGO_MANIFEST_DIR");
+        let mut n_tested = 0;
+        for dir in ["examples", "golden/fmt", "golden/rcl"] {
+            let mut entries: Vec<_> = std::fs::read_dir(format!("{root}/{dir}"))
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .collect();
+            entries.sort();
+            for path in entries {
+                let data = std::fs::read_to_string(&path).unwrap();
+                // For golden tests, the input is the part before the output.
+                let input = match data.split_once("\n# output:\n") {
+                    Some((input, _output)) => input,
+                    None => &data[..],
+                };
+                let mut loader = Loader::new();
+                let id = loader.load_named_string(&path.to_string_lossy(), input.to_string());
+                // Some golden inputs intentionally fail to parse, we skip those.
+                if loader.get_unchecked_ast(id).is_err() {
+                    continue;
+                }
+                let style = FormatStyle::default();
+                if let Err(err) = self_test(&mut loader, id, &style) {
+                    let inputs = loader.as_inputs();
+                    let report = err.report(&inputs);
+                    panic!("{}", report.println(&style.print).to_string_no_markup());
+                }
+                n_tested += 1;
+            }
+        }
+        assert!(n_tested > 50);
+    }
+
+    #[test]
+    fn format_docs_preserves_order() {
+        let inputs: Vec<String> = (0..50).map(|i| format!("[{i},{i}]")).collect();
+        let style = FormatStyle::default();
+        let docs: Vec<(DocId, &str, &FormatStyle)> = inputs
+            .iter()
+            .enumerate()
+            .map(|(i, input)| (DocId(i as u32), &input[..], &style))
+            .collect();
+        for jobs in [1, 4] {
+            let results = format_docs(jobs, &docs);
+            for (i, result) in results.iter().enumerate() {
+                assert_eq!(result.as_deref(), Some(&format!("[{i}, {i}]\n")[..]));
+            }
+        }
+        let failed = format_docs(4, &[(DocId(0), "[1,", &style)]);
+        assert_eq!(failed, vec![None]);
+    }
+
+    fn parse_config_str(input: &str) -> std::result::Result<FormatStyle, String> {
+        let mut style = FormatStyle::default();
+        let mut loader = Loader::new();
+        let id = loader.load_string(input.to_string());
+        let mut type_env = crate::typecheck::prelude();
+        let mut value_env = crate::runtime::prelude();
+        let value = loader
+            .evaluate(&mut type_env, &mut value_env, id, &mut VoidTracer)
+            .unwrap();
+        let overrides = parse_config(loader.get_span(id), &value).map_err(|err| {
+            let inputs = loader.as_inputs();
+            err.report(&inputs)
+                .println(&Config::default())
+                .to_string_no_markup()
+        })?;
+        overrides.apply(&mut style);
+        Ok(style)
+    }
+
+    #[test]
+    fn parse_config_accepts_all_fields() {
+        let style = parse_config_str(
+            "{ width = 100, indent = 4, indent_style = \"tabs\", tab_width = 8, \
+            trailing_comma = \"never\", line_ending = \"crlf\", ambiguous_width = \"wide\" }",
+        )
+        .unwrap();
+        let expected = FormatStyle {
+            print: Config {
+                width: 100,
+                indent_width: 4,
+                indent_style: IndentStyle::Tabs,
+                tab_width: 8,
+                line_ending: LineEnding::CrLf,
+                ambiguous_width: AmbiguousWidth::Wide,
+            },
+            trailing_comma: TrailingComma::Never,
+        };
+        assert_eq!(style, expected);
+        assert_eq!(parse_config_str("{}"), Ok(FormatStyle::default()));
+    }
+
+    #[test]
+    fn parse_config_rejects_invalid_fields() {
+        let err = parse_config_str("{ tabs = true }").unwrap_err();
+        assert!(err.contains("Unknown formatter configuration field: 'tabs'."));
+        let err = parse_config_str("{ width = 0 }").unwrap_err();
+        assert!(err.contains("Invalid value for 'width': 0."));
+        let err = parse_config_str("{ trailing_comma = \"always\" }").unwrap_err();
+        assert!(err.contains("Invalid value for 'trailing_comma': \"always\"."));
+        let err = parse_config_str("{ indent_style = \"tab\" }").unwrap_err();
+        assert!(err.contains("Invalid value for 'indent_style': \"tab\"."));
+        let err = parse_config_str("[80]").unwrap_err();
+        assert!(err.contains("The formatter configuration must be a dict."));
+    }
+
+    #[test]
+    fn editorconfig_maps_onto_style() {
+        let properties = |kvs: &[(&str, &str)]| -> BTreeMap<String, String> {
+            kvs.iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+        let overrides = StyleOverrides::from_editorconfig(&properties(&[
+            ("indent_style", "space"),
+            ("indent_size", "4"),
+            ("max_line_length", "100"),
+            ("end_of_line", "CRLF"),
+        ]));
+        let expected = StyleOverrides {
+            width: Some(100),
+            indent_width: Some(4),
+            indent_style: Some(IndentStyle::Spaces),
+            tab_width: Some(4),
+            line_ending: Some(LineEnding::CrLf),
+            ambiguous_width: None,
+            trailing_comma: None,
+        };
+        assert_eq!(overrides, expected);
+
+        let overrides = StyleOverrides::from_editorconfig(&properties(&[
+            ("indent_size", "tab"),
+            ("tab_width", "8"),
+            ("max_line_length", "off"),
+            ("end_of_line", "cr"),
+        ]));
+        let expected = StyleOverrides {
+            indent_width: Some(8),
+            tab_width: Some(8),
+            ..StyleOverrides::default()
+        };
+        assert_eq!(overrides, expected);
+
+        let overrides = StyleOverrides::from_editorconfig(&properties(&[
+            ("indent_style", "tab"),
+            ("indent_size", "2"),
+        ]));
+        let expected = StyleOverrides {
+            indent_width: Some(2),
+            indent_style: Some(IndentStyle::Tabs),
+            tab_width: Some(2),
+            ..StyleOverrides::default()
+        };
+        assert_eq!(overrides, expected);
+    }
+
+    #[test]
+    fn first_difference_line_counts_from_one() {
+        assert_eq!(first_difference_line("a\nb\n", "a\nc\n"), 2);
+        assert_eq!(first_difference_line("a\nb\n", "x\nb\n"), 1);
+        assert_eq!(first_difference_line("a\nb\n", "a\nb\nc\n"), 3);
+        assert_eq!(first_difference_line("a\r\nb", "a\nb"), 1);
+    }
+
+    #[test]
+    fn summary_describes_counts() {
+        let summary = FormatSummary {
+            n_unchanged: 3,
+            n_changed: 2,
+            n_failed: 1,
+        };
+        assert_eq!(
+            summary.describe(false),
+            "Formatted 6 files: 3 unchanged, 2 reformatted, 1 failed to parse.",
+        );
+        assert_eq!(
+            summary.describe(true),
+            "Checked 6 files: 3 formatted correctly, 2 would be reformatted, 1 failed to parse.",
+        );
+    }
+
+    #[test]
+    fn format_doc_applies_style() {
+        let style = FormatStyle {
+            print: Config {
+                width: 10,
+                indent_width: 4,
+                line_ending: LineEnding::CrLf,
+                ..Config::default()
+            },
+            trailing_comma: TrailingComma::Never,
+        };
+        let formatted = format_doc(DocId(0), "// A.\r\n[1000, 2000]\r\n", &style);
+        let expected = "// A.\r\n[\r\n    1000,\r\n    2000\r\n]\r\n";
+        assert_eq!(formatted.as_deref(), Some(expected));
+    }
+
+    #[test]
+    fn format_doc_never_ignores_magic_trailing_comma() {
+        // The comma is removed, so it must not force the list tall either,
+        // otherwise formatting again would make it wide.
+        let style = FormatStyle {
+            trailing_comma: TrailingComma::Never,
+            ..FormatStyle::default()
+        };
+        let formatted = format_doc(DocId(0), "[1, 2,]", &style);
+        assert_eq!(formatted.as_deref(), Some("[1, 2]\n"));
+    }
+}
@@ -0,0 +1,418 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Generate syntax highlighting grammars for editors, for `rcl generate-grammar`.
+//!
+//! The keywords and punctuation come from the lexer, and the builtins and types
+//! from the highlighter, so the grammars cannot drift out of sync with the
+//! language. Only the structure of literals (strings, numbers, comments) is
+//! written out here, as regexes that mirror the lexer.
+
+use crate::cli::GrammarFormat;
+use crate::highlight::{BUILTINS, TYPES};
+use crate::lexer::{Token, DIGRAPHS, KEYWORDS, MONOGRAPHS};
+use crate::string::escape_json;
+
+/// Single-byte punctuation that is a delimiter, rather than an operator.
+const DELIMITERS: &[Token] = &[
+    Token::Eq1,
+    Token::Comma,
+    Token::Dot,
+    Token::Colon,
+    Token::Semicolon,
+];
+
+/// How a keyword should be highlighted.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum KeywordClass {
+    Boolean,
+    Null,
+    Conditional,
+    Repeat,
+    Operator,
+    Exception,
+    Other,
+}
+
+const KEYWORD_CLASSES: &[KeywordClass] = &[
+    KeywordClass::Null,
+    KeywordClass::Boolean,
+    KeywordClass::Conditional,
+    KeywordClass::Repeat,
+    KeywordClass::Operator,
+    KeywordClass::Other,
+    KeywordClass::Exception,
+];
+
+impl KeywordClass {
+    fn of(token: Token) -> KeywordClass {
+        match token {
+            Token::KwFalse | Token::KwTrue => KeywordClass::Boolean,
+            Token::KwNull => KeywordClass::Null,
+            Token::KwIf | Token::KwElse => KeywordClass::Conditional,
+            Token::KwFor => KeywordClass::Repeat,
+            Token::KwAnd | Token::KwNot | Token::KwOr => KeywordClass::Operator,
+            Token::KwAssert | Token::KwTrace => KeywordClass::Exception,
+            _ => KeywordClass::Other,
+        }
+    }
+
+    /// The keywords in this class, in the order of the lexer.
+    fn keywords(self) -> impl Iterator<Item = &'static str> {
+        KEYWORDS
+            .iter()
+            .filter(move |(_, token)| KeywordClass::of(*token) == self)
+            .map(|(keyword, _)| *keyword)
+    }
+
+    fn vim_group(self) -> &'static str {
+        match self {
+            KeywordClass::Boolean => "rclBoolean",
+            KeywordClass::Null => "rclNull",
+            KeywordClass::Conditional => "rclConditional",
+            KeywordClass::Repeat => "rclRepeat",
+            KeywordClass::Operator => "rclOperator",
+            KeywordClass::Exception => "rclException",
+            KeywordClass::Other => "rclKeyword",
+        }
+    }
+
+    fn textmate_scope(self) -> &'static str {
+        match self {
+            KeywordClass::Boolean => "constant.language.boolean.rcl",
+            KeywordClass::Null => "constant.language.null.rcl",
+            KeywordClass::Conditional => "keyword.control.conditional.rcl",
+            KeywordClass::Repeat => "keyword.control.loop.rcl",
+            KeywordClass::Operator => "keyword.operator.word.rcl",
+            KeywordClass::Exception => "keyword.other.debug.rcl",
+            KeywordClass::Other => "keyword.other.rcl",
+        }
+    }
+}
+
+/// Generate the highlighting grammar for the given editor format.
+pub fn generate(format: GrammarFormat) -> String {
+    match format {
+        GrammarFormat::TextMate => generate_textmate(),
+        GrammarFormat::Vim => generate_vim(),
+    }
+}
+
+/// Return the operators of the language, longest first.
+fn get_operators() -> impl Iterator<Item = &'static str> {
+    let monographs = MONOGRAPHS
+        .iter()
+        .filter(|(_, token)| !DELIMITERS.contains(token))
+        .map(|(monograph, _)| *monograph);
+    DIGRAPHS
+        .iter()
+        .map(|(digraph, _)| *digraph)
+        .chain(monographs)
+}
+
+/// Words that have a meaning in a Vim `syn keyword` command.
+///
+/// To use those as keywords, they need a `[]` suffix, see also
+/// <https://vi.stackexchange.com/questions/5966/>.
+const VIM_RESERVED: &[&str] = &[
+    "cchar",
+    "conceal",
+    "concealends",
+    "contained",
+    "containedin",
+    "contains",
+    "display",
+    "extend",
+    "fold",
+    "nextgroup",
+    "oneline",
+    "skipempty",
+    "skipnl",
+    "skipwhite",
+    "transparent",
+];
+
+fn generate_vim() -> String {
+    let mut out = String::new();
+    out.push_str("\" Vim syntax file for RCL, generated by 'rcl generate-grammar vim'.\n\n");
+    out.push_str("if exists('b:current_syntax')\n  finish\nendif\n\n");
+
+    for class in KEYWORD_CLASSES {
+        let keywords: Vec<&str> = class.keywords().collect();
+        out.push_str(&format!(
+            "syn keyword {:<15} {}\n",
+            class.vim_group(),
+            keywords.join(" ")
+        ));
+    }
+    out.push_str("syn cluster rclKeywords     contains=");
+    let groups: Vec<&str> = KEYWORD_CLASSES.iter().map(|c| c.vim_group()).collect();
+    out.push_str(&groups.join(","));
+    out.push_str("\n\n");
+
+    for operator in get_operators() {
+        out.push_str(&format!("syn match rclOperator '{operator}'\n"));
+    }
+    out.push('\n');
+
+    out.push_str(
+        r#"syn keyword rclTodo FIXME NOTE TODO HACK contained
+syn match   rclComment '//.*' contains=rclTodo,@Spell
+syn match   rclShebang '\%^#!.*'
+
+syn match   rclHexadecimal '\<0x[0-9a-fA-F_]\+\>'
+syn match   rclBinary      '\<0b[01_]\+\>'
+syn match   rclDecimal     '\<\(0\|[1-9][0-9_]*\)\(\.[0-9][0-9_]*\)\?\([eE][+-]\?[0-9][0-9_]*\)\?\>'
+syn cluster rclNumber      contains=rclHexadecimal,rclBinary,rclDecimal
+
+syn match   rclEscape '\\\(u{[0-9a-fA-F_]*}\|u[0-9a-fA-F]\{4}\|.\)' contained
+syn region  rclStringDouble start='"' end='"' skip='\\\\\|\\"' contains=rclEscape
+syn region  rclStringTriple start='"""' end='"""' skip='\\\\\|\\"' contains=rclEscape
+
+syn region  rclInterpolation matchgroup=rclInterpolationDelimiter start="{" end="}" contained contains=@rclExpr
+syn region  rclFormatDouble  start='f"'   end='"'   skip='\\\\\|\\"\|\\{' contains=rclEscape,rclInterpolation
+syn region  rclFormatTriple  start='f"""' end='"""' skip='\\\\\|\\"\|\\{' contains=rclEscape,rclInterpolation
+syn cluster rclString contains=rclStringDouble,rclStringTriple,rclFormatDouble,rclFormatTriple
+
+"#,
+    );
+
+    let builtins: Vec<String> = BUILTINS
+        .iter()
+        .map(|name| match VIM_RESERVED.contains(name) {
+            true => format!("{name}[]"),
+            false => name.to_string(),
+        })
+        .collect();
+    out.push_str(&format!("syn keyword rclBuiltin {}\n", builtins.join(" ")));
+    out.push_str(&format!("syn keyword rclType    {}\n\n", TYPES.join(" ")));
+
+    out.push_str(
+        r#"syn cluster rclExpr contains=@rclKeywords,rclOperator,@rclNumber,rclComment,rclBuiltin,rclType,@rclString
+
+highlight default link rclBoolean     Boolean
+highlight default link rclConditional Conditional
+highlight default link rclRepeat      Repeat
+highlight default link rclOperator    Operator
+highlight default link rclNull        Constant
+highlight default link rclKeyword     Keyword
+highlight default link rclException   Keyword
+highlight default link rclComment     Comment
+highlight default link rclShebang     PreProc
+highlight default link rclTodo        Todo
+highlight default link rclBuiltin     Function
+highlight default link rclType        Type
+highlight default link rclStringDouble String
+highlight default link rclStringTriple String
+highlight default link rclFormatDouble String
+highlight default link rclFormatTriple String
+highlight default link rclEscape      SpecialChar
+highlight default link rclHexadecimal Number
+highlight default link rclBinary      Number
+highlight default link rclDecimal     Number
+highlight default link rclInterpolationDelimiter Delimiter
+
+let b:current_syntax = 'rcl'
+"#,
+    );
+    out
+}
+
+/// Escape a string for use in a regex, but not yet for json.
+fn escape_regex(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for ch in s.chars() {
+        if r"\^$.|?*+()[]{}".contains(ch) {
+            out.push('\\');
+        }
+        out.push(ch);
+    }
+    out
+}
+
+/// Push a json object with a `name` scope and `match` regex.
+fn push_textmate_match(out: &mut String, scope: &str, regex: &str) {
+    out.push_str("        { \"name\": \"");
+    escape_json(scope, out);
+    out.push_str("\", \"match\": \"");
+    escape_json(regex, out);
+    out.push_str("\" }");
+}
+
+/// Build a regex that matches any of the words as a whole word.
+fn words_regex<'a>(words: impl Iterator<Item = &'a str>) -> String {
+    let words: Vec<String> = words.map(escape_regex).collect();
+    format!(r"\b({})\b", words.join("|"))
+}
+
+fn generate_textmate() -> String {
+    let mut out = String::new();
+    out.push_str(
+        r##"{
+  "$schema": "https://raw.githubusercontent.com/martinring/tmlanguage/master/tmlanguage.json",
+  "name": "RCL",
+  "scopeName": "source.rcl",
+  "fileTypes": ["rcl"],
+  "comment": "Generated by 'rcl generate-grammar textmate'.",
+  "patterns": [
+    { "include": "#comment" },
+    { "include": "#string" },
+    { "include": "#number" },
+    { "include": "#keyword" },
+    { "include": "#builtin" },
+    { "include": "#operator" }
+  ],
+  "repository": {
+    "comment": {
+      "patterns": [
+        { "name": "comment.line.number-sign.shebang.rcl", "match": "\\A#!.*$" },
+        { "name": "comment.line.double-slash.rcl", "match": "//.*$" }
+      ]
+    },
+    "escape": {
+      "name": "constant.character.escape.rcl",
+      "match": "\\\\(u\\{[0-9a-fA-F_]*\\}|u[0-9a-fA-F]{4}|.)"
+    },
+    "interpolation": {
+      "name": "meta.interpolation.rcl",
+      "begin": "\\{",
+      "end": "\\}",
+      "beginCaptures": { "0": { "name": "punctuation.section.interpolation.begin.rcl" } },
+      "endCaptures": { "0": { "name": "punctuation.section.interpolation.end.rcl" } },
+      "patterns": [{ "include": "$self" }]
+    },
+    "string": {
+      "patterns": [
+        {
+          "name": "string.quoted.triple.format.rcl",
+          "begin": "f\"\"\"",
+          "end": "\"\"\"",
+          "patterns": [{ "include": "#escape" }, { "include": "#interpolation" }]
+        },
+        {
+          "name": "string.quoted.double.format.rcl",
+          "begin": "f\"",
+          "end": "\"",
+          "patterns": [{ "include": "#escape" }, { "include": "#interpolation" }]
+        },
+        {
+          "name": "string.quoted.triple.rcl",
+          "begin": "\"\"\"",
+          "end": "\"\"\"",
+          "patterns": [{ "include": "#escape" }]
+        },
+        {
+          "name": "string.quoted.double.rcl",
+          "begin": "\"",
+          "end": "\"",
+          "patterns": [{ "include": "#escape" }]
+        }
+      ]
+    },
+    "number": {
+      "patterns": [
+        { "name": "constant.numeric.hex.rcl", "match": "\\b0x[0-9a-fA-F_]+\\b" },
+        { "name": "constant.numeric.binary.rcl", "match": "\\b0b[01_]+\\b" },
+        {
+          "name": "constant.numeric.decimal.rcl",
+          "match": "\\b(0|[1-9][0-9_]*)(\\.[0-9][0-9_]*)?([eE][+-]?[0-9][0-9_]*)?\\b"
+        }
+      ]
+    },
+    "keyword": {
+      "patterns": [
+"##,
+    );
+
+    for (i, class) in KEYWORD_CLASSES.iter().enumerate() {
+        if i > 0 {
+            out.push_str(",\n");
+        }
+        push_textmate_match(
+            &mut out,
+            class.textmate_scope(),
+            &words_regex(class.keywords()),
+        );
+    }
+
+    out.push_str("\n      ]\n    },\n    \"builtin\": {\n      \"patterns\": [\n");
+    push_textmate_match(
+        &mut out,
+        "support.type.rcl",
+        &words_regex(TYPES.iter().copied()),
+    );
+    out.push_str(",\n");
+    push_textmate_match(
+        &mut out,
+        "support.function.builtin.rcl",
+        &words_regex(BUILTINS.iter().copied()),
+    );
+
+    out.push_str("\n      ]\n    },\n    \"operator\": {\n      \"patterns\": [\n");
+    let operators: Vec<String> = get_operators().map(escape_regex).collect();
+    push_textmate_match(&mut out, "keyword.operator.rcl", &operators.join("|"));
+    out.push_str("\n      ]\n    }\n  }\n}\n");
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::{generate_textmate, generate_vim, get_operators};
+    use crate::json_parser::parse_json;
+    use crate::lexer::{DIGRAPHS, MONOGRAPHS};
+    use crate::parser::{to_binop, to_unop};
+    use crate::source::DocId;
+
+    /// Return the quoted strings in the `choice` of the rule `name` in `grammar.js`.
+    fn get_tree_sitter_choice(name: &str) -> Vec<&'static str> {
+        let grammar = include_str!("../grammar/tree-sitter-rcl/grammar.js");
+        let start = format!("    {name}: $ => choice(");
+        let rule = &grammar[grammar.find(&start).expect("Rule should exist.") + start.len()..];
+        let rule = &rule[..rule.find(')').expect("Rule should end.")];
+        let mut choices: Vec<&str> = rule.split('"').skip(1).step_by(2).collect();
+        choices.sort();
+        choices
+    }
+
+    #[test]
+    fn generate_textmate_is_valid_json() {
+        assert!(parse_json(DocId(0), &generate_textmate()).is_ok());
+    }
+
+    #[test]
+    fn get_operators_matches_lexer() {
+        let operators: Vec<&str> = get_operators().collect();
+        assert_eq!(
+            operators,
+            ["<=", ">=", "==", "!=", "->", "=>", "<", ">", "!", "*", "+", "-", "/", "|"],
+        );
+    }
+
+    #[test]
+    fn committed_vim_grammar_is_up_to_date() {
+        // If this fails, regenerate the file with `rcl generate-grammar vim`.
+        let committed = include_str!("../grammar/rcl.vim/syntax/rcl.vim");
+        assert_eq!(committed, generate_vim());
+    }
+
+    #[test]
+    fn tree_sitter_operators_match_parser() {
+        let punctuation = || DIGRAPHS.iter().chain(MONOGRAPHS.iter());
+        let mut binops: Vec<&str> = punctuation()
+            .filter(|(_, token)| to_binop(*token).is_some())
+            .map(|(op, _)| *op)
+            .collect();
+        let mut unops: Vec<&str> = punctuation()
+            .filter(|(_, token)| to_unop(*token).is_some())
+            .map(|(op, _)| *op)
+            .collect();
+        binops.sort();
+        unops.sort();
+        assert_eq!(get_tree_sitter_choice("binop"), binops);
+        assert_eq!(get_tree_sitter_choice("unop"), unops);
+    }
+}
@@ -0,0 +1,831 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Implementation of `rcl lsp`, a server for the Language Server Protocol.
+//!
+//! The server communicates over stdio with JSON-RPC messages. We do not pull
+//! in a json library for this: we parse incoming messages with the json parser
+//! that `--input-format=json` uses, and we serialize outgoing messages with the
+//! json formatter. Messages are untrusted input, so we deliberately do not
+//! evaluate them as RCL, which would allow imports and arbitrary computation.
+//!
+//! The server supports only a small part of the protocol:
+//!
+//!  * Full document sync, after which we publish parse and type errors as
+//!    diagnostics.
+//!  * `textDocument/formatting`, which formats the entire document.
+//!  * `textDocument/hover`, which shows the inferred type of a variable, and
+//!    the comment above its binding.
+//!  * `textDocument/definition`, which jumps to the binding of a variable, or
+//!    to the file that an import refers to.
+//!  * `textDocument/completion`, which suggests variables in scope, methods
+//!    that apply to the inferred type of the expression before a dot, and the
+//!    members of `std`.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::cmd_fmt::FormatStyle;
+use crate::error::{Error, Result};
+use crate::fmt_json;
+use crate::fmt_type::format_type;
+use crate::json_parser::parse_json;
+use crate::loader::Loader;
+use crate::navigation::{find_definition, find_scope, Definition};
+use crate::pprint::{Config, Doc};
+use crate::runtime::Value;
+use crate::source::{DocId, Span};
+use crate::typecheck::{self, TypeChecker};
+use crate::types::{Function, SourcedType, Type};
+use crate::{lexer, parser, stdlib};
+
+/// JSON-RPC error code for a message that is not valid json.
+const PARSE_ERROR: i128 = -32700;
+
+/// JSON-RPC error code for a request that the server does not implement.
+const METHOD_NOT_FOUND: i128 = -32601;
+
+/// LSP `TextDocumentSyncKind.Full`: clients send the full text on every change.
+const SYNC_FULL: i128 = 1;
+
+/// LSP `DiagnosticSeverity.Error`.
+const SEVERITY_ERROR: i128 = 1;
+
+/// LSP `CompletionItemKind` values.
+const KIND_METHOD: i128 = 2;
+const KIND_FUNCTION: i128 = 3;
+const KIND_VARIABLE: i128 = 6;
+const KIND_MODULE: i128 = 9;
+const KIND_CONSTANT: i128 = 21;
+
+/// LSP `InsertTextFormat.Snippet`.
+const FORMAT_SNIPPET: i128 = 2;
+
+/// A variable name that we put at the cursor when completing in the middle of
+/// nothing, so the document has a variable there that we can find the scope of.
+const PLACEHOLDER: &str = "completion-placeholder";
+
+/// Read one message, return `None` at end of input.
+fn read_message(input: &mut dyn BufRead) -> std::io::Result<Option<String>> {
+    let mut content_length = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        // Header names are case-insensitive. The only other header defined by
+        // the protocol is Content-Type, which we ignore.
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let len = content_length.ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "Message does not have a valid Content-Length header.",
+        )
+    })?;
+    let mut body = vec![0_u8; len];
+    input.read_exact(&mut body)?;
+    String::from_utf8(body)
+        .map(Some)
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+}
+
+/// Serialize a message to json.
+fn to_json(message: &Value) -> String {
+    // The caller span is only used for errors about values that cannot be
+    // represented in json, and we only output values that we construct here.
+    let caller = Span::new(DocId(0), 0, 0);
+    let doc = fmt_json::format_json(caller, message).expect("Messages should be valid json.");
+    doc.println(&Config::default()).to_string_no_markup()
+}
+
+/// Write one message, including its header.
+fn write_message(output: &mut dyn Write, message: &Value) -> std::io::Result<()> {
+    let body = to_json(message);
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+/// Parse the json body of a message into a value.
+fn parse_message(body: &str) -> Result<Value> {
+    // The message is not part of any document that we report errors in, we
+    // turn parse errors into a JSON-RPC error response.
+    parse_json(DocId(0), body)
+}
+
+fn dict<const N: usize>(kvs: [(&str, Value); N]) -> Value {
+    let map: BTreeMap<Value, Value> = kvs.into_iter().map(|(k, v)| (k.into(), v)).collect();
+    Value::Dict(Rc::new(map))
+}
+
+fn list(elements: Vec<Value>) -> Value {
+    Value::List(Rc::new(elements))
+}
+
+fn get<'a>(value: &'a Value, key: &str) -> Option<&'a Value> {
+    match value {
+        Value::Dict(kvs) => kvs.get(&key.into()),
+        _ => None,
+    }
+}
+
+fn get_str<'a>(value: &'a Value, key: &str) -> Option<&'a str> {
+    match get(value, key) {
+        Some(Value::String(s)) => Some(s.as_ref()),
+        _ => None,
+    }
+}
+
+fn get_int(value: &Value, key: &str) -> Option<i128> {
+    match get(value, key) {
+        Some(Value::Int(i)) => Some(*i),
+        _ => None,
+    }
+}
+
+/// Convert an LSP position into a byte offset, clamped to the document.
+fn offset(text: &str, position: &Value) -> usize {
+    let line = get_int(position, "line").unwrap_or(0);
+    let character = get_int(position, "character").unwrap_or(0);
+
+    let mut line_start = 0;
+    for _ in 0..line {
+        match text[line_start..].find('\n') {
+            Some(i) => line_start += i + 1,
+            None => return text.len(),
+        }
+    }
+
+    let mut n_utf16 = 0;
+    for (i, ch) in text[line_start..].char_indices() {
+        if n_utf16 >= character || ch == '\n' {
+            return line_start + i;
+        }
+        n_utf16 += ch.len_utf16() as i128;
+    }
+    text.len()
+}
+
+/// Convert a `file://` uri into a path, or return `None` for other schemes.
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?;
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut iter = encoded.bytes();
+    while let Some(b) = iter.next() {
+        if b == b'%' {
+            let hex: Vec<u8> = iter.by_ref().take(2).collect();
+            let hex = std::str::from_utf8(&hex).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+    String::from_utf8(bytes).ok().map(PathBuf::from)
+}
+
+/// Convert a path into a `file://` uri.
+fn path_to_uri(path: &Path) -> String {
+    let mut uri = "file://".to_string();
+    for b in path.to_string_lossy().bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(b as char)
+            }
+            _ => uri.push_str(&format!("%{b:02X}")),
+        }
+    }
+    uri
+}
+
+/// Convert a byte offset into an LSP position.
+///
+/// LSP counts characters in UTF-16 code units by default.
+fn position(text: &str, offset: usize) -> Value {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let line = before.matches('\n').count();
+    let character: usize = before[line_start..].chars().map(char::len_utf16).sum();
+    dict([
+        ("character", Value::Int(character as i128)),
+        ("line", Value::Int(line as i128)),
+    ])
+}
+
+fn range(text: &str, span: Span) -> Value {
+    dict([
+        ("end", position(text, span.end())),
+        ("start", position(text, span.start())),
+    ])
+}
+
+/// Render a message without markup, for display in the editor.
+fn render(doc: &Doc) -> String {
+    let result = doc.println(&Config::default()).to_string_no_markup();
+    result.trim_end().to_string()
+}
+
+/// Convert an error into an LSP diagnostic.
+fn diagnostic(uri: &str, text: &str, err: &Error) -> Value {
+    let mut message = render(&err.message);
+    if let Some(body) = &err.body {
+        message.push('\n');
+        message.push_str(&render(body));
+    }
+    if let Some(help) = &err.help {
+        message.push_str("\nHelp: ");
+        message.push_str(&render(help));
+    }
+
+    let related = err
+        .notes
+        .iter()
+        .map(|(span, note)| {
+            let location = dict([("range", range(text, *span)), ("uri", uri.into())]);
+            dict([("location", location), ("message", render(note)[..].into())])
+        })
+        .collect();
+
+    let span = err.origin.unwrap_or(Span::new(DocId(0), 0, 0));
+    dict([
+        ("message", message[..].into()),
+        ("range", range(text, span)),
+        ("relatedInformation", list(related)),
+        ("severity", Value::Int(SEVERITY_ERROR)),
+        ("source", "rcl".into()),
+    ])
+}
+
+/// Parse and typecheck the document, and return its problems as diagnostics.
+///
+/// We do not evaluate the document. Evaluation may read other files, and it
+/// can take arbitrarily long, which is not something to do on every keystroke.
+fn diagnose(uri: &str, text: &str) -> Vec<Value> {
+    let mut loader = Loader::new();
+    let id = loader.load_named_string(uri, text.to_string());
+    let mut type_env = typecheck::prelude();
+    match loader.get_typechecked_ast(&mut type_env, id) {
+        Ok(..) => Vec::new(),
+        Err(err) => vec![diagnostic(uri, text, &err)],
+    }
+}
+
+fn response(id: Value, result: Value) -> Value {
+    dict([("id", id), ("jsonrpc", "2.0".into()), ("result", result)])
+}
+
+fn error_response(id: Value, code: i128, message: &str) -> Value {
+    let error = dict([("code", Value::Int(code)), ("message", message.into())]);
+    dict([("error", error), ("id", id), ("jsonrpc", "2.0".into())])
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    dict([
+        ("jsonrpc", "2.0".into()),
+        ("method", method.into()),
+        ("params", params),
+    ])
+}
+
+/// Typecheck the document, and return the types of the variables in it.
+///
+/// We return the types also when typechecking fails, for the variables before
+/// the error.
+fn get_var_types(input: &str) -> Vec<(Span, SourcedType)> {
+    let mut loader = Loader::new();
+    let id = loader.load_string(input.to_string());
+    let Ok(mut ast) = loader.get_unchecked_ast(id) else {
+        return Vec::new();
+    };
+    let mut type_env = typecheck::prelude();
+    let mut checker = TypeChecker::new(&mut type_env);
+    checker.record_var_types();
+    let _ = checker.check_expr(typecheck::type_any(), loader.get_span(id), &mut ast);
+    checker.take_var_types()
+}
+
+fn is_ident_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_' || b == b'-'
+}
+
+/// A completion item for a function or method, that inserts a call snippet.
+fn completion_call(label: &str, kind: i128, detail: &str, f: &Function) -> Value {
+    let args: Vec<String> = f
+        .args
+        .iter()
+        .enumerate()
+        .map(|(i, arg)| match &arg.name {
+            Some(name) => format!("${{{}:{}}}", i + 1, name.as_ref()),
+            None => format!("${}", i + 1),
+        })
+        .collect();
+    let snippet = format!("{}({})", label, args.join(", "));
+    let signature = render(&format_type(&Type::Function(Rc::new(f.clone()))));
+    dict([
+        ("detail", format!("{detail}{signature}")[..].into()),
+        ("insertText", snippet[..].into()),
+        ("insertTextFormat", Value::Int(FORMAT_SNIPPET)),
+        ("kind", Value::Int(kind)),
+        ("label", label.into()),
+    ])
+}
+
+/// Completions for the members of `std`.
+fn complete_std() -> Vec<Value> {
+    let Value::Dict(members) = stdlib::initialize() else {
+        unreachable!("The standard library is a dict.");
+    };
+    members
+        .iter()
+        .map(|(name, value)| {
+            let name = name.expect_string();
+            match value {
+                Value::BuiltinFunction(f) => completion_call(name, KIND_FUNCTION, "", &(f.type_)()),
+                _ => dict([("kind", Value::Int(KIND_CONSTANT)), ("label", name.into())]),
+            }
+        })
+        .collect()
+}
+
+/// Completions for the methods on a receiver of the given type.
+///
+/// When we do not know the type, we suggest the methods of all types.
+fn complete_methods(receiver: Option<&Type>) -> Vec<Value> {
+    let prefix = match receiver {
+        Some(Type::Dict(..)) => "Dict.",
+        Some(Type::List(..)) => "List.",
+        Some(Type::Set(..)) => "Set.",
+        Some(Type::String) => "String.",
+        _ => "",
+    };
+    stdlib::METHODS
+        .iter()
+        .filter(|method| method.name.starts_with(prefix))
+        .filter_map(|method| {
+            let (type_name, name) = method.name.split_once('.')?;
+            let detail = if prefix.is_empty() {
+                format!("{type_name}.")
+            } else {
+                String::new()
+            };
+            Some(completion_call(
+                name,
+                KIND_METHOD,
+                &detail,
+                &(method.type_)(),
+            ))
+        })
+        .collect()
+}
+
+/// State of the language server: the documents that the client has open.
+#[derive(Default)]
+pub struct Server {
+    documents: HashMap<String, String>,
+
+    /// The root directory of the workspace, which `//` imports are relative to.
+    root: Option<PathBuf>,
+
+    is_shutdown: bool,
+}
+
+impl Server {
+    pub fn new() -> Server {
+        Server::default()
+    }
+
+    fn publish_diagnostics(&self, uri: &str) -> Value {
+        let diagnostics = match self.documents.get(uri) {
+            Some(text) => diagnose(uri, text),
+            // For a closed document, we clear the diagnostics.
+            None => Vec::new(),
+        };
+        let params = dict([("diagnostics", list(diagnostics)), ("uri", uri.into())]);
+        notification("textDocument/publishDiagnostics", params)
+    }
+
+    fn format(&self, uri: &str) -> Value {
+        let Some(text) = self.documents.get(uri) else {
+            return Value::Null;
+        };
+        let style = FormatStyle::default();
+        match crate::cmd_fmt::format_doc(DocId(0), text, &style) {
+            // When the document does not parse, we cannot format it, and the
+            // diagnostics already explain why.
+            None => Value::Null,
+            Some(formatted) if &formatted == text => list(Vec::new()),
+            Some(formatted) => {
+                let edit = dict([
+                    ("newText", formatted[..].into()),
+                    ("range", range(text, Span::new(DocId(0), 0, text.len()))),
+                ]);
+                list(vec![edit])
+            }
+        }
+    }
+
+    /// Find the definition of the name at the given position in a document.
+    fn get_definition(&self, uri: &str, position: &Value) -> Option<(&str, usize, Definition)> {
+        let text = self.documents.get(uri)?;
+        let offset = offset(text, position);
+        let tokens = lexer::lex(DocId(0), text).ok()?;
+        let (_span, cst) = parser::parse(DocId(0), text, &tokens).ok()?;
+        let definition = find_definition(text, &cst, offset)?;
+        Some((text, offset, definition))
+    }
+
+    fn definition(&self, uri: &str, position: &Value) -> Value {
+        match self.get_definition(uri, position) {
+            Some((text, _, Definition::Binding(binding))) => {
+                dict([("range", range(text, binding.ident)), ("uri", uri.into())])
+            }
+            Some((_, _, Definition::Import(path))) => {
+                let Some(from) = uri_to_path(uri) else {
+                    return Value::Null;
+                };
+                let target = match path.strip_prefix("//") {
+                    Some(relative) => match self.root.as_ref() {
+                        Some(root) => root.join(relative),
+                        None => from.with_file_name(relative),
+                    },
+                    None => from.with_file_name(&path),
+                };
+                match std::fs::canonicalize(target) {
+                    Ok(target) => {
+                        let start = Span::new(DocId(0), 0, 0);
+                        dict([
+                            ("range", range("", start)),
+                            ("uri", path_to_uri(&target)[..].into()),
+                        ])
+                    }
+                    Err(..) => Value::Null,
+                }
+            }
+            None => Value::Null,
+        }
+    }
+
+    fn hover(&self, uri: &str, position: &Value) -> Value {
+        let Some((text, offset, Definition::Binding(binding))) =
+            self.get_definition(uri, position)
+        else {
+            return Value::Null;
+        };
+
+        // Of the variables that contain the cursor, the last one is the
+        // innermost one; spans can only overlap at their boundaries.
+        let var_type = get_var_types(text)
+            .into_iter()
+            .filter(|(span, _)| span.start() <= offset && offset <= span.end())
+            .last();
+
+        let name = binding.ident.resolve(text);
+        let mut contents = match &var_type {
+            Some((_, t)) => format!("```rcl\n{}: {}\n```", name, render(&format_type(&t.type_))),
+            None => format!("```rcl\n{}\n```", name),
+        };
+        if let Some(comment) = binding.doc_comment(text) {
+            contents.push_str("\n\n");
+            contents.push_str(&comment);
+        }
+
+        let markup = dict([("kind", "markdown".into()), ("value", contents[..].into())]);
+        match var_type {
+            Some((span, _)) => dict([("contents", markup), ("range", range(text, span))]),
+            None => dict([("contents", markup)]),
+        }
+    }
+
+    /// Completions for the field after the dot at byte offset `dot`.
+    fn complete_field(&self, text: &str, dot: usize) -> Vec<Value> {
+        let bytes = text.as_bytes();
+        let mut receiver_start = dot;
+        while receiver_start > 0 && is_ident_byte(bytes[receiver_start - 1]) {
+            receiver_start -= 1;
+        }
+        if &text[receiver_start..dot] == "std" {
+            return complete_std();
+        }
+
+        // A literal directly before the dot tells us the type.
+        match bytes[..dot].last() {
+            Some(b'"') => return complete_methods(Some(&Type::String)),
+            Some(b']') => return complete_methods(Some(&Type::List(Rc::new(SourcedType::any())))),
+            _ => {}
+        }
+
+        // Otherwise, if a variable ends at the dot, we can look up its type.
+        // While the user is typing, the dot makes the document invalid, so we
+        // remove it before typechecking.
+        let without_dot = format!("{}{}", &text[..dot], &text[dot + 1..]);
+        let var_type = get_var_types(&without_dot)
+            .into_iter()
+            .filter(|(span, _)| span.end() == dot)
+            .last();
+        complete_methods(var_type.as_ref().map(|(_, t)| &t.type_))
+    }
+
+    /// Completions for a variable name that starts at byte offset `start`.
+    fn complete_variable(&self, text: &str, start: usize, end: usize) -> Vec<Value> {
+        let patched = if start == end {
+            format!("{}{}{}", &text[..start], PLACEHOLDER, &text[end..])
+        } else {
+            text.to_string()
+        };
+        let scope = lexer::lex(DocId(0), &patched)
+            .ok()
+            .and_then(|tokens| parser::parse(DocId(0), &patched, &tokens).ok())
+            .and_then(|(_span, cst)| find_scope(&patched, &cst, start))
+            .unwrap_or_default();
+        let var_types = get_var_types(&patched);
+
+        let mut result = Vec::new();
+        let mut seen = HashSet::new();
+        // Visit the innermost bindings first, those shadow outer ones.
+        for binding in scope.iter().rev() {
+            let name = binding.ident.resolve(&patched[..]);
+            if !seen.insert(name) {
+                continue;
+            }
+            let mut item = BTreeMap::new();
+            item.insert("kind".into(), Value::Int(KIND_VARIABLE));
+            item.insert("label".into(), name.into());
+            if let Some((_, t)) = var_types.iter().find(|(span, _)| *span == binding.ident) {
+                item.insert("detail".into(), render(&format_type(&t.type_))[..].into());
+            }
+            if let Some(comment) = binding.doc_comment(&patched) {
+                item.insert("documentation".into(), comment[..].into());
+            }
+            result.push(Value::Dict(Rc::new(item)));
+        }
+        if !seen.contains("std") {
+            result.push(dict([
+                ("kind", Value::Int(KIND_MODULE)),
+                ("label", "std".into()),
+            ]));
+        }
+        result
+    }
+
+    fn completion(&self, uri: &str, position: &Value) -> Value {
+        let Some(text) = self.documents.get(uri) else {
+            return Value::Null;
+        };
+        let end = offset(text, position);
+        let bytes = text.as_bytes();
+        let mut start = end;
+        while start > 0 && is_ident_byte(bytes[start - 1]) {
+            start -= 1;
+        }
+        let items = if start > 0 && bytes[start - 1] == b'.' {
+            // Remove the partial field name, so the document is more likely
+            // to parse.
+            let patched = format!("{}{}", &text[..start], &text[end..]);
+            self.complete_field(&patched, start - 1)
+        } else {
+            self.complete_variable(text, start, end)
+        };
+        list(items)
+    }
+
+    /// Handle one incoming message, return the messages to send in response.
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let params = get(message, "params").unwrap_or(&Value::Null);
+        let doc = get(params, "textDocument").unwrap_or(&Value::Null);
+        let uri = get_str(doc, "uri").unwrap_or("");
+        let position = get(params, "position").unwrap_or(&Value::Null);
+
+        match (get_str(message, "method"), get(message, "id").cloned()) {
+            (Some("initialize"), Some(id)) => {
+                self.root = get_str(params, "rootUri").and_then(uri_to_path);
+                let completion = dict([("triggerCharacters", list(vec![".".into()]))]);
+                let capabilities = dict([
+                    ("completionProvider", completion),
+                    ("definitionProvider", Value::Bool(true)),
+                    ("documentFormattingProvider", Value::Bool(true)),
+                    ("hoverProvider", Value::Bool(true)),
+                    ("textDocumentSync", Value::Int(SYNC_FULL)),
+                ]);
+                let server_info = dict([
+                    ("name", "rcl".into()),
+                    ("version", env!("CARGO_PKG_VERSION").into()),
+                ]);
+                let result = dict([("capabilities", capabilities), ("serverInfo", server_info)]);
+                vec![response(id, result)]
+            }
+            (Some("shutdown"), Some(id)) => {
+                self.is_shutdown = true;
+                vec![response(id, Value::Null)]
+            }
+            (Some("textDocument/formatting"), Some(id)) => vec![response(id, self.format(uri))],
+            (Some("textDocument/hover"), Some(id)) => {
+                vec![response(id, self.hover(uri, position))]
+            }
+            (Some("textDocument/definition"), Some(id)) => {
+                vec![response(id, self.definition(uri, position))]
+            }
+            (Some("textDocument/completion"), Some(id)) => {
+                vec![response(id, self.completion(uri, position))]
+            }
+            (Some(_), Some(id)) => vec![error_response(id, METHOD_NOT_FOUND, "Method not found.")],
+            (Some("textDocument/didOpen"), None) => {
+                let text = get_str(doc, "text").unwrap_or("");
+                self.documents.insert(uri.to_string(), text.to_string());
+                vec![self.publish_diagnostics(uri)]
+            }
+            (Some("textDocument/didChange"), None) => {
+                // With full sync, the last change contains the full new text.
+                let changes = match get(params, "contentChanges") {
+                    Some(Value::List(changes)) => &changes[..],
+                    _ => &[],
+                };
+                match changes.last().and_then(|change| get_str(change, "text")) {
+                    Some(text) => {
+                        self.documents.insert(uri.to_string(), text.to_string());
+                        vec![self.publish_diagnostics(uri)]
+                    }
+                    None => Vec::new(),
+                }
+            }
+            (Some("textDocument/didClose"), None) => {
+                self.documents.remove(uri);
+                vec![self.publish_diagnostics(uri)]
+            }
+            // Other notifications we can safely ignore, and we never send
+            // requests, so we do not expect responses.
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Run the server until the client sends `exit`, or closes the input.
+pub fn serve(input: &mut dyn BufRead, output: &mut dyn Write) -> Result<()> {
+    let mut server = Server::new();
+    let io_error = |err: std::io::Error| Error::new(format!("LSP communication failed: {err}"));
+
+    while let Some(body) = read_message(input).map_err(io_error)? {
+        let responses = match parse_message(&body) {
+            Ok(message) if get_str(&message, "method") == Some("exit") => {
+                if server.is_shutdown {
+                    return Ok(());
+                } else {
+                    return Error::new("Received 'exit' before 'shutdown'.").err();
+                }
+            }
+            Ok(message) => server.handle(&message),
+            Err(..) => vec![error_response(
+                Value::Null,
+                PARSE_ERROR,
+                "Failed to parse message.",
+            )],
+        };
+        for response in responses.iter() {
+            write_message(output, response).map_err(io_error)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{dict, serve, to_json, Server, Value};
+
+    fn frame(body: &str) -> String {
+        format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+    }
+
+    #[test]
+    fn serve_reports_diagnostics_and_formats() {
+        let messages = [
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}"#,
+            r#"{"jsonrpc": "2.0", "method": "initialized", "params": {}}"#,
+            r#"{"jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {
+                "textDocument": {"uri": "file:///a.rcl", "text": "[1,\n2,"}
+            }}"#,
+            r#"{"jsonrpc": "2.0", "method": "textDocument/didChange", "params": {
+                "textDocument": {"uri": "file:///a.rcl"},
+                "contentChanges": [{"text": "[1,2]"}]
+            }}"#,
+            r#"{"jsonrpc": "2.0", "id": 2, "method": "textDocument/formatting", "params": {
+                "textDocument": {"uri": "file:///a.rcl"}
+            }}"#,
+            r#"{"jsonrpc": "2.0", "id": 3, "method": "shutdown"}"#,
+            r#"{"jsonrpc": "2.0", "method": "exit"}"#,
+        ];
+        let input: String = messages.iter().map(|m| frame(m)).collect();
+        let mut output = Vec::new();
+        serve(&mut input.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.contains(r#""documentFormattingProvider": true"#));
+        // The unclosed list is reported at the end of the document, and the
+        // opening bracket is a related location.
+        assert!(output.contains(r#""message": "Expected ']'.""#));
+        assert!(output.contains(r#""end": {"character": 2, "line": 1}"#));
+        assert!(output.contains(r#""diagnostics": [], "uri""#));
+        assert!(output.contains(r#""newText": "[1, 2]\n""#));
+    }
+
+    #[test]
+    fn serve_hover_and_definition() {
+        let messages = [
+            r#"{"jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {
+                "textDocument": {"uri": "file:///a.rcl", "text": "// Count.\nlet n = 1;\nn + 1"}
+            }}"#,
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "textDocument/hover", "params": {
+                "textDocument": {"uri": "file:///a.rcl"}, "position": {"line": 2, "character": 0}
+            }}"#,
+            r#"{"jsonrpc": "2.0", "id": 2, "method": "textDocument/definition", "params": {
+                "textDocument": {"uri": "file:///a.rcl"}, "position": {"line": 2, "character": 0}
+            }}"#,
+            r#"{"jsonrpc": "2.0", "id": 3, "method": "shutdown"}"#,
+            r#"{"jsonrpc": "2.0", "method": "exit"}"#,
+        ];
+        let input: String = messages.iter().map(|m| frame(m)).collect();
+        let mut output = Vec::new();
+        serve(&mut input.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.contains(r#""value": "```rcl\nn: Int\n```\n\nCount.""#));
+        assert!(output.contains(r#""end": {"character": 5, "line": 1}"#));
+    }
+
+    #[test]
+    fn completion_suggests_methods_and_variables() {
+        let mut server = Server::new();
+        let uri = "file:///a.rcl";
+        let complete_at = |server: &mut Server, text: &str, line: i128, character: i128| {
+            server.documents.insert(uri.to_string(), text.to_string());
+            let position = dict([
+                ("character", Value::Int(character)),
+                ("line", Value::Int(line)),
+            ]);
+            to_json(&server.completion(uri, &position))
+        };
+
+        let items = complete_at(&mut server, "let xs = [1, 2];\nxs.ma", 1, 5);
+        assert!(items.contains(r#""insertText": "map(${1:map_element})""#));
+        assert!(!items.contains(r#""label": "split""#));
+
+        let items = complete_at(&mut server, "std.", 0, 4);
+        assert!(items.contains(r#""insertText": "range(${1:lower}, ${2:upper})""#));
+
+        let items = complete_at(&mut server, "// Some numbers.\nlet xs = [1, 2];\n", 2, 0);
+        assert!(items.contains(r#""detail": "List[Int]""#));
+        assert!(items.contains(r#""documentation": "Some numbers.""#));
+        assert!(items.contains(r#""label": "std""#));
+    }
+
+    #[test]
+    fn path_uri_roundtrip() {
+        let path = std::path::Path::new("/home/user/my config/a.rcl");
+        let uri = super::path_to_uri(path);
+        assert_eq!(uri, "file:///home/user/my%20config/a.rcl");
+        assert_eq!(super::uri_to_path(&uri).as_deref(), Some(path));
+    }
+
+    #[test]
+    fn serve_rejects_messages_that_are_not_json() {
+        // These are valid RCL but not json. Evaluating the import would access
+        // the filesystem, the server must reject the message instead.
+        let messages = [
+            r#"import "/etc/passwd""#,
+            r#"{"jsonrpc": "2.0", "id": import "x", "method": "shutdown"}"#,
+            r#"let x = std.range(0, 1000000); {"jsonrpc": "2.0", "id": x}"#,
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "shutdown",}"#,
+            r#"{"jsonrpc": "2.0", "id": 2, "method": "shutdown"}"#,
+            r#"{"jsonrpc": "2.0", "method": "exit"}"#,
+        ];
+        let input: String = messages.iter().map(|m| frame(m)).collect();
+        let mut output = Vec::new();
+        serve(&mut input.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        let n_errors = output.matches(r#""code": -32700"#).count();
+        assert_eq!(n_errors, 4);
+        // The server keeps going after the errors, and it still shuts down.
+        assert!(output.contains(r#""id": 2, "jsonrpc": "2.0", "result": null"#));
+    }
+
+    #[test]
+    fn serve_fails_on_exit_without_shutdown() {
+        let input = frame(r#"{"jsonrpc": "2.0", "method": "exit"}"#);
+        assert!(serve(&mut input.as_bytes(), &mut Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,358 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Parsers for CSV and TSV tables.
+//!
+//! The first line is the header, and every following line becomes a record: a
+//! dict from the column names to the cells. Every record must have as many
+//! cells as the header has columns. Cells are always strings: whether `007` is
+//! a number or a zip code depends on the column, so we leave the conversion to
+//! the document that imports the table, with methods such as `parse_int`.
+//!
+//! For CSV, we accept the quoting rules of RFC 4180, with either `\n` or `\r\n`
+//! line endings, and a delimiter other than `,` for `std.csv.parse`. TSV fields cannot contain tabs or line breaks, so for TSV we
+//! accept the backslash escapes that [`crate::fmt_csv`] produces.
+
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use crate::error::{IntoError, Result};
+use crate::fmt_rcl::format_rcl;
+use crate::pprint::concat;
+use crate::runtime::Value;
+use crate::source::{DocId, Span};
+
+/// Parse a CSV document into a list of records.
+pub fn parse_csv(doc: DocId, input: &str) -> Result<Value> {
+    parse_delimited(doc, input, b',')
+}
+
+/// Parse a CSV document with a different delimiter than `,`.
+///
+/// The delimiter must be an ASCII character other than `"` or a line break.
+pub fn parse_delimited(doc: DocId, input: &str, delimiter: u8) -> Result<Value> {
+    debug_assert!(delimiter.is_ascii() && !matches!(delimiter, b'"' | b'\r' | b'\n'));
+    let mut parser = Parser::new(doc, input);
+    parser.delimiter = delimiter;
+    let rows = parser.parse_rows(Parser::parse_csv_field)?;
+    into_records(rows)
+}
+
+/// Parse a TSV document into a list of records, see also [`parse_csv`].
+pub fn parse_tsv(doc: DocId, input: &str) -> Result<Value> {
+    let rows = Parser::new(doc, input).parse_rows(Parser::parse_tsv_field)?;
+    into_records(rows)
+}
+
+/// A line of the table, with the span of the line for error reporting.
+struct Row {
+    span: Span,
+    cells: Vec<(String, Span)>,
+}
+
+/// Turn the rows into records, using the first row as header.
+fn into_records(rows: Vec<Row>) -> Result<Value> {
+    let mut rows = rows.into_iter();
+    let header = match rows.next() {
+        Some(header) => header,
+        None => return Ok(Value::List(Rc::new(Vec::new()))),
+    };
+
+    let mut names: Vec<Value> = Vec::with_capacity(header.cells.len());
+    for (name, span) in header.cells {
+        let name = Value::String(name.into());
+        if names.contains(&name) {
+            let message = concat! {
+                "Duplicate column " format_rcl(&name).into_owned() " in the header."
+            };
+            return span.error(message).err();
+        }
+        names.push(name);
+    }
+
+    let mut records = Vec::new();
+    for row in rows {
+        if row.cells.len() != names.len() {
+            let message = concat! {
+                "This row has " row.cells.len().to_string()
+                if row.cells.len() == 1 { " field" } else { " fields" }
+                ", but the header has " names.len().to_string()
+                if names.len() == 1 { " column." } else { " columns." }
+            };
+            return row.span.error(message).err();
+        }
+        let record: BTreeMap<Value, Value> = names
+            .iter()
+            .cloned()
+            .zip(
+                row.cells
+                    .into_iter()
+                    .map(|(cell, _)| Value::String(cell.into())),
+            )
+            .collect();
+        records.push(Value::Dict(Rc::new(record)));
+    }
+
+    Ok(Value::List(Rc::new(records)))
+}
+
+struct Parser<'a> {
+    doc: DocId,
+    input: &'a str,
+    pos: usize,
+    /// The field separator for CSV fields. TSV fields are always separated by tabs.
+    delimiter: u8,
+}
+
+impl<'a> Parser<'a> {
+    fn new(doc: DocId, input: &'a str) -> Parser<'a> {
+        // Spreadsheet programs tend to start the file with a byte order mark.
+        let pos = if input.starts_with('\u{feff}') { 3 } else { 0 };
+        Parser {
+            doc,
+            input,
+            pos,
+            delimiter: b',',
+        }
+    }
+
+    fn span(&self, start: usize, end: usize) -> Span {
+        Span::new(self.doc, start, end)
+    }
+
+    /// Return a span of the character at the cursor, or an empty one at the end.
+    fn span_here(&self) -> Span {
+        let n = self.input[self.pos..]
+            .chars()
+            .next()
+            .map_or(0, |ch| ch.len_utf8());
+        self.span(self.pos, self.pos + n)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.pos).copied()
+    }
+
+    /// Return whether the cursor is at the end of a line or the document.
+    fn at_line_end(&self) -> bool {
+        let rest = &self.input[self.pos..];
+        rest.is_empty() || rest.starts_with('\n') || rest.starts_with("\r\n")
+    }
+
+    fn skip_line_end(&mut self) {
+        match self.peek() {
+            Some(b'\n') => self.pos += 1,
+            Some(b'\r') => self.pos += 2,
+            _ => {}
+        }
+    }
+
+    /// Parse all lines, with the given function to parse a single field.
+    ///
+    /// Empty lines are skipped, because most tools that read tables do so.
+    fn parse_rows(mut self, parse_field: impl Fn(&mut Self) -> Result<String>) -> Result<Vec<Row>> {
+        let mut rows = Vec::new();
+        while self.pos < self.input.len() {
+            if self.at_line_end() {
+                self.skip_line_end();
+                continue;
+            }
+            let start = self.pos;
+            let mut cells = Vec::new();
+            loop {
+                let field_start = self.pos;
+                let field = parse_field(&mut self)?;
+                cells.push((field, self.span(field_start, self.pos)));
+                if self.at_line_end() {
+                    break;
+                }
+                // The field parsers stop only at a separator or line end.
+                self.pos += 1;
+            }
+            rows.push(Row {
+                span: self.span(start, self.pos),
+                cells,
+            });
+            self.skip_line_end();
+        }
+        Ok(rows)
+    }
+
+    fn parse_csv_field(&mut self) -> Result<String> {
+        if self.peek() != Some(b'"') {
+            let start = self.pos;
+            while !self.at_line_end() {
+                match self.peek() {
+                    Some(b) if b == self.delimiter => break,
+                    Some(b'"') => {
+                        return self
+                            .span_here()
+                            .error("Quotes can only occur in quoted fields.")
+                            .with_help("To include a quote, quote the field and double the quote.")
+                            .err()
+                    }
+                    _ => self.pos += 1,
+                }
+            }
+            return Ok(self.input[start..self.pos].to_string());
+        }
+
+        let quote_start = self.pos;
+        self.pos += 1;
+        let mut result = String::new();
+        loop {
+            let run_start = self.pos;
+            while let Some(b) = self.peek() {
+                if b == b'"' {
+                    break;
+                }
+                self.pos += 1;
+            }
+            result.push_str(&self.input[run_start..self.pos]);
+            if self.peek().is_none() {
+                return self
+                    .span(quote_start, quote_start + 1)
+                    .error("Unterminated quoted field, expected '\"'.")
+                    .err();
+            }
+            self.pos += 1;
+            if self.peek() == Some(b'"') {
+                result.push('"');
+                self.pos += 1;
+                continue;
+            }
+            if self.peek() == Some(self.delimiter) || self.at_line_end() {
+                // Normalize line breaks, so the value does not depend on the
+                // line endings of the file.
+                return Ok(result.replace("\r\n", "\n"));
+            }
+            let delimiter = char::from(self.delimiter).escape_default().to_string();
+            let message = concat! {
+                "Expected '" delimiter
+                "' or a line break after the closing quote."
+            };
+            return self.span_here().error(message).err();
+        }
+    }
+
+    fn parse_tsv_field(&mut self) -> Result<String> {
+        let mut result = String::new();
+        while !self.at_line_end() {
+            let run_start = self.pos;
+            while let Some(b) = self.peek() {
+                if matches!(b, b'\t' | b'\\' | b'\n') || self.at_line_end() {
+                    break;
+                }
+                self.pos += 1;
+            }
+            result.push_str(&self.input[run_start..self.pos]);
+            if self.peek() != Some(b'\\') {
+                break;
+            }
+            let escape_start = self.pos;
+            self.pos += 1;
+            let ch = match self.peek() {
+                Some(b't') => '\t',
+                Some(b'n') => '\n',
+                Some(b'r') => '\r',
+                Some(b'\\') => '\\',
+                _ => {
+                    return self
+                        .span(escape_start, self.span_here().end())
+                        .error(r"Invalid escape sequence, expected '\t', '\n', '\r', or '\\'.")
+                        .err()
+                }
+            };
+            result.push(ch);
+            self.pos += 1;
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_csv, parse_delimited, parse_tsv};
+    use crate::fmt_rcl::format_rcl;
+    use crate::pprint::Config;
+    use crate::source::DocId;
+
+    fn parse(input: &str) -> String {
+        let value = parse_csv(DocId(0), input).unwrap();
+        let cfg = Config::default();
+        format_rcl(&value).println(&cfg).to_string_no_markup()
+    }
+
+    fn fail(input: &str) -> String {
+        let err = parse_csv(DocId(0), input).err().unwrap();
+        let cfg = Config::default();
+        err.message.println(&cfg).to_string_no_markup()
+    }
+
+    #[test]
+    fn parse_csv_quoted_fields() {
+        assert_eq!(parse(""), "[]\n");
+        assert_eq!(parse("a,b\n"), "[]\n");
+        assert_eq!(
+            parse("a,b\r\n1,\"x,\"\"y\"\"\"\r\n\r\n\"\",\"two\r\nlines\""),
+            "[{ a = \"1\", b = \"x,\\\"y\\\"\" }, { a = \"\", b = \"two\\nlines\" }]\n",
+        );
+        assert_eq!(
+            parse("\u{feff}name, port\nweb,80"),
+            "[{ \" port\": \"80\", name = \"web\" }]\n",
+        );
+    }
+
+    #[test]
+    fn parse_csv_rejects_malformed_rows() {
+        assert_eq!(
+            fail("a,b\n1\n"),
+            "This row has 1 field, but the header has 2 columns.\n"
+        );
+        assert_eq!(fail("a,a\n"), "Duplicate column \"a\" in the header.\n");
+        assert_eq!(
+            fail("a\nx\"y\n"),
+            "Quotes can only occur in quoted fields.\n"
+        );
+        assert_eq!(
+            fail("a\n\"x\"y\n"),
+            "Expected ',' or a line break after the closing quote.\n"
+        );
+        assert_eq!(
+            fail("a\n\"x\n"),
+            "Unterminated quoted field, expected '\"'.\n"
+        );
+    }
+
+    #[test]
+    fn parse_tsv_unescapes_fields() {
+        let value = parse_tsv(DocId(0), "a\tb\r\nx\\ty\t\\\\\n\t\n").unwrap();
+        let cfg = Config::default();
+        assert_eq!(
+            format_rcl(&value).println(&cfg).to_string_no_markup(),
+            "[{ a = \"x\\ty\", b = \"\\\\\" }, { a = \"\", b = \"\" }]\n",
+        );
+        assert!(parse_tsv(DocId(0), "a\n\\x\n").is_err());
+    }
+
+    #[test]
+    fn parse_delimited_uses_delimiter() {
+        let value = parse_delimited(DocId(0), "a;b\n1,5;\"x;y\"\n", b';').unwrap();
+        let cfg = Config::default();
+        assert_eq!(
+            format_rcl(&value).println(&cfg).to_string_no_markup(),
+            "[{ a = \"1,5\", b = \"x;y\" }]\n",
+        );
+        let err = parse_delimited(DocId(0), "a\n\"x\",\n", b'\t')
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.message.println(&cfg).to_string_no_markup(),
+            "Expected '\\t' or a line break after the closing quote.\n",
+        );
+    }
+}
@@ -0,0 +1,611 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! A decimal number type for values that are not integers.
+//!
+//! Numbers are stored as a mantissa and a power of ten, rather than as binary
+//! floating-point, so a literal like `0.1` is represented exactly and prints
+//! back the way it was written. Arithmetic is exact as long as the result fits
+//! in the 128-bit mantissa, which holds 38 significant digits. When it does
+//! not, or when a division does not terminate, the result is rounded half to
+//! even.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+/// How [`Decimal::to_int`] rounds numbers that are not integers.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Rounding {
+    /// Round towards negative infinity.
+    Floor,
+    /// Round towards positive infinity.
+    Ceil,
+    /// Round to the nearest integer, and to the even one when halfway.
+    HalfEven,
+}
+
+/// A decimal number, `mantissa * 10^exponent`.
+///
+/// The number is always normalized: the mantissa has no trailing zeros, and
+/// zero has exponent zero. This makes structural equality agree with numeric
+/// equality.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Decimal {
+    mantissa: i128,
+    exponent: i32,
+}
+
+/// The number of decimal digits of `i128::MAX`, an upper bound on the digits of any mantissa.
+const MAX_DIGITS: u64 = 39;
+
+/// The smallest mantissa magnitude with more than 38 digits.
+///
+/// Rounded results are reduced to 38 digits, so rounding up cannot overflow.
+const TEN_POW_38: u128 = 10_u128.pow(38);
+
+/// Return `m * 10^k`, or `None` if that does not fit.
+fn scale(m: i128, k: u32) -> Option<i128> {
+    10_i128.checked_pow(k).and_then(|p| m.checked_mul(p))
+}
+
+/// Return `n / d` rounded half to even.
+fn div_round(n: i128, d: i128) -> i128 {
+    let q = n / d;
+    let r = (n % d).unsigned_abs();
+    let twice_r = r * 2;
+    let d_abs = d.unsigned_abs();
+    if twice_r > d_abs || (twice_r == d_abs && q % 2 != 0) {
+        if (n < 0) == (d < 0) {
+            q + 1
+        } else {
+            q - 1
+        }
+    } else {
+        q
+    }
+}
+
+/// Return `a * b` exactly, as 64-bit limbs with the least significant first.
+fn mul_wide(a: u128, b: u128) -> [u64; 4] {
+    let a = [a as u64, (a >> 64) as u64];
+    let b = [b as u64, (b >> 64) as u64];
+    let mut out = [0_u64; 4];
+    for (i, ai) in a.iter().enumerate() {
+        let mut carry: u128 = 0;
+        for (j, bj) in b.iter().enumerate() {
+            let t = (*ai as u128) * (*bj as u128) + out[i + j] as u128 + carry;
+            out[i + j] = t as u64;
+            carry = t >> 64;
+        }
+        out[i + 2] = carry as u64;
+    }
+    out
+}
+
+/// Divide the wide number by 10 in place, return the remainder.
+fn div_wide_10(x: &mut [u64; 4]) -> u64 {
+    let mut rem: u128 = 0;
+    for limb in x.iter_mut().rev() {
+        let t = (rem << 64) | *limb as u128;
+        *limb = (t / 10) as u64;
+        rem = t % 10;
+    }
+    rem as u64
+}
+
+/// Return `10 * r / d` and `10 * r % d`, for `r < d`, without overflowing.
+fn next_digit(r: u128, d: u128) -> (u128, u128) {
+    let mut digit = 0;
+    let mut acc: u128 = 0;
+    for _ in 0..10 {
+        // Both terms are less than `d`, so the sum fits.
+        acc += r;
+        if acc >= d {
+            acc -= d;
+            digit += 1;
+        }
+    }
+    (digit, acc)
+}
+
+/// Apply the sign to a magnitude that fits in the mantissa.
+fn with_sign(negative: bool, m: u128) -> i128 {
+    let m = i128::try_from(m).expect("Rounded mantissas fit in an i128.");
+    if negative {
+        -m
+    } else {
+        m
+    }
+}
+
+impl Decimal {
+    /// The number zero.
+    pub const ZERO: Decimal = Decimal {
+        mantissa: 0,
+        exponent: 0,
+    };
+
+    /// Construct `mantissa * 10^exponent`, or `None` if the exponent overflows.
+    pub fn new(mut mantissa: i128, mut exponent: i32) -> Option<Decimal> {
+        if mantissa == 0 {
+            return Some(Decimal::ZERO);
+        }
+        while mantissa % 10 == 0 {
+            mantissa /= 10;
+            exponent = exponent.checked_add(1)?;
+        }
+        Some(Decimal { mantissa, exponent })
+    }
+
+    /// Return the mantissa, which has no trailing zeros.
+    pub fn mantissa(&self) -> i128 {
+        self.mantissa
+    }
+
+    /// Return the power of ten that the mantissa is multiplied by.
+    pub fn exponent(&self) -> i32 {
+        self.exponent
+    }
+
+    /// Parse a number in decimal notation, with optional sign, fraction, and exponent.
+    ///
+    /// This is lenient about the syntax, the parsers of the input languages
+    /// validate it. Digit separators must be removed beforehand. Returns `None`
+    /// when the input is not a number, or when it has more significant digits
+    /// than the mantissa can hold.
+    pub fn parse(s: &str) -> Option<Decimal> {
+        let (negative, s) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (digits, exponent) = match s.find(['e', 'E']) {
+            Some(i) => (&s[..i], s[i + 1..].parse::<i32>().ok()?),
+            None => (s, 0),
+        };
+        let (int_part, frac_part) = digits.split_once('.').unwrap_or((digits, ""));
+        let all_digits = int_part.bytes().chain(frac_part.bytes());
+        if int_part.len() + frac_part.len() == 0 || !all_digits.clone().all(|b| b.is_ascii_digit())
+        {
+            return None;
+        }
+
+        // Accumulate the significant digits only, so that leading and trailing
+        // zeros do not count against the precision.
+        let mut mantissa: i128 = 0;
+        let mut n_trailing_zeros: i32 = 0;
+        for b in all_digits {
+            if b == b'0' {
+                n_trailing_zeros = n_trailing_zeros.checked_add(1)?;
+                continue;
+            }
+            let digit = (b - b'0') as i128;
+            mantissa = match mantissa {
+                0 => digit,
+                _ => scale(mantissa, n_trailing_zeros as u32 + 1)?.checked_add(digit)?,
+            };
+            n_trailing_zeros = 0;
+        }
+        if mantissa == 0 {
+            return Some(Decimal::ZERO);
+        }
+        let frac_len: i32 = frac_part.len().try_into().ok()?;
+        let exponent = exponent
+            .checked_sub(frac_len)?
+            .checked_add(n_trailing_zeros)?;
+        Decimal::new(if negative { -mantissa } else { mantissa }, exponent)
+    }
+
+    /// Convert a float to the shortest decimal that converts back to the same float.
+    ///
+    /// Returns `None` for infinities and NaN.
+    pub fn from_f64(f: f64) -> Option<Decimal> {
+        if f.is_finite() {
+            Decimal::parse(&format!("{f:e}"))
+        } else {
+            None
+        }
+    }
+
+    /// Return the float nearest to this number.
+    pub fn to_f64(&self) -> f64 {
+        format!("{}e{}", self.mantissa, self.exponent)
+            .parse()
+            .expect("Rust can parse its own integers as float.")
+    }
+
+    /// Drop the least significant digit, rounding half to even.
+    fn round_digit(self) -> Option<Decimal> {
+        Some(Decimal {
+            mantissa: div_round(self.mantissa, 10),
+            exponent: self.exponent.checked_add(1)?,
+        })
+    }
+
+    /// Return `-self`, or `None` if the exponent overflows.
+    pub fn checked_neg(self) -> Option<Decimal> {
+        match self.mantissa.checked_neg() {
+            Some(m) => Decimal::new(m, self.exponent),
+            None => self.round_digit()?.checked_neg(),
+        }
+    }
+
+    /// Return `self + other`, or `None` if the exponent overflows.
+    pub fn checked_add(self, other: Decimal) -> Option<Decimal> {
+        let (mut hi, mut lo) = if self.exponent >= other.exponent {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        loop {
+            // The difference is non-negative, but it may not fit an i32.
+            let k = (hi.exponent as i64 - lo.exponent as i64) as u64;
+            // When the gap exceeds the digits of both mantissas, `lo` is less
+            // than half a unit in the last place of any result, so it rounds
+            // away entirely. Return early rather than rounding it one digit at
+            // a time, because the gap can be billions of digits.
+            if lo.mantissa == 0 || k > 2 * MAX_DIGITS {
+                return Some(hi);
+            }
+            let sum = u32::try_from(k)
+                .ok()
+                .and_then(|k| scale(hi.mantissa, k))
+                .and_then(|hi_m| hi_m.checked_add(lo.mantissa));
+            if let Some(m) = sum {
+                return Decimal::new(m, lo.exponent);
+            }
+            // If we cannot bring both to the same exponent, precision runs out
+            // at the least significant digits of the smaller-exponent operand.
+            if k == 0 {
+                hi = hi.round_digit()?;
+            }
+            lo = lo.round_digit()?;
+        }
+    }
+
+    /// Return `self - other`, or `None` if the exponent overflows.
+    pub fn checked_sub(self, other: Decimal) -> Option<Decimal> {
+        self.checked_add(other.checked_neg()?)
+    }
+
+    /// Return `self * other`, or `None` if the exponent overflows.
+    pub fn checked_mul(self, other: Decimal) -> Option<Decimal> {
+        let mut exponent = self.exponent.checked_add(other.exponent)?;
+        if let Some(m) = self.mantissa.checked_mul(other.mantissa) {
+            return Decimal::new(m, exponent);
+        }
+
+        // Form the exact product, and drop digits until 38 remain. We track
+        // the last digit dropped, and whether any digit before it was nonzero,
+        // which is all we need to round the exact product once.
+        let mut product = mul_wide(self.mantissa.unsigned_abs(), other.mantissa.unsigned_abs());
+        let mut last = 0;
+        let mut sticky = false;
+        while product[2] != 0
+            || product[3] != 0
+            || (product[0] as u128 | (product[1] as u128) << 64) >= TEN_POW_38
+        {
+            sticky = sticky || last != 0;
+            last = div_wide_10(&mut product);
+            exponent = exponent.checked_add(1)?;
+        }
+        let mut m = product[0] as u128 | (product[1] as u128) << 64;
+        if last > 5 || (last == 5 && (sticky || m % 2 == 1)) {
+            m += 1;
+        }
+        let negative = (self.mantissa < 0) != (other.mantissa < 0);
+        Decimal::new(with_sign(negative, m), exponent)
+    }
+
+    /// Return `self / other`, or `None` if `other` is zero or the exponent overflows.
+    pub fn checked_div(self, other: Decimal) -> Option<Decimal> {
+        // Dividing by a power of ten only moves the decimal point. We handle
+        // it separately because `i128::MIN / -1` would overflow.
+        match other.mantissa {
+            0 => return None,
+            1 => return Decimal::new(self.mantissa, self.exponent.checked_sub(other.exponent)?),
+            -1 => {
+                return Decimal::new(self.mantissa, self.exponent.checked_sub(other.exponent)?)?
+                    .checked_neg()
+            }
+            _ => {}
+        }
+        // Long division, one decimal digit at a time, until the division is
+        // exact or the quotient has 38 digits. Then the remainder tells us how
+        // to round the exact quotient.
+        let d = other.mantissa.unsigned_abs();
+        let mut q = self.mantissa.unsigned_abs() / d;
+        let mut r = self.mantissa.unsigned_abs() % d;
+        let mut exponent = self.exponent.checked_sub(other.exponent)?;
+        while r != 0 && q < TEN_POW_38 / 10 {
+            let (digit, rem) = next_digit(r, d);
+            q = q * 10 + digit;
+            r = rem;
+            exponent = exponent.checked_sub(1)?;
+        }
+        // The remainder is less than `d < 2^127`, so doubling it fits.
+        let twice_r = r * 2;
+        if twice_r > d || (twice_r == d && q % 2 == 1) {
+            q += 1;
+        }
+        let negative = (self.mantissa < 0) != (other.mantissa < 0);
+        Decimal::new(with_sign(negative, q), exponent)
+    }
+
+    /// Round to an integer, or return `None` if the result does not fit in an `i128`.
+    pub fn to_int(self, rounding: Rounding) -> Option<i128> {
+        if self.exponent >= 0 {
+            return scale(self.mantissa, self.exponent as u32);
+        }
+        // When the power of ten does not fit, it exceeds any mantissa, so the
+        // integer part is zero, and the fraction is less than a half.
+        let k = self.exponent.unsigned_abs();
+        let (q, r) = match 10_i128.checked_pow(k) {
+            Some(d) => (self.mantissa / d, self.mantissa % d),
+            None => (0, self.mantissa),
+        };
+        let round_away = match rounding {
+            _ if r == 0 => false,
+            Rounding::Floor => r < 0,
+            Rounding::Ceil => r > 0,
+            Rounding::HalfEven => {
+                // Compare the fraction `r / 10^k` against one half.
+                let twice_r = r.unsigned_abs() * 2;
+                match 10_u128.checked_pow(k) {
+                    Some(d) => twice_r > d || (twice_r == d && q % 2 != 0),
+                    None => false,
+                }
+            }
+        };
+        // The quotient is at most `i128::MAX / 10` in magnitude, so this fits.
+        Some(if round_away { q + r.signum() } else { q })
+    }
+}
+
+impl From<i128> for Decimal {
+    fn from(i: i128) -> Decimal {
+        Decimal::new(i, 0).expect("Normalizing an integer cannot overflow the exponent.")
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Decimal) -> Ordering {
+        match self.mantissa.signum().cmp(&other.mantissa.signum()) {
+            Ordering::Equal => {}
+            unequal => return unequal,
+        }
+        let (hi, lo) = match self.exponent.cmp(&other.exponent) {
+            Ordering::Equal => return self.mantissa.cmp(&other.mantissa),
+            Ordering::Greater => (self, other),
+            Ordering::Less => (other, self),
+        };
+        // Bring the larger exponent down. If that does not fit, its magnitude
+        // exceeds that of any mantissa at the lower exponent.
+        let k = (hi.exponent as i64 - lo.exponent as i64) as u64;
+        let ord = match u32::try_from(k).ok().and_then(|k| scale(hi.mantissa, k)) {
+            Some(m) => m.cmp(&lo.mantissa),
+            None if hi.mantissa > 0 => Ordering::Greater,
+            None => Ordering::Less,
+        };
+        if hi == self {
+            ord
+        } else {
+            ord.reverse()
+        }
+    }
+}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Decimal) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Decimal {
+    /// Format the number such that it is valid in RCL, JSON, TOML, YAML, and Nix.
+    ///
+    /// There is always a decimal point, so the number cannot be confused with
+    /// an integer. Very large and very small numbers use an exponent.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.mantissa < 0 {
+            f.write_str("-")?;
+        }
+        let digits = self.mantissa.unsigned_abs().to_string();
+        let n = digits.len() as i64;
+        // The position of the decimal point, relative to the first digit.
+        let point = n + self.exponent as i64;
+        if point > 21 || point < -5 {
+            let (first, rest) = digits.split_at(1);
+            let rest = if rest.is_empty() { "0" } else { rest };
+            let exp = point - 1;
+            let sign = if exp < 0 { '-' } else { '+' };
+            write!(f, "{first}.{rest}e{sign}{}", exp.unsigned_abs())
+        } else if self.exponent >= 0 {
+            write!(f, "{digits}{:0<1$}.0", "", self.exponent as usize)
+        } else if point > 0 {
+            let (int_part, frac_part) = digits.split_at(point as usize);
+            write!(f, "{int_part}.{frac_part}")
+        } else {
+            write!(f, "0.{:0<1$}{digits}", "", (-point) as usize)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Decimal, Rounding};
+
+    fn d(s: &str) -> Decimal {
+        Decimal::parse(s).unwrap_or_else(|| panic!("Failed to parse {s}."))
+    }
+
+    fn fmt(s: &str) -> String {
+        d(s).to_string()
+    }
+
+    #[test]
+    fn parse_normalizes() {
+        assert_eq!(d("0.50"), d("0.5"));
+        assert_eq!(d("5e-1"), d("0.5"));
+        assert_eq!(d("0.000"), Decimal::ZERO);
+        assert_eq!(d("-0.0"), Decimal::ZERO);
+        assert_eq!(d("100"), Decimal::from(100));
+        assert_eq!(d("1200").mantissa(), 12);
+        assert_eq!(d("1200").exponent(), 2);
+        assert_eq!(d("1e+3"), Decimal::from(1000));
+        assert_eq!(d(&format!("0.{}1", "0".repeat(100))).exponent(), -101);
+        assert_eq!(Decimal::parse(""), None);
+        assert_eq!(Decimal::parse("."), None);
+        assert_eq!(Decimal::parse("1.2.3"), None);
+        assert_eq!(Decimal::parse("1e"), None);
+        assert_eq!(Decimal::parse("1x"), None);
+        assert_eq!(Decimal::parse(&"1".repeat(40)), None);
+    }
+
+    #[test]
+    fn display_roundtrips() {
+        assert_eq!(fmt("0.5"), "0.5");
+        assert_eq!(fmt("-0.5"), "-0.5");
+        assert_eq!(fmt("1.0"), "1.0");
+        assert_eq!(fmt("1200"), "1200.0");
+        assert_eq!(fmt("3.14159"), "3.14159");
+        assert_eq!(fmt("0.000001"), "0.000001");
+        assert_eq!(fmt("0.0000001"), "1.0e-7");
+        assert_eq!(fmt("1.5e20"), "150000000000000000000.0");
+        assert_eq!(fmt("1.5e21"), "1.5e+21");
+        assert_eq!(fmt("-1.25e-30"), "-1.25e-30");
+        assert_eq!(fmt("0"), "0.0");
+        for s in ["0.5", "1.0e-7", "1.5e+21", "-1.25e-30", "123.456"] {
+            assert_eq!(d(&fmt(s)), d(s));
+        }
+    }
+
+    #[test]
+    fn compare_by_value() {
+        assert!(d("0.5") < d("1"));
+        assert!(d("-1") < d("-0.5"));
+        assert!(d("-0.5") < Decimal::ZERO);
+        assert!(d("1e30") > d("99999"));
+        assert!(d("-1e30") < d("-99999"));
+        assert!(d("1e100") > d("1e99"));
+        assert!(d("1e-100") < d("1e-99"));
+        assert!(d("1e1000") > d(&"9".repeat(38)));
+        assert_eq!(d("2.50").cmp(&d("2.5")), std::cmp::Ordering::Equal);
+    }
+
+    #[test]
+    fn to_int_rounds() {
+        let round =
+            |s: &str| [Rounding::Floor, Rounding::Ceil, Rounding::HalfEven].map(|r| d(s).to_int(r));
+        assert_eq!(round("2"), [Some(2), Some(2), Some(2)]);
+        assert_eq!(round("1200"), [Some(1200), Some(1200), Some(1200)]);
+        assert_eq!(round("2.4"), [Some(2), Some(3), Some(2)]);
+        assert_eq!(round("2.5"), [Some(2), Some(3), Some(2)]);
+        assert_eq!(round("3.5"), [Some(3), Some(4), Some(4)]);
+        assert_eq!(round("2.51"), [Some(2), Some(3), Some(3)]);
+        assert_eq!(round("-2.5"), [Some(-3), Some(-2), Some(-2)]);
+        assert_eq!(round("-3.5"), [Some(-4), Some(-3), Some(-4)]);
+        assert_eq!(round("-0.1"), [Some(-1), Some(0), Some(0)]);
+        assert_eq!(round("1e-40"), [Some(0), Some(1), Some(0)]);
+        assert_eq!(round("-1e-40"), [Some(-1), Some(0), Some(0)]);
+        assert_eq!(round("5e-1"), [Some(0), Some(1), Some(0)]);
+        assert_eq!(
+            round(&format!("0.{}", "5".repeat(38))),
+            [Some(0), Some(1), Some(1)]
+        );
+        assert_eq!(round("1e38"), [Some(10_i128.pow(38)); 3]);
+        assert_eq!(round("1e39"), [None, None, None]);
+    }
+
+    #[test]
+    fn arithmetic_is_exact_when_it_fits() {
+        assert_eq!(d("0.1").checked_add(d("0.2")), Some(d("0.3")));
+        assert_eq!(d("1.5").checked_sub(d("2")), Some(d("-0.5")));
+        assert_eq!(d("0.5").checked_mul(d("0.5")), Some(d("0.25")));
+        assert_eq!(d("1").checked_div(d("4")), Some(d("0.25")));
+        assert_eq!(d("1e3").checked_div(d("0.5")), Some(d("2000")));
+        assert_eq!(d("1").checked_div(Decimal::ZERO), None);
+        assert_eq!(d("-0.5").checked_neg(), Some(d("0.5")));
+    }
+
+    #[test]
+    fn arithmetic_rounds_half_to_even() {
+        let third = d("1").checked_div(d("3")).unwrap();
+        assert_eq!(third, d(&format!("0.{}", "3".repeat(38))));
+        let two_thirds = d("2").checked_div(d("3")).unwrap();
+        assert_eq!(two_thirds, d(&format!("0.{}7", "6".repeat(37))));
+        // The 1 falls off the end of the 38-digit mantissa.
+        assert_eq!(d("1e40").checked_add(d("1")), Some(d("1e40")));
+        let big = d(&"9".repeat(38));
+        assert_eq!(
+            big.checked_mul(big),
+            Some(d(&format!("{}8e38", "9".repeat(37))))
+        );
+        assert_eq!(d("1e2147483647").checked_mul(d("10")), None);
+    }
+
+    #[test]
+    fn arithmetic_keeps_38_digits_of_long_mantissas() {
+        let a = d("1.2345678901234567890123456789012345678");
+        let b = d("9.8765432109876543210987654321098765432");
+        assert_eq!(
+            a.checked_div(b),
+            Some(d("0.12499999886093750001423828124982202148"))
+        );
+        assert_eq!(
+            d("1.5").checked_div(b),
+            Some(d("0.15187499998291406250021357421874733032"))
+        );
+        assert_eq!(
+            d("1").checked_div(b.checked_neg().unwrap()),
+            Some(d("-0.10124999998860937500014238281249822021"))
+        );
+        assert_eq!(
+            a.checked_mul(b),
+            Some(d("12.193263113702179522618503273386678859"))
+        );
+        assert_eq!(
+            a.checked_neg().unwrap().checked_mul(b),
+            Some(d("-12.193263113702179522618503273386678859"))
+        );
+        let max = Decimal::from(i128::MAX);
+        assert_eq!(
+            max.checked_div(d("7")),
+            Some(d("24305883351495604533098186245126300818"))
+        );
+        assert_eq!(
+            d("1").checked_div(max),
+            Some(d("5.8774717541114375398436826861112283891e-39"))
+        );
+    }
+
+    #[test]
+    fn add_with_distant_exponents_terminates() {
+        let huge = d("1e2000000000");
+        let tiny = d("1e-2000000000");
+        assert_eq!(huge.checked_add(tiny), Some(huge));
+        assert_eq!(tiny.checked_add(huge), Some(huge));
+        assert_eq!(huge.checked_sub(tiny), Some(huge));
+        assert_eq!(tiny.checked_sub(huge), huge.checked_neg());
+        let max = Decimal::new(1, i32::MAX).unwrap();
+        let min = Decimal::new(-1, i32::MIN).unwrap();
+        assert_eq!(max.checked_add(min), Some(max));
+        // Within the digits of both mantissas, the smaller operand still counts.
+        assert_eq!(d("1e38").checked_add(d("1e-1")), Some(d("1e38")));
+        let sum = d("1e37").checked_add(d("1e-1")).unwrap();
+        assert_eq!(sum, d(&format!("1{}.1", "0".repeat(37))));
+    }
+
+    #[test]
+    fn f64_conversion() {
+        assert_eq!(Decimal::from_f64(0.1), Some(d("0.1")));
+        assert_eq!(Decimal::from_f64(-2.5e-300), Some(d("-2.5e-300")));
+        assert_eq!(Decimal::from_f64(f64::NAN), None);
+        assert_eq!(Decimal::from_f64(f64::INFINITY), None);
+        assert_eq!(d("0.1").to_f64(), 0.1);
+        assert_eq!(d("1e400").to_f64(), f64::INFINITY);
+    }
+}
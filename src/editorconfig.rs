@@ -0,0 +1,211 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Reading `.editorconfig` files, so `rcl format` can follow the same
+//! conventions as the other tools in a repository.
+//!
+//! See <https://editorconfig.org> for the file format. We are lenient about
+//! malformed lines: like other implementations, we ignore them.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::error::{Error, Result};
+use crate::glob::Glob;
+use crate::pprint::{concat, Doc};
+
+/// The name of the files to look for in the ancestors of a document.
+pub const FILE_NAME: &str = ".editorconfig";
+
+/// A section of an `.editorconfig` file, the properties under a glob header.
+#[derive(Debug)]
+struct Section {
+    pattern: Glob,
+    properties: Vec<(String, String)>,
+}
+
+/// A parsed `.editorconfig` file.
+#[derive(Debug)]
+pub struct EditorConfig {
+    /// Whether this file is the root, so we should stop searching upwards.
+    root: bool,
+    sections: Vec<Section>,
+}
+
+impl EditorConfig {
+    /// Parse the contents of an `.editorconfig` file.
+    pub fn parse(input: &str) -> EditorConfig {
+        let mut result = EditorConfig {
+            root: false,
+            sections: Vec::new(),
+        };
+        for line in input.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                result.sections.push(Section {
+                    pattern: parse_section_pattern(header),
+                    properties: Vec::new(),
+                });
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let key = key.trim().to_ascii_lowercase();
+            let value = value.trim().to_string();
+            match result.sections.last_mut() {
+                Some(section) => section.properties.push((key, value)),
+                // Before the first section, only `root` has a meaning.
+                None if key == "root" => result.root = value.eq_ignore_ascii_case("true"),
+                None => continue,
+            }
+        }
+        result
+    }
+
+    /// Set the properties that apply to `path`, in order of appearance.
+    ///
+    /// The path is relative to the directory that contains the file.
+    fn apply(&self, path: &str, properties: &mut BTreeMap<String, String>) {
+        for section in self.sections.iter() {
+            if section.pattern.matches(path) {
+                for (k, v) in section.properties.iter() {
+                    properties.insert(k.clone(), v.clone());
+                }
+            }
+        }
+    }
+}
+
+/// Parse a section header into a glob that we match against `/`-prefixed paths.
+///
+/// A glob without slashes matches the file name in any directory, a glob with
+/// a slash is relative to the directory of the `.editorconfig` file.
+fn parse_section_pattern(header: &str) -> Glob {
+    if !header.contains('/') {
+        Glob::new(&format!("/**/{header}"))
+    } else if header.starts_with('/') {
+        Glob::new(header)
+    } else {
+        Glob::new(&format!("/{header}"))
+    }
+}
+
+/// Finds the properties for a file, and caches the `.editorconfig` files it reads.
+#[derive(Default)]
+pub struct Resolver {
+    files: BTreeMap<PathBuf, Option<Rc<EditorConfig>>>,
+}
+
+impl Resolver {
+    /// Load the `.editorconfig` file in `dir`, if there is one.
+    fn load(&mut self, dir: &Path) -> Result<Option<Rc<EditorConfig>>> {
+        let path = dir.join(FILE_NAME);
+        if let Some(config) = self.files.get(&path) {
+            return Ok(config.clone());
+        }
+        let config = if path.is_file() {
+            let data = std::fs::read_to_string(&path).map_err(|err| {
+                Error::new(concat! {
+                    "Failed to read '"
+                    Doc::path(path.clone())
+                    "': "
+                    err.to_string()
+                })
+            })?;
+            Some(Rc::new(EditorConfig::parse(&data)))
+        } else {
+            None
+        };
+        self.files.insert(path, config.clone());
+        Ok(config)
+    }
+
+    /// Return the properties that apply to the file at the absolute `path`.
+    ///
+    /// Keys are lowercase, values are as written in the file.
+    pub fn properties(&mut self, path: &Path) -> Result<BTreeMap<String, String>> {
+        // Collect the files from the nearest one upwards, up to the root.
+        let mut configs = Vec::new();
+        for dir in path.ancestors().skip(1) {
+            if let Some(config) = self.load(dir)? {
+                let is_root = config.root;
+                configs.push((dir, config));
+                if is_root {
+                    break;
+                }
+            }
+        }
+
+        // Then apply them top-down, so nearer files take precedence.
+        let mut properties = BTreeMap::new();
+        for (dir, config) in configs.iter().rev() {
+            let Ok(relative) = path.strip_prefix(dir) else {
+                continue;
+            };
+            let mut relative_str = String::new();
+            for component in relative.components() {
+                relative_str.push('/');
+                relative_str.push_str(&component.as_os_str().to_string_lossy());
+            }
+            config.apply(&relative_str, &mut properties);
+        }
+        Ok(properties)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_section_pattern, EditorConfig};
+    use std::collections::BTreeMap;
+
+    fn glob(header: &str, path: &str) -> bool {
+        parse_section_pattern(header).matches(path)
+    }
+
+    #[test]
+    fn glob_matches_file_names_in_any_directory() {
+        assert!(glob("*", "/a.rcl"));
+        assert!(glob("*.rcl", "/a.rcl"));
+        assert!(glob("*.rcl", "/conf/deep/a.rcl"));
+        assert!(!glob("*.rcl", "/a.json"));
+        assert!(glob("*.{rcl,json}", "/conf/a.json"));
+    }
+
+    #[test]
+    fn glob_with_slash_is_relative_to_config_dir() {
+        assert!(glob("conf/*.rcl", "/conf/a.rcl"));
+        assert!(glob("/conf/*.rcl", "/conf/a.rcl"));
+        assert!(!glob("conf/*.rcl", "/sub/conf/a.rcl"));
+        assert!(!glob("conf/*.rcl", "/conf/deep/a.rcl"));
+        assert!(glob("conf/**.rcl", "/conf/deep/a.rcl"));
+    }
+
+    #[test]
+    fn parse_applies_sections_in_order() {
+        let config = EditorConfig::parse(
+            "root = true\n\
+            ; A comment.\n\
+            [*]\n\
+            indent_size = 2\n\
+            end_of_line = lf\n\
+            not a property\n\
+            [*.rcl]\n\
+            Indent_Size = 4\n",
+        );
+        assert!(config.root);
+        let mut properties = BTreeMap::new();
+        config.apply("/conf/a.rcl", &mut properties);
+        assert_eq!(properties["indent_size"], "4");
+        assert_eq!(properties["end_of_line"], "lf");
+        assert_eq!(properties.len(), 2);
+    }
+}
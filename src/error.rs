@@ -8,10 +8,12 @@
 //! Types and functions for error reporting.
 
 use crate::fmt_rcl::format_rcl;
+use crate::highlight::highlight_range;
 use crate::markup::Markup;
-use crate::pprint::{concat, Doc};
+use crate::pprint::{concat, Config, Doc};
 use crate::runtime::Value;
 use crate::source::{Inputs, Span};
+use crate::string::escape_json;
 
 pub type Result<T> = std::result::Result<T, Box<Error>>;
 
@@ -23,6 +25,54 @@ pub enum PathElement {
     Index(usize),
 }
 
+/// The class of an error, which determines the exit code of the program.
+///
+/// Scripts can use the exit code to react differently to different failures,
+/// without having to parse error messages.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum ErrorKind {
+    /// Evaluation failed, or any other error not covered by a different kind,
+    /// such as failing to read a file.
+    #[default]
+    Runtime,
+
+    /// The command line arguments are invalid.
+    Usage,
+
+    /// A document failed to lex or parse.
+    Parse,
+
+    /// A document failed to typecheck statically.
+    Type,
+
+    /// A `--check` found files that are not up to date.
+    Check,
+}
+
+impl ErrorKind {
+    /// Return the name of the kind, as used in json diagnostics.
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorKind::Runtime => "runtime",
+            ErrorKind::Usage => "usage",
+            ErrorKind::Parse => "parse",
+            ErrorKind::Type => "type",
+            ErrorKind::Check => "check",
+        }
+    }
+
+    /// Return the exit code that the program exits with for this kind of error.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorKind::Runtime => 1,
+            ErrorKind::Usage => 2,
+            ErrorKind::Parse => 3,
+            ErrorKind::Type => 4,
+            ErrorKind::Check => 5,
+        }
+    }
+}
+
 /// Any type of error that occurred in the program.
 ///
 /// Errors use [`Doc`] for pretty-printing them. This enables a few things:
@@ -79,6 +129,9 @@ pub struct Error {
     /// For example, when the user writes a `#`, we can explain that comments
     /// are written with `//` instead.
     pub help: Option<Doc<'static>>,
+
+    /// The class of the error, see [`ErrorKind`].
+    pub kind: ErrorKind,
 }
 
 impl Error {
@@ -95,9 +148,16 @@ impl Error {
             path: Vec::new(),
             notes: Vec::new(),
             help: None,
+            kind: ErrorKind::Runtime,
         }
     }
 
+    /// Replace the kind of the error.
+    pub fn with_kind(mut self, kind: ErrorKind) -> Error {
+        self.kind = kind;
+        self
+    }
+
     /// Replace the origin of the error with the given span.
     pub fn with_origin(mut self, origin: Span) -> Error {
         self.origin = Some(origin);
@@ -285,6 +345,62 @@ impl Error {
 
         Doc::Concat(result)
     }
+
+    /// Format the error as a json object on a single line.
+    ///
+    /// This is the machine-readable counterpart of [`Error::report`], intended
+    /// for tools that consume diagnostics. Fields that do not apply to the
+    /// error are `null`, or an empty list.
+    pub fn report_json(&self, inputs: &Inputs) -> String {
+        let mut out = String::new();
+        out.push_str(r#"{"severity": "error", "kind": ""#);
+        out.push_str(self.kind.name());
+        out.push_str(r#"", "message": "#);
+        push_json_doc(&mut out, &self.message);
+
+        out.push_str(r#", "body": "#);
+        match &self.body {
+            Some(body) => push_json_doc(&mut out, body),
+            None => out.push_str("null"),
+        }
+
+        out.push_str(", ");
+        match self.origin {
+            Some(span) => push_json_location(&mut out, inputs, span),
+            None => out.push_str(r#""file": null, "span": null"#),
+        }
+
+        // The value path is stored innermost first, but we report it from the
+        // root of the value down, the order in which you would index it.
+        out.push_str(r#", "path": ["#);
+        for (i, elem) in self.path.iter().rev().enumerate() {
+            if i > 0 {
+                out.push_str(", ");
+            }
+            match elem {
+                PathElement::Key(k) => {
+                    out.push_str(r#"{"key": "#);
+                    push_json_doc(&mut out, &format_rcl(k));
+                    out.push('}');
+                }
+                PathElement::Index(i) => out.push_str(&format!(r#"{{"index": {i}}}"#)),
+            }
+        }
+
+        out.push_str(r#"], "notes": "#);
+        push_json_notes(&mut out, inputs, &self.notes);
+
+        out.push_str(r#", "help": "#);
+        match &self.help {
+            Some(help) => push_json_doc(&mut out, help),
+            None => out.push_str("null"),
+        }
+
+        out.push_str(r#", "call_stack": "#);
+        push_json_notes(&mut out, inputs, &self.call_stack);
+        out.push('}');
+        out
+    }
 }
 
 pub trait IntoError {
@@ -305,6 +421,51 @@ impl IntoError for Span {
     }
 }
 
+/// Append a json string literal with the contents of a document, without markup.
+fn push_json_doc(out: &mut String, doc: &Doc) {
+    let text = doc.println(&Config::default()).to_string_no_markup();
+    out.push('"');
+    escape_json(text.trim_end(), out);
+    out.push('"');
+}
+
+/// Append a json object with the file and source location of a span.
+///
+/// Lines and columns start at 1, and columns count bytes, as in the human
+/// readable format. Offsets are byte offsets from the start of the file.
+fn push_json_location(out: &mut String, inputs: &Inputs, span: Span) {
+    let doc = &inputs[span.doc().0 as usize];
+    let position = |offset: usize| {
+        let before = &doc.data[..offset];
+        let line = 1 + before.matches('\n').count();
+        let column = 1 + offset - before.rfind('\n').map_or(0, |i| i + 1);
+        format!(r#"{{"line": {line}, "column": {column}, "offset": {offset}}}"#)
+    };
+    out.push_str(r#""file": ""#);
+    escape_json(doc.name, out);
+    out.push_str(r#"", "span": {"start": "#);
+    out.push_str(&position(span.start()));
+    out.push_str(r#", "end": "#);
+    out.push_str(&position(span.end()));
+    out.push('}');
+}
+
+/// Append a json list of messages at source locations.
+fn push_json_notes(out: &mut String, inputs: &Inputs, notes: &[(Span, Doc)]) {
+    out.push('[');
+    for (i, (span, message)) in notes.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        out.push_str(r#"{"message": "#);
+        push_json_doc(out, message);
+        out.push_str(", ");
+        push_json_location(out, inputs, *span);
+        out.push('}');
+    }
+    out.push(']');
+}
+
 /// Highlight a span in a line.
 pub fn highlight_span<'a>(inputs: &'a Inputs, span: Span, markup: Markup) -> Doc<'a> {
     use std::cmp;
@@ -369,6 +530,7 @@ pub fn highlight_span<'a>(inputs: &'a Inputs, span: Span, markup: Markup) -> Doc
     }
 
     let line_content = &input[line_start..line_end];
+    let line_doc = highlight_range(span.doc(), input, line_start, line_end);
 
     // The length of the mark can be longer than the line, for example when
     // token to mark was a multiline string literal. In that case, highlight
@@ -389,11 +551,11 @@ pub fn highlight_span<'a>(inputs: &'a Inputs, span: Span, markup: Markup) -> Doc
     let doc_under = concat! { "^" mark_under };
 
     concat! {
-        doc.name format!(":{line}:{column}")
+        Doc::from(format!("{}:{line}:{column}", doc.name)).with_markup(Markup::Link)
         Doc::HardBreak
         line_num_pad.clone() " " Doc::from("╷").with_markup(markup)
         Doc::HardBreak
-        line_num_str " " Doc::from("│").with_markup(markup) " " trunc_prefix line_content trunc_suffix
+        line_num_str " " Doc::from("│").with_markup(markup) " " trunc_prefix line_doc trunc_suffix
         Doc::HardBreak
         line_num_pad " " Doc::from("╵").with_markup(markup) " " mark_indent doc_under.with_markup(markup)
         Doc::HardBreak
@@ -10,11 +10,14 @@
 use std::collections::HashMap;
 use std::collections::{BTreeMap, BTreeSet};
 use std::rc::Rc;
+use std::time::Instant;
 
 use crate::ast::{BinOp, CallArg, Expr, FormatFragment, Seq, Stmt, UnOp, Yield};
+use crate::decimal::Decimal;
 use crate::error::{Error, IntoError, Result};
 use crate::fmt_rcl::{self, format_rcl};
 use crate::loader::Loader;
+use crate::markup::Markup;
 use crate::pprint::{concat, indent, Doc};
 use crate::runtime::{
     self, BuiltinFunction, BuiltinMethod, Env, Function, FunctionCall, MethodCall, MethodInstance,
@@ -22,7 +25,8 @@ use crate::runtime::{
 };
 use crate::source::{DocId, Span};
 use crate::stdlib;
-use crate::tracer::Tracer;
+use crate::tracer::{Phase, Tracer};
+use crate::type_diff;
 use crate::typecheck;
 use crate::types;
 
@@ -65,6 +69,14 @@ impl EvalCount {
     /// Increment the count, return an error if the budget is exceeded.
     #[inline]
     pub fn inc(&mut self, at: Span) -> Result<()> {
+        self.inc_by(at, 1)
+    }
+
+    /// Add `n` steps to the count, return an error if the budget is exceeded.
+    ///
+    /// Builtins that produce a collection use this to charge one step per
+    /// element up front, before they allocate it.
+    pub fn inc_by(&mut self, at: Span, n: usize) -> Result<()> {
         if at > self.span {
             self.span = at;
             self.count = 0;
@@ -92,7 +104,8 @@ impl EvalCount {
         #[cfg(all(not(fuzzing), not(debug_assertions)))]
         let max_steps = 10_000_000;
 
-        self.count += 1;
+        let n = u32::try_from(n).unwrap_or(u32::MAX);
+        self.count = self.count.saturating_add(n);
 
         if self.count >= max_steps {
             return at
@@ -200,17 +213,47 @@ impl<'a> Evaluator<'a> {
         doc: DocId,
     ) -> Result<Value> {
         debug_assert!(self.import_stack.is_empty());
-        let expr = self.loader.get_typechecked_ast(type_env, doc)?;
+        let expr = self.get_typechecked_ast(type_env, doc)?;
         let ctx = EvalContext {
             doc,
             imported_from: None,
         };
         self.import_stack.push(ctx);
-        let result = self.eval_expr(value_env, &expr)?;
+        let result = self.eval_traced(value_env, doc, &expr)?;
         self.import_stack.pop().expect("Push/pop are balanced.");
         Ok(result)
     }
 
+    /// Lex, parse, and typecheck a document, and report the phases to the tracer.
+    fn get_typechecked_ast(&mut self, type_env: &mut typecheck::Env, doc: DocId) -> Result<Expr> {
+        let span = self.loader.get_span(doc);
+        self.tracer.enter(Phase::Lex, span);
+        let tokens = self.loader.get_tokens(doc);
+        self.tracer.exit();
+        let tokens = tokens?;
+
+        self.tracer.enter(Phase::Parse, span);
+        let expr = self.loader.get_unchecked_ast_from_tokens(doc, &tokens);
+        self.tracer.exit();
+        let mut expr = expr?;
+
+        self.tracer.enter(Phase::Typecheck, span);
+        let result = Loader::typecheck_ast(type_env, span, &mut expr);
+        self.tracer.exit();
+        result?;
+
+        Ok(expr)
+    }
+
+    /// Evaluate the body of a document, and report that to the tracer.
+    fn eval_traced(&mut self, env: &mut Env, doc: DocId, expr: &Expr) -> Result<Value> {
+        self.tracer
+            .enter(Phase::Evaluate, self.loader.get_span(doc));
+        let result = self.eval_expr(env, expr);
+        self.tracer.exit();
+        result
+    }
+
     /// Evaluate a document for an import.
     fn eval_import(&mut self, doc: DocId, imported_from: Span) -> Result<Value> {
         // Before we allow the import, check that this would not create a cycle.
@@ -239,22 +282,44 @@ impl<'a> Evaluator<'a> {
             return Err(err.into());
         }
 
+        // Data documents cannot import anything, so they do not go on the
+        // import stack.
+        if let Some(parse_data) = self.loader.get_data_parser(doc) {
+            self.tracer.enter(Phase::Import, self.loader.get_span(doc));
+            self.tracer.enter(Phase::Parse, self.loader.get_span(doc));
+            let result = parse_data(self.loader, doc);
+            self.tracer.exit();
+            self.tracer.exit();
+            if let Ok(value) = &result {
+                self.loader.record_import_result(doc, value);
+            }
+            return result;
+        }
+
         // Evaluate the import in its own clean environment, it should not be
         // affected by the surrounding environment of the import statement.
         let mut type_env = typecheck::prelude();
         let mut value_env = runtime::prelude();
 
-        let expr = self.loader.get_typechecked_ast(&mut type_env, doc)?;
-        let ctx = EvalContext {
-            doc,
-            imported_from: Some(imported_from),
-        };
-
-        self.import_stack.push(ctx);
-        let result = self.eval_expr(&mut value_env, &expr)?;
-        self.import_stack.pop().expect("Push/pop are balanced.");
+        self.tracer.enter(Phase::Import, self.loader.get_span(doc));
+        let result = self
+            .get_typechecked_ast(&mut type_env, doc)
+            .and_then(|expr| {
+                let ctx = EvalContext {
+                    doc,
+                    imported_from: Some(imported_from),
+                };
+                self.import_stack.push(ctx);
+                let result = self.eval_traced(&mut value_env, doc, &expr)?;
+                self.import_stack.pop().expect("Push/pop are balanced.");
+                Ok(result)
+            });
+        self.tracer.exit();
 
-        Ok(result)
+        if let Ok(value) = &result {
+            self.loader.record_import_result(doc, value);
+        }
+        result
     }
 
     fn eval_expr(&mut self, env: &mut Env, expr: &Expr) -> Result<Value> {
@@ -284,6 +349,7 @@ impl<'a> Evaluator<'a> {
                     }
                 };
                 let from = Some(path_span.doc());
+                let start = Instant::now();
                 let doc = self
                     .loader
                     .load_path(path.as_ref(), from)
@@ -293,6 +359,8 @@ impl<'a> Evaluator<'a> {
                         }
                         err
                     })?;
+                self.tracer
+                    .record(Phase::Read, self.loader.get_span(doc), start);
                 self.eval_import(doc, *path_span)
             }
 
@@ -348,6 +416,8 @@ impl<'a> Evaluator<'a> {
 
             Expr::IntegerLit(i) => Ok(Value::Int(*i)),
 
+            Expr::NumberLit(d) => Ok(Value::Number(*d)),
+
             Expr::StringLit(s) => Ok(Value::String(s.clone())),
 
             Expr::Format(fragments) => self.eval_format(env, fragments),
@@ -390,6 +460,15 @@ impl<'a> Evaluator<'a> {
                 self.dec_eval_depth();
                 let field_name_value = Value::String(field_name.0.clone());
 
+                // Without this, `std.string.len` would be `Dict.len`.
+                if let Value::Dict(fields) = &inner {
+                    if stdlib::is_module(&inner) {
+                        if let Some(member) = fields.get(&field_name_value) {
+                            return Ok(member.clone());
+                        }
+                    }
+                }
+
                 let builtin = match (&inner, field_name.as_ref()) {
                     (Value::String(_), "chars") => Some(&stdlib::STRING_CHARS),
                     (Value::String(_), "contains") => Some(&stdlib::STRING_CONTAINS),
@@ -609,6 +688,7 @@ impl<'a> Evaluator<'a> {
     ) -> Result<Value> {
         let call_open = call.call_open;
         self.inc_eval_depth(call_open)?;
+        self.tracer.enter(Phase::Call, callee_span);
 
         let result = match callee {
             Value::BuiltinMethod(instance) => {
@@ -659,6 +739,7 @@ impl<'a> Evaluator<'a> {
                 .err(),
         };
 
+        self.tracer.exit();
         self.dec_eval_depth();
 
         result
@@ -690,6 +771,7 @@ impl<'a> Evaluator<'a> {
         match value {
             Value::Bool(b) => out.push((if *b { "true" } else { "false" }).into()),
             Value::Int(i) => out.push(i.to_string().into()),
+            Value::Number(d) => out.push(d.to_string().into()),
             Value::Null => out.push("null".into()),
             Value::String(s) => out.push(s.clone()),
             not_formattable => {
@@ -767,7 +849,7 @@ impl<'a> Evaluator<'a> {
 
         let i = match i_signed {
             _ if i_signed >= 0 && (i_signed as usize) < list.len() => i_signed as usize,
-            _ if i_signed >= -(list.len() as i64) && i_signed < 0 => {
+            _ if i_signed >= -(list.len() as i128) && i_signed < 0 => {
                 list.len() - (-i_signed as usize)
             }
             _ => {
@@ -823,6 +905,15 @@ impl<'a> Evaluator<'a> {
                     op_span.error(err).err()
                 }
             },
+            (UnOp::Neg, Value::Number(x)) => match x.checked_neg() {
+                Some(nx) => Ok(Value::Number(nx)),
+                None => {
+                    let err = concat! {
+                        "Negation of " x.to_string() " would overflow."
+                    };
+                    op_span.error(err).err()
+                }
+            },
             _ => unreachable!("Invalid cases are prevented by the typechecker."),
         }
     }
@@ -845,6 +936,23 @@ impl<'a> Evaluator<'a> {
                 result.extend(ys.iter().cloned());
                 Ok(Value::Set(Rc::new(result)))
             }
+            // An empty dict on the left is likely intended to be an empty set.
+            (BinOp::Union, Value::Dict(xs), ys @ (Value::Set(..) | Value::List(..)))
+                if xs.is_empty() =>
+            {
+                let rhs_name = if matches!(ys, Value::Set(..)) {
+                    "set"
+                } else {
+                    "list"
+                };
+                op_span
+                    .error(concat! {
+                        "Union operator " Doc::highlight("|")
+                        " is not supported between a dict and a " rhs_name "."
+                    })
+                    .with_help(type_diff::help_empty_set())
+                    .err()
+            }
             (BinOp::Union, _, _) => {
                 // We could make a nicer error and include the values, but I plan
                 // to remove | in favor of unpack, so I'm not going to bother.
@@ -892,26 +1000,34 @@ impl<'a> Evaluator<'a> {
                 if y == 0 {
                     op_span.error("Division by zero.").err()
                 } else {
-                    // For division, the result may not be an integer. In that case,
-                    // probably the right thing to do is to add rational numbers as
-                    // values and make the result a rational. However, I don't want
-                    // to implement all of that right now, so the conservative thing
-                    // to do is to only allow division when it results in an integer.
-                    // If we'd choose integer division now, it would be a subtle
-                    // change of behavior later.
+                    // For division, the result may not be an integer. Division
+                    // of two integers should produce an integer, so we only
+                    // allow it when the result is exact. Integer division that
+                    // rounds would be a subtle source of bugs, and silently
+                    // switching to a number would make the result type depend
+                    // on the values.
                     let q = x / y;
                     if q * y == x {
                         Ok(Value::Int(q))
                     } else {
                         let err = concat! {
                             "Non-integer division: "
-                            x.to_string() " is not a multiple of " y.to_string()
-                            ". Non-integer division is not supported at this time."
+                            x.to_string() " is not a multiple of " y.to_string() "."
                         };
-                        op_span.error(err).err()
+                        let help = concat! {
+                            "To get a non-integer result, make one of the operands a number, e.g. '"
+                            Doc::from(format!("{x}.0 / {y}")).with_markup(Markup::Highlight) "'."
+                        };
+                        op_span.error(err).with_help(help).err()
                     }
                 }
             }
+            // As soon as a number is involved, the integers are promoted.
+            (
+                op @ (BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div),
+                x @ (Value::Int(..) | Value::Number(..)),
+                y @ (Value::Int(..) | Value::Number(..)),
+            ) => self.eval_binop_number(op, op_span, &x, &y),
             // We allow comparing any two values, even if they are not of the
             // same type. I would prefer to make nonsensical comparisons a type
             // error (e.g. `1 < "2"` should return "Int and String incomparable",
@@ -929,6 +1045,35 @@ impl<'a> Evaluator<'a> {
         }
     }
 
+    fn eval_binop_number(
+        &mut self,
+        op: BinOp,
+        op_span: Span,
+        x: &Value,
+        y: &Value,
+    ) -> Result<Value> {
+        let dx = x.as_decimal().expect("Caller ensures numeric values.");
+        let dy = y.as_decimal().expect("Caller ensures numeric values.");
+        let (result, name, symbol) = match op {
+            BinOp::Add => (dx.checked_add(dy), "Addition", "+"),
+            BinOp::Sub => (dx.checked_sub(dy), "Subtraction", "-"),
+            BinOp::Mul => (dx.checked_mul(dy), "Multiplication", "*"),
+            BinOp::Div if dy == Decimal::ZERO => return op_span.error("Division by zero.").err(),
+            BinOp::Div => (dx.checked_div(dy), "Division", "/"),
+            _ => unreachable!("Caller ensures arithmetic operators."),
+        };
+        match result {
+            Some(z) => Ok(Value::Number(z)),
+            None => {
+                let err = concat! {
+                    name " " format_rcl(x).into_owned() " " symbol " "
+                    format_rcl(y).into_owned() " would overflow."
+                };
+                op_span.error(err).err()
+            }
+        }
+    }
+
     fn eval_stmt(&mut self, env: &mut Env, stmt: &Stmt) -> Result<()> {
         match stmt {
             Stmt::Let { ident, value, .. } => {
@@ -18,9 +18,29 @@ use crate::pprint::{concat, flush_indent, group, indent, Doc};
 use crate::source::Span;
 use crate::string;
 
+/// When to put a comma after the last element of a collection or argument list.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum TrailingComma {
+    /// Add a trailing comma when the elements are formatted on separate lines.
+    #[default]
+    Multiline,
+
+    /// Only add a trailing comma when it is needed before a comment.
+    Never,
+}
+
 /// Format a document.
 pub fn format_expr<'a>(input: &'a str, expr: &'a Expr) -> Doc<'a> {
-    Formatter::new(input).expr(expr)
+    format_expr_with(input, expr, TrailingComma::default())
+}
+
+/// Format a document, with the given trailing comma policy.
+pub fn format_expr_with<'a>(
+    input: &'a str,
+    expr: &'a Expr,
+    trailing_comma: TrailingComma,
+) -> Doc<'a> {
+    Formatter::new(input, trailing_comma).expr(expr)
 }
 
 /// Helper so we can use methods for resolving spans against the input.
@@ -28,11 +48,23 @@ struct Formatter<'a> {
     // TODO: This could all be more efficient if we resolved on bytestrings, so
     // the code point slicing check can be omitted.
     input: &'a str,
+    trailing_comma: TrailingComma,
 }
 
 impl<'a> Formatter<'a> {
-    pub fn new(input: &'a str) -> Self {
-        Self { input }
+    pub fn new(input: &'a str, trailing_comma: TrailingComma) -> Self {
+        Self {
+            input,
+            trailing_comma,
+        }
+    }
+
+    /// A comma that is only present in tall mode, if the policy allows it.
+    fn tall_comma(&self) -> Doc<'a> {
+        match self.trailing_comma {
+            TrailingComma::Multiline => Doc::tall(","),
+            TrailingComma::Never => Doc::Empty,
+        }
     }
 
     /// Format the span as-is. It should not contain newlines.
@@ -71,9 +103,13 @@ impl<'a> Formatter<'a> {
         // would fit in wide mode, we force it to be tall, to give the user some
         // control over wide/tall. This is inspired by Black's "magic trailing comma":
         // https://black.readthedocs.io/en/stable/the_black_code_style/current_style.html#the-magic-trailing-comma
+        // When the policy drops trailing commas, the comma would be gone after
+        // formatting once, so it cannot force the collection tall, or a second
+        // formatting pass would make it wide again.
+        let keeps_comma = self.trailing_comma == TrailingComma::Multiline;
         match list.elements.len() {
             0 => None,
-            _ if list.trailing_comma => Some(Doc::HardBreak),
+            _ if list.trailing_comma && keeps_comma => Some(Doc::HardBreak),
             _ => Some(Doc::SoftBreak),
         }
     }
@@ -99,7 +135,10 @@ impl<'a> Formatter<'a> {
                     result.push(Doc::HardBreak);
                 }
                 NonCode::LineComment(span) => {
-                    result.push(self.span(*span).with_markup(Markup::Comment));
+                    // Keep trailing spaces, they may be significant, for
+                    // example in Markdown.
+                    let comment = self.span(*span).with_markup(Markup::Comment);
+                    result.push(Doc::NoTrim(Box::new(comment)));
                     result.push(Doc::HardBreak);
                 }
                 NonCode::Shebang(span) => {
@@ -116,6 +155,15 @@ impl<'a> Formatter<'a> {
     ///
     /// Also includes the soft break after the comma.
     pub fn trailing_comma<T>(&self, list: &List<T>) -> Doc<'a> {
+        // Without elements there is nothing to separate, and a lone comma
+        // would be a syntax error.
+        if !list.suffix.is_empty() && list.elements.is_empty() {
+            return concat! {
+                Doc::SoftBreak
+                self.non_code(&list.suffix)
+            };
+        }
+
         if !list.suffix.is_empty() {
             return concat! {
                 ","
@@ -129,7 +177,7 @@ impl<'a> Formatter<'a> {
         }
 
         concat! {
-            Doc::tall(",")
+            self.tall_comma()
             Doc::SoftBreak
         }
     }
@@ -171,33 +219,18 @@ impl<'a> Formatter<'a> {
     ///
     /// The line contents should not contain `\n`.
     fn push_string_line(&self, line: &'a str, out: &mut Vec<Doc<'a>>) {
-        let mut line = line;
-        let mut n_trailing_spaces = 0_u32;
-
-        // If there are trailing spaces, the pretty-printer would eat them.
-        // We can (and maybe should) fix this in the pretty printer, but
-        // trailing spaces are a hazard to humans too, so instead of
-        // preserving them verbatim, we escape them to make them visible.
-        // This has the nice side effects of removing trailing whitespace,
-        // so it doesn't get eaten.
-        while !line.is_empty() && line.as_bytes()[line.len() - 1] == b' ' {
-            line = &line[..line.len() - 1];
-            n_trailing_spaces += 1;
-        }
-
+        // Trailing spaces in the string are significant, so we must prevent
+        // the pretty-printer from trimming them at the end of the line.
         if !line.is_empty() {
-            out.push(Doc::str(line).with_markup(Markup::String));
-        }
-
-        for _ in 0..n_trailing_spaces {
-            out.push(Doc::str(r"\u0020").with_markup(Markup::Escape));
+            let line = Doc::str(line).with_markup(Markup::String);
+            out.push(Doc::NoTrim(Box::new(line)));
         }
     }
 
     /// Format a `"""` or `f"""` quoted string or format string.
     fn string_triple(&self, open: &'static str, parts: &[StringPart]) -> Doc<'a> {
         let n_strip = string::count_common_leading_spaces(self.input, parts);
-        let mut result = vec![Doc::str(open).with_markup(Markup::String)];
+        let mut result = Vec::new();
 
         for (i, part) in parts.iter().enumerate() {
             match part {
@@ -240,7 +273,13 @@ impl<'a> Formatter<'a> {
 
         result.push(Doc::str("\"\"\"").with_markup(Markup::String));
 
-        flush_indent! { Doc::Concat(result) }
+        // The evaluator strips the leading spaces that lines have in common,
+        // so the lines must be indented with spaces, also when using tabs, or
+        // else reformatting would change the value of the string.
+        flush_indent! {
+            Doc::str(open).with_markup(Markup::String)
+            Doc::SpaceIndent(Box::new(Doc::Concat(result)))
+        }
     }
 
     pub fn prefixed_type(&self, type_: &Prefixed<Type>) -> Doc<'a> {
@@ -584,9 +623,9 @@ impl<'a> Formatter<'a> {
                 // but only rarely are there multiple seqs in the collection.
                 _ if seqs.elements.len() == 1 => match seqs.elements[0].inner.is_comprehension() {
                     true => Doc::Empty,
-                    false => Doc::tall(","),
+                    false => self.tall_comma(),
                 },
-                _ if is_last => Doc::tall(","),
+                _ if is_last => self.tall_comma(),
                 _ => Doc::str(","),
             };
             result.push(sep_doc);
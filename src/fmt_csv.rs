@@ -0,0 +1,354 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Formatters that print lists of records as CSV or TSV tables.
+//!
+//! The top-level value must be a list (or set) of rows. When the rows are
+//! dicts, the header is the union of their keys, and a record that lacks a key
+//! gets an empty cell. When the rows are lists, there is no header. Cells must
+//! be strings, integers, booleans, or null, which becomes an empty cell.
+//!
+//! For CSV we quote fields as described in RFC 4180, see
+//! <https://www.rfc-editor.org/rfc/rfc4180>, but we end lines in `\n` rather
+//! than `\r\n`, like the other formats. TSV fields cannot contain tabs or line
+//! breaks, so there we use backslash escapes, like PostgreSQL and most other
+//! tools that produce TSV.
+
+use std::collections::BTreeSet;
+
+use crate::error::{IntoError, PathElement, Result};
+use crate::fmt_rcl::format_rcl;
+use crate::markup::Markup;
+use crate::pprint::{concat, Doc};
+use crate::runtime::Value;
+use crate::source::Span;
+
+/// Which tabular format to output.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Dialect {
+    Csv,
+    Tsv,
+}
+
+impl Dialect {
+    fn name(self) -> &'static str {
+        match self {
+            Dialect::Csv => "CSV",
+            Dialect::Tsv => "TSV",
+        }
+    }
+
+    fn separator(self) -> &'static str {
+        match self {
+            Dialect::Csv => ",",
+            Dialect::Tsv => "\t",
+        }
+    }
+
+    /// Quote or escape a field, if needed.
+    fn field(self, s: &str) -> String {
+        match self {
+            // Spaces are part of the field, but some readers trim unquoted ones.
+            Dialect::Csv
+                if s.trim() != s || s.contains(|ch| matches!(ch, ',' | '"' | '\n' | '\r')) =>
+            {
+                format!("\"{}\"", s.replace('"', "\"\""))
+            }
+            Dialect::Csv => s.to_string(),
+            Dialect::Tsv => {
+                let mut into = String::with_capacity(s.len());
+                for ch in s.chars() {
+                    match ch {
+                        '\\' => into.push_str("\\\\"),
+                        '\t' => into.push_str("\\t"),
+                        '\n' => into.push_str("\\n"),
+                        '\r' => into.push_str("\\r"),
+                        _ => into.push(ch),
+                    }
+                }
+                into
+            }
+        }
+    }
+}
+
+/// Render a value as CSV table.
+///
+/// When `require_same_keys` is set, all records must have the same keys,
+/// rather than taking the union.
+pub fn format_csv(caller: Span, require_same_keys: bool, v: &Value) -> Result<Doc> {
+    Formatter::new(caller, Dialect::Csv, require_same_keys).table(v)
+}
+
+/// Render a value as TSV table, see also [`format_csv`].
+pub fn format_tsv(caller: Span, require_same_keys: bool, v: &Value) -> Result<Doc> {
+    Formatter::new(caller, Dialect::Tsv, require_same_keys).table(v)
+}
+
+/// Helper for formatting values as tables.
+///
+/// The formatter tracks the path in the value that we are formatting from, such
+/// that we can report the location of an error, in case an error occurs.
+struct Formatter {
+    /// The source location where formatting was triggered from.
+    caller: Span,
+
+    /// Where we currently are in the value to be formatted.
+    path: Vec<PathElement>,
+
+    dialect: Dialect,
+
+    /// Whether all rows must have the same keys, or the same length for lists.
+    require_same_keys: bool,
+}
+
+impl Formatter {
+    fn new(caller: Span, dialect: Dialect, require_same_keys: bool) -> Formatter {
+        Formatter {
+            caller,
+            path: Vec::new(),
+            dialect,
+            require_same_keys,
+        }
+    }
+
+    /// Report an error at the current value path.
+    fn error<T>(&mut self, message: Doc<'static>) -> Result<T> {
+        // Steal the path from the formatter and move it into the error. We have
+        // to leave an empty path in its place. This is fine, because returning
+        // the error prevents further formatting.
+        let path = std::mem::take(&mut self.path);
+        self.caller.error(message).with_path(path).err()
+    }
+
+    fn table<'a>(&mut self, v: &'a Value) -> Result<Doc<'a>> {
+        let rows: Vec<&Value> = match v {
+            Value::List(rows) => rows.iter().collect(),
+            Value::Set(rows) => rows.iter().collect(),
+            _ => {
+                let message = concat! {
+                    "To format as "
+                    self.dialect.name()
+                    ", the top-level value must be a list of rows."
+                };
+                return self.error(message);
+            }
+        };
+        match rows.first() {
+            None => Ok(Doc::Empty),
+            Some(Value::Dict(..)) => self.records(&rows),
+            Some(Value::List(..)) => self.lists(&rows),
+            Some(_) => {
+                self.path.push(PathElement::Index(0));
+                self.error("Rows must be dicts or lists.".into())
+            }
+        }
+    }
+
+    /// Format rows of dicts, with a header line.
+    fn records(&mut self, rows: &[&Value]) -> Result<Doc<'static>> {
+        let mut header: BTreeSet<&str> = BTreeSet::new();
+        let mut first_keys: Option<BTreeSet<&str>> = None;
+
+        for (i, row) in rows.iter().enumerate() {
+            self.path.push(PathElement::Index(i));
+            let kv = match row {
+                Value::Dict(kv) => kv,
+                _ => {
+                    return self
+                        .error("All rows must be dicts when the first row is a dict.".into())
+                }
+            };
+            let mut keys = BTreeSet::new();
+            for k in kv.keys() {
+                match k {
+                    Value::String(k_str) => keys.insert(k_str.as_ref()),
+                    _ => {
+                        self.path.push(PathElement::Key(k.clone()));
+                        let message = concat! {
+                            "To export as " self.dialect.name() ", keys must be strings."
+                        };
+                        return self.error(message);
+                    }
+                };
+            }
+            match &first_keys {
+                Some(first) if self.require_same_keys && *first != keys => {
+                    return self.error_different_keys(first, &keys);
+                }
+                Some(..) => {}
+                None => first_keys = Some(keys.clone()),
+            }
+            header.extend(keys);
+            self.path.pop().expect("Push and pop are balanced.");
+        }
+
+        // Without columns, the header would be an empty line, which readers
+        // skip, so there is no way to represent the table.
+        if header.is_empty() {
+            let message = concat! {
+                "To export as " self.dialect.name() ", records must have at least one key."
+            };
+            return self.error(message);
+        }
+
+        let mut lines = Vec::new();
+        let header_cells = header.iter().map(|k| (k.to_string(), Markup::Field));
+        lines.push(self.line(header_cells.collect()));
+
+        for (i, row) in rows.iter().enumerate() {
+            let kv = match row {
+                Value::Dict(kv) => kv,
+                _ => unreachable!("We checked above that all rows are dicts."),
+            };
+            self.path.push(PathElement::Index(i));
+            let mut cells = Vec::with_capacity(header.len());
+            for k in header.iter() {
+                let key = Value::from(*k);
+                cells.push(match kv.get(&key) {
+                    Some(v) => {
+                        self.path.push(PathElement::Key(key));
+                        let cell = self.cell(v)?;
+                        self.path.pop().expect("Push and pop are balanced.");
+                        cell
+                    }
+                    None => (String::new(), Markup::None),
+                });
+            }
+            self.path.pop().expect("Push and pop are balanced.");
+            lines.push(self.line(cells));
+        }
+
+        Ok(Doc::join(lines.into_iter(), Doc::HardBreak))
+    }
+
+    fn error_different_keys<T>(
+        &mut self,
+        first: &BTreeSet<&str>,
+        keys: &BTreeSet<&str>,
+    ) -> Result<T> {
+        let (key, is_missing) = match first.difference(keys).next() {
+            Some(missing) => (missing, true),
+            None => (
+                keys.difference(first)
+                    .next()
+                    .expect("If the sets are not equal, one has a key that the other lacks."),
+                false,
+            ),
+        };
+        let message = concat! {
+            "This record has different keys than the first record: "
+            if is_missing { "it lacks " } else { "it has an extra key " }
+            format_rcl(&Value::from(*key)).into_owned()
+            "."
+        };
+        let path = std::mem::take(&mut self.path);
+        self.caller
+            .error(message)
+            .with_path(path)
+            .with_help("Without --require-same-keys, the header is the union of all keys.")
+            .err()
+    }
+
+    /// Format rows of lists, without a header line.
+    fn lists(&mut self, rows: &[&Value]) -> Result<Doc<'static>> {
+        let mut lines = Vec::new();
+        let mut first_len: Option<usize> = None;
+        for (i, row) in rows.iter().enumerate() {
+            self.path.push(PathElement::Index(i));
+            let cells = match row {
+                Value::List(cells) => cells,
+                _ => {
+                    return self
+                        .error("All rows must be lists when the first row is a list.".into())
+                }
+            };
+            match first_len {
+                Some(n) if self.require_same_keys && n != cells.len() => {
+                    let message = concat! {
+                        "This row has length " cells.len().to_string()
+                        ", but the first row has length " n.to_string() "."
+                    };
+                    return self.error(message);
+                }
+                Some(..) => {}
+                None => first_len = Some(cells.len()),
+            }
+            // If the first row is empty, the table would start with an empty
+            // line, which readers skip, so the table has no columns.
+            if i == 0 && cells.is_empty() {
+                let message = concat! {
+                    "To export as " self.dialect.name() ", the first row must have at least one cell."
+                };
+                return self.error(message);
+            }
+            let mut docs = Vec::with_capacity(cells.len());
+            for (j, cell) in cells.iter().enumerate() {
+                self.path.push(PathElement::Index(j));
+                docs.push(self.cell(cell)?);
+                self.path.pop().expect("Push and pop are balanced.");
+            }
+            self.path.pop().expect("Push and pop are balanced.");
+            lines.push(self.line(docs));
+        }
+        Ok(Doc::join(lines.into_iter(), Doc::HardBreak))
+    }
+
+    /// Format one line of the table, quoting or escaping the cells as needed.
+    fn line(&self, cells: Vec<(String, Markup)>) -> Doc<'static> {
+        // A line with a single empty field would be an empty line, which
+        // readers skip, so in that case we quote it, like Python does.
+        if let (Dialect::Csv, [(cell, _)]) = (self.dialect, &cells[..]) {
+            if cell.is_empty() {
+                return Doc::str("\"\"");
+            }
+        }
+        let cells = cells.iter().map(|(cell, markup)| {
+            Doc::lines(&self.dialect.field(cell))
+                .into_owned()
+                .with_markup(*markup)
+        });
+        Doc::join(cells, Doc::str(self.dialect.separator()))
+    }
+
+    /// Return the contents of the cell, before quoting, and its markup.
+    fn cell(&mut self, v: &Value) -> Result<(String, Markup)> {
+        let result = match v {
+            Value::Null => (String::new(), Markup::None),
+            Value::Bool(b) => (b.to_string(), Markup::Keyword),
+            Value::Int(i) => (i.to_string(), Markup::Number),
+            Value::Number(d) => (d.to_string(), Markup::Number),
+            Value::String(s) => (s.to_string(), Markup::String),
+            _ => {
+                let message = concat! {
+                    "To export as "
+                    self.dialect.name()
+                    ", cells must be strings, numbers, booleans, or null, but got: "
+                    format_rcl(v).into_owned()
+                };
+                return self.error(message);
+            }
+        };
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Dialect;
+
+    #[test]
+    fn field_quotes_csv_and_escapes_tsv() {
+        assert_eq!(Dialect::Csv.field("plain text"), "plain text");
+        assert_eq!(Dialect::Csv.field("a,b"), "\"a,b\"");
+        assert_eq!(Dialect::Csv.field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(Dialect::Csv.field("two\nlines"), "\"two\nlines\"");
+        assert_eq!(Dialect::Csv.field(" padded"), "\" padded\"");
+        assert_eq!(Dialect::Tsv.field("a,b"), "a,b");
+        assert_eq!(Dialect::Tsv.field("a\tb\nc\\d"), "a\\tb\\nc\\\\d");
+    }
+}
@@ -0,0 +1,289 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Formatter that prints values as HCL attributes, such as Terraform `.tfvars`.
+//!
+//! The top-level value must be a dict, it becomes a body with one attribute per
+//! key. Nested dicts become objects, lists and sets become tuples. We do not
+//! generate blocks, because their meaning depends on the schema of the tool
+//! that reads the file. See also
+//! <https://github.com/hashicorp/hcl/blob/main/hclsyntax/spec.md>.
+
+use std::collections::BTreeMap;
+
+use crate::error::{IntoError, PathElement, Result};
+use crate::markup::Markup;
+use crate::pprint::{concat, group, indent, Doc};
+use crate::runtime::Value;
+use crate::source::Span;
+
+/// Render a value as HCL body.
+pub fn format_hcl(caller: Span, v: &Value) -> Result<Doc> {
+    let mut formatter = Formatter::new(caller);
+    match v {
+        Value::Dict(kv) => formatter.body(kv),
+        _ => formatter.error("To format as HCL, the top-level value must be a dict."),
+    }
+}
+
+/// Return whether the string is a valid HCL identifier.
+fn is_hcl_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(ch) if ch.is_ascii_alphabetic() || ch == '_' => {}
+        _ => return false,
+    }
+    chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '-')
+}
+
+/// Return whether we can write the object key without quotes.
+///
+/// An identifier as object key is a literal string, but keywords are not:
+/// `null` evaluates to null, and `for` would start a `for` expression.
+fn is_bare_object_key(s: &str) -> bool {
+    is_hcl_identifier(s) && !matches!(s, "true" | "false" | "null" | "for" | "in" | "if")
+}
+
+/// Escape a string for use inside a quoted HCL template.
+///
+/// Aside from the usual escape sequences, `${` and `%{` would start an
+/// interpolation or directive, so we double the sigil.
+fn escape_hcl_doc<'a>(s: &str) -> Doc<'a> {
+    let mut parts = Vec::new();
+    let mut plain = String::new();
+    let mut chars = s.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        let escaped = match ch {
+            '"' => "\\\"".to_string(),
+            '\\' => "\\\\".to_string(),
+            '\n' => "\\n".to_string(),
+            '\r' => "\\r".to_string(),
+            '\t' => "\\t".to_string(),
+            '$' | '%' if chars.peek() == Some(&'{') => format!("{ch}{ch}"),
+            ch if ch.is_control() => format!("\\u{:04X}", ch as u32),
+            _ => {
+                plain.push(ch);
+                continue;
+            }
+        };
+        if !plain.is_empty() {
+            parts.push(Doc::from(std::mem::take(&mut plain)));
+        }
+        parts.push(Doc::from(escaped).with_markup(Markup::Escape));
+    }
+    if !plain.is_empty() {
+        parts.push(Doc::from(plain));
+    }
+
+    Doc::Concat(parts)
+}
+
+/// Format a string as heredoc, if that can represent it exactly.
+///
+/// A heredoc always ends in a newline, and it cannot contain escape sequences,
+/// so we only use it for multi-line strings that end in a newline, and that do
+/// not contain control characters other than newlines and tabs.
+fn heredoc<'a>(s: &str) -> Option<Doc<'a>> {
+    let body = s.strip_suffix('\n')?;
+    if !body.contains('\n') || s.contains(|ch: char| ch.is_control() && ch != '\n' && ch != '\t') {
+        return None;
+    }
+
+    // The closing marker cannot occur as a line of its own in the string.
+    let mut marker = "EOT".to_string();
+    let mut n = 0;
+    while body.lines().any(|line| line.trim() == marker) {
+        n += 1;
+        marker = format!("EOT{n}");
+    }
+
+    // We use `<<` rather than `<<-`, because the latter strips leading
+    // whitespace from the lines, and this way we don't have to compute how
+    // much. This means the lines have to start at the beginning of the line.
+    let mut parts = vec![Doc::from(format!("<<{marker}")), Doc::RawBreak];
+    for line in body.split('\n') {
+        let line = line.replace("${", "$${").replace("%{", "%%{");
+        parts.push(Doc::from(line).with_markup(Markup::String));
+        parts.push(Doc::RawBreak);
+    }
+    parts.push(Doc::from(marker));
+    Some(Doc::Concat(parts))
+}
+
+/// Helper for formatting values as HCL.
+///
+/// The formatter tracks the path in the value that we are formatting from, such
+/// that we can report the location of an error, in case an error occurs.
+struct Formatter {
+    /// The source location where HCL formatting was triggered from.
+    caller: Span,
+
+    /// Where we currently are in the value to be formatted.
+    path: Vec<PathElement>,
+}
+
+impl Formatter {
+    pub fn new(caller: Span) -> Formatter {
+        Formatter {
+            caller,
+            path: Vec::new(),
+        }
+    }
+
+    /// Report an error at the current value path.
+    fn error<T>(&mut self, message: &'static str) -> Result<T> {
+        // Steal the path from the formatter and move it into the error. We have
+        // to leave an empty path in its place. This is fine, because returning
+        // the error prevents further formatting.
+        let path = std::mem::take(&mut self.path);
+        self.caller.error(message).with_path(path).err()
+    }
+
+    fn string<'a>(&self, s: &str) -> Doc<'a> {
+        concat! { "\"" escape_hcl_doc(s) "\"" }
+    }
+
+    /// Format the value of an attribute or object element.
+    ///
+    /// Here we can use heredocs, because the separator that follows is a newline.
+    fn attribute_value<'a>(&mut self, v: &'a Value) -> Result<Doc<'a>> {
+        match v {
+            Value::String(s) => match heredoc(s) {
+                Some(doc) => Ok(doc),
+                None => Ok(self.string(s).with_markup(Markup::String)),
+            },
+            _ => self.value(v),
+        }
+    }
+
+    /// Format the top-level dict as a body of attributes.
+    fn body<'a>(&mut self, kv: &'a BTreeMap<Value, Value>) -> Result<Doc<'a>> {
+        let mut lines = Vec::new();
+        for (k, v) in kv.iter() {
+            self.path.push(PathElement::Key(k.clone()));
+            let key = match k {
+                Value::String(k_str) if is_hcl_identifier(k_str) => k_str.as_ref(),
+                Value::String(..) => {
+                    return self.error("To export as HCL, top-level keys must be identifiers.")
+                }
+                _ => return self.error("To export as HCL, keys must be strings."),
+            };
+            if !lines.is_empty() {
+                lines.push(Doc::HardBreak);
+            }
+            lines.push(concat! {
+                Doc::from(key).with_markup(Markup::Field)
+                " = "
+                self.attribute_value(v)?
+            });
+            self.path.pop().expect("Push and pop are balanced.");
+        }
+        Ok(Doc::Concat(lines))
+    }
+
+    fn tuple<'a>(&mut self, vs: impl Iterator<Item = &'a Value>) -> Result<Doc<'a>> {
+        let mut elements = Vec::new();
+        for (i, v) in vs.enumerate() {
+            if !elements.is_empty() {
+                elements.push(",".into());
+                elements.push(Doc::Sep);
+            }
+            self.path.push(PathElement::Index(i));
+            elements.push(self.value(v)?);
+            self.path.pop().expect("Push and pop are balanced.");
+        }
+
+        if elements.is_empty() {
+            return Ok("[]".into());
+        }
+
+        // Add a trailing comma in tall mode.
+        elements.push(Doc::tall(","));
+        let result = group! {
+            "["
+            Doc::SoftBreak
+            indent! { Doc::Concat(elements) }
+            Doc::SoftBreak
+            "]"
+        };
+        Ok(result)
+    }
+
+    /// Format a dict as object, with one element per line.
+    ///
+    /// In HCL, elements can be separated by commas or newlines. We always use
+    /// newlines, like `terraform fmt`, because a comma cannot follow a heredoc.
+    fn object<'a>(&mut self, vs: impl Iterator<Item = (&'a Value, &'a Value)>) -> Result<Doc<'a>> {
+        let mut elements = Vec::new();
+        for (k, v) in vs {
+            if !elements.is_empty() {
+                elements.push(Doc::HardBreak);
+            }
+            self.path.push(PathElement::Key(k.clone()));
+            match k {
+                Value::String(k_str) if is_bare_object_key(k_str) => {
+                    elements.push(Doc::from(k_str.as_ref()).with_markup(Markup::Field))
+                }
+                Value::String(k_str) => {
+                    elements.push(self.string(k_str).with_markup(Markup::Field))
+                }
+                _ => return self.error("To export as HCL, keys must be strings."),
+            };
+            elements.push(" = ".into());
+            elements.push(self.attribute_value(v)?);
+            self.path.pop().expect("Push and pop are balanced.");
+        }
+
+        if elements.is_empty() {
+            return Ok("{}".into());
+        }
+
+        let result = concat! {
+            "{"
+            indent! { Doc::HardBreak Doc::Concat(elements) }
+            Doc::HardBreak
+            "}"
+        };
+        Ok(result)
+    }
+
+    fn value<'a>(&mut self, v: &'a Value) -> Result<Doc<'a>> {
+        let result = match v {
+            Value::Null => Doc::from("null").with_markup(Markup::Keyword),
+            Value::Bool(true) => Doc::from("true").with_markup(Markup::Keyword),
+            Value::Bool(false) => Doc::from("false").with_markup(Markup::Keyword),
+            Value::Int(i) => Doc::from(i.to_string()).with_markup(Markup::Number),
+            Value::Number(d) => Doc::from(d.to_string()).with_markup(Markup::Number),
+            Value::String(s) => self.string(s).with_markup(Markup::String),
+            Value::List(vs) => self.tuple(vs.iter())?,
+            // HCL has no set literal, we format sets as tuples.
+            Value::Set(vs) => self.tuple(vs.iter())?,
+            Value::Dict(vs) => self.object(vs.iter())?,
+            Value::Function(..) => self.error("Functions cannot be exported as HCL.")?,
+            Value::BuiltinFunction(..) => self.error("Functions cannot be exported as HCL.")?,
+            Value::BuiltinMethod { .. } => self.error("Methods cannot be exported as HCL.")?,
+        };
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::is_bare_object_key;
+
+    #[test]
+    fn is_bare_object_key_excludes_keywords() {
+        assert!(is_bare_object_key("instance_type"));
+        assert!(is_bare_object_key("kebab-case"));
+        assert!(!is_bare_object_key(""));
+        assert!(!is_bare_object_key("-leading-dash"));
+        assert!(!is_bare_object_key("a.b"));
+        assert!(!is_bare_object_key("null"));
+        assert!(!is_bare_object_key("for"));
+    }
+}
@@ -0,0 +1,321 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Formatters that print values as flat key-value pairs, INI and Java properties.
+//!
+//! Both formats have only strings as values, and no nesting. In INI, a
+//! top-level dict becomes a section. Nested dicts below that get flattened:
+//! their keys are joined with a separator, which defaults to a dot. A list
+//! cannot be represented, and neither can null, so those are errors.
+//!
+//! There is no standard for INI. We quote values with double quotes when they
+//! have leading or trailing whitespace, or characters that parsers commonly
+//! treat specially, and we reject what we can't represent unambiguously. For
+//! properties, we escape according to the documentation of `Properties.load`,
+//! see <https://docs.oracle.com/javase/8/docs/api/java/util/Properties.html>.
+//! Non-ASCII characters get escaped too, so the output is valid regardless of
+//! whether it gets read as UTF-8 or as Latin-1.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::error::{IntoError, PathElement, Result};
+use crate::markup::Markup;
+use crate::pprint::{concat, Doc};
+use crate::runtime::Value;
+use crate::source::Span;
+
+/// The default separator to join the keys of nested dicts with.
+pub const DEFAULT_KEY_SEPARATOR: &str = ".";
+
+/// Which flat key-value format to output.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Dialect {
+    Ini,
+    Properties,
+}
+
+impl Dialect {
+    fn name(self) -> &'static str {
+        match self {
+            Dialect::Ini => "INI",
+            Dialect::Properties => "properties",
+        }
+    }
+}
+
+/// Render a value as INI file.
+pub fn format_ini<'a>(caller: Span, key_separator: &str, v: &'a Value) -> Result<Doc<'a>> {
+    let mut formatter = Formatter::new(caller, Dialect::Ini, key_separator);
+    match v {
+        Value::Dict(kv) => formatter.ini_top_level(kv),
+        _ => formatter.error("To format as INI, the top-level value must be a dict."),
+    }
+}
+
+/// Render a value as Java properties file.
+pub fn format_properties<'a>(caller: Span, key_separator: &str, v: &'a Value) -> Result<Doc<'a>> {
+    let mut formatter = Formatter::new(caller, Dialect::Properties, key_separator);
+    match v {
+        Value::Dict(kv) => {
+            let mut lines = Vec::new();
+            formatter.section_body(&mut lines, None, kv)?;
+            Ok(Doc::Concat(lines))
+        }
+        _ => formatter.error("To format as properties, the top-level value must be a dict."),
+    }
+}
+
+/// Helper for formatting values as flat key-value pairs.
+///
+/// The formatter tracks the path in the value that we are formatting from, such
+/// that we can report the location of an error, in case an error occurs.
+struct Formatter<'s> {
+    /// The source location where formatting was triggered from.
+    caller: Span,
+
+    /// Where we currently are in the value to be formatted.
+    path: Vec<PathElement>,
+
+    dialect: Dialect,
+
+    /// The separator between the keys of nested dicts in a flattened key.
+    key_separator: &'s str,
+
+    /// The flattened keys in the current section, to detect collisions.
+    keys: BTreeSet<String>,
+}
+
+impl<'s> Formatter<'s> {
+    fn new(caller: Span, dialect: Dialect, key_separator: &'s str) -> Formatter<'s> {
+        Formatter {
+            caller,
+            path: Vec::new(),
+            dialect,
+            key_separator,
+            keys: BTreeSet::new(),
+        }
+    }
+
+    /// Report an error at the current value path.
+    fn error<T>(&mut self, message: &'static str) -> Result<T> {
+        // Steal the path from the formatter and move it into the error. We have
+        // to leave an empty path in its place. This is fine, because returning
+        // the error prevents further formatting.
+        let path = std::mem::take(&mut self.path);
+        self.caller.error(message).with_path(path).err()
+    }
+
+    /// Report an error about a value that is too structured for the format.
+    fn error_structured<T>(&mut self, what: &'static str) -> Result<T> {
+        let message = concat! {
+            what
+            " cannot be exported as "
+            self.dialect.name()
+            ", values must be strings, integers, or booleans."
+        };
+        let path = std::mem::take(&mut self.path);
+        self.caller.error(message).with_path(path).err()
+    }
+
+    /// Push the key to the path, and return it, or return an error on non-strings.
+    fn push_key<'a>(&mut self, key: &'a Value) -> Result<&'a str> {
+        self.path.push(PathElement::Key(key.clone()));
+        match key {
+            Value::String(k_str) => Ok(k_str),
+            _ => match self.dialect {
+                Dialect::Ini => self.error("To export as INI, keys must be strings."),
+                Dialect::Properties => self.error("To export as properties, keys must be strings."),
+            },
+        }
+    }
+
+    fn ini_top_level<'a>(&mut self, kv: &'a BTreeMap<Value, Value>) -> Result<Doc<'a>> {
+        let mut globals = Vec::new();
+        let mut sections = Vec::new();
+
+        // Values before the first section header are global, so those go first.
+        for (k, v) in kv.iter() {
+            if !matches!(v, Value::Dict(..)) {
+                let key = self.push_key(k)?;
+                self.key_value(&mut globals, key.to_string(), v)?;
+                self.path.pop().expect("We pushed the key before.");
+            }
+        }
+
+        for (k, v) in kv.iter() {
+            if let Value::Dict(inner) = v {
+                let name = self.push_key(k)?;
+                if name.is_empty() || name.contains(|ch: char| ch == ']' || ch.is_control()) {
+                    return self.error(
+                        "INI section names must be non-empty, and cannot contain ']' or control characters.",
+                    );
+                }
+                self.keys.clear();
+                let mut section = vec![
+                    concat! { "[" Doc::from(name.to_string()).with_markup(Markup::Field) "]" },
+                    Doc::HardBreak,
+                ];
+                self.section_body(&mut section, None, inner)?;
+                self.path.pop().expect("We pushed the key before.");
+                sections.push(Doc::Concat(section));
+            }
+        }
+
+        // Separate sections by a blank line.
+        for section in sections {
+            if !globals.is_empty() {
+                globals.push(Doc::HardBreak);
+            }
+            globals.push(section);
+        }
+
+        Ok(Doc::Concat(globals))
+    }
+
+    /// Push the key-value lines for a dict, flattening nested dicts.
+    fn section_body(
+        &mut self,
+        lines: &mut Vec<Doc<'static>>,
+        prefix: Option<&str>,
+        kv: &BTreeMap<Value, Value>,
+    ) -> Result<()> {
+        for (k, v) in kv.iter() {
+            let key = self.push_key(k)?;
+            let full_key = match prefix {
+                None => key.to_string(),
+                Some(prefix) => format!("{prefix}{}{key}", self.key_separator),
+            };
+            match v {
+                Value::Dict(inner) => self.section_body(lines, Some(&full_key), inner)?,
+                _ => self.key_value(lines, full_key, v)?,
+            }
+            self.path.pop().expect("We pushed the key before.");
+        }
+        Ok(())
+    }
+
+    /// Push a `key = value` line, the key is the full flattened key.
+    fn key_value(&mut self, lines: &mut Vec<Doc<'static>>, key: String, v: &Value) -> Result<()> {
+        let value = self.value(v)?;
+        let key_doc = match self.dialect {
+            Dialect::Ini => self.ini_key(&key)?,
+            Dialect::Properties => escape_properties(&key, true),
+        };
+        if !self.keys.insert(key) {
+            return self.error("After flattening, this key is the same as a preceding key.");
+        }
+        let eq = match self.dialect {
+            Dialect::Ini => " = ",
+            Dialect::Properties => "=",
+        };
+        lines.push(concat! { Doc::from(key_doc).with_markup(Markup::Field) eq value });
+        lines.push(Doc::HardBreak);
+        Ok(())
+    }
+
+    fn ini_key(&mut self, key: &str) -> Result<String> {
+        let is_special = |ch: char| "=:;#[]".contains(ch) || ch.is_control();
+        if key.is_empty() || key.contains(is_special) || key.trim() != key {
+            return self.error(
+                "INI keys must be non-empty, cannot start or end with whitespace, \
+                and cannot contain '=', ':', ';', '#', '[', ']', or control characters.",
+            );
+        }
+        Ok(key.to_string())
+    }
+
+    fn ini_string(&mut self, s: &str) -> Result<Doc<'static>> {
+        if s.contains(|ch: char| ch.is_control() && ch != '\t') {
+            return self.error("INI values cannot contain line breaks or control characters.");
+        }
+        let needs_quotes = s.trim() != s || s.contains(|ch: char| "\"\\;#".contains(ch));
+        if !needs_quotes {
+            return Ok(s.to_string().into());
+        }
+        let mut into = String::with_capacity(s.len() + 2);
+        into.push('"');
+        for ch in s.chars() {
+            if ch == '"' || ch == '\\' {
+                into.push('\\');
+            }
+            into.push(ch);
+        }
+        into.push('"');
+        Ok(into.into())
+    }
+
+    fn value(&mut self, v: &Value) -> Result<Doc<'static>> {
+        let result = match v {
+            Value::Bool(true) => Doc::from("true").with_markup(Markup::Keyword),
+            Value::Bool(false) => Doc::from("false").with_markup(Markup::Keyword),
+            Value::Int(i) => Doc::from(i.to_string()).with_markup(Markup::Number),
+            Value::Number(d) => Doc::from(d.to_string()).with_markup(Markup::Number),
+            Value::String(s) => match self.dialect {
+                Dialect::Ini => self.ini_string(s)?,
+                Dialect::Properties => escape_properties(s, false).into(),
+            }
+            .with_markup(Markup::String),
+            Value::Null => self.error_structured("Null")?,
+            Value::List(..) => self.error_structured("Lists")?,
+            Value::Set(..) => self.error_structured("Sets")?,
+            Value::Dict(..) => unreachable!("Dicts get flattened before we get here."),
+            Value::Function(..) => self.error_structured("Functions")?,
+            Value::BuiltinFunction(..) => self.error_structured("Functions")?,
+            Value::BuiltinMethod { .. } => self.error_structured("Methods")?,
+        };
+        Ok(result)
+    }
+}
+
+/// Escape a key or value for a Java properties file.
+///
+/// In keys, whitespace, separators, and comment characters must be escaped.
+/// In values, only leading whitespace needs escaping, but we escape a leading
+/// separator or comment character too, so the line does not look like one.
+fn escape_properties(s: &str, is_key: bool) -> String {
+    let mut into = String::with_capacity(s.len());
+    for (i, ch) in s.chars().enumerate() {
+        match ch {
+            '\\' => into.push_str("\\\\"),
+            '\t' => into.push_str("\\t"),
+            '\n' => into.push_str("\\n"),
+            '\r' => into.push_str("\\r"),
+            '\x0c' => into.push_str("\\f"),
+            ' ' if is_key || i == 0 => into.push_str("\\ "),
+            '=' | ':' | '#' | '!' if is_key || i == 0 => {
+                into.push('\\');
+                into.push(ch);
+            }
+            ' '..='~' => into.push(ch),
+            _ => {
+                let mut buf = [0_u16; 2];
+                for unit in ch.encode_utf16(&mut buf) {
+                    into.push_str(&format!("\\u{unit:04x}"));
+                }
+            }
+        }
+    }
+    into
+}
+
+#[cfg(test)]
+mod test {
+    use super::escape_properties;
+
+    #[test]
+    fn escape_properties_escapes_keys_and_values() {
+        assert_eq!(escape_properties("a.b", true), "a.b");
+        assert_eq!(escape_properties("a b=c:d", true), "a\\ b\\=c\\:d");
+        assert_eq!(escape_properties("#!", true), "\\#\\!");
+        assert_eq!(escape_properties(" a b=c", false), "\\ a b=c");
+        assert_eq!(escape_properties("#x", false), "\\#x");
+        assert_eq!(escape_properties("x#", false), "x#");
+        assert_eq!(escape_properties("C:\\\n", false), "C:\\\\\\n");
+        assert_eq!(escape_properties("caf\u{e9}", false), "caf\\u00e9");
+        assert_eq!(escape_properties("\u{1f980}", false), "\\ud83e\\udd80");
+    }
+}
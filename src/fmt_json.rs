@@ -7,12 +7,13 @@
 
 //! Formatter that prints values as json.
 
+use crate::decimal::Decimal;
 use crate::error::{IntoError, PathElement, Result};
 use crate::markup::Markup;
 use crate::pprint::{concat, group, indent, Doc};
 use crate::runtime::Value;
 use crate::source::Span;
-use crate::string::escape_json;
+use crate::string::{escape_json_canonical_doc, escape_json_doc};
 
 /// Render a value as json.
 pub fn format_json(caller: Span, v: &Value) -> Result<Doc> {
@@ -20,6 +21,61 @@ pub fn format_json(caller: Span, v: &Value) -> Result<Doc> {
     formatter.value(v)
 }
 
+/// Render a value as json on a single line, without any whitespace.
+pub fn format_json_compact(caller: Span, v: &Value) -> Result<Doc> {
+    let mut formatter = Formatter::new(caller);
+    formatter.compact = true;
+    formatter.value(v)
+}
+
+/// Render a value as canonical json, as specified by RFC 8785.
+///
+/// This is compact json with object keys sorted by their UTF-16 code units and
+/// minimal string escaping, so that equal values produce identical bytes.
+pub fn format_json_canonical(caller: Span, v: &Value) -> Result<Doc> {
+    let mut formatter = Formatter::new(caller);
+    formatter.compact = true;
+    formatter.canonical = true;
+    formatter.value(v)
+}
+
+/// The largest integer that RFC 8785 can represent exactly, 2^53 - 1.
+///
+/// Canonical json numbers are IEEE 754 doubles, so larger integers would lose
+/// precision. We report an error rather than silently rounding them.
+const MAX_CANONICAL_INT: i128 = (1 << 53) - 1;
+
+/// Format a number the way ECMAScript's `Number.prototype.toString` does.
+///
+/// Canonical json (RFC 8785) prescribes this format. The caller must ensure
+/// that the digits of the decimal are the shortest digits that identify the
+/// double, which holds when it round-trips through [`Decimal::from_f64`].
+fn format_es6(d: Decimal) -> String {
+    if d == Decimal::ZERO {
+        return "0".to_string();
+    }
+    let sign = if d.mantissa() < 0 { "-" } else { "" };
+    let digits = d.mantissa().unsigned_abs().to_string();
+    let k = digits.len() as i64;
+    let n = k + d.exponent() as i64;
+    if k <= n && n <= 21 {
+        format!("{sign}{digits}{:0<1$}", "", (n - k) as usize)
+    } else if 0 < n && n <= 21 {
+        let (int_part, frac_part) = digits.split_at(n as usize);
+        format!("{sign}{int_part}.{frac_part}")
+    } else if -6 < n && n <= 0 {
+        format!("{sign}0.{:0<1$}{digits}", "", (-n) as usize)
+    } else {
+        let (first, rest) = digits.split_at(1);
+        let point = if rest.is_empty() { "" } else { "." };
+        let exp_sign = if n > 0 { '+' } else { '-' };
+        format!(
+            "{sign}{first}{point}{rest}e{exp_sign}{}",
+            (n - 1).unsigned_abs()
+        )
+    }
+}
+
 /// Helper for formatting values as json.
 ///
 /// The formatter tracks the path in the value that we are formatting from, such
@@ -30,6 +86,12 @@ pub struct Formatter {
 
     /// Where we currently are in the value to be formatted.
     pub path: Vec<PathElement>,
+
+    /// Whether to omit all whitespace, rather than to pretty-print.
+    pub compact: bool,
+
+    /// Whether to sort keys and escape strings as RFC 8785 requires.
+    pub canonical: bool,
 }
 
 impl Formatter {
@@ -37,6 +99,35 @@ impl Formatter {
         Formatter {
             caller,
             path: Vec::new(),
+            compact: false,
+            canonical: false,
+        }
+    }
+
+    /// Wrap the elements of a collection in its delimiters.
+    fn collection<'a>(
+        &self,
+        open: &'static str,
+        elements: Vec<Doc<'a>>,
+        close: &'static str,
+    ) -> Doc<'a> {
+        if self.compact {
+            return concat! { open Doc::Concat(elements) close };
+        }
+        group! {
+            open
+            Doc::SoftBreak
+            indent! { Doc::Concat(elements) }
+            Doc::SoftBreak
+            close
+        }
+    }
+
+    /// Push the separator between two elements of a collection.
+    fn push_separator(&self, elements: &mut Vec<Doc>) {
+        elements.push(",".into());
+        if !self.compact {
+            elements.push(Doc::Sep);
         }
     }
 
@@ -51,39 +142,41 @@ impl Formatter {
     }
 
     fn string<'a>(&self, s: &str) -> Doc<'a> {
-        let mut into = String::with_capacity(s.len());
-        // TODO: Escape into a Doc so we can highlight escape sequences.
-        escape_json(s, &mut into);
-        concat! { "\"" into "\"" }
+        if self.canonical {
+            concat! { "\"" escape_json_canonical_doc(s) "\"" }
+        } else {
+            concat! { "\"" escape_json_doc(s) "\"" }
+        }
     }
 
     fn list<'a>(&mut self, vs: impl Iterator<Item = &'a Value>) -> Result<Doc<'a>> {
         let mut elements = Vec::new();
         for (i, v) in vs.enumerate() {
             if !elements.is_empty() {
-                elements.push(",".into());
-                elements.push(Doc::Sep);
+                self.push_separator(&mut elements);
             }
             self.path.push(PathElement::Index(i));
             elements.push(self.value(v)?);
             self.path.pop().expect("Push and pop are balanced.");
         }
-        let result = group! {
-            "["
-            Doc::SoftBreak
-            indent! { Doc::Concat(elements) }
-            Doc::SoftBreak
-            "]"
-        };
-        Ok(result)
+        Ok(self.collection("[", elements, "]"))
     }
 
     fn dict<'a>(&mut self, vs: impl Iterator<Item = (&'a Value, &'a Value)>) -> Result<Doc<'a>> {
+        let mut entries: Vec<_> = vs.collect();
+        if self.canonical {
+            // Dicts are ordered by the UTF-8 bytes of their keys, but RFC 8785
+            // orders by UTF-16 code units. These differ for code points above
+            // U+FFFF, which sort before U+E000..U+FFFF in UTF-16.
+            entries.sort_by(|(k1, _), (k2, _)| match (k1, k2) {
+                (Value::String(s1), Value::String(s2)) => s1.encode_utf16().cmp(s2.encode_utf16()),
+                _ => k1.cmp(k2),
+            });
+        }
         let mut elements = Vec::new();
-        for (k, v) in vs {
+        for (k, v) in entries {
             if !elements.is_empty() {
-                elements.push(",".into());
-                elements.push(Doc::Sep);
+                self.push_separator(&mut elements);
             }
             self.path.push(PathElement::Key(k.clone()));
             match k {
@@ -92,18 +185,45 @@ impl Formatter {
                 }
                 _ => return self.error("To export as json, keys must be strings."),
             };
-            elements.push(": ".into());
+            elements.push(if self.compact { ":" } else { ": " }.into());
             elements.push(self.value(v)?);
             self.path.pop().expect("Push and pop are balanced.");
         }
-        let result = group! {
-            "{"
-            Doc::SoftBreak
-            indent! { Doc::Concat(elements) }
-            Doc::SoftBreak
-            "}"
-        };
-        Ok(result)
+        Ok(self.collection("{", elements, "}"))
+    }
+
+    /// Check that the value can be formatted, without building a document.
+    ///
+    /// This reports the same errors as [`Formatter::value`], at the same paths.
+    pub fn check(&mut self, v: &Value) -> Result<()> {
+        match v {
+            Value::List(vs) => self.check_list(vs.iter()),
+            Value::Set(vs) => self.check_list(vs.iter()),
+            Value::Dict(vs) => {
+                for (k, v) in vs.iter() {
+                    self.path.push(PathElement::Key(k.clone()));
+                    if !matches!(k, Value::String(..)) {
+                        return self.error("To export as json, keys must be strings.");
+                    }
+                    self.check(v)?;
+                    self.path.pop().expect("Push and pop are balanced.");
+                }
+                Ok(())
+            }
+            Value::Function(..) => self.error("Functions cannot be exported as json."),
+            Value::BuiltinFunction(..) => self.error("Functions cannot be exported as json."),
+            Value::BuiltinMethod { .. } => self.error("Methods cannot be exported as json."),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_list<'a>(&mut self, vs: impl Iterator<Item = &'a Value>) -> Result<()> {
+        for (i, v) in vs.enumerate() {
+            self.path.push(PathElement::Index(i));
+            self.check(v)?;
+            self.path.pop().expect("Push and pop are balanced.");
+        }
+        Ok(())
     }
 
     pub fn value<'a>(&mut self, v: &'a Value) -> Result<Doc<'a>> {
@@ -111,7 +231,20 @@ impl Formatter {
             Value::Null => Doc::from("null").with_markup(Markup::Keyword),
             Value::Bool(true) => Doc::from("true").with_markup(Markup::Keyword),
             Value::Bool(false) => Doc::from("false").with_markup(Markup::Keyword),
+            Value::Int(i) if self.canonical && i.unsigned_abs() > MAX_CANONICAL_INT as u128 => self
+                .error(
+                    "Canonical json can only represent integers from -(2^53 - 1) to 2^53 - 1.",
+                )?,
             Value::Int(i) => Doc::from(i.to_string()).with_markup(Markup::Number),
+            Value::Number(d) if self.canonical => {
+                if Decimal::from_f64(d.to_f64()) != Some(*d) {
+                    return self.error(
+                        "Canonical json can only represent numbers that are exact doubles.",
+                    );
+                }
+                Doc::from(format_es6(*d)).with_markup(Markup::Number)
+            }
+            Value::Number(d) => Doc::from(d.to_string()).with_markup(Markup::Number),
             Value::String(s) => self.string(s).with_markup(Markup::String),
             Value::List(vs) => self.list(vs.iter())?,
             Value::Set(vs) => self.list(vs.iter())?,
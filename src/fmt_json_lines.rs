@@ -0,0 +1,46 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Formatter that prints list elements as compact json, one per line.
+//!
+//! See <https://jsonlines.org/> for the format.
+
+use crate::error::{IntoError, PathElement, Result};
+use crate::fmt_json::Formatter;
+use crate::pprint::Doc;
+use crate::runtime::Value;
+use crate::source::Span;
+
+/// Return the elements to format, the top-level value must be a list.
+pub fn elements(caller: Span, v: &Value) -> Result<&[Value]> {
+    match v {
+        Value::List(xs) => Ok(xs),
+        _ => caller
+            .error("To format as JSON Lines, the top-level value must be a list.")
+            .err(),
+    }
+}
+
+/// Render a value in JSON Lines format.
+pub fn format_json_lines(caller: Span, v: &Value) -> Result<Doc> {
+    let elements = elements(caller, v)?;
+
+    let mut formatter = Formatter::new(caller);
+    formatter.compact = true;
+    let mut parts = Vec::new();
+
+    for (i, element) in elements.iter().enumerate() {
+        if !parts.is_empty() {
+            parts.push(Doc::HardBreak)
+        }
+        formatter.path.push(PathElement::Index(i));
+        parts.push(formatter.value(element)?);
+        formatter.path.pop();
+    }
+
+    Ok(Doc::Concat(parts))
+}
@@ -0,0 +1,484 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Serializers that write json directly to a writer, without building a document.
+//!
+//! Formatting through [`Doc`](crate::pprint::Doc) holds the document and the
+//! printed string in memory at the same time, which for outputs of hundreds of
+//! megabytes is wasteful. The writers in this module produce the same bytes as
+//! printing the document from [`crate::fmt_json`] without markup, but they
+//! decide between wide and tall layout by measuring the value directly, so
+//! apart from a fixed-size buffer, they need memory only for the nesting depth.
+//!
+//! Writing happens in two phases. [`check_value`] reports any error that the
+//! document-based formatter would report, so that by the time we write, the
+//! only possible failures are IO errors, which [`write_value`] returns.
+
+use std::io::{BufWriter, Write};
+
+use crate::cli::OutputFormat;
+use crate::error::{PathElement, Result};
+use crate::fmt_json::Formatter;
+use crate::pprint::{AmbiguousWidth, Config, IndentStyle, LineEnding};
+use crate::runtime::Value;
+use crate::source::Span;
+use crate::string::escape_json;
+
+/// Whether `format` can be written with [`write_value`].
+pub fn supports(format: OutputFormat) -> bool {
+    matches!(
+        format,
+        OutputFormat::Json
+            | OutputFormat::JsonCompact
+            | OutputFormat::JsonLines
+            | OutputFormat::YamlStream
+    )
+}
+
+/// Check that the value can be written in the given format.
+///
+/// This reports the same errors as [`crate::cmd_eval::format_value`].
+pub fn check_value(format: OutputFormat, caller: Span, v: &Value) -> Result<()> {
+    debug_assert!(supports(format), "Format {format:?} cannot be streamed.");
+    let mut formatter = Formatter::new(caller);
+    let elements = match format {
+        OutputFormat::JsonLines => crate::fmt_json_lines::elements(caller, v)?,
+        OutputFormat::YamlStream => crate::fmt_yaml_stream::elements(caller, v)?,
+        _ => return formatter.check(v),
+    };
+    for (i, element) in elements.iter().enumerate() {
+        formatter.path.push(PathElement::Index(i));
+        formatter.check(element)?;
+        formatter.path.pop();
+    }
+    Ok(())
+}
+
+/// Write the value in the given format, including the final newline.
+///
+/// The value must have passed [`check_value`] for the same format.
+pub fn write_value(
+    format: OutputFormat,
+    config: &Config,
+    v: &Value,
+    out: &mut dyn Write,
+) -> std::io::Result<()> {
+    let mut writer = Writer {
+        out: BufWriter::new(out),
+        width: config.width,
+        indent_width: match config.indent_style {
+            IndentStyle::Spaces => config.indent_width,
+            IndentStyle::Tabs => config.tab_width,
+        },
+        indent_style: config.indent_style,
+        indent_levels: 0,
+        newline: match config.line_ending {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        },
+        ambiguous_width: config.ambiguous_width,
+        indent: 0,
+        column: 0,
+        buffer: String::new(),
+    };
+    match format {
+        OutputFormat::JsonCompact => {
+            writer.compact(v)?;
+            writer.newline()?;
+        }
+        OutputFormat::JsonLines => {
+            for element in list_elements(v)? {
+                writer.compact(element)?;
+                writer.newline()?;
+            }
+        }
+        OutputFormat::YamlStream => {
+            for element in list_elements(v)? {
+                writer.write("---", 3)?;
+                writer.newline()?;
+                writer.pretty(element)?;
+                writer.newline()?;
+            }
+        }
+        _ => {
+            writer.pretty(v)?;
+            writer.newline()?;
+        }
+    }
+    writer.out.flush()
+}
+
+fn list_elements(v: &Value) -> std::io::Result<&[Value]> {
+    match v {
+        Value::List(xs) => Ok(xs),
+        _ => Err(not_checked()),
+    }
+}
+
+/// The error for values that [`check_value`] would have rejected.
+fn not_checked() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        "The value cannot be formatted as json.",
+    )
+}
+
+/// Return the width of the json representation of the integer.
+fn int_width(i: i128) -> u32 {
+    let mut width = if i < 0 { 2 } else { 1 };
+    let mut n = i.unsigned_abs();
+    while n >= 10 {
+        n /= 10;
+        width += 1;
+    }
+    width
+}
+
+/// Return the width of the json string literal for `s`, including quotes.
+///
+/// This matches the width that the pretty-printer computes for the document
+/// that [`crate::string::escape_json_doc`] returns.
+fn string_width(s: &str, ambiguous_width: AmbiguousWidth) -> u32 {
+    let mut width = 2;
+    for ch in s.chars() {
+        width += match ch {
+            '\n' | '\r' | '\x08' | '\x0c' | '\t' | '\"' | '\\' => 2,
+            ch if ch.is_ascii_control() => 6,
+            ch => ambiguous_width.char_width(ch),
+        };
+    }
+    width
+}
+
+struct Writer<'a> {
+    out: BufWriter<&'a mut dyn Write>,
+
+    /// The line width target, like [`Config::width`].
+    width: u32,
+
+    /// The number of columns to indent per level, like the pretty-printer.
+    indent_width: u32,
+
+    /// Whether to indent with spaces or with tabs.
+    indent_style: IndentStyle,
+
+    /// The indentation of the current collection, counted in levels.
+    indent_levels: u32,
+
+    /// The string to emit for a newline.
+    newline: &'static str,
+
+    /// How wide to count characters with an ambiguous width.
+    ambiguous_width: AmbiguousWidth,
+
+    /// The indentation of the current collection, counted in spaces.
+    indent: u32,
+
+    /// The width of the current line so far, including indentation.
+    column: u32,
+
+    /// Reusable buffer for escaping strings.
+    buffer: String,
+}
+
+impl<'a> Writer<'a> {
+    fn write(&mut self, s: &str, width: u32) -> std::io::Result<()> {
+        self.column += width;
+        self.out.write_all(s.as_bytes())
+    }
+
+    fn newline(&mut self) -> std::io::Result<()> {
+        self.column = 0;
+        self.out.write_all(self.newline.as_bytes())
+    }
+
+    fn write_indent(&mut self) -> std::io::Result<()> {
+        match self.indent_style {
+            IndentStyle::Spaces => {
+                for _ in 0..self.indent {
+                    self.out.write_all(b" ")?;
+                }
+            }
+            IndentStyle::Tabs => {
+                for _ in 0..self.indent_levels {
+                    self.out.write_all(b"\t")?;
+                }
+            }
+        }
+        self.column = self.indent;
+        Ok(())
+    }
+
+    fn string(&mut self, s: &str) -> std::io::Result<()> {
+        let mut buffer = std::mem::take(&mut self.buffer);
+        buffer.clear();
+        buffer.push('"');
+        escape_json(s, &mut buffer);
+        buffer.push('"');
+        let result = self.write(&buffer, string_width(s, self.ambiguous_width));
+        self.buffer = buffer;
+        result
+    }
+
+    /// Write a value that is not a collection.
+    fn scalar(&mut self, v: &Value) -> std::io::Result<()> {
+        match v {
+            Value::Null => self.write("null", 4),
+            Value::Bool(true) => self.write("true", 4),
+            Value::Bool(false) => self.write("false", 5),
+            Value::Int(i) => self.write(&i.to_string(), int_width(*i)),
+            Value::Number(d) => {
+                let s = d.to_string();
+                self.write(&s, s.len() as u32)
+            }
+            Value::String(s) => self.string(s),
+            _ => Err(not_checked()),
+        }
+    }
+
+    /// Subtract the width of `v` in wide mode from `budget`.
+    ///
+    /// Returns false if the value does not fit, in which case measuring stops
+    /// early, so the cost is bounded by the budget rather than the value.
+    fn fits(&self, v: &Value, budget: &mut u32) -> bool {
+        let mut take = |width: u32| match budget.checked_sub(width) {
+            Some(remainder) => {
+                *budget = remainder;
+                true
+            }
+            None => false,
+        };
+        match v {
+            Value::Null | Value::Bool(true) => take(4),
+            Value::Bool(false) => take(5),
+            Value::Int(i) => take(int_width(*i)),
+            Value::Number(d) => take(d.to_string().len() as u32),
+            Value::String(s) => take(string_width(s, self.ambiguous_width)),
+            Value::List(vs) => self.fits_list(vs.iter(), budget),
+            Value::Set(vs) => self.fits_list(vs.iter(), budget),
+            Value::Dict(vs) => {
+                let n = vs.len() as u32;
+                // Braces, the separators between entries, and the ": "s.
+                if !take(2 + n.saturating_sub(1) * 2 + n * 2) {
+                    return false;
+                }
+                vs.iter()
+                    .all(|(k, v)| self.fits(k, budget) && self.fits(v, budget))
+            }
+            _ => true,
+        }
+    }
+
+    fn fits_list<'v>(
+        &self,
+        mut vs: impl ExactSizeIterator<Item = &'v Value>,
+        budget: &mut u32,
+    ) -> bool {
+        // Brackets, and the separators between elements.
+        let width = 2 + (vs.len() as u32).saturating_sub(1) * 2;
+        match budget.checked_sub(width) {
+            Some(remainder) => *budget = remainder,
+            None => return false,
+        }
+        vs.all(|v| self.fits(v, budget))
+    }
+
+    /// Write the value on a single line, with spaces after separators.
+    fn wide(&mut self, v: &Value) -> std::io::Result<()> {
+        self.collection(v, ", ", ": ", Writer::wide)
+    }
+
+    /// Write the value on a single line, without any whitespace.
+    fn compact(&mut self, v: &Value) -> std::io::Result<()> {
+        self.collection(v, ",", ":", Writer::compact)
+    }
+
+    /// Write a collection on a single line, with `element` for the elements.
+    fn collection(
+        &mut self,
+        v: &Value,
+        separator: &str,
+        key_separator: &str,
+        element: fn(&mut Writer<'a>, &Value) -> std::io::Result<()>,
+    ) -> std::io::Result<()> {
+        let separator_width = separator.len() as u32;
+        let key_separator_width = key_separator.len() as u32;
+        let list = |w: &mut Writer<'a>, vs: &mut dyn Iterator<Item = &Value>| {
+            w.write("[", 1)?;
+            for (i, v) in vs.enumerate() {
+                if i > 0 {
+                    w.write(separator, separator_width)?;
+                }
+                element(w, v)?;
+            }
+            w.write("]", 1)
+        };
+        match v {
+            Value::List(vs) => list(self, &mut vs.iter()),
+            Value::Set(vs) => list(self, &mut vs.iter()),
+            Value::Dict(vs) => {
+                self.write("{", 1)?;
+                for (i, (k, v)) in vs.iter().enumerate() {
+                    if i > 0 {
+                        self.write(separator, separator_width)?;
+                    }
+                    self.scalar(k)?;
+                    self.write(key_separator, key_separator_width)?;
+                    element(self, v)?;
+                }
+                self.write("}", 1)
+            }
+            _ => self.scalar(v),
+        }
+    }
+
+    /// Write the value wide if it fits on the current line, or tall otherwise.
+    fn pretty(&mut self, v: &Value) -> std::io::Result<()> {
+        if !matches!(v, Value::List(..) | Value::Set(..) | Value::Dict(..)) {
+            return self.scalar(v);
+        }
+        let mut budget = self.width.saturating_sub(self.column);
+        if self.column <= self.width && self.fits(v, &mut budget) {
+            return self.wide(v);
+        }
+        let (open, close) = match v {
+            Value::Dict(..) => ("{", "}"),
+            _ => ("[", "]"),
+        };
+        self.write(open, 1)?;
+        self.newline()?;
+        self.indent += self.indent_width;
+        self.indent_levels += 1;
+        let result = self.tall_elements(v);
+        self.indent_levels -= 1;
+        self.indent -= self.indent_width;
+        result?;
+        // Like the pretty-printer, an empty collection that does not fit
+        // leaves a blank line, because indentation is only written before
+        // content.
+        self.newline()?;
+        self.write_indent()?;
+        self.write(close, 1)
+    }
+
+    fn tall_elements(&mut self, v: &Value) -> std::io::Result<()> {
+        let list = |w: &mut Writer<'a>, vs: &mut dyn Iterator<Item = &Value>| {
+            for (i, v) in vs.enumerate() {
+                if i > 0 {
+                    w.write(",", 1)?;
+                    w.newline()?;
+                }
+                w.write_indent()?;
+                w.pretty(v)?;
+            }
+            Ok(())
+        };
+        match v {
+            Value::List(vs) => list(self, &mut vs.iter()),
+            Value::Set(vs) => list(self, &mut vs.iter()),
+            Value::Dict(vs) => {
+                for (i, (k, v)) in vs.iter().enumerate() {
+                    if i > 0 {
+                        self.write(",", 1)?;
+                        self.newline()?;
+                    }
+                    self.write_indent()?;
+                    self.scalar(k)?;
+                    self.write(": ", 2)?;
+                    self.pretty(v)?;
+                }
+                Ok(())
+            }
+            _ => unreachable!("Only collections have elements."),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_value, write_value};
+    use crate::cli::OutputFormat;
+    use crate::cmd_eval::{format_value, FormatOptions};
+    use crate::json_parser::parse_json;
+    use crate::pprint::{Config, IndentStyle, LineEnding};
+    use crate::source::{DocId, Span};
+
+    /// Assert that streaming produces the same output as printing the document.
+    fn assert_same(format: OutputFormat, config: &Config, input: &str) {
+        let span = Span::new(DocId(0), 0, 0);
+        let value = parse_json(DocId(0), input).unwrap();
+        let doc = format_value(format, &FormatOptions::default(), span, &value).unwrap();
+        let expected = doc.println(config).to_string_no_markup();
+        check_value(format, span, &value).unwrap();
+        let mut actual = Vec::new();
+        write_value(format, config, &value, &mut actual).unwrap();
+        assert_eq!(
+            String::from_utf8(actual).unwrap(),
+            expected,
+            "Mismatch for {format:?} at width {}: {input}",
+            config.width,
+        );
+    }
+
+    #[test]
+    fn write_value_matches_pretty_printer() {
+        let inputs = [
+            r#"null"#,
+            r#"-1234567890"#,
+            r#""tab\t quote\" nul\u0000 wide 漢字 emoji 🦀""#,
+            r#"[]"#,
+            r#"{}"#,
+            r#"[[], {}, [[[]]], {"a": {}}]"#,
+            r#"[1, 22, 333, -4444, true, false, null, "five"]"#,
+            r#"{"name": "example", "tags": ["a", "b", "c"], "nested": {"x": [1, 2], "y": []}}"#,
+            r#"[{"id": 1, "values": [10, 20, 30]}, {"id": 2, "values": []}, "trailing"]"#,
+            r#"{"a key with spaces": {"deeper": {"deepest": ["x", "y", "z", 1000000]}}}"#,
+        ];
+        for width in [0, 1, 5, 10, 14, 20, 30, 45, 80] {
+            for indent_width in [2, 4] {
+                let config = Config {
+                    width,
+                    indent_width,
+                    ..Config::default()
+                };
+                for input in inputs {
+                    assert_same(OutputFormat::Json, &config, input);
+                    assert_same(OutputFormat::JsonCompact, &config, input);
+                }
+                let stream = format!("[{}]", inputs.join(", "));
+                assert_same(OutputFormat::JsonLines, &config, &stream);
+                assert_same(OutputFormat::YamlStream, &config, &stream);
+                assert_same(OutputFormat::YamlStream, &config, "[]");
+            }
+        }
+        let config = Config {
+            width: 10,
+            line_ending: LineEnding::CrLf,
+            ..Config::default()
+        };
+        assert_same(OutputFormat::Json, &config, inputs[7]);
+        for width in [10, 30] {
+            let config = Config {
+                width,
+                indent_style: IndentStyle::Tabs,
+                tab_width: 8,
+                ..Config::default()
+            };
+            assert_same(OutputFormat::Json, &config, inputs[9]);
+            assert_same(OutputFormat::YamlStream, &config, inputs[8]);
+        }
+    }
+
+    #[test]
+    fn check_value_requires_list_for_streams() {
+        let span = Span::new(DocId(0), 0, 0);
+        let value = parse_json(DocId(0), "{}").unwrap();
+        assert!(check_value(OutputFormat::Json, span, &value).is_ok());
+        assert!(check_value(OutputFormat::JsonLines, span, &value).is_err());
+        assert!(check_value(OutputFormat::YamlStream, span, &value).is_err());
+    }
+}
@@ -0,0 +1,231 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Formatter that prints values as Nix expressions.
+//!
+//! Dicts become attribute sets, lists and sets become lists. Nix has no set
+//! type, and attribute names must be strings. See also
+//! <https://nixos.org/manual/nix/stable/language/values> for the syntax.
+
+use crate::error::{IntoError, PathElement, Result};
+use crate::markup::Markup;
+use crate::pprint::{concat, group, indent, Doc};
+use crate::runtime::Value;
+use crate::source::Span;
+
+/// Render a value as Nix expression.
+pub fn format_nix(caller: Span, v: &Value) -> Result<Doc> {
+    let mut formatter = Formatter::new(caller);
+    formatter.value(v)
+}
+
+/// Keywords that cannot be used as attribute names without quotes.
+///
+/// Strictly, `or` is allowed in some places, but quoting it is always valid.
+const KEYWORDS: &[&str] = &[
+    "assert", "else", "if", "in", "inherit", "let", "or", "rec", "then", "with",
+];
+
+/// Return whether the attribute name can be written without quotes.
+///
+/// In Nix, identifiers may contain apostrophes and dashes, but they cannot
+/// start with one.
+fn is_nix_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(ch) if ch.is_ascii_alphabetic() || ch == '_' => {}
+        _ => return false,
+    }
+    chars.all(|ch| ch.is_ascii_alphanumeric() || "_'-".contains(ch)) && !KEYWORDS.contains(&s)
+}
+
+/// Escape a string for use inside a double-quoted Nix string literal.
+///
+/// Aside from quotes and backslashes, `${` would start an interpolation, so we
+/// escape the dollar sign. All other characters can occur literally.
+fn escape_nix_doc<'a>(s: &str) -> Doc<'a> {
+    let mut parts = Vec::new();
+    let mut plain = String::new();
+    let mut chars = s.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        let escaped = match ch {
+            '"' => "\\\"",
+            '\\' => "\\\\",
+            '\n' => "\\n",
+            '\r' => "\\r",
+            '\t' => "\\t",
+            '$' if chars.peek() == Some(&'{') => "\\$",
+            _ => {
+                plain.push(ch);
+                continue;
+            }
+        };
+        if !plain.is_empty() {
+            parts.push(Doc::from(std::mem::take(&mut plain)));
+        }
+        parts.push(Doc::str(escaped).with_markup(Markup::Escape));
+    }
+    if !plain.is_empty() {
+        parts.push(Doc::from(plain));
+    }
+
+    Doc::Concat(parts)
+}
+
+/// Helper for formatting values as Nix.
+///
+/// The formatter tracks the path in the value that we are formatting from, such
+/// that we can report the location of an error, in case an error occurs.
+struct Formatter {
+    /// The source location where Nix formatting was triggered from.
+    caller: Span,
+
+    /// Where we currently are in the value to be formatted.
+    path: Vec<PathElement>,
+}
+
+impl Formatter {
+    pub fn new(caller: Span) -> Formatter {
+        Formatter {
+            caller,
+            path: Vec::new(),
+        }
+    }
+
+    /// Report an error at the current value path.
+    fn error<T>(&mut self, message: &'static str) -> Result<T> {
+        // Steal the path from the formatter and move it into the error. We have
+        // to leave an empty path in its place. This is fine, because returning
+        // the error prevents further formatting.
+        let path = std::mem::take(&mut self.path);
+        self.caller.error(message).with_path(path).err()
+    }
+
+    fn string<'a>(&self, s: &str) -> Doc<'a> {
+        concat! { "\"" escape_nix_doc(s) "\"" }
+    }
+
+    fn int<'a>(&mut self, i: i128) -> Result<Doc<'a>> {
+        if i64::try_from(i).is_err() {
+            return self.error("Nix integers are 64 bits, this integer is out of range.");
+        }
+        if i == i64::MIN as i128 {
+            // Nix parses a negative literal as negation of a positive one, and
+            // the positive one overflows.
+            let lit = format!("(-{} - 1)", i64::MAX);
+            return Ok(Doc::from(lit).with_markup(Markup::Number));
+        }
+        Ok(Doc::from(i.to_string()).with_markup(Markup::Number))
+    }
+
+    /// Format a list element, which needs parens if it's a negative number.
+    ///
+    /// Nix list elements are separated by whitespace, so `[ 1 -2 ]` would be a
+    /// subtraction.
+    fn element<'a>(&mut self, v: &'a Value) -> Result<Doc<'a>> {
+        match v {
+            Value::Int(i) if *i < 0 && *i != i64::MIN as i128 => {
+                Ok(concat! { "(" self.int(*i)? ")" })
+            }
+            _ => self.value(v),
+        }
+    }
+
+    fn list<'a>(&mut self, vs: impl Iterator<Item = &'a Value>) -> Result<Doc<'a>> {
+        let mut elements = Vec::new();
+        for (i, v) in vs.enumerate() {
+            elements.push(Doc::Sep);
+            self.path.push(PathElement::Index(i));
+            elements.push(self.element(v)?);
+            self.path.pop().expect("Push and pop are balanced.");
+        }
+
+        if elements.is_empty() {
+            return Ok("[ ]".into());
+        }
+
+        elements.push(Doc::Sep);
+        let result = group! {
+            "["
+            indent! { Doc::Concat(elements) }
+            "]"
+        };
+        Ok(result)
+    }
+
+    fn attrset<'a>(&mut self, vs: impl Iterator<Item = (&'a Value, &'a Value)>) -> Result<Doc<'a>> {
+        let mut elements = Vec::new();
+        for (k, v) in vs {
+            elements.push(Doc::Sep);
+            self.path.push(PathElement::Key(k.clone()));
+            match k {
+                Value::String(k_str) if is_nix_identifier(k_str) => {
+                    elements.push(Doc::from(k_str.as_ref()).with_markup(Markup::Field))
+                }
+                Value::String(k_str) => {
+                    elements.push(self.string(k_str).with_markup(Markup::Field))
+                }
+                _ => return self.error("To export as Nix, keys must be strings."),
+            };
+            elements.push(" = ".into());
+            elements.push(self.value(v)?);
+            elements.push(";".into());
+            self.path.pop().expect("Push and pop are balanced.");
+        }
+
+        if elements.is_empty() {
+            return Ok("{ }".into());
+        }
+
+        elements.push(Doc::Sep);
+        let result = group! {
+            "{"
+            indent! { Doc::Concat(elements) }
+            "}"
+        };
+        Ok(result)
+    }
+
+    fn value<'a>(&mut self, v: &'a Value) -> Result<Doc<'a>> {
+        let result = match v {
+            Value::Null => Doc::from("null").with_markup(Markup::Keyword),
+            Value::Bool(true) => Doc::from("true").with_markup(Markup::Keyword),
+            Value::Bool(false) => Doc::from("false").with_markup(Markup::Keyword),
+            Value::Int(i) => self.int(*i)?,
+            Value::Number(d) => Doc::from(d.to_string()).with_markup(Markup::Number),
+            Value::String(s) => self.string(s).with_markup(Markup::String),
+            Value::List(vs) => self.list(vs.iter())?,
+            // Nix has no set type, we format sets as lists.
+            Value::Set(vs) => self.list(vs.iter())?,
+            Value::Dict(vs) => self.attrset(vs.iter())?,
+            Value::Function(..) => self.error("Functions cannot be exported as Nix.")?,
+            Value::BuiltinFunction(..) => self.error("Functions cannot be exported as Nix.")?,
+            Value::BuiltinMethod { .. } => self.error("Methods cannot be exported as Nix.")?,
+        };
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::is_nix_identifier;
+
+    #[test]
+    fn is_nix_identifier_excludes_keywords() {
+        assert!(is_nix_identifier("services"));
+        assert!(is_nix_identifier("x'"));
+        assert!(is_nix_identifier("enable-ipv6"));
+        assert!(is_nix_identifier("_module"));
+        assert!(!is_nix_identifier(""));
+        assert!(!is_nix_identifier("1password"));
+        assert!(!is_nix_identifier("'quoted"));
+        assert!(!is_nix_identifier("with"));
+        assert!(!is_nix_identifier("inherit"));
+        assert!(!is_nix_identifier("a.b"));
+    }
+}
@@ -0,0 +1,205 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Formatter that prints values as Apple property lists, in the XML format.
+//!
+//! Dicts become `<dict>`, lists and sets become `<array>`. Property lists have
+//! no null, so null is an error, and keys must be strings. RCL has no byte
+//! strings or dates, so we never output `<data>` or `<date>`. See also
+//! <https://developer.apple.com/library/archive/documentation/Darwin/Reference/ManPages/man5/plist.5.html>.
+
+use crate::error::{IntoError, PathElement, Result};
+use crate::markup::Markup;
+use crate::pprint::{concat, indent, Doc};
+use crate::runtime::Value;
+use crate::source::Span;
+
+/// The lines that precede the top-level value.
+const HEADER: &str = std::concat!(
+    r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+    "\n",
+    r#"<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">"#,
+    "\n",
+    r#"<plist version="1.0">"#,
+);
+
+/// Render a value as XML property list.
+pub fn format_plist(caller: Span, v: &Value) -> Result<Doc> {
+    let mut formatter = Formatter::new(caller);
+    let result = concat! {
+        Doc::lines(HEADER)
+        Doc::HardBreak
+        formatter.value(v)?
+        Doc::HardBreak
+        "</plist>"
+    };
+    Ok(result)
+}
+
+/// Escape a string for use as XML character data.
+///
+/// Newlines are preserved as line breaks. A carriage return would get
+/// normalized to a newline by the XML parser, and the printer trims whitespace
+/// at the end of a line, so we write those as character references. XML 1.0
+/// cannot represent other control characters at all, not even escaped, so for
+/// those we return `None`.
+fn escape_xml_doc<'a>(s: &str) -> Option<Doc<'a>> {
+    let mut parts = Vec::new();
+    let mut plain = String::new();
+    let mut chars = s.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        let escaped = match ch {
+            '&' => "&amp;",
+            '<' => "&lt;",
+            '>' => "&gt;",
+            '\r' => "&#13;",
+            ' ' if chars.peek() == Some(&'\n') => "&#32;",
+            '\t' if chars.peek() == Some(&'\n') => "&#9;",
+            '\n' => {
+                if !plain.is_empty() {
+                    parts.push(Doc::from(std::mem::take(&mut plain)));
+                }
+                parts.push(Doc::RawBreak);
+                continue;
+            }
+            '\x00'..='\x08' | '\x0b' | '\x0c' | '\x0e'..='\x1f' => return None,
+            _ => {
+                plain.push(ch);
+                continue;
+            }
+        };
+        if !plain.is_empty() {
+            parts.push(Doc::from(std::mem::take(&mut plain)));
+        }
+        parts.push(Doc::str(escaped).with_markup(Markup::Escape));
+    }
+    if !plain.is_empty() {
+        parts.push(Doc::from(plain));
+    }
+
+    Some(Doc::Concat(parts))
+}
+
+/// Helper for formatting values as property list.
+///
+/// The formatter tracks the path in the value that we are formatting from, such
+/// that we can report the location of an error, in case an error occurs.
+struct Formatter {
+    /// The source location where plist formatting was triggered from.
+    caller: Span,
+
+    /// Where we currently are in the value to be formatted.
+    path: Vec<PathElement>,
+}
+
+impl Formatter {
+    pub fn new(caller: Span) -> Formatter {
+        Formatter {
+            caller,
+            path: Vec::new(),
+        }
+    }
+
+    /// Report an error at the current value path.
+    fn error<T>(&mut self, message: &'static str) -> Result<T> {
+        // Steal the path from the formatter and move it into the error. We have
+        // to leave an empty path in its place. This is fine, because returning
+        // the error prevents further formatting.
+        let path = std::mem::take(&mut self.path);
+        self.caller.error(message).with_path(path).err()
+    }
+
+    /// Format a string as the contents of an element.
+    fn text<'a>(&mut self, s: &str) -> Result<Doc<'a>> {
+        match escape_xml_doc(s) {
+            Some(doc) => Ok(doc),
+            None => self.error(
+                "Control characters other than tab and newline cannot be exported as plist.",
+            ),
+        }
+    }
+
+    fn array<'a>(&mut self, vs: impl Iterator<Item = &'a Value>) -> Result<Doc<'a>> {
+        let mut elements = Vec::new();
+        for (i, v) in vs.enumerate() {
+            elements.push(Doc::HardBreak);
+            self.path.push(PathElement::Index(i));
+            elements.push(self.value(v)?);
+            self.path.pop().expect("Push and pop are balanced.");
+        }
+
+        if elements.is_empty() {
+            return Ok("<array/>".into());
+        }
+
+        let result = concat! {
+            "<array>"
+            indent! { Doc::Concat(elements) }
+            Doc::HardBreak
+            "</array>"
+        };
+        Ok(result)
+    }
+
+    fn dict<'a>(&mut self, vs: impl Iterator<Item = (&'a Value, &'a Value)>) -> Result<Doc<'a>> {
+        let mut elements = Vec::new();
+        for (k, v) in vs {
+            self.path.push(PathElement::Key(k.clone()));
+            let key = match k {
+                Value::String(k_str) => self.text(k_str)?,
+                _ => return self.error("To export as plist, keys must be strings."),
+            };
+            elements.push(Doc::HardBreak);
+            elements.push(concat! { "<key>" key.with_markup(Markup::Field) "</key>" });
+            elements.push(Doc::HardBreak);
+            elements.push(self.value(v)?);
+            self.path.pop().expect("Push and pop are balanced.");
+        }
+
+        if elements.is_empty() {
+            return Ok("<dict/>".into());
+        }
+
+        let result = concat! {
+            "<dict>"
+            indent! { Doc::Concat(elements) }
+            Doc::HardBreak
+            "</dict>"
+        };
+        Ok(result)
+    }
+
+    fn value<'a>(&mut self, v: &'a Value) -> Result<Doc<'a>> {
+        let result = match v {
+            Value::Null => self.error("Null cannot be exported as plist.")?,
+            Value::Bool(true) => Doc::from("<true/>").with_markup(Markup::Keyword),
+            Value::Bool(false) => Doc::from("<false/>").with_markup(Markup::Keyword),
+            Value::Int(i) if i64::try_from(*i).is_err() => {
+                return self
+                    .error("Property list integers are 64 bits, this integer is out of range.")
+            }
+            Value::Int(i) => concat! {
+                "<integer>" Doc::from(i.to_string()).with_markup(Markup::Number) "</integer>"
+            },
+            Value::Number(d) => concat! {
+                "<real>" Doc::from(d.to_string()).with_markup(Markup::Number) "</real>"
+            },
+            Value::String(s) => concat! {
+                "<string>" self.text(s)?.with_markup(Markup::String) "</string>"
+            },
+            Value::List(vs) => self.array(vs.iter())?,
+            // Property lists have no set type, we format sets as arrays.
+            Value::Set(vs) => self.array(vs.iter())?,
+            Value::Dict(vs) => self.dict(vs.iter())?,
+            Value::Function(..) => self.error("Functions cannot be exported as plist.")?,
+            Value::BuiltinFunction(..) => self.error("Functions cannot be exported as plist.")?,
+            Value::BuiltinMethod { .. } => self.error("Methods cannot be exported as plist.")?,
+        };
+        Ok(result)
+    }
+}
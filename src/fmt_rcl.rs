@@ -12,7 +12,7 @@
 use crate::markup::Markup;
 use crate::pprint::{concat, group, indent, Doc};
 use crate::runtime::Value;
-use crate::string::{escape_json, is_identifier};
+use crate::string::{escape_json_doc, is_identifier};
 
 /// Render a value as RCL.
 pub fn format_rcl(v: &Value) -> Doc {
@@ -22,9 +22,7 @@ pub fn format_rcl(v: &Value) -> Doc {
 /// Format a string.
 fn string<'a>(s: &str) -> Doc<'a> {
     // TODO: Check if the string is multiline, and possibly format using a """-string.
-    let mut into = String::with_capacity(s.len());
-    escape_json(s, &mut into);
-    concat! { "\"" into "\"" }
+    concat! { "\"" escape_json_doc(s) "\"" }
 }
 
 fn list<'a>(open: &'a str, close: &'a str, vs: impl Iterator<Item = &'a Value>) -> Doc<'a> {
@@ -104,6 +102,7 @@ fn value(v: &Value) -> Doc {
         Value::Bool(true) => Doc::from("true").with_markup(Markup::Keyword),
         Value::Bool(false) => Doc::from("false").with_markup(Markup::Keyword),
         Value::Int(i) => Doc::from(i.to_string()).with_markup(Markup::Number),
+        Value::Number(d) => Doc::from(d.to_string()).with_markup(Markup::Number),
         Value::String(s) => string(s).with_markup(Markup::String),
         Value::List(vs) => list("[", "]", vs.iter()),
         Value::Set(vs) if vs.is_empty() => group! {
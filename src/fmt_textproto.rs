@@ -0,0 +1,245 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Formatter that prints values in the Protocol Buffers text format.
+//!
+//! The top-level value must be a dict, it becomes the fields of the message.
+//! Nested dicts become nested messages, and lists and sets become repeated
+//! fields, with one line per element. Null fields are omitted, which in proto3
+//! is the same as setting them to their default value. We do not know the
+//! schema, so map fields should be written as lists of dicts with `key` and
+//! `value`, and enum fields as integers, because a string is always quoted.
+//! See also <https://protobuf.dev/reference/protobuf/textformat-spec/>.
+
+use std::collections::BTreeMap;
+
+use crate::error::{IntoError, PathElement, Result};
+use crate::markup::Markup;
+use crate::pprint::{concat, indent, Doc};
+use crate::runtime::Value;
+use crate::source::Span;
+
+/// Render a value as the fields of a textproto message.
+pub fn format_textproto(caller: Span, v: &Value) -> Result<Doc> {
+    let mut formatter = Formatter::new(caller);
+    match v {
+        Value::Dict(kv) => Ok(join_lines(formatter.fields(kv)?)),
+        _ => formatter.error("To format as textproto, the top-level value must be a dict."),
+    }
+}
+
+/// Put every line on its own line.
+fn join_lines(lines: Vec<Doc>) -> Doc {
+    let mut result = Vec::with_capacity(lines.len() * 2);
+    for line in lines {
+        if !result.is_empty() {
+            result.push(Doc::HardBreak);
+        }
+        result.push(line);
+    }
+    Doc::Concat(result)
+}
+
+/// Return whether the string is a valid protobuf field name.
+fn is_field_name(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(ch) if ch.is_ascii_alphabetic() || ch == '_' => {}
+        _ => return false,
+    }
+    chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+}
+
+/// Escape a string for use inside a double-quoted textproto string.
+///
+/// Control characters become octal escapes of their UTF-8 bytes, which every
+/// implementation of the text format accepts. Other characters, including
+/// non-ASCII ones, are written as-is, the text format is UTF-8.
+fn escape_textproto_doc<'a>(s: &str) -> Doc<'a> {
+    let mut parts = Vec::new();
+    let mut plain = String::new();
+
+    for ch in s.chars() {
+        let escaped = match ch {
+            '"' => "\\\"".to_string(),
+            '\\' => "\\\\".to_string(),
+            '\n' => "\\n".to_string(),
+            '\r' => "\\r".to_string(),
+            '\t' => "\\t".to_string(),
+            ch if ch.is_control() => {
+                let mut buf = [0; 4];
+                let bytes = ch.encode_utf8(&mut buf).bytes();
+                bytes.map(|b| format!("\\{b:03o}")).collect()
+            }
+            _ => {
+                plain.push(ch);
+                continue;
+            }
+        };
+        if !plain.is_empty() {
+            parts.push(Doc::from(std::mem::take(&mut plain)));
+        }
+        parts.push(Doc::from(escaped).with_markup(Markup::Escape));
+    }
+    if !plain.is_empty() {
+        parts.push(Doc::from(plain));
+    }
+
+    Doc::Concat(parts)
+}
+
+/// Helper for formatting values as textproto.
+///
+/// The formatter tracks the path in the value that we are formatting from, such
+/// that we can report the location of an error, in case an error occurs.
+struct Formatter {
+    /// The source location where textproto formatting was triggered from.
+    caller: Span,
+
+    /// Where we currently are in the value to be formatted.
+    path: Vec<PathElement>,
+}
+
+impl Formatter {
+    pub fn new(caller: Span) -> Formatter {
+        Formatter {
+            caller,
+            path: Vec::new(),
+        }
+    }
+
+    /// Report an error at the current value path.
+    fn error<T>(&mut self, message: &'static str) -> Result<T> {
+        // Steal the path from the formatter and move it into the error. We have
+        // to leave an empty path in its place. This is fine, because returning
+        // the error prevents further formatting.
+        let path = std::mem::take(&mut self.path);
+        self.caller.error(message).with_path(path).err()
+    }
+
+    /// Format the fields of a message, one line per field occurrence.
+    ///
+    /// Null fields are omitted, so there can be fewer lines than fields.
+    fn fields<'a>(&mut self, kv: &'a BTreeMap<Value, Value>) -> Result<Vec<Doc<'a>>> {
+        let mut lines = Vec::new();
+        for (k, v) in kv.iter() {
+            self.path.push(PathElement::Key(k.clone()));
+            let name = match k {
+                Value::String(k_str) if is_field_name(k_str) => k_str.as_ref(),
+                Value::String(..) => {
+                    return self.error("To export as textproto, keys must be field names.")
+                }
+                _ => return self.error("To export as textproto, keys must be strings."),
+            };
+            match v {
+                Value::Null => {}
+                // Protobuf has no list values, a repeated field instead occurs
+                // once per element.
+                Value::List(vs) => self.repeated(&mut lines, name, vs.iter())?,
+                Value::Set(vs) => self.repeated(&mut lines, name, vs.iter())?,
+                _ => lines.push(self.field(name, v)?),
+            }
+            self.path.pop().expect("Push and pop are balanced.");
+        }
+        Ok(lines)
+    }
+
+    fn repeated<'a>(
+        &mut self,
+        lines: &mut Vec<Doc<'a>>,
+        name: &'a str,
+        vs: impl Iterator<Item = &'a Value>,
+    ) -> Result<()> {
+        for (i, v) in vs.enumerate() {
+            self.path.push(PathElement::Index(i));
+            match v {
+                Value::List(..) | Value::Set(..) => {
+                    // Repeated fields cannot be repeated themselves.
+                    return self.error("To export as textproto, lists cannot contain lists.");
+                }
+                Value::Null => {
+                    return self.error("To export as textproto, lists cannot contain null.")
+                }
+                _ => lines.push(self.field(name, v)?),
+            }
+            self.path.pop().expect("Push and pop are balanced.");
+        }
+        Ok(())
+    }
+
+    /// Format a single occurrence of a field.
+    fn field<'a>(&mut self, name: &'a str, v: &'a Value) -> Result<Doc<'a>> {
+        let name = Doc::from(name).with_markup(Markup::Field);
+        let result = match v {
+            Value::Dict(kv) => {
+                let lines = self.fields(kv)?;
+                if lines.is_empty() {
+                    concat! { name " {}" }
+                } else {
+                    concat! {
+                        name
+                        " {"
+                        indent! { Doc::HardBreak join_lines(lines) }
+                        Doc::HardBreak
+                        "}"
+                    }
+                }
+            }
+            _ => concat! { name ": " self.scalar(v)? },
+        };
+        Ok(result)
+    }
+
+    fn scalar<'a>(&mut self, v: &'a Value) -> Result<Doc<'a>> {
+        let result = match v {
+            Value::Bool(true) => Doc::from("true").with_markup(Markup::Keyword),
+            Value::Bool(false) => Doc::from("false").with_markup(Markup::Keyword),
+            Value::Int(i) if i64::try_from(*i).is_err() => {
+                return self.error("Textproto integers are 64 bits, this integer is out of range.")
+            }
+            Value::Int(i) => Doc::from(i.to_string()).with_markup(Markup::Number),
+            Value::Number(d) => Doc::from(d.to_string()).with_markup(Markup::Number),
+            Value::String(s) => {
+                concat! { "\"" escape_textproto_doc(s) "\"" }.with_markup(Markup::String)
+            }
+            Value::Function(..) => self.error("Functions cannot be exported as textproto.")?,
+            Value::BuiltinFunction(..) => {
+                self.error("Functions cannot be exported as textproto.")?
+            }
+            Value::BuiltinMethod { .. } => {
+                self.error("Methods cannot be exported as textproto.")?
+            }
+            Value::Null | Value::List(..) | Value::Set(..) | Value::Dict(..) => {
+                unreachable!("Handled by the caller.")
+            }
+        };
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{escape_textproto_doc, is_field_name};
+    use crate::pprint::Config;
+
+    #[test]
+    fn is_field_name_accepts_identifiers() {
+        assert!(is_field_name("server_name"));
+        assert!(is_field_name("_private2"));
+        assert!(!is_field_name(""));
+        assert!(!is_field_name("2fast"));
+        assert!(!is_field_name("kebab-case"));
+        assert!(!is_field_name("a.b"));
+    }
+
+    #[test]
+    fn escape_textproto_doc_uses_octal_for_control() {
+        let doc = escape_textproto_doc("a\"\\\n\x01\x7f\u{85}ü");
+        let result = doc.println(&Config::default()).to_string_no_markup();
+        assert_eq!(result, "a\\\"\\\\\\n\\001\\177\\302\\205ü\n");
+    }
+}
@@ -204,7 +204,11 @@ impl Formatter {
             Value::Null => self.error("Null cannot be exported as TOML.")?,
             Value::Bool(true) => Doc::from("true").with_markup(Markup::Keyword),
             Value::Bool(false) => Doc::from("false").with_markup(Markup::Keyword),
+            Value::Int(i) if i64::try_from(*i).is_err() => {
+                return self.error("TOML integers are 64 bits, this integer is out of range.")
+            }
             Value::Int(i) => Doc::from(i.to_string()).with_markup(Markup::Number),
+            Value::Number(d) => Doc::from(d.to_string()).with_markup(Markup::Number),
             Value::String(s) => self.string(s).with_markup(Markup::String),
             Value::List(vs) => self.array(vs.iter())?,
             // TOML has no set type, we format sets as arrays (lists).
@@ -20,9 +20,13 @@ use crate::types::{FunctionArg, Side, SourcedType, Type};
 pub fn format_type(type_: &Type) -> Doc {
     match type_ {
         // For primitive types the short name is the full name.
-        Type::Any | Type::Void | Type::Bool | Type::Int | Type::Null | Type::String => {
-            Doc::from(type_.short_name()).with_markup(Markup::Type)
-        }
+        Type::Any
+        | Type::Void
+        | Type::Bool
+        | Type::Int
+        | Type::Number
+        | Type::Null
+        | Type::String => Doc::from(type_.short_name()).with_markup(Markup::Type),
 
         // Collection types.
         Type::Dict(kv) => concat! {
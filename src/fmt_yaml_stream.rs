@@ -14,16 +14,27 @@ use crate::pprint::Doc;
 use crate::runtime::Value;
 use crate::source::Span;
 
+/// Return the elements to format, the top-level value must be a list.
+pub fn elements(caller: Span, v: &Value) -> Result<&[Value]> {
+    match v {
+        Value::List(xs) => Ok(xs),
+        _ => caller
+            .error("To format as YAML stream, the top-level value must be a list.")
+            .err(),
+    }
+}
+
 /// Render a value in YAML stream format.
-pub fn format_yaml_stream(caller: Span, v: &Value) -> Result<Doc> {
-    let elements = match v {
-        Value::List(xs) => xs,
-        _ => {
-            return caller
-                .error("To format as YAML stream, the top-level value must be a list.")
-                .err()
-        }
-    };
+///
+/// When `sources` is provided, it holds one name per element, and every
+/// document is preceded by a `# Source:` comment with that name, like the
+/// output of `helm template`.
+pub fn format_yaml_stream<'a>(
+    caller: Span,
+    sources: Option<&[String]>,
+    v: &'a Value,
+) -> Result<Doc<'a>> {
+    let elements = elements(caller, v)?;
 
     let mut formatter = Formatter::new(caller);
     let mut parts = Vec::new();
@@ -34,6 +45,10 @@ pub fn format_yaml_stream(caller: Span, v: &Value) -> Result<Doc> {
         }
         parts.push(Doc::str("---").with_markup(Markup::Comment));
         parts.push(Doc::HardBreak);
+        if let Some(source) = sources.and_then(|names| names.get(i)) {
+            parts.push(Doc::from(format!("# Source: {source}")).with_markup(Markup::Comment));
+            parts.push(Doc::HardBreak);
+        }
         formatter.path.push(PathElement::Index(i));
         parts.push(formatter.value(element)?);
         formatter.path.pop();
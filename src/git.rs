@@ -0,0 +1,267 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Loading documents from a revision in a Git repository, for `rcl diff --git`.
+//!
+//! The files at a past revision need not exist in the working tree, so unlike
+//! [`SandboxFilesystem`](crate::loader::SandboxFilesystem), we cannot resolve
+//! paths by canonicalizing them. Instead we resolve paths lexically, relative
+//! to the working directory, and ask `git cat-file` for the contents.
+
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::error::{Error, Result};
+use crate::loader::{Document, Filesystem, OpenMode, PathLookup, SandboxMode};
+use crate::pprint::{concat, Doc};
+
+/// Read files from a Git revision, rather than from the working tree.
+///
+/// Documents are named `<rev>:<path>`, with the path relative to the working
+/// directory. Because the name includes the revision, the same file at two
+/// revisions is loaded as two different documents.
+#[derive(Debug)]
+pub struct GitFilesystem {
+    rev: String,
+    mode: SandboxMode,
+    workdir: PathBuf,
+}
+
+impl GitFilesystem {
+    pub fn new(rev: &str, mode: SandboxMode, workdir: Option<&str>) -> io::Result<GitFilesystem> {
+        let workdir = match workdir {
+            Some(d) => PathBuf::from(d),
+            None => std::env::current_dir()?,
+        };
+        let result = GitFilesystem {
+            rev: rev.to_string(),
+            mode,
+            workdir: std::fs::canonicalize(workdir)?,
+        };
+        Ok(result)
+    }
+
+    /// Join `path` onto the directory `base`, and normalize `.` and `..`.
+    ///
+    /// Both are relative to the working directory. The result can start with
+    /// `..` components only when the sandbox policy allows that.
+    fn resolve_relative(
+        &self,
+        base: &[&str],
+        path: &str,
+        sandbox_mode: SandboxMode,
+    ) -> Result<PathLookup> {
+        let mut components: Vec<&str> = base.to_vec();
+        for component in path.split('/') {
+            match component {
+                "" | "." => continue,
+                ".." if components.last().map_or(false, |last| *last != "..") => {
+                    components.pop();
+                }
+                _ => components.push(component),
+            }
+        }
+        if sandbox_mode == SandboxMode::Workdir && components.first() == Some(&"..") {
+            return Error::new(concat! {
+                "Sandbox policy '"
+                Doc::highlight("workdir")
+                "' does not allow loading '"
+                Doc::path(path)
+                "' because it lies outside of the working directory."
+            })
+            .with_help(concat! {
+                "Try executing from a parent directory or use '"
+                Doc::highlight("--sandbox=unrestricted")
+                "'."
+            })
+            .err();
+        }
+        let relative = components.join("/");
+        let result = PathLookup {
+            name: format!("{}:{}", self.rev, relative),
+            path: PathBuf::from(format!("{}:./{}", self.rev, relative)),
+        };
+        Ok(result)
+    }
+}
+
+/// Return the paths of the `.rcl` files that are staged in the Git index.
+///
+/// Git reports paths relative to the root of the repository. We return them
+/// relative to the working directory when they are inside it, so they are
+/// named the same way as files passed on the command line.
+pub fn get_staged_rcl_files(workdir: Option<&str>) -> Result<Vec<String>> {
+    let workdir = match workdir {
+        Some(d) => PathBuf::from(d),
+        None => std::env::current_dir().map_err(|err| {
+            Error::new(concat! { "Failed to get working directory: " err.to_string() })
+        })?,
+    };
+    let run_git = |args: &[&str]| -> Result<Vec<u8>> {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(&workdir)
+            .output()
+            .map_err(|err| Error::new(concat! { "Failed to run git: " err.to_string() }))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let reason = stderr.trim();
+            let reason = reason.strip_prefix("fatal: ").unwrap_or(reason);
+            return Error::new(concat! {
+                "Failed to list staged files: " reason.to_string()
+            })
+            .err();
+        }
+        Ok(output.stdout)
+    };
+
+    let toplevel = run_git(&["rev-parse", "--show-toplevel"])?;
+    let toplevel = PathBuf::from(String::from_utf8_lossy(&toplevel).trim());
+    // Deleted files can't be formatted, so we exclude those with the filter.
+    let names = run_git(&[
+        "diff",
+        "--cached",
+        "--name-only",
+        "-z",
+        "--diff-filter=ACMR",
+    ])?;
+
+    let workdir = std::fs::canonicalize(&workdir).unwrap_or(workdir);
+    let toplevel = std::fs::canonicalize(&toplevel).unwrap_or(toplevel);
+    let mut result = Vec::new();
+    for name in split_nul(&String::from_utf8_lossy(&names)) {
+        if !name.ends_with(".rcl") {
+            continue;
+        }
+        let path = toplevel.join(name);
+        let path = path.strip_prefix(&workdir).unwrap_or(&path);
+        result.push(path.to_string_lossy().into_owned());
+    }
+    Ok(result)
+}
+
+/// Split a NUL-separated list of paths, as produced by `git -z` and `find -print0`.
+pub fn split_nul(list: &str) -> impl Iterator<Item = &str> {
+    list.split('\0').filter(|name| !name.is_empty())
+}
+
+impl Filesystem for GitFilesystem {
+    fn resolve(&self, path: &str, from: &str) -> Result<PathLookup> {
+        if let Some(relative_to_workdir) = path.strip_prefix("//") {
+            return self.resolve_relative(&[], relative_to_workdir, self.mode);
+        }
+        if path.starts_with('/') {
+            return Error::new("Importing absolute paths is not allowed.").err();
+        }
+        // The `from` document was loaded by us, so it is named `<rev>:<path>`.
+        let from = from.strip_prefix(&self.rev).unwrap_or(from);
+        let from = from.strip_prefix(':').unwrap_or(from);
+        let mut base: Vec<&str> = from.split('/').collect();
+        base.pop();
+        self.resolve_relative(&base, path, self.mode)
+    }
+
+    fn resolve_entrypoint(&self, path: &str) -> Result<PathLookup> {
+        if path.starts_with('/') {
+            return Error::new(concat! {
+                "Cannot load '"
+                Doc::path(path)
+                "' from a Git revision, the path must be relative."
+            })
+            .err();
+        }
+        // As for the sandbox filesystem, the entrypoint is implicitly trusted.
+        self.resolve_relative(&[], path, SandboxMode::Unrestricted)
+    }
+
+    fn resolve_cli_output(&self, path: &str) -> PathBuf {
+        self.workdir.join(path)
+    }
+
+    fn load(&self, path: PathLookup) -> Result<Document> {
+        let output = Command::new("git")
+            .arg("cat-file")
+            .arg("blob")
+            .arg(&path.path)
+            .current_dir(&self.workdir)
+            .output()
+            .map_err(|err| Error::new(concat! { "Failed to run git: " err.to_string() }))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let reason = stderr.trim();
+            let reason = reason.strip_prefix("fatal: ").unwrap_or(reason);
+            return Error::new(concat! {
+                "Failed to read '"
+                Doc::path(path.name)
+                "' from Git: "
+                reason.to_string()
+            })
+            .err();
+        }
+
+        let data = String::from_utf8(output.stdout).map_err(|_| {
+            Error::new(concat! {
+                "Failed to read '"
+                Doc::path(&path.name)
+                "' from Git: the file is not valid UTF-8."
+            })
+        })?;
+
+        Ok(Document::new(path.name, data))
+    }
+
+    fn open_build_output(&self, _: &str, _: &str, _: OpenMode) -> Result<File> {
+        Error::new("Writing files is not possible when reading from a Git revision.").err()
+    }
+
+    fn get_relative_path<'a>(&self, path: &'a Path) -> &'a Path {
+        path
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{split_nul, GitFilesystem};
+    use crate::loader::{Filesystem, SandboxMode};
+
+    fn resolve(mode: SandboxMode, path: &str, from: &str) -> Result<String, String> {
+        let fs = GitFilesystem::new("HEAD", mode, None).unwrap();
+        match fs.resolve(path, from) {
+            Ok(lookup) => Ok(lookup.name),
+            Err(err) => Err(format!("{:?}", err.kind)),
+        }
+    }
+
+    #[test]
+    fn resolve_normalizes_paths_relative_to_importer() {
+        let workdir = SandboxMode::Workdir;
+        let resolved = resolve(workdir, "b.rcl", "HEAD:conf/a.rcl");
+        assert_eq!(resolved.as_deref(), Ok("HEAD:conf/b.rcl"));
+        let resolved = resolve(workdir, "./../lib/./b.rcl", "HEAD:conf/a.rcl");
+        assert_eq!(resolved.as_deref(), Ok("HEAD:lib/b.rcl"));
+        let resolved = resolve(workdir, "//lib/b.rcl", "HEAD:conf/a.rcl");
+        assert_eq!(resolved.as_deref(), Ok("HEAD:lib/b.rcl"));
+    }
+
+    #[test]
+    fn split_nul_skips_empty_names() {
+        let names: Vec<&str> = split_nul("a.rcl\0b c.rcl\0\0").collect();
+        assert_eq!(names, ["a.rcl", "b c.rcl"]);
+        assert_eq!(split_nul("").count(), 0);
+    }
+
+    #[test]
+    fn resolve_enforces_sandbox() {
+        assert!(resolve(SandboxMode::Workdir, "../b.rcl", "HEAD:a.rcl").is_err());
+        assert!(resolve(SandboxMode::Workdir, "/etc/passwd", "HEAD:a.rcl").is_err());
+        let resolved = resolve(SandboxMode::Unrestricted, "../../b.rcl", "HEAD:conf/a.rcl");
+        assert_eq!(resolved.as_deref(), Ok("HEAD:../b.rcl"));
+    }
+}
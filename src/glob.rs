@@ -0,0 +1,346 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Matching paths against glob patterns.
+//!
+//! This is the syntax that `.editorconfig` uses, which is a superset of the
+//! syntax that `.gitignore` uses. Paths are always separated by `/`.
+
+/// A parsed glob pattern.
+#[derive(Clone, Debug)]
+pub struct Glob {
+    tokens: Vec<Token>,
+}
+
+impl Glob {
+    /// Parse a glob pattern.
+    ///
+    /// Characters with a special meaning can be escaped with a backslash.
+    /// Brackets and braces without a matching closing bracket match literally.
+    pub fn new(pattern: &str) -> Glob {
+        let chars: Vec<char> = pattern.chars().collect();
+        Glob {
+            tokens: parse_glob(&chars),
+        }
+    }
+
+    /// Return whether the glob matches the entire path.
+    pub fn matches(&self, path: &str) -> bool {
+        matches(&self.tokens, path)
+    }
+}
+
+/// A part of a glob.
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Literal(char),
+    /// `?`, any single character except `/`.
+    Any,
+    /// `*`, any string that does not contain `/`.
+    Star,
+    /// `**`, any string.
+    DoubleStar,
+    /// `/**/`, a slash, or any string that starts and ends with a slash.
+    AnyDirs,
+    /// `[abc]` or `[!a-z]`, a single character in (or not in) the ranges, except `/`.
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+    /// `{a,b,c}`, any of the alternatives.
+    Alt(Vec<Vec<Token>>),
+    /// `{1..5}`, an integer in the inclusive range.
+    Range(i64, i64),
+}
+
+fn parse_glob(chars: &[char]) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if i + 1 < chars.len() => {
+                tokens.push(Token::Literal(chars[i + 1]));
+                i += 2;
+                continue;
+            }
+            '/' if chars[i + 1..].starts_with(&['*', '*', '/']) => {
+                tokens.push(Token::AnyDirs);
+                i += 3;
+            }
+            '?' => tokens.push(Token::Any),
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                tokens.push(Token::DoubleStar);
+                i += 1;
+            }
+            '*' => tokens.push(Token::Star),
+            '[' => match parse_class(&chars[i + 1..]) {
+                Some((token, len)) => {
+                    tokens.push(token);
+                    i += len + 1;
+                }
+                None => tokens.push(Token::Literal('[')),
+            },
+            '{' => match parse_braces(&chars[i + 1..]) {
+                Some((token, len)) => {
+                    tokens.push(token);
+                    i += len + 1;
+                }
+                None => tokens.push(Token::Literal('{')),
+            },
+            c => tokens.push(Token::Literal(c)),
+        }
+        i += 1;
+    }
+    tokens
+}
+
+/// Parse the inside of a `[...]` class, return the token and the index of `]`.
+fn parse_class(chars: &[char]) -> Option<(Token, usize)> {
+    let end = chars.iter().position(|c| *c == ']')?;
+    let mut inner = &chars[..end];
+    let negated = matches!(inner.first(), Some('!' | '^'));
+    if negated {
+        inner = &inner[1..];
+    }
+    let mut ranges = Vec::new();
+    let mut i = 0;
+    while i < inner.len() {
+        if i + 2 < inner.len() && inner[i + 1] == '-' {
+            ranges.push((inner[i], inner[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((inner[i], inner[i]));
+            i += 1;
+        }
+    }
+    Some((Token::Class { negated, ranges }, end))
+}
+
+/// Parse the inside of a `{...}` group, return the token and the index of `}`.
+fn parse_braces(chars: &[char]) -> Option<(Token, usize)> {
+    let mut depth = 0;
+    let mut commas = Vec::new();
+    let mut end = None;
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '{' => depth += 1,
+            '}' if depth == 0 => {
+                end = Some(i);
+                break;
+            }
+            '}' => depth -= 1,
+            ',' if depth == 0 => commas.push(i),
+            _ => {}
+        }
+        i += 1;
+    }
+    let end = end?;
+    let inner = &chars[..end];
+
+    if commas.is_empty() {
+        let inner: String = inner.iter().collect();
+        let (lo, hi) = inner.split_once("..")?;
+        let lo: i64 = lo.parse().ok()?;
+        let hi: i64 = hi.parse().ok()?;
+        return Some((Token::Range(lo.min(hi), lo.max(hi)), end));
+    }
+
+    let mut alts = Vec::with_capacity(commas.len() + 1);
+    let mut start = 0;
+    for comma in commas.into_iter().chain(std::iter::once(end)) {
+        alts.push(parse_glob(&inner[start..comma]));
+        start = comma + 1;
+    }
+    Some((Token::Alt(alts), end))
+}
+
+/// Match a glob against a path.
+///
+/// When the tokens after a star fail to match, the star consumes one more
+/// character, and we retry the tokens after it. Only the most recent star needs
+/// to be retried, because a later star can absorb anything that the earlier one
+/// could, so this takes O(n·m) steps rather than exponentially many. A `*`
+/// cannot absorb a slash, so when it gets stuck, we retry the most recent `**`
+/// instead. This relies on `?` and classes not matching a slash either.
+fn matches(tokens: &[Token], path: &str) -> bool {
+    /// A star to retry: the index of the token after it, and the end of its match.
+    #[derive(Clone, Copy)]
+    struct Retry {
+        token: usize,
+        end: usize,
+    }
+
+    let mut t = 0;
+    let mut p = 0;
+    let mut star: Option<(Retry, bool)> = None;
+    let mut double_star: Option<Retry> = None;
+
+    loop {
+        let rest = &path[p..];
+        let next_char = rest.chars().next();
+        let len = match tokens.get(t) {
+            None if rest.is_empty() => return true,
+            None => None,
+            Some(token @ (Token::Star | Token::DoubleStar)) => {
+                let retry = Retry {
+                    token: t + 1,
+                    end: p,
+                };
+                let stop_at_slash = *token == Token::Star;
+                star = Some((retry, stop_at_slash));
+                if !stop_at_slash {
+                    double_star = Some(retry);
+                }
+                t += 1;
+                continue;
+            }
+            Some(Token::Literal(expected)) => next_char.filter(|c| c == expected),
+            Some(Token::Any) => next_char.filter(|c| *c != '/'),
+            Some(Token::Class { negated, ranges }) => next_char.filter(|c| {
+                let in_class = ranges.iter().any(|(lo, hi)| lo <= c && c <= hi);
+                *c != '/' && in_class != *negated
+            }),
+            Some(token) => {
+                if matches_variable(token, &tokens[t + 1..], rest) {
+                    return true;
+                }
+                None
+            }
+        }
+        .map(char::len_utf8);
+
+        if let Some(len) = len {
+            t += 1;
+            p += len;
+            continue;
+        }
+
+        // The token does not match here. Retry the most recent star with one
+        // more character, or the most recent `**` when it is a `*` that would
+        // have to absorb a slash.
+        let retry = match star {
+            Some((retry, true)) if path[retry.end..].starts_with('/') => double_star,
+            Some((retry, _)) => Some(retry),
+            None => None,
+        };
+        let Some(Retry { token, end }) = retry else {
+            return false;
+        };
+        let Some(c) = path[end..].chars().next() else {
+            return false;
+        };
+        let retry = Retry {
+            token,
+            end: end + c.len_utf8(),
+        };
+        let stop_at_slash = Some(token) != double_star.map(|r| r.token);
+        if !stop_at_slash {
+            double_star = Some(retry);
+        }
+        star = Some((retry, stop_at_slash));
+        t = retry.token;
+        p = retry.end;
+    }
+}
+
+/// Match a token that can match strings of different lengths, followed by the rest.
+fn matches_variable(token: &Token, rest: &[Token], path: &str) -> bool {
+    match token {
+        Token::AnyDirs => {
+            let Some(after_slash) = path.strip_prefix('/') else {
+                return false;
+            };
+            after_slash
+                .char_indices()
+                .filter(|(_, c)| *c == '/')
+                .map(|(i, _)| i + 1)
+                .chain(std::iter::once(0))
+                .any(|i| matches(rest, &after_slash[i..]))
+        }
+        Token::Alt(alts) => alts.iter().any(|alt| {
+            // Match the alternative followed by the rest, which needs them in
+            // one sequence. Globs are short, so the copying is fine.
+            let mut sequence = alt.clone();
+            sequence.extend(rest.iter().cloned());
+            matches(&sequence, path)
+        }),
+        Token::Range(lo, hi) => {
+            let digits_len = path
+                .char_indices()
+                .find(|(i, c)| !(c.is_ascii_digit() || (*i == 0 && *c == '-')))
+                .map_or(path.len(), |(i, _)| i);
+            (1..=digits_len).any(|len| match path[..len].parse::<i64>() {
+                Ok(n) => *lo <= n && n <= *hi && matches(rest, &path[len..]),
+                Err(..) => false,
+            })
+        }
+        _ => unreachable!("Tokens of a single character are matched in `matches`."),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Glob;
+
+    fn glob(pattern: &str, path: &str) -> bool {
+        Glob::new(pattern).matches(path)
+    }
+
+    #[test]
+    fn glob_matches_wildcards() {
+        assert!(glob("*.rcl", "a.rcl"));
+        assert!(!glob("*.rcl", "conf/a.rcl"));
+        assert!(glob("**.rcl", "conf/a.rcl"));
+        assert!(glob("a.rc?", "a.rcl"));
+        assert!(!glob("a?rcl", "a/rcl"));
+        assert!(glob("[ab].rcl", "b.rcl"));
+        assert!(!glob("[!ab].rcl", "b.rcl"));
+        assert!(!glob("[^ab].rcl", "a.rcl"));
+        assert!(glob("[a-c].rcl", "c.rcl"));
+        assert!(glob("[a.rcl", "[a.rcl"));
+        assert!(glob("\\*.rcl", "*.rcl"));
+        assert!(!glob("\\*.rcl", "a.rcl"));
+        assert!(!glob("a[!b]c", "a/c"));
+        assert!(!glob("a[/]c", "a/c"));
+    }
+
+    #[test]
+    fn glob_matches_many_stars_in_linear_time() {
+        // With naive backtracking, every star retries every split of the path
+        // for the stars after it, which takes exponential time for these.
+        let path = "a".repeat(100);
+        assert!(!glob("*a*a*a*a*a*a*a*a*a*a*b", &path));
+        assert!(!glob("**a**a**a**a**a**a**a**a**a**a**b", &path));
+        assert!(glob("*a*a*a*a*a*a*a*a*a*a*", &path));
+        let path = format!("{}/b", "a/".repeat(50));
+        assert!(glob("**a*/**a*/**a*/**b", &path));
+        assert!(!glob("*a*/*a*/*a*/*a*/*b", &path));
+    }
+
+    #[test]
+    fn glob_matches_braces() {
+        assert!(glob("*.{rcl,json}", "a.json"));
+        assert!(glob("{a,b{c,d}}.rcl", "bd.rcl"));
+        assert!(!glob("{a,b{c,d}}.rcl", "b.rcl"));
+        assert!(glob("{a}.rcl", "{a}.rcl"));
+        assert!(glob("v{1..10}.rcl", "v7.rcl"));
+        assert!(!glob("v{1..10}.rcl", "v11.rcl"));
+        assert!(glob("v{-3..3}.rcl", "v-2.rcl"));
+    }
+
+    #[test]
+    fn glob_double_star_matches_zero_or_more_dirs() {
+        assert!(glob("/**/a.rcl", "/a.rcl"));
+        assert!(glob("/**/a.rcl", "/x/y/a.rcl"));
+        assert!(!glob("/**/a.rcl", "/xa.rcl"));
+        assert!(glob("conf/**/a.rcl", "conf/a.rcl"));
+        assert!(glob("conf/**/a.rcl", "conf/x/a.rcl"));
+        assert!(!glob("conf/**/a.rcl", "other/a.rcl"));
+    }
+}
@@ -7,12 +7,13 @@
 
 //! A syntax highlighter that acts on the token stream.
 
-use crate::lexer::{Lexeme, Token};
+use crate::lexer::{self, Lexeme, Token};
 use crate::markup::{Markup, MarkupString};
+use crate::pprint::Doc;
+use crate::source::DocId;
 
-// TODO: These are now unused. Bring back highlighting of builtins.
-#[allow(dead_code)]
-const BUILTINS: &[&str] = &[
+/// Names of built-in functions and methods, highlighted as builtins.
+pub const BUILTINS: &[&str] = &[
     "all",
     "any",
     "chars",
@@ -47,12 +48,19 @@ const BUILTINS: &[&str] = &[
     "values",
 ];
 
-fn get_markup(token: &Token) -> Markup {
+/// Names of built-in types, highlighted as types.
+pub const TYPES: &[&str] = &[
+    "Any", "Bool", "Dict", "Int", "List", "Null", "Number", "Set", "String", "Union", "Void",
+];
+
+fn get_markup(token: &Token, text: &str) -> Markup {
     match token {
         Token::LineComment => Markup::Comment,
         Token::NumBinary | Token::NumHexadecimal | Token::NumDecimal => Markup::Number,
         Token::QuoteOpen(..) | Token::QuoteClose | Token::StringInner => Markup::String,
         Token::HoleOpen | Token::HoleClose | Token::Escape(..) => Markup::Escape,
+        Token::Ident if TYPES.contains(&text) => Markup::Type,
+        Token::Ident if BUILTINS.contains(&text) => Markup::Builtin,
         Token::Ident => Markup::Field,
 
         Token::KwAnd
@@ -85,12 +93,82 @@ pub fn highlight<'a>(tokens: &[Lexeme], input: &'a str) -> MarkupString<'a> {
             out.push(&input[end..span.start()], Markup::None);
         }
 
-        let markup = get_markup(token);
         let string = &input[span.start()..span.end()];
-        out.push(string, markup);
+        out.push(string, get_markup(token, string));
 
         end = span.end();
     }
 
     out
 }
+
+/// Highlight the byte range `start..end` of a document as a [`Doc`].
+///
+/// This lexes the full document, so that a range that starts inside e.g. a
+/// multi-line string is still highlighted correctly. If the document fails to
+/// lex, the range is returned without highlighting.
+pub fn highlight_range(doc: DocId, input: &str, start: usize, end: usize) -> Doc {
+    if start >= end {
+        return Doc::Empty;
+    }
+    let tokens = match lexer::lex(doc, input) {
+        Ok(tokens) => tokens,
+        Err(..) => return Doc::str(&input[start..end]),
+    };
+
+    let mut parts = Vec::new();
+    let mut pos = start;
+
+    for (token, span) in &tokens {
+        if span.end() <= pos {
+            continue;
+        }
+        if span.start() >= end {
+            break;
+        }
+        // Space between tokens is not highlighted.
+        if span.start() > pos {
+            parts.push(Doc::str(&input[pos..span.start()]));
+            pos = span.start();
+        }
+        // Tokens can extend beyond the range (multi-line strings do), clip them.
+        let token_end = span.end().min(end);
+        let markup = get_markup(token, &input[span.start()..span.end()]);
+        parts.push(Doc::str(&input[pos..token_end]).with_markup(markup));
+        pos = token_end;
+    }
+
+    if pos < end {
+        parts.push(Doc::str(&input[pos..end]));
+    }
+
+    Doc::Concat(parts)
+}
+
+#[cfg(test)]
+mod test {
+    use super::highlight_range;
+    use crate::markup::Markup;
+    use crate::pprint::{Config, Doc};
+    use crate::source::DocId;
+
+    #[test]
+    fn highlight_range_clips_tokens() {
+        let input = "let x = \"a\nb\"; x";
+        let doc = highlight_range(DocId(0), input, 0, 10);
+        let cfg = Config::default();
+        let result = doc.println(&cfg);
+        let expected = [
+            ("let", Markup::Keyword),
+            (" ", Markup::None),
+            ("x", Markup::Field),
+            (" ", Markup::None),
+            ("=", Markup::None),
+            (" ", Markup::None),
+            ("\"", Markup::String),
+            ("a", Markup::String),
+        ];
+        assert_eq!(&result.fragments[..expected.len()], &expected[..]);
+        assert!(matches!(highlight_range(DocId(0), input, 3, 3), Doc::Empty));
+    }
+}
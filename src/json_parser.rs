@@ -0,0 +1,395 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! A parser for JSON documents.
+//!
+//! RCL is a superset of JSON, so any JSON document can be evaluated as RCL too.
+//! This parser is for inputs that are data rather than code: it accepts only
+//! the JSON grammar of RFC 8259, and it produces a value directly, without
+//! typechecking or evaluating anything.
+
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use crate::decimal::Decimal;
+use crate::error::{IntoError, Result};
+use crate::runtime::Value;
+use crate::source::{DocId, Span};
+
+/// Parse a JSON document into a value.
+pub fn parse_json(doc: DocId, input: &str) -> Result<Value> {
+    let mut parser = Parser {
+        doc,
+        input,
+        pos: 0,
+        depth: 0,
+    };
+    let result = parser.parse_value()?;
+    parser.skip_whitespace();
+    if parser.pos < input.len() {
+        return parser
+            .span(parser.pos, input.len())
+            .error("Expected the document to end after the value.")
+            .err();
+    }
+    Ok(result)
+}
+
+struct Parser<'a> {
+    doc: DocId,
+    input: &'a str,
+    pos: usize,
+    /// The nesting depth of arrays and objects, to prevent stack overflow.
+    depth: u32,
+}
+
+impl<'a> Parser<'a> {
+    fn span(&self, start: usize, end: usize) -> Span {
+        Span::new(self.doc, start, end)
+    }
+
+    /// Return a span of the character at the cursor, or an empty one at the end.
+    fn span_here(&self) -> Span {
+        let n = self.input[self.pos..]
+            .chars()
+            .next()
+            .map_or(0, |ch| ch.len_utf8());
+        self.span(self.pos, self.pos + n)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8, message: &'static str) -> Result<()> {
+        self.skip_whitespace();
+        if self.peek() == Some(byte) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            self.span_here().error(message).err()
+        }
+    }
+
+    fn increase_depth(&mut self) -> Result<()> {
+        self.depth += 1;
+        if self.depth >= 100 {
+            return self
+                .span_here()
+                .error("Parser recursion limit reached, please reduce nesting.")
+                .err();
+        }
+        Ok(())
+    }
+
+    fn parse_value(&mut self) -> Result<Value> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{') => self.parse_object(),
+            Some(b'[') => self.parse_array(),
+            Some(b'"') => Ok(Value::String(self.parse_string()?.into())),
+            Some(b'-' | b'0'..=b'9') => self.parse_number(),
+            Some(b't') => self.parse_literal("true", Value::Bool(true)),
+            Some(b'f') => self.parse_literal("false", Value::Bool(false)),
+            Some(b'n') => self.parse_literal("null", Value::Null),
+            None => self
+                .span_here()
+                .error("Expected a value, but the document ends here.")
+                .err(),
+            Some(..) => self.span_here().error("Expected a value.").err(),
+        }
+    }
+
+    fn parse_literal(&mut self, literal: &'static str, value: Value) -> Result<Value> {
+        if self.input[self.pos..].starts_with(literal) {
+            self.pos += literal.len();
+            Ok(value)
+        } else {
+            self.span_here().error("Expected a value.").err()
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<Value> {
+        self.increase_depth()?;
+        self.pos += 1;
+        let mut result = BTreeMap::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+        } else {
+            loop {
+                self.skip_whitespace();
+                if self.peek() != Some(b'"') {
+                    return self.span_here().error("Expected a string key.").err();
+                }
+                let key = self.parse_string()?;
+                self.expect(b':', "Expected ':' after the key.")?;
+                let value = self.parse_value()?;
+                // Like in RCL itself, for duplicate keys the last one wins.
+                result.insert(Value::String(key.into()), value);
+                self.skip_whitespace();
+                match self.peek() {
+                    Some(b',') => self.pos += 1,
+                    Some(b'}') => {
+                        self.pos += 1;
+                        break;
+                    }
+                    _ => return self.span_here().error("Expected ',' or '}'.").err(),
+                }
+            }
+        }
+        self.depth -= 1;
+        Ok(Value::Dict(Rc::new(result)))
+    }
+
+    fn parse_array(&mut self) -> Result<Value> {
+        self.increase_depth()?;
+        self.pos += 1;
+        let mut result = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+        } else {
+            loop {
+                result.push(self.parse_value()?);
+                self.skip_whitespace();
+                match self.peek() {
+                    Some(b',') => self.pos += 1,
+                    Some(b']') => {
+                        self.pos += 1;
+                        break;
+                    }
+                    _ => return self.span_here().error("Expected ',' or ']'.").err(),
+                }
+            }
+        }
+        self.depth -= 1;
+        Ok(Value::List(Rc::new(result)))
+    }
+
+    /// Parse four hex digits of a `\u` escape sequence.
+    fn parse_hex4(&mut self) -> Result<u32> {
+        let hex = self.input.get(self.pos..self.pos + 4).unwrap_or("");
+        match u32::from_str_radix(hex, 16) {
+            Ok(n) if hex.bytes().all(|b| b.is_ascii_hexdigit()) => {
+                self.pos += 4;
+                Ok(n)
+            }
+            _ => self
+                .span_here()
+                .error("Expected four hex digits after '\\u'.")
+                .err(),
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut result = String::new();
+        loop {
+            let run_start = self.pos;
+            while let Some(b) = self.peek() {
+                if b == b'"' || b == b'\\' || b < 0x20 {
+                    break;
+                }
+                self.pos += 1;
+            }
+            result.push_str(&self.input[run_start..self.pos]);
+
+            match self.peek() {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(result);
+                }
+                Some(b'\\') => {
+                    let escape_start = self.pos;
+                    self.pos += 1;
+                    let ch = match self.peek() {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\x08',
+                        Some(b'f') => '\x0c',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            self.pos += 1;
+                            let mut n = self.parse_hex4()?;
+                            // Code points outside the basic multilingual plane
+                            // are escaped as a surrogate pair.
+                            if (0xd800..0xdc00).contains(&n)
+                                && self.input[self.pos..].starts_with("\\u")
+                            {
+                                self.pos += 2;
+                                let low = self.parse_hex4()?;
+                                if (0xdc00..0xe000).contains(&low) {
+                                    n = 0x10000 + ((n - 0xd800) << 10) + (low - 0xdc00);
+                                }
+                            }
+                            match char::from_u32(n) {
+                                Some(ch) => result.push(ch),
+                                None => {
+                                    return self
+                                        .span(escape_start, self.pos)
+                                        .error("Invalid code point in escape sequence.")
+                                        .err()
+                                }
+                            }
+                            continue;
+                        }
+                        _ => {
+                            return self
+                                .span(escape_start, self.span_here().end())
+                                .error("Invalid escape sequence.")
+                                .err()
+                        }
+                    };
+                    self.pos += 1;
+                    result.push(ch);
+                }
+                Some(..) => {
+                    return self
+                        .span_here()
+                        .error("Control characters must be escaped in strings.")
+                        .err()
+                }
+                None => {
+                    return self
+                        .span(start, self.pos)
+                        .error("Unterminated string, expected '\"'.")
+                        .err()
+                }
+            }
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<Value> {
+        let start = self.pos;
+        let bytes = self.input.as_bytes();
+        let skip_digits = |mut i: usize| {
+            while i < bytes.len() && bytes[i].is_ascii_digit() {
+                i += 1;
+            }
+            i
+        };
+
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+        let int_end = skip_digits(self.pos);
+        if int_end == self.pos {
+            return self.span_here().error("Expected a digit.").err();
+        }
+        if bytes[self.pos] == b'0' && int_end > self.pos + 1 {
+            return self
+                .span(start, int_end)
+                .error("Leading zeros are not allowed in numbers.")
+                .err();
+        }
+        self.pos = int_end;
+
+        let mut is_integer = true;
+        if self.peek() == Some(b'.') {
+            is_integer = false;
+            self.pos += 1;
+            let frac_end = skip_digits(self.pos);
+            if frac_end == self.pos {
+                return self
+                    .span_here()
+                    .error("Expected a digit after the decimal point.")
+                    .err();
+            }
+            self.pos = frac_end;
+        }
+        if let Some(b'e' | b'E') = self.peek() {
+            is_integer = false;
+            self.pos += 1;
+            if let Some(b'+' | b'-') = self.peek() {
+                self.pos += 1;
+            }
+            let exp_end = skip_digits(self.pos);
+            if exp_end == self.pos {
+                return self
+                    .span_here()
+                    .error("Expected a digit of the exponent.")
+                    .err();
+            }
+            self.pos = exp_end;
+        }
+
+        let span = self.span(start, self.pos);
+        if !is_integer {
+            return match Decimal::parse(span.resolve(self.input)) {
+                Some(d) => Ok(Value::Number(d)),
+                None => span.error("Overflow in number literal.").err(),
+            };
+        }
+        match span.resolve(self.input).parse() {
+            Ok(n) => Ok(Value::Int(n)),
+            Err(..) => span.error("Overflow in integer literal.").err(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse_json;
+    use crate::fmt_rcl::format_rcl;
+    use crate::pprint::Config;
+    use crate::source::DocId;
+
+    fn parse(input: &str) -> String {
+        let value = parse_json(DocId(0), input).unwrap();
+        let cfg = Config::default();
+        format_rcl(&value).println(&cfg).to_string_no_markup()
+    }
+
+    fn fail(input: &str) -> String {
+        let err = parse_json(DocId(0), input).err().unwrap();
+        let cfg = Config::default();
+        err.message.println(&cfg).to_string_no_markup()
+    }
+
+    #[test]
+    fn parse_json_accepts_json() {
+        assert_eq!(
+            parse(r#" {"a": [1, -2, true, null], "b": {}, "a b": "x\tyé😀\/"} "#),
+            "{ a = [1, -2, true, null], \"a b\": \"x\\ty\u{e9}\u{1f600}/\", b = {} }\n",
+        );
+        assert_eq!(parse(r#"{"a": 1, "a": 2}"#), "{ a = 2 }\n");
+        assert_eq!(parse(r#""\ud83d\ude00\u00e9""#), "\"\u{1f600}\u{e9}\"\n");
+        assert_eq!(
+            parse("[0.5, -1.25e3, 1E-7, 2.0]"),
+            "[0.5, -1250.0, 1.0e-7, 2.0]\n"
+        );
+    }
+
+    #[test]
+    fn parse_json_rejects_non_json() {
+        assert_eq!(fail("[1, 2,]"), "Expected a value.\n");
+        assert_eq!(fail("{a: 1}"), "Expected a string key.\n");
+        assert_eq!(fail("01"), "Leading zeros are not allowed in numbers.\n");
+        assert_eq!(fail("1."), "Expected a digit after the decimal point.\n");
+        assert_eq!(fail("1e+"), "Expected a digit of the exponent.\n");
+        assert_eq!(fail("\"a"), "Unterminated string, expected '\"'.\n");
+        assert_eq!(
+            fail("[] []"),
+            "Expected the document to end after the value.\n"
+        );
+        assert_eq!(
+            fail(&"[".repeat(200)),
+            "Parser recursion limit reached, please reduce nesting.\n"
+        );
+    }
+}
@@ -0,0 +1,1053 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! JSON Schema support: generating schemas from types, for `rcl schema`, and
+//! validating values against schemas, for `--validate-schema`.
+//!
+//! In both directions, the schema describes the JSON that
+//! `rcl evaluate --format=json` would output for a value. We target JSON Schema
+//! 2020-12, see <https://json-schema.org/draft/2020-12/json-schema-core>.
+//!
+//! When generating, `Any` becomes the schema `true` that accepts everything,
+//! and `Void`, the type of the elements of an empty collection, becomes `false`
+//! that accepts nothing. Sets become arrays with unique items. JSON has no
+//! functions, and object keys are strings, so those types have no schema.
+//!
+//! The validator supports the assertions of the 2020-12 vocabulary, and local
+//! references with `$ref`. Annotations such as `title` and `format` are
+//! ignored. RCL has no regular expressions, so a schema that uses `pattern` is
+//! an error, rather than silently accepting values that it would reject.
+
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use crate::error::{IntoError, PathElement, Result};
+use crate::fmt_rcl::format_rcl;
+use crate::fmt_type::format_type;
+use crate::pprint::{concat, Doc};
+use crate::runtime::Value;
+use crate::source::Span;
+use crate::types::Type;
+
+/// The URI of the JSON Schema dialect that we generate.
+pub const SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// Return a JSON Schema document that describes the type.
+///
+/// Errors are reported at `caller`, because types do not generally have a
+/// span that points at the offending part.
+pub fn json_schema(caller: Span, type_: &Type) -> Result<Value> {
+    let mut schema = match type_to_schema(caller, type_)? {
+        Value::Dict(kv) => kv.as_ref().clone(),
+        // For the boolean schemas, we can't add the dialect directly, we
+        // express them as an equivalent object schema.
+        Value::Bool(true) => BTreeMap::new(),
+        Value::Bool(false) => object([("not", Value::Dict(Rc::new(BTreeMap::new())))]),
+        _ => unreachable!("Schemas are objects or booleans."),
+    };
+    schema.insert("$schema".into(), SCHEMA_DIALECT.into());
+    Ok(Value::Dict(Rc::new(schema)))
+}
+
+/// Build a dict with string keys.
+fn object<const N: usize>(entries: [(&str, Value); N]) -> BTreeMap<Value, Value> {
+    entries.into_iter().map(|(k, v)| (k.into(), v)).collect()
+}
+
+/// Return a schema that only checks the JSON `type`.
+fn primitive(json_type: &str) -> Value {
+    Value::Dict(Rc::new(object([("type", json_type.into())])))
+}
+
+/// Return the schema for the type, without the `$schema` key.
+fn type_to_schema(caller: Span, type_: &Type) -> Result<Value> {
+    let result = match type_ {
+        Type::Any => Value::Bool(true),
+        Type::Void => Value::Bool(false),
+        Type::Null => primitive("null"),
+        Type::Bool => primitive("boolean"),
+        Type::Int => primitive("integer"),
+        Type::Number => primitive("number"),
+        Type::String => primitive("string"),
+        Type::List(element) => Value::Dict(Rc::new(object([
+            ("type", "array".into()),
+            ("items", type_to_schema(caller, &element.type_)?),
+        ]))),
+        Type::Set(element) => Value::Dict(Rc::new(object([
+            ("type", "array".into()),
+            ("items", type_to_schema(caller, &element.type_)?),
+            ("uniqueItems", Value::Bool(true)),
+        ]))),
+        Type::Dict(kv) => {
+            // The key type of an empty dict is `Void`, which is fine too.
+            if !matches!(kv.key.type_, Type::String | Type::Any | Type::Void) {
+                let message = concat! {
+                    "JSON object keys are strings, but this dict has keys of type "
+                    format_type(&kv.key.type_).into_owned()
+                    "."
+                };
+                return caller.error(message).err();
+            }
+            Value::Dict(Rc::new(object([
+                ("type", "object".into()),
+                (
+                    "additionalProperties",
+                    type_to_schema(caller, &kv.value.type_)?,
+                ),
+            ])))
+        }
+        Type::Union(union) => {
+            let mut members = Vec::with_capacity(union.members.len());
+            for member in union.members.iter() {
+                members.push(type_to_schema(caller, &member.type_)?);
+            }
+            Value::Dict(Rc::new(object([("anyOf", Value::List(Rc::new(members)))])))
+        }
+        Type::Function(..) => {
+            return caller
+                .error("Functions cannot be exported as JSON, so they have no JSON Schema.")
+                .err();
+        }
+    };
+    Ok(result)
+}
+
+/// Keywords that affect validation, but that we do not support.
+const UNSUPPORTED_KEYWORDS: &[&str] = &[
+    "$dynamicRef",
+    "$recursiveRef",
+    "pattern",
+    "patternProperties",
+    "unevaluatedItems",
+    "unevaluatedProperties",
+];
+
+/// Check that the value matches the JSON Schema, report the first violation.
+///
+/// Violations are reported at `caller`, with the path to the offending part of
+/// the value.
+pub fn validate(caller: Span, schema: &Value, value: &Value) -> Result<()> {
+    let mut validator = Validator {
+        caller,
+        root: schema,
+        path: Vec::new(),
+        location: Vec::new(),
+        depth: 0,
+    };
+    match validator.check(schema, value)? {
+        None => Ok(()),
+        Some(violation) => caller
+            .error(violation.message)
+            .with_path(violation.path)
+            .with_help(concat! {
+                "This is required by the schema at '" violation.location "'."
+            })
+            .err(),
+    }
+}
+
+/// A reason why a value does not match a schema.
+struct Violation {
+    message: Doc<'static>,
+    /// The path in the value to the offending part.
+    path: Vec<PathElement>,
+    /// A JSON Pointer to the offending part of the schema.
+    location: String,
+}
+
+/// The outcome of checking a value: `None` if it matches, or why it does not.
+///
+/// Errors are problems with the schema itself, they do not depend on the value.
+type Check = Result<Option<Violation>>;
+
+/// Return the violation if there is one, continue if the value matches.
+macro_rules! check {
+    ($check:expr) => {
+        if let Some(violation) = $check? {
+            return Ok(Some(violation));
+        }
+    };
+}
+
+/// Return the JSON type name of a value, with an article.
+fn describe_type(v: &Value) -> &'static str {
+    match v {
+        Value::Null => "null",
+        Value::Bool(..) => "a boolean",
+        Value::Int(..) => "an integer",
+        Value::Number(..) => "a number",
+        Value::String(..) => "a string",
+        Value::List(..) | Value::Set(..) => "an array",
+        Value::Dict(..) => "an object",
+        Value::Function(..) | Value::BuiltinFunction(..) | Value::BuiltinMethod { .. } => {
+            "a function"
+        }
+    }
+}
+
+/// Return whether the value has the given JSON type.
+fn has_type(v: &Value, json_type: &str) -> bool {
+    match (json_type, v) {
+        ("null", Value::Null)
+        | ("boolean", Value::Bool(..))
+        | ("integer" | "number", Value::Int(..))
+        | ("number", Value::Number(..))
+        | ("string", Value::String(..))
+        | ("array", Value::List(..) | Value::Set(..))
+        | ("object", Value::Dict(..)) => true,
+        // JSON Schema considers numbers with a zero fractional part integers.
+        ("integer", Value::Number(d)) => d.exponent() >= 0,
+        _ => false,
+    }
+}
+
+/// Compare two values as JSON, where sets are arrays.
+fn json_eq(a: &Value, b: &Value) -> bool {
+    let elements = |v: &Value| -> Option<Vec<Value>> {
+        match v {
+            Value::List(xs) => Some(xs.as_ref().clone()),
+            Value::Set(xs) => Some(xs.iter().cloned().collect()),
+            _ => None,
+        }
+    };
+    match (a, b) {
+        (Value::Dict(xs), Value::Dict(ys)) => {
+            xs.len() == ys.len()
+                && xs
+                    .iter()
+                    .zip(ys.iter())
+                    .all(|((kx, vx), (ky, vy))| kx == ky && json_eq(vx, vy))
+        }
+        _ => match (elements(a), elements(b)) {
+            (Some(xs), Some(ys)) => {
+                xs.len() == ys.len() && xs.iter().zip(ys.iter()).all(|(x, y)| json_eq(x, y))
+            }
+            (None, None) => a == b,
+            _ => false,
+        },
+    }
+}
+
+/// Escape a reference token for use in a JSON Pointer, see RFC 6901.
+fn escape_pointer_token(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+/// Helper for validating values against a schema.
+///
+/// Like the formatters, the validator tracks the path in the value, such that
+/// we can report where a violation occurs. It also tracks the location in the
+/// schema, to report which part of the schema the value violates.
+struct Validator<'a> {
+    /// The source location where validation was triggered from.
+    caller: Span,
+
+    /// The root schema, which `$ref` pointers are resolved against.
+    root: &'a Value,
+
+    /// Where we currently are in the value to be validated.
+    path: Vec<PathElement>,
+
+    /// Where we currently are in the schema, as JSON Pointer reference tokens.
+    location: Vec<String>,
+
+    /// The nesting depth of schemas, to prevent stack overflow on cyclic `$ref`.
+    depth: u32,
+}
+
+impl<'a> Validator<'a> {
+    fn location_pointer(&self) -> String {
+        let mut pointer = "#".to_string();
+        for token in self.location.iter() {
+            pointer.push('/');
+            pointer.push_str(&escape_pointer_token(token));
+        }
+        pointer
+    }
+
+    fn violation(&self, message: Doc<'static>) -> Check {
+        let path = self
+            .path
+            .iter()
+            .map(|elem| match elem {
+                PathElement::Key(k) => PathElement::Key(k.clone()),
+                PathElement::Index(i) => PathElement::Index(*i),
+            })
+            .collect();
+        Ok(Some(Violation {
+            message,
+            path,
+            location: self.location_pointer(),
+        }))
+    }
+
+    /// Report a problem with the schema itself.
+    fn schema_error<T>(&self, message: Doc<'static>) -> Result<T> {
+        self.caller
+            .error(message)
+            .with_help(concat! {
+                "The problem is in the schema at '" self.location_pointer() "'."
+            })
+            .err()
+    }
+
+    /// Check the value against a subschema at the named keyword.
+    fn check_at(&mut self, keyword: &str, schema: &'a Value, value: &Value) -> Check {
+        self.location.push(keyword.to_string());
+        let result = self.check(schema, value);
+        self.location.pop().expect("Push and pop are balanced.");
+        result
+    }
+
+    /// Check whether the value matches, without reporting the violation.
+    fn matches(&mut self, keyword: &str, schema: &'a Value, value: &Value) -> Result<bool> {
+        Ok(self.check_at(keyword, schema, value)?.is_none())
+    }
+
+    fn check(&mut self, schema: &'a Value, value: &Value) -> Check {
+        self.depth += 1;
+        if self.depth > 100 {
+            return self.schema_error(
+                "Schema nesting limit reached, the schema may contain a cyclic '$ref'.".into(),
+            );
+        }
+        let result = self.check_schema(schema, value);
+        self.depth -= 1;
+        result
+    }
+
+    fn check_schema(&mut self, schema: &'a Value, value: &Value) -> Check {
+        let kv = match schema {
+            Value::Bool(true) => return Ok(None),
+            Value::Bool(false) => return self.violation("The schema allows no value here.".into()),
+            Value::Dict(kv) => kv,
+            _ => return self.schema_error("A schema must be an object or a boolean.".into()),
+        };
+        let get = |keyword: &str| kv.get(&Value::from(keyword));
+
+        for keyword in UNSUPPORTED_KEYWORDS {
+            if get(keyword).is_some() {
+                return self.schema_error(concat! {
+                    "The schema keyword '" keyword.to_string() "' is not supported."
+                });
+            }
+        }
+
+        if let Some(reference) = get("$ref") {
+            check!(self.check_ref(reference, value));
+        }
+
+        if let Some(types) = get("type") {
+            check!(self.check_type(types, value));
+        }
+        if let Some(Value::List(options)) = get("enum") {
+            if !options.iter().any(|option| json_eq(option, value)) {
+                self.location.push("enum".into());
+                let result = self.violation(concat! {
+                    "Expected one of " format_rcl(&Value::List(options.clone())).into_owned()
+                    ", but got " format_rcl(value).into_owned() "."
+                });
+                self.location.pop().expect("Push and pop are balanced.");
+                return result;
+            }
+        }
+        if let Some(expected) = get("const") {
+            if !json_eq(expected, value) {
+                self.location.push("const".into());
+                let result = self.violation(concat! {
+                    "Expected " format_rcl(expected).into_owned()
+                    ", but got " format_rcl(value).into_owned() "."
+                });
+                self.location.pop().expect("Push and pop are balanced.");
+                return result;
+            }
+        }
+
+        match value {
+            Value::Int(..) | Value::Number(..) => check!(self.check_number(kv, value)),
+            Value::String(s) => check!(self.check_string(kv, s)),
+            Value::List(xs) => check!(self.check_array(kv, xs.iter())),
+            Value::Set(xs) => check!(self.check_array(kv, xs.iter())),
+            Value::Dict(xs) => check!(self.check_object(kv, xs)),
+            _ => {}
+        }
+
+        self.check_combinators(kv, value)
+    }
+
+    fn check_ref(&mut self, reference: &'a Value, value: &Value) -> Check {
+        let pointer =
+            match reference {
+                Value::String(r) if r.as_ref() == "#" => "",
+                Value::String(r) if r.starts_with("#/") => &r[1..],
+                _ => return self.schema_error(
+                    "Only references within the same schema, that start with '#', are supported."
+                        .into(),
+                ),
+            };
+        let mut target = self.root;
+        let mut tokens = Vec::new();
+        for token in pointer.split('/').skip(1) {
+            let token = token.replace("~1", "/").replace("~0", "~");
+            let next = match target {
+                Value::Dict(kv) => kv.get(&Value::from(token.as_str())),
+                Value::List(xs) => token.parse::<usize>().ok().and_then(|i| xs.get(i)),
+                _ => None,
+            };
+            target = match next {
+                Some(next) => next,
+                None => {
+                    return self.schema_error(concat! {
+                        "The reference " format_rcl(reference).into_owned()
+                        " does not point at a part of the schema."
+                    })
+                }
+            };
+            tokens.push(token);
+        }
+        // The location continues at the target, we restore it afterwards.
+        let location = std::mem::replace(&mut self.location, tokens);
+        let result = self.check(target, value);
+        self.location = location;
+        result
+    }
+
+    fn check_type(&mut self, types: &Value, value: &Value) -> Check {
+        let names: Vec<&str> = match types {
+            Value::String(t) => vec![t.as_ref()],
+            Value::List(ts) => ts
+                .iter()
+                .filter_map(|t| match t {
+                    Value::String(t) => Some(t.as_ref()),
+                    _ => None,
+                })
+                .collect(),
+            _ => return self.schema_error("The 'type' must be a string or array.".into()),
+        };
+        if names.iter().any(|t| has_type(value, t)) {
+            return Ok(None);
+        }
+        self.location.push("type".into());
+        let result = self.violation(concat! {
+            "Expected type "
+            names.join(" or ")
+            ", but got "
+            describe_type(value)
+            "."
+        });
+        self.location.pop().expect("Push and pop are balanced.");
+        result
+    }
+
+    /// Return the value of a keyword that must be a non-negative integer.
+    fn get_count(&self, kv: &BTreeMap<Value, Value>, keyword: &str) -> Result<Option<usize>> {
+        match kv.get(&Value::from(keyword)) {
+            None => Ok(None),
+            Some(Value::Int(n)) if *n >= 0 => match usize::try_from(*n) {
+                Ok(count) => Ok(Some(count)),
+                Err(..) => self.schema_error(concat! {
+                    "The '" keyword.to_string() "' is too large, it must be at most "
+                    usize::MAX.to_string() "."
+                }),
+            },
+            Some(..) => self.schema_error(concat! {
+                "The '" keyword.to_string() "' must be a non-negative integer."
+            }),
+        }
+    }
+
+    /// Report a violation at a keyword.
+    fn violation_at(&mut self, keyword: &str, message: Doc<'static>) -> Check {
+        self.location.push(keyword.to_string());
+        let result = self.violation(message);
+        self.location.pop().expect("Push and pop are balanced.");
+        result
+    }
+
+    fn check_number(&mut self, kv: &BTreeMap<Value, Value>, n: &Value) -> Check {
+        type Bound = (&'static str, &'static str, fn(&Value, &Value) -> bool);
+        let bounds: [Bound; 4] = [
+            ("minimum", "at least ", |n, bound| n >= bound),
+            ("maximum", "at most ", |n, bound| n <= bound),
+            ("exclusiveMinimum", "greater than ", |n, bound| n > bound),
+            ("exclusiveMaximum", "less than ", |n, bound| n < bound),
+        ];
+        for (keyword, description, is_ok) in bounds {
+            match kv.get(&Value::from(keyword)) {
+                None => {}
+                Some(bound @ (Value::Int(..) | Value::Number(..))) if is_ok(n, bound) => {}
+                Some(bound @ (Value::Int(..) | Value::Number(..))) => {
+                    return self.violation_at(
+                        keyword,
+                        concat! {
+                            "Expected a number " description format_rcl(bound).into_owned()
+                            ", but got " format_rcl(n).into_owned() "."
+                        },
+                    )
+                }
+                // Draft 4 used booleans for the exclusive bounds, these are
+                // not part of 2020-12, so we ignore them.
+                Some(Value::Bool(..)) if keyword.starts_with("exclusive") => {}
+                Some(..) => {
+                    return self.schema_error(concat! {
+                        "The '" keyword "' must be a number."
+                    })
+                }
+            }
+        }
+        match kv.get(&Value::from("multipleOf")) {
+            None => {}
+            Some(m @ (Value::Int(..) | Value::Number(..))) if *m > Value::Int(0) => {
+                let is_multiple = match (n, m) {
+                    (Value::Int(n), Value::Int(m)) => n % m == 0,
+                    _ => {
+                        let (n, m) = (n.as_decimal(), m.as_decimal());
+                        let q = n.zip(m).and_then(|(n, m)| n.checked_div(m));
+                        q.map_or(false, |q| q.exponent() >= 0)
+                    }
+                };
+                if !is_multiple {
+                    return self.violation_at(
+                        "multipleOf",
+                        concat! {
+                            "Expected a multiple of " format_rcl(m).into_owned()
+                            ", but got " format_rcl(n).into_owned() "."
+                        },
+                    );
+                }
+            }
+            Some(..) => {
+                return self.schema_error("The 'multipleOf' must be a positive number.".into())
+            }
+        }
+        Ok(None)
+    }
+
+    fn check_string(&mut self, kv: &BTreeMap<Value, Value>, s: &str) -> Check {
+        let len = s.chars().count();
+        if let Some(min) = self.get_count(kv, "minLength")? {
+            if len < min {
+                return self.violation_at(
+                    "minLength",
+                    concat! {
+                        "Expected a string of at least " min.to_string()
+                        " characters, but got " len.to_string() "."
+                    },
+                );
+            }
+        }
+        if let Some(max) = self.get_count(kv, "maxLength")? {
+            if len > max {
+                return self.violation_at(
+                    "maxLength",
+                    concat! {
+                        "Expected a string of at most " max.to_string()
+                        " characters, but got " len.to_string() "."
+                    },
+                );
+            }
+        }
+        Ok(None)
+    }
+
+    fn check_array<'v>(
+        &mut self,
+        kv: &'a BTreeMap<Value, Value>,
+        elements: impl Iterator<Item = &'v Value>,
+    ) -> Check {
+        let elements: Vec<&Value> = elements.collect();
+        let get = |keyword: &str| kv.get(&Value::from(keyword));
+
+        if let Some(min) = self.get_count(kv, "minItems")? {
+            if elements.len() < min {
+                return self.violation_at(
+                    "minItems",
+                    concat! {
+                        "Expected at least " min.to_string() " items, but got "
+                        elements.len().to_string() "."
+                    },
+                );
+            }
+        }
+        if let Some(max) = self.get_count(kv, "maxItems")? {
+            if elements.len() > max {
+                return self.violation_at(
+                    "maxItems",
+                    concat! {
+                        "Expected at most " max.to_string() " items, but got "
+                        elements.len().to_string() "."
+                    },
+                );
+            }
+        }
+        if let Some(Value::Bool(true)) = get("uniqueItems") {
+            for (i, x) in elements.iter().enumerate() {
+                if let Some(j) = elements[..i].iter().position(|y| json_eq(x, y)) {
+                    self.path.push(PathElement::Index(i));
+                    let result = self.violation_at(
+                        "uniqueItems",
+                        concat! {
+                            "Expected unique items, but this item is equal to the item at index "
+                            j.to_string() "."
+                        },
+                    );
+                    self.path.pop().expect("Push and pop are balanced.");
+                    return result;
+                }
+            }
+        }
+
+        // The first items can have their own schema, the rest are checked
+        // against `items`. Draft 7 used an array `items` for the former.
+        let (prefix, prefix_keyword, rest, rest_keyword) = match (get("prefixItems"), get("items"))
+        {
+            (Some(Value::List(prefix)), rest) => (&prefix[..], "prefixItems", rest, "items"),
+            (None, Some(Value::List(prefix))) => (
+                &prefix[..],
+                "items",
+                get("additionalItems"),
+                "additionalItems",
+            ),
+            (None, rest) => (&[][..], "prefixItems", rest, "items"),
+            (Some(..), _) => {
+                return self.schema_error("The 'prefixItems' must be an array.".into())
+            }
+        };
+        for (i, element) in elements.iter().enumerate() {
+            let (schema, keyword, index) = match prefix.get(i) {
+                Some(schema) => (schema, prefix_keyword, Some(i)),
+                None => match rest {
+                    Some(schema) => (schema, rest_keyword, None),
+                    None => break,
+                },
+            };
+            self.path.push(PathElement::Index(i));
+            self.location.push(keyword.to_string());
+            if let Some(index) = index {
+                self.location.push(index.to_string());
+            }
+            let result = self.check(schema, element);
+            if index.is_some() {
+                self.location.pop().expect("Push and pop are balanced.");
+            }
+            self.location.pop().expect("Push and pop are balanced.");
+            self.path.pop().expect("Push and pop are balanced.");
+            check!(result);
+        }
+
+        if let Some(contains) = get("contains") {
+            let mut n_matches = 0;
+            for element in elements.iter() {
+                if self.matches("contains", contains, element)? {
+                    n_matches += 1;
+                }
+            }
+            let min = self.get_count(kv, "minContains")?.unwrap_or(1);
+            if n_matches < min {
+                return self.violation_at(
+                    "contains",
+                    concat! {
+                        "Expected at least " min.to_string()
+                        if min == 1 { " item" } else { " items" }
+                        " that match the 'contains' schema, but got " n_matches.to_string() "."
+                    },
+                );
+            }
+            if let Some(max) = self.get_count(kv, "maxContains")? {
+                if n_matches > max {
+                    return self.violation_at(
+                        "maxContains",
+                        concat! {
+                            "Expected at most " max.to_string()
+                            " items that match the 'contains' schema, but got "
+                            n_matches.to_string() "."
+                        },
+                    );
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn check_object(
+        &mut self,
+        kv: &'a BTreeMap<Value, Value>,
+        properties: &BTreeMap<Value, Value>,
+    ) -> Check {
+        let get = |keyword: &str| kv.get(&Value::from(keyword));
+
+        for key in properties.keys() {
+            if !matches!(key, Value::String(..)) {
+                self.path.push(PathElement::Key(key.clone()));
+                let result = self.violation("JSON object keys must be strings.".into());
+                self.path.pop().expect("Push and pop are balanced.");
+                return result;
+            }
+        }
+
+        if let Some(min) = self.get_count(kv, "minProperties")? {
+            if properties.len() < min {
+                return self.violation_at(
+                    "minProperties",
+                    concat! {
+                        "Expected at least " min.to_string() " properties, but got "
+                        properties.len().to_string() "."
+                    },
+                );
+            }
+        }
+        if let Some(max) = self.get_count(kv, "maxProperties")? {
+            if properties.len() > max {
+                return self.violation_at(
+                    "maxProperties",
+                    concat! {
+                        "Expected at most " max.to_string() " properties, but got "
+                        properties.len().to_string() "."
+                    },
+                );
+            }
+        }
+
+        match get("required") {
+            None => {}
+            Some(Value::List(required)) => {
+                for name in required.iter() {
+                    if !properties.contains_key(name) {
+                        return self.violation_at(
+                            "required",
+                            concat! {
+                                "The required property " format_rcl(name).into_owned()
+                                " is missing."
+                            },
+                        );
+                    }
+                }
+            }
+            Some(..) => return self.schema_error("The 'required' must be an array.".into()),
+        }
+
+        match get("dependentRequired") {
+            None => {}
+            Some(Value::Dict(dependencies)) => {
+                for (name, required) in dependencies.iter() {
+                    if !properties.contains_key(name) {
+                        continue;
+                    }
+                    let required = match required {
+                        Value::List(required) => required,
+                        _ => {
+                            return self.schema_error(
+                                "The 'dependentRequired' values must be arrays.".into(),
+                            )
+                        }
+                    };
+                    for other in required.iter() {
+                        if !properties.contains_key(other) {
+                            self.location.push("dependentRequired".into());
+                            let result = self.violation_at(
+                                name.expect_string(),
+                                concat! {
+                                    "The property " format_rcl(name).into_owned()
+                                    " requires the property " format_rcl(other).into_owned()
+                                    ", but it is missing."
+                                },
+                            );
+                            self.location.pop().expect("Push and pop are balanced.");
+                            return result;
+                        }
+                    }
+                }
+            }
+            Some(..) => {
+                return self.schema_error("The 'dependentRequired' must be an object.".into())
+            }
+        }
+
+        let declared = match get("properties") {
+            None => None,
+            Some(Value::Dict(declared)) => Some(declared),
+            Some(..) => return self.schema_error("The 'properties' must be an object.".into()),
+        };
+        for (name, v) in properties.iter() {
+            self.path.push(PathElement::Key(name.clone()));
+            let result = match declared.and_then(|d| d.get(name)) {
+                Some(schema) => {
+                    self.location.push("properties".into());
+                    let result = self.check_at(name.expect_string(), schema, v);
+                    self.location.pop().expect("Push and pop are balanced.");
+                    result
+                }
+                None => match get("additionalProperties") {
+                    Some(Value::Bool(false)) => self.violation_at(
+                        "additionalProperties",
+                        concat! {
+                            "The property " format_rcl(name).into_owned()
+                            " is not allowed by the schema."
+                        },
+                    ),
+                    Some(schema) => self.check_at("additionalProperties", schema, v),
+                    None => Ok(None),
+                },
+            };
+            let result = match (result, get("propertyNames")) {
+                (Ok(None), Some(schema)) => self.check_at("propertyNames", schema, name),
+                (result, _) => result,
+            };
+            self.path.pop().expect("Push and pop are balanced.");
+            check!(result);
+        }
+
+        match get("dependentSchemas") {
+            None => {}
+            Some(Value::Dict(dependencies)) => {
+                for (name, schema) in dependencies.iter() {
+                    if properties.contains_key(name) {
+                        self.location.push("dependentSchemas".into());
+                        let result = self.check_at(
+                            name.expect_string(),
+                            schema,
+                            &Value::Dict(Rc::new(properties.clone())),
+                        );
+                        self.location.pop().expect("Push and pop are balanced.");
+                        check!(result);
+                    }
+                }
+            }
+            Some(..) => {
+                return self.schema_error("The 'dependentSchemas' must be an object.".into())
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Return the value of a keyword that must be a non-empty array of schemas.
+    fn get_schemas(
+        &self,
+        kv: &'a BTreeMap<Value, Value>,
+        keyword: &'static str,
+    ) -> Result<Option<&'a [Value]>> {
+        match kv.get(&Value::from(keyword)) {
+            None => Ok(None),
+            Some(Value::List(schemas)) if !schemas.is_empty() => Ok(Some(&schemas[..])),
+            Some(..) => self.schema_error(concat! {
+                "The '" keyword "' must be a non-empty array."
+            }),
+        }
+    }
+
+    fn check_combinators(&mut self, kv: &'a BTreeMap<Value, Value>, value: &Value) -> Check {
+        let get = |keyword: &str| kv.get(&Value::from(keyword));
+
+        if let Some(schemas) = self.get_schemas(kv, "allOf")? {
+            self.location.push("allOf".into());
+            for (i, schema) in schemas.iter().enumerate() {
+                let result = self.check_at(&i.to_string(), schema, value);
+                if !matches!(result, Ok(None)) {
+                    self.location.pop().expect("Push and pop are balanced.");
+                    return result;
+                }
+            }
+            self.location.pop().expect("Push and pop are balanced.");
+        }
+
+        if let Some(schemas) = self.get_schemas(kv, "anyOf")? {
+            self.location.push("anyOf".into());
+            let mut any_matches = false;
+            for (i, schema) in schemas.iter().enumerate() {
+                if self.matches(&i.to_string(), schema, value)? {
+                    any_matches = true;
+                    break;
+                }
+            }
+            self.location.pop().expect("Push and pop are balanced.");
+            if !any_matches {
+                return self.violation_at(
+                    "anyOf",
+                    "The value does not match any of the schemas in 'anyOf'.".into(),
+                );
+            }
+        }
+
+        if let Some(schemas) = self.get_schemas(kv, "oneOf")? {
+            self.location.push("oneOf".into());
+            let mut matching = Vec::new();
+            for (i, schema) in schemas.iter().enumerate() {
+                if self.matches(&i.to_string(), schema, value)? {
+                    matching.push(i);
+                }
+            }
+            self.location.pop().expect("Push and pop are balanced.");
+            match matching[..] {
+                [_] => {}
+                [] => {
+                    return self.violation_at(
+                        "oneOf",
+                        "The value does not match any of the schemas in 'oneOf'.".into(),
+                    )
+                }
+                [i, j, ..] => {
+                    return self.violation_at(
+                        "oneOf",
+                        concat! {
+                            "The value must match exactly one of the schemas in 'oneOf', "
+                            "but it matches schema " i.to_string() " and " j.to_string() "."
+                        },
+                    )
+                }
+            }
+        }
+
+        if let Some(schema) = get("not") {
+            if self.matches("not", schema, value)? {
+                return self.violation_at(
+                    "not",
+                    "The value matches the schema in 'not', but it must not.".into(),
+                );
+            }
+        }
+
+        if let Some(condition) = get("if") {
+            let branch = match self.matches("if", condition, value)? {
+                true => "then",
+                false => "else",
+            };
+            if let Some(schema) = get(branch) {
+                check!(self.check_at(branch, schema, value));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{type_to_schema, validate};
+    use crate::fmt_json::format_json_compact;
+    use crate::json_parser::parse_json;
+    use crate::pprint::Config;
+    use crate::runtime::Value;
+    use crate::source::{DocId, Span};
+    use crate::types::{Dict, SourcedType, Type};
+    use std::rc::Rc;
+
+    fn schema(type_: Type) -> String {
+        let span = Span::new(DocId(0), 0, 0);
+        let value = type_to_schema(span, &type_).unwrap();
+        let doc = format_json_compact(span, &value).unwrap();
+        doc.println(&Config::default()).to_string_no_markup()
+    }
+
+    fn sourced(type_: Type) -> SourcedType {
+        SourcedType {
+            type_,
+            source: crate::type_source::Source::None,
+        }
+    }
+
+    #[test]
+    fn type_to_schema_handles_collections() {
+        assert_eq!(schema(Type::Any), "true\n");
+        assert_eq!(
+            schema(Type::List(Rc::new(sourced(Type::Int)))),
+            r#"{"items":{"type":"integer"},"type":"array"}"#.to_string() + "\n",
+        );
+        assert_eq!(
+            schema(Type::Set(Rc::new(sourced(Type::Void)))),
+            r#"{"items":false,"type":"array","uniqueItems":true}"#.to_string() + "\n",
+        );
+        let dict = Type::Dict(Rc::new(Dict {
+            key: sourced(Type::String),
+            value: sourced(Type::Bool),
+        }));
+        assert_eq!(
+            schema(dict),
+            r#"{"additionalProperties":{"type":"boolean"},"type":"object"}"#.to_string() + "\n",
+        );
+    }
+
+    #[test]
+    fn type_to_schema_rejects_non_string_keys() {
+        let span = Span::new(DocId(0), 0, 0);
+        let dict = Type::Dict(Rc::new(Dict {
+            key: sourced(Type::Int),
+            value: sourced(Type::Int),
+        }));
+        assert!(type_to_schema(span, &dict).is_err());
+    }
+
+    /// Validate the JSON value against the JSON schema, return the error message.
+    fn check(schema: &str, value: &str) -> Option<String> {
+        let span = Span::new(DocId(0), 0, 0);
+        let parse = |input: &str| -> Value { parse_json(DocId(0), input).unwrap() };
+        let err = validate(span, &parse(schema), &parse(value)).err()?;
+        Some(
+            err.message
+                .println(&Config::default())
+                .to_string_no_markup()
+                .trim_end()
+                .to_string(),
+        )
+    }
+
+    #[test]
+    fn validate_handles_combinators() {
+        let one_of = r#"{"oneOf": [{"type": "integer"}, {"minimum": 10}]}"#;
+        assert_eq!(check(one_of, "3"), None);
+        assert_eq!(check(one_of, "\"x\""), None);
+        assert_eq!(
+            check(one_of, "12").as_deref(),
+            Some("The value must match exactly one of the schemas in 'oneOf', but it matches schema 0 and 1."),
+        );
+
+        let cond = r#"{"if": {"required": ["a"]}, "then": {"required": ["b"]}, "else": false}"#;
+        assert_eq!(check(cond, r#"{"a": 1, "b": 2}"#), None);
+        assert!(check(cond, r#"{"a": 1}"#).is_some());
+        assert!(check(cond, r#"{"b": 1}"#).is_some());
+
+        let contains = r#"{"contains": {"const": [1, {"x": null}]}, "not": {"maxItems": 1}}"#;
+        assert_eq!(check(contains, r#"[0, [1, {"x": null}]]"#), None);
+        assert!(check(contains, r#"[[1, {"x": null}]]"#).is_some());
+        assert!(check(contains, r#"[0, [1, {"x": 0}]]"#).is_some());
+    }
+
+    #[test]
+    fn validate_rejects_unsupported_schemas() {
+        assert_eq!(
+            check(r#"{"pattern": "^a"}"#, "\"a\"").as_deref(),
+            Some("The schema keyword 'pattern' is not supported."),
+        );
+        assert_eq!(
+            check(r#"{"$ref": "other.json"}"#, "1").as_deref(),
+            Some("Only references within the same schema, that start with '#', are supported."),
+        );
+        assert_eq!(
+            check(
+                r##"{"$defs": {"a/b": {"$ref": "#/$defs/a~1b"}}, "$ref": "#/$defs/a~1b"}"##,
+                "1"
+            )
+            .as_deref(),
+            Some("Schema nesting limit reached, the schema may contain a cyclic '$ref'."),
+        );
+    }
+
+    #[test]
+    fn validate_rejects_counts_that_do_not_fit_usize() {
+        let huge = format!(r#"{{"maxItems": {}}}"#, u128::from(u64::MAX) + 1);
+        assert_eq!(
+            check(&huge, "[]").as_deref(),
+            Some(
+                &format!(
+                    "The 'maxItems' is too large, it must be at most {}.",
+                    usize::MAX
+                )[..]
+            ),
+        );
+    }
+}
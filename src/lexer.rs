@@ -44,6 +44,7 @@ pub enum StringPrefix {
     Format,
 }
 
+/// The kind of a lexeme.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum Token {
     /// A sequence of ascii whitespace not significant for reformatting.
@@ -220,6 +221,56 @@ pub enum Token {
     Eof,
 }
 
+/// The keywords of the language, and their tokens.
+///
+/// Besides the lexer, this list also drives the grammars for editors, see
+/// [`crate::cmd_grammar`].
+pub const KEYWORDS: &[(&str, Token)] = &[
+    ("and", Token::KwAnd),
+    ("assert", Token::KwAssert),
+    ("else", Token::KwElse),
+    ("false", Token::KwFalse),
+    ("for", Token::KwFor),
+    ("if", Token::KwIf),
+    ("import", Token::KwImport),
+    ("in", Token::KwIn),
+    ("let", Token::KwLet),
+    ("not", Token::KwNot),
+    ("null", Token::KwNull),
+    ("or", Token::KwOr),
+    ("trace", Token::KwTrace),
+    ("true", Token::KwTrue),
+];
+
+/// Punctuation of two bytes in length, and their tokens.
+pub const DIGRAPHS: &[(&str, Token)] = &[
+    ("<=", Token::LtEq),
+    (">=", Token::GtEq),
+    ("==", Token::Eq2),
+    ("!=", Token::Neq),
+    ("->", Token::ThinArrow),
+    ("=>", Token::FatArrow),
+];
+
+/// Punctuation of a single byte in length, other than brackets, and their tokens.
+///
+/// Like [`DIGRAPHS`], this list also drives the grammars for editors.
+pub const MONOGRAPHS: &[(&str, Token)] = &[
+    ("<", Token::Lt),
+    (">", Token::Gt),
+    ("=", Token::Eq1),
+    ("!", Token::Bang),
+    ("*", Token::Star),
+    ("+", Token::Plus),
+    (",", Token::Comma),
+    ("-", Token::Minus),
+    (".", Token::Dot),
+    ("/", Token::Slash),
+    (":", Token::Colon),
+    (";", Token::Semicolon),
+    ("|", Token::Pipe),
+];
+
 /// A state in the state stack of the lexer.
 #[derive(Debug)]
 enum State {
@@ -237,37 +288,79 @@ enum State {
     Hole,
 }
 
+/// A token together with the span of the input that it covers.
 pub type Lexeme = (Token, Span);
 
 /// Lex an input document into tokens.
 pub fn lex(doc: DocId, input: &str) -> Result<Vec<Lexeme>> {
-    let mut tokens = Vec::new();
-    let mut lexer = Lexer::new(doc, input);
-    while lexer.start < lexer.input.len() {
-        match lexer.next()? {
-            // We drop non-significant whitespace in the lexer to simplify the
-            // parser. Blank lines we do keep, because we want to preserve them
-            // when autoformatting.
-            (Token::Space, _) => continue,
-            (token, span) => {
-                debug_assert!(
-                    input.is_char_boundary(span.start()),
-                    // coverage:off -- Error not expected to be hit.
-                    "Start of {token:?} is not a char boundary.",
-                    // coverage:on
-                );
-                debug_assert!(
-                    input.is_char_boundary(span.end()),
-                    // coverage:off -- Error not expected to be hit.
-                    "Start of {token:?} is not a char boundary.",
-                    // coverage:on
-                );
-                tokens.push((token, span));
+    tokens(doc, input).collect()
+}
+
+/// Lex an input document incrementally, see [`Tokens`].
+pub fn tokens(doc: DocId, input: &str) -> Tokens {
+    Tokens {
+        lexer: Lexer::new(doc, input),
+        done: false,
+    }
+}
+
+/// An iterator over the tokens of a document.
+///
+/// This yields the same tokens as [`lex`], but without collecting them into a
+/// vector first, which is useful for tools that only need a prefix of the
+/// tokens, or that want to process large inputs in a streaming fashion. The
+/// text of a token is the slice of the input that its span covers, use
+/// [`Span::resolve`] to get it.
+///
+/// Insignificant whitespace is not included, it can be inferred from the gap
+/// between the spans of consecutive tokens. If the input fails to lex, the
+/// iterator yields the error as its final element.
+pub struct Tokens<'a> {
+    lexer: Lexer<'a>,
+    done: bool,
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Result<Lexeme>;
+
+    fn next(&mut self) -> Option<Result<Lexeme>> {
+        if self.done {
+            return None;
+        }
+        let input = self.lexer.input;
+        while self.lexer.start < input.len() {
+            match self.lexer.next() {
+                // We drop non-significant whitespace in the lexer to simplify the
+                // parser. Blank lines we do keep, because we want to preserve them
+                // when autoformatting.
+                Ok((Token::Space, _)) => continue,
+                Ok((token, span)) => {
+                    debug_assert!(
+                        input.is_char_boundary(span.start()),
+                        // coverage:off -- Error not expected to be hit.
+                        "Start of {token:?} is not a char boundary.",
+                        // coverage:on
+                    );
+                    debug_assert!(
+                        input.is_char_boundary(span.end()),
+                        // coverage:off -- Error not expected to be hit.
+                        "Start of {token:?} is not a char boundary.",
+                        // coverage:on
+                    );
+                    return Some(Ok((token, span)));
+                }
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
             }
-        };
+        }
+        self.done = true;
+        match self.lexer.report_unclosed_delimiters() {
+            Ok(()) => None,
+            Err(err) => Some(Err(err)),
+        }
     }
-    lexer.report_unclosed_delimiters()?;
-    Ok(tokens)
 }
 
 /// Return whether a given string is a keyword.
@@ -501,23 +594,10 @@ impl<'a> Lexer<'a> {
 
     /// Return which keyword the string is, or identifier otherwise.
     fn get_keyword_or_ident(ident: &str) -> Token {
-        match ident {
-            "and" => Token::KwAnd,
-            "assert" => Token::KwAssert,
-            "else" => Token::KwElse,
-            "false" => Token::KwFalse,
-            "for" => Token::KwFor,
-            "if" => Token::KwIf,
-            "import" => Token::KwImport,
-            "in" => Token::KwIn,
-            "let" => Token::KwLet,
-            "not" => Token::KwNot,
-            "null" => Token::KwNull,
-            "or" => Token::KwOr,
-            "trace" => Token::KwTrace,
-            "true" => Token::KwTrue,
-            _ => Token::Ident,
-        }
+        KEYWORDS
+            .iter()
+            .find(|(kw, _)| *kw == ident)
+            .map_or(Token::Ident, |(_, token)| *token)
     }
 
     fn lex_in_ident(&mut self) -> Lexeme {
@@ -642,17 +722,11 @@ impl<'a> Lexer<'a> {
             return None;
         }
 
-        let token = match &input[..2] {
-            b"<=" => Token::LtEq,
-            b">=" => Token::GtEq,
-            b"==" => Token::Eq2,
-            b"!=" => Token::Neq,
-            b"->" => Token::ThinArrow,
-            b"=>" => Token::FatArrow,
-            _ => return None,
-        };
+        let (_, token) = DIGRAPHS
+            .iter()
+            .find(|(digraph, _)| digraph.as_bytes() == &input[..2])?;
 
-        Some((token, self.span(2)))
+        Some((*token, self.span(2)))
     }
 
     /// Try to lex punctuation of a single byte in length.
@@ -683,26 +757,19 @@ impl<'a> Lexer<'a> {
                 self.pop_delimiter(span)?;
                 Token::RBrace
             }
-            b'<' => Token::Lt,
-            b'>' => Token::Gt,
-            b'=' => Token::Eq1,
-            b'!' => Token::Bang,
-            b'*' => Token::Star,
-            b'+' => Token::Plus,
-            b',' => Token::Comma,
-            b'-' => Token::Minus,
-            b'.' => Token::Dot,
-            b'/' => Token::Slash,
-            b':' => Token::Colon,
-            b';' => Token::Semicolon,
-            b'|' => Token::Pipe,
             b'#' => {
                 return span
                     .error("Unrecognized punctuation here.")
                     .with_help("Comments are written with '//', not with '#'.")
                     .err();
             }
-            _ => return span.error("Unrecognized punctuation here.").err(),
+            byte => match MONOGRAPHS
+                .iter()
+                .find(|(monograph, _)| monograph.as_bytes() == [byte])
+            {
+                Some((_, token)) => *token,
+                None => return span.error("Unrecognized punctuation here.").err(),
+            },
         };
 
         Ok((token, span))
@@ -870,3 +937,65 @@ impl<'a> Lexer<'a> {
         err.err()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::tokens;
+    use crate::pprint::Config;
+    use crate::source::DocId;
+
+    /// Render the tokens of the input one per line, with the error if any.
+    fn lex_lines(input: &str) -> Vec<String> {
+        tokens(DocId(0), input)
+            .map(|lexeme| match lexeme {
+                Ok((token, span)) => format!("{token:?} {:?}", span.resolve(input)),
+                Err(err) => {
+                    let cfg = Config::default();
+                    let message = err.message.println(&cfg).to_string_no_markup();
+                    format!("Error: {}", message.trim_end())
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn tokens_yields_lexemes_without_space() {
+        assert_eq!(
+            lex_lines("let x = [1, f\"{y}\"];\n\n// z\nx"),
+            [
+                "KwLet \"let\"",
+                "Ident \"x\"",
+                "Eq1 \"=\"",
+                "LBracket \"[\"",
+                "NumDecimal \"1\"",
+                "Comma \",\"",
+                "QuoteOpen(Format, Double) \"f\\\"\"",
+                "HoleOpen \"{\"",
+                "Ident \"y\"",
+                "HoleClose \"}\"",
+                "QuoteClose \"\\\"\"",
+                "RBracket \"]\"",
+                "Semicolon \";\"",
+                "Blank \"\\n\\n\"",
+                "LineComment \"// z\"",
+                "Ident \"x\"",
+            ],
+        );
+    }
+
+    #[test]
+    fn tokens_yields_error_last() {
+        assert_eq!(
+            lex_lines("[1 # 2]"),
+            [
+                "LBracket \"[\"",
+                "NumDecimal \"1\"",
+                "Error: Unrecognized punctuation here.",
+            ],
+        );
+        assert_eq!(
+            lex_lines("(x"),
+            ["LParen \"(\"", "Ident \"x\"", "Error: Expected ')'."],
+        );
+    }
+}
@@ -17,30 +17,72 @@ pub mod abstraction;
 pub mod ast;
 pub mod cli;
 pub mod cmd_build;
+pub mod cmd_check;
+pub mod cmd_completion;
+pub mod cmd_diff;
 pub mod cmd_eval;
+pub mod cmd_fmt;
+pub mod cmd_grammar;
+pub mod cmd_lsp;
 pub mod cst;
+pub mod csv_parser;
+pub mod decimal;
+pub mod editorconfig;
 pub mod env;
 pub mod error;
 pub mod eval;
+#[cfg(feature = "cbor")]
+pub mod fmt_cbor;
 pub mod fmt_cst;
+pub mod fmt_csv;
+pub mod fmt_hcl;
+pub mod fmt_ini;
 pub mod fmt_json;
+pub mod fmt_json_lines;
+pub mod fmt_json_stream;
+pub mod fmt_nix;
+pub mod fmt_plist;
 pub mod fmt_raw;
 pub mod fmt_rcl;
+pub mod fmt_textproto;
 pub mod fmt_toml;
 pub mod fmt_type;
 pub mod fmt_yaml_stream;
+pub mod git;
+pub mod glob;
 pub mod highlight;
+pub mod json_parser;
+pub mod json_schema;
 pub mod lexer;
 pub mod loader;
 pub mod markup;
+pub mod navigation;
+pub mod pager;
+pub mod parallel;
 pub mod parser;
 pub mod pprint;
 pub mod runtime;
+#[cfg(feature = "serde")]
+pub mod serde_rcl;
 pub mod source;
 pub mod stdlib;
+pub mod stdlib_csv;
+pub mod stdlib_datetime;
+pub mod stdlib_dict;
+pub mod stdlib_encode;
+pub mod stdlib_float;
+pub mod stdlib_hash;
+pub mod stdlib_int;
+pub mod stdlib_json;
+pub mod stdlib_list;
+pub mod stdlib_set;
+pub mod stdlib_string;
 pub mod string;
+pub mod toml_parser;
 pub mod tracer;
 pub mod type_diff;
 pub mod type_source;
 pub mod typecheck;
 pub mod types;
+pub mod walk;
+pub mod yaml_parser;
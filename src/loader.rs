@@ -18,15 +18,20 @@ use crate::abstraction;
 use crate::ast;
 use crate::cli::Target;
 use crate::cst;
-use crate::error::{Error, Result};
+use crate::csv_parser;
+use crate::error::{Error, ErrorKind, Result};
 use crate::eval::Evaluator;
+use crate::json_parser;
 use crate::lexer;
 use crate::parser;
 use crate::pprint::{self, concat, indent};
 use crate::runtime::{Env, Value};
 use crate::source::{Doc, DocId, Span};
+use crate::toml_parser;
 use crate::tracer::Tracer;
 use crate::typecheck::{self, TypeChecker};
+use crate::types::SourcedType;
+use crate::yaml_parser;
 
 /// An owned document.
 ///
@@ -47,6 +52,18 @@ pub struct Document {
 }
 
 impl Document {
+    /// Create a document with the given name and contents.
+    ///
+    /// The span is set when the document is added to a [`Loader`].
+    pub fn new(name: String, data: String) -> Document {
+        Document {
+            name,
+            data,
+            // This span is a placeholder that is overwritten by `push`.
+            span: Span::new(DocId(0), 0, 0),
+        }
+    }
+
     pub fn as_doc(&self) -> Doc {
         Doc {
             name: &self.name,
@@ -470,6 +487,9 @@ impl Filesystem for SandboxFilesystem {
     }
 }
 
+/// A parser for a data document, see [`Loader::get_data_parser`].
+pub type DataParser = fn(&Loader, DocId) -> Result<Value>;
+
 pub struct Loader {
     documents: Vec<Document>,
 
@@ -479,6 +499,20 @@ pub struct Loader {
     loaded_files: HashMap<PathBuf, DocId>,
 
     filesystem: Box<dyn Filesystem>,
+
+    /// The name of a document loaded from stdin.
+    ///
+    /// Imports in that document are resolved relative to this name, as if it
+    /// were a file.
+    stdin_name: String,
+
+    /// Whether to normalize line endings of loaded documents.
+    deterministic: bool,
+
+    /// The collections that imported documents evaluated to.
+    ///
+    /// See [`Loader::get_import_origin`].
+    import_results: Vec<(DocId, Value)>,
 }
 
 impl Loader {
@@ -487,9 +521,27 @@ impl Loader {
             documents: Vec::new(),
             loaded_files: HashMap::new(),
             filesystem: Box::new(PanicFilesystem),
+            stdin_name: "stdin".to_string(),
+            deterministic: false,
+            import_results: Vec::new(),
         }
     }
 
+    /// Set the name to use for documents loaded from stdin, see [`Loader::load_stdin`].
+    pub fn set_stdin_name(&mut self, name: &str) {
+        self.stdin_name = name.to_string();
+    }
+
+    /// Make loaded documents independent of the platform they were read on.
+    ///
+    /// When enabled, `\r\n` line endings are normalized to `\n` in every
+    /// document we load, so a checkout that converted line endings on Windows
+    /// evaluates to the same value as one on a Unix-like system, including for
+    /// strings returned by `std.read_file_utf8`.
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        self.deterministic = deterministic;
+    }
+
     /// Set the filesystem access handler.
     pub fn set_filesystem(&mut self, filesystem: Box<dyn Filesystem>) {
         self.filesystem = filesystem;
@@ -543,15 +595,25 @@ impl Loader {
     /// Lex the given document and return its tokens.
     pub fn get_tokens(&self, id: DocId) -> Result<Vec<lexer::Lexeme>> {
         let doc = self.get_doc(id);
-        let tokens = lexer::lex(id, doc.data)?;
+        let tokens = lexer::lex(id, doc.data).map_err(|err| err.with_kind(ErrorKind::Parse))?;
         Ok(tokens)
     }
 
     /// Parse the given document and return its Concrete Syntax Tree.
     pub fn get_cst(&mut self, id: DocId) -> Result<cst::Expr> {
-        let doc = self.get_doc(id);
         let tokens = self.get_tokens(id)?;
-        let (doc_span, expr) = parser::parse(id, doc.data, &tokens)?;
+        self.get_cst_from_tokens(id, &tokens)
+    }
+
+    /// Parse the given document from the tokens returned by [`Loader::get_tokens`].
+    pub fn get_cst_from_tokens(
+        &mut self,
+        id: DocId,
+        tokens: &[lexer::Lexeme],
+    ) -> Result<cst::Expr> {
+        let doc = self.get_doc(id);
+        let (doc_span, expr) =
+            parser::parse(id, doc.data, tokens).map_err(|err| err.with_kind(ErrorKind::Parse))?;
 
         // After parsing we have a more precise span for the document's body
         // expression, store it so we can later use it to blame errors on.
@@ -560,13 +622,85 @@ impl Loader {
         Ok(expr)
     }
 
+    /// Parse the given document as JSON data, rather than as an RCL expression.
+    pub fn get_json(&self, id: DocId) -> Result<Value> {
+        let doc = self.get_doc(id);
+        let value =
+            json_parser::parse_json(id, doc.data).map_err(|err| err.with_kind(ErrorKind::Parse))?;
+        Ok(value)
+    }
+
+    /// Parse the given document as a YAML stream, see [`yaml_parser`].
+    pub fn get_yaml(&self, id: DocId) -> Result<Value> {
+        let doc = self.get_doc(id);
+        let value =
+            yaml_parser::parse_yaml(id, doc.data).map_err(|err| err.with_kind(ErrorKind::Parse))?;
+        Ok(value)
+    }
+
+    /// Return the parser for the document if it is data, based on its extension.
+    ///
+    /// JSON, YAML, TOML, CSV, and TSV documents are data, so rather than
+    /// evaluating them as RCL, imports parse them with their own parser.
+    pub fn get_data_parser(&self, id: DocId) -> Option<DataParser> {
+        let name = self.get_doc(id).name;
+        if name.ends_with(".json") {
+            Some(Loader::get_json)
+        } else if name.ends_with(".yaml") || name.ends_with(".yml") {
+            Some(Loader::get_yaml)
+        } else if name.ends_with(".toml") {
+            Some(Loader::get_toml)
+        } else if name.ends_with(".csv") {
+            Some(Loader::get_csv)
+        } else if name.ends_with(".tsv") {
+            Some(Loader::get_tsv)
+        } else {
+            None
+        }
+    }
+
+    /// Parse the given document as CSV table, see [`csv_parser`].
+    pub fn get_csv(&self, id: DocId) -> Result<Value> {
+        let doc = self.get_doc(id);
+        let value =
+            csv_parser::parse_csv(id, doc.data).map_err(|err| err.with_kind(ErrorKind::Parse))?;
+        Ok(value)
+    }
+
+    /// Parse the given document as TSV table, see [`csv_parser`].
+    pub fn get_tsv(&self, id: DocId) -> Result<Value> {
+        let doc = self.get_doc(id);
+        let value =
+            csv_parser::parse_tsv(id, doc.data).map_err(|err| err.with_kind(ErrorKind::Parse))?;
+        Ok(value)
+    }
+
+    /// Parse the given document as TOML, see [`toml_parser`].
+    pub fn get_toml(&self, id: DocId) -> Result<Value> {
+        let doc = self.get_doc(id);
+        let value =
+            toml_parser::parse_toml(id, doc.data).map_err(|err| err.with_kind(ErrorKind::Parse))?;
+        Ok(value)
+    }
+
     /// Parse the given document and return its Abstract Syntax Tree.
     ///
     /// This is the AST before typecheking.
     pub fn get_unchecked_ast(&mut self, id: DocId) -> Result<ast::Expr> {
-        let cst = self.get_cst(id)?;
+        let tokens = self.get_tokens(id)?;
+        self.get_unchecked_ast_from_tokens(id, &tokens)
+    }
+
+    /// Like [`Loader::get_unchecked_ast`], but from already lexed tokens.
+    pub fn get_unchecked_ast_from_tokens(
+        &mut self,
+        id: DocId,
+        tokens: &[lexer::Lexeme],
+    ) -> Result<ast::Expr> {
+        let cst = self.get_cst_from_tokens(id, tokens)?;
         let doc = self.get_doc(id);
-        let ast = abstraction::abstract_expr(doc.data, &cst)?;
+        let ast = abstraction::abstract_expr(doc.data, &cst)
+            .map_err(|err| err.with_kind(ErrorKind::Parse))?;
         Ok(ast)
     }
 
@@ -583,11 +717,52 @@ impl Loader {
         // span from the import site.
         let span = self.get_span(id);
         let mut ast = self.get_unchecked_ast(id)?;
-        let mut checker = TypeChecker::new(env);
-        checker.check_expr(typecheck::type_any(), span, &mut ast)?;
+        Loader::typecheck_ast(env, span, &mut ast)?;
         Ok(ast)
     }
 
+    /// Parse and typecheck the given document, and return its inferred type.
+    pub fn get_type(&mut self, env: &mut typecheck::Env, id: DocId) -> Result<SourcedType> {
+        let span = self.get_span(id);
+        let mut ast = self.get_unchecked_ast(id)?;
+        Loader::typecheck_ast(env, span, &mut ast)
+    }
+
+    /// Typecheck an AST, see [`Loader::get_typechecked_ast`] for the span.
+    ///
+    /// Returns the inferred type of the expression.
+    pub fn typecheck_ast(
+        env: &mut typecheck::Env,
+        span: Span,
+        ast: &mut ast::Expr,
+    ) -> Result<SourcedType> {
+        let mut checker = TypeChecker::new(env);
+        let type_ = checker
+            .check_expr(typecheck::type_any(), span, ast)
+            .map_err(|err| err.with_kind(ErrorKind::Type))?;
+        Ok(type_)
+    }
+
+    /// Record the value that an imported document evaluated to.
+    ///
+    /// Only collections are recorded, because other values have no identity.
+    pub fn record_import_result(&mut self, id: DocId, value: &Value) {
+        if matches!(value, Value::List(..) | Value::Set(..) | Value::Dict(..)) {
+            self.import_results.push((id, value.clone()));
+        }
+    }
+
+    /// Return the imported document that `value` is the result of, if any.
+    ///
+    /// This is the case when the value is the very collection that an import
+    /// evaluated to, as opposed to a collection that was derived from it.
+    pub fn get_import_origin(&self, value: &Value) -> Option<DocId> {
+        self.import_results
+            .iter()
+            .find(|(_, result)| result.is_same_collection(value))
+            .map(|(id, _)| *id)
+    }
+
     /// Evaluate the given document and return the resulting value.
     pub fn evaluate(
         &mut self,
@@ -605,6 +780,9 @@ impl Loader {
     /// We set the span here because the span contains the document id, which is
     /// only known after we push the document.
     fn push(&mut self, mut document: Document) -> DocId {
+        if self.deterministic && document.data.contains("\r\n") {
+            document.data = document.data.replace("\r\n", "\n");
+        }
         let n = self.documents.len();
         let id = DocId(n.try_into().expect("Cannot load that many documents!"));
         document.span = Span::new(id, 0, document.data.len());
@@ -613,13 +791,16 @@ impl Loader {
     }
 
     /// Load stdin into a new document.
+    ///
+    /// The document is named `stdin`, unless a different name was set with
+    /// [`Loader::set_stdin_name`].
     pub fn load_stdin(&mut self) -> Result<DocId> {
         let mut buf = String::new();
         io::stdin()
             .read_to_string(&mut buf)
             .map_err(|err| Error::new(format!("Failed to read from stdin: {}.", err)))?;
         let doc = Document {
-            name: "stdin".to_string(),
+            name: self.stdin_name.clone(),
             data: buf,
             // This span is a placeholder that is overwritten by `push`.
             span: Span::new(DocId(0), 0, 0),
@@ -638,6 +819,20 @@ impl Loader {
         self.load_file(resolved)
     }
 
+    /// Return the paths of all files loaded so far, in the order they were loaded.
+    ///
+    /// After evaluation, these are the entry point and its transitive imports.
+    /// The order does not depend on hashing, so depfiles are reproducible.
+    pub fn loaded_paths(&self) -> impl Iterator<Item = &Path> {
+        let mut paths: Vec<(DocId, &Path)> = self
+            .loaded_files
+            .iter()
+            .map(|(path, id)| (*id, path.as_path()))
+            .collect();
+        paths.sort_by_key(|(id, _)| id.0);
+        paths.into_iter().map(|(_, path)| path)
+    }
+
     /// Load a file into a new document.
     pub fn load_file(&mut self, path: PathLookup) -> Result<DocId> {
         // Avoid loading the same file twice if we already loaded it. This is
@@ -657,8 +852,13 @@ impl Loader {
 
     /// Load a string into a new document.
     pub fn load_string(&mut self, data: String) -> DocId {
+        self.load_named_string("input", data)
+    }
+
+    /// Load a string into a new document, with a name to use in error messages.
+    pub fn load_named_string(&mut self, name: &str, data: String) -> DocId {
         let doc = Document {
-            name: "input".to_string(),
+            name: name.to_string(),
             data,
             // This span is a placeholder that is overwritten by `push`.
             span: Span::new(DocId(0), 0, 0),
@@ -695,15 +895,20 @@ impl Loader {
     }
 
     #[cfg(unix)]
-    fn write_depfile_impl(&self, target_path: &Path, depfile_path: &Path) -> io::Result<()> {
+    fn write_depfile_impl(&self, target_paths: &[PathBuf], depfile_path: &Path) -> io::Result<()> {
         use std::io::Write;
         use std::os::unix::ffi::OsStrExt;
         let f = std::fs::File::create(depfile_path)?;
         let mut w = std::io::BufWriter::new(f);
-        let rel_target = self.filesystem.get_relative_path(target_path);
-        w.write_all(rel_target.as_os_str().as_bytes())?;
+        for (i, target_path) in target_paths.iter().enumerate() {
+            let rel_target = self.filesystem.get_relative_path(target_path);
+            if i > 0 {
+                w.write_all(b" ")?;
+            }
+            w.write_all(rel_target.as_os_str().as_bytes())?;
+        }
         w.write_all(b":")?;
-        for (path, _doc_id) in self.loaded_files.iter() {
+        for path in self.loaded_paths() {
             let rel_path = self.filesystem.get_relative_path(path);
             w.write_all(b" ")?;
             w.write_all(rel_path.as_os_str().as_bytes())?;
@@ -735,9 +940,30 @@ impl Loader {
             }
         };
 
+        self.write_depfile_targets(&[resolved_target], &resolved_depfile)
+    }
+
+    /// Write a depfile for the outputs of a build file, see also [`Loader::write_depfile`].
+    ///
+    /// Build outputs are relative to the build file, like imports are.
+    pub fn write_build_depfile(
+        &self,
+        out_paths: &[&str],
+        buildfile: DocId,
+        depfile_path: &str,
+    ) -> Result<()> {
+        let resolved_depfile = self.resolve_cli_output_path(depfile_path);
+        let build_dir = Path::new(self.get_doc(buildfile).name)
+            .parent()
+            .unwrap_or(Path::new(""));
+        let targets: Vec<PathBuf> = out_paths.iter().map(|p| build_dir.join(p)).collect();
+        self.write_depfile_targets(&targets, &resolved_depfile)
+    }
+
+    fn write_depfile_targets(&self, targets: &[PathBuf], resolved_depfile: &Path) -> Result<()> {
         #[cfg(unix)]
         {
-            self.write_depfile_impl(&resolved_target, &resolved_depfile)
+            self.write_depfile_impl(targets, resolved_depfile)
                 .map_err(|err| Error::new(format!("Failed to write depfile: {}.", err)).into())
         }
 
@@ -5,24 +5,35 @@
 // you may not use this file except in compliance with the License.
 // A copy of the License has been included in the root of the repository.
 
-use std::io::Write;
+use std::borrow::Cow;
+use std::io::{IsTerminal, Read, Write};
 use std::path::Path;
+use std::time::Instant;
 
 use rcl::cli::{
-    self, Cmd, EvalOptions, FormatTarget, GlobalOptions, OutputTarget, StyleOptions, Target,
+    self, Cmd, ErrorFormat, EvalOptions, FileList, FormatTarget, GlobalOptions, InputFormat,
+    OutputFormat, OutputTarget, StyleOptions, Target,
 };
-use rcl::error::{Error, Result};
+use rcl::cmd_fmt::{FormatStyle, FormatSummary, StyleResolver};
+use rcl::error::{Error, ErrorKind, Result};
+use rcl::git::{self, GitFilesystem};
 use rcl::loader::{Loader, SandboxMode};
-use rcl::markup::{MarkupMode, MarkupString};
+use rcl::markup::{Markup, MarkupMode, MarkupString, Theme};
+use rcl::pager::Pager;
 use rcl::pprint::{self, Doc};
 use rcl::runtime::{self, Value};
-use rcl::source::Span;
-use rcl::tracer::StderrTracer;
+use rcl::source::{DocId, Span};
+use rcl::tracer::{ChromeTracer, Phase, StderrTracer, TimingTracer, Timings, Tracer};
 use rcl::typecheck;
+use rcl::walk::{find_documents, split_glob, Select};
 
 struct App {
     loader: Loader,
     opts: GlobalOptions,
+    /// Time spent per phase and document, recorded when `--time` is set.
+    timings: Option<Timings>,
+    /// Whether to pipe long output to stdout through a pager.
+    use_pager: bool,
 }
 
 impl App {
@@ -50,76 +61,157 @@ impl App {
         w.flush()
     }
 
+    /// Replace the contents of an existing file atomically.
+    ///
+    /// We write to a temporary file next to the target, and then rename it over
+    /// the target. If we get interrupted halfway, the target is either in its
+    /// old state or in its new state, but never truncated. When the target is a
+    /// symlink, we replace the file it points to, and the link stays intact.
+    fn replace_file_impl(&self, data: MarkupString, out_path: &Path) -> std::io::Result<()> {
+        // Renaming over a symlink would replace the link itself, so resolve
+        // it first, and put the temporary file next to the real file, such
+        // that the rename does not cross a filesystem boundary.
+        let out_path = std::fs::canonicalize(out_path)?;
+        let out_path = out_path.as_path();
+        let mut tmp_name = std::ffi::OsString::from(".");
+        tmp_name.push(out_path.file_name().unwrap_or_default());
+        tmp_name.push(format!(".{}.tmp", std::process::id()));
+        let tmp_path = out_path.with_file_name(tmp_name);
+
+        // Preserve the permissions of the original, e.g. it may be executable.
+        let permissions = std::fs::metadata(out_path)?.permissions();
+        let result = self
+            .print_to_file_impl(MarkupMode::None, data, &tmp_path)
+            .and_then(|()| std::fs::set_permissions(&tmp_path, permissions))
+            .and_then(|()| std::fs::rename(&tmp_path, out_path));
+
+        if result.is_err() {
+            // Clean up after ourselves, but the original error is the one to
+            // report, so ignore any errors here.
+            let _ = std::fs::remove_file(&tmp_path);
+        }
+
+        result
+    }
+
+    /// Resolve an output path, and write to it with the given writer.
+    fn write_file_with<F>(&self, out_path: &str, write: F) -> Result<()>
+    where
+        F: FnOnce(&Path) -> std::io::Result<()>,
+    {
+        let out_path = self.loader.resolve_cli_output_path(out_path);
+
+        write(out_path.as_ref()).map_err(|err| {
+            // The concat! macro is not exported, we'll make do with a vec here.
+            let parts = vec![
+                "Failed to write to file '".into(),
+                Doc::path(out_path),
+                "': ".into(),
+                err.to_string().into(),
+            ];
+            Error::new(Doc::Concat(parts)).into()
+        })
+    }
+
     /// Write a string to a file.
     fn print_to_file(&self, mode: MarkupMode, data: MarkupString, out_path: &str) -> Result<()> {
-        let out_path = self.loader.resolve_cli_output_path(out_path);
+        self.write_file_with(out_path, |path| self.print_to_file_impl(mode, data, path))
+    }
 
-        self.print_to_file_impl(mode, data, out_path.as_ref())
-            .map_err(|err| {
-                // The concat! macro is not exported, we'll make do with a vec here.
-                let parts = vec![
-                    "Failed to write to file '".into(),
-                    Doc::path(out_path),
-                    "': ".into(),
-                    err.to_string().into(),
-                ];
-                Error::new(Doc::Concat(parts)).into()
-            })
+    /// Replace the contents of an existing file, see [`App::replace_file_impl`].
+    fn replace_file(&self, data: MarkupString, out_path: &str) -> Result<()> {
+        self.write_file_with(out_path, |path| self.replace_file_impl(data, path))
     }
 
-    fn print_doc_target(
-        &self,
-        output: OutputTarget,
-        style_opts: &StyleOptions,
-        doc: Doc,
-    ) -> Result<()> {
+    fn print_doc_target(&self, output: OutputTarget, cfg: &pprint::Config, doc: Doc) -> Result<()> {
         let stdout = std::io::stdout();
-        let markup = match output {
-            OutputTarget::Stdout => self
-                .opts
-                .markup
-                .unwrap_or_else(|| MarkupMode::default_for_fd(&stdout)),
-            // When the output is a file, we don't want to put ANSI escape codes
-            // in the file; --output is unaffected by --color.
-            OutputTarget::File(..) => MarkupMode::None,
-        };
-        let cfg = pprint::Config {
-            width: style_opts.width,
-        };
-        let result = doc.println(&cfg);
         match output {
             OutputTarget::Stdout => {
-                let mut out = stdout.lock();
-                self.print_string(markup, result, &mut out);
-            }
-            OutputTarget::File(fname) => {
-                self.print_to_file(markup, result, &fname)?;
+                let markup = self
+                    .opts
+                    .markup
+                    .unwrap_or_else(|| MarkupMode::default_for_fd(&stdout));
+                // Without markup and without a pager, we don't need the full
+                // output in memory, so we can write it out as we go.
+                if markup == MarkupMode::None && !stdout.is_terminal() {
+                    let mut out = std::io::BufWriter::new(stdout.lock());
+                    let res = doc
+                        .print_to_writer(cfg, &mut out)
+                        .and_then(|()| out.flush());
+                    if res.is_err() {
+                        // Like for `print_string`, there is nothing to report.
+                        std::process::exit(1);
+                    }
+                } else {
+                    self.print_stdout(markup, doc.println(cfg));
+                }
             }
+            // When the output is a file, we don't want to put ANSI escape codes
+            // in the file; --output is unaffected by --color.
+            OutputTarget::File(fname) => self.write_file_with(&fname, |path| {
+                let f = std::fs::File::create(path)?;
+                let mut w = std::io::BufWriter::new(f);
+                doc.print_to_writer(cfg, &mut w)?;
+                w.flush()
+            })?,
         };
         Ok(())
     }
 
+    /// Print to stdout, or through the pager if the output is long, see [`rcl::pager`].
+    fn print_stdout(&self, markup: MarkupMode, data: MarkupString) {
+        if self.use_pager {
+            if let Some(mut pager) = Pager::for_output(&data) {
+                // When the user quits the pager before it read everything,
+                // writing fails, but that is not an error.
+                let _ = data.write_bytes(markup, pager.stdin());
+                pager.wait();
+                return;
+            }
+        }
+        let mut out = std::io::stdout().lock();
+        self.print_string(markup, data, &mut out);
+    }
+
     fn print_doc_stderr(&self, doc: Doc) {
         let stderr = std::io::stderr();
         let markup = self
             .opts
             .markup
             .unwrap_or_else(|| MarkupMode::default_for_fd(&stderr));
-        let cfg = pprint::Config { width: 80 };
+        let cfg = pprint::Config::default();
         let result = doc.println(&cfg);
         let mut out = stderr.lock();
         self.print_string(markup, result, &mut out);
     }
 
+    /// Format and print the value.
+    ///
+    /// For `--source-comments`, elements that are not the result of an import
+    /// are attributed to `source_doc`.
     pub fn print_value(
-        &self,
+        &mut self,
         eval_opts: &EvalOptions,
         style_opts: &StyleOptions,
         output: OutputTarget,
+        source_doc: DocId,
         value_span: Span,
         value: &Value,
     ) -> Result<()> {
-        let out_doc = rcl::cmd_eval::format_value(eval_opts.format, value_span, value)?;
+        let start = Instant::now();
+        let mut format_opts = eval_opts.format_options();
+        if eval_opts.source_comments && eval_opts.format == OutputFormat::YamlStream {
+            format_opts.yaml_sources = Some(self.get_yaml_sources(source_doc, value_span, value)?);
+        }
+
+        // The streaming writer does not write source comments.
+        if format_opts.yaml_sources.is_none() && self.should_stream(eval_opts.format, &output) {
+            let result = self.stream_value_target(eval_opts, style_opts, output, value_span, value);
+            self.record_time(Phase::Serialize, value_span, start);
+            return result;
+        }
+        let out_doc =
+            rcl::cmd_eval::format_value(eval_opts.format, &format_opts, value_span, value)?;
 
         // Prepend the banner if the user specified one.
         let out_doc = match eval_opts.banner.as_ref() {
@@ -127,117 +219,716 @@ impl App {
             None => out_doc,
         };
 
-        self.print_doc_target(output, style_opts, out_doc)
+        let result = self.print_doc_target(output, &style_opts.pprint_config(), out_doc);
+        self.record_time(Phase::Serialize, value_span, start);
+        result
     }
 
-    fn print_fatal_error(&self, err: Error) -> ! {
+    /// Return the name of the document that every element of the list came from.
+    fn get_yaml_sources(
+        &self,
+        source_doc: DocId,
+        value_span: Span,
+        value: &Value,
+    ) -> Result<Vec<String>> {
+        let elements = rcl::fmt_yaml_stream::elements(value_span, value)?;
+        let sources = elements
+            .iter()
+            .map(|element| {
+                let doc = self.loader.get_import_origin(element).unwrap_or(source_doc);
+                self.loader.get_doc(doc).name.to_string()
+            })
+            .collect();
+        Ok(sources)
+    }
+
+    /// Whether to write the value directly, rather than through a document.
+    ///
+    /// Streaming produces the same bytes, and it avoids holding the full output
+    /// in memory, but it does not support markup, and the pager needs the full
+    /// output, so we only stream when the output is not a terminal.
+    fn should_stream(&self, format: OutputFormat, output: &OutputTarget) -> bool {
+        if !rcl::fmt_json_stream::supports(format) {
+            return false;
+        }
+        match output {
+            OutputTarget::File(..) => true,
+            OutputTarget::Stdout => {
+                let stdout = std::io::stdout();
+                let markup = self
+                    .opts
+                    .markup
+                    .unwrap_or_else(|| MarkupMode::default_for_fd(&stdout));
+                markup == MarkupMode::None && !stdout.is_terminal()
+            }
+        }
+    }
+
+    /// Write the value with [`rcl::fmt_json_stream`], see [`App::should_stream`].
+    fn stream_value_target(
+        &self,
+        eval_opts: &EvalOptions,
+        style_opts: &StyleOptions,
+        output: OutputTarget,
+        value_span: Span,
+        value: &Value,
+    ) -> Result<()> {
+        rcl::fmt_json_stream::check_value(eval_opts.format, value_span, value)?;
+
+        let cfg = style_opts.pprint_config();
+        let write = |out: &mut dyn Write| -> std::io::Result<()> {
+            if let Some(banner) = eval_opts.banner.as_ref() {
+                let banner_doc = Doc::lines(banner) + Doc::HardBreak;
+                banner_doc
+                    .println(&cfg)
+                    .write_bytes(MarkupMode::None, out)?;
+            }
+            rcl::fmt_json_stream::write_value(eval_opts.format, &cfg, value, out)
+        };
+
+        match output {
+            OutputTarget::Stdout => {
+                if write(&mut std::io::stdout().lock()).is_err() {
+                    // Like `print_string`, there is no point in reporting this.
+                    std::process::exit(1);
+                }
+                Ok(())
+            }
+            OutputTarget::File(fname) => self.write_file_with(&fname, |path| {
+                let mut f = std::fs::File::create(path)?;
+                write(&mut f)
+            }),
+        }
+    }
+
+    /// Record the time since `start` for `--time`, if it is enabled.
+    fn record_time(&mut self, phase: Phase, span: Span, start: Instant) {
+        if let Some(timings) = self.timings.as_mut() {
+            timings.record(phase, span, start);
+        }
+    }
+
+    /// Load a document named on the command line, and record the time for `--time`.
+    fn load_cli_target(&mut self, target: &Target) -> Result<DocId> {
+        let start = Instant::now();
+        let doc = self.loader.load_cli_target(target)?;
+        self.record_time(Phase::Read, self.loader.get_span(doc), start);
+        Ok(doc)
+    }
+
+    /// Load the JSON Schema for `--validate-schema`, if the user specified one.
+    fn load_validation_schema(&mut self, eval_opts: &EvalOptions) -> Result<Option<Value>> {
+        match eval_opts.validate_schema.as_ref() {
+            None => Ok(None),
+            Some(path) => {
+                let doc = self.load_cli_target(&Target::File(path.clone()))?;
+                Ok(Some(self.loader.get_json(doc)?))
+            }
+        }
+    }
+
+    /// Print the report of `--time` to stderr, if it is enabled.
+    fn print_timings(&self) {
+        if let Some(timings) = self.timings.as_ref() {
+            if !timings.is_empty() {
+                eprint!("{}", timings.report(&self.loader.as_inputs()));
+            }
+        }
+    }
+
+    /// Load documents from the given Git revision, see [`rcl::git`].
+    fn set_git_filesystem(&mut self, rev: &str, mode: SandboxMode) -> Result<()> {
+        let git_fs =
+            GitFilesystem::new(rev, mode, self.opts.workdir.as_deref()).map_err(|err| {
+                Error::new(format!(
+                    "Failed to initialize filesystem access layer: {err}"
+                ))
+            })?;
+        self.loader.set_filesystem(Box::new(git_fs));
+        Ok(())
+    }
+
+    /// Print an error to stderr, in the format selected with `--error-format`.
+    fn print_error(&self, err: Error) {
         let inputs = self.loader.as_inputs();
-        let err_doc = err.report(&inputs);
-        self.print_doc_stderr(err_doc);
+        match self.opts.error_format {
+            ErrorFormat::Human => self.print_doc_stderr(err.report(&inputs)),
+            ErrorFormat::Json => {
+                let mut line = err.report_json(&inputs);
+                line.push('\n');
+                if std::io::stderr().write_all(line.as_bytes()).is_err() {
+                    // As in `print_string`, if we can't report, just exit.
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+
+    fn print_fatal_error(&self, err: Error) -> ! {
+        let kind = err.kind;
+        self.print_error(err);
         // Regardless of whether printing to stderr failed or not, the error was
-        // fatal, so we exit with code 1.
-        std::process::exit(1);
+        // fatal, so we exit with an error code.
+        std::process::exit(kind.exit_code());
     }
 
     fn get_tracer(&self) -> StderrTracer {
         StderrTracer::new(self.opts.markup)
     }
 
+    /// Run `f` with a tracer, and write a trace file if `--trace-file` is set.
+    ///
+    /// The trace file is written also when evaluation fails, because a trace
+    /// of a slow failure can be just as useful as one of a slow success.
+    fn with_tracer<R>(
+        &mut self,
+        eval_opts: &EvalOptions,
+        f: impl FnOnce(&mut Self, &mut dyn Tracer) -> Result<R>,
+    ) -> Result<R> {
+        // The timings are borrowed by the tracer, so we take them out of
+        // `self` while `f` runs, and put them back afterwards.
+        let mut timings = self.timings.take();
+        let result = self.with_timing_tracer(eval_opts, timings.as_mut(), f);
+        self.timings = timings;
+        result
+    }
+
+    fn with_timing_tracer<R>(
+        &mut self,
+        eval_opts: &EvalOptions,
+        timings: Option<&mut Timings>,
+        f: impl FnOnce(&mut Self, &mut dyn Tracer) -> Result<R>,
+    ) -> Result<R> {
+        let mut stderr_tracer = self.get_tracer();
+        let mut timing_tracer;
+        let tracer: &mut dyn Tracer = match timings {
+            None => &mut stderr_tracer,
+            Some(timings) => {
+                timing_tracer = TimingTracer::new(&mut stderr_tracer, timings);
+                &mut timing_tracer
+            }
+        };
+        let trace_file = match eval_opts.trace_file.as_ref() {
+            None => return f(self, tracer),
+            Some(fname) => fname,
+        };
+        let mut tracer = ChromeTracer::new(tracer);
+        let result = f(self, &mut tracer);
+        let trace = tracer.to_json(&self.loader.as_inputs());
+        let mut data = MarkupString::new();
+        data.push(&trace, Markup::None);
+        self.print_to_file(MarkupMode::None, data, trace_file)?;
+        result
+    }
+
+    /// Initialize the loader for a command that evaluates documents.
+    fn initialize_eval_loader(&mut self, eval_opts: &EvalOptions) -> Result<()> {
+        if eval_opts.deterministic && matches!(eval_opts.sandbox, SandboxMode::Unrestricted) {
+            // Imports by absolute path can resolve to different files on
+            // different machines, so the output would not be reproducible.
+            let parts = vec![
+                Doc::highlight("--deterministic").into_owned(),
+                " cannot be combined with ".into(),
+                Doc::highlight("--sandbox=unrestricted").into_owned(),
+                ".".into(),
+            ];
+            return Error::new(Doc::Concat(parts))
+                .with_kind(ErrorKind::Usage)
+                .err();
+        }
+        self.loader.set_deterministic(eval_opts.deterministic);
+        self.loader
+            .initialize_filesystem(eval_opts.sandbox, self.opts.workdir.as_deref())
+    }
+
+    /// Evaluate a loaded input document according to `--input-format`.
+    fn evaluate_input(
+        &mut self,
+        input_format: InputFormat,
+        type_env: &mut typecheck::Env,
+        value_env: &mut runtime::Env,
+        doc: DocId,
+        tracer: &mut dyn Tracer,
+    ) -> Result<Value> {
+        match input_format {
+            InputFormat::Json => {
+                let start = Instant::now();
+                let val = self.loader.get_json(doc);
+                tracer.record(Phase::Parse, self.loader.get_span(doc), start);
+                val
+            }
+            InputFormat::Rcl => self.loader.evaluate(type_env, value_env, doc, tracer),
+        }
+    }
+
+    /// Load the entry point of an evaluating command and evaluate it.
+    ///
+    /// This initializes the filesystem with the sandbox mode from the options,
+    /// so it should be called only once per command.
+    fn evaluate_target(
+        &mut self,
+        eval_opts: &EvalOptions,
+        target: &Target,
+    ) -> Result<(DocId, Value)> {
+        self.initialize_eval_loader(eval_opts)?;
+
+        let mut type_env = typecheck::prelude();
+        let mut value_env = runtime::prelude();
+        let doc = self.load_cli_target(target)?;
+        let val = self.with_tracer(eval_opts, |app, tracer| {
+            app.evaluate_input(
+                eval_opts.input_format,
+                &mut type_env,
+                &mut value_env,
+                doc,
+                tracer,
+            )
+        })?;
+
+        Ok((doc, val))
+    }
+
     fn main_fmt(
         &mut self,
         output: OutputTarget,
         style_opts: &StyleOptions,
         targets: FormatTarget,
+        no_ignore: bool,
+        jobs: Option<usize>,
+        file_list: Option<FileList>,
     ) -> Result<()> {
-        let cfg = pprint::Config {
-            width: style_opts.width,
-        };
+        let mut styles = StyleResolver::default();
         let (is_write_in_place, fnames) = match targets {
             FormatTarget::Stdout { fname } => {
-                let doc = self.loader.load_cli_target(&fname)?;
+                let style = self.get_format_style(&mut styles, style_opts, &fname)?;
+                let mut doc = self.loader.load_cli_target(&fname)?;
+                let data = self.loader.get_doc(doc).data;
+                if let Cow::Owned(normalized) = rcl::cmd_fmt::normalize_line_endings(data) {
+                    let name = self.loader.get_doc(doc).name.to_string();
+                    doc = self.loader.load_named_string(&name, normalized);
+                }
                 let cst = self.loader.get_cst(doc)?;
                 let data = self.loader.get_doc(doc).data;
-                let res = rcl::fmt_cst::format_expr(data, &cst);
-                return self.print_doc_target(output, style_opts, res);
+                let res = rcl::fmt_cst::format_expr_with(data, &cst, style.trailing_comma);
+                return self.print_doc_target(output, &style.print, res);
             }
             FormatTarget::InPlace { fnames } => (true, fnames),
             FormatTarget::Check { mut fnames } => {
                 // For in-place formatting we really need files, but for checking,
-                // we can check stdin if the user did not specify any files.
-                if fnames.is_empty() {
+                // we can check stdin if the user did not specify any files. An
+                // empty file list means there is nothing to check though.
+                if fnames.is_empty() && file_list.is_none() {
                     fnames.push(Target::StdinDefault);
                 }
                 (false, fnames)
             }
         };
+        let mut fnames = self.expand_targets(fnames, no_ignore)?;
 
-        let mut n_changed: u32 = 0;
-        let mut n_loaded: u32 = 0;
+        // Files from a list are taken literally, they are not searched or
+        // matched as globs. In this mode we print a machine-readable status
+        // per file, for use in Git hooks and lint runners.
+        let is_porcelain = file_list.is_some();
+        if let Some(file_list) = file_list {
+            for fname in self.read_file_list(&file_list)? {
+                fnames.push(Target::File(fname));
+            }
+        }
 
+        // Load all documents up front. This needs the loader, so we do it on
+        // the main thread. Stdin cannot be rewritten in-place, check that before
+        // we start writing anything.
+        let mut docs = Vec::with_capacity(fnames.len());
         for target in fnames {
-            n_loaded += 1;
-            let doc = self.loader.load_cli_target(&target)?;
-            let cst = self.loader.get_cst(doc)?;
+            let fname = match target {
+                Target::File(ref fname) => Some(fname.clone()),
+                Target::Stdin if is_write_in_place => {
+                    let msg =
+                        "Formatting in-place is only possible for named files, not for stdin.";
+                    return Error::new(msg).with_kind(ErrorKind::Usage).err();
+                }
+                Target::Stdin => None,
+                Target::StdinDefault => {
+                    debug_assert!(
+                        !is_write_in_place,
+                        "In-place default is empty list, not stdin."
+                    );
+                    None
+                }
+            };
+            let style = self.get_format_style(&mut styles, style_opts, &target)?;
+            docs.push((fname, self.loader.load_cli_target(&target)?, style));
+        }
+
+        // Then format all of them in parallel. Lexing, parsing, and formatting
+        // do not need the loader, only the document contents.
+        let inputs: Vec<_> = docs
+            .iter()
+            .map(|(_, doc, style)| (*doc, self.loader.get_doc(*doc).data, style))
+            .collect();
+        let jobs = jobs.unwrap_or_else(rcl::parallel::default_jobs);
+        let results = rcl::cmd_fmt::format_docs(jobs, &inputs);
+
+        let n_loaded = docs.len() as u32;
+        let mut summary = FormatSummary::default();
+
+        // Finally process the results in order, so the output is deterministic.
+        for ((fname, doc, _style), formatted) in docs.into_iter().zip(results) {
+            let formatted = match formatted {
+                Some(formatted) => formatted,
+                None => {
+                    // Errors can't be sent across threads, so the formatter only
+                    // reported that parsing failed. Parse again to get the error,
+                    // report it, and continue with the other files.
+                    let err = match self.loader.get_cst(doc) {
+                        Err(err) => err,
+                        // coverage:off -- Formatting fails only when parsing fails.
+                        Ok(..) => unreachable!("Formatting failed, so parsing fails too."),
+                        // coverage:on
+                    };
+                    self.print_error(*err);
+                    summary.n_failed += 1;
+                    if is_porcelain {
+                        println!("failed\t{}", self.loader.get_doc(doc).name);
+                    }
+                    continue;
+                }
+            };
             let data = self.loader.get_doc(doc).data;
-            let fmt_doc = rcl::fmt_cst::format_expr(data, &cst);
-            let res = fmt_doc.println(&cfg);
-            let formatted = res.to_string_no_markup();
             let did_change = data != &formatted[..];
+            if did_change {
+                summary.n_changed += 1;
+            } else {
+                summary.n_unchanged += 1;
+            }
+            if is_porcelain {
+                let status = match (did_change, is_write_in_place) {
+                    (false, _) => "unchanged",
+                    (true, true) => "reformatted",
+                    (true, false) => "would-reformat",
+                };
+                println!("{}\t{}", status, self.loader.get_doc(doc).name);
+            }
 
             if is_write_in_place {
-                let fname = match target {
-                    Target::File(fname) => fname,
-                    Target::Stdin => {
-                        let msg =
-                            "Formatting in-place is only possible for named files, not for stdin.";
-                        return Error::new(msg).err();
-                    }
-                    Target::StdinDefault => {
-                        unreachable!("In-place default is empty list, not stdin.")
-                    }
-                };
+                let fname = fname.expect("We checked for stdin before.");
                 // We only write to the file if we changed anything. This ensures
                 // that we don't cause rebuilds for build systems that look at mtimes,
                 // that we don't waste space on CoW filesystems, and that we don't
                 // unnecessarily burn through SSDs in general.
                 if did_change {
-                    n_changed += 1;
-                    self.print_to_file(MarkupMode::None, res, &fname)?;
+                    let mut res = MarkupString::new();
+                    res.push(&formatted, Markup::None);
+                    self.replace_file(res, &fname)?;
                 }
             } else {
                 // We are in the --check case, not the --in-place case.
-                if did_change {
-                    n_changed += 1;
-                    println!("Would reformat {}", self.loader.get_doc(doc).name);
+                if did_change && !is_porcelain {
+                    println!(
+                        "Would reformat {}, first difference on line {}.",
+                        self.loader.get_doc(doc).name,
+                        rcl::cmd_fmt::first_difference_line(data, &formatted),
+                    );
                 }
             }
         }
 
+        // For a single file the outcome is clear from the other output, but
+        // when there are many, the summary gives an overview at a glance.
+        let is_clean = summary.n_changed == 0 && summary.n_failed == 0;
+        if !is_porcelain && (is_write_in_place || (n_loaded > 1 && !is_clean)) {
+            println!("{}", summary.describe(!is_write_in_place));
+        }
+        if summary.n_failed > 0 {
+            let parts = vec![
+                summary.n_failed.to_string().into(),
+                Doc::str(" of "),
+                n_loaded.to_string().into(),
+                Doc::str(" files could not be formatted."),
+            ];
+            return Error::new(Doc::Concat(parts))
+                .with_kind(ErrorKind::Parse)
+                .err();
+        }
         if is_write_in_place {
-            println!("Reformatted {} of {} files.", n_changed, n_loaded);
             return Ok(());
         }
-        if n_changed == 0 {
+        if summary.n_changed == 0 {
             match n_loaded {
+                _ if is_porcelain => {}
                 1 => println!("The file is formatted correctly."),
                 n => println!("All {} files are formatted correctly.", n),
             }
             Ok(())
         } else {
             let parts = vec![
-                n_changed.to_string().into(),
+                summary.n_changed.to_string().into(),
                 Doc::str(" of "),
                 n_loaded.to_string().into(),
                 Doc::str(" files would be reformatted."),
             ];
-            Error::new(Doc::Concat(parts)).err()
+            Error::new(Doc::Concat(parts))
+                .with_kind(ErrorKind::Check)
+                .err()
+        }
+    }
+
+    /// Replace directories and globs with the documents that they match.
+    ///
+    /// A file name that contains wildcards is treated as a glob, unless a file
+    /// with that exact name exists.
+    fn expand_targets(&self, targets: Vec<Target>, no_ignore: bool) -> Result<Vec<Target>> {
+        let mut result = Vec::with_capacity(targets.len());
+        for target in targets {
+            let fname = match &target {
+                Target::File(fname) => fname,
+                _ => {
+                    result.push(target);
+                    continue;
+                }
+            };
+            let path = self.loader.resolve_cli_output_path(fname);
+            let (dir, select, glob);
+            if path.is_dir() {
+                dir = &fname[..];
+                select = Select::RclFiles;
+            } else if let (false, Some(split)) = (path.exists(), split_glob(fname)) {
+                (dir, glob) = split;
+                select = Select::Glob(&glob);
+            } else {
+                result.push(target);
+                continue;
+            }
+            let dir_path = self.loader.resolve_cli_output_path(dir);
+            let is_glob = matches!(select, Select::Glob(..));
+            let documents = find_documents(&dir_path, select, !no_ignore)?;
+            if is_glob && documents.is_empty() {
+                let parts = vec![
+                    Doc::str("Pattern '"),
+                    Doc::path(fname),
+                    Doc::str("' does not match any files."),
+                ];
+                return Error::new(Doc::Concat(parts)).err();
+            }
+            for document in documents {
+                let path = Path::new(dir).join(document);
+                result.push(Target::File(path.to_string_lossy().into_owned()));
+            }
+        }
+        Ok(result)
+    }
+
+    /// Read the paths to format for `--files-from` or `--staged`.
+    fn read_file_list(&self, file_list: &FileList) -> Result<Vec<String>> {
+        let list = match file_list {
+            FileList::GitStaged => return git::get_staged_rcl_files(self.opts.workdir.as_deref()),
+            FileList::FilesFrom(Target::File(fname)) => {
+                let path = self.loader.resolve_cli_output_path(fname);
+                std::fs::read_to_string(path).map_err(|err| {
+                    let parts = vec![
+                        Doc::str("Failed to read file list '"),
+                        Doc::path(fname),
+                        Doc::str("': "),
+                        err.to_string().into(),
+                    ];
+                    Error::new(Doc::Concat(parts))
+                })?
+            }
+            FileList::FilesFrom(..) => {
+                let mut list = String::new();
+                std::io::stdin().read_to_string(&mut list).map_err(|err| {
+                    Error::new(format!("Failed to read file list from stdin: {err}"))
+                })?;
+                list
+            }
+        };
+        Ok(git::split_nul(&list).map(|name| name.to_string()).collect())
+    }
+
+    /// Return the style to format `target` in.
+    ///
+    /// This is the style from the configuration files that apply to the
+    /// target, with the command-line options applied on top.
+    fn get_format_style(
+        &mut self,
+        styles: &mut StyleResolver,
+        style_opts: &StyleOptions,
+        target: &Target,
+    ) -> Result<FormatStyle> {
+        // For stdin we look for configuration files relative to the name that
+        // the user gave it, or in the working directory if they didn't.
+        let path = match target {
+            Target::File(fname) => self.loader.resolve_cli_output_path(fname),
+            Target::Stdin | Target::StdinDefault => self
+                .loader
+                .resolve_cli_output_path(self.opts.stdin_filename.as_deref().unwrap_or("stdin")),
+        };
+        let mut style = styles.get_style(&mut self.loader, &path)?;
+        if let Some(width) = style_opts.width {
+            style.print.width = width;
+        }
+        Ok(style)
+    }
+
+    fn main_check(&mut self, fnames: &[Target]) -> Result<()> {
+        let mut n_failed: usize = 0;
+        let mut kinds = Vec::new();
+        let mut docs = Vec::with_capacity(fnames.len());
+        for target in fnames {
+            match self.loader.load_cli_target(target) {
+                Ok(doc) => docs.push(doc),
+                Err(err) => {
+                    kinds.push(err.kind);
+                    self.print_error(*err);
+                    n_failed += 1;
+                }
+            }
+        }
+
+        let result = rcl::cmd_check::check_docs(&mut self.loader, &docs);
+        let n_errors = n_failed + result.errors.len();
+        let n_files = n_failed + result.n_checked;
+        for err in result.errors {
+            kinds.push(err.kind);
+            self.print_error(err);
+        }
+
+        if n_errors == 0 {
+            match n_files {
+                1 => println!("The file has no errors."),
+                n => println!("All {} files have no errors.", n),
+            }
+            return Ok(());
+        }
+
+        let parts = vec![
+            Doc::str("Found "),
+            n_errors.to_string().into(),
+            Doc::str(if n_errors == 1 { " error" } else { " errors" }),
+            Doc::str(" in "),
+            n_files.to_string().into(),
+            Doc::str(if n_files == 1 { " file." } else { " files." }),
+        ];
+        // Exit with the most specific kind of error that we found, so a parse
+        // error alone exits with the parse exit code, but a type error anywhere
+        // exits with the type exit code.
+        let kind = kinds
+            .into_iter()
+            .max_by_key(|kind| kind.exit_code())
+            .unwrap_or_default();
+        Error::new(Doc::Concat(parts)).with_kind(kind).err()
+    }
+
+    /// Run the command, and run it again whenever one of its inputs changes.
+    ///
+    /// Errors in the command itself are reported, but they do not stop the
+    /// loop. We poll the modification times of all files that were loaded
+    /// during the last run; this avoids a dependency on platform-specific
+    /// file notification APIs, and it picks up changes to the set of imports.
+    fn main_watch(&mut self, cmd: Cmd) -> Result<()> {
+        let is_stdin = match &cmd {
+            Cmd::Build { fname, .. } | Cmd::Evaluate { fname, .. } | Cmd::Query { fname, .. } => {
+                !matches!(fname, Target::File(..))
+            }
+            _ => false,
+        };
+        if is_stdin {
+            return Error::new("Cannot use --watch when reading from stdin.")
+                .with_kind(ErrorKind::Usage)
+                .err();
+        }
+
+        // Forcing color does not make stderr a screen that we can clear.
+        let is_tty = std::io::stderr().is_terminal()
+            && matches!(
+                MarkupMode::default_for_fd(&std::io::stderr()),
+                MarkupMode::Ansi(..)
+            );
+
+        loop {
+            if is_tty {
+                // Clear the screen and move the cursor to the top left.
+                eprint!("\x1b[2J\x1b[H");
+            }
+
+            self.loader = Loader::new();
+            if self.timings.is_some() {
+                self.timings = Some(Timings::new());
+            }
+            if let Err(err) = self.run(cmd.clone()) {
+                self.print_error(*err);
+            }
+            self.print_timings();
+
+            let mtimes: Vec<_> = self
+                .loader
+                .loaded_paths()
+                .map(|path| (path.to_path_buf(), get_mtime(path)))
+                .collect();
+
+            // If we failed before loading anything, there is nothing to watch.
+            if mtimes.is_empty() {
+                return Error::new("No files to watch, exiting.").err();
+            }
+
+            loop {
+                std::thread::sleep(std::time::Duration::from_millis(250));
+                let is_changed = mtimes.iter().any(|(path, mtime)| get_mtime(path) != *mtime);
+                if is_changed {
+                    break;
+                }
+            }
         }
     }
 
     fn main(&mut self) -> Result<()> {
-        let (opts, cmd) = cli::parse(std::env::args().collect())?;
+        let (opts, cmd) = cli::parse(std::env::args().collect())
+            .map_err(|err| err.with_kind(ErrorKind::Usage))?;
         self.opts = opts;
+        self.opts.markup = self.opts.markup.map(MarkupMode::with_user_colors);
+        if let Err(message) = rcl::markup::check_user_colors() {
+            let parts = vec![
+                Doc::from("Warning:").with_markup(Markup::Warning),
+                " Ignoring ".into(),
+                Doc::highlight(rcl::markup::COLORS_VAR),
+                ": ".into(),
+                message.into(),
+            ];
+            self.print_doc_stderr(Doc::Concat(parts));
+        }
+        if let Some(name) = self.opts.stdin_filename.as_ref() {
+            self.loader.set_stdin_name(name);
+        }
+
+        let (is_watch, is_time) = match &cmd {
+            Cmd::Build { eval_opts, .. }
+            | Cmd::Diff { eval_opts, .. }
+            | Cmd::Evaluate { eval_opts, .. }
+            | Cmd::Query { eval_opts, .. } => (eval_opts.watch, eval_opts.time),
+            _ => (false, false),
+        };
+        if is_time {
+            self.timings = Some(Timings::new());
+        }
+        // In watch mode the output gets replaced, so it should not block on
+        // a pager.
+        self.use_pager = !self.opts.no_pager
+            && !is_watch
+            && matches!(
+                cmd,
+                Cmd::Diff { .. } | Cmd::Evaluate { .. } | Cmd::Highlight { .. } | Cmd::Query { .. }
+            );
+
+        if is_watch {
+            self.main_watch(cmd)
+        } else {
+            let result = self.run(cmd);
+            self.print_timings();
+            result
+        }
+    }
 
+    fn run(&mut self, cmd: Cmd) -> Result<()> {
         match cmd {
             Cmd::Help { usage } => {
                 println!("{}", usage.trim());
@@ -248,32 +939,31 @@ impl App {
                 eval_opts,
                 build_mode,
                 fname,
+                jobs,
             } => {
-                // Evaluation options support a depfile, but this is not implemented
-                // for builds, we'd have to put multiple output filenames in there
-                // and that is not supported right now.
-                if eval_opts.output_depfile.is_some() {
-                    return Error::new("Generating depfiles is not supported for 'rcl build'.")
-                        .err();
-                }
-
-                self.loader
-                    .initialize_filesystem(eval_opts.sandbox, self.opts.workdir.as_deref())?;
-
-                // TODO: We can make these members, then we can share a lot of code between commands!
-                let mut tracer = self.get_tracer();
-                let mut type_env = typecheck::prelude();
-                let mut value_env = runtime::prelude();
-                let doc = self.loader.load_cli_target(&fname)?;
-
                 // TODO: Would be nice to be able to feed in an expected type.
-                let val = self
-                    .loader
-                    .evaluate(&mut type_env, &mut value_env, doc, &mut tracer)?;
+                let (doc, val) = self.evaluate_target(&eval_opts, &fname)?;
+                let schema = self.load_validation_schema(&eval_opts)?;
 
                 let full_span = self.loader.get_span(doc);
 
-                rcl::cmd_build::execute_build(&self.loader, build_mode, doc, full_span, val)
+                let start = Instant::now();
+                let result = rcl::cmd_build::execute_build(
+                    &self.loader,
+                    jobs.unwrap_or_else(rcl::parallel::default_jobs),
+                    build_mode,
+                    doc,
+                    val,
+                    schema.as_ref(),
+                    eval_opts.output_depfile.as_deref(),
+                );
+                self.record_time(Phase::Serialize, full_span, start);
+                result
+            }
+
+            Cmd::Check { eval_opts, fnames } => {
+                self.initialize_eval_loader(&eval_opts)?;
+                self.main_check(&fnames)
             }
 
             Cmd::Evaluate {
@@ -282,23 +972,17 @@ impl App {
                 fname,
                 output,
             } => {
-                self.loader
-                    .initialize_filesystem(eval_opts.sandbox, self.opts.workdir.as_deref())?;
-
-                let mut tracer = self.get_tracer();
-                let mut type_env = typecheck::prelude();
-                let mut value_env = runtime::prelude();
-                let doc = self.loader.load_cli_target(&fname)?;
-                let val = self
-                    .loader
-                    .evaluate(&mut type_env, &mut value_env, doc, &mut tracer)?;
+                let (doc, val) = self.evaluate_target(&eval_opts, &fname)?;
 
                 if let Some(depfile_path) = eval_opts.output_depfile.as_ref() {
                     self.loader.write_depfile(&output, depfile_path)?;
                 }
 
                 let body_span = self.loader.get_span(doc);
-                self.print_value(&eval_opts, &style_opts, output, body_span, &val)
+                if let Some(schema) = self.load_validation_schema(&eval_opts)? {
+                    rcl::json_schema::validate(body_span, &schema, &val)?;
+                }
+                self.print_value(&eval_opts, &style_opts, output, doc, body_span, &val)
             }
 
             Cmd::Query {
@@ -308,47 +992,82 @@ impl App {
                 query: expr,
                 output,
             } => {
-                self.loader
-                    .initialize_filesystem(eval_opts.sandbox, self.opts.workdir.as_deref())?;
+                self.initialize_eval_loader(&eval_opts)?;
 
-                let input = self.loader.load_cli_target(&fname)?;
-                let query = self.loader.load_string(expr);
+                let input = self.load_cli_target(&fname)?;
+                // The input document gets bound to `input`, so name the query
+                // differently to avoid confusion in error messages.
+                let query = self.loader.load_named_string("query", expr);
 
-                // First we evaluate the input document.
-                let mut tracer = self.get_tracer();
-                let mut type_env = typecheck::prelude();
-                let mut value_env = runtime::prelude();
-                let val_input =
-                    self.loader
-                        .evaluate(&mut type_env, &mut value_env, input, &mut tracer)?;
-
-                // Then we bind that to the variable `input`, and in that context,
-                // we evaluate the query expression. The environments should be
-                // clean at this point, so we can reuse them.
-                type_env.push("input".into(), typecheck::type_any().clone());
-                value_env.push("input".into(), val_input);
-                let val_result =
-                    self.loader
-                        .evaluate(&mut type_env, &mut value_env, query, &mut tracer)?;
+                let val_result = self.with_tracer(&eval_opts, |app, tracer| {
+                    // First we evaluate the input document.
+                    let mut type_env = typecheck::prelude();
+                    let mut value_env = runtime::prelude();
+                    let val_input = app.evaluate_input(
+                        eval_opts.input_format,
+                        &mut type_env,
+                        &mut value_env,
+                        input,
+                        tracer,
+                    )?;
+
+                    // Then we bind that to the variable `input`, and in that
+                    // context, we evaluate the query expression. The
+                    // environments should be clean at this point, so we can
+                    // reuse them.
+                    type_env.push("input".into(), typecheck::type_any().clone());
+                    value_env.push("input".into(), val_input);
+                    app.loader
+                        .evaluate(&mut type_env, &mut value_env, query, tracer)
+                })?;
 
                 if let Some(depfile_path) = eval_opts.output_depfile.as_ref() {
                     self.loader.write_depfile(&output, depfile_path)?;
                 }
 
                 let body_span = self.loader.get_span(query);
-                self.print_value(&eval_opts, &style_opts, output, body_span, &val_result)
+                if let Some(schema) = self.load_validation_schema(&eval_opts)? {
+                    rcl::json_schema::validate(body_span, &schema, &val_result)?;
+                }
+                self.print_value(
+                    &eval_opts,
+                    &style_opts,
+                    output,
+                    input,
+                    body_span,
+                    &val_result,
+                )
+            }
+
+            Cmd::Schema {
+                eval_opts,
+                style_opts,
+                fname,
+                output,
+            } => {
+                self.initialize_eval_loader(&eval_opts)?;
+                let doc = self.load_cli_target(&fname)?;
+                let mut type_env = typecheck::prelude();
+                let type_ = self.loader.get_type(&mut type_env, doc)?;
+                let body_span = self.loader.get_span(doc);
+                let schema = rcl::json_schema::json_schema(body_span, &type_.type_)?;
+                let out_doc = rcl::fmt_json::format_json(body_span, &schema)?;
+                self.print_doc_target(output, &style_opts.pprint_config(), out_doc)
             }
 
             Cmd::Format {
                 style_opts,
                 target,
                 output,
+                no_ignore,
+                jobs,
+                file_list,
             } => {
                 self.loader.initialize_filesystem(
                     SandboxMode::Unrestricted,
                     self.opts.workdir.as_deref(),
                 )?;
-                self.main_fmt(output, &style_opts, target)
+                self.main_fmt(output, &style_opts, target, no_ignore, jobs, file_list)
             }
 
             Cmd::Highlight { fname } => {
@@ -360,11 +1079,126 @@ impl App {
                 let tokens = self.loader.get_tokens(doc)?;
                 let data = self.loader.get_doc(doc).data;
                 let result = rcl::highlight::highlight(&tokens, data);
-                let mut out = std::io::stdout().lock();
-                self.print_string(MarkupMode::Ansi, result, &mut out);
+                let markup = self.opts.markup.unwrap_or(MarkupMode::Ansi(Theme::DARK));
+                self.print_stdout(markup, result);
                 Ok(())
             }
 
+            Cmd::Completion { shell } => {
+                print!("{}", rcl::cmd_completion::generate(shell));
+                Ok(())
+            }
+
+            Cmd::GenerateGrammar { format } => {
+                print!("{}", rcl::cmd_grammar::generate(format));
+                Ok(())
+            }
+
+            Cmd::Diff {
+                eval_opts,
+                style_opts,
+                old,
+                new,
+                git_revs,
+                output,
+            } => {
+                self.initialize_eval_loader(&eval_opts)?;
+
+                let revs = match git_revs {
+                    Some((old_rev, new_rev)) => [Some(old_rev), Some(new_rev)],
+                    None => [None, None],
+                };
+                let (docs, values) = self.with_tracer(&eval_opts, |app, tracer| {
+                    let mut docs = Vec::with_capacity(2);
+                    let mut values = Vec::with_capacity(2);
+                    for (target, rev) in [&old, &new].into_iter().zip(&revs) {
+                        if let Some(rev) = rev {
+                            app.set_git_filesystem(rev, eval_opts.sandbox)?;
+                        }
+                        // Evaluate both documents in a clean environment.
+                        let mut type_env = typecheck::prelude();
+                        let mut value_env = runtime::prelude();
+                        let start = Instant::now();
+                        let doc = app.loader.load_cli_target(target)?;
+                        tracer.record(Phase::Read, app.loader.get_span(doc), start);
+                        let val = app.evaluate_input(
+                            eval_opts.input_format,
+                            &mut type_env,
+                            &mut value_env,
+                            doc,
+                            tracer,
+                        )?;
+                        docs.push(doc);
+                        values.push(val);
+                    }
+                    Ok((docs, values))
+                })?;
+
+                if eval_opts.format == OutputFormat::Json {
+                    let report = rcl::cmd_diff::diff_values_report(&values[0], &values[1]);
+                    let body_span = self.loader.get_span(docs[1]);
+                    return self.print_value(
+                        &eval_opts,
+                        &style_opts,
+                        output,
+                        docs[1],
+                        body_span,
+                        &report,
+                    );
+                }
+
+                let changes = rcl::cmd_diff::diff_values(&values[0], &values[1]);
+                if changes.is_empty() {
+                    // There is nothing to print, but when writing to a file,
+                    // we should not leave behind the output of an earlier run.
+                    return match output {
+                        OutputTarget::Stdout => Ok(()),
+                        OutputTarget::File(fname) => {
+                            self.print_to_file(MarkupMode::None, MarkupString::new(), &fname)
+                        }
+                    };
+                }
+                let out_doc = Doc::join(changes.into_iter(), Doc::HardBreak);
+                self.print_doc_target(output, &style_opts.pprint_config(), out_doc)
+            }
+
+            Cmd::Lsp => {
+                let stdin = std::io::stdin();
+                let stdout = std::io::stdout();
+                rcl::cmd_lsp::serve(&mut stdin.lock(), &mut stdout.lock())
+            }
+
+            Cmd::SelfTestFmt { style_opts, fnames } => {
+                self.loader.initialize_filesystem(
+                    SandboxMode::Unrestricted,
+                    self.opts.workdir.as_deref(),
+                )?;
+                let style = FormatStyle {
+                    print: style_opts.pprint_config(),
+                    ..FormatStyle::default()
+                };
+                let mut n_failed = 0_usize;
+                for fname in fnames.iter() {
+                    let doc = self.loader.load_cli_target(fname)?;
+                    if let Err(err) = rcl::cmd_fmt::self_test(&mut self.loader, doc, &style) {
+                        self.print_error(*err);
+                        n_failed += 1;
+                    }
+                }
+                if n_failed == 0 {
+                    return Ok(());
+                }
+                let parts = vec![
+                    n_failed.to_string().into(),
+                    Doc::str(" of "),
+                    fnames.len().to_string().into(),
+                    Doc::str(" files failed the formatter self-test."),
+                ];
+                Error::new(Doc::Concat(parts))
+                    .with_kind(ErrorKind::Check)
+                    .err()
+            }
+
             Cmd::Version => {
                 println!("RCL version {}", env!("CARGO_PKG_VERSION"));
                 Ok(())
@@ -373,13 +1207,62 @@ impl App {
     }
 }
 
+/// Return the modification time of the file, or `None` if it is inaccessible.
+fn get_mtime(path: &Path) -> Option<std::time::SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
 fn main() {
     let mut app = App {
         opts: GlobalOptions::default(),
         loader: Loader::new(),
+        timings: None,
+        use_pager: false,
     };
 
     if let Err(err) = app.main() {
         app.print_fatal_error(*err);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::App;
+    use rcl::cli::GlobalOptions;
+    use rcl::loader::Loader;
+    use rcl::markup::{Markup, MarkupString};
+
+    #[cfg(unix)]
+    #[test]
+    fn replace_file_writes_through_symlinks() {
+        let app = App {
+            opts: GlobalOptions::default(),
+            loader: Loader::new(),
+            timings: None,
+            use_pager: false,
+        };
+        let root = std::env::temp_dir().join(format!("rcl-replace-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("real")).unwrap();
+        std::fs::write(root.join("real/config.rcl"), "old").unwrap();
+        std::os::unix::fs::symlink("real/config.rcl", root.join("config.rcl")).unwrap();
+
+        let mut data = MarkupString::new();
+        data.push("new", Markup::None);
+        app.replace_file_impl(data, &root.join("config.rcl"))
+            .unwrap();
+
+        let link = std::fs::symlink_metadata(root.join("config.rcl")).unwrap();
+        assert!(link.file_type().is_symlink());
+        let contents = std::fs::read_to_string(root.join("real/config.rcl")).unwrap();
+        assert_eq!(contents, "new");
+        let mut names: Vec<_> = std::fs::read_dir(root.join("real"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["config.rcl"]);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -9,6 +9,8 @@
 
 use std::io::{IsTerminal, Write};
 
+use crate::string::escape_json;
+
 /// A markup hint, used to apply color and other markup to output.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Markup {
@@ -28,6 +30,17 @@ pub enum Markup {
     /// to clarify visually where the boundaries of a quotation are.
     Highlight,
 
+    /// A source location `path:line:column` in error messages.
+    ///
+    /// In ANSI output, when the path refers to a file, this becomes an OSC 8
+    /// hyperlink to that file, so terminals that support it can open the file.
+    Link,
+
+    // These are meant for structural diffs.
+    DiffAdd,
+    DiffChange,
+    DiffRemove,
+
     // These are meant for syntax highlighting.
     Builtin,
     Comment,
@@ -39,75 +52,495 @@ pub enum Markup {
     Type,
 }
 
+impl Markup {
+    /// All kinds of markup, other than [`Markup::None`], in declaration order.
+    pub const ALL: [Markup; 16] = [
+        Markup::Error,
+        Markup::Warning,
+        Markup::Trace,
+        Markup::Highlight,
+        Markup::Link,
+        Markup::DiffAdd,
+        Markup::DiffChange,
+        Markup::DiffRemove,
+        Markup::Builtin,
+        Markup::Comment,
+        Markup::Escape,
+        Markup::Field,
+        Markup::Keyword,
+        Markup::Number,
+        Markup::String,
+        Markup::Type,
+    ];
+}
+
 /// How to treat color and other markup hints.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum MarkupMode {
     /// Ignore all markup hints, do not output them.
     None,
 
-    /// Output markup as ANSI escape sequences.
-    Ansi,
+    /// Output markup as ANSI escape sequences, styled with the given theme.
+    Ansi(Theme),
 
     /// Output as html spans in the same style as Pandoc with Pygments style.
     HtmlPandoc,
+
+    /// Output as html spans with descriptive `rcl-` prefixed class names.
+    ///
+    /// Unlike [`MarkupMode::HtmlPandoc`], this does not wrap the output in a
+    /// `<pre>` element, so it can be embedded in a page as the page sees fit.
+    Html,
 }
 
-/// Whether we should use ANSI colors when writing to this file descriptor.
+/// Whether to color output regardless of whether it goes to a terminal.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ColorOverride {
+    /// Use color if the output is a terminal.
+    Auto,
+    /// Always use color, for example for CI logs that render ANSI escapes.
+    Always,
+    /// Never use color.
+    Never,
+}
+
+impl ColorOverride {
+    /// Read the override from the environment.
+    ///
+    /// A nonempty `NO_COLOR` disables color, see <https://no-color.org/>.
+    /// Otherwise, `CLICOLOR_FORCE` or `FORCE_COLOR` set to a value other than
+    /// empty or `0` forces color on, see <https://force-color.org/>.
+    pub fn from_env() -> ColorOverride {
+        ColorOverride::from_vars(|name| std::env::var(name).ok())
+    }
+
+    /// Determine the override from a function that looks up variables.
+    fn from_vars(get: impl Fn(&str) -> Option<String>) -> ColorOverride {
+        let is_set = |name: &str| matches!(get(name).as_deref(), Some(v) if v != "" && v != "0");
+        if get("NO_COLOR").map_or(false, |v| v != "") {
+            ColorOverride::Never
+        } else if is_set("CLICOLOR_FORCE") || is_set("FORCE_COLOR") {
+            ColorOverride::Always
+        } else {
+            ColorOverride::Auto
+        }
+    }
+}
+
+/// Whether the terminal interprets ANSI escape codes.
 ///
-/// Returns true when the file descriptor refers to a terminal, unless the
-/// `NO_COLOR` environment variable is set to a nonempty string. See also
-/// <https://no-color.org/>.
-fn should_color<T: IsTerminal>(fd: &T) -> bool {
-    if !fd.is_terminal() {
-        return false;
-    }
-    // coverage:off -- Tests never run with a terminal, so this is never covered.
-    match std::env::var("NO_COLOR") {
-        Ok(no_color) => no_color == "",
-        Err(..) => true,
-    }
-    // coverage:on
+/// The Windows console only does so when virtual terminal processing is
+/// enabled, and without it, escape codes show up as garbage. Enabling it takes
+/// unsafe calls to the console API, which we avoid. Instead, we recognize the
+/// terminals that enable it themselves, or that emulate a Unix terminal.
+#[cfg(windows)]
+fn terminal_supports_ansi() -> bool {
+    let is_var =
+        |name: &str, pred: fn(&str) -> bool| std::env::var(name).map_or(false, |v| pred(&v));
+    is_var("WT_SESSION", |_| true)
+        || is_var("TERM_PROGRAM", |_| true)
+        || is_var("ConEmuANSI", |v| v == "ON")
+        || is_var("TERM", |v| v != "dumb")
+}
+
+#[cfg(not(windows))]
+fn terminal_supports_ansi() -> bool {
+    true
+}
+
+/// Whether we should use ANSI colors when writing to this file descriptor.
+fn should_color<T: IsTerminal>(fd: &T, color: ColorOverride) -> bool {
+    match color {
+        ColorOverride::Always => true,
+        ColorOverride::Never => false,
+        ColorOverride::Auto => fd.is_terminal() && terminal_supports_ansi(),
+    }
 }
 
 impl MarkupMode {
     /// Get the default markup configuration for a file descriptor.
+    ///
+    /// This uses color when the file descriptor refers to a terminal, unless
+    /// the environment overrides it, see [`ColorOverride::from_env`].
     pub fn default_for_fd<T: IsTerminal>(fd: &T) -> Self {
-        if should_color(fd) {
-            MarkupMode::Ansi
+        MarkupMode::for_fd(fd, ColorOverride::from_env())
+    }
+
+    /// Get the markup configuration for a file descriptor with an explicit override.
+    pub fn for_fd<T: IsTerminal>(fd: &T, color: ColorOverride) -> Self {
+        if should_color(fd, color) {
+            MarkupMode::Ansi(Theme::DARK).with_user_colors()
         } else {
             MarkupMode::None
         }
     }
+
+    /// Apply the overrides from `RCL_COLORS` to the theme, if this mode has one.
+    ///
+    /// When the variable is invalid, the theme is left unchanged. Use
+    /// [`check_user_colors`] to report the problem to the user.
+    pub fn with_user_colors(self) -> Self {
+        match (self, std::env::var(COLORS_VAR)) {
+            (MarkupMode::Ansi(theme), Ok(spec)) => {
+                MarkupMode::Ansi(theme.with_overrides(&spec).unwrap_or(theme))
+            }
+            _ => self,
+        }
+    }
 }
 
-/// Return the ANSI escape code to switch to style `markup`.
-pub fn switch_ansi(markup: Markup) -> &'static str {
-    let reset = "\x1b[0m";
-    let bold_blue = "\x1b[34;1m";
-    let bold_green = "\x1b[32;1m";
-    let bold_red = "\x1b[31;1m";
-    let bold_yellow = "\x1b[33;1m";
-    let blue = "\x1b[34m";
-    let cyan = "\x1b[36m";
-    let magenta = "\x1b[35m";
-    let red = "\x1b[31m";
-    let white = "\x1b[37m";
-    let yellow = "\x1b[33m";
+/// The environment variable that overrides styles, see [`Theme::with_overrides`].
+pub const COLORS_VAR: &str = "RCL_COLORS";
 
-    match markup {
-        Markup::None => reset,
-        Markup::Error => bold_red,
-        Markup::Warning => bold_yellow,
-        Markup::Trace => bold_blue,
-        Markup::Highlight => white,
-        Markup::Builtin => red,
-        Markup::Comment => white,
-        Markup::Field => blue,
-        Markup::Keyword => bold_green,
-        Markup::Number => cyan,
-        Markup::String => red,
-        Markup::Escape => yellow,
-        Markup::Type => magenta,
+/// Validate the `RCL_COLORS` environment variable, if it is set.
+pub fn check_user_colors() -> Result<(), String> {
+    match std::env::var(COLORS_VAR) {
+        Ok(spec) => Theme::DARK.with_overrides(&spec).map(|_| ()),
+        Err(..) => Ok(()),
+    }
+}
+
+/// A terminal color, as used in ANSI escape sequences.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Color {
+    /// One of the 16 basic colors, 0-7 are the regular ones, 8-15 the bright ones.
+    Basic(u8),
+    /// A color from the 256-color palette.
+    Palette(u8),
+    /// A 24-bit truecolor color.
+    Rgb(u8, u8, u8),
+}
+
+/// How to style a single kind of markup in ANSI output.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Style {
+    pub color: Option<Color>,
+    pub bold: bool,
+    pub italic: bool,
+}
+
+impl Style {
+    /// The default style, no color and no attributes.
+    pub const PLAIN: Style = Style {
+        color: None,
+        bold: false,
+        italic: false,
+    };
+
+    /// A style with only a foreground color.
+    pub const fn fg(color: Color) -> Style {
+        Style {
+            color: Some(color),
+            bold: false,
+            italic: false,
+        }
+    }
+
+    /// Return a copy of the style that is also bold.
+    pub const fn bold(self) -> Style {
+        Style { bold: true, ..self }
+    }
+
+    /// Return a copy of the style that is also italic.
+    pub const fn italic(self) -> Style {
+        Style {
+            italic: true,
+            ..self
+        }
+    }
+
+    /// Parse a style from semicolon-separated SGR parameters, e.g. `38;5;124;1`.
+    ///
+    /// Only the parameters that a [`Style`] can represent are supported, the
+    /// style starts out plain.
+    pub fn parse_sgr(params: &str) -> Option<Style> {
+        Style::PLAIN.with_sgr(params)
+    }
+
+    /// Return the style after applying the SGR parameters, as a terminal would.
+    ///
+    /// Like for [`Style::parse_sgr`], empty parameters reset the style, and
+    /// unsupported parameters make this return `None`.
+    pub fn with_sgr(self, params: &str) -> Option<Style> {
+        let mut style = self;
+        if params.is_empty() {
+            return Some(Style::PLAIN);
+        }
+        let mut params = params.split(';').map(|p| p.parse::<u8>().ok());
+        while let Some(param) = params.next() {
+            match param? {
+                0 => style = Style::PLAIN,
+                1 => style.bold = true,
+                3 => style.italic = true,
+                22 => style.bold = false,
+                23 => style.italic = false,
+                n @ 30..=37 => style.color = Some(Color::Basic(n - 30)),
+                n @ 90..=97 => style.color = Some(Color::Basic(n - 90 + 8)),
+                39 => style.color = None,
+                38 => match params.next()?? {
+                    5 => style.color = Some(Color::Palette(params.next()??)),
+                    2 => {
+                        let r = params.next()??;
+                        let g = params.next()??;
+                        let b = params.next()??;
+                        style.color = Some(Color::Rgb(r, g, b));
+                    }
+                    _ => return None,
+                },
+                _ => return None,
+            }
+        }
+        Some(style)
+    }
+
+    /// Whether switching from `prev` to this style has to reset attributes first.
+    ///
+    /// An escape sequence only sets attributes, it does not clear the ones that
+    /// the previous style set, so if we drop any, we have to reset.
+    fn needs_reset_from(&self, prev: &Style) -> bool {
+        (prev.bold && !self.bold)
+            || (prev.italic && !self.italic)
+            || (prev.color.is_some() && self.color.is_none())
+    }
+
+    /// Write the SGR parameters for this style, separated by semicolons.
+    fn write_sgr_params(&self, out: &mut dyn Write) -> std::io::Result<()> {
+        let mut sep = "";
+        match self.color {
+            None => {}
+            Some(Color::Basic(n)) if n < 8 => write!(out, "{}", 30 + n as u32)?,
+            Some(Color::Basic(n)) => write!(out, "{}", 90 + (n as u32 & 7))?,
+            Some(Color::Palette(n)) => write!(out, "38;5;{n}")?,
+            Some(Color::Rgb(r, g, b)) => write!(out, "38;2;{r};{g};{b}")?,
+        }
+        if self.color.is_some() {
+            sep = ";";
+        }
+        if self.bold {
+            write!(out, "{sep}1")?;
+            sep = ";";
+        }
+        if self.italic {
+            write!(out, "{sep}3")?;
+        }
+        Ok(())
+    }
+}
+
+const BLACK: u8 = 0;
+const RED: u8 = 1;
+const GREEN: u8 = 2;
+const YELLOW: u8 = 3;
+const BLUE: u8 = 4;
+const MAGENTA: u8 = 5;
+const CYAN: u8 = 6;
+const WHITE: u8 = 7;
+const BRIGHT_BLACK: u8 = BLACK + 8;
+
+/// A color scheme for ANSI output, with a style for every kind of markup.
+///
+/// [`Markup::None`] has no entry, it always resets to the terminal's default.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Theme {
+    pub error: Style,
+    pub warning: Style,
+    pub trace: Style,
+    pub highlight: Style,
+    pub link: Style,
+    pub diff_add: Style,
+    pub diff_change: Style,
+    pub diff_remove: Style,
+    pub builtin: Style,
+    pub comment: Style,
+    pub escape: Style,
+    pub field: Style,
+    pub keyword: Style,
+    pub number: Style,
+    pub string: Style,
+    pub type_: Style,
+}
+
+impl Theme {
+    /// The default theme, using the 16 basic colors, for dark backgrounds.
+    pub const DARK: Theme = Theme {
+        error: Style::fg(Color::Basic(RED)).bold(),
+        warning: Style::fg(Color::Basic(YELLOW)).bold(),
+        trace: Style::fg(Color::Basic(BLUE)).bold(),
+        highlight: Style::fg(Color::Basic(WHITE)),
+        link: Style::PLAIN,
+        diff_add: Style::fg(Color::Basic(GREEN)),
+        diff_change: Style::fg(Color::Basic(YELLOW)),
+        diff_remove: Style::fg(Color::Basic(RED)),
+        builtin: Style::fg(Color::Basic(RED)),
+        comment: Style::fg(Color::Basic(WHITE)),
+        escape: Style::fg(Color::Basic(YELLOW)),
+        field: Style::fg(Color::Basic(BLUE)),
+        keyword: Style::fg(Color::Basic(GREEN)).bold(),
+        number: Style::fg(Color::Basic(CYAN)),
+        string: Style::fg(Color::Basic(RED)),
+        type_: Style::fg(Color::Basic(MAGENTA)),
+    };
+
+    /// A theme for light backgrounds, using the 256-color palette.
+    ///
+    /// It avoids white and yellow, which are hard to read on a light background,
+    /// and uses darker shades than the basic colors.
+    pub const LIGHT: Theme = Theme {
+        error: Style::fg(Color::Palette(124)).bold(),
+        warning: Style::fg(Color::Palette(130)).bold(),
+        trace: Style::fg(Color::Palette(25)).bold(),
+        highlight: Style::PLAIN.bold(),
+        link: Style::PLAIN,
+        diff_add: Style::fg(Color::Palette(28)),
+        diff_change: Style::fg(Color::Palette(130)),
+        diff_remove: Style::fg(Color::Palette(124)),
+        builtin: Style::fg(Color::Palette(90)),
+        comment: Style::fg(Color::Basic(BRIGHT_BLACK)).italic(),
+        escape: Style::fg(Color::Palette(130)),
+        field: Style::fg(Color::Palette(25)),
+        keyword: Style::fg(Color::Palette(28)).bold(),
+        number: Style::fg(Color::Palette(30)),
+        string: Style::fg(Color::Palette(124)),
+        type_: Style::fg(Color::Palette(90)),
+    };
+
+    /// A theme for dark backgrounds, using 24-bit colors.
+    pub const TRUECOLOR: Theme = Theme {
+        error: Style::fg(Color::Rgb(0xe0, 0x6c, 0x75)).bold(),
+        warning: Style::fg(Color::Rgb(0xe5, 0xc0, 0x7b)).bold(),
+        trace: Style::fg(Color::Rgb(0x61, 0xaf, 0xef)).bold(),
+        highlight: Style::fg(Color::Rgb(0xdc, 0xdf, 0xe4)),
+        link: Style::PLAIN,
+        diff_add: Style::fg(Color::Rgb(0x98, 0xc3, 0x79)),
+        diff_change: Style::fg(Color::Rgb(0xe5, 0xc0, 0x7b)),
+        diff_remove: Style::fg(Color::Rgb(0xe0, 0x6c, 0x75)),
+        builtin: Style::fg(Color::Rgb(0x56, 0xb6, 0xc2)),
+        comment: Style::fg(Color::Rgb(0x7f, 0x84, 0x8e)).italic(),
+        escape: Style::fg(Color::Rgb(0xd1, 0x9a, 0x66)),
+        field: Style::fg(Color::Rgb(0x61, 0xaf, 0xef)),
+        keyword: Style::fg(Color::Rgb(0xc6, 0x78, 0xdd)).bold(),
+        number: Style::fg(Color::Rgb(0xd1, 0x9a, 0x66)),
+        string: Style::fg(Color::Rgb(0x98, 0xc3, 0x79)),
+        type_: Style::fg(Color::Rgb(0xe5, 0xc0, 0x7b)),
+    };
+
+    /// Return the style for `markup`.
+    pub fn style(&self, markup: Markup) -> Style {
+        match markup {
+            Markup::None => Style::PLAIN,
+            Markup::Error => self.error,
+            Markup::Warning => self.warning,
+            Markup::Trace => self.trace,
+            Markup::Highlight => self.highlight,
+            Markup::Link => self.link,
+            Markup::DiffAdd => self.diff_add,
+            Markup::DiffChange => self.diff_change,
+            Markup::DiffRemove => self.diff_remove,
+            Markup::Builtin => self.builtin,
+            Markup::Comment => self.comment,
+            Markup::Escape => self.escape,
+            Markup::Field => self.field,
+            Markup::Keyword => self.keyword,
+            Markup::Number => self.number,
+            Markup::String => self.string,
+            Markup::Type => self.type_,
+        }
+    }
+
+    /// Return the first markup that has the given style, or [`Markup::None`].
+    ///
+    /// Plain text is always [`Markup::None`]. Themes can use the same style for
+    /// multiple kinds of markup, those are tried in the order of [`Markup`].
+    pub fn markup_for_style(&self, style: Style) -> Markup {
+        if style == Style::PLAIN {
+            return Markup::None;
+        }
+        Markup::ALL
+            .into_iter()
+            .find(|m| self.style(*m) == style)
+            .unwrap_or(Markup::None)
+    }
+
+    /// Return the style for the markup with the given name, if it exists.
+    ///
+    /// The names are those of the `rcl-` classes, without the prefix.
+    fn style_mut(&mut self, name: &str) -> Option<&mut Style> {
+        let style = match name {
+            "error" => &mut self.error,
+            "warning" => &mut self.warning,
+            "trace" => &mut self.trace,
+            "highlight" => &mut self.highlight,
+            "link" => &mut self.link,
+            "diff-add" => &mut self.diff_add,
+            "diff-change" => &mut self.diff_change,
+            "diff-remove" => &mut self.diff_remove,
+            "builtin" => &mut self.builtin,
+            "comment" => &mut self.comment,
+            "escape" => &mut self.escape,
+            "field" => &mut self.field,
+            "keyword" => &mut self.keyword,
+            "number" => &mut self.number,
+            "string" => &mut self.string,
+            "type" => &mut self.type_,
+            _ => return None,
+        };
+        Some(style)
+    }
+
+    /// Return a copy of the theme with the styles from `spec` replaced.
+    ///
+    /// The spec is formatted like `LS_COLORS`: a colon-separated list of
+    /// `name=params` entries, where `name` is the name of the markup as in
+    /// `rcl-` class names, and `params` are SGR parameters as accepted by
+    /// [`Style::parse_sgr`]. For example, `string=32:comment=3;90`.
+    pub fn with_overrides(&self, spec: &str) -> Result<Theme, String> {
+        let mut theme = *self;
+        for entry in spec.split(':').filter(|e| !e.is_empty()) {
+            let (name, params) = match entry.split_once('=') {
+                Some(kv) => kv,
+                None => return Err(format!("Expected 'name=style', but found '{entry}'.")),
+            };
+            let style = match theme.style_mut(name) {
+                Some(style) => style,
+                None => return Err(format!("Unknown markup '{name}'.")),
+            };
+            *style = match Style::parse_sgr(params) {
+                Some(parsed) => parsed,
+                None => return Err(format!("Invalid style '{params}' for '{name}'.")),
+            };
+        }
+        Ok(theme)
+    }
+
+    /// Write the ANSI escape sequence to switch from style `from` to `to`.
+    ///
+    /// When both have the same style, nothing is written.
+    pub fn switch_ansi(
+        &self,
+        out: &mut dyn Write,
+        from: Markup,
+        to: Markup,
+    ) -> std::io::Result<()> {
+        let prev = self.style(from);
+        let next = self.style(to);
+        if next == prev {
+            return Ok(());
+        }
+        if next == Style::PLAIN {
+            return out.write_all(b"\x1b[0m");
+        }
+        out.write_all(b"\x1b[")?;
+        if next.needs_reset_from(&prev) {
+            out.write_all(b"0;")?;
+        }
+        next.write_sgr_params(out)?;
+        out.write_all(b"m")
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::DARK
     }
 }
 
@@ -121,6 +554,10 @@ pub fn html_class_pandoc(markup: Markup) -> &'static str {
         Markup::Warning => "warn",
         Markup::Trace => "trace",
         Markup::Highlight => "highlight",
+        Markup::Link => "link",
+        Markup::DiffAdd => "ins",
+        Markup::DiffChange => "chg",
+        Markup::DiffRemove => "del",
 
         Markup::Builtin => "fu",
         Markup::Comment => "co",
@@ -133,6 +570,61 @@ pub fn html_class_pandoc(markup: Markup) -> &'static str {
     }
 }
 
+/// Return the class name for `markup` in [`MarkupMode::Html`] output.
+pub fn html_class(markup: Markup) -> &'static str {
+    match markup {
+        Markup::None => panic!("Should not be called for Markup::None."),
+        Markup::Error => "rcl-error",
+        Markup::Warning => "rcl-warning",
+        Markup::Trace => "rcl-trace",
+        Markup::Highlight => "rcl-highlight",
+        Markup::Link => "rcl-link",
+        Markup::DiffAdd => "rcl-diff-add",
+        Markup::DiffChange => "rcl-diff-change",
+        Markup::DiffRemove => "rcl-diff-remove",
+        Markup::Builtin => "rcl-builtin",
+        Markup::Comment => "rcl-comment",
+        Markup::Escape => "rcl-escape",
+        Markup::Field => "rcl-field",
+        Markup::Keyword => "rcl-keyword",
+        Markup::Number => "rcl-number",
+        Markup::String => "rcl-string",
+        Markup::Type => "rcl-type",
+    }
+}
+
+/// Return a `file://` URI for a location `path:line:column` in error messages.
+///
+/// Relative paths are resolved against the working directory. Returns `None`
+/// when the location does not refer to an existing file, for example when the
+/// input is stdin. There is no standard way to link to a line in a file, so the
+/// URI refers to the file only.
+fn location_uri(location: &str) -> Option<String> {
+    let mut parts = location.rsplitn(3, ':');
+    let (_column, _line, path) = (parts.next()?, parts.next()?, parts.next()?);
+    let path = std::env::current_dir().ok()?.join(path);
+    if !path.is_file() {
+        return None;
+    }
+    // On Windows, the path starts with a drive letter and uses backslashes,
+    // but the URI needs forward slashes, and a slash before the drive.
+    let path = path.to_str()?.replace('\\', "/");
+    let mut uri = String::with_capacity(path.len() + 8);
+    uri.push_str("file://");
+    if !path.starts_with('/') {
+        uri.push('/');
+    }
+    for b in path.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                uri.push(b as char)
+            }
+            _ => uri.push_str(&format!("%{b:02X}")),
+        }
+    }
+    Some(uri)
+}
+
 /// A string pieced together from fragments that have markup.
 pub struct MarkupString<'a> {
     pub fragments: Vec<(&'a str, Markup)>,
@@ -147,6 +639,82 @@ impl<'a> MarkupString<'a> {
         }
     }
 
+    /// Reconstruct a string with markup from text with ANSI escape codes.
+    ///
+    /// This is the inverse of [`MarkupString::write_bytes_ansi`] with the
+    /// default theme, see [`MarkupString::parse_ansi_with_theme`].
+    pub fn parse_ansi(input: &'a str) -> MarkupString<'a> {
+        MarkupString::parse_ansi_with_theme(input, &Theme::DARK)
+    }
+
+    /// Reconstruct a string with markup from text with ANSI escape codes.
+    ///
+    /// SGR sequences update the current style, and the text that follows gets
+    /// the markup that has this style in the theme, see
+    /// [`Theme::markup_for_style`]. A sequence with parameters that a [`Style`]
+    /// cannot represent leaves the style unchanged. Other escape sequences, such
+    /// as cursor movement and hyperlinks, are removed.
+    pub fn parse_ansi_with_theme(input: &'a str, theme: &Theme) -> MarkupString<'a> {
+        let bytes = input.as_bytes();
+        let mut result = MarkupString::new();
+        let mut style = Style::PLAIN;
+        let mut text_start = 0;
+        let mut i = 0;
+
+        while i < bytes.len() {
+            if bytes[i] != b'\x1b' {
+                i += 1;
+                continue;
+            }
+            if text_start < i {
+                result.push(&input[text_start..i], theme.markup_for_style(style));
+            }
+            i += 1;
+            match bytes.get(i) {
+                Some(b'[') => {
+                    // A control sequence: parameter and intermediate bytes,
+                    // followed by a single final byte.
+                    let params_start = i + 1;
+                    i = params_start;
+                    while i < bytes.len() && (0x20..0x40).contains(&bytes[i]) {
+                        i += 1;
+                    }
+                    if bytes.get(i) == Some(&b'm') {
+                        if let Some(new_style) = style.with_sgr(&input[params_start..i]) {
+                            style = new_style;
+                        }
+                    }
+                    if i < bytes.len() && (0x40..0x7f).contains(&bytes[i]) {
+                        i += 1;
+                    }
+                }
+                Some(b']') => {
+                    // An operating system command, such as a hyperlink, which
+                    // ends in BEL, or in ESC followed by a backslash.
+                    while i < bytes.len() && bytes[i] != 0x07 && bytes[i] != b'\x1b' {
+                        i += 1;
+                    }
+                    match bytes.get(i) {
+                        Some(0x07) => i += 1,
+                        Some(b'\x1b') if bytes.get(i + 1) == Some(&b'\\') => i += 2,
+                        _ => {}
+                    }
+                }
+                // Other escape sequences consist of a single character after
+                // the escape. Check that it is ASCII to not split a code point.
+                Some(ch) if ch.is_ascii() => i += 1,
+                _ => {}
+            }
+            text_start = i;
+        }
+
+        if text_start < bytes.len() {
+            result.push(&input[text_start..], theme.markup_for_style(style));
+        }
+
+        result
+    }
+
     /// Append a new fragment.
     pub fn push(&mut self, fragment: &'a str, markup: Markup) {
         debug_assert!(!fragment.is_empty(), "Should not push empty fragments.");
@@ -168,10 +736,15 @@ impl<'a> MarkupString<'a> {
         self.fragments.truncate(num_fragments)
     }
 
-    /// Remove all spaces at the end.
-    pub fn trim_spaces_end(&mut self) {
-        while let Some((fragment, _markup)) = self.fragments.last_mut() {
+    /// Remove all spaces at the end, but keep the first `n_keep` fragments intact.
+    ///
+    /// Returns the number of spaces removed.
+    pub fn trim_spaces_end(&mut self, n_keep: usize) -> usize {
+        let mut n_removed = 0;
+        while self.fragments.len() > n_keep {
+            let (fragment, _markup) = self.fragments.last_mut().expect("Not empty.");
             let f_trimmed = fragment.trim_end_matches(' ');
+            n_removed += fragment.len() - f_trimmed.len();
             if f_trimmed.is_empty() {
                 self.fragments.pop();
             } else {
@@ -179,6 +752,7 @@ impl<'a> MarkupString<'a> {
                 break;
             }
         }
+        n_removed
     }
 
     /// Append the string to a regular `String`, discarding all markup.
@@ -205,25 +779,61 @@ impl<'a> MarkupString<'a> {
     }
 
     /// Write the string to a writer, using ANSI escape codes for markup.
-    pub fn write_bytes_ansi(&self, out: &mut dyn Write) -> std::io::Result<()> {
+    pub fn write_bytes_ansi(&self, theme: &Theme, out: &mut dyn Write) -> std::io::Result<()> {
         let mut markup = Markup::None;
+        let mut in_link = false;
 
-        for (frag_str, frag_markup) in self.fragments.iter() {
+        for (i, (frag_str, frag_markup)) in self.fragments.iter().enumerate() {
             if markup != *frag_markup {
-                out.write_all(switch_ansi(*frag_markup).as_bytes())?;
+                if in_link {
+                    out.write_all(b"\x1b]8;;\x1b\\")?;
+                    in_link = false;
+                }
+                theme.switch_ansi(out, markup, *frag_markup)?;
+                if *frag_markup == Markup::Link {
+                    // The location can be split over multiple fragments, the
+                    // link target is the full text of the run.
+                    let location: String = self.fragments[i..]
+                        .iter()
+                        .take_while(|(_, m)| *m == Markup::Link)
+                        .map(|(f, _)| *f)
+                        .collect();
+                    if let Some(uri) = location_uri(&location) {
+                        write!(out, "\x1b]8;;{uri}\x1b\\")?;
+                        in_link = true;
+                    }
+                }
                 markup = *frag_markup;
             }
             out.write_all(frag_str.as_bytes())?;
         }
 
+        if in_link {
+            out.write_all(b"\x1b]8;;\x1b\\")?;
+        }
+
         Ok(())
     }
 
     /// Write the string to a writer, using Pandoc class names for the spans.
     pub fn write_bytes_html_pandoc(&self, out: &mut dyn Write) -> std::io::Result<()> {
-        let mut markup = Markup::None;
-
         write!(out, "<pre><code class=\"sourceCode\">")?;
+        self.write_html_spans(out, html_class_pandoc)?;
+        writeln!(out, "</code></pre>")
+    }
+
+    /// Write the string to a writer as html spans with `rcl-` class names.
+    pub fn write_bytes_html(&self, out: &mut dyn Write) -> std::io::Result<()> {
+        self.write_html_spans(out, html_class)
+    }
+
+    /// Write the fragments as html-escaped text, in spans with the given classes.
+    fn write_html_spans(
+        &self,
+        out: &mut dyn Write,
+        class: fn(Markup) -> &'static str,
+    ) -> std::io::Result<()> {
+        let mut markup = Markup::None;
 
         for (frag_str, frag_markup) in self.fragments.iter() {
             if markup != *frag_markup {
@@ -231,7 +841,7 @@ impl<'a> MarkupString<'a> {
                     write!(out, "</span>")?;
                 }
                 if *frag_markup != Markup::None {
-                    write!(out, "<span class=\"{}\">", html_class_pandoc(*frag_markup))?;
+                    write!(out, "<span class=\"{}\">", class(*frag_markup))?;
                 }
             }
 
@@ -251,15 +861,250 @@ impl<'a> MarkupString<'a> {
             write!(out, "</span>")?;
         }
 
-        writeln!(out, "</code></pre>")
+        Ok(())
+    }
+
+    /// Write the string as a json array of tokens, for consumers that want the markup.
+    ///
+    /// Every token is an object with the `text`, the `markup` name as in the
+    /// `rcl-` class names without prefix (or `null` for unmarked text), and the
+    /// byte `offset` of the text in the output. Adjacent fragments with the same
+    /// markup are merged into one token.
+    pub fn write_json_tokens(&self, out: &mut dyn Write) -> std::io::Result<()> {
+        let mut tokens = String::from("[");
+        let mut offset = 0;
+        let mut i = 0;
+        while i < self.fragments.len() {
+            let markup = self.fragments[i].1;
+            let mut text = String::new();
+            while let Some((frag_str, _)) = self.fragments[i..].first().filter(|f| f.1 == markup) {
+                text.push_str(frag_str);
+                i += 1;
+            }
+            if offset > 0 {
+                tokens.push_str(", ");
+            }
+            tokens.push_str(r#"{"text": ""#);
+            escape_json(&text, &mut tokens);
+            tokens.push_str(r#"", "markup": "#);
+            match markup {
+                Markup::None => tokens.push_str("null"),
+                _ => {
+                    tokens.push('"');
+                    tokens.push_str(&html_class(markup)["rcl-".len()..]);
+                    tokens.push('"');
+                }
+            }
+            tokens.push_str(&format!(r#", "offset": {offset}}}"#));
+            offset += text.len();
+        }
+        tokens.push(']');
+        out.write_all(tokens.as_bytes())
     }
 
     /// Write the string to a write with the given markup mode.
     pub fn write_bytes(&self, mode: MarkupMode, out: &mut dyn Write) -> std::io::Result<()> {
         match mode {
             MarkupMode::None => self.write_bytes_no_markup(out),
-            MarkupMode::Ansi => self.write_bytes_ansi(out),
+            MarkupMode::Ansi(theme) => self.write_bytes_ansi(&theme, out),
             MarkupMode::HtmlPandoc => self.write_bytes_html_pandoc(out),
+            MarkupMode::Html => self.write_bytes_html(out),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{location_uri, Color, ColorOverride, Markup, MarkupString, Style, Theme};
+
+    fn ansi(theme: &Theme, fragments: &[(&'static str, Markup)]) -> String {
+        let mut s = MarkupString::new();
+        for (f, m) in fragments {
+            s.push(f, *m);
+        }
+        let mut out = Vec::new();
+        s.write_bytes_ansi(theme, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn dark_theme_uses_basic_colors() {
+        let out = ansi(
+            &Theme::DARK,
+            &[
+                ("Error:", Markup::Error),
+                (" ", Markup::None),
+                ("x", Markup::String),
+            ],
+        );
+        assert_eq!(out, "\x1b[31;1mError:\x1b[0m \x1b[31mx");
+    }
+
+    #[test]
+    fn switching_resets_attributes_that_are_dropped() {
+        let out = ansi(
+            &Theme::DARK,
+            &[("let", Markup::Keyword), ("x", Markup::Field)],
+        );
+        assert_eq!(out, "\x1b[32;1mlet\x1b[0;34mx");
+        let out = ansi(
+            &Theme::LIGHT,
+            &[("x", Markup::Field), ("y", Markup::Highlight)],
+        );
+        assert_eq!(out, "\x1b[38;5;25mx\x1b[0;1my");
+    }
+
+    #[test]
+    fn truecolor_theme_uses_rgb() {
+        let out = ansi(&Theme::TRUECOLOR, &[("// c", Markup::Comment)]);
+        assert_eq!(out, "\x1b[38;2;127;132;142;3m// c");
+    }
+
+    #[test]
+    fn parse_sgr_handles_all_color_kinds() {
+        let style = Style::parse_sgr("1;34").unwrap();
+        assert_eq!(style, Style::fg(Color::Basic(4)).bold());
+        let style = Style::parse_sgr("3;38;5;124").unwrap();
+        assert_eq!(style, Style::fg(Color::Palette(124)).italic());
+        let style = Style::parse_sgr("38;2;1;2;3").unwrap();
+        assert_eq!(style, Style::fg(Color::Rgb(1, 2, 3)));
+        assert_eq!(Style::parse_sgr("95;0"), Some(Style::PLAIN));
+        assert_eq!(Style::parse_sgr(""), Some(Style::PLAIN));
+        assert_eq!(Style::parse_sgr("4"), None);
+        assert_eq!(Style::parse_sgr("38;5"), None);
+        assert_eq!(Style::parse_sgr("38;2;1;2;300"), None);
+        assert_eq!(Style::parse_sgr("red"), None);
+    }
+
+    #[test]
+    fn with_overrides_replaces_only_named_styles() {
+        let theme = Theme::DARK.with_overrides("string=32:type=1").unwrap();
+        assert_eq!(theme.string, Style::fg(Color::Basic(2)));
+        assert_eq!(theme.type_, Style::PLAIN.bold());
+        assert_eq!(theme.field, Theme::DARK.field);
+        assert_eq!(Theme::LIGHT.with_overrides(""), Ok(Theme::LIGHT));
+
+        let err = Theme::DARK.with_overrides("string").unwrap_err();
+        assert_eq!(err, "Expected 'name=style', but found 'string'.");
+        let err = Theme::DARK.with_overrides("strings=32").unwrap_err();
+        assert_eq!(err, "Unknown markup 'strings'.");
+        let err = Theme::DARK
+            .with_overrides("diff-add=32:string=x")
+            .unwrap_err();
+        assert_eq!(err, "Invalid style 'x' for 'string'.");
+    }
+
+    #[test]
+    fn locations_of_files_become_hyperlinks() {
+        let uri = location_uri("Cargo.toml:1:1").unwrap();
+        assert!(uri.starts_with("file:///"));
+        assert!(uri.ends_with("/Cargo.toml"));
+        assert_eq!(location_uri("stdin:1:1"), None);
+        assert_eq!(location_uri("Cargo.toml"), None);
+
+        let out = ansi(
+            &Theme::DARK,
+            &[
+                ("stdin", Markup::Link),
+                (":1:1", Markup::Link),
+                ("\n", Markup::None),
+            ],
+        );
+        assert_eq!(out, "stdin:1:1\n");
+        let out = ansi(
+            &Theme::DARK,
+            &[("Cargo.toml", Markup::Link), (":1:1", Markup::Link)],
+        );
+        let expected = format!("\x1b]8;;{uri}\x1b\\Cargo.toml:1:1\x1b]8;;\x1b\\");
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn color_override_follows_environment() {
+        let from = |vars: &[(&str, &str)]| {
+            ColorOverride::from_vars(|name| {
+                vars.iter()
+                    .find(|(k, _)| *k == name)
+                    .map(|(_, v)| v.to_string())
+            })
+        };
+        assert_eq!(from(&[]), ColorOverride::Auto);
+        assert_eq!(from(&[("NO_COLOR", "")]), ColorOverride::Auto);
+        assert_eq!(from(&[("NO_COLOR", "1")]), ColorOverride::Never);
+        assert_eq!(from(&[("CLICOLOR_FORCE", "1")]), ColorOverride::Always);
+        assert_eq!(from(&[("CLICOLOR_FORCE", "0")]), ColorOverride::Auto);
+        assert_eq!(from(&[("FORCE_COLOR", "true")]), ColorOverride::Always);
+        assert_eq!(from(&[("FORCE_COLOR", "")]), ColorOverride::Auto);
+        assert_eq!(
+            from(&[("FORCE_COLOR", "1"), ("NO_COLOR", "1")]),
+            ColorOverride::Never
+        );
+    }
+
+    #[test]
+    fn write_json_tokens_merges_fragments() {
+        let mut s = MarkupString::new();
+        s.push("let", Markup::Keyword);
+        s.push(" ", Markup::None);
+        s.push("\"a", Markup::String);
+        s.push("\"", Markup::String);
+        s.push(";", Markup::None);
+        let mut out = Vec::new();
+        s.write_json_tokens(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            concat!(
+                r#"[{"text": "let", "markup": "keyword", "offset": 0}, "#,
+                r#"{"text": " ", "markup": null, "offset": 3}, "#,
+                r#"{"text": "\"a\"", "markup": "string", "offset": 4}, "#,
+                r#"{"text": ";", "markup": null, "offset": 7}]"#,
+            ),
+        );
+
+        let mut out = Vec::new();
+        MarkupString::new().write_json_tokens(&mut out).unwrap();
+        assert_eq!(out, b"[]");
+    }
+
+    #[test]
+    fn parse_ansi_recovers_markup() {
+        let input =
+            "\x1b]8;;file:///a.rcl\x1b\\a.rcl:1:1\x1b]8;;\x1b\\\n\x1b[31;1mError:\x1b[0m x\x1b[2J";
+        let s = MarkupString::parse_ansi(input);
+        assert_eq!(
+            s.fragments,
+            [
+                ("a.rcl:1:1", Markup::None),
+                ("\n", Markup::None),
+                ("Error:", Markup::Error),
+                (" x", Markup::None),
+            ]
+        );
+
+        // Parameters that we can't represent leave the style unchanged.
+        let s = MarkupString::parse_ansi("\x1b[33;1ma\x1b[4mb\x1b[mc");
+        assert_eq!(
+            s.fragments,
+            [
+                ("a", Markup::Warning),
+                ("b", Markup::Warning),
+                ("c", Markup::None)
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_ansi_roundtrips_write_bytes_ansi() {
+        let fragments = [
+            ("let", Markup::Keyword),
+            (" ", Markup::None),
+            ("x", Markup::Field),
+            (" = ", Markup::None),
+            ("std", Markup::Builtin),
+            (" // ü", Markup::Comment),
+        ];
+        let out = ansi(&Theme::TRUECOLOR, &fragments);
+        let s = MarkupString::parse_ansi_with_theme(&out, &Theme::TRUECOLOR);
+        assert_eq!(s.fragments, fragments);
+    }
+}
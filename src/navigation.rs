@@ -0,0 +1,345 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Resolving names in a document to their definitions, for editor tooling.
+//!
+//! This works on the CST rather than the AST, because the CST preserves the
+//! comments that precede a binding, which we treat as its documentation.
+
+use crate::cst::{Chain, Expr, NonCode, Prefixed, Seq, Stmt, StringPart};
+use crate::source::Span;
+
+/// A place where a variable is bound.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Binding {
+    /// The identifier at the binding site.
+    pub ident: Span,
+
+    /// The line comments directly above the binding, if any.
+    pub comments: Vec<Span>,
+}
+
+impl Binding {
+    /// Return the text of the comments, with the `//` markers removed.
+    pub fn doc_comment(&self, input: &str) -> Option<String> {
+        if self.comments.is_empty() {
+            return None;
+        }
+        let lines: Vec<&str> = self
+            .comments
+            .iter()
+            .map(|span| {
+                let line = span.resolve(input).trim_start_matches('/');
+                line.strip_prefix(' ').unwrap_or(line)
+            })
+            .collect();
+        Some(lines.join("\n"))
+    }
+}
+
+/// What a name under the cursor refers to.
+#[derive(Debug, Eq, PartialEq)]
+pub enum Definition {
+    /// A variable, defined at the given binding.
+    Binding(Binding),
+
+    /// An import of a literal path, relative to the importing document.
+    Import(String),
+}
+
+/// Find the definition of the name at byte offset `offset` in the document.
+///
+/// For a variable, this returns the place where it is bound. For a binding
+/// site, this returns the binding itself. For the path of an import, this
+/// returns the path.
+pub fn find_definition(input: &str, expr: &Expr, offset: usize) -> Option<Definition> {
+    let mut resolver = Resolver {
+        input,
+        offset,
+        scope: Vec::new(),
+        result: None,
+        scope_result: None,
+    };
+    resolver.expr(expr);
+    resolver.result
+}
+
+/// Return the bindings in scope at the variable at byte offset `offset`.
+///
+/// The bindings are ordered from outermost to innermost, and names can occur
+/// multiple times if they are shadowed. Returns `None` if there is no variable
+/// at the offset.
+pub fn find_scope(input: &str, expr: &Expr, offset: usize) -> Option<Vec<Binding>> {
+    let mut resolver = Resolver {
+        input,
+        offset,
+        scope: Vec::new(),
+        result: None,
+        scope_result: None,
+    };
+    resolver.expr(expr);
+    resolver.scope_result
+}
+
+fn contains(span: Span, offset: usize) -> bool {
+    // The end is inclusive, so that a cursor right after an identifier also
+    // selects it.
+    span.start() <= offset && offset <= span.end()
+}
+
+/// Return the line comments at the end of a prefix, up to the last blank line.
+fn get_comments(prefix: &[NonCode]) -> Vec<Span> {
+    let mut comments = Vec::new();
+    for non_code in prefix.iter().rev() {
+        match non_code {
+            NonCode::LineComment(span) => comments.push(*span),
+            NonCode::Blank(..) | NonCode::Shebang(..) => break,
+        }
+    }
+    comments.reverse();
+    comments
+}
+
+struct Resolver<'a> {
+    input: &'a str,
+    offset: usize,
+    /// The bindings in scope, innermost last.
+    scope: Vec<Binding>,
+    result: Option<Definition>,
+    /// The scope at the variable at the offset, if there is one.
+    scope_result: Option<Vec<Binding>>,
+}
+
+impl<'a> Resolver<'a> {
+    fn bind(&mut self, ident: Span, comments: Vec<Span>) {
+        if self.result.is_none() && contains(ident, self.offset) {
+            let binding = Binding {
+                ident,
+                comments: comments.clone(),
+            };
+            self.result = Some(Definition::Binding(binding));
+        }
+        self.scope.push(Binding { ident, comments });
+    }
+
+    fn lookup(&mut self, var: Span) {
+        if self.result.is_some() || !contains(var, self.offset) {
+            return;
+        }
+        self.scope_result = Some(self.scope.clone());
+        let name = var.resolve(self.input);
+        let binding = self
+            .scope
+            .iter()
+            .rev()
+            .find(|b| b.ident.resolve(self.input) == name);
+        if let Some(binding) = binding {
+            self.result = Some(Definition::Binding(binding.clone()));
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt, prefix: &[NonCode]) {
+        match stmt {
+            Stmt::Let { ident, value, .. } => {
+                // The binding is not in scope in its own value.
+                self.expr(value);
+                self.bind(*ident, get_comments(prefix));
+            }
+            Stmt::Assert {
+                condition, message, ..
+            } => {
+                self.expr(condition);
+                self.expr(message);
+            }
+            Stmt::Trace { message, .. } => self.expr(message),
+        }
+    }
+
+    fn import(&mut self, path_span: Span, path: &Expr) {
+        if self.result.is_some() || !contains(path_span, self.offset) {
+            return;
+        }
+        // We can only follow imports of string literals without escapes or
+        // holes, which matches how imports are written in practice.
+        if let Expr::StringLit { parts, .. } = path {
+            let mut result = String::new();
+            for part in parts {
+                match part {
+                    StringPart::String(span) => result.push_str(span.resolve(self.input)),
+                    _ => return,
+                }
+            }
+            self.result = Some(Definition::Import(result));
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        if self.result.is_some() {
+            return;
+        }
+        match expr {
+            Expr::Statements { stmts, body, .. } => {
+                let n = self.scope.len();
+                for (_span, stmt) in stmts {
+                    self.stmt(&stmt.inner, &stmt.prefix);
+                }
+                self.expr(&body.inner);
+                self.scope.truncate(n);
+            }
+            Expr::Import { path_span, path } => {
+                self.import(*path_span, path);
+                self.expr(path);
+            }
+            Expr::BraceLit { elements, .. } | Expr::BracketLit { elements, .. } => {
+                for elem in elements.elements.iter() {
+                    self.seq(elem);
+                }
+            }
+            Expr::Parens { body, .. } => self.expr(body),
+            Expr::NullLit(..)
+            | Expr::BoolLit(..)
+            | Expr::NumHexadecimal(..)
+            | Expr::NumBinary(..)
+            | Expr::NumDecimal(..) => {}
+            Expr::StringLit { parts, .. } => {
+                for part in parts {
+                    if let StringPart::Hole(_span, hole) = part {
+                        self.expr(hole);
+                    }
+                }
+            }
+            Expr::Var(span) => self.lookup(*span),
+            Expr::IfThenElse {
+                condition,
+                then_body,
+                else_body,
+                ..
+            } => {
+                self.expr(condition);
+                self.expr(then_body);
+                self.expr(else_body);
+            }
+            Expr::Function { args, body, .. } => {
+                let n = self.scope.len();
+                for arg in args.elements.iter() {
+                    self.bind(arg.inner, get_comments(&arg.prefix));
+                }
+                self.expr(body);
+                self.scope.truncate(n);
+            }
+            Expr::UnOp { body, .. } => self.expr(body),
+            Expr::BinOp { lhs, rhs, .. } => {
+                self.expr(lhs);
+                self.expr(rhs);
+            }
+            Expr::Chain { base_expr, chain } => {
+                self.expr(base_expr);
+                for (_span, link) in chain {
+                    match link {
+                        Chain::Field { .. } => {}
+                        Chain::Call { args, .. } => {
+                            for (_span, arg) in args.elements.iter() {
+                                self.expr(arg);
+                            }
+                        }
+                        Chain::Index { index, .. } => self.expr(index),
+                    }
+                }
+            }
+        }
+    }
+
+    fn seq(&mut self, seq: &Prefixed<Seq>) {
+        if self.result.is_some() {
+            return;
+        }
+        match &seq.inner {
+            Seq::Elem { value, .. } => self.expr(value),
+            Seq::AssocExpr { field, value, .. } => {
+                self.expr(field);
+                self.expr(value);
+            }
+            Seq::AssocIdent { value, .. } => self.expr(value),
+            Seq::Stmt { stmt, body, .. } => {
+                let n = self.scope.len();
+                self.stmt(stmt, &seq.prefix);
+                self.seq(body);
+                self.scope.truncate(n);
+            }
+            Seq::For {
+                idents,
+                collection,
+                body,
+                ..
+            } => {
+                self.expr(collection);
+                let n = self.scope.len();
+                for ident in idents.iter() {
+                    self.bind(*ident, Vec::new());
+                }
+                self.seq(body);
+                self.scope.truncate(n);
+            }
+            Seq::If {
+                condition, body, ..
+            } => {
+                self.expr(condition);
+                self.seq(body);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{find_definition, find_scope, Definition};
+    use crate::source::DocId;
+    use crate::{lexer, parser};
+
+    fn definition_at(input: &str, needle: &str) -> Option<Definition> {
+        let tokens = lexer::lex(DocId(0), input).unwrap();
+        let (_span, cst) = parser::parse(DocId(0), input, &tokens).unwrap();
+        let offset = input.find(needle).unwrap();
+        find_definition(input, &cst, offset)
+    }
+
+    #[test]
+    fn find_definition_resolves_shadowed_bindings() {
+        let input = "// The answer.\nlet x = 1;\nlet y = [for x in [x]: x + 1];\ny";
+        // The loop variable is not in scope in the collection.
+        let Some(Definition::Binding(b)) = definition_at(input, "x]") else {
+            panic!("Expected a binding.");
+        };
+        assert_eq!(b.ident.start(), input.find("x =").unwrap());
+        assert_eq!(b.doc_comment(input).as_deref(), Some("The answer."));
+
+        let Some(Definition::Binding(b)) = definition_at(input, "x + 1") else {
+            panic!("Expected a binding.");
+        };
+        assert_eq!(b.ident.start(), input.find("x in").unwrap());
+        assert_eq!(b.doc_comment(input), None);
+    }
+
+    #[test]
+    fn find_scope_returns_bindings_in_scope() {
+        let input = "let a = 1; let f = b => [for c in [b]: z]; a";
+        let tokens = lexer::lex(DocId(0), input).unwrap();
+        let (_span, cst) = parser::parse(DocId(0), input, &tokens).unwrap();
+        let scope = find_scope(input, &cst, input.find('z').unwrap()).unwrap();
+        let names: Vec<&str> = scope.iter().map(|b| b.ident.resolve(input)).collect();
+        assert_eq!(names, ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn find_definition_resolves_imports() {
+        let input = "let a = import \"a.rcl\"; a";
+        assert_eq!(
+            definition_at(input, "a.rcl"),
+            Some(Definition::Import("a.rcl".to_string())),
+        );
+    }
+}
@@ -0,0 +1,185 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Piping long output through a pager, like Git does.
+//!
+//! When stdout is a terminal and the output does not fit on the screen, we
+//! start the user's pager and write the output to its stdin instead of to
+//! stdout. The pager is `$RCL_PAGER`, or else `$PAGER`, or else `less`. Like
+//! Git, we set `LESS=FRX` when it is not set already, so `less` passes through
+//! ANSI escape codes, and exits right away when the output fits after all.
+
+use std::io::IsTerminal;
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+use unicode_width::UnicodeWidthStr;
+
+use crate::markup::MarkupString;
+
+/// Return the pager command to use, or `None` if paging is disabled.
+///
+/// Setting the pager to the empty string or to `cat` disables paging.
+pub fn get_pager_command() -> Option<String> {
+    let cmd = std::env::var("RCL_PAGER")
+        .or_else(|_| std::env::var("PAGER"))
+        .unwrap_or_else(|_| "less".to_string());
+    let cmd = cmd.trim();
+    match cmd {
+        "" | "cat" => None,
+        _ => Some(cmd.to_string()),
+    }
+}
+
+/// Return the size of the terminal as (rows, columns), if we can find out.
+///
+/// We ask `stty` rather than calling `ioctl` directly, to avoid unsafe code.
+/// The `LINES` and `COLUMNS` environment variables take precedence.
+pub fn get_terminal_size() -> Option<(usize, usize)> {
+    let from_env = |name: &str| std::env::var(name).ok().and_then(|v| v.parse().ok());
+    if let (Some(rows), Some(cols)) = (from_env("LINES"), from_env("COLUMNS")) {
+        return Some((rows, cols));
+    }
+    get_terminal_size_stty()
+}
+
+#[cfg(unix)]
+fn get_terminal_size_stty() -> Option<(usize, usize)> {
+    // Stdin may be redirected, so point `stty` at the controlling terminal.
+    let tty = std::fs::File::open("/dev/tty").ok()?;
+    let output = Command::new("stty")
+        .arg("size")
+        .stdin(tty)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let output = String::from_utf8(output.stdout).ok()?;
+    let (rows, cols) = output.trim().split_once(' ')?;
+    Some((rows.parse().ok()?, cols.parse().ok()?))
+}
+
+#[cfg(not(unix))]
+fn get_terminal_size_stty() -> Option<(usize, usize)> {
+    None
+}
+
+/// Return how many rows the text takes up on a terminal `width` columns wide.
+///
+/// Lines that are wider than the terminal wrap and take up multiple rows.
+pub fn count_rows(text: &MarkupString, width: usize) -> usize {
+    let mut rows = 0;
+    let mut line_width = 0;
+    let line_rows = |line_width: usize| 1 + line_width.saturating_sub(1) / width.max(1);
+    for (fragment, _markup) in text.fragments.iter() {
+        let mut lines = fragment.split('\n');
+        if let Some(first) = lines.next() {
+            line_width += first.width();
+        }
+        for line in lines {
+            rows += line_rows(line_width);
+            line_width = line.width();
+        }
+    }
+    if line_width > 0 {
+        rows += line_rows(line_width);
+    }
+    rows
+}
+
+/// A running pager process that we write output to.
+pub struct Pager {
+    child: Child,
+}
+
+impl Pager {
+    /// Start the pager if stdout is a terminal and the text does not fit on it.
+    ///
+    /// Returns `None` when the output should go to stdout directly, also when
+    /// the pager fails to start.
+    pub fn for_output(text: &MarkupString) -> Option<Pager> {
+        if !std::io::stdout().is_terminal() {
+            return None;
+        }
+        let cmd = get_pager_command()?;
+        // If we can't tell the terminal size, we rely on the pager to exit
+        // when the output fits, which `less` does with `LESS=F`.
+        if let Some((rows, cols)) = get_terminal_size() {
+            // Leave one row for the prompt that follows the output.
+            if count_rows(text, cols) < rows {
+                return None;
+            }
+        }
+        Pager::spawn(&cmd)
+    }
+
+    fn spawn(cmd: &str) -> Option<Pager> {
+        let mut command = if cfg!(unix) {
+            // Like Git, run the pager through the shell, so it can have arguments.
+            let mut command = Command::new("sh");
+            command.arg("-c").arg(cmd);
+            command
+        } else {
+            let mut parts = cmd.split_whitespace();
+            let mut command = Command::new(parts.next()?);
+            command.args(parts);
+            command
+        };
+        if std::env::var_os("LESS").is_none() {
+            command.env("LESS", "FRX");
+        }
+        if std::env::var_os("LV").is_none() {
+            command.env("LV", "-c");
+        }
+        let child = command.stdin(Stdio::piped()).spawn().ok()?;
+        Some(Pager { child })
+    }
+
+    /// The stdin of the pager, to write the output to.
+    pub fn stdin(&mut self) -> &mut ChildStdin {
+        self.child
+            .stdin
+            .as_mut()
+            .expect("We spawn the pager with piped stdin.")
+    }
+
+    /// Close the pager's stdin, and wait for the user to exit the pager.
+    pub fn wait(mut self) {
+        drop(self.child.stdin.take());
+        // If the pager failed, there is nothing we can do, the output was
+        // handed off already.
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::count_rows;
+    use crate::markup::{Markup, MarkupString};
+
+    fn rows(fragments: &[&'static str], width: usize) -> usize {
+        let mut text = MarkupString::new();
+        for fragment in fragments {
+            text.push(fragment, Markup::None);
+        }
+        count_rows(&text, width)
+    }
+
+    #[test]
+    fn count_rows_counts_lines() {
+        assert_eq!(rows(&[], 80), 0);
+        assert_eq!(rows(&["a\n"], 80), 1);
+        assert_eq!(rows(&["a\n", "\n", "b"], 80), 3);
+        assert_eq!(rows(&["a", "b\nc", "d\n"], 80), 2);
+    }
+
+    #[test]
+    fn count_rows_counts_wrapped_lines() {
+        assert_eq!(rows(&["abcd\n"], 4), 1);
+        assert_eq!(rows(&["abcde\n"], 4), 2);
+        assert_eq!(rows(&["ab", "cdefghi\n"], 4), 3);
+        assert_eq!(rows(&["日本語\n"], 4), 2);
+    }
+}
@@ -0,0 +1,98 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Spreading independent pieces of work over threads, for `--jobs`.
+//!
+//! Runtime values are reference counted with [`Rc`](std::rc::Rc), so they
+//! cannot cross threads, and evaluation happens on the main thread. What can
+//! run in parallel is the work that only needs strings or documents, such as
+//! formatting source files, and pretty-printing build outputs.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The number of threads to use when `--jobs` is not specified.
+///
+/// This is the number of logical CPUs, or 1 if we can't find out.
+pub fn default_jobs() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
+
+/// Apply `f` to every item, on at most `jobs` threads.
+///
+/// The results are in the same order as the items, regardless of the order in
+/// which the threads finish. With one job, or one item, this does not spawn
+/// any threads, so `--jobs=1` runs everything in order on the main thread.
+pub fn map<T, R, F>(jobs: usize, items: &[T], f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let n_threads = jobs.min(items.len());
+    if n_threads <= 1 {
+        return items.iter().map(f).collect();
+    }
+
+    // Rather than splitting the items into equal chunks up front, the threads
+    // take the next item from a shared counter, so that a few large items do
+    // not leave the other threads idle.
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<Option<R>> = items.iter().map(|_| None).collect();
+
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..n_threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut out = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(item) = items.get(i) else {
+                            break;
+                        };
+                        out.push((i, f(item)));
+                    }
+                    out
+                })
+            })
+            .collect();
+
+        for worker in workers {
+            let out = match worker.join() {
+                Ok(out) => out,
+                // coverage:off -- The work is not expected to panic.
+                Err(panic) => std::panic::resume_unwind(panic),
+                // coverage:on
+            };
+            for (i, result) in out {
+                results[i] = Some(result);
+            }
+        }
+    });
+
+    results
+        .into_iter()
+        .map(|r| r.expect("Every item is taken by exactly one thread."))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::map;
+
+    #[test]
+    fn map_preserves_order() {
+        let items: Vec<u32> = (0..100).collect();
+        for jobs in [1, 2, 7, 200] {
+            let squares = map(jobs, &items, |x| x * x);
+            let expected: Vec<u32> = items.iter().map(|x| x * x).collect();
+            assert_eq!(squares, expected);
+        }
+        assert_eq!(map(4, &[] as &[u32], |x| *x), Vec::<u32>::new());
+    }
+}
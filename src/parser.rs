@@ -26,7 +26,7 @@ pub fn parse(doc: DocId, input: &str, tokens: &[Lexeme]) -> Result<(Span, Expr)>
     Ok((span, result))
 }
 
-fn to_unop(token: Token) -> Option<UnOp> {
+pub(crate) fn to_unop(token: Token) -> Option<UnOp> {
     match token {
         Token::KwNot => Some(UnOp::Not),
         Token::Minus => Some(UnOp::Neg),
@@ -34,7 +34,7 @@ fn to_unop(token: Token) -> Option<UnOp> {
     }
 }
 
-fn to_binop(token: Token) -> Option<BinOp> {
+pub(crate) fn to_binop(token: Token) -> Option<BinOp> {
     match token {
         Token::KwAnd => Some(BinOp::And),
         Token::KwOr => Some(BinOp::Or),
@@ -715,7 +715,16 @@ impl<'a> Parser<'a> {
                 Some(op) if allowed_op.is_none() || allowed_op == Some(op) => {
                     let span = self.consume();
                     self.skip_non_code()?;
-                    let (rhs_span, rhs) = self.parse_expr_not_op()?;
+                    self.check_bad_unop()?;
+                    // A unary operator is allowed on the right-hand side, as
+                    // in `a - -1` or `a and not b`. It is unambiguous there,
+                    // because `parse_expr_unop` rejects any binary operator
+                    // after its body.
+                    let (rhs_span, rhs) = if to_unop(self.peek()).is_some() {
+                        self.parse_expr_unop()?
+                    } else {
+                        self.parse_expr_not_op()?
+                    };
                     allowed_span = Some(span);
                     allowed_op = Some(op);
                     result = Expr::BinOp {
@@ -15,6 +15,29 @@
 //! the newline.
 //!
 //! [wadler2003]: https://homepages.inf.ed.ac.uk/wadler/papers/prettier/prettier.pdf
+//!
+//! The module is public, so other tools can reuse the printer. A document is
+//! built from [`Doc`] nodes, most conveniently with the [`concat!`], [`group!`],
+//! and [`indent!`] macros, and then printed with [`Doc::println`]:
+//!
+//! ```
+//! use rcl::pprint::{group, indent, Config, Doc};
+//!
+//! let doc = group! {
+//!     "["
+//!     Doc::SoftBreak
+//!     indent! { "elem0," Doc::Sep "elem1" Doc::tall(",") }
+//!     Doc::SoftBreak
+//!     "]"
+//! };
+//! let wide = Config { width: 80, ..Config::default() };
+//! let tall = Config { width: 10, ..Config::default() };
+//! assert_eq!(doc.println(&wide).to_string_no_markup(), "[elem0, elem1]\n");
+//! assert_eq!(doc.println(&tall).to_string_no_markup(), "[\n  elem0,\n  elem1,\n]\n");
+//! ```
+
+use std::collections::HashMap;
+use std::io::{self, Write};
 
 use crate::markup::{Markup, MarkupString};
 use crate::pprint::printer::{PrintResult, Printer};
@@ -26,11 +49,170 @@ enum Mode {
     Tall,
 }
 
+/// The width of a document when printed in wide mode, see [`Doc::measure_wide`].
+#[derive(Copy, Clone, Debug)]
+struct WideWidth {
+    /// The number of columns that the content spans.
+    width: u32,
+
+    /// Whether anything gets printed at all.
+    ///
+    /// When a line starts with content, it is preceded by indentation, so
+    /// this affects the width of the line even if the content has width zero.
+    has_content: bool,
+}
+
+impl WideWidth {
+    const EMPTY: WideWidth = WideWidth {
+        width: 0,
+        has_content: false,
+    };
+}
+
+/// The measured wide width of every group node, keyed by the node's address.
+type WideWidths<'a> = HashMap<*const Doc<'a>, Option<WideWidth>>;
+
+/// A position in the printed output.
+///
+/// Lines and columns are counted from zero. Columns are counted in the same way
+/// that the printer measures the line width, so a tab in the indentation counts
+/// as the tab width.
+#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct OutputPosition {
+    pub line: u32,
+    pub column: u32,
+}
+
+/// The range in the output that the content of a [`Doc::Markup`] node occupied.
+///
+/// The end is exclusive. See [`Doc::println_with_markup_ranges`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct MarkupRange {
+    pub markup: Markup,
+    pub start: OutputPosition,
+    pub end: OutputPosition,
+}
+
+/// Statistics about the printed form of a document, see [`Doc::measure`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct DocMetrics {
+    /// The number of lines in the output.
+    pub lines: u32,
+
+    /// The width of the widest line, in columns.
+    pub max_width: u32,
+
+    /// Whether any line exceeds the target width.
+    ///
+    /// This happens when a group does not fit even in tall mode, for example
+    /// because it contains a long string.
+    pub overflows: bool,
+}
+
+/// How to end lines in the output.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum LineEnding {
+    /// End lines with `\n`, as is common on Unix-like systems.
+    #[default]
+    Lf,
+    /// End lines with `\r\n`, as is common on Windows.
+    CrLf,
+}
+
+/// What to indent with.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum IndentStyle {
+    /// Indent with `indent_width` spaces per level.
+    #[default]
+    Spaces,
+    /// Indent with one tab per level, that counts as `tab_width` columns.
+    Tabs,
+}
+
+/// How wide to count characters whose East Asian Width is ambiguous.
+///
+/// Characters such as `±` and `…` take one column in most terminals, but two
+/// columns in terminals that use a CJK font.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum AmbiguousWidth {
+    /// Count ambiguous characters as one column.
+    #[default]
+    Narrow,
+    /// Count ambiguous characters as two columns.
+    Wide,
+}
+
+impl AmbiguousWidth {
+    /// Return the width of `s` in columns.
+    pub fn str_width(self, s: &str) -> u32 {
+        use unicode_width::UnicodeWidthStr;
+        match self {
+            AmbiguousWidth::Narrow => s.width() as u32,
+            AmbiguousWidth::Wide => s.width_cjk() as u32,
+        }
+    }
+
+    /// Return the width of `s`, given its `width` when counted as narrow.
+    fn adjust_width(self, s: &str, width: u32) -> u32 {
+        match self {
+            AmbiguousWidth::Narrow => width,
+            AmbiguousWidth::Wide => self.str_width(s),
+        }
+    }
+
+    /// Return the width of `ch` in columns, zero for control characters.
+    pub fn char_width(self, ch: char) -> u32 {
+        use unicode_width::UnicodeWidthChar;
+        let width = match self {
+            AmbiguousWidth::Narrow => ch.width(),
+            AmbiguousWidth::Wide => ch.width_cjk(),
+        };
+        width.unwrap_or(0) as u32
+    }
+}
+
 /// Configuration for the pretty-printer.
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Config {
     /// The pretty printer will try to avoid creating lines longer than `width`
     /// columns, but this is not always possible.
     pub width: u32,
+
+    /// The number of spaces to indent by for every level of indentation.
+    ///
+    /// This only applies when the indent style is [`IndentStyle::Spaces`].
+    pub indent_width: u32,
+
+    /// Whether to indent with spaces or with tabs.
+    pub indent_style: IndentStyle,
+
+    /// The number of columns that a tab counts as, to measure the line width.
+    ///
+    /// This only applies when the indent style is [`IndentStyle::Tabs`].
+    pub tab_width: u32,
+
+    /// The line ending to emit at the end of every line.
+    pub line_ending: LineEnding,
+
+    /// How wide to count characters with an ambiguous width.
+    ///
+    /// The width of [`Doc::Str`] and [`Doc::String`] nodes is computed when
+    /// they are constructed, assuming narrow. When this is wide, the printer
+    /// recomputes the width of all content.
+    pub ambiguous_width: AmbiguousWidth,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            width: 80,
+            indent_width: 2,
+            indent_style: IndentStyle::Spaces,
+            tab_width: 4,
+            line_ending: LineEnding::Lf,
+            ambiguous_width: AmbiguousWidth::Narrow,
+        }
+    }
 }
 
 /// A document tree that can be pretty-printed.
@@ -127,6 +309,14 @@ pub enum Doc<'a> {
     /// A group can be formatted either in wide mode or in tall mode.
     Group(Box<Doc<'a>>),
 
+    /// A sequence of elements that are packed onto as few lines as possible.
+    ///
+    /// In wide mode, the elements are separated by a space. In tall mode, every
+    /// element goes on the current line, after a space, if it fits there in wide
+    /// mode, and on a new line otherwise. Every element behaves like a group.
+    /// This is like the `fill` combinator in Wadler's paper.
+    Fill(Vec<Doc<'a>>),
+
     /// An indented block.
     Indent(Box<Doc<'a>>),
 
@@ -141,8 +331,48 @@ pub enum Doc<'a> {
     /// In wide mode, this is a no-op.
     FlushIndent(Box<Doc<'a>>),
 
+    /// A block whose lines are indented up to the column where it starts.
+    ///
+    /// Unlike [`Doc::Indent`], which indents by a fixed width, this can be
+    /// used to make continuation lines hang under an opening token, such as
+    /// the `(` of a call. Nested indentation adds to the aligned column.
+    ///
+    /// In wide mode, this is a no-op.
+    Align(Box<Doc<'a>>),
+
     /// Apply markup to the inner document.
     Markup(Markup, Box<Doc<'a>>),
+
+    /// Prose that is word-wrapped to the target width in tall mode.
+    ///
+    /// Every line starts with the prefix, for example `// ` for a comment,
+    /// followed by as many words as fit, separated by spaces. A word that is
+    /// too wide by itself goes on a line of its own. In wide mode, all words go
+    /// on one line. Construct this with [`Doc::paragraph`].
+    Paragraph {
+        prefix: Box<Doc<'a>>,
+        words: Vec<Doc<'a>>,
+    },
+
+    /// Print the inner document with a different target width.
+    ///
+    /// Groups inside decide between wide and tall against this width instead
+    /// of the configured one. With `u32::MAX`, the content is never wrapped.
+    WithWidth(u32, Box<Doc<'a>>),
+
+    /// Content whose trailing spaces are preserved at the end of a line.
+    ///
+    /// The printer removes spaces at the end of a line, such that separators
+    /// like the space in `"= "` don't linger when a line break follows. Spaces
+    /// that may be significant, such as in a string literal or in a comment,
+    /// should be wrapped in this node.
+    NoTrim(Box<Doc<'a>>),
+
+    /// Content whose lines are indented with spaces, even when indenting with tabs.
+    ///
+    /// This is for content where the leading spaces are significant, such as
+    /// the lines of a `"""`-string, which strip the spaces that they share.
+    SpaceIndent(Box<Doc<'a>>),
 }
 
 impl<'a> Doc<'a> {
@@ -257,10 +487,74 @@ impl<'a> Doc<'a> {
         Doc::Concat(result)
     }
 
+    /// Construct a [`Doc::Paragraph`] of the whitespace-separated words in `text`.
+    pub fn paragraph(prefix: &'a str, text: &'a str) -> Doc<'a> {
+        Doc::Paragraph {
+            prefix: Box::new(prefix.into()),
+            words: text.split_whitespace().map(Doc::str).collect(),
+        }
+    }
+
+    /// Wrap the document in a [`Doc::Markup`] node.
     pub fn with_markup(self, markup: Markup) -> Doc<'a> {
         Doc::Markup(markup, Box::new(self))
     }
 
+    /// Render the structure of the document tree as an s-expression.
+    ///
+    /// This shows the groups, indents, and breaks that are otherwise invisible
+    /// in the printed output, which helps to debug why a group does not get
+    /// formatted the way you expect.
+    pub fn to_sexpr(&self) -> String {
+        self.sexpr()
+            .println(&Config::default())
+            .to_string_no_markup()
+    }
+
+    /// Build the document for [`Doc::to_sexpr`].
+    fn sexpr(&self) -> Doc<'static> {
+        let node = |head: &'static str, children: Vec<Doc<'static>>| {
+            let mut inner = Vec::with_capacity(children.len() * 2);
+            for child in children {
+                inner.push(Doc::Sep);
+                inner.push(child);
+            }
+            group! { "(" head indent! { Doc::Concat(inner) } ")" }
+        };
+        let quote = |content: &str| Doc::string(format!("{content:?}"));
+        match self {
+            Doc::Empty => Doc::str("empty"),
+            Doc::Str { content, .. } => quote(content),
+            Doc::String { content, .. } => quote(content),
+            Doc::WhenTall { content, .. } => node("tall", vec![quote(content)]),
+            Doc::Sep => Doc::str("sep"),
+            Doc::SoftBreak => Doc::str("soft-break"),
+            Doc::HardBreak => Doc::str("hard-break"),
+            Doc::RawBreak => Doc::str("raw-break"),
+            Doc::Concat(children) => node("concat", children.iter().map(Doc::sexpr).collect()),
+            Doc::Group(inner) => node("group", vec![inner.sexpr()]),
+            Doc::Fill(elements) => node("fill", elements.iter().map(Doc::sexpr).collect()),
+            Doc::Paragraph { prefix, words } => {
+                let mut children = vec![prefix.sexpr()];
+                children.extend(words.iter().map(Doc::sexpr));
+                node("paragraph", children)
+            }
+            Doc::Indent(inner) => node("indent", vec![inner.sexpr()]),
+            Doc::FlushIndent(inner) => node("flush-indent", vec![inner.sexpr()]),
+            Doc::Align(inner) => node("align", vec![inner.sexpr()]),
+            Doc::Markup(markup, inner) => node(
+                "markup",
+                vec![Doc::string(format!("{markup:?}")), inner.sexpr()],
+            ),
+            Doc::WithWidth(width, inner) => node(
+                "with-width",
+                vec![Doc::string(width.to_string()), inner.sexpr()],
+            ),
+            Doc::NoTrim(inner) => node("no-trim", vec![inner.sexpr()]),
+            Doc::SpaceIndent(inner) => node("space-indent", vec![inner.sexpr()]),
+        }
+    }
+
     /// Clone all strings and make them owned.
     pub fn into_owned(self) -> Doc<'static> {
         match self {
@@ -279,30 +573,169 @@ impl<'a> Doc<'a> {
                 Doc::Concat(children.into_iter().map(|c| c.into_owned()).collect())
             }
             Doc::Group(inner) => Doc::Group(Box::new(inner.into_owned())),
+            Doc::Fill(elements) => {
+                Doc::Fill(elements.into_iter().map(|e| e.into_owned()).collect())
+            }
             Doc::Indent(inner) => Doc::Indent(Box::new(inner.into_owned())),
             Doc::FlushIndent(inner) => Doc::FlushIndent(Box::new(inner.into_owned())),
+            Doc::Align(inner) => Doc::Align(Box::new(inner.into_owned())),
             Doc::Markup(m, inner) => Doc::Markup(m, Box::new(inner.into_owned())),
+            Doc::Paragraph { prefix, words } => Doc::Paragraph {
+                prefix: Box::new(prefix.into_owned()),
+                words: words.into_iter().map(|w| w.into_owned()).collect(),
+            },
+            Doc::WithWidth(width, inner) => Doc::WithWidth(width, Box::new(inner.into_owned())),
+            Doc::NoTrim(inner) => Doc::NoTrim(Box::new(inner.into_owned())),
+            Doc::SpaceIndent(inner) => Doc::SpaceIndent(Box::new(inner.into_owned())),
         }
     }
 
-    /// Whether any of the nodes in this tree force tall mode.
+    /// Measure how wide the document is when printed in wide mode.
     ///
-    /// A hard break forces tall mode.
-    fn is_forced_tall(&self) -> bool {
+    /// Returns `None` when the document contains a hard break, which forces
+    /// tall mode. The result for every group in the tree is recorded in
+    /// `groups`, so the printer can decide between wide and tall for a group
+    /// without printing it speculatively. Without this, deciding for nested
+    /// groups would re-render the same subtrees for every level of nesting.
+    fn measure_wide(
+        &'a self,
+        groups: &mut WideWidths<'a>,
+        ambiguous: AmbiguousWidth,
+    ) -> Option<WideWidth> {
+        let content = |width| {
+            Some(WideWidth {
+                width,
+                has_content: true,
+            })
+        };
         match self {
-            Doc::HardBreak => true,
-            Doc::RawBreak => true,
-            Doc::Concat(children) => children.iter().any(|node| node.is_forced_tall()),
-            Doc::Group(inner) => inner.is_forced_tall(),
-            Doc::Indent(inner) => inner.is_forced_tall(),
-            Doc::FlushIndent(inner) => inner.is_forced_tall(),
-            Doc::Markup(_, inner) => inner.is_forced_tall(),
-            _ => false,
+            Doc::Empty => Some(WideWidth::EMPTY),
+            Doc::Str { content: s, width } => content(ambiguous.adjust_width(s, *width)),
+            Doc::String { content: s, width } => content(ambiguous.adjust_width(s, *width)),
+            Doc::WhenTall { .. } => Some(WideWidth::EMPTY),
+            Doc::Sep => content(1),
+            Doc::SoftBreak => Some(WideWidth::EMPTY),
+            Doc::HardBreak => None,
+            Doc::RawBreak => None,
+            Doc::Concat(children) => {
+                // Measure all children, also after a hard break, because the
+                // groups inside them need to be recorded too.
+                let mut result = Some(WideWidth::EMPTY);
+                for child in children.iter() {
+                    let child_width = child.measure_wide(groups, ambiguous);
+                    result = match (result, child_width) {
+                        (Some(a), Some(b)) => Some(WideWidth {
+                            width: a.width.saturating_add(b.width),
+                            has_content: a.has_content || b.has_content,
+                        }),
+                        _ => None,
+                    };
+                }
+                result
+            }
+            Doc::Group(inner) => {
+                let result = inner.measure_wide(groups, ambiguous);
+                groups.insert(self, result);
+                result
+            }
+            Doc::Fill(elements) => Doc::measure_fill(elements, groups, ambiguous),
+            Doc::Paragraph { prefix, words } => {
+                let prefix_width = prefix.measure_wide(groups, ambiguous);
+                let words_width = Doc::measure_fill(words, groups, ambiguous);
+                match (prefix_width, words_width) {
+                    (Some(a), Some(b)) => Some(WideWidth {
+                        width: a.width.saturating_add(b.width),
+                        has_content: a.has_content || b.has_content,
+                    }),
+                    _ => None,
+                }
+            }
+            Doc::Indent(inner) => inner.measure_wide(groups, ambiguous),
+            Doc::FlushIndent(inner) => inner.measure_wide(groups, ambiguous),
+            Doc::Align(inner) => inner.measure_wide(groups, ambiguous),
+            Doc::Markup(_, inner) => inner.measure_wide(groups, ambiguous),
+            Doc::WithWidth(_, inner) => inner.measure_wide(groups, ambiguous),
+            Doc::NoTrim(inner) => inner.measure_wide(groups, ambiguous),
+            Doc::SpaceIndent(inner) => inner.measure_wide(groups, ambiguous),
+        }
+    }
+
+    /// Measure the elements of a [`Doc::Fill`], see [`Doc::measure_wide`].
+    fn measure_fill(
+        elements: &'a [Doc<'a>],
+        groups: &mut WideWidths<'a>,
+        ambiguous: AmbiguousWidth,
+    ) -> Option<WideWidth> {
+        // Every element behaves like a group, so we record it the same.
+        let mut result = Some(WideWidth::EMPTY);
+        for (i, element) in elements.iter().enumerate() {
+            let element_width = element.measure_wide(groups, ambiguous);
+            groups.insert(element, element_width);
+            let sep_width = if i > 0 { 1 } else { 0 };
+            result = match (result, element_width) {
+                (Some(a), Some(b)) => Some(WideWidth {
+                    width: a.width.saturating_add(sep_width).saturating_add(b.width),
+                    has_content: a.has_content || b.has_content || sep_width > 0,
+                }),
+                _ => None,
+            };
+        }
+        result
+    }
+
+    /// Print the elements of a [`Doc::Fill`] or the words of a [`Doc::Paragraph`].
+    ///
+    /// When there is a line prefix, it is printed at the start of every line.
+    fn print_fill(
+        elements: &'a [Doc<'a>],
+        line_prefix: Option<&'a Doc<'a>>,
+        printer: &mut Printer<'a, '_>,
+        mode: Mode,
+    ) -> PrintResult {
+        let mut result = PrintResult::Fits;
+        if let Some(prefix) = line_prefix {
+            result = prefix.print_to(printer, Mode::Wide);
+        }
+        match mode {
+            Mode::Wide => {
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 && printer.push_str(" ", 1).is_overflow() {
+                        return PrintResult::Overflow;
+                    }
+                    if element.print_to(printer, Mode::Wide).is_overflow() {
+                        return PrintResult::Overflow;
+                    }
+                }
+                result
+            }
+            Mode::Tall => {
+                for (i, element) in elements.iter().enumerate() {
+                    // Put the element after a space on the current line if
+                    // it fits there, otherwise start a new line for it.
+                    if i > 0 {
+                        if printer.fits_wide(element, 1) {
+                            printer.push_str(" ", 1);
+                            element.print_to(printer, Mode::Wide);
+                            continue;
+                        }
+                        printer.newline();
+                        if let Some(prefix) = line_prefix {
+                            result = prefix.print_to(printer, Mode::Wide).max(result);
+                        }
+                    }
+                    let element_mode = match printer.fits_wide(element, 0) {
+                        true => Mode::Wide,
+                        false => Mode::Tall,
+                    };
+                    result = element.print_to(printer, element_mode).max(result);
+                }
+                result
+            }
         }
     }
 
     /// Print the document to the given printer.
-    fn print_to(&'a self, printer: &mut Printer<'a>, mode: Mode) -> PrintResult {
+    fn print_to(&'a self, printer: &mut Printer<'a, '_>, mode: Mode) -> PrintResult {
         match self {
             Doc::Empty => PrintResult::Fits,
             Doc::Str { content, width } => printer.push_str(content, *width),
@@ -347,25 +780,22 @@ impl<'a> Doc<'a> {
                     result
                 }
             },
-            Doc::Group(inner) => {
-                if inner.is_forced_tall() {
-                    debug_assert!(matches!(mode, Mode::Tall));
-                    return inner.print_to(printer, mode);
-                }
+            Doc::Group(inner) => match mode {
+                // If we are wide, then the inner content must be wide too.
+                Mode::Wide => inner.print_to(printer, mode),
 
-                match mode {
-                    // If we are wide, then the inner content must be wide too.
-                    Mode::Wide => inner.print_to(printer, mode),
-
-                    // If we are tall, then we can try to make the inner content
-                    // wide. If that is too wide, then we backtrack and try to
-                    // make it tall instead.
-                    Mode::Tall => match printer.try_(|p| inner.print_to(p, Mode::Wide)) {
-                        PrintResult::Overflow => inner.print_to(printer, Mode::Tall),
-                        PrintResult::Fits => PrintResult::Fits,
-                    },
+                // If we are tall, then we make the inner content wide if it
+                // fits, and tall otherwise.
+                Mode::Tall => {
+                    if printer.fits_wide(self, 0) {
+                        inner.print_to(printer, Mode::Wide)
+                    } else {
+                        inner.print_to(printer, Mode::Tall)
+                    }
                 }
-            }
+            },
+            Doc::Fill(elements) => Doc::print_fill(elements, None, printer, mode),
+            Doc::Paragraph { prefix, words } => Doc::print_fill(words, Some(prefix), printer, mode),
             Doc::Indent(inner) => match mode {
                 Mode::Wide => inner.print_to(printer, mode),
                 Mode::Tall => printer.indented(|p| inner.print_to(p, mode)),
@@ -380,7 +810,14 @@ impl<'a> Doc<'a> {
                     }
                 }
             },
+            Doc::Align(inner) => match mode {
+                Mode::Wide => inner.print_to(printer, mode),
+                Mode::Tall => printer.aligned(|p| inner.print_to(p, mode)),
+            },
             Doc::Markup(markup, inner) => printer.with_markup(*markup, |p| inner.print_to(p, mode)),
+            Doc::WithWidth(width, inner) => printer.with_width(*width, |p| inner.print_to(p, mode)),
+            Doc::NoTrim(inner) => printer.without_trim(|p| inner.print_to(p, mode)),
+            Doc::SpaceIndent(inner) => printer.with_space_indent(|p| inner.print_to(p, mode)),
         }
     }
 
@@ -389,11 +826,70 @@ impl<'a> Doc<'a> {
     where
         's: 'a,
     {
-        let mut printer: Printer<'a> = Printer::new(config);
+        let mut groups = WideWidths::new();
+        self.measure_wide(&mut groups, config.ambiguous_width);
+        let mut printer: Printer<'a, '_> = Printer::new(config, groups);
         self.print_to(&mut printer, Mode::Tall);
         printer.flush_newline();
         printer.into_inner()
     }
+
+    /// Pretty-print the document, and record where its markup ended up.
+    ///
+    /// This is like [`Doc::println`], but it also returns the output range of
+    /// every [`Doc::Markup`] node, in the order in which the nodes start.
+    pub fn println_with_markup_ranges<'s>(
+        &'s self,
+        config: &'s Config,
+    ) -> (MarkupString<'a>, Vec<MarkupRange>)
+    where
+        's: 'a,
+    {
+        let mut groups = WideWidths::new();
+        self.measure_wide(&mut groups, config.ambiguous_width);
+        let mut printer: Printer<'a, '_> = Printer::new(config, groups);
+        printer.record_markup_ranges();
+        self.print_to(&mut printer, Mode::Tall);
+        printer.flush_newline();
+        printer.into_inner_with_markup_ranges()
+    }
+
+    /// Measure the document as it would be printed, without building the output.
+    pub fn measure<'s>(&'s self, config: &'s Config) -> DocMetrics
+    where
+        's: 'a,
+    {
+        let mut groups = WideWidths::new();
+        self.measure_wide(&mut groups, config.ambiguous_width);
+        let mut sink = io::sink();
+        let mut printer: Printer<'a, '_> = Printer::new_streaming(config, groups, &mut sink);
+        self.print_to(&mut printer, Mode::Tall);
+        printer.flush_newline();
+        let (lines, max_width) = (printer.num_lines(), printer.max_line_width());
+        // Writing to the sink cannot fail.
+        let _ = printer.finish();
+        DocMetrics {
+            lines,
+            max_width,
+            overflows: max_width > config.width,
+        }
+    }
+
+    /// Pretty-print the document to a writer, without markup.
+    ///
+    /// Unlike [`Doc::println`], this does not build the full output in memory.
+    /// The printer only buffers the current line.
+    pub fn print_to_writer<'s>(&'s self, config: &'s Config, out: &mut dyn Write) -> io::Result<()>
+    where
+        's: 'a,
+    {
+        let mut groups = WideWidths::new();
+        self.measure_wide(&mut groups, config.ambiguous_width);
+        let mut printer: Printer<'a, '_> = Printer::new_streaming(config, groups, out);
+        self.print_to(&mut printer, Mode::Tall);
+        printer.flush_newline();
+        printer.finish()
+    }
 }
 
 impl<'a> From<&'a str> for Doc<'a> {
@@ -462,45 +958,70 @@ impl<'a> std::ops::Add<Doc<'a>> for Doc<'a> {
     }
 }
 
-macro_rules! doc_concat {
+// The macros are exported at the crate root because that is the only way for
+// `macro_rules!` macros to be public, but they are hidden there and re-exported
+// from this module, so users refer to them as `rcl::pprint::group!` etc.
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __pprint_concat {
     { $($fragment:expr)* } => {
         {
             #[allow(unused_mut)]
-            let mut result = crate::pprint::Doc::Empty;
+            let mut result = $crate::pprint::Doc::Empty;
             $( result = result + $fragment.into(); )*
             result
         }
     }
 }
-pub(crate) use doc_concat as concat;
 
-macro_rules! group {
+/// Concatenate document fragments, converting each with `Into<Doc>`.
+pub use crate::__pprint_concat as concat;
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __pprint_group {
     { $($fragment:expr)* } => {
-        crate::pprint::Doc::Group(Box::new( $crate::pprint::concat! { $($fragment)* } ))
+        $crate::pprint::Doc::Group(Box::new( $crate::pprint::concat! { $($fragment)* } ))
     }
 }
-pub(crate) use group;
 
-macro_rules! indent {
+/// Concatenate the fragments into a [`Doc::Group`].
+pub use crate::__pprint_group as group;
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __pprint_indent {
     { $($fragment:expr)* } => {
-        crate::pprint::Doc::Indent(Box::new( $crate::pprint::concat! { $($fragment)* } ))
+        $crate::pprint::Doc::Indent(Box::new( $crate::pprint::concat! { $($fragment)* } ))
     }
 }
-pub(crate) use indent;
 
-macro_rules! flush_indent {
+/// Concatenate the fragments into a [`Doc::Indent`].
+pub use crate::__pprint_indent as indent;
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __pprint_flush_indent {
     { $($fragment:expr)* } => {
-        crate::pprint::Doc::FlushIndent(Box::new( $crate::pprint::concat! { $($fragment)* } ))
+        $crate::pprint::Doc::FlushIndent(Box::new( $crate::pprint::concat! { $($fragment)* } ))
     }
 }
-pub(crate) use flush_indent;
+
+/// Concatenate the fragments into a [`Doc::FlushIndent`].
+pub use crate::__pprint_flush_indent as flush_indent;
 
 /// Helper module for pretty printing.
 ///
 /// This is a separate module to be able to hide some of the printer internals
 /// from the [`Doc::println`] implementation.
 mod printer {
-    use super::Config;
+    use std::io::{self, Write};
+
+    use super::{
+        AmbiguousWidth, Config, Doc, IndentStyle, LineEnding, MarkupRange, OutputPosition,
+        WideWidths,
+    };
     use crate::markup::{Markup, MarkupString};
 
     /// Whether printing in a particular mode fitted or not.
@@ -522,19 +1043,61 @@ mod printer {
     }
 
     /// Helper for pretty-printing documents that tracks indentation state.
-    pub struct Printer<'a> {
+    pub struct Printer<'a, 'w> {
         /// Buffer where we place the output.
         out: MarkupString<'a>,
 
+        /// When streaming, the writer that completed lines are flushed to.
+        sink: Option<&'w mut dyn Write>,
+
+        /// The first error that occurred while writing to the sink, if any.
+        sink_error: Option<io::Error>,
+
+        /// Whether any lines were flushed to the sink already.
+        flushed_any: bool,
+
+        /// The measured wide width of every group in the document.
+        wide_widths: WideWidths<'a>,
+
         /// Target width that we should try to not exceed.
         width: u32,
 
         /// The width so far of the line that we are currently writing.
         line_width: u32,
 
-        /// The current indentation level, counted in spaces.
+        /// The zero-based index of the line that we are currently writing.
+        line: u32,
+
+        /// The width of the widest line completed so far.
+        max_line_width: u32,
+
+        /// Whether we are printing content inside a [`Doc::NoTrim`].
+        no_trim: bool,
+
+        /// The number of fragments at the start of `out` that must not be trimmed.
+        n_untrimmable: usize,
+
+        /// When enabled, the output ranges of all markup nodes printed so far.
+        markup_ranges: Option<Vec<MarkupRange>>,
+
+        /// The current indentation, counted in columns.
         indent: u32,
 
+        /// The number of columns to add to the indentation per level.
+        indent_width: u32,
+
+        /// The current indentation, counted in levels.
+        indent_levels: u32,
+
+        /// Whether to write the indentation as spaces or as tabs.
+        indent_style: IndentStyle,
+
+        /// The string to emit for a newline.
+        newline: &'static str,
+
+        /// How wide to count characters with an ambiguous width.
+        ambiguous_width: AmbiguousWidth,
+
         /// Whether indentation has been written for the current line.
         needs_indent: bool,
 
@@ -542,59 +1105,242 @@ mod printer {
         markup: Markup,
     }
 
-    impl<'a> Printer<'a> {
+    impl<'a, 'w> Printer<'a, 'w> {
         /// Create a new printer with the given line width target.
-        pub fn new(config: &Config) -> Printer {
+        pub(super) fn new(config: &Config, wide_widths: WideWidths<'a>) -> Printer<'a, 'w> {
             Printer {
                 out: MarkupString::new(),
+                sink: None,
+                sink_error: None,
+                flushed_any: false,
+                wide_widths,
                 width: config.width,
                 line_width: 0,
+                line: 0,
+                max_line_width: 0,
+                markup_ranges: None,
+                no_trim: false,
+                n_untrimmable: 0,
                 indent: 0,
+                indent_width: match config.indent_style {
+                    IndentStyle::Spaces => config.indent_width,
+                    IndentStyle::Tabs => config.tab_width,
+                },
+                indent_levels: 0,
+                indent_style: config.indent_style,
+                newline: match config.line_ending {
+                    LineEnding::Lf => "\n",
+                    LineEnding::CrLf => "\r\n",
+                },
+                ambiguous_width: config.ambiguous_width,
                 needs_indent: true,
                 markup: Markup::None,
             }
         }
 
+        /// Create a printer that writes completed lines to `sink` as it goes.
+        pub(super) fn new_streaming(
+            config: &Config,
+            wide_widths: WideWidths<'a>,
+            sink: &'w mut dyn Write,
+        ) -> Printer<'a, 'w> {
+            Printer {
+                sink: Some(sink),
+                ..Printer::new(config, wide_widths)
+            }
+        }
+
         /// Return the result string printed to the printer.
         pub fn into_inner(self) -> MarkupString<'a> {
+            debug_assert!(self.sink.is_none(), "Use `finish` for a streaming printer.");
             self.out
         }
 
-        /// Execute `f` against this printer. If the result was too wide, roll back.
-        pub fn try_<F: FnOnce(&mut Printer<'a>) -> PrintResult>(&mut self, f: F) -> PrintResult {
-            let fragment_len = self.out.num_fragments();
-            let line_width = self.line_width;
-            let needs_indent = self.needs_indent;
-            let result = f(self);
-            if result.is_overflow() {
-                self.out.truncate(fragment_len);
-                self.line_width = line_width;
-                self.needs_indent = needs_indent;
+        /// Record the output range of every markup node from now on.
+        pub fn record_markup_ranges(&mut self) {
+            self.markup_ranges = Some(Vec::new());
+        }
+
+        /// Return the result string, and the markup ranges that were recorded.
+        pub fn into_inner_with_markup_ranges(self) -> (MarkupString<'a>, Vec<MarkupRange>) {
+            debug_assert!(self.sink.is_none(), "Use `finish` for a streaming printer.");
+            (self.out, self.markup_ranges.unwrap_or_default())
+        }
+
+        /// Return the number of newlines printed so far.
+        pub fn num_lines(&self) -> u32 {
+            self.line
+        }
+
+        /// Return the width of the widest line completed so far.
+        pub fn max_line_width(&self) -> u32 {
+            self.max_line_width
+        }
+
+        /// Return the position where the next content will be written.
+        fn position(&self) -> OutputPosition {
+            OutputPosition {
+                line: self.line,
+                // If we are at the start of a line, the content will start
+                // after the indent, which has not been written yet.
+                column: if self.needs_indent {
+                    self.indent
+                } else {
+                    self.line_width
+                },
+            }
+        }
+
+        /// Write the remaining output to the sink, and report any write error.
+        pub fn finish(mut self) -> io::Result<()> {
+            self.flush_sink();
+            match self.sink_error {
+                Some(err) => Err(err),
+                None => Ok(()),
+            }
+        }
+
+        /// Write the buffered output to the sink, if there is one.
+        fn flush_sink(&mut self) {
+            let sink = match self.sink.as_mut() {
+                Some(sink) => sink,
+                None => return,
+            };
+            // After a write error, we still keep printing, but we drop the
+            // output. The error gets reported by `finish`.
+            if self.sink_error.is_none() {
+                if let Err(err) = self.out.write_bytes_no_markup(&mut **sink) {
+                    self.sink_error = Some(err);
+                }
+            }
+            self.flushed_any = self.flushed_any || !self.out.is_empty();
+            self.out.truncate(0);
+            self.n_untrimmable = 0;
+        }
+
+        /// Report whether the group fits on the current line when printed wide.
+        ///
+        /// The `prefix_width` is the width of content that we would print before
+        /// the group. The width of the group must have been measured, and it
+        /// does not fit if it contains a hard break.
+        pub fn fits_wide(&self, group: &Doc<'a>, prefix_width: u32) -> bool {
+            let measured = self
+                .wide_widths
+                .get(&(group as *const Doc<'a>))
+                .expect("Groups are measured before printing.");
+            match measured {
+                None => false,
+                Some(wide) => {
+                    let has_content = prefix_width > 0 || wide.has_content;
+                    let indent = if self.needs_indent && has_content {
+                        self.indent
+                    } else {
+                        0
+                    };
+                    let end = self.line_width + indent + prefix_width;
+                    end.saturating_add(wide.width) <= self.width
+                }
             }
-            result
         }
 
         /// Execute `f` under increased indentation width.
-        pub fn indented<F: FnOnce(&mut Printer<'a>) -> PrintResult>(
+        pub fn indented<F: FnOnce(&mut Printer<'a, 'w>) -> PrintResult>(
+            &mut self,
+            f: F,
+        ) -> PrintResult {
+            self.indent += self.indent_width;
+            self.indent_levels += 1;
+            let result = f(self);
+            self.indent_levels -= 1;
+            self.indent -= self.indent_width;
+            result
+        }
+
+        /// Execute `f` with the indentation set to the current column.
+        pub fn aligned<F: FnOnce(&mut Printer<'a, 'w>) -> PrintResult>(
             &mut self,
             f: F,
         ) -> PrintResult {
-            self.indent += 2;
+            let prev = self.indent;
+            // When we are still at the start of the line, the indent has not
+            // been written yet, but content will start after it.
+            if !self.needs_indent {
+                self.indent = self.line_width;
+            }
             let result = f(self);
-            self.indent -= 2;
+            self.indent = prev;
             result
         }
 
         /// Execute `f` with markup applied.
-        pub fn with_markup<F: FnOnce(&mut Printer<'a>) -> PrintResult>(
+        pub fn with_markup<F: FnOnce(&mut Printer<'a, 'w>) -> PrintResult>(
             &mut self,
             markup: Markup,
             f: F,
         ) -> PrintResult {
+            let start = self.position();
+            let range_index = self.markup_ranges.as_mut().map(|ranges| {
+                ranges.push(MarkupRange {
+                    markup,
+                    start,
+                    end: start,
+                });
+                ranges.len() - 1
+            });
+
             let prev = self.markup;
             self.markup = markup;
             let result = f(self);
             self.markup = prev;
+
+            if let Some(i) = range_index {
+                let end = OutputPosition {
+                    line: self.line,
+                    column: self.line_width,
+                };
+                if let Some(ranges) = self.markup_ranges.as_mut() {
+                    // If the markup was empty at the start of a line, the end
+                    // would be before the indent, but the range is empty.
+                    ranges[i].end = end.max(start);
+                }
+            }
+            result
+        }
+
+        /// Execute `f` with a different target width.
+        pub fn with_width<F: FnOnce(&mut Printer<'a, 'w>) -> PrintResult>(
+            &mut self,
+            width: u32,
+            f: F,
+        ) -> PrintResult {
+            let prev = self.width;
+            self.width = width;
+            let result = f(self);
+            self.width = prev;
+            result
+        }
+
+        /// Execute `f` such that its trailing spaces don't get removed.
+        pub fn without_trim<F: FnOnce(&mut Printer<'a, 'w>) -> PrintResult>(
+            &mut self,
+            f: F,
+        ) -> PrintResult {
+            let prev = self.no_trim;
+            self.no_trim = true;
+            let result = f(self);
+            self.no_trim = prev;
+            result
+        }
+
+        /// Execute `f` such that new lines are indented with spaces.
+        pub fn with_space_indent<F: FnOnce(&mut Printer<'a, 'w>) -> PrintResult>(
+            &mut self,
+            f: F,
+        ) -> PrintResult {
+            let prev = self.indent_style;
+            self.indent_style = IndentStyle::Spaces;
+            let result = f(self);
+            self.indent_style = prev;
             result
         }
 
@@ -604,14 +1350,29 @@ mod printer {
                 return;
             }
 
-            // 50 spaces.
+            // 50 spaces, and 50 tabs.
             let spaces = "                                                  ";
-
-            let mut n_left = self.indent as usize;
-            while n_left > 0 {
-                let n = n_left.min(spaces.len());
-                self.out.push(&spaces[..n], Markup::None);
-                n_left -= n;
+            let tabs = "\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t";
+
+            // With tabs there is one tab per level, which counts as the tab
+            // width for the line width. Any remaining columns, from alignment,
+            // are filled with spaces after the tabs.
+            let (n_tabs, n_spaces) = match self.indent_style {
+                IndentStyle::Spaces => (0, self.indent),
+                IndentStyle::Tabs => {
+                    let n_tabs = self.indent_levels;
+                    (
+                        n_tabs,
+                        self.indent.saturating_sub(n_tabs * self.indent_width),
+                    )
+                }
+            };
+            for (fill, mut n_left) in [(tabs, n_tabs as usize), (spaces, n_spaces as usize)] {
+                while n_left > 0 {
+                    let n = n_left.min(fill.len());
+                    self.out.push(&fill[..n], Markup::None);
+                    n_left -= n;
+                }
             }
 
             self.line_width += self.indent;
@@ -637,32 +1398,38 @@ mod printer {
             );
             self.write_indent();
             self.out.push(value, self.markup);
-            self.line_width += width;
+            if self.no_trim {
+                self.n_untrimmable = self.out.num_fragments();
+            }
+            self.line_width += self.ambiguous_width.adjust_width(value, width);
             self.fits()
         }
 
         pub fn newline(&mut self) -> PrintResult {
             debug_assert!(
-                !self.out.is_empty(),
+                self.flushed_any || !self.out.is_empty(),
                 // coverage:off -- Error not expected to be hit.
                 "Should not try to create leading whitespace!",
                 // coverage:on
             );
 
-            // HACK: Remove any trailing spaces from the current line before we
-            // move on to the next. This is bad because it might trim
-            // significant spaces from user code (e.g. a trailing space in
-            // Markdown is significant, and maybe you write Markdown in a
-            // comment). Or even worse, there may be trailing whitespace in a
-            // multiline string literal. But it is the quick and dirty fix for
-            // not emitting space after e.g. a multi-line `let` binding. We work
-            // around this hack in string literals by escaping trailing spaces,
-            // which is arguably better anyway for visibility.
-            self.out.trim_spaces_end();
-
-            self.out.push("\n", Markup::None);
+            // Remove any trailing spaces from the current line before we move
+            // on to the next, so we don't emit the space after e.g. `=` in a
+            // multi-line `let` binding. Content that was printed inside a
+            // `Doc::NoTrim` is kept, so significant spaces in user code (e.g.
+            // in a string literal, or Markdown in a comment) are preserved.
+            let n_trimmed = self.out.trim_spaces_end(self.n_untrimmable);
+            let line_width = self.line_width.saturating_sub(n_trimmed as u32);
+            self.max_line_width = self.max_line_width.max(line_width);
+
+            self.out.push(self.newline, Markup::None);
             self.line_width = 0;
+            self.line += 1;
             self.needs_indent = true;
+
+            // The line is complete now, so we can hand it off to the sink.
+            self.flush_sink();
+
             // For the print result, we measure until the end of the line, so a
             // newline fits by definition, even if the previous line might have
             // exceeded the target width. This is mostly to simplify call sites
@@ -693,10 +1460,13 @@ mod printer {
 
 #[cfg(test)]
 mod test {
-    use super::{Config, Doc};
+    use super::{concat, group, indent, Config, Doc, IndentStyle};
 
     fn print_width(doc: &Doc, width: u32) -> String {
-        let config = Config { width };
+        let config = Config {
+            width,
+            ..Config::default()
+        };
         doc.println(&config).to_string_no_markup()
     }
 
@@ -772,4 +1542,362 @@ mod test {
             "[\n  [\n    a,\n    b,\n    c,\n  ],\n  elem0,\n  elem1,\n  elem2,\n]\n",
         );
     }
+
+    #[test]
+    fn tabs_indent_one_per_level_and_count_as_tab_width() {
+        use Doc::{Sep, SoftBreak};
+        let doc = group! {
+            "["
+            SoftBreak
+            indent! {
+                group! {
+                    "["
+                    SoftBreak
+                    indent! { "a" "," Sep "b" Doc::tall(",") }
+                    SoftBreak
+                    "]"
+                }
+                "," Sep
+                "elem" Doc::tall(",")
+            }
+            SoftBreak
+            "]"
+        };
+        let config = |width, tab_width| Config {
+            width,
+            indent_style: IndentStyle::Tabs,
+            tab_width,
+            ..Config::default()
+        };
+        let print = |config: &Config| doc.println(config).to_string_no_markup();
+        // The inner list with its comma is 7 columns wide, so after a tab of 4 columns it
+        // fits in 12, but after a tab of 8 it does not.
+        assert_eq!(print(&config(12, 4)), "[\n\t[a, b],\n\telem,\n]\n");
+        assert_eq!(
+            print(&config(12, 8)),
+            "[\n\t[\n\t\ta,\n\t\tb,\n\t],\n\telem,\n]\n"
+        );
+        // The indent width does not matter for tabs.
+        let config = Config {
+            indent_width: 7,
+            ..config(12, 4)
+        };
+        assert_eq!(print(&config), "[\n\t[a, b],\n\telem,\n]\n");
+    }
+
+    #[test]
+    fn print_to_writer_matches_println() {
+        use Doc::{HardBreak, Sep, SoftBreak};
+        let doc = group! {
+            "["
+            SoftBreak
+            indent! {
+                "// Comment, with trailing space. "
+                HardBreak
+                HardBreak
+                group! {
+                    "["
+                    SoftBreak
+                    indent! { "a" "," Sep "b" Doc::tall(",") }
+                    SoftBreak
+                    "]"
+                }
+                "," Sep
+                "elem" Doc::tall(",")
+            }
+            SoftBreak
+            "]"
+        };
+        for width in 0..40 {
+            let config = Config {
+                width,
+                ..Config::default()
+            };
+            let mut out = Vec::new();
+            doc.print_to_writer(&config, &mut out).unwrap();
+            assert_eq!(String::from_utf8(out).unwrap(), print_width(&doc, width));
+        }
+    }
+
+    #[test]
+    fn deeply_nested_groups_are_measured_once() {
+        use Doc::SoftBreak;
+        // Every group contains all groups below it, and the content only
+        // overflows at the innermost one. Deciding wide or tall by printing
+        // speculatively would re-render the full subtree for every level,
+        // which is hundreds of millions of nodes here.
+        let depth = 100;
+        let mut doc = Doc::from("a-string-that-is-too-wide-to-fit-on-a-line-in-wide-mode");
+        for _ in 0..depth {
+            let padding: Vec<Doc> = (0..10_000).map(|_| Doc::Empty).collect();
+            doc = group! {
+                "["
+                SoftBreak
+                indent! { Doc::Concat(padding) doc }
+                SoftBreak
+                "]"
+            };
+        }
+        let result = print_width(&doc, 80);
+        let lines: Vec<&str> = result.lines().collect();
+        assert_eq!(lines.len(), 2 * depth + 1);
+        assert_eq!(lines[depth - 1], format!("{}[", " ".repeat(2 * depth - 2)));
+        assert!(lines[depth].starts_with(&format!("{}a-string", " ".repeat(2 * depth))));
+    }
+
+    #[test]
+    fn fill_packs_elements_per_line() {
+        use Doc::SoftBreak;
+        // The trailing comma goes after the fill, so it only gets printed when
+        // the list is tall.
+        let elements: Vec<Doc> = (0..8)
+            .map(|i| match i {
+                7 => Doc::string(format!("elem{i}")),
+                _ => Doc::Concat(vec![Doc::string(format!("elem{i}")), ",".into()]),
+            })
+            .collect();
+        let doc = group! {
+            "["
+            SoftBreak
+            indent! { Doc::Fill(elements) Doc::tall(",") }
+            SoftBreak
+            "]"
+        };
+        assert_eq!(
+            print_width(&doc, 80),
+            "[elem0, elem1, elem2, elem3, elem4, elem5, elem6, elem7]\n",
+        );
+        assert_eq!(
+            print_width(&doc, 24),
+            "[\n  elem0, elem1, elem2,\n  elem3, elem4, elem5,\n  elem6, elem7,\n]\n",
+        );
+        // An element that is too wide by itself still gets a line of its own.
+        assert_eq!(
+            print_width(&doc, 6),
+            "[\n  elem0,\n  elem1,\n  elem2,\n  elem3,\n  elem4,\n  elem5,\n  elem6,\n  elem7,\n]\n",
+        );
+    }
+
+    #[test]
+    fn align_indents_to_current_column() {
+        use Doc::Sep;
+        let doc = concat! {
+            "let x = "
+            group! {
+                "call("
+                Doc::Align(Box::new(concat! { "alpha," Sep "beta," Sep "gamma" }))
+                ")"
+            }
+        };
+        assert_eq!(print_width(&doc, 80), "let x = call(alpha, beta, gamma)\n");
+        assert_eq!(
+            print_width(&doc, 20),
+            "let x = call(alpha,\n             beta,\n             gamma)\n",
+        );
+        // With tabs, the alignment beyond the indent levels is done with spaces.
+        let config = Config {
+            width: 20,
+            indent_style: IndentStyle::Tabs,
+            ..Config::default()
+        };
+        let doc = concat! { "{" indent! { Doc::HardBreak doc } };
+        assert_eq!(
+            doc.println(&config).to_string_no_markup(),
+            "{\n\tlet x = call(alpha,\n\t             beta,\n\t             gamma)\n",
+        );
+    }
+
+    #[test]
+    fn space_indent_uses_spaces_also_with_tabs() {
+        use Doc::HardBreak;
+        let config = Config {
+            indent_style: IndentStyle::Tabs,
+            tab_width: 4,
+            ..Config::default()
+        };
+        let doc = concat! {
+            "{"
+            indent! {
+                HardBreak "a"
+                Doc::SpaceIndent(Box::new(indent! { HardBreak "b" HardBreak "c" }))
+                HardBreak "d"
+            }
+        };
+        assert_eq!(
+            doc.println(&config).to_string_no_markup(),
+            "{\n\ta\n        b\n        c\n\td\n",
+        );
+    }
+
+    #[test]
+    fn println_with_markup_ranges_records_output_positions() {
+        use super::{MarkupRange, OutputPosition};
+        use crate::markup::Markup;
+        use Doc::HardBreak;
+        let pos = |line, column| OutputPosition { line, column };
+        let doc = concat! {
+            "{"
+            indent! {
+                HardBreak
+                Doc::str("\"key\"").with_markup(Markup::String)
+                ": "
+                Doc::Concat(vec!["1".into(), HardBreak, "2".into()]).with_markup(Markup::Number)
+            }
+            HardBreak
+            "}"
+        };
+        let config = Config::default();
+        let (result, ranges) = doc.println_with_markup_ranges(&config);
+        assert_eq!(result.to_string_no_markup(), "{\n  \"key\": 1\n  2\n}\n");
+        assert_eq!(
+            ranges,
+            vec![
+                MarkupRange {
+                    markup: Markup::String,
+                    start: pos(1, 2),
+                    end: pos(1, 7),
+                },
+                MarkupRange {
+                    markup: Markup::Number,
+                    start: pos(1, 9),
+                    end: pos(2, 3),
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn no_trim_preserves_trailing_spaces() {
+        use Doc::HardBreak;
+        let doc = concat! {
+            "let x = "
+            HardBreak
+            Doc::NoTrim(Box::new(Doc::str("kept  ")))
+            " "
+            HardBreak
+            "end"
+        };
+        // The space after the no-trim content is trimmed, but not the spaces
+        // inside it.
+        assert_eq!(print_width(&doc, 80), "let x =\nkept  \nend\n");
+    }
+
+    #[test]
+    fn measure_reports_lines_and_width() {
+        use super::DocMetrics;
+        use Doc::{Sep, SoftBreak};
+        let doc = group! {
+            "["
+            SoftBreak
+            indent! { "elem0," Sep "a-much-longer-element" Doc::tall(",") }
+            SoftBreak
+            "]"
+        };
+        let measure = |width| {
+            let config = Config {
+                width,
+                ..Config::default()
+            };
+            doc.measure(&config)
+        };
+        assert_eq!(
+            measure(80),
+            DocMetrics {
+                lines: 1,
+                max_width: 30,
+                overflows: false,
+            },
+        );
+        assert_eq!(
+            measure(24),
+            DocMetrics {
+                lines: 4,
+                max_width: 24,
+                overflows: false,
+            },
+        );
+        assert_eq!(
+            measure(20),
+            DocMetrics {
+                lines: 4,
+                max_width: 24,
+                overflows: true,
+            },
+        );
+    }
+
+    #[test]
+    fn paragraph_wraps_words_with_prefix() {
+        use Doc::HardBreak;
+        let text = "The quick brown fox jumps over the lazy dog, \
+            and a-word-that-is-too-long-to-fit-on-a-line goes alone.";
+        let doc = concat! {
+            "{"
+            indent! { HardBreak Doc::paragraph("// ", text) HardBreak "x" }
+            HardBreak
+            "}"
+        };
+        assert_eq!(
+            print_width(&doc, 30),
+            "{\n  // The quick brown fox jumps\n  // over the lazy dog, and\n  \
+            // a-word-that-is-too-long-to-fit-on-a-line\n  // goes alone.\n  x\n}\n",
+        );
+        // When it fits, it stays on one line, also when it was wrapped before.
+        assert_eq!(
+            print_width(&Doc::paragraph("# ", "Short\n   text."), 30),
+            "# Short text.\n",
+        );
+    }
+
+    #[test]
+    fn with_width_overrides_width_for_subtree() {
+        use Doc::{Sep, SoftBreak};
+        let list = |elems: [&'static str; 2]| {
+            group! {
+                "["
+                SoftBreak
+                indent! { elems[0] "," Sep elems[1] Doc::tall(",") }
+                SoftBreak
+                "]"
+            }
+        };
+        let url = list(["\"https://example.com/a/long/path\"", "\"more\""]);
+        let doc = group! {
+            "["
+            SoftBreak
+            indent! {
+                Doc::WithWidth(u32::MAX, Box::new(url)) "," Sep
+                list(["\"short\"", "\"another-element\""]) Doc::tall(",")
+            }
+            SoftBreak
+            "]"
+        };
+        assert_eq!(
+            print_width(&doc, 28),
+            "[\n  [\"https://example.com/a/long/path\", \"more\"],\n  [\n    \"short\",\n    \"another-element\",\n  ],\n]\n",
+        );
+    }
+
+    #[test]
+    fn to_sexpr_shows_structure() {
+        use crate::markup::Markup;
+        use Doc::{Sep, SoftBreak};
+        let doc = group! {
+            "["
+            SoftBreak
+            indent! { "elem0" "," Sep Doc::str("elem1").with_markup(Markup::String) Doc::tall(",") }
+            SoftBreak
+            "]"
+        };
+        assert_eq!(
+            doc.to_sexpr(),
+            "(group\n  (concat\n    \"[\"\n    soft-break\n    \
+            (indent (concat \"elem0\" \",\" sep (markup String \"elem1\") (tall \",\")))\n    \
+            soft-break\n    \"]\"))\n",
+        );
+        assert_eq!(
+            Doc::Group(Box::new(Doc::HardBreak)).to_sexpr(),
+            "(group hard-break)\n"
+        );
+    }
 }
@@ -12,13 +12,14 @@ use std::collections::{BTreeMap, BTreeSet};
 use std::rc::Rc;
 
 use crate::ast::{CallArg, Expr};
+use crate::decimal::Decimal;
 use crate::error::{IntoError, PathElement, Result};
 use crate::eval::Evaluator;
 use crate::fmt_rcl::format_rcl;
 use crate::fmt_type::format_type;
 use crate::pprint::{concat, indent, Doc};
 use crate::source::Span;
-use crate::type_diff::{Mismatch, TypeDiff};
+use crate::type_diff::{help_empty_set, Mismatch, TypeDiff};
 use crate::type_source::Source;
 use crate::types;
 use crate::types::{Side, SourcedType, Type};
@@ -147,14 +148,21 @@ impl Ord for Function {
 }
 
 /// A value.
-#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+///
+/// Values are ordered first by their type, and then by their contents. The
+/// exception are integers and numbers, which compare by numeric value, also
+/// with each other. In particular, `1 == 1.0`.
+#[derive(Clone, Debug)]
 pub enum Value {
     Null,
 
     Bool(bool),
 
-    // TODO: Should be a bigint.
-    Int(i64),
+    /// An integer, with 128 bits, so every signed and unsigned 64-bit integer fits.
+    Int(i128),
+
+    /// A number that is not necessarily an integer.
+    Number(Decimal),
 
     String(Rc<str>),
 
@@ -174,6 +182,44 @@ pub enum Value {
 }
 
 impl Value {
+    /// The position of the value's type in the order between values of different types.
+    fn type_rank(&self) -> u8 {
+        match self {
+            Value::Null => 0,
+            Value::Bool(..) => 1,
+            Value::Int(..) | Value::Number(..) => 2,
+            Value::String(..) => 3,
+            Value::List(..) => 4,
+            Value::Set(..) => 5,
+            Value::Dict(..) => 6,
+            Value::Function(..) => 7,
+            Value::BuiltinFunction(..) => 8,
+            Value::BuiltinMethod(..) => 9,
+        }
+    }
+
+    /// Return the numeric value of an `Int` or `Number`, or `None` for other values.
+    pub fn as_decimal(&self) -> Option<Decimal> {
+        match self {
+            Value::Int(i) => Some(Decimal::from(*i)),
+            Value::Number(d) => Some(*d),
+            _ => None,
+        }
+    }
+
+    /// Return whether both values are the same list, set, or dict instance.
+    ///
+    /// Unlike `==`, this does not compare contents, so two equal collections
+    /// that were constructed separately are not the same.
+    pub fn is_same_collection(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::List(a), Value::List(b)) => Rc::ptr_eq(a, b),
+            (Value::Set(a), Value::Set(b)) => Rc::ptr_eq(a, b),
+            (Value::Dict(a), Value::Dict(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+
     /// Extract the dict if it is one, panic otherwise.
     #[inline]
     pub fn expect_dict(&self) -> &BTreeMap<Value, Value> {
@@ -230,6 +276,7 @@ impl Value {
             (Type::Null, Value::Null) => return Ok(()),
             (Type::Bool, Value::Bool(..)) => return Ok(()),
             (Type::Int, Value::Int(..)) => return Ok(()),
+            (Type::Number, Value::Int(..) | Value::Number(..)) => return Ok(()),
             (Type::String, Value::String(..)) => return Ok(()),
 
             // For compound types, we descend into them to check.
@@ -306,11 +353,51 @@ impl Value {
             Doc::HardBreak Doc::HardBreak
             indent! { format_rcl(self).into_owned() }
         });
+        if let (Type::Set(..), Value::Dict(kvs)) = (req_type, self) {
+            if kvs.is_empty() {
+                error.set_help(help_empty_set());
+            }
+        }
         type_.explain_error(Side::Expected, &mut error);
         error.err()
     }
 }
 
+impl Ord for Value {
+    fn cmp(&self, other: &Value) -> Ordering {
+        match (self, other) {
+            (Value::Null, Value::Null) => Ordering::Equal,
+            (Value::Bool(x), Value::Bool(y)) => x.cmp(y),
+            (Value::Int(x), Value::Int(y)) => x.cmp(y),
+            (Value::Int(x), Value::Number(y)) => Decimal::from(*x).cmp(y),
+            (Value::Number(x), Value::Int(y)) => x.cmp(&Decimal::from(*y)),
+            (Value::Number(x), Value::Number(y)) => x.cmp(y),
+            (Value::String(x), Value::String(y)) => x.cmp(y),
+            (Value::List(x), Value::List(y)) => x.cmp(y),
+            (Value::Set(x), Value::Set(y)) => x.cmp(y),
+            (Value::Dict(x), Value::Dict(y)) => x.cmp(y),
+            (Value::Function(x), Value::Function(y)) => x.cmp(y),
+            (Value::BuiltinFunction(x), Value::BuiltinFunction(y)) => x.cmp(y),
+            (Value::BuiltinMethod(x), Value::BuiltinMethod(y)) => x.cmp(y),
+            _ => self.type_rank().cmp(&other.type_rank()),
+        }
+    }
+}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Value) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Value {}
+
 impl<'a> From<&'a str> for Value {
     #[inline]
     fn from(value: &'a str) -> Self {
@@ -0,0 +1,522 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! A `serde::Serializer` that converts Rust values into RCL values.
+//!
+//! This is for applications that embed RCL and want to write their state or
+//! configuration as RCL, for example to generate an initial configuration file.
+//! The value is formatted with [`format_rcl`], so the output is pretty-printed
+//! like that of `rcl evaluate`, and it evaluates back to the same value.
+//!
+//! We follow the data model of `serde_json`: structs and maps become dicts,
+//! sequences and tuples become lists, `None` and `()` become `null`, unit
+//! variants become strings, and other enum variants become a dict with a single
+//! key, the name of the variant. Unlike json, map keys do not have to be
+//! strings. Floats become the shortest decimal number that identifies them,
+//! except for infinities and NaN, which cannot be serialized. Integers are 128
+//! bits, larger integers cannot be serialized either.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::rc::Rc;
+
+use serde::ser::{self, Serialize};
+
+use crate::decimal::Decimal;
+use crate::fmt_rcl::format_rcl;
+use crate::pprint::Config;
+use crate::runtime::Value;
+
+/// An error that occurred while serializing a Rust value.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Error {
+    message: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Error {
+        Error {
+            message: msg.to_string(),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Convert a Rust value into an RCL value.
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<Value> {
+    value.serialize(Serializer)
+}
+
+/// Format a Rust value as RCL source code, pretty-printed to the given config.
+///
+/// The result ends in a newline.
+pub fn to_string<T: Serialize + ?Sized>(value: &T, cfg: &Config) -> Result<String> {
+    let value = to_value(value)?;
+    Ok(format_rcl(&value).println(cfg).to_string_no_markup())
+}
+
+fn int<T: TryInto<i128> + fmt::Display + Copy>(n: T) -> Result<Value> {
+    match n.try_into() {
+        Ok(n) => Ok(Value::Int(n)),
+        Err(..) => Err(Error {
+            message: format!("Integer {n} does not fit in a 128-bit signed integer."),
+        }),
+    }
+}
+
+fn number<T: fmt::Display>(v: T, d: Option<Decimal>) -> Result<Value> {
+    match d {
+        Some(d) => Ok(Value::Number(d)),
+        None => Err(Error {
+            message: format!("Cannot serialize {v}, RCL numbers cannot be infinite or NaN."),
+        }),
+    }
+}
+
+fn string(s: &str) -> Value {
+    Value::String(s.into())
+}
+
+/// Wrap the value in a dict with the variant name as key.
+fn variant(name: &'static str, value: Value) -> Value {
+    let mut kv = BTreeMap::new();
+    kv.insert(string(name), value);
+    Value::Dict(Rc::new(kv))
+}
+
+/// The serializer, see the module documentation for how it maps types.
+pub struct Serializer;
+
+impl ser::Serializer for Serializer {
+    type Ok = Value;
+    type Error = Error;
+
+    type SerializeSeq = SerializeList;
+    type SerializeTuple = SerializeList;
+    type SerializeTupleStruct = SerializeList;
+    type SerializeTupleVariant = SerializeList;
+    type SerializeMap = SerializeDict;
+    type SerializeStruct = SerializeDict;
+    type SerializeStructVariant = SerializeDict;
+
+    fn serialize_bool(self, v: bool) -> Result<Value> {
+        Ok(Value::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value> {
+        int(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value> {
+        int(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value> {
+        int(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value> {
+        int(v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Value> {
+        int(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value> {
+        int(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value> {
+        int(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value> {
+        int(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value> {
+        int(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Value> {
+        int(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value> {
+        // Format the f32 itself rather than converting to f64 first, so that
+        // 0.1 becomes 0.1, and not the digits of the f64 nearest to it.
+        let d = if v.is_finite() {
+            Decimal::parse(&format!("{v:e}"))
+        } else {
+            None
+        };
+        number(v, d)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value> {
+        number(v, Decimal::from_f64(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value> {
+        Ok(Value::String(v.to_string().into()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value> {
+        Ok(string(v))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value> {
+        let elements = v.iter().map(|b| Value::Int(*b as i128)).collect();
+        Ok(Value::List(Rc::new(elements)))
+    }
+
+    fn serialize_none(self) -> Result<Value> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Value> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Value> {
+        Ok(string(variant))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Value> {
+        Ok(variant(name, value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeList> {
+        Ok(SerializeList {
+            variant: None,
+            elements: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeList> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SerializeList> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        name: &'static str,
+        len: usize,
+    ) -> Result<SerializeList> {
+        Ok(SerializeList {
+            variant: Some(name),
+            elements: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<SerializeDict> {
+        Ok(SerializeDict {
+            variant: None,
+            entries: BTreeMap::new(),
+            next_key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<SerializeDict> {
+        self.serialize_map(None)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        name: &'static str,
+        _len: usize,
+    ) -> Result<SerializeDict> {
+        Ok(SerializeDict {
+            variant: Some(name),
+            entries: BTreeMap::new(),
+            next_key: None,
+        })
+    }
+}
+
+/// Helper for serializing sequences, tuples, and tuple variants into a list.
+pub struct SerializeList {
+    /// For tuple variants, the name of the variant.
+    variant: Option<&'static str>,
+    elements: Vec<Value>,
+}
+
+impl SerializeList {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.elements.push(value.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Value> {
+        let list = Value::List(Rc::new(self.elements));
+        match self.variant {
+            None => Ok(list),
+            Some(name) => Ok(variant(name, list)),
+        }
+    }
+}
+
+impl ser::SerializeSeq for SerializeList {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SerializeList {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeList {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeList {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value> {
+        self.finish()
+    }
+}
+
+/// Helper for serializing maps, structs, and struct variants into a dict.
+pub struct SerializeDict {
+    /// For struct variants, the name of the variant.
+    variant: Option<&'static str>,
+    entries: BTreeMap<Value, Value>,
+    /// For maps, the key of the entry whose value we serialize next.
+    next_key: Option<Value>,
+}
+
+impl SerializeDict {
+    fn insert(&mut self, key: Value, value: Value) -> Result<()> {
+        if self.entries.contains_key(&key) {
+            return Err(Error {
+                message: format!(
+                    "Duplicate key {}.",
+                    format_rcl(&key)
+                        .println(&Config::default())
+                        .to_string_no_markup()
+                        .trim_end()
+                ),
+            });
+        }
+        self.entries.insert(key, value);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Value> {
+        let dict = Value::Dict(Rc::new(self.entries));
+        match self.variant {
+            None => Ok(dict),
+            Some(name) => Ok(variant(name, dict)),
+        }
+    }
+}
+
+impl ser::SerializeMap for SerializeDict {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        self.next_key = Some(key.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let key = self
+            .next_key
+            .take()
+            .expect("Serde calls serialize_key before serialize_value.");
+        let value = value.serialize(Serializer)?;
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Value> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for SerializeDict {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        let value = value.serialize(Serializer)?;
+        self.insert(string(key), value)
+    }
+
+    fn end(self) -> Result<Value> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeDict {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        let value = value.serialize(Serializer)?;
+        self.insert(string(key), value)
+    }
+
+    fn end(self) -> Result<Value> {
+        self.finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+    use super::{to_string, to_value};
+    use crate::pprint::Config;
+    use crate::runtime::Value;
+
+    /// A struct with a hand-written impl, because we do not depend on serde_derive.
+    struct Service {
+        name: &'static str,
+        ports: Vec<u16>,
+        replicas: Option<u32>,
+    }
+
+    impl Serialize for Service {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut s = serializer.serialize_struct("Service", 3)?;
+            s.serialize_field("name", self.name)?;
+            s.serialize_field("ports", &self.ports)?;
+            s.serialize_field("replicas", &self.replicas)?;
+            s.end()
+        }
+    }
+
+    #[test]
+    fn to_string_formats_structs_as_rcl() {
+        let service = Service {
+            name: "web",
+            ports: vec![80, 443],
+            replicas: None,
+        };
+        let cfg = Config {
+            width: 30,
+            ..Config::default()
+        };
+        assert_eq!(
+            to_string(&service, &cfg).unwrap(),
+            "{\n  name = \"web\",\n  ports = [80, 443],\n  replicas = null,\n}\n",
+        );
+
+        let mut map = BTreeMap::new();
+        map.insert(1_u8, BTreeSet::from(['a', 'b']));
+        map.insert(2_u8, BTreeSet::new());
+        assert_eq!(
+            to_string(&(map, "x", ()), &Config::default()).unwrap(),
+            "[{ 1: [\"a\", \"b\"], 2: [] }, \"x\", null]\n",
+        );
+    }
+
+    #[test]
+    fn to_value_rejects_unrepresentable_numbers() {
+        assert_eq!(
+            to_value(&f64::NAN).unwrap_err().to_string(),
+            "Cannot serialize NaN, RCL numbers cannot be infinite or NaN.",
+        );
+        assert_eq!(
+            to_string(&[0.1_f32, 1.5], &Config::default()).unwrap(),
+            "[0.1, 1.5]\n",
+        );
+        assert_eq!(
+            to_value(&u128::MAX).unwrap_err().to_string(),
+            "Integer 340282366920938463463374607431768211455 does not fit in a 128-bit signed integer.",
+        );
+        assert!(to_value(&i64::MIN).is_ok());
+        assert_eq!(to_value(&u64::MAX).unwrap(), Value::Int(u64::MAX as i128));
+    }
+}
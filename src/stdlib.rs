@@ -16,9 +16,28 @@ use crate::eval::Evaluator;
 use crate::fmt_rcl::format_rcl;
 use crate::markup::Markup;
 use crate::pprint::{concat, indent, Doc};
-use crate::runtime::{builtin_function, builtin_method, FunctionCall, MethodCall, Value};
+use crate::runtime::{
+    builtin_function, builtin_method, BuiltinMethod, FunctionCall, MethodCall, Value,
+};
+use crate::source::Span;
 use crate::types::AsTypeName;
 
+/// Extract a string argument, or report the message at the argument.
+pub(crate) fn string_arg<'a>(arg: &'a CallArg<Value>, message: &'static str) -> Result<&'a str> {
+    match &arg.value {
+        Value::String(s) => Ok(s.as_ref()),
+        _ => arg.span.error(message).err(),
+    }
+}
+
+/// Extract an integer argument, or report the message at the argument.
+pub(crate) fn int_arg(arg: &CallArg<Value>, message: &'static str) -> Result<i128> {
+    match &arg.value {
+        Value::Int(i) => Ok(*i),
+        _ => arg.span.error(message).err(),
+    }
+}
+
 builtin_function!(
     "std.read_file_utf8",
     (path: String) -> String,
@@ -50,8 +69,8 @@ builtin_function!(
     const STD_RANGE,
     builtin_std_range
 );
-fn builtin_std_range(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
-    let lower: i64 = match &call.args[0].value {
+pub(crate) fn builtin_std_range(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let lower: i128 = match &call.args[0].value {
         Value::Int(i) => *i,
         _not_string => {
             // TODO: Add proper typechecking and a proper type error.
@@ -61,7 +80,7 @@ fn builtin_std_range(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value>
                 .err();
         }
     };
-    let upper: i64 = match &call.args[1].value {
+    let upper: i128 = match &call.args[1].value {
         Value::Int(i) => *i,
         _not_string => {
             // TODO: Add proper typechecking and a proper type error.
@@ -104,8 +123,33 @@ fn builtin_std_range(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value>
     Ok(Value::List(Rc::new(values)))
 }
 
-/// Initialize the standard library.
+thread_local! {
+    /// The single instance of the standard library, see [`initialize`].
+    static STDLIB: Value = build_stdlib();
+}
+
+/// Return the standard library.
+///
+/// Every call returns the same instance, which is what makes [`is_module`]
+/// possible.
 pub fn initialize() -> Value {
+    STDLIB.with(|stdlib| stdlib.clone())
+}
+
+/// Return whether the value is one of the modules in the standard library.
+///
+/// Modules such as `std.string` are dicts, but when evaluating a field access
+/// on them, their members take precedence over the methods of `Dict`.
+pub fn is_module(value: &Value) -> bool {
+    STDLIB.with(|stdlib| {
+        stdlib
+            .expect_dict()
+            .values()
+            .any(|member| member.is_same_collection(value))
+    })
+}
+
+fn build_stdlib() -> Value {
     let mut builtins: BTreeMap<Value, Value> = BTreeMap::new();
 
     builtins.insert("empty_set".into(), Value::Set(Rc::new(BTreeSet::new())));
@@ -114,6 +158,17 @@ pub fn initialize() -> Value {
         "read_file_utf8".into(),
         Value::BuiltinFunction(&STD_READ_FILE_UTF8),
     );
+    builtins.insert("csv".into(), crate::stdlib_csv::initialize());
+    builtins.insert("datetime".into(), crate::stdlib_datetime::initialize());
+    builtins.insert("dict".into(), crate::stdlib_dict::initialize());
+    builtins.insert("encode".into(), crate::stdlib_encode::initialize());
+    builtins.insert("float".into(), crate::stdlib_float::initialize());
+    builtins.insert("hash".into(), crate::stdlib_hash::initialize());
+    builtins.insert("int".into(), crate::stdlib_int::initialize());
+    builtins.insert("json".into(), crate::stdlib_json::initialize());
+    builtins.insert("list".into(), crate::stdlib_list::initialize());
+    builtins.insert("set".into(), crate::stdlib_set::initialize());
+    builtins.insert("string".into(), crate::stdlib_string::initialize());
 
     Value::Dict(Rc::new(builtins))
 }
@@ -603,11 +658,16 @@ fn builtin_set_filter(eval: &mut Evaluator, call: MethodCall) -> Result<Value> {
     Ok(Value::Set(Rc::new(result)))
 }
 
-fn builtin_sum_impl<'a>(
-    call: MethodCall,
+/// Shared implementation of `{List,Set}.sum` and `std.int.sum`.
+///
+/// Overflow is reported at `op_span`, elements that are not integers at
+/// `xs_span`.
+pub(crate) fn builtin_sum_impl<'a>(
+    op_span: Span,
+    xs_span: Span,
     xs: impl IntoIterator<Item = &'a Value>,
 ) -> Result<Value> {
-    let mut acc: i64 = 0;
+    let mut acc: i128 = 0;
     for x in xs {
         match x {
             Value::Int(n) => match acc.checked_add(*n) {
@@ -616,14 +676,14 @@ fn builtin_sum_impl<'a>(
                     let err = concat! {
                         "Addition " acc.to_string() " + " n.to_string() " would overflow."
                     };
-                    return call.method_span.error(err).err();
+                    return op_span.error(err).err();
                 }
             },
             not_int => {
                 let err = concat! {
                     "Expected integers to add, but found " format_rcl(not_int).into_owned() "."
                 };
-                return call.receiver_span.error(err).err();
+                return xs_span.error(err).err();
             }
         }
     }
@@ -634,13 +694,13 @@ fn builtin_sum_impl<'a>(
 builtin_method!("List.sum", () -> Int, const LIST_SUM, builtin_list_sum);
 fn builtin_list_sum(_eval: &mut Evaluator, call: MethodCall) -> Result<Value> {
     let list = call.receiver.expect_list();
-    builtin_sum_impl(call, list)
+    builtin_sum_impl(call.method_span, call.receiver_span, list)
 }
 
 builtin_method!("Set.sum", () -> Int, const SET_SUM, builtin_set_sum);
 fn builtin_set_sum(_eval: &mut Evaluator, call: MethodCall) -> Result<Value> {
     let set = call.receiver.expect_set();
-    builtin_sum_impl(call, set)
+    builtin_sum_impl(call.method_span, call.receiver_span, set)
 }
 
 /// Which function to implement in [`builtin_any_all_impl`].
@@ -832,7 +892,7 @@ fn builtin_string_parse_int(_eval: &mut Evaluator, call: MethodCall) -> Result<V
 
     let string = call.receiver.expect_string();
 
-    match i64::from_str(string) {
+    match i128::from_str(string) {
         Ok(i) => Ok(Value::Int(i)),
         Err(..) => call
             .receiver_span
@@ -1138,3 +1198,56 @@ fn builtin_list_enumerate(_eval: &mut Evaluator, call: MethodCall) -> Result<Val
         .collect();
     Ok(Value::Dict(Rc::new(kv)))
 }
+
+/// All builtin methods, ordered by receiver type and name.
+///
+/// The evaluator resolves methods with a match on the receiver and field name,
+/// this list is for tooling that needs to enumerate them, such as completion
+/// in the language server.
+pub const METHODS: &[&BuiltinMethod] = &[
+    &DICT_CONTAINS,
+    &DICT_EXCEPT,
+    &DICT_GET,
+    &DICT_KEYS,
+    &DICT_LEN,
+    &DICT_VALUES,
+    &LIST_ALL,
+    &LIST_ANY,
+    &LIST_CONTAINS,
+    &LIST_ENUMERATE,
+    &LIST_FILTER,
+    &LIST_FLAT_MAP,
+    &LIST_FOLD,
+    &LIST_GROUP_BY,
+    &LIST_JOIN,
+    &LIST_KEY_BY,
+    &LIST_LEN,
+    &LIST_MAP,
+    &LIST_REVERSE,
+    &LIST_SORT,
+    &LIST_SUM,
+    &SET_ALL,
+    &SET_ANY,
+    &SET_CONTAINS,
+    &SET_EXCEPT,
+    &SET_FILTER,
+    &SET_FLAT_MAP,
+    &SET_GROUP_BY,
+    &SET_KEY_BY,
+    &SET_LEN,
+    &SET_MAP,
+    &SET_SUM,
+    &STRING_CHARS,
+    &STRING_CONTAINS,
+    &STRING_ENDS_WITH,
+    &STRING_LEN,
+    &STRING_PARSE_INT,
+    &STRING_REMOVE_PREFIX,
+    &STRING_REMOVE_SUFFIX,
+    &STRING_REPLACE,
+    &STRING_SPLIT,
+    &STRING_SPLIT_LINES,
+    &STRING_STARTS_WITH,
+    &STRING_TO_LOWERCASE,
+    &STRING_TO_UPPERCASE,
+];
@@ -0,0 +1,58 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Implementation of the `std.csv` module.
+
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use crate::csv_parser::parse_delimited;
+use crate::error::{IntoError, Result};
+use crate::eval::Evaluator;
+use crate::runtime::{builtin_function, FunctionCall, Value};
+use crate::stdlib::string_arg;
+
+builtin_function!(
+    "std.csv.parse",
+    (csv: String, delimiter: String) -> [{String: String}],
+    const STD_CSV_PARSE,
+    builtin_std_csv_parse
+);
+fn builtin_std_csv_parse(eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let csv = string_arg(&call.args[0], "Expected a String here.")?;
+    let delimiter = string_arg(&call.args[1], "Expected a String here.")?;
+    let delimiter = match delimiter.as_bytes() {
+        [b] if b.is_ascii() && !matches!(b, b'"' | b'\r' | b'\n') => *b,
+        _ => {
+            return call.args[1]
+                .span
+                .error("The delimiter must be a single ASCII character.")
+                .with_help("Quotes and line breaks cannot be used as delimiter.")
+                .err()
+        }
+    };
+    // Load the string as a document of its own, so that syntax errors can
+    // point into the table, rather than only at the argument.
+    let doc = eval.loader.load_named_string("csv", csv.to_string());
+    parse_delimited(doc, eval.loader.get_doc(doc).data, delimiter)
+}
+
+/// Initialize the `std.csv` module.
+pub fn initialize() -> Value {
+    let mut builtins: BTreeMap<Value, Value> = BTreeMap::new();
+
+    let functions = [&STD_CSV_PARSE];
+    for f in functions {
+        let name = f
+            .name
+            .strip_prefix("std.csv.")
+            .expect("Names are prefixed.");
+        builtins.insert(name.into(), Value::BuiltinFunction(f));
+    }
+
+    Value::Dict(Rc::new(builtins))
+}
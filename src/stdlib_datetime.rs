@@ -0,0 +1,570 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Implementation of the `std.datetime` module.
+//!
+//! RCL has no dedicated date or time types. A timestamp is an integer number of
+//! seconds since the Unix epoch, 1970-01-01T00:00:00Z, and a duration is an
+//! integer number of seconds, so the arithmetic operators work on both. All
+//! calendar computations are in UTC, in the proleptic Gregorian calendar, and
+//! ignore leap seconds, like Unix time does. There is deliberately no way to
+//! get the current time, because evaluation must be deterministic.
+
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use crate::ast::CallArg;
+use crate::error::{IntoError, Result};
+use crate::eval::Evaluator;
+use crate::markup::Markup;
+use crate::pprint::{concat, Doc};
+use crate::runtime::{builtin_function, FunctionCall, Value};
+use crate::stdlib::{int_arg, string_arg};
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// Return the number of days since 1970-01-01 of the given date.
+///
+/// This is the `days_from_civil` algorithm from Howard Hinnant's
+/// “chrono-Compatible Low-Level Date Algorithms”.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let m = month as i64;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Return the year, month, and day of the given number of days since 1970-01-01.
+///
+/// This is the inverse of [`days_from_civil`].
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn is_leap_year(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// The smallest supported timestamp, 0000-01-01T00:00:00Z.
+const MIN_TIMESTAMP: i64 = -62_167_219_200;
+
+/// The largest supported timestamp, 9999-12-31T23:59:59Z.
+const MAX_TIMESTAMP: i64 = 253_402_300_799;
+
+/// A timestamp broken down into its calendar components, in UTC.
+#[derive(Debug, Eq, PartialEq)]
+struct Civil {
+    year: i64,
+    month: u32,
+    day: u32,
+    hour: u32,
+    minute: u32,
+    second: u32,
+}
+
+impl Civil {
+    fn from_timestamp(t: i64) -> Civil {
+        let (year, month, day) = civil_from_days(t.div_euclid(SECONDS_PER_DAY));
+        let secs = t.rem_euclid(SECONDS_PER_DAY) as u32;
+        Civil {
+            year,
+            month,
+            day,
+            hour: secs / 3600,
+            minute: secs / 60 % 60,
+            second: secs % 60,
+        }
+    }
+
+    /// The day of the year, starting at 1 for January 1.
+    fn day_of_year(&self) -> u32 {
+        (days_from_civil(self.year, self.month, self.day) - days_from_civil(self.year, 1, 1)) as u32
+            + 1
+    }
+
+    /// The ISO 8601 day of the week, 1 for Monday through 7 for Sunday.
+    fn weekday(&self) -> u32 {
+        // 1970-01-01 was a Thursday.
+        let days = days_from_civil(self.year, self.month, self.day);
+        (days + 3).rem_euclid(7) as u32 + 1
+    }
+}
+
+/// Helper for parsing fixed-width fields of ISO 8601 strings.
+struct Cursor<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, ch: u8) -> bool {
+        if self.peek() == Some(ch) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Parse exactly `n` decimal digits.
+    fn digits(
+        &mut self,
+        n: usize,
+        message: &'static str,
+    ) -> std::result::Result<u32, &'static str> {
+        let mut result = 0;
+        for _ in 0..n {
+            match self.peek() {
+                Some(ch @ b'0'..=b'9') => result = result * 10 + (ch - b'0') as u32,
+                _ => return Err(message),
+            }
+            self.pos += 1;
+        }
+        Ok(result)
+    }
+
+    /// Parse a number in a range, with exactly `n` digits.
+    fn field(
+        &mut self,
+        n: usize,
+        range: std::ops::RangeInclusive<u32>,
+        message: &'static str,
+    ) -> std::result::Result<u32, &'static str> {
+        let value = self.digits(n, message)?;
+        if range.contains(&value) {
+            Ok(value)
+        } else {
+            Err(message)
+        }
+    }
+
+    fn expect(&mut self, ch: u8, message: &'static str) -> std::result::Result<(), &'static str> {
+        if self.eat(ch) {
+            Ok(())
+        } else {
+            Err(message)
+        }
+    }
+}
+
+/// Parse an ISO 8601 date or date-time with UTC offset into a timestamp.
+///
+/// The accepted formats are `YYYY-MM-DD`, which means midnight UTC, and
+/// `YYYY-MM-DDTHH:MM:SS` followed by `Z` or an offset `±HH:MM`, which is the
+/// format of RFC 3339 without fractional seconds.
+fn parse_iso8601(s: &str) -> std::result::Result<i64, &'static str> {
+    let mut c = Cursor {
+        input: s.as_bytes(),
+        pos: 0,
+    };
+    let year = c.digits(4, "Expected a four-digit year.")? as i64;
+    c.expect(b'-', "Expected '-' after the year.")?;
+    let month = c.field(2, 1..=12, "Expected a two-digit month from 01 to 12.")?;
+    c.expect(b'-', "Expected '-' after the month.")?;
+    let day = c.digits(2, "Expected a two-digit day.")?;
+    if day < 1 || day > days_in_month(year, month) {
+        return Err("The day is out of range for the month.");
+    }
+    let mut t = days_from_civil(year, month, day) * SECONDS_PER_DAY;
+
+    if c.peek().is_none() {
+        return Ok(t);
+    }
+
+    c.expect(b'T', "Expected 'T' between the date and the time.")?;
+    let hour = c.field(2, 0..=23, "Expected a two-digit hour from 00 to 23.")?;
+    c.expect(b':', "Expected ':' after the hour.")?;
+    let minute = c.field(2, 0..=59, "Expected a two-digit minute from 00 to 59.")?;
+    c.expect(b':', "Expected ':' after the minute.")?;
+    let second = c.field(2, 0..=59, "Expected a two-digit second from 00 to 59.")?;
+    t += (hour * 3600 + minute * 60 + second) as i64;
+
+    if c.peek() == Some(b'.') {
+        return Err("Fractional seconds are not supported, timestamps are whole seconds.");
+    }
+
+    let sign = match c.peek() {
+        Some(b'Z') => 0,
+        Some(b'+') => -1,
+        Some(b'-') => 1,
+        _ => return Err("Expected a UTC offset, 'Z' or '±HH:MM', after the time."),
+    };
+    c.pos += 1;
+    if sign != 0 {
+        let oh = c.field(2, 0..=23, "Expected a two-digit offset hour from 00 to 23.")?;
+        c.expect(b':', "Expected ':' in the UTC offset.")?;
+        let om = c.field(
+            2,
+            0..=59,
+            "Expected a two-digit offset minute from 00 to 59.",
+        )?;
+        t += sign * (oh * 3600 + om * 60) as i64;
+    }
+
+    if c.peek().is_some() {
+        return Err("Unexpected content after the timestamp.");
+    }
+
+    Ok(t)
+}
+
+/// Parse an ISO 8601 duration such as `P1DT12H` into a number of seconds.
+///
+/// Only weeks, days, hours, minutes, and seconds are supported. Years and
+/// months do not have a fixed number of seconds.
+fn parse_duration(s: &str) -> std::result::Result<i64, &'static str> {
+    let mut c = Cursor {
+        input: s.as_bytes(),
+        pos: 0,
+    };
+    c.expect(b'P', "A duration must start with 'P'.")?;
+
+    // The designators in the order they must appear in, with their length.
+    let units: [(bool, u8, i64); 5] = [
+        (false, b'W', 7 * SECONDS_PER_DAY),
+        (false, b'D', SECONDS_PER_DAY),
+        (true, b'H', 3600),
+        (true, b'M', 60),
+        (true, b'S', 1),
+    ];
+    let mut next_unit = 0;
+    let mut in_time = false;
+    let mut has_component = false;
+    let mut total: i64 = 0;
+
+    while c.peek().is_some() {
+        if c.eat(b'T') {
+            if in_time {
+                return Err("A duration can contain 'T' only once.");
+            }
+            in_time = true;
+            next_unit = units.iter().position(|u| u.0).expect("Has time units.");
+            continue;
+        }
+
+        let start = c.pos;
+        let mut n: i64 = 0;
+        while let Some(ch @ b'0'..=b'9') = c.peek() {
+            n = n
+                .checked_mul(10)
+                .and_then(|n| n.checked_add((ch - b'0') as i64))
+                .ok_or("The duration is too large.")?;
+            c.pos += 1;
+        }
+        if c.pos == start {
+            return Err("Expected a number in the duration.");
+        }
+
+        let designator = c.peek().ok_or("Expected a unit after the number.")?;
+        c.pos += 1;
+        if !in_time && (designator == b'Y' || designator == b'M') {
+            return Err("Years and months have no fixed length, use weeks or days instead.");
+        }
+        let i = units[next_unit..]
+            .iter()
+            .position(|u| u.0 == in_time && u.1 == designator)
+            .map(|i| i + next_unit)
+            .ok_or("Expected W or D before 'T', or H, M, or S after it, in that order.")?;
+        next_unit = i + 1;
+        has_component = true;
+        total = n
+            .checked_mul(units[i].2)
+            .and_then(|x| total.checked_add(x))
+            .ok_or("The duration is too large.")?;
+    }
+
+    if !has_component {
+        return Err("A duration must contain at least one component.");
+    }
+    Ok(total)
+}
+
+/// Format a timestamp with `strftime`-style conversion specifications.
+fn format_timestamp(t: i64, format: &str) -> std::result::Result<String, String> {
+    let civil = Civil::from_timestamp(t);
+    let mut result = String::with_capacity(format.len() + 16);
+    let mut chars = format.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '%' {
+            result.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('Y') => result.push_str(&format!("{:04}", civil.year)),
+            Some('m') => result.push_str(&format!("{:02}", civil.month)),
+            Some('d') => result.push_str(&format!("{:02}", civil.day)),
+            Some('H') => result.push_str(&format!("{:02}", civil.hour)),
+            Some('M') => result.push_str(&format!("{:02}", civil.minute)),
+            Some('S') => result.push_str(&format!("{:02}", civil.second)),
+            Some('j') => result.push_str(&format!("{:03}", civil.day_of_year())),
+            Some('u') => result.push_str(&civil.weekday().to_string()),
+            Some('s') => result.push_str(&t.to_string()),
+            Some('%') => result.push('%'),
+            Some(other) => return Err(format!("Unknown format specifier '%{other}'.")),
+            None => return Err("The format ends in an incomplete '%' specifier.".to_string()),
+        }
+    }
+    Ok(result)
+}
+
+/// Extract a timestamp argument, and check that it is in the supported range.
+fn timestamp_arg(arg: &CallArg<Value>) -> Result<i64> {
+    let t = int_arg(arg, "Expected a timestamp, an Int, here.")?;
+    match i64::try_from(t) {
+        Ok(t) if (MIN_TIMESTAMP..=MAX_TIMESTAMP).contains(&t) => Ok(t),
+        _ => arg
+            .span
+            .error(concat! {
+                "Timestamp "
+                Doc::string(t.to_string()).with_markup(Markup::Number)
+                " is outside of the supported range of years 0000 through 9999."
+            })
+            .err(),
+    }
+}
+
+builtin_function!(
+    "std.datetime.parse_iso8601",
+    (string: String) -> Int,
+    const STD_DATETIME_PARSE_ISO8601,
+    builtin_std_datetime_parse_iso8601
+);
+fn builtin_std_datetime_parse_iso8601(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let arg = &call.args[0];
+    let string = string_arg(arg, "Expected a String here.")?;
+    match parse_iso8601(string) {
+        Ok(t) => Ok(Value::Int(t.into())),
+        Err(message) => arg.span.error(message).err(),
+    }
+}
+
+builtin_function!(
+    "std.datetime.parse_duration",
+    (string: String) -> Int,
+    const STD_DATETIME_PARSE_DURATION,
+    builtin_std_datetime_parse_duration
+);
+fn builtin_std_datetime_parse_duration(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let arg = &call.args[0];
+    let string = string_arg(arg, "Expected a String here.")?;
+    match parse_duration(string) {
+        Ok(t) => Ok(Value::Int(t.into())),
+        Err(message) => arg.span.error(message).err(),
+    }
+}
+
+builtin_function!(
+    "std.datetime.format",
+    (timestamp: Int, format: String) -> String,
+    const STD_DATETIME_FORMAT,
+    builtin_std_datetime_format
+);
+fn builtin_std_datetime_format(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let t = timestamp_arg(&call.args[0])?;
+    let format = string_arg(&call.args[1], "Expected a format String here.")?;
+    match format_timestamp(t, format) {
+        Ok(result) => Ok(Value::String(result.into())),
+        Err(message) => call.args[1].span.error(message).err(),
+    }
+}
+
+/// Define a builtin that returns one component of a timestamp.
+macro_rules! component_function {
+    ($name:literal, $const_name:ident, $fn_name:ident, $component:expr) => {
+        builtin_function!(
+            $name,
+            (timestamp: Int) -> Int,
+            const $const_name,
+            $fn_name
+        );
+        fn $fn_name(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+            let t = timestamp_arg(&call.args[0])?;
+            let component: fn(&Civil) -> i64 = $component;
+            Ok(Value::Int(component(&Civil::from_timestamp(t)).into()))
+        }
+    };
+}
+
+component_function!(
+    "std.datetime.year",
+    STD_DATETIME_YEAR,
+    builtin_std_datetime_year,
+    |c| c.year
+);
+component_function!(
+    "std.datetime.month",
+    STD_DATETIME_MONTH,
+    builtin_std_datetime_month,
+    |c| c.month as i64
+);
+component_function!(
+    "std.datetime.day",
+    STD_DATETIME_DAY,
+    builtin_std_datetime_day,
+    |c| c.day as i64
+);
+component_function!(
+    "std.datetime.hour",
+    STD_DATETIME_HOUR,
+    builtin_std_datetime_hour,
+    |c| c.hour as i64
+);
+component_function!(
+    "std.datetime.minute",
+    STD_DATETIME_MINUTE,
+    builtin_std_datetime_minute,
+    |c| c.minute as i64
+);
+component_function!(
+    "std.datetime.second",
+    STD_DATETIME_SECOND,
+    builtin_std_datetime_second,
+    |c| c.second as i64
+);
+component_function!(
+    "std.datetime.weekday",
+    STD_DATETIME_WEEKDAY,
+    builtin_std_datetime_weekday,
+    |c| c.weekday() as i64
+);
+
+/// Initialize the `std.datetime` module.
+pub fn initialize() -> Value {
+    let mut builtins: BTreeMap<Value, Value> = BTreeMap::new();
+
+    let functions = [
+        &STD_DATETIME_DAY,
+        &STD_DATETIME_FORMAT,
+        &STD_DATETIME_HOUR,
+        &STD_DATETIME_MINUTE,
+        &STD_DATETIME_MONTH,
+        &STD_DATETIME_PARSE_DURATION,
+        &STD_DATETIME_PARSE_ISO8601,
+        &STD_DATETIME_SECOND,
+        &STD_DATETIME_WEEKDAY,
+        &STD_DATETIME_YEAR,
+    ];
+    for f in functions {
+        let name = f
+            .name
+            .strip_prefix("std.datetime.")
+            .expect("Names are prefixed.");
+        builtins.insert(name.into(), Value::BuiltinFunction(f));
+    }
+
+    // Durations are plain integers, these constants make them readable.
+    builtins.insert("seconds_per_minute".into(), Value::Int(60));
+    builtins.insert("seconds_per_hour".into(), Value::Int(3600));
+    builtins.insert("seconds_per_day".into(), Value::Int(SECONDS_PER_DAY.into()));
+
+    Value::Dict(Rc::new(builtins))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        civil_from_days, days_from_civil, format_timestamp, parse_duration, parse_iso8601, Civil,
+        MAX_TIMESTAMP, MIN_TIMESTAMP,
+    };
+
+    #[test]
+    fn civil_conversion_roundtrips() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11_017);
+        assert_eq!(days_from_civil(1969, 12, 31), -1);
+        for days in (-800_000..3_000_000).step_by(997) {
+            let (y, m, d) = civil_from_days(days);
+            assert_eq!(days_from_civil(y, m, d), days);
+        }
+        assert_eq!(Civil::from_timestamp(MIN_TIMESTAMP).year, 0);
+        assert_eq!(
+            Civil::from_timestamp(MAX_TIMESTAMP),
+            Civil {
+                year: 9999,
+                month: 12,
+                day: 31,
+                hour: 23,
+                minute: 59,
+                second: 59
+            }
+        );
+    }
+
+    #[test]
+    fn parse_iso8601_handles_offsets() {
+        assert_eq!(parse_iso8601("1970-01-01"), Ok(0));
+        assert_eq!(parse_iso8601("2024-02-29T12:30:00Z"), Ok(1_709_209_800));
+        assert_eq!(
+            parse_iso8601("2024-02-29T14:30:00+02:00"),
+            Ok(1_709_209_800)
+        );
+        assert_eq!(
+            parse_iso8601("2024-02-29T07:00:00-05:30"),
+            Ok(1_709_209_800)
+        );
+        assert!(parse_iso8601("2023-02-29").is_err());
+        assert!(parse_iso8601("2024-13-01").is_err());
+        assert!(parse_iso8601("2024-01-01T00:00:00").is_err());
+        assert!(parse_iso8601("2024-01-01T00:00:00.5Z").is_err());
+        assert!(parse_iso8601("2024-01-01T24:00:00Z").is_err());
+        assert!(parse_iso8601("2024-01-01Z").is_err());
+    }
+
+    #[test]
+    fn parse_duration_accepts_ordered_components() {
+        assert_eq!(parse_duration("PT30S"), Ok(30));
+        assert_eq!(parse_duration("PT1H30M"), Ok(5400));
+        assert_eq!(parse_duration("P1W2DT3H"), Ok(9 * 86_400 + 3 * 3600));
+        assert!(parse_duration("P").is_err());
+        assert!(parse_duration("PT").is_err());
+        assert!(parse_duration("P1M").is_err());
+        assert!(parse_duration("PT1M1H").is_err());
+        assert!(parse_duration("PT1H").is_ok());
+        assert!(parse_duration("P1H").is_err());
+        assert!(parse_duration("P99999999999999999999D").is_err());
+    }
+
+    #[test]
+    fn format_timestamp_supports_specifiers() {
+        let t = 1_709_209_800;
+        assert_eq!(
+            format_timestamp(t, "%Y-%m-%dT%H:%M:%SZ"),
+            Ok("2024-02-29T12:30:00Z".to_string())
+        );
+        assert_eq!(
+            format_timestamp(t, "%j %u %s %%"),
+            Ok("060 4 1709209800 %".to_string())
+        );
+        assert!(format_timestamp(t, "%q").is_err());
+        assert!(format_timestamp(t, "100%").is_err());
+    }
+}
@@ -0,0 +1,160 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Implementation of the `std.dict` module.
+//!
+//! Dicts iterate in the order of their keys, so `keys`, `values`, and
+//! `to_pairs` all return their elements in that order, and the results line up
+//! with each other.
+
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use crate::ast::CallArg;
+use crate::error::{IntoError, Result};
+use crate::eval::Evaluator;
+use crate::markup::Markup;
+use crate::pprint::{concat, Doc};
+use crate::runtime::{builtin_function, FunctionCall, Value};
+
+/// Extract a dict argument, or report an error at the argument.
+fn dict_arg(arg: &CallArg<Value>) -> Result<&BTreeMap<Value, Value>> {
+    match &arg.value {
+        Value::Dict(kv) => Ok(kv),
+        _ => arg.span.error("Expected a Dict here.").err(),
+    }
+}
+
+builtin_function!(
+    "std.dict.get",
+    (dict: {Any: Any}, key: Any, default: Any) -> Any,
+    const STD_DICT_GET,
+    builtin_std_dict_get
+);
+fn builtin_std_dict_get(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let dict = dict_arg(&call.args[0])?;
+    let key = &call.args[1].value;
+    let default = &call.args[2].value;
+    match dict.get(key) {
+        Some(v) => Ok(v.clone()),
+        None => Ok(default.clone()),
+    }
+}
+
+builtin_function!(
+    "std.dict.contains_key",
+    (dict: {Any: Any}, key: Any) -> Bool,
+    const STD_DICT_CONTAINS_KEY,
+    builtin_std_dict_contains_key
+);
+fn builtin_std_dict_contains_key(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let dict = dict_arg(&call.args[0])?;
+    Ok(Value::Bool(dict.contains_key(&call.args[1].value)))
+}
+
+builtin_function!(
+    "std.dict.keys",
+    (dict: {Any: Any}) -> [Any],
+    const STD_DICT_KEYS,
+    builtin_std_dict_keys
+);
+fn builtin_std_dict_keys(eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let dict = dict_arg(&call.args[0])?;
+    eval.eval_count.inc_by(call.call_open, dict.len())?;
+    let keys: Vec<Value> = dict.keys().cloned().collect();
+    Ok(Value::List(Rc::new(keys)))
+}
+
+builtin_function!(
+    "std.dict.values",
+    (dict: {Any: Any}) -> [Any],
+    const STD_DICT_VALUES,
+    builtin_std_dict_values
+);
+fn builtin_std_dict_values(eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let dict = dict_arg(&call.args[0])?;
+    eval.eval_count.inc_by(call.call_open, dict.len())?;
+    let values: Vec<Value> = dict.values().cloned().collect();
+    Ok(Value::List(Rc::new(values)))
+}
+
+builtin_function!(
+    "std.dict.to_pairs",
+    (dict: {Any: Any}) -> [[Any]],
+    const STD_DICT_TO_PAIRS,
+    builtin_std_dict_to_pairs
+);
+fn builtin_std_dict_to_pairs(eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let dict = dict_arg(&call.args[0])?;
+    eval.eval_count.inc_by(call.call_open, dict.len())?;
+    let pairs: Vec<Value> = dict
+        .iter()
+        .map(|(k, v)| Value::List(Rc::new(vec![k.clone(), v.clone()])))
+        .collect();
+    Ok(Value::List(Rc::new(pairs)))
+}
+
+builtin_function!(
+    "std.dict.from_pairs",
+    (pairs: [[Any]]) -> {Any: Any},
+    const STD_DICT_FROM_PAIRS,
+    builtin_std_dict_from_pairs
+);
+fn builtin_std_dict_from_pairs(eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let arg = &call.args[0];
+    let pairs = match &arg.value {
+        Value::List(pairs) => pairs,
+        _ => return arg.span.error("Expected a List of pairs here.").err(),
+    };
+    eval.eval_count.inc_by(call.call_open, pairs.len())?;
+
+    // As in a dict comprehension, when a key occurs more than once, the last
+    // occurrence wins.
+    let mut result = BTreeMap::new();
+    for (i, pair) in pairs.iter().enumerate() {
+        match pair {
+            Value::List(kv) if kv.len() == 2 => {
+                result.insert(kv[0].clone(), kv[1].clone());
+            }
+            _ => {
+                return arg
+                    .span
+                    .error(concat! {
+                        "Expected a List of [key, value] pairs, but the element at index "
+                        Doc::string(i.to_string()).with_markup(Markup::Number)
+                        " is not a list of two elements."
+                    })
+                    .err()
+            }
+        }
+    }
+
+    Ok(Value::Dict(Rc::new(result)))
+}
+
+/// Initialize the `std.dict` module.
+pub fn initialize() -> Value {
+    let mut builtins: BTreeMap<Value, Value> = BTreeMap::new();
+
+    let functions = [
+        &STD_DICT_CONTAINS_KEY,
+        &STD_DICT_FROM_PAIRS,
+        &STD_DICT_GET,
+        &STD_DICT_KEYS,
+        &STD_DICT_TO_PAIRS,
+        &STD_DICT_VALUES,
+    ];
+    for f in functions {
+        let name = f
+            .name
+            .strip_prefix("std.dict.")
+            .expect("Names are prefixed.");
+        builtins.insert(name.into(), Value::BuiltinFunction(f));
+    }
+
+    Value::Dict(Rc::new(builtins))
+}
@@ -0,0 +1,300 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Implementation of the `std.encode` module.
+//!
+//! RCL has no byte strings, so the encoders encode the UTF-8 bytes of a string,
+//! and the decoders report an error when the decoded bytes are not valid UTF-8.
+
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use crate::error::{IntoError, Result};
+use crate::eval::Evaluator;
+use crate::runtime::{builtin_function, FunctionCall, Value};
+use crate::stdlib::string_arg;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+/// Encode bytes as base64 with the standard alphabet and padding (RFC 4648).
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut result = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (n >> (18 - 6 * i)) & 0x3f;
+                result.push(BASE64_ALPHABET[index as usize] as char);
+            } else {
+                result.push('=');
+            }
+        }
+    }
+    result
+}
+
+/// Decode standard base64 with padding, the inverse of [`encode_base64`].
+fn decode_base64(s: &str) -> std::result::Result<Vec<u8>, &'static str> {
+    let s = s.as_bytes();
+    if s.len() % 4 != 0 {
+        return Err("The length of base64 data must be a multiple of 4.");
+    }
+    let mut result = Vec::with_capacity(s.len() / 4 * 3);
+    for (i, chunk) in s.chunks(4).enumerate() {
+        let is_last = (i + 1) * 4 == s.len();
+        let n_pad = chunk.iter().rev().take_while(|ch| **ch == b'=').count();
+        if n_pad > 2 || (n_pad > 0 && !is_last) {
+            return Err("Padding is only allowed at the end of base64 data.");
+        }
+        let mut n: u32 = 0;
+        for &ch in &chunk[..4 - n_pad] {
+            let digit = match BASE64_ALPHABET.iter().position(|a| *a == ch) {
+                Some(d) => d as u32,
+                None => return Err("Invalid character in base64 data."),
+            };
+            n = n << 6 | digit;
+        }
+        n <<= 6 * n_pad as u32;
+        let decoded = [(n >> 16) as u8, (n >> 8) as u8, n as u8];
+        result.extend_from_slice(&decoded[..3 - n_pad]);
+    }
+    Ok(result)
+}
+
+/// Encode bytes as lowercase hexadecimal, two digits per byte.
+pub(crate) fn encode_hex(bytes: &[u8]) -> String {
+    let mut result = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        result.push(HEX_DIGITS[(b >> 4) as usize] as char);
+        result.push(HEX_DIGITS[(b & 0xf) as usize] as char);
+    }
+    result
+}
+
+/// Return the value of a hexadecimal digit, case-insensitive.
+fn hex_digit_value(ch: u8) -> Option<u8> {
+    (ch as char).to_digit(16).map(|d| d as u8)
+}
+
+/// Decode hexadecimal with two digits per byte, the inverse of [`encode_hex`].
+fn decode_hex(s: &str) -> std::result::Result<Vec<u8>, &'static str> {
+    let s = s.as_bytes();
+    if s.len() % 2 != 0 {
+        return Err("Hexadecimal data must have an even number of digits.");
+    }
+    s.chunks(2)
+        .map(
+            |pair| match (hex_digit_value(pair[0]), hex_digit_value(pair[1])) {
+                (Some(hi), Some(lo)) => Ok(hi << 4 | lo),
+                _ => Err("Invalid character in hexadecimal data."),
+            },
+        )
+        .collect()
+}
+
+/// Percent-encode all bytes except the unreserved characters of RFC 3986.
+fn encode_url(bytes: &[u8]) -> String {
+    let mut result = String::with_capacity(bytes.len());
+    for &b in bytes {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                result.push(b as char)
+            }
+            _ => {
+                result.push('%');
+                result.push(HEX_DIGITS[(b >> 4) as usize].to_ascii_uppercase() as char);
+                result.push(HEX_DIGITS[(b & 0xf) as usize].to_ascii_uppercase() as char);
+            }
+        }
+    }
+    result
+}
+
+/// Decode percent-escapes, the inverse of [`encode_url`].
+///
+/// Characters other than escapes are kept as-is, in particular `+` is not
+/// decoded as space, because that is specific to HTML form encoding.
+fn decode_url(s: &str) -> std::result::Result<Vec<u8>, &'static str> {
+    let s = s.as_bytes();
+    let mut result = Vec::with_capacity(s.len());
+    let mut i = 0;
+    while i < s.len() {
+        if s[i] == b'%' {
+            let hi = s.get(i + 1).copied().and_then(hex_digit_value);
+            let lo = s.get(i + 2).copied().and_then(hex_digit_value);
+            match (hi, lo) {
+                (Some(hi), Some(lo)) => result.push(hi << 4 | lo),
+                _ => return Err("A '%' must be followed by two hexadecimal digits."),
+            }
+            i += 3;
+        } else {
+            result.push(s[i]);
+            i += 1;
+        }
+    }
+    Ok(result)
+}
+
+/// Shared implementation of the decoding functions.
+fn decode_impl(
+    call: FunctionCall,
+    decode: fn(&str) -> std::result::Result<Vec<u8>, &'static str>,
+) -> Result<Value> {
+    let arg = &call.args[0];
+    let encoded = string_arg(arg, "Expected a String here.")?;
+    let bytes = match decode(encoded) {
+        Ok(bytes) => bytes,
+        Err(message) => return arg.span.error(message).err(),
+    };
+    match String::from_utf8(bytes) {
+        Ok(decoded) => Ok(Value::String(decoded.into())),
+        Err(..) => arg
+            .span
+            .error("The decoded data is not valid UTF-8, it cannot be represented as a string.")
+            .err(),
+    }
+}
+
+builtin_function!(
+    "std.encode.base64",
+    (string: String) -> String,
+    const STD_ENCODE_BASE64,
+    builtin_std_encode_base64
+);
+fn builtin_std_encode_base64(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let string = string_arg(&call.args[0], "Expected a String here.")?;
+    Ok(Value::String(encode_base64(string.as_bytes()).into()))
+}
+
+builtin_function!(
+    "std.encode.base64_decode",
+    (encoded: String) -> String,
+    const STD_ENCODE_BASE64_DECODE,
+    builtin_std_encode_base64_decode
+);
+fn builtin_std_encode_base64_decode(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    decode_impl(call, decode_base64)
+}
+
+builtin_function!(
+    "std.encode.hex",
+    (string: String) -> String,
+    const STD_ENCODE_HEX,
+    builtin_std_encode_hex
+);
+fn builtin_std_encode_hex(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let string = string_arg(&call.args[0], "Expected a String here.")?;
+    Ok(Value::String(encode_hex(string.as_bytes()).into()))
+}
+
+builtin_function!(
+    "std.encode.hex_decode",
+    (encoded: String) -> String,
+    const STD_ENCODE_HEX_DECODE,
+    builtin_std_encode_hex_decode
+);
+fn builtin_std_encode_hex_decode(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    decode_impl(call, decode_hex)
+}
+
+builtin_function!(
+    "std.encode.url_encode",
+    (string: String) -> String,
+    const STD_ENCODE_URL_ENCODE,
+    builtin_std_encode_url_encode
+);
+fn builtin_std_encode_url_encode(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let string = string_arg(&call.args[0], "Expected a String here.")?;
+    Ok(Value::String(encode_url(string.as_bytes()).into()))
+}
+
+builtin_function!(
+    "std.encode.url_decode",
+    (encoded: String) -> String,
+    const STD_ENCODE_URL_DECODE,
+    builtin_std_encode_url_decode
+);
+fn builtin_std_encode_url_decode(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    decode_impl(call, decode_url)
+}
+
+/// Initialize the `std.encode` module.
+pub fn initialize() -> Value {
+    let mut builtins: BTreeMap<Value, Value> = BTreeMap::new();
+
+    let functions = [
+        &STD_ENCODE_BASE64,
+        &STD_ENCODE_BASE64_DECODE,
+        &STD_ENCODE_HEX,
+        &STD_ENCODE_HEX_DECODE,
+        &STD_ENCODE_URL_DECODE,
+        &STD_ENCODE_URL_ENCODE,
+    ];
+    for f in functions {
+        let name = f
+            .name
+            .strip_prefix("std.encode.")
+            .expect("Names are prefixed.");
+        builtins.insert(name.into(), Value::BuiltinFunction(f));
+    }
+
+    Value::Dict(Rc::new(builtins))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode_base64, decode_hex, decode_url, encode_base64, encode_hex, encode_url};
+
+    #[test]
+    fn base64_matches_rfc_4648_test_vectors() {
+        let vectors = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (plain, encoded) in vectors {
+            assert_eq!(encode_base64(plain.as_bytes()), encoded);
+            assert_eq!(decode_base64(encoded), Ok(plain.as_bytes().to_vec()));
+        }
+        assert_eq!(decode_base64("/+8="), Ok(vec![0xff, 0xef]));
+        assert!(decode_base64("Zg").is_err());
+        assert!(decode_base64("Zg==Zm8=").is_err());
+        assert!(decode_base64("Z===").is_err());
+        assert!(decode_base64("Zm9-").is_err());
+    }
+
+    #[test]
+    fn hex_roundtrips() {
+        let bytes = [0x00, 0x7f, 0x80, 0xff];
+        assert_eq!(encode_hex(&bytes), "007f80ff");
+        assert_eq!(decode_hex("007f80ff"), Ok(bytes.to_vec()));
+        assert_eq!(decode_hex("007F80FF"), Ok(bytes.to_vec()));
+        assert!(decode_hex("abc").is_err());
+        assert!(decode_hex("zz").is_err());
+    }
+
+    #[test]
+    fn url_encoding_keeps_unreserved_characters() {
+        assert_eq!(encode_url(b"a-b_c.d~e"), "a-b_c.d~e");
+        assert_eq!(encode_url("a b/c?d=ü".as_bytes()), "a%20b%2Fc%3Fd%3D%C3%BC");
+        assert_eq!(decode_url("a%20b%2fc+d"), Ok(b"a b/c+d".to_vec()));
+        assert!(decode_url("100%").is_err());
+        assert!(decode_url("%g0").is_err());
+    }
+}
@@ -0,0 +1,148 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Implementation of the `std.float` module.
+//!
+//! The functions operate on values of type `Number`, which are decimal rather
+//! than binary floats, see [`Decimal`]. Integers are numbers too, so they are
+//! accepted wherever a number is.
+
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use crate::ast::CallArg;
+use crate::decimal::{Decimal, Rounding};
+use crate::error::{IntoError, Result};
+use crate::eval::Evaluator;
+use crate::fmt_rcl::format_rcl;
+use crate::pprint::concat;
+use crate::runtime::{builtin_function, FunctionCall, Value};
+use crate::stdlib::string_arg;
+
+/// Parse a decimal number, as accepted by `std.float.parse`.
+///
+/// This accepts an optional sign, digits with an optional fraction, and an
+/// optional exponent. Unlike RCL literals, there are no digit separators.
+fn parse_decimal(s: &str) -> Option<Decimal> {
+    let is_digits = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+    let unsigned = s.strip_prefix(['+', '-']).unwrap_or(s);
+    let (significand, exponent) = match unsigned.split_once(['e', 'E']) {
+        Some((significand, exponent)) => (significand, Some(exponent)),
+        None => (unsigned, None),
+    };
+    let (int_part, frac_part) = match significand.split_once('.') {
+        Some((int_part, frac_part)) => (int_part, Some(frac_part)),
+        None => (significand, None),
+    };
+    let is_valid = is_digits(int_part)
+        && frac_part.map_or(true, is_digits)
+        && exponent.map_or(true, |e| is_digits(e.strip_prefix(['+', '-']).unwrap_or(e)));
+    match is_valid {
+        true => Decimal::parse(s),
+        false => None,
+    }
+}
+
+builtin_function!(
+    "std.float.parse",
+    (string: String) -> Number,
+    const STD_FLOAT_PARSE,
+    builtin_std_float_parse
+);
+fn builtin_std_float_parse(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let arg = &call.args[0];
+    let string = string_arg(arg, "Expected a String here.")?;
+    match parse_decimal(string) {
+        Some(d) => Ok(Value::Number(d)),
+        None => arg
+            .span
+            .error("Failed to parse as number:")
+            .with_body(format_rcl(&arg.value).into_owned())
+            .err(),
+    }
+}
+
+builtin_function!(
+    "std.float.try_parse",
+    (string: String) -> Any,
+    const STD_FLOAT_TRY_PARSE,
+    builtin_std_float_try_parse
+);
+fn builtin_std_float_try_parse(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let string = string_arg(&call.args[0], "Expected a String here.")?;
+    match parse_decimal(string) {
+        Some(d) => Ok(Value::Number(d)),
+        None => Ok(Value::Null),
+    }
+}
+
+/// Shared implementation of `std.float.{round,floor,ceil}`.
+fn round_impl(arg: &CallArg<Value>, rounding: Rounding) -> Result<Value> {
+    let x = match arg.value.as_decimal() {
+        Some(x) => x,
+        None => return arg.span.error("Expected a Number here.").err(),
+    };
+    match x.to_int(rounding) {
+        Some(i) => Ok(Value::Int(i)),
+        None => arg
+            .span
+            .error(concat! { "Rounding " x.to_string() " to an integer would overflow." })
+            .err(),
+    }
+}
+
+builtin_function!(
+    "std.float.round",
+    (x: Number) -> Int,
+    const STD_FLOAT_ROUND,
+    builtin_std_float_round
+);
+fn builtin_std_float_round(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    round_impl(&call.args[0], Rounding::HalfEven)
+}
+
+builtin_function!(
+    "std.float.floor",
+    (x: Number) -> Int,
+    const STD_FLOAT_FLOOR,
+    builtin_std_float_floor
+);
+fn builtin_std_float_floor(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    round_impl(&call.args[0], Rounding::Floor)
+}
+
+builtin_function!(
+    "std.float.ceil",
+    (x: Number) -> Int,
+    const STD_FLOAT_CEIL,
+    builtin_std_float_ceil
+);
+fn builtin_std_float_ceil(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    round_impl(&call.args[0], Rounding::Ceil)
+}
+
+/// Initialize the `std.float` module.
+pub fn initialize() -> Value {
+    let mut builtins: BTreeMap<Value, Value> = BTreeMap::new();
+
+    let functions = [
+        &STD_FLOAT_CEIL,
+        &STD_FLOAT_FLOOR,
+        &STD_FLOAT_PARSE,
+        &STD_FLOAT_ROUND,
+        &STD_FLOAT_TRY_PARSE,
+    ];
+    for f in functions {
+        let name = f
+            .name
+            .strip_prefix("std.float.")
+            .expect("Names are prefixed.");
+        builtins.insert(name.into(), Value::BuiltinFunction(f));
+    }
+
+    Value::Dict(Rc::new(builtins))
+}
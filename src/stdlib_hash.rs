@@ -0,0 +1,186 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Implementation of the `std.hash` module.
+//!
+//! Like the encoders in `std.encode`, the hash functions operate on the UTF-8
+//! bytes of a string. Digests are returned as lowercase hexadecimal strings.
+
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use crate::error::Result;
+use crate::eval::Evaluator;
+use crate::runtime::{builtin_function, FunctionCall, Value};
+use crate::stdlib::string_arg;
+use crate::stdlib_encode::encode_hex;
+
+/// The SHA-256 round constants, from section 4.2.2 of FIPS 180-4.
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// The initial SHA-256 hash value, from section 5.3.3 of FIPS 180-4.
+const SHA256_H0: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// Process one 64-byte block, updating the hash state in place.
+fn sha256_compress(state: &mut [u32; 8], block: &[u8]) {
+    debug_assert_eq!(block.len(), 64);
+
+    let mut w = [0_u32; 64];
+    for (i, word) in block.chunks(4).enumerate() {
+        w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+    }
+    for i in 16..64 {
+        let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+        let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+        w[i] = w[i - 16]
+            .wrapping_add(s0)
+            .wrapping_add(w[i - 7])
+            .wrapping_add(s1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+    for i in 0..64 {
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let ch = (e & f) ^ (!e & g);
+        let t1 = h
+            .wrapping_add(s1)
+            .wrapping_add(ch)
+            .wrapping_add(SHA256_K[i])
+            .wrapping_add(w[i]);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let t2 = s0.wrapping_add(maj);
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(t2);
+    }
+
+    for (s, x) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+        *s = s.wrapping_add(x);
+    }
+}
+
+/// Compute the SHA-256 digest of the bytes, as specified by FIPS 180-4.
+fn sha256(bytes: &[u8]) -> [u8; 32] {
+    let mut state = SHA256_H0;
+
+    let mut blocks = bytes.chunks_exact(64);
+    for block in &mut blocks {
+        sha256_compress(&mut state, block);
+    }
+
+    // Pad the remainder with a single 1 bit, zeros, and the message length in
+    // bits, such that the total is a multiple of the block size. This takes
+    // one or two more blocks.
+    let tail = blocks.remainder();
+    let mut last = [0_u8; 128];
+    last[..tail.len()].copy_from_slice(tail);
+    last[tail.len()] = 0x80;
+    let n_last = if tail.len() < 56 { 64 } else { 128 };
+    let len_bits = (bytes.len() as u64).wrapping_mul(8);
+    last[n_last - 8..n_last].copy_from_slice(&len_bits.to_be_bytes());
+    for block in last[..n_last].chunks(64) {
+        sha256_compress(&mut state, block);
+    }
+
+    let mut digest = [0_u8; 32];
+    for (out, s) in digest.chunks_mut(4).zip(state) {
+        out.copy_from_slice(&s.to_be_bytes());
+    }
+    digest
+}
+
+builtin_function!(
+    "std.hash.sha256",
+    (string: String) -> String,
+    const STD_HASH_SHA256,
+    builtin_std_hash_sha256
+);
+fn builtin_std_hash_sha256(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let string = string_arg(&call.args[0], "Expected a String here.")?;
+    Ok(Value::String(encode_hex(&sha256(string.as_bytes())).into()))
+}
+
+/// Initialize the `std.hash` module.
+pub fn initialize() -> Value {
+    let mut builtins: BTreeMap<Value, Value> = BTreeMap::new();
+
+    let functions = [&STD_HASH_SHA256];
+    for f in functions {
+        let name = f
+            .name
+            .strip_prefix("std.hash.")
+            .expect("Names are prefixed.");
+        builtins.insert(name.into(), Value::BuiltinFunction(f));
+    }
+
+    Value::Dict(Rc::new(builtins))
+}
+
+#[cfg(test)]
+mod test {
+    use super::sha256;
+    use crate::stdlib_encode::encode_hex;
+
+    fn sha256_hex(s: &str) -> String {
+        encode_hex(&sha256(s.as_bytes()))
+    }
+
+    #[test]
+    fn sha256_matches_fips_180_test_vectors() {
+        assert_eq!(
+            sha256_hex(""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+        );
+        assert_eq!(
+            sha256_hex("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+        );
+        assert_eq!(
+            sha256_hex("abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+        );
+        assert_eq!(
+            sha256_hex(&"a".repeat(1_000_000)),
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0",
+        );
+    }
+
+    #[test]
+    fn sha256_pads_at_block_boundaries() {
+        // Lengths around 56 and 64 bytes exercise the one and two padding block
+        // cases. The expected digests were computed with `sha256sum`.
+        assert_eq!(
+            sha256_hex(&"a".repeat(55)),
+            "9f4390f8d30c2dd92ec9f095b65e2b9ae9b0a925a5258e241c9f1e910f734318",
+        );
+        assert_eq!(
+            sha256_hex(&"a".repeat(56)),
+            "b35439a4ac6f0948b6d6f9e3c6af0f5f590ce20f1bde7090ef7970686ec6738a",
+        );
+        assert_eq!(
+            sha256_hex(&"a".repeat(64)),
+            "ffe054fe7ae0cb6dc65c3af9b61d5209f439851db43d0ba5997337df154668eb",
+        );
+    }
+}
@@ -0,0 +1,186 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Implementation of the `std.int` module.
+//!
+//! Integers are 64-bit signed, and like the arithmetic operators, these
+//! functions report an error on overflow rather than wrapping around.
+
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use crate::error::{IntoError, Result};
+use crate::eval::Evaluator;
+use crate::fmt_rcl::format_rcl;
+use crate::markup::Markup;
+use crate::pprint::{concat, Doc};
+use crate::runtime::{builtin_function, FunctionCall, Value};
+use crate::stdlib::{builtin_sum_impl, int_arg, string_arg};
+
+/// Parse a decimal integer with optional sign, as accepted by `std.int.parse`.
+fn parse_decimal(s: &str) -> Option<i128> {
+    use std::str::FromStr;
+    i128::from_str(s).ok()
+}
+
+builtin_function!(
+    "std.int.parse",
+    (string: String) -> Int,
+    const STD_INT_PARSE,
+    builtin_std_int_parse
+);
+fn builtin_std_int_parse(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let arg = &call.args[0];
+    let string = string_arg(arg, "Expected a String here.")?;
+    match parse_decimal(string) {
+        Some(i) => Ok(Value::Int(i)),
+        None => arg
+            .span
+            .error("Failed to parse as integer:")
+            .with_body(format_rcl(&arg.value).into_owned())
+            .err(),
+    }
+}
+
+builtin_function!(
+    "std.int.try_parse",
+    (string: String) -> Any,
+    const STD_INT_TRY_PARSE,
+    builtin_std_int_try_parse
+);
+fn builtin_std_int_try_parse(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let string = string_arg(&call.args[0], "Expected a String here.")?;
+    match parse_decimal(string) {
+        Some(i) => Ok(Value::Int(i)),
+        None => Ok(Value::Null),
+    }
+}
+
+builtin_function!(
+    "std.int.abs",
+    (x: Int) -> Int,
+    const STD_INT_ABS,
+    builtin_std_int_abs
+);
+fn builtin_std_int_abs(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let x = int_arg(&call.args[0], "Expected an Int here.")?;
+    match x.checked_abs() {
+        Some(y) => Ok(Value::Int(y)),
+        None => call.args[0]
+            .span
+            .error(concat! { "Absolute value of " x.to_string() " would overflow." })
+            .err(),
+    }
+}
+
+builtin_function!(
+    "std.int.min",
+    (a: Int, b: Int) -> Int,
+    const STD_INT_MIN,
+    builtin_std_int_min
+);
+fn builtin_std_int_min(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let a = int_arg(&call.args[0], "Expected an Int here.")?;
+    let b = int_arg(&call.args[1], "Expected an Int here.")?;
+    Ok(Value::Int(a.min(b)))
+}
+
+builtin_function!(
+    "std.int.max",
+    (a: Int, b: Int) -> Int,
+    const STD_INT_MAX,
+    builtin_std_int_max
+);
+fn builtin_std_int_max(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let a = int_arg(&call.args[0], "Expected an Int here.")?;
+    let b = int_arg(&call.args[1], "Expected an Int here.")?;
+    Ok(Value::Int(a.max(b)))
+}
+
+builtin_function!(
+    "std.int.sum",
+    (xs: [Int]) -> Int,
+    const STD_INT_SUM,
+    builtin_std_int_sum
+);
+fn builtin_std_int_sum(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let arg = &call.args[0];
+    match &arg.value {
+        Value::List(xs) => builtin_sum_impl(arg.span, arg.span, xs.iter()),
+        _ => arg.span.error("Expected a List here.").err(),
+    }
+}
+
+builtin_function!(
+    "std.int.format",
+    (x: Int, width: Int, precision: Int) -> String,
+    const STD_INT_FORMAT,
+    builtin_std_int_format
+);
+fn builtin_std_int_format(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let x = int_arg(&call.args[0], "Expected an Int here.")?;
+    let width = int_arg(&call.args[1], "Width must be an integer.")?;
+    let precision = int_arg(&call.args[2], "Precision must be an integer.")?;
+
+    // Like `std.range`, limit the size so that a single call cannot exhaust
+    // memory, with a lower limit when fuzzing.
+    #[cfg(fuzzing)]
+    let max_len = 500;
+    #[cfg(not(fuzzing))]
+    let max_len = 1_000_000;
+
+    for (arg, n) in [(&call.args[1], width), (&call.args[2], precision)] {
+        if !(0..=max_len).contains(&n) {
+            return arg
+                .span
+                .error(concat! {
+                    "Expected a value between 0 and "
+                    Doc::string(max_len.to_string()).with_markup(Markup::Number)
+                    ", but got "
+                    Doc::string(n.to_string()).with_markup(Markup::Number)
+                    "."
+                })
+                .err();
+        }
+    }
+
+    // The precision is the minimum number of digits, as for `%.*d` in C, and
+    // the width is the minimum length of the result, including the sign.
+    let sign = if x < 0 { "-" } else { "" };
+    let digits = x.unsigned_abs().to_string();
+    let number = format!(
+        "{sign}{digits:0>precision$}",
+        precision = precision as usize
+    );
+    let result = format!("{number:>width$}", width = width as usize);
+
+    Ok(Value::String(result.into()))
+}
+
+/// Initialize the `std.int` module.
+pub fn initialize() -> Value {
+    let mut builtins: BTreeMap<Value, Value> = BTreeMap::new();
+
+    let functions = [
+        &STD_INT_ABS,
+        &STD_INT_FORMAT,
+        &STD_INT_MAX,
+        &STD_INT_MIN,
+        &STD_INT_PARSE,
+        &STD_INT_SUM,
+        &STD_INT_TRY_PARSE,
+    ];
+    for f in functions {
+        let name = f
+            .name
+            .strip_prefix("std.int.")
+            .expect("Names are prefixed.");
+        builtins.insert(name.into(), Value::BuiltinFunction(f));
+    }
+
+    Value::Dict(Rc::new(builtins))
+}
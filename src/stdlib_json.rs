@@ -0,0 +1,81 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Implementation of the `std.json` module.
+
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use crate::error::Result;
+use crate::eval::Evaluator;
+use crate::fmt_json::{format_json, format_json_compact};
+use crate::json_parser::parse_json;
+use crate::pprint::{Config, Doc};
+use crate::runtime::{builtin_function, FunctionCall, Value};
+use crate::stdlib::string_arg;
+
+builtin_function!(
+    "std.json.parse",
+    (json: String) -> Any,
+    const STD_JSON_PARSE,
+    builtin_std_json_parse
+);
+fn builtin_std_json_parse(eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let json = string_arg(&call.args[0], "Expected a String here.")?;
+    // Load the string as a document of its own, so that syntax errors can
+    // point into the json, rather than only at the argument.
+    let doc = eval.loader.load_named_string("json", json.to_string());
+    parse_json(doc, eval.loader.get_doc(doc).data)
+}
+
+/// Render the document to a string, without the trailing newline.
+fn print_to_string(doc: Doc) -> String {
+    let mut result = doc.println(&Config::default()).to_string_no_markup();
+    let n = result.trim_end_matches('\n').len();
+    result.truncate(n);
+    result
+}
+
+builtin_function!(
+    "std.json.encode",
+    (value: Any) -> String,
+    const STD_JSON_ENCODE,
+    builtin_std_json_encode
+);
+fn builtin_std_json_encode(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let arg = &call.args[0];
+    let doc = format_json_compact(arg.span, &arg.value)?;
+    Ok(Value::String(print_to_string(doc).into()))
+}
+
+builtin_function!(
+    "std.json.encode_pretty",
+    (value: Any) -> String,
+    const STD_JSON_ENCODE_PRETTY,
+    builtin_std_json_encode_pretty
+);
+fn builtin_std_json_encode_pretty(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let arg = &call.args[0];
+    let doc = format_json(arg.span, &arg.value)?;
+    Ok(Value::String(print_to_string(doc).into()))
+}
+
+/// Initialize the `std.json` module.
+pub fn initialize() -> Value {
+    let mut builtins: BTreeMap<Value, Value> = BTreeMap::new();
+
+    let functions = [&STD_JSON_ENCODE, &STD_JSON_ENCODE_PRETTY, &STD_JSON_PARSE];
+    for f in functions {
+        let name = f
+            .name
+            .strip_prefix("std.json.")
+            .expect("Names are prefixed.");
+        builtins.insert(name.into(), Value::BuiltinFunction(f));
+    }
+
+    Value::Dict(Rc::new(builtins))
+}
@@ -0,0 +1,161 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Implementation of the `std.list` module.
+//!
+//! Functions that build a new list charge one evaluation step per element of
+//! the result, so that e.g. repeatedly zipping large lists runs into the
+//! evaluation budget, rather than silently using lots of memory and time.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::rc::Rc;
+
+use crate::ast::CallArg;
+use crate::error::{IntoError, Result};
+use crate::eval::Evaluator;
+use crate::markup::Markup;
+use crate::pprint::{concat, Doc};
+use crate::runtime::{builtin_function, FunctionCall, Value};
+
+/// Extract a list argument, or report an error at the argument.
+fn list_arg(arg: &CallArg<Value>) -> Result<&[Value]> {
+    match &arg.value {
+        Value::List(xs) => Ok(xs),
+        _ => arg.span.error("Expected a List here.").err(),
+    }
+}
+
+builtin_function!(
+    "std.list.range",
+    (lower: Int, upper: Int) -> [Int],
+    const STD_LIST_RANGE,
+    builtin_std_list_range
+);
+fn builtin_std_list_range(eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let call_open = call.call_open;
+    // The range implementation already limits the length, so it is fine to
+    // charge the budget after it built the list.
+    let result = crate::stdlib::builtin_std_range(eval, call)?;
+    eval.eval_count
+        .inc_by(call_open, result.expect_list().len())?;
+    Ok(result)
+}
+
+builtin_function!(
+    "std.list.enumerate",
+    (xs: [Any]) -> {Int: Any},
+    const STD_LIST_ENUMERATE,
+    builtin_std_list_enumerate
+);
+fn builtin_std_list_enumerate(eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let xs = list_arg(&call.args[0])?;
+    eval.eval_count.inc_by(call.call_open, xs.len())?;
+    let kv: BTreeMap<_, _> = xs
+        .iter()
+        .zip(0..)
+        .map(|(v, i)| (Value::Int(i), v.clone()))
+        .collect();
+    Ok(Value::Dict(Rc::new(kv)))
+}
+
+builtin_function!(
+    "std.list.zip",
+    (xs: [Any], ys: [Any]) -> [[Any]],
+    const STD_LIST_ZIP,
+    builtin_std_list_zip
+);
+fn builtin_std_list_zip(eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let xs = list_arg(&call.args[0])?;
+    let ys = list_arg(&call.args[1])?;
+
+    // Silently dropping the tail of the longer list would hide mistakes, so
+    // unlike Python, we require the lengths to match.
+    if xs.len() != ys.len() {
+        let args_span = call.args[0].span.union(call.args[1].span);
+        return args_span
+            .error(concat! {
+                "Cannot zip lists of different lengths, "
+                Doc::string(xs.len().to_string()).with_markup(Markup::Number)
+                " and "
+                Doc::string(ys.len().to_string()).with_markup(Markup::Number)
+                "."
+            })
+            .err();
+    }
+
+    eval.eval_count.inc_by(call.call_open, xs.len())?;
+    let result: Vec<Value> = xs
+        .iter()
+        .zip(ys)
+        .map(|(x, y)| Value::List(Rc::new(vec![x.clone(), y.clone()])))
+        .collect();
+    Ok(Value::List(Rc::new(result)))
+}
+
+builtin_function!(
+    "std.list.reverse",
+    (xs: [Any]) -> [Any],
+    const STD_LIST_REVERSE,
+    builtin_std_list_reverse
+);
+fn builtin_std_list_reverse(eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let xs = list_arg(&call.args[0])?;
+    eval.eval_count.inc_by(call.call_open, xs.len())?;
+    let result: Vec<Value> = xs.iter().rev().cloned().collect();
+    Ok(Value::List(Rc::new(result)))
+}
+
+builtin_function!(
+    "std.list.contains",
+    (xs: [Any], element: Any) -> Bool,
+    const STD_LIST_CONTAINS,
+    builtin_std_list_contains
+);
+fn builtin_std_list_contains(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let xs = list_arg(&call.args[0])?;
+    let needle = &call.args[1].value;
+    Ok(Value::Bool(xs.contains(needle)))
+}
+
+builtin_function!(
+    "std.list.unique",
+    (xs: [Any]) -> [Any],
+    const STD_LIST_UNIQUE,
+    builtin_std_list_unique
+);
+fn builtin_std_list_unique(eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let xs = list_arg(&call.args[0])?;
+    eval.eval_count.inc_by(call.call_open, xs.len())?;
+
+    // Keep the first occurrence of every element, in the original order.
+    let mut seen = BTreeSet::new();
+    let result: Vec<Value> = xs.iter().filter(|x| seen.insert(*x)).cloned().collect();
+    Ok(Value::List(Rc::new(result)))
+}
+
+/// Initialize the `std.list` module.
+pub fn initialize() -> Value {
+    let mut builtins: BTreeMap<Value, Value> = BTreeMap::new();
+
+    let functions = [
+        &STD_LIST_CONTAINS,
+        &STD_LIST_ENUMERATE,
+        &STD_LIST_RANGE,
+        &STD_LIST_REVERSE,
+        &STD_LIST_UNIQUE,
+        &STD_LIST_ZIP,
+    ];
+    for f in functions {
+        let name = f
+            .name
+            .strip_prefix("std.list.")
+            .expect("Names are prefixed.");
+        builtins.insert(name.into(), Value::BuiltinFunction(f));
+    }
+
+    Value::Dict(Rc::new(builtins))
+}
@@ -0,0 +1,95 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Implementation of the `std.set` module.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::rc::Rc;
+
+use crate::ast::CallArg;
+use crate::error::{IntoError, Result};
+use crate::eval::Evaluator;
+use crate::runtime::{builtin_function, FunctionCall, Value};
+
+/// Extract a set argument, or report an error at the argument.
+fn set_arg(arg: &CallArg<Value>) -> Result<&BTreeSet<Value>> {
+    match &arg.value {
+        Value::Set(xs) => Ok(xs),
+        _ => arg.span.error("Expected a Set here.").err(),
+    }
+}
+
+builtin_function!(
+    "std.set.len",
+    (set: {Any}) -> Int,
+    const STD_SET_LEN,
+    builtin_std_set_len
+);
+fn builtin_std_set_len(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let set = set_arg(&call.args[0])?;
+    Ok(Value::Int(set.len() as _))
+}
+
+builtin_function!(
+    "std.set.to_sorted_list",
+    (set: {Any}) -> [Any],
+    const STD_SET_TO_SORTED_LIST,
+    builtin_std_set_to_sorted_list
+);
+fn builtin_std_set_to_sorted_list(eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let set = set_arg(&call.args[0])?;
+    eval.eval_count.inc_by(call.call_open, set.len())?;
+    // The set is ordered by the same order that `List.sort` uses, so its
+    // iteration order is already the sorted order.
+    let result: Vec<Value> = set.iter().cloned().collect();
+    Ok(Value::List(Rc::new(result)))
+}
+
+builtin_function!(
+    "std.set.is_subset_of",
+    (set: {Any}, other: {Any}) -> Bool,
+    const STD_SET_IS_SUBSET_OF,
+    builtin_std_set_is_subset_of
+);
+fn builtin_std_set_is_subset_of(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let set = set_arg(&call.args[0])?;
+    let other = set_arg(&call.args[1])?;
+    Ok(Value::Bool(set.is_subset(other)))
+}
+
+builtin_function!(
+    "std.set.is_superset_of",
+    (set: {Any}, other: {Any}) -> Bool,
+    const STD_SET_IS_SUPERSET_OF,
+    builtin_std_set_is_superset_of
+);
+fn builtin_std_set_is_superset_of(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let set = set_arg(&call.args[0])?;
+    let other = set_arg(&call.args[1])?;
+    Ok(Value::Bool(set.is_superset(other)))
+}
+
+/// Initialize the `std.set` module.
+pub fn initialize() -> Value {
+    let mut builtins: BTreeMap<Value, Value> = BTreeMap::new();
+
+    let functions = [
+        &STD_SET_IS_SUBSET_OF,
+        &STD_SET_IS_SUPERSET_OF,
+        &STD_SET_LEN,
+        &STD_SET_TO_SORTED_LIST,
+    ];
+    for f in functions {
+        let name = f
+            .name
+            .strip_prefix("std.set.")
+            .expect("Names are prefixed.");
+        builtins.insert(name.into(), Value::BuiltinFunction(f));
+    }
+
+    Value::Dict(Rc::new(builtins))
+}
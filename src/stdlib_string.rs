@@ -0,0 +1,308 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Implementation of the `std.string` module.
+//!
+//! These functions take the string as their first argument, which makes them
+//! convenient to pass to e.g. `List.map`. Lengths, widths, and characters are
+//! all measured in Unicode code points, the same as `String.len`, and case
+//! conversion and trimming follow the Unicode definitions rather than ASCII.
+
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use crate::error::{IntoError, Result};
+use crate::eval::Evaluator;
+use crate::markup::Markup;
+use crate::pprint::{concat, Doc};
+use crate::runtime::{builtin_function, FunctionCall, Value};
+use crate::stdlib::{int_arg, string_arg};
+
+builtin_function!(
+    "std.string.len",
+    (string: String) -> Int,
+    const STD_STRING_LEN,
+    builtin_std_string_len
+);
+fn builtin_std_string_len(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let string = string_arg(&call.args[0], "Expected a String here.")?;
+    Ok(Value::Int(string.chars().count() as _))
+}
+
+builtin_function!(
+    "std.string.chars",
+    (string: String) -> [String],
+    const STD_STRING_CHARS,
+    builtin_std_string_chars
+);
+fn builtin_std_string_chars(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let string = string_arg(&call.args[0], "Expected a String here.")?;
+    let mut buf = [0; 4];
+    let result: Vec<Value> = string
+        .chars()
+        .map(|ch| Value::from(&*ch.encode_utf8(&mut buf)))
+        .collect();
+    Ok(Value::List(Rc::new(result)))
+}
+
+builtin_function!(
+    "std.string.split",
+    (string: String, separator: String) -> [String],
+    const STD_STRING_SPLIT,
+    builtin_std_string_split
+);
+fn builtin_std_string_split(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let string = string_arg(&call.args[0], "Expected a String here.")?;
+    let sep = string_arg(&call.args[1], "Separator must be a string.")?;
+
+    if sep.is_empty() {
+        return call.args[1]
+            .span
+            .error("Cannot split on empty separator.")
+            .err();
+    }
+
+    let result: Vec<Value> = string.split(sep).map(Value::from).collect();
+    Ok(Value::List(Rc::new(result)))
+}
+
+builtin_function!(
+    "std.string.join",
+    (parts: [String], separator: String) -> String,
+    const STD_STRING_JOIN,
+    builtin_std_string_join
+);
+fn builtin_std_string_join(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let parts_arg = &call.args[0];
+    let parts = match &parts_arg.value {
+        Value::List(parts) => parts,
+        _ => {
+            return parts_arg
+                .span
+                .error("Expected a List of strings here.")
+                .err()
+        }
+    };
+    let sep = string_arg(&call.args[1], "Separator must be a string.")?;
+
+    let mut result = String::new();
+    for (i, part) in parts.iter().enumerate() {
+        let part = match part {
+            Value::String(s) => s.as_ref(),
+            _ => {
+                return parts_arg
+                    .span
+                    .error(concat! {
+                        "Expected a List of strings, but the element at index "
+                        Doc::string(i.to_string()).with_markup(Markup::Number)
+                        " is not a string."
+                    })
+                    .err()
+            }
+        };
+        if i > 0 {
+            result.push_str(sep);
+        }
+        result.push_str(part);
+    }
+
+    Ok(Value::String(result.into()))
+}
+
+builtin_function!(
+    "std.string.trim",
+    (string: String) -> String,
+    const STD_STRING_TRIM,
+    builtin_std_string_trim
+);
+fn builtin_std_string_trim(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let string = string_arg(&call.args[0], "Expected a String here.")?;
+    // Rust's `trim` removes characters with the Unicode `White_Space`
+    // property, which includes e.g. no-break space and ideographic space.
+    Ok(Value::from(string.trim()))
+}
+
+builtin_function!(
+    "std.string.to_upper",
+    (string: String) -> String,
+    const STD_STRING_TO_UPPER,
+    builtin_std_string_to_upper
+);
+fn builtin_std_string_to_upper(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let string = string_arg(&call.args[0], "Expected a String here.")?;
+    Ok(Value::String(string.to_uppercase().into()))
+}
+
+builtin_function!(
+    "std.string.to_lower",
+    (string: String) -> String,
+    const STD_STRING_TO_LOWER,
+    builtin_std_string_to_lower
+);
+fn builtin_std_string_to_lower(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let string = string_arg(&call.args[0], "Expected a String here.")?;
+    Ok(Value::String(string.to_lowercase().into()))
+}
+
+builtin_function!(
+    "std.string.starts_with",
+    (string: String, prefix: String) -> Bool,
+    const STD_STRING_STARTS_WITH,
+    builtin_std_string_starts_with
+);
+fn builtin_std_string_starts_with(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let string = string_arg(&call.args[0], "Expected a String here.")?;
+    let prefix = string_arg(&call.args[1], "Prefix must be a string.")?;
+    Ok(Value::Bool(string.starts_with(prefix)))
+}
+
+builtin_function!(
+    "std.string.ends_with",
+    (string: String, suffix: String) -> Bool,
+    const STD_STRING_ENDS_WITH,
+    builtin_std_string_ends_with
+);
+fn builtin_std_string_ends_with(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let string = string_arg(&call.args[0], "Expected a String here.")?;
+    let suffix = string_arg(&call.args[1], "Suffix must be a string.")?;
+    Ok(Value::Bool(string.ends_with(suffix)))
+}
+
+builtin_function!(
+    "std.string.contains",
+    (string: String, needle: String) -> Bool,
+    const STD_STRING_CONTAINS,
+    builtin_std_string_contains
+);
+fn builtin_std_string_contains(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let string = string_arg(&call.args[0], "Expected a String here.")?;
+    let needle = string_arg(&call.args[1], "Needle must be a string.")?;
+    Ok(Value::Bool(string.contains(needle)))
+}
+
+builtin_function!(
+    "std.string.replace",
+    (string: String, needle: String, replacement: String) -> String,
+    const STD_STRING_REPLACE,
+    builtin_std_string_replace
+);
+fn builtin_std_string_replace(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let string = string_arg(&call.args[0], "Expected a String here.")?;
+    let needle = string_arg(&call.args[1], "Needle must be a string.")?;
+    let replacement = string_arg(&call.args[2], "Replacement must be a string.")?;
+
+    Ok(Value::String(string.replace(needle, replacement).into()))
+}
+
+/// Which side [`pad`] adds the fill characters to.
+#[derive(Copy, Clone)]
+enum Side {
+    Left,
+    Right,
+}
+
+/// Shared implementation of `pad_left` and `pad_right`.
+fn pad(call: FunctionCall, side: Side) -> Result<Value> {
+    let string = string_arg(&call.args[0], "Expected a String here.")?;
+    let width = int_arg(&call.args[1], "Width must be an integer.")?;
+    let fill = string_arg(&call.args[2], "Fill must be a string.")?;
+
+    let mut fill_chars = fill.chars();
+    let fill_char = match (fill_chars.next(), fill_chars.next()) {
+        (Some(ch), None) => ch,
+        _ => {
+            return call.args[2]
+                .span
+                .error("Fill must be a single character.")
+                .err()
+        }
+    };
+
+    let len = string.chars().count() as i128;
+    if width <= len {
+        return Ok(Value::from(string));
+    }
+
+    // Like `std.range`, limit the size so that a single call cannot exhaust
+    // memory, with a lower limit when fuzzing.
+    #[cfg(fuzzing)]
+    let max_width = 500;
+    #[cfg(not(fuzzing))]
+    let max_width = 1_000_000;
+
+    if width > max_width {
+        return call.args[1]
+            .span
+            .error(concat! {
+                "Width "
+                Doc::string(width.to_string()).with_markup(Markup::Number)
+                " exceeds the maximum of "
+                Doc::string(max_width.to_string()).with_markup(Markup::Number)
+                "."
+            })
+            .err();
+    }
+
+    let padding: String = std::iter::repeat(fill_char)
+        .take((width - len) as usize)
+        .collect();
+    let result = match side {
+        Side::Left => padding + string,
+        Side::Right => string.to_string() + &padding,
+    };
+    Ok(Value::String(result.into()))
+}
+
+builtin_function!(
+    "std.string.pad_left",
+    (string: String, width: Int, fill: String) -> String,
+    const STD_STRING_PAD_LEFT,
+    builtin_std_string_pad_left
+);
+fn builtin_std_string_pad_left(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    pad(call, Side::Left)
+}
+
+builtin_function!(
+    "std.string.pad_right",
+    (string: String, width: Int, fill: String) -> String,
+    const STD_STRING_PAD_RIGHT,
+    builtin_std_string_pad_right
+);
+fn builtin_std_string_pad_right(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    pad(call, Side::Right)
+}
+
+/// Initialize the `std.string` module.
+pub fn initialize() -> Value {
+    let mut builtins: BTreeMap<Value, Value> = BTreeMap::new();
+
+    let functions = [
+        &STD_STRING_CHARS,
+        &STD_STRING_CONTAINS,
+        &STD_STRING_ENDS_WITH,
+        &STD_STRING_JOIN,
+        &STD_STRING_LEN,
+        &STD_STRING_PAD_LEFT,
+        &STD_STRING_PAD_RIGHT,
+        &STD_STRING_REPLACE,
+        &STD_STRING_SPLIT,
+        &STD_STRING_STARTS_WITH,
+        &STD_STRING_TO_LOWER,
+        &STD_STRING_TO_UPPER,
+        &STD_STRING_TRIM,
+    ];
+    for f in functions {
+        let name = f
+            .name
+            .strip_prefix("std.string.")
+            .expect("Names are prefixed.");
+        builtins.insert(name.into(), Value::BuiltinFunction(f));
+    }
+
+    Value::Dict(Rc::new(builtins))
+}
@@ -10,6 +10,8 @@
 use crate::cst::StringPart;
 use crate::error::{IntoError, Result};
 use crate::lexer::Escape;
+use crate::markup::Markup;
+use crate::pprint::Doc;
 use crate::source::Span;
 
 /// Convert an escape sequence into the string it represents.
@@ -139,6 +141,71 @@ pub fn escape_json(str: &str, into: &mut String) {
     }
 }
 
+/// Escape a string for use inside a canonical json string literal.
+///
+/// This follows [RFC 8785](https://www.rfc-editor.org/rfc/rfc8785#section-3.2.2.2):
+/// only quotes, backslashes, and control characters below U+0020 are escaped,
+/// using the short form where one exists.
+pub fn escape_json_canonical(str: &str, into: &mut String) {
+    use std::fmt::Write;
+
+    into.reserve(str.len());
+
+    for ch in str.chars() {
+        match ch {
+            '\n' => into.push_str(r#"\n"#),
+            '\r' => into.push_str(r#"\r"#),
+            '\x08' => into.push_str(r#"\b"#),
+            '\x0c' => into.push_str(r#"\f"#),
+            '\t' => into.push_str(r#"\t"#),
+            '\"' => into.push_str(r#"\""#),
+            '\\' => into.push_str(r#"\\"#),
+            ch if ch < '\x20' => write!(into, "\\u{:04x}", ch as u32)
+                .expect("Writing into &mut String does not fail."),
+            ch => into.push(ch),
+        }
+    }
+}
+
+/// Escape a string for use inside a json string literal, as a document.
+///
+/// This escapes the same characters as [`escape_json`], but the escape
+/// sequences get [`Markup::Escape`], so they stand out in highlighted output.
+pub fn escape_json_doc<'a>(str: &str) -> Doc<'a> {
+    escape_doc_with(str, escape_json)
+}
+
+/// Like [`escape_json_doc`], but with [`escape_json_canonical`].
+pub fn escape_json_canonical_doc<'a>(str: &str) -> Doc<'a> {
+    escape_doc_with(str, escape_json_canonical)
+}
+
+fn escape_doc_with<'a>(str: &str, escape: fn(&str, &mut String)) -> Doc<'a> {
+    let mut parts = Vec::new();
+    let mut plain = String::new();
+    let mut escaped = String::new();
+    let mut buf = [0; 4];
+
+    for ch in str.chars() {
+        let ch_str = ch.encode_utf8(&mut buf);
+        escaped.clear();
+        escape(ch_str, &mut escaped);
+        if escaped == *ch_str {
+            plain.push(ch);
+            continue;
+        }
+        if !plain.is_empty() {
+            parts.push(Doc::from(std::mem::take(&mut plain)));
+        }
+        parts.push(Doc::from(escaped.clone()).with_markup(Markup::Escape));
+    }
+    if !plain.is_empty() {
+        parts.push(Doc::from(plain));
+    }
+
+    Doc::Concat(parts)
+}
+
 pub fn count_common_leading_spaces(input: &str, parts: &[StringPart]) -> usize {
     let mut n_spaces = None;
 
@@ -274,6 +341,13 @@ mod test {
         )
     }
 
+    #[test]
+    fn escape_json_canonical_escapes_only_what_is_required() {
+        let mut out = String::new();
+        super::escape_json_canonical("\"\\/\x00\x08\x1f\x7f\u{2028}é", &mut out);
+        assert_eq!(out, "\\\"\\\\/\\u0000\\b\\u001f\x7f\u{2028}é");
+    }
+
     // Note, the main test for json escaping is the `escapes` fuzzer.
 
     #[test]
@@ -320,4 +394,22 @@ mod test {
             2
         );
     }
+
+    #[test]
+    fn escape_json_doc_marks_escape_sequences() {
+        use crate::markup::Markup;
+        use crate::pprint::Config;
+
+        let doc = super::escape_json_doc("a\tb\"\u{1}ü");
+        let cfg = Config::default();
+        let result = doc.println(&cfg);
+        let escapes: Vec<&str> = result
+            .fragments
+            .iter()
+            .filter(|(_, markup)| *markup == Markup::Escape)
+            .map(|(fragment, _)| *fragment)
+            .collect();
+        assert_eq!(escapes, [r"\t", r#"\""#, r"\u0001"]);
+        assert_eq!(result.to_string_no_markup(), "a\\tb\\\"\\u0001ü\n");
+    }
 }
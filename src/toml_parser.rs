@@ -0,0 +1,951 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! A parser for TOML documents.
+//!
+//! This parser follows TOML 1.0, see <https://toml.io/en/v1.0.0>. Tables
+//! become dicts, and arrays of tables become lists of dicts. Floats become
+//! decimal numbers, except for `inf` and `nan`, which RCL cannot represent.
+//! RCL has no date or time type, so offset date-times, local date-times, local
+//! dates, and local times become strings, in the RFC 3339 form in which they
+//! appear in the document.
+//!
+//! TOML forbids defining a key or table twice, and the rules for that are
+//! subtle, because tables can be defined with headers, implicitly as the
+//! parent of a header, and with dotted keys. We build the document as a tree
+//! of [`Table`] that tracks how every table was defined, and convert that
+//! into a value at the end.
+
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use crate::decimal::Decimal;
+use crate::error::{IntoError, Result};
+use crate::runtime::Value;
+use crate::source::{DocId, Span};
+
+/// Parse a TOML document into a value.
+pub fn parse_toml(doc: DocId, input: &str) -> Result<Value> {
+    let mut parser = Parser {
+        doc,
+        input,
+        pos: 0,
+        depth: 0,
+    };
+    let root = parser.parse_document()?;
+    Ok(root.into_value())
+}
+
+/// How a table came into existence, which determines how we can extend it.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Definition {
+    /// As the parent of a table header, such as `a` in `[a.b]`.
+    Implicit,
+    /// By a table header, or as element of an array of tables.
+    Header,
+    /// By a dotted key, such as `a` in `a.b = 1`.
+    Dotted,
+}
+
+enum Node {
+    /// A value that cannot be extended, including arrays and inline tables.
+    Value(Value),
+    Table(Table),
+    /// An array of tables, defined by `[[header]]`.
+    Array(Vec<Table>),
+}
+
+struct Table {
+    entries: BTreeMap<String, Node>,
+    definition: Definition,
+}
+
+impl Table {
+    fn new(definition: Definition) -> Table {
+        Table {
+            entries: BTreeMap::new(),
+            definition,
+        }
+    }
+
+    fn into_value(self) -> Value {
+        let entries = self
+            .entries
+            .into_iter()
+            .map(|(k, node)| {
+                let v = match node {
+                    Node::Value(v) => v,
+                    Node::Table(t) => t.into_value(),
+                    Node::Array(ts) => {
+                        Value::List(Rc::new(ts.into_iter().map(Table::into_value).collect()))
+                    }
+                };
+                (Value::String(k.into()), v)
+            })
+            .collect();
+        Value::Dict(Rc::new(entries))
+    }
+}
+
+/// A key as written in the document, with its span for error reporting.
+struct Key {
+    parts: Vec<(String, Span)>,
+}
+
+struct Parser<'a> {
+    doc: DocId,
+    input: &'a str,
+    pos: usize,
+    /// The nesting depth of arrays and inline tables, to prevent stack overflow.
+    depth: u32,
+}
+
+impl<'a> Parser<'a> {
+    fn span(&self, start: usize, end: usize) -> Span {
+        Span::new(self.doc, start, end)
+    }
+
+    /// Return a span of the character at the cursor, or an empty one at the end.
+    fn span_here(&self) -> Span {
+        let n = self.input[self.pos..]
+            .chars()
+            .next()
+            .map_or(0, |ch| ch.len_utf8());
+        self.span(self.pos, self.pos + n)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.pos).copied()
+    }
+
+    fn increase_depth(&mut self) -> Result<()> {
+        self.depth += 1;
+        if self.depth >= 100 {
+            return self
+                .span_here()
+                .error("Parser recursion limit reached, please reduce nesting.")
+                .err();
+        }
+        Ok(())
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    /// Skip a comment, if there is one at the cursor.
+    fn skip_comment(&mut self) -> Result<()> {
+        if self.peek() != Some(b'#') {
+            return Ok(());
+        }
+        while let Some(b) = self.peek() {
+            match b {
+                b'\n' => break,
+                b'\r' if self.input[self.pos..].starts_with("\r\n") => break,
+                b'\t' => {}
+                b if b < 0x20 || b == 0x7f => {
+                    return self
+                        .span_here()
+                        .error("Control characters are not allowed in comments.")
+                        .err()
+                }
+                _ => {}
+            }
+            self.pos += 1;
+        }
+        Ok(())
+    }
+
+    /// Consume a line break, return whether there was one.
+    fn skip_newline(&mut self) -> bool {
+        if self.peek() == Some(b'\n') {
+            self.pos += 1;
+            true
+        } else if self.input[self.pos..].starts_with("\r\n") {
+            self.pos += 2;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Skip whitespace, comments, and line breaks, as inside arrays.
+    fn skip_whitespace_and_newlines(&mut self) -> Result<()> {
+        loop {
+            self.skip_whitespace();
+            self.skip_comment()?;
+            if !self.skip_newline() {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Expect the end of the line, after a key-value pair or table header.
+    fn expect_line_end(&mut self) -> Result<()> {
+        self.skip_whitespace();
+        self.skip_comment()?;
+        if self.pos < self.input.len() && !self.skip_newline() {
+            return self.span_here().error("Expected a line break.").err();
+        }
+        Ok(())
+    }
+
+    fn parse_document(&mut self) -> Result<Table> {
+        let mut root = Table::new(Definition::Header);
+        // The path of the table that key-value pairs currently go into.
+        let mut current: Vec<String> = Vec::new();
+
+        loop {
+            self.skip_whitespace_and_newlines()?;
+            match self.peek() {
+                None => break,
+                Some(b'[') => {
+                    let is_array = self.input[self.pos..].starts_with("[[");
+                    self.pos += if is_array { 2 } else { 1 };
+                    self.skip_whitespace();
+                    let key = self.parse_key()?;
+                    self.skip_whitespace();
+                    let close = if is_array { "]]" } else { "]" };
+                    if !self.input[self.pos..].starts_with(close) {
+                        let message = if is_array {
+                            "Expected ']]' to close the array of tables header."
+                        } else {
+                            "Expected ']' to close the table header."
+                        };
+                        return self.span_here().error(message).err();
+                    }
+                    self.pos += close.len();
+                    define_table(&mut root, &key, is_array)?;
+                    current = key.parts.into_iter().map(|(k, _)| k).collect();
+                }
+                Some(..) => {
+                    let table = lookup_table(&mut root, &current);
+                    self.parse_key_value(table)?;
+                }
+            }
+            self.expect_line_end()?;
+        }
+
+        Ok(root)
+    }
+
+    /// Parse a `key = value` pair and insert it into the table.
+    fn parse_key_value(&mut self, table: &mut Table) -> Result<()> {
+        let key = self.parse_key()?;
+        self.skip_whitespace();
+        if self.peek() != Some(b'=') {
+            return self.span_here().error("Expected '=' after the key.").err();
+        }
+        self.pos += 1;
+        self.skip_whitespace();
+        let value = self.parse_value()?;
+        insert_dotted(table, &key, value)
+    }
+
+    /// Parse a key, which may be dotted.
+    fn parse_key(&mut self) -> Result<Key> {
+        let mut parts = Vec::new();
+        loop {
+            let start = self.pos;
+            let part = match self.peek() {
+                Some(b'"') => {
+                    if self.input[self.pos..].starts_with("\"\"\"") {
+                        return self
+                            .span_here()
+                            .error("Multi-line strings cannot be used as keys.")
+                            .err();
+                    }
+                    self.parse_basic_string()?
+                }
+                Some(b'\'') => {
+                    if self.input[self.pos..].starts_with("'''") {
+                        return self
+                            .span_here()
+                            .error("Multi-line strings cannot be used as keys.")
+                            .err();
+                    }
+                    self.parse_literal_string()?
+                }
+                _ => {
+                    while let Some(b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'_' | b'-') =
+                        self.peek()
+                    {
+                        self.pos += 1;
+                    }
+                    if self.pos == start {
+                        return self.span_here().error("Expected a key.").err();
+                    }
+                    self.input[start..self.pos].to_string()
+                }
+            };
+            parts.push((part, self.span(start, self.pos)));
+
+            self.skip_whitespace();
+            if self.peek() != Some(b'.') {
+                return Ok(Key { parts });
+            }
+            self.pos += 1;
+            self.skip_whitespace();
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value> {
+        match self.peek() {
+            Some(b'"') if self.input[self.pos..].starts_with("\"\"\"") => {
+                Ok(Value::String(self.parse_multiline_basic_string()?.into()))
+            }
+            Some(b'"') => Ok(Value::String(self.parse_basic_string()?.into())),
+            Some(b'\'') if self.input[self.pos..].starts_with("'''") => {
+                Ok(Value::String(self.parse_multiline_literal_string()?.into()))
+            }
+            Some(b'\'') => Ok(Value::String(self.parse_literal_string()?.into())),
+            Some(b'[') => self.parse_array(),
+            Some(b'{') => self.parse_inline_table(),
+            Some(b't') if self.input[self.pos..].starts_with("true") => {
+                self.pos += 4;
+                Ok(Value::Bool(true))
+            }
+            Some(b'f') if self.input[self.pos..].starts_with("false") => {
+                self.pos += 5;
+                Ok(Value::Bool(false))
+            }
+            Some(b'0'..=b'9' | b'+' | b'-' | b'i' | b'n') => self.parse_number_or_date(),
+            None => self
+                .span_here()
+                .error("Expected a value, but the document ends here.")
+                .err(),
+            Some(..) => self.span_here().error("Expected a value.").err(),
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Value> {
+        self.increase_depth()?;
+        let start = self.pos;
+        self.pos += 1;
+        let mut result = Vec::new();
+        loop {
+            self.skip_whitespace_and_newlines()?;
+            if self.peek() == Some(b']') {
+                self.pos += 1;
+                break;
+            }
+            if self.peek().is_none() {
+                return self
+                    .span(start, start + 1)
+                    .error("Unterminated array, expected ']'.")
+                    .err();
+            }
+            result.push(self.parse_value()?);
+            self.skip_whitespace_and_newlines()?;
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    break;
+                }
+                _ => return self.span_here().error("Expected ',' or ']'.").err(),
+            }
+        }
+        self.depth -= 1;
+        Ok(Value::List(Rc::new(result)))
+    }
+
+    fn parse_inline_table(&mut self) -> Result<Value> {
+        self.increase_depth()?;
+        self.pos += 1;
+        let mut table = Table::new(Definition::Header);
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+        } else {
+            loop {
+                self.skip_whitespace();
+                self.parse_key_value(&mut table)?;
+                self.skip_whitespace();
+                match self.peek() {
+                    Some(b',') => self.pos += 1,
+                    Some(b'}') => {
+                        self.pos += 1;
+                        break;
+                    }
+                    Some(b'\n' | b'\r') => {
+                        return self
+                            .span_here()
+                            .error("Inline tables must be on a single line.")
+                            .err()
+                    }
+                    _ => return self.span_here().error("Expected ',' or '}'.").err(),
+                }
+            }
+        }
+        self.depth -= 1;
+        Ok(table.into_value())
+    }
+
+    /// Parse a fixed number of hex digits of a `\u` or `\U` escape sequence.
+    fn parse_hex(&mut self, escape_start: usize, n: usize) -> Result<char> {
+        let hex = self.input.get(self.pos..self.pos + n).unwrap_or("");
+        let code_point = match u32::from_str_radix(hex, 16) {
+            Ok(code_point) if hex.bytes().all(|b| b.is_ascii_hexdigit()) => code_point,
+            _ => {
+                return self
+                    .span(escape_start, self.span_here().end())
+                    .error("Expected hex digits in escape sequence.")
+                    .err()
+            }
+        };
+        self.pos += n;
+        match char::from_u32(code_point) {
+            Some(ch) => Ok(ch),
+            None => self
+                .span(escape_start, self.pos)
+                .error("Invalid code point in escape sequence.")
+                .err(),
+        }
+    }
+
+    /// Parse the escape sequence at the cursor, which is at the backslash.
+    fn parse_escape(&mut self) -> Result<char> {
+        let escape_start = self.pos;
+        self.pos += 1;
+        let ch = match self.peek() {
+            Some(b'b') => '\x08',
+            Some(b't') => '\t',
+            Some(b'n') => '\n',
+            Some(b'f') => '\x0c',
+            Some(b'r') => '\r',
+            Some(b'"') => '"',
+            Some(b'\\') => '\\',
+            Some(b'u') => {
+                self.pos += 1;
+                return self.parse_hex(escape_start, 4);
+            }
+            Some(b'U') => {
+                self.pos += 1;
+                return self.parse_hex(escape_start, 8);
+            }
+            _ => {
+                return self
+                    .span(escape_start, self.span_here().end())
+                    .error("Invalid escape sequence.")
+                    .err()
+            }
+        };
+        self.pos += 1;
+        Ok(ch)
+    }
+
+    fn error_control<T>(&self) -> Result<T> {
+        self.span_here()
+            .error("Control characters must be escaped in strings.")
+            .err()
+    }
+
+    fn parse_basic_string(&mut self) -> Result<String> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut result = String::new();
+        loop {
+            let run_start = self.pos;
+            while let Some(b) = self.peek() {
+                if b == b'"' || b == b'\\' || (b < 0x20 && b != b'\t') || b == 0x7f {
+                    break;
+                }
+                self.pos += 1;
+            }
+            result.push_str(&self.input[run_start..self.pos]);
+            match self.peek() {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(result);
+                }
+                Some(b'\\') => result.push(self.parse_escape()?),
+                Some(b'\n' | b'\r') | None => {
+                    return self
+                        .span(start, self.pos)
+                        .error("Unterminated string, expected '\"'.")
+                        .err()
+                }
+                Some(..) => return self.error_control(),
+            }
+        }
+    }
+
+    fn parse_literal_string(&mut self) -> Result<String> {
+        let start = self.pos;
+        self.pos += 1;
+        while let Some(b) = self.peek() {
+            match b {
+                b'\'' => {
+                    self.pos += 1;
+                    return Ok(self.input[start + 1..self.pos - 1].to_string());
+                }
+                b'\n' | b'\r' => break,
+                b if (b < 0x20 && b != b'\t') || b == 0x7f => return self.error_control(),
+                _ => self.pos += 1,
+            }
+        }
+        self.span(start, self.pos)
+            .error("Unterminated string, expected \"'\".")
+            .err()
+    }
+
+    /// After the opening delimiter of a multi-line string, skip a line break.
+    fn skip_opening_newline(&mut self) {
+        self.skip_newline();
+    }
+
+    /// If the cursor is at the closing delimiter, consume it.
+    ///
+    /// The delimiter can be followed by up to two more quotes, which are part
+    /// of the string. We return how many.
+    fn at_multiline_close(&mut self, quote: u8) -> Option<usize> {
+        let bytes = &self.input.as_bytes()[self.pos..];
+        let n = bytes.iter().take_while(|b| **b == quote).count();
+        if n < 3 {
+            return None;
+        }
+        let extra = (n - 3).min(2);
+        self.pos += 3 + extra;
+        Some(extra)
+    }
+
+    fn parse_multiline_basic_string(&mut self) -> Result<String> {
+        let start = self.pos;
+        self.pos += 3;
+        self.skip_opening_newline();
+        let mut result = String::new();
+        loop {
+            let run_start = self.pos;
+            while let Some(b) = self.peek() {
+                if b == b'"' || b == b'\\' || (b < 0x20 && b != b'\t' && b != b'\n') || b == 0x7f {
+                    break;
+                }
+                self.pos += 1;
+            }
+            result.push_str(&self.input[run_start..self.pos]);
+            match self.peek() {
+                Some(b'"') => match self.at_multiline_close(b'"') {
+                    Some(extra) => {
+                        result.push_str(&"\"\""[..extra]);
+                        return Ok(result);
+                    }
+                    None => {
+                        result.push('"');
+                        self.pos += 1;
+                    }
+                },
+                Some(b'\\') => {
+                    // A backslash at the end of a line removes the line break
+                    // and all whitespace up to the next content.
+                    let after = self.input[self.pos + 1..].trim_start_matches([' ', '\t']);
+                    if after.starts_with('\n') || after.starts_with("\r\n") {
+                        self.pos += 1;
+                        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+                            self.pos += 1;
+                        }
+                    } else {
+                        result.push(self.parse_escape()?);
+                    }
+                }
+                Some(b'\r') if self.input[self.pos..].starts_with("\r\n") => {
+                    result.push('\n');
+                    self.pos += 2;
+                }
+                None => {
+                    return self
+                        .span(start, start + 3)
+                        .error("Unterminated string, expected '\"\"\"'.")
+                        .err()
+                }
+                Some(..) => return self.error_control(),
+            }
+        }
+    }
+
+    fn parse_multiline_literal_string(&mut self) -> Result<String> {
+        let start = self.pos;
+        self.pos += 3;
+        self.skip_opening_newline();
+        let mut result = String::new();
+        loop {
+            let run_start = self.pos;
+            while let Some(b) = self.peek() {
+                if b == b'\'' || (b < 0x20 && b != b'\t' && b != b'\n') || b == 0x7f {
+                    break;
+                }
+                self.pos += 1;
+            }
+            result.push_str(&self.input[run_start..self.pos]);
+            match self.peek() {
+                Some(b'\'') => match self.at_multiline_close(b'\'') {
+                    Some(extra) => {
+                        result.push_str(&"''"[..extra]);
+                        return Ok(result);
+                    }
+                    None => {
+                        result.push('\'');
+                        self.pos += 1;
+                    }
+                },
+                Some(b'\r') if self.input[self.pos..].starts_with("\r\n") => {
+                    result.push('\n');
+                    self.pos += 2;
+                }
+                None => {
+                    return self
+                        .span(start, start + 3)
+                        .error("Unterminated string, expected \"'''\".")
+                        .err()
+                }
+                Some(..) => return self.error_control(),
+            }
+        }
+    }
+
+    /// Parse an integer, float, or date-time, which all start similarly.
+    fn parse_number_or_date(&mut self) -> Result<Value> {
+        let start = self.pos;
+        let is_token_byte =
+            |b: u8| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'+' | b'-' | b'.' | b':');
+        while let Some(b) = self.peek() {
+            if !is_token_byte(b) {
+                break;
+            }
+            self.pos += 1;
+        }
+        // A date and a time can be separated by a space instead of a `T`.
+        let bytes = self.input.as_bytes();
+        if is_local_date(&self.input[start..self.pos])
+            && bytes.get(self.pos) == Some(&b' ')
+            && bytes.get(self.pos + 1).map_or(false, u8::is_ascii_digit)
+        {
+            self.pos += 1;
+            while let Some(b) = self.peek() {
+                if !is_token_byte(b) {
+                    break;
+                }
+                self.pos += 1;
+            }
+        }
+
+        let span = self.span(start, self.pos);
+        let token = &self.input[start..self.pos];
+
+        if is_date_time(token) {
+            return Ok(Value::String(token.into()));
+        }
+        match parse_integer(token) {
+            Some(Ok(n)) => return Ok(Value::Int(n.into())),
+            Some(Err(..)) => return span.error("Overflow in integer literal.").err(),
+            None => {}
+        }
+        if is_float(token) {
+            return match Decimal::parse(&token.replace('_', "")) {
+                Some(d) => Ok(Value::Number(d)),
+                None if token.ends_with(['f', 'n']) => span
+                    .error("RCL numbers cannot be infinite or NaN.")
+                    .with_help("To use this value as a string, put it in quotes.")
+                    .err(),
+                None => span.error("Overflow in number literal.").err(),
+            };
+        }
+        span.error("Expected a value.").err()
+    }
+}
+
+/// Look up the table that key-value pairs go into, after its header was defined.
+fn lookup_table<'t>(root: &'t mut Table, path: &[String]) -> &'t mut Table {
+    let mut table = root;
+    for part in path {
+        table = match table.entries.get_mut(part) {
+            Some(Node::Table(t)) => t,
+            Some(Node::Array(ts)) => ts.last_mut().expect("Arrays of tables are not empty."),
+            _ => unreachable!("The header was defined before we look it up."),
+        };
+    }
+    table
+}
+
+/// Define a table or array of tables for a header.
+fn define_table(root: &mut Table, key: &Key, is_array: bool) -> Result<()> {
+    let (last, parents) = key.parts.split_last().expect("Keys are not empty.");
+    let mut table = root;
+    for (part, span) in parents {
+        let node = table
+            .entries
+            .entry(part.clone())
+            .or_insert_with(|| Node::Table(Table::new(Definition::Implicit)));
+        table = match node {
+            Node::Table(t) => t,
+            Node::Array(ts) => ts.last_mut().expect("Arrays of tables are not empty."),
+            Node::Value(..) => {
+                return span
+                    .error("This key is already defined as a value, it cannot be a table.")
+                    .err()
+            }
+        };
+    }
+
+    let (part, span) = last;
+    match (table.entries.get_mut(part), is_array) {
+        (None, false) => {
+            table
+                .entries
+                .insert(part.clone(), Node::Table(Table::new(Definition::Header)));
+        }
+        (None, true) => {
+            let tables = vec![Table::new(Definition::Header)];
+            table.entries.insert(part.clone(), Node::Array(tables));
+        }
+        (Some(Node::Table(t)), false) if t.definition == Definition::Implicit => {
+            t.definition = Definition::Header;
+        }
+        (Some(Node::Array(ts)), true) => ts.push(Table::new(Definition::Header)),
+        (Some(..), _) => return span.error("This table is already defined.").err(),
+    }
+    Ok(())
+}
+
+/// Insert a value for a possibly dotted key into the table.
+fn insert_dotted(table: &mut Table, key: &Key, value: Value) -> Result<()> {
+    let (last, parents) = key.parts.split_last().expect("Keys are not empty.");
+    let mut table = table;
+    for (part, span) in parents {
+        let node = table
+            .entries
+            .entry(part.clone())
+            .or_insert_with(|| Node::Table(Table::new(Definition::Dotted)));
+        table = match node {
+            // A table that only exists as the parent of a header has not been
+            // defined yet, so the dotted key defines it.
+            Node::Table(t) if t.definition != Definition::Header => {
+                t.definition = Definition::Dotted;
+                t
+            }
+            _ => {
+                return span
+                    .error("This key is already defined, it cannot be extended with a dotted key.")
+                    .err()
+            }
+        };
+    }
+
+    let (part, span) = last;
+    if table.entries.contains_key(part) {
+        return span.error("This key is already defined.").err();
+    }
+    table.entries.insert(part.clone(), Node::Value(value));
+    Ok(())
+}
+
+fn is_digits(s: &str, n: usize) -> bool {
+    s.len() == n && s.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Whether the token is a local date, `YYYY-MM-DD`.
+fn is_local_date(s: &str) -> bool {
+    let mut parts = s.split('-');
+    matches!(
+        (parts.next(), parts.next(), parts.next(), parts.next()),
+        (Some(y), Some(m), Some(d), None) if is_digits(y, 4) && is_digits(m, 2) && is_digits(d, 2)
+    )
+}
+
+/// Whether the token is a local time, `HH:MM:SS` with optional fraction.
+fn is_local_time(s: &str) -> bool {
+    let (hms, frac) = match s.split_once('.') {
+        Some((hms, frac)) => (hms, Some(frac)),
+        None => (s, None),
+    };
+    let mut parts = hms.split(':');
+    let hms_ok = matches!(
+        (parts.next(), parts.next(), parts.next(), parts.next()),
+        (Some(h), Some(m), Some(s), None) if is_digits(h, 2) && is_digits(m, 2) && is_digits(s, 2)
+    );
+    let frac_ok = frac.map_or(true, |f| !f.is_empty() && is_digits(f, f.len()));
+    hms_ok && frac_ok
+}
+
+/// Whether the token is a date, a time, or a date-time with optional offset.
+fn is_date_time(s: &str) -> bool {
+    if is_local_date(s) || is_local_time(s) {
+        return true;
+    }
+    if s.len() < 11 || !is_local_date(&s[..10]) || !matches!(s.as_bytes()[10], b'T' | b't' | b' ') {
+        return false;
+    }
+    let time = &s[11..];
+    let time = time
+        .strip_suffix(['Z', 'z'])
+        .or_else(|| {
+            let i = time.rfind(['+', '-'])?;
+            let offset = &time[i + 1..];
+            let is_offset = offset.len() == 5
+                && is_digits(&offset[..2], 2)
+                && offset.as_bytes()[2] == b':'
+                && is_digits(&offset[3..], 2);
+            is_offset.then_some(&time[..i])
+        })
+        .unwrap_or(time);
+    is_local_time(time)
+}
+
+/// Remove underscores between digits, or return `None` if they are misplaced.
+fn strip_underscores(digits: &str, is_digit: impl Fn(u8) -> bool) -> Option<String> {
+    let bytes = digits.as_bytes();
+    if bytes.is_empty() || !is_digit(bytes[0]) || !is_digit(bytes[bytes.len() - 1]) {
+        return None;
+    }
+    let mut result = String::with_capacity(digits.len());
+    for (i, &b) in bytes.iter().enumerate() {
+        if b == b'_' {
+            if !is_digit(bytes[i - 1]) || !is_digit(bytes[i + 1]) {
+                return None;
+            }
+        } else if is_digit(b) {
+            result.push(b as char);
+        } else {
+            return None;
+        }
+    }
+    Some(result)
+}
+
+/// Parse an integer token, or return `None` if the token is not an integer.
+fn parse_integer(s: &str) -> Option<std::result::Result<i64, std::num::ParseIntError>> {
+    for (prefix, radix) in [("0x", 16), ("0o", 8), ("0b", 2)] {
+        if let Some(digits) = s.strip_prefix(prefix) {
+            let digits = strip_underscores(digits, |b| (b as char).is_digit(radix))?;
+            return Some(i64::from_str_radix(&digits, radix));
+        }
+    }
+    let (sign, unsigned) = match s.strip_prefix(['+', '-']) {
+        Some(unsigned) => (&s[..1], unsigned),
+        None => ("", s),
+    };
+    let digits = strip_underscores(unsigned, |b| b.is_ascii_digit())?;
+    if digits.len() > 1 && digits.starts_with('0') {
+        return None;
+    }
+    Some(format!("{sign}{digits}").parse())
+}
+
+/// Whether the token is a float, including `inf` and `nan`.
+fn is_float(s: &str) -> bool {
+    let unsigned = s.strip_prefix(['+', '-']).unwrap_or(s);
+    if unsigned == "inf" || unsigned == "nan" {
+        return true;
+    }
+    let (mantissa, exponent) = match unsigned.find(['e', 'E']) {
+        Some(i) => (&unsigned[..i], Some(&unsigned[i + 1..])),
+        None => (unsigned, None),
+    };
+    let (int_part, frac_part) = match mantissa.split_once('.') {
+        Some((int_part, frac_part)) => (int_part, Some(frac_part)),
+        None => (mantissa, None),
+    };
+    let is_decimal = |s: &str| strip_underscores(s, |b| b.is_ascii_digit()).is_some();
+    let exponent_ok = match exponent {
+        None => frac_part.is_some(),
+        Some(e) => is_decimal(e.strip_prefix(['+', '-']).unwrap_or(e)),
+    };
+    is_decimal(int_part) && frac_part.map_or(true, is_decimal) && exponent_ok
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse_toml;
+    use crate::fmt_rcl::format_rcl;
+    use crate::pprint::Config;
+    use crate::source::DocId;
+
+    fn parse(input: &str) -> String {
+        let value = parse_toml(DocId(0), input).unwrap();
+        let cfg = Config::default();
+        format_rcl(&value).println(&cfg).to_string_no_markup()
+    }
+
+    fn fail(input: &str) -> String {
+        let err = parse_toml(DocId(0), input).err().unwrap();
+        let cfg = Config::default();
+        err.message.println(&cfg).to_string_no_markup()
+    }
+
+    #[test]
+    fn parse_toml_tables() {
+        assert_eq!(
+            parse("a = 1\n[b]\nc = true # Comment\n[b.d]\ne = 'x'\n"),
+            "{ a = 1, b = { c = true, d = { e = \"x\" } } }\n",
+        );
+        assert_eq!(
+            parse("[[bin]]\nname = \"a\"\n[[bin]]\nname = \"b\"\n[bin.extra]\nx = 1\n"),
+            "{ bin = [{ name = \"a\" }, { extra = { x = 1 }, name = \"b\" }] }\n",
+        );
+        assert_eq!(
+            parse("a.b = 1\na . \"c d\" = 2\n[x.y]\n[x]\nz = {p = 1, q.r = 2}\n"),
+            "{ a = { b = 1, \"c d\": 2 }, x = { y = {}, z = { p = 1, q = { r = 2 } } } }\n",
+        );
+    }
+
+    #[test]
+    fn parse_toml_values() {
+        assert_eq!(
+            parse("a = [1_000, 0xff, 0o17, 0b101, -3, +4, [\n  \"x\", # Comment\n],]"),
+            "{ a = [1000, 255, 15, 5, -3, 4, [\"x\"]] }\n",
+        );
+        assert_eq!(
+            parse("a = [3.14, -0.01, 5e+22, 1_000.5, 6.626e-34]"),
+            "{ a = [3.14, -0.01, 5.0e+22, 1000.5, 6.626e-34] }\n",
+        );
+        assert_eq!(
+            parse("a = 1979-05-27T07:32:00Z\nb = 1979-05-27 07:32:00.5-07:00"),
+            "{ a = \"1979-05-27T07:32:00Z\", b = \"1979-05-27 07:32:00.5-07:00\" }\n",
+        );
+        assert_eq!(
+            parse("c = 07:32:00\nd = 1979-05-27"),
+            "{ c = \"07:32:00\", d = \"1979-05-27\" }\n",
+        );
+        assert_eq!(
+            parse("a = \"\"\"\nx\\\n   y\"\"\"\"\nb = '''\n'q'\n'''\nc = \"\\u00e9\\t\""),
+            "{ a = \"xy\\\"\", b = \"'q'\\n\", c = \"\u{e9}\\t\" }\n",
+        );
+    }
+
+    #[test]
+    fn parse_toml_rejects_redefinition() {
+        assert_eq!(fail("a = 1\na = 2"), "This key is already defined.\n");
+        assert_eq!(fail("[a]\n[a]"), "This table is already defined.\n");
+        assert_eq!(
+            fail("[a]\nb.c = 1\n[a.b]"),
+            "This table is already defined.\n"
+        );
+        assert_eq!(
+            fail("a = {}\n[a.b]"),
+            "This key is already defined as a value, it cannot be a table.\n"
+        );
+        assert_eq!(
+            fail("a = {b = 1}\na.c = 2"),
+            "This key is already defined, it cannot be extended with a dotted key.\n"
+        );
+        assert_eq!(fail("a = [1]\n[[a]]"), "This table is already defined.\n");
+    }
+
+    #[test]
+    fn parse_toml_rejects_non_toml() {
+        assert_eq!(fail("a = inf"), "RCL numbers cannot be infinite or NaN.\n");
+        assert_eq!(fail("a = -nan"), "RCL numbers cannot be infinite or NaN.\n");
+        assert_eq!(fail("a = 01"), "Expected a value.\n");
+        assert_eq!(fail("a = 1_"), "Expected a value.\n");
+        assert_eq!(fail("a = 1 b = 2"), "Expected a line break.\n");
+        assert_eq!(fail("a = {b = 1,\n}"), "Expected a key.\n");
+        assert_eq!(fail("a = \"x"), "Unterminated string, expected '\"'.\n");
+        assert_eq!(fail("a b = 1"), "Expected '=' after the key.\n");
+    }
+}
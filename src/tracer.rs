@@ -7,12 +7,53 @@
 
 //! Runtime configurable behavior for trace messages.
 
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
 use crate::error::highlight_span;
 use crate::fmt_rcl::format_rcl;
 use crate::markup::{Markup, MarkupMode};
 use crate::pprint::{self, concat, Doc};
 use crate::runtime::Value;
-use crate::source::{Inputs, Span};
+use crate::source::{DocId, Inputs, Span};
+use crate::string::escape_json;
+
+/// A phase of evaluation, for tracers that record where time is spent.
+#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Phase {
+    /// Reading a document from the filesystem.
+    Read,
+    /// Lexing a document.
+    Lex,
+    /// Parsing and abstracting a document.
+    Parse,
+    /// Typechecking a document.
+    Typecheck,
+    /// Evaluating a document.
+    Evaluate,
+    /// An import, including parsing, typechecking, and evaluating the document.
+    Import,
+    /// A function or method call, the span is that of the callee.
+    Call,
+    /// Formatting and writing the output of a document.
+    Serialize,
+}
+
+impl Phase {
+    /// The name of the phase, used as category in trace files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Phase::Read => "read",
+            Phase::Lex => "lex",
+            Phase::Parse => "parse",
+            Phase::Typecheck => "typecheck",
+            Phase::Evaluate => "evaluate",
+            Phase::Import => "import",
+            Phase::Call => "call",
+            Phase::Serialize => "serialize",
+        }
+    }
+}
 
 /// Configurable behavior for trace messages.
 ///
@@ -27,6 +68,39 @@ use crate::source::{Inputs, Span};
 /// tracer that ignores trace messages.
 pub trait Tracer {
     fn trace(&mut self, inputs: &Inputs, span: Span, message: &Value);
+
+    /// Mark the start of a phase of evaluation.
+    ///
+    /// Every call is matched by a call to [`Tracer::exit`], also when the
+    /// phase fails with an error. By default this does nothing.
+    fn enter(&mut self, _phase: Phase, _span: Span) {}
+
+    /// Mark the end of the phase that was entered most recently.
+    fn exit(&mut self) {}
+
+    /// Record a phase that started at `start` and that ended just now.
+    ///
+    /// This is for phases where the span is only known afterwards, such as
+    /// reading a document. By default this does nothing.
+    fn record(&mut self, _phase: Phase, _span: Span, _start: Instant) {}
+}
+
+impl<T: Tracer + ?Sized> Tracer for &mut T {
+    fn trace(&mut self, inputs: &Inputs, span: Span, message: &Value) {
+        (**self).trace(inputs, span, message)
+    }
+
+    fn enter(&mut self, phase: Phase, span: Span) {
+        (**self).enter(phase, span)
+    }
+
+    fn exit(&mut self) {
+        (**self).exit()
+    }
+
+    fn record(&mut self, phase: Phase, span: Span, start: Instant) {
+        (**self).record(phase, span, start)
+    }
 }
 
 /// Tracer that writes messages to stderr.
@@ -39,7 +113,7 @@ impl StderrTracer {
     pub fn new(markup: Option<MarkupMode>) -> StderrTracer {
         let stderr = std::io::stderr();
         StderrTracer {
-            config: pprint::Config { width: 80 },
+            config: pprint::Config::default(),
             markup: markup.unwrap_or_else(|| MarkupMode::default_for_fd(&stderr)),
         }
     }
@@ -68,11 +142,336 @@ impl Tracer for StderrTracer {
 
 /// Tracer that ignores its messages.
 ///
-/// Intended for use by the fuzzer.
+/// Intended for use by the fuzzer, and for evaluating json messages in the
+/// language server, which do not contain trace calls.
 pub struct VoidTracer;
 
-// coverage:off -- The void tracer is only used by the fuzzer, not production code.
+// coverage:off -- The void tracer is not used for any user-facing trace calls.
 impl Tracer for VoidTracer {
     fn trace(&mut self, _inputs: &Inputs, _span: Span, _message: &Value) {}
 }
 // coverage:on
+
+enum EventKind {
+    Begin(Phase, Span),
+    End,
+    Message(Span),
+}
+
+/// Tracer that records the durations of evaluation phases.
+///
+/// This is used for `--trace-file`. The recording can be exported in the
+/// Chrome trace event format, which can be viewed in `about:tracing` or in
+/// [Perfetto](https://ui.perfetto.dev). Trace messages are passed on to the
+/// inner tracer, and they show up as instant events in the recording.
+pub struct ChromeTracer<T> {
+    inner: T,
+    start: Instant,
+    events: Vec<(Duration, EventKind)>,
+}
+
+impl<T: Tracer> ChromeTracer<T> {
+    pub fn new(inner: T) -> ChromeTracer<T> {
+        ChromeTracer {
+            inner,
+            start: Instant::now(),
+            events: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, kind: EventKind) {
+        self.events.push((self.start.elapsed(), kind));
+    }
+
+    /// Export the recording as a Chrome trace event json document.
+    ///
+    /// The inputs must be the same documents that were traced; they are used
+    /// to name the events.
+    pub fn to_json(&self, inputs: &Inputs) -> String {
+        // Byte offsets of the line starts of every document, to locate events.
+        let line_starts: Vec<Vec<usize>> = inputs
+            .iter()
+            .map(|doc| {
+                let newlines = doc.data.match_indices('\n').map(|(i, _)| i + 1);
+                std::iter::once(0).chain(newlines).collect()
+            })
+            .collect();
+        let push_location = |out: &mut String, span: Span| {
+            let doc = &inputs[span.doc().0 as usize];
+            let starts = &line_starts[span.doc().0 as usize];
+            let line = starts.partition_point(|&i| i <= span.start());
+            let column = 1 + span.start() - starts[line - 1];
+            out.push_str(r#", "args": {"location": ""#);
+            escape_json(doc.name, out);
+            out.push_str(&format!(":{line}:{column}\"}}"));
+        };
+
+        let mut out = String::from("{\"traceEvents\": [\n");
+        for (i, (time, kind)) in self.events.iter().enumerate() {
+            if i > 0 {
+                out.push_str(",\n");
+            }
+            let ts = time.as_secs_f64() * 1e6;
+            match kind {
+                EventKind::Begin(phase, span) => {
+                    out.push_str(r#"{"ph": "B", "cat": ""#);
+                    out.push_str(phase.name());
+                    out.push_str(r#"", "name": ""#);
+                    match phase {
+                        // For calls, the callee expression is a good name. We
+                        // take only the first line, it can be a long lambda.
+                        Phase::Call => {
+                            let callee = span.resolve(inputs[span.doc().0 as usize].data);
+                            let callee = callee.lines().next().unwrap_or("");
+                            escape_json(callee, &mut out);
+                        }
+                        _ => escape_json(inputs[span.doc().0 as usize].name, &mut out),
+                    }
+                    out.push_str(&format!(r#"", "ts": {ts:.3}, "pid": 1, "tid": 1"#));
+                    push_location(&mut out, *span);
+                    out.push('}');
+                }
+                EventKind::End => {
+                    out.push_str(&format!(
+                        r#"{{"ph": "E", "ts": {ts:.3}, "pid": 1, "tid": 1}}"#
+                    ));
+                }
+                EventKind::Message(span) => {
+                    out.push_str(r#"{"ph": "i", "cat": "trace", "name": "trace", "s": "t""#);
+                    out.push_str(&format!(r#", "ts": {ts:.3}, "pid": 1, "tid": 1"#));
+                    push_location(&mut out, *span);
+                    out.push('}');
+                }
+            }
+        }
+        out.push_str("\n], \"displayTimeUnit\": \"ms\"}\n");
+        out
+    }
+}
+
+impl<T: Tracer> Tracer for ChromeTracer<T> {
+    fn trace(&mut self, inputs: &Inputs, span: Span, message: &Value) {
+        self.push(EventKind::Message(span));
+        self.inner.trace(inputs, span, message);
+    }
+
+    fn enter(&mut self, phase: Phase, span: Span) {
+        self.push(EventKind::Begin(phase, span));
+        self.inner.enter(phase, span);
+    }
+
+    fn exit(&mut self) {
+        self.push(EventKind::End);
+        self.inner.exit();
+    }
+
+    fn record(&mut self, phase: Phase, span: Span, start: Instant) {
+        let begin = start.saturating_duration_since(self.start);
+        self.events.push((begin, EventKind::Begin(phase, span)));
+        self.push(EventKind::End);
+        self.inner.record(phase, span, start);
+    }
+}
+
+/// A phase that was entered but not yet exited, see [`Timings`].
+struct Frame {
+    phase: Phase,
+    doc: DocId,
+    start: Instant,
+    /// Time spent in nested phases that are attributed elsewhere.
+    child_time: Duration,
+}
+
+/// The wall-clock time spent per document in every phase, for `--time`.
+///
+/// Time is attributed to the innermost phase only: the time spent evaluating
+/// an imported document counts towards that document, not towards the
+/// document that imports it. Imports themselves are not reported, and calls
+/// count towards the phase that they are part of.
+#[derive(Default)]
+pub struct Timings {
+    stack: Vec<Frame>,
+    totals: BTreeMap<(DocId, Phase), Duration>,
+}
+
+/// The phases that [`Timings::report`] prints, in order.
+const REPORT_PHASES: [Phase; 6] = [
+    Phase::Read,
+    Phase::Lex,
+    Phase::Parse,
+    Phase::Typecheck,
+    Phase::Evaluate,
+    Phase::Serialize,
+];
+
+impl Timings {
+    pub fn new() -> Timings {
+        Timings::default()
+    }
+
+    /// Whether nothing was recorded so far.
+    pub fn is_empty(&self) -> bool {
+        self.totals.is_empty()
+    }
+
+    /// Return the time spent in `phase` for `doc`.
+    pub fn get(&self, doc: DocId, phase: Phase) -> Duration {
+        self.totals
+            .get(&(doc, phase))
+            .copied()
+            .unwrap_or(Duration::ZERO)
+    }
+
+    fn add(&mut self, doc: DocId, phase: Phase, elapsed: Duration, child_time: Duration) {
+        let self_time = elapsed.saturating_sub(child_time);
+        *self.totals.entry((doc, phase)).or_default() += self_time;
+        if let Some(parent) = self.stack.last_mut() {
+            parent.child_time += self_time;
+        }
+    }
+
+    pub fn enter(&mut self, phase: Phase, span: Span) {
+        self.stack.push(Frame {
+            phase,
+            doc: span.doc(),
+            start: Instant::now(),
+            child_time: Duration::ZERO,
+        });
+    }
+
+    pub fn exit(&mut self) {
+        let frame = match self.stack.pop() {
+            Some(frame) => frame,
+            None => return,
+        };
+        match frame.phase {
+            // Calls and imports are not reported on their own, their time
+            // belongs to the phases nested in them, or to the enclosing phase.
+            Phase::Call => {
+                if let Some(parent) = self.stack.last_mut() {
+                    parent.child_time += frame.child_time;
+                }
+            }
+            Phase::Import => {
+                if let Some(parent) = self.stack.last_mut() {
+                    parent.child_time += frame.start.elapsed();
+                }
+            }
+            phase => self.add(frame.doc, phase, frame.start.elapsed(), frame.child_time),
+        }
+    }
+
+    pub fn record(&mut self, phase: Phase, span: Span, start: Instant) {
+        self.add(span.doc(), phase, start.elapsed(), Duration::ZERO);
+    }
+
+    /// Format a table with the time per phase in milliseconds, one row per document.
+    pub fn report(&self, inputs: &Inputs) -> String {
+        let docs: Vec<DocId> = {
+            let mut docs: Vec<DocId> = self.totals.keys().map(|(doc, _)| *doc).collect();
+            docs.dedup();
+            docs
+        };
+        let mut out = String::new();
+        for phase in REPORT_PHASES.iter() {
+            out.push_str(&format!("{:>10}", phase.name()));
+        }
+        out.push_str("  document\n");
+
+        let push_row = |out: &mut String, times: [Duration; 6], name: &str| {
+            for t in times.iter() {
+                out.push_str(&format!("{:>10.3}", t.as_secs_f64() * 1e3));
+            }
+            out.push_str("  ");
+            out.push_str(name);
+            out.push('\n');
+        };
+
+        let mut total = [Duration::ZERO; 6];
+        for doc in docs.iter() {
+            let times = REPORT_PHASES.map(|phase| self.get(*doc, phase));
+            for (t, dt) in total.iter_mut().zip(times.iter()) {
+                *t += *dt;
+            }
+            push_row(&mut out, times, inputs[doc.0 as usize].name);
+        }
+        if docs.len() > 1 {
+            push_row(&mut out, total, "(total)");
+        }
+        out
+    }
+}
+
+/// Tracer that records [`Timings`], and passes everything on to the inner tracer.
+pub struct TimingTracer<'a, T> {
+    inner: T,
+    timings: &'a mut Timings,
+}
+
+impl<'a, T: Tracer> TimingTracer<'a, T> {
+    pub fn new(inner: T, timings: &'a mut Timings) -> TimingTracer<'a, T> {
+        TimingTracer { inner, timings }
+    }
+}
+
+impl<'a, T: Tracer> Tracer for TimingTracer<'a, T> {
+    fn trace(&mut self, inputs: &Inputs, span: Span, message: &Value) {
+        self.inner.trace(inputs, span, message);
+    }
+
+    fn enter(&mut self, phase: Phase, span: Span) {
+        self.timings.enter(phase, span);
+        self.inner.enter(phase, span);
+    }
+
+    fn exit(&mut self) {
+        self.timings.exit();
+        self.inner.exit();
+    }
+
+    fn record(&mut self, phase: Phase, span: Span, start: Instant) {
+        self.timings.record(phase, span, start);
+        self.inner.record(phase, span, start);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Phase, Timings};
+    use crate::source::{DocId, Span};
+    use std::time::{Duration, Instant};
+
+    fn sleep_ms(ms: u64) {
+        std::thread::sleep(Duration::from_millis(ms));
+    }
+
+    #[test]
+    fn timings_attribute_imports_to_the_imported_document() {
+        let a = Span::new(DocId(0), 0, 0);
+        let b = Span::new(DocId(1), 0, 0);
+        let mut timings = Timings::new();
+        timings.enter(Phase::Evaluate, a);
+        timings.enter(Phase::Call, a);
+        sleep_ms(5);
+        timings.enter(Phase::Import, b);
+        let start = Instant::now();
+        sleep_ms(5);
+        timings.record(Phase::Read, b, start);
+        timings.enter(Phase::Evaluate, b);
+        sleep_ms(40);
+        timings.exit();
+        timings.exit();
+        timings.exit();
+        timings.exit();
+
+        let a_eval = timings.get(DocId(0), Phase::Evaluate);
+        let b_eval = timings.get(DocId(1), Phase::Evaluate);
+        assert!(timings.get(DocId(1), Phase::Read) >= Duration::from_millis(5));
+        assert!(b_eval >= Duration::from_millis(40));
+        assert!(a_eval >= Duration::from_millis(5));
+        // The evaluation of `b` is not counted towards `a`.
+        assert!(a_eval < Duration::from_millis(40));
+        assert_eq!(timings.get(DocId(0), Phase::Import), Duration::ZERO);
+        assert_eq!(timings.get(DocId(0), Phase::Call), Duration::ZERO);
+    }
+}
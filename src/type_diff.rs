@@ -94,6 +94,10 @@ impl<T> TypeDiff<T> {
                         .with_body(report_type_mismatch(&expected, &actual))
                 };
 
+                if is_empty_dict_for_set(&expected.type_, &actual.type_) {
+                    error.set_help(help_empty_set());
+                }
+
                 // If we have it, explain why the expected type is expected.
                 expected.explain_error(Side::Expected, &mut error);
 
@@ -119,6 +123,29 @@ impl<T> TypeDiff<T> {
     }
 }
 
+/// Return whether `actual` is the type of `{}` in a place where a set is expected.
+///
+/// An empty `{}` is a dict, unless the expected type at the literal is a set.
+/// When the literal is bound first and used as a set later, that is a type
+/// error that deserves a hint.
+fn is_empty_dict_for_set(expected: &Type, actual: &Type) -> bool {
+    match (expected, actual) {
+        (Type::Set(..), Type::Dict(kv)) => {
+            kv.key.type_ == Type::Void && kv.value.type_ == Type::Void
+        }
+        _ => false,
+    }
+}
+
+/// Help message for when an empty dict `{}` was used where a set is needed.
+pub fn help_empty_set() -> Doc<'static> {
+    concat! {
+        "'" Doc::highlight("{}") "' is an empty dict. For an empty set, use '"
+        Doc::highlight("std.empty_set") "', or annotate the type, e.g. '"
+        Doc::highlight("let xs: Set[Int] = {};") "'."
+    }
+}
+
 /// Format a static type error body.
 ///
 /// This does not include the "Type mismatch." message, so that the body can be
@@ -40,6 +40,7 @@ fn get_primitive_type(name: &str) -> Option<Type> {
         "Bool" => Some(Type::Bool),
         "Int" => Some(Type::Int),
         "Null" => Some(Type::Null),
+        "Number" => Some(Type::Number),
         "String" => Some(Type::String),
         "Void" => Some(Type::Void),
         _ => None,
@@ -251,11 +252,38 @@ pub struct TypeChecker<'a> {
     // TODO: Do I really need to borrow it?
     // Could also move it into and out of the checker.
     env: &'a mut Env,
+
+    /// If enabled, the types of variables at their binding sites and uses.
+    ///
+    /// This is not needed for typechecking itself, it is for tooling such as
+    /// the language server, where we want to show the type of a variable.
+    var_types: Option<Vec<(Span, SourcedType)>>,
 }
 
 impl<'a> TypeChecker<'a> {
     pub fn new(env: &'a mut Env) -> TypeChecker<'a> {
-        TypeChecker { env }
+        TypeChecker {
+            env,
+            var_types: None,
+        }
+    }
+
+    /// Start recording the types of variables, see [`TypeChecker::take_var_types`].
+    pub fn record_var_types(&mut self) {
+        self.var_types = Some(Vec::new());
+    }
+
+    /// Return the recorded types of variables, in the order we encountered them.
+    ///
+    /// When typechecking fails, this contains the variables up to the error.
+    pub fn take_var_types(&mut self) -> Vec<(Span, SourcedType)> {
+        self.var_types.take().unwrap_or_default()
+    }
+
+    fn record_var_type(&mut self, span: Span, type_: &SourcedType) {
+        if let Some(var_types) = self.var_types.as_mut() {
+            var_types.push((span, type_.clone()));
+        }
     }
 
     /// Check that an expression fits the type requirements.
@@ -377,6 +405,7 @@ impl<'a> TypeChecker<'a> {
             Expr::NullLit => type_literal(expr_span, Type::Null).is_subtype_of(expected).check(expr_span)?,
             Expr::BoolLit(..) => type_literal(expr_span, Type::Bool).is_subtype_of(expected).check(expr_span)?,
             Expr::IntegerLit(..) => type_literal(expr_span, Type::Int).is_subtype_of(expected).check(expr_span)?,
+            Expr::NumberLit(..) => type_literal(expr_span, Type::Number).is_subtype_of(expected).check(expr_span)?,
             Expr::StringLit(..) => type_literal(expr_span, Type::String).is_subtype_of(expected).check(expr_span)?,
 
             Expr::Format(fragments) => {
@@ -411,7 +440,13 @@ impl<'a> TypeChecker<'a> {
 
             Expr::Var { span, ident } => match self.env.lookup(ident) {
                 None => return span.error("Unknown variable.").err(),
-                Some(t) => t.is_subtype_of(expected).check(*span)?,
+                Some(t) => {
+                    // Not `record_var_type`, because `t` borrows the env.
+                    if let Some(var_types) = self.var_types.as_mut() {
+                        var_types.push((*span, t.clone()));
+                    }
+                    t.is_subtype_of(expected).check(*span)?
+                }
             },
 
             Expr::Field { inner, inner_span, .. } => {
@@ -584,6 +619,7 @@ impl<'a> TypeChecker<'a> {
                         type_: arg_type.type_.clone(),
                     };
                     arg_types.push(fn_arg);
+                    self.record_var_type(*arg_span, &arg_type.type_);
                     self.env.push(arg_name.clone(), arg_type.type_.clone());
                 }
                 &fn_req.result
@@ -602,6 +638,7 @@ impl<'a> TypeChecker<'a> {
                         type_: type_any().clone(),
                     };
                     arg_types.push(fn_arg);
+                    self.record_var_type(*arg_span, type_any());
                     self.env.push(arg_name.clone(), type_any().clone());
                 }
                 type_any()
@@ -646,11 +683,17 @@ impl<'a> TypeChecker<'a> {
         // that's an error. But there's *another* error, which is applying `not`
         // to an int, and if we report only one type error, that seems like it
         // should come first, as it comes first in the evaluation order too.
-        let (body_type, result_type) = match op {
-            UnOp::Neg => (Type::Int, Type::Int),
-            UnOp::Not => (Type::Bool, Type::Bool),
+        let body_type = match op {
+            UnOp::Neg => Type::Number,
+            UnOp::Not => Type::Bool,
+        };
+        let body_type = self.check_expr(&type_operator(op_span, body_type), body_span, body)?;
+        let result_type = match op {
+            // Negation preserves integers.
+            UnOp::Neg if body_type.type_ == Type::Int => Type::Int,
+            UnOp::Neg => Type::Number,
+            UnOp::Not => Type::Bool,
         };
-        self.check_expr(&type_operator(op_span, body_type), body_span, body)?;
         Ok(type_operator(op_span, result_type))
     }
 
@@ -664,7 +707,9 @@ impl<'a> TypeChecker<'a> {
         rhs: &mut Expr,
     ) -> Result<SourcedType> {
         let (arg_type, result_type) = match op {
-            BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div => (Type::Int, Type::Int),
+            BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div => {
+                return self.check_binop_arithmetic(op_span, lhs_span, rhs_span, lhs, rhs)
+            }
             BinOp::And | BinOp::Or => (Type::Bool, Type::Bool),
             // Comparison operators make sense on many types (Int, String), even
             // composite types (e.g. List[Int] would have lexicographic order).
@@ -683,6 +728,26 @@ impl<'a> TypeChecker<'a> {
         Ok(type_operator(op_span, result_type))
     }
 
+    fn check_binop_arithmetic(
+        &mut self,
+        op_span: Span,
+        lhs_span: Span,
+        rhs_span: Span,
+        lhs: &mut Expr,
+        rhs: &mut Expr,
+    ) -> Result<SourcedType> {
+        let arg_type = type_operator(op_span, Type::Number);
+        let lhs_type = self.check_expr(&arg_type, lhs_span, lhs)?;
+        let rhs_type = self.check_expr(&arg_type, rhs_span, rhs)?;
+        // Arithmetic on integers produces integers, as soon as a number is
+        // involved, the result is a number.
+        let result_type = match (&lhs_type.type_, &rhs_type.type_) {
+            (Type::Int, Type::Int) => Type::Int,
+            _ => Type::Number,
+        };
+        Ok(type_operator(op_span, result_type))
+    }
+
     fn check_binop_union(
         &mut self,
         op_span: Span,
@@ -913,7 +978,7 @@ impl<'a> TypeChecker<'a> {
     fn check_stmt(&mut self, stmt: &mut Stmt) -> Result<()> {
         match stmt {
             Stmt::Let {
-                ident_span: _,
+                ident_span,
                 ident,
                 type_,
                 value_span,
@@ -933,6 +998,7 @@ impl<'a> TypeChecker<'a> {
                     None => inferred,
                     Some(_) => required_type,
                 };
+                self.record_var_type(*ident_span, &bound_type);
                 self.env.push(ident.clone(), bound_type);
 
                 Ok(())
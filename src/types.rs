@@ -41,6 +41,9 @@ pub enum Type {
     /// The primitive type `Int`.
     Int,
 
+    /// The primitive type `Number`, which includes `Int`.
+    Number,
+
     /// The primitive type `String`.
     String,
 
@@ -65,7 +68,13 @@ impl Type {
     pub fn is_atom(&self) -> bool {
         matches!(
             self,
-            Type::Bool | Type::Int | Type::Null | Type::String | Type::Void | Type::Any,
+            Type::Bool
+                | Type::Int
+                | Type::Number
+                | Type::Null
+                | Type::String
+                | Type::Void
+                | Type::Any,
         )
     }
 
@@ -80,6 +89,7 @@ impl Type {
             Type::Null => "Null",
             Type::Bool => "Bool",
             Type::Int => "Int",
+            Type::Number => "Number",
             Type::String => "String",
             Type::Dict(..) => "Dict",
             Type::List(..) => "List",
@@ -436,6 +446,8 @@ impl SourcedType {
             // If we have matching primitive types, they are preserved.
             (Type::Bool, Type::Bool) => (Type::Bool, src_meet),
             (Type::Int, Type::Int) => (Type::Int, src_meet),
+            (Type::Number, Type::Number) => (Type::Number, src_meet),
+            (Type::Int | Type::Number, Type::Int | Type::Number) => (Type::Number, Source::None),
             (Type::Null, Type::Null) => (Type::Null, src_meet),
             (Type::String, Type::String) => (Type::String, src_meet),
 
@@ -518,6 +530,12 @@ impl SourcedType {
             // or will it work fine like this?
             (Type::Bool, Type::Bool) => TypeDiff::Ok(other.clone()),
             (Type::Int, Type::Int) => TypeDiff::Ok(other.clone()),
+            (Type::Number, Type::Number) => TypeDiff::Ok(other.clone()),
+
+            // Every integer is a number, but a number is an integer only
+            // for some values.
+            (Type::Int, Type::Number) => TypeDiff::Ok(self.clone()),
+            (Type::Number, Type::Int) => TypeDiff::Defer(other.clone()),
             (Type::Null, Type::Null) => TypeDiff::Ok(other.clone()),
             (Type::String, Type::String) => TypeDiff::Ok(other.clone()),
 
@@ -729,6 +747,7 @@ pub fn builtin(type_: Type) -> SourcedType {
 macro_rules! make_type {
     (Any) => { builtin(Type::Any) };
     (Int) => { builtin(Type::Int) };
+    (Number) => { builtin(Type::Number) };
     (Bool) => { builtin(Type::Bool) };
     (String) => { builtin(Type::String) };
     ([$elem:tt]) => { builtin(Type::List(Rc::new(make_type!($elem)))) };
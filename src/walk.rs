@@ -0,0 +1,338 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Finding the documents in a directory, for `rcl format`.
+//!
+//! While walking the directory, we respect ignore files in the style of
+//! `.gitignore`, so vendored or generated documents can be skipped. We follow
+//! symlinks, but we visit every directory only once, so a symlink that points
+//! to one of its parents does not trap us in a loop.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+use crate::glob::Glob;
+use crate::pprint::{concat, Doc};
+
+/// The name of the RCL-specific ignore file.
+///
+/// This has the same format as `.gitignore`, and it takes precedence over a
+/// `.gitignore` in the same directory.
+pub const IGNORE_FILE_NAME: &str = ".rclignore";
+
+/// The names of the ignore files that we read in every directory, in order.
+const IGNORE_FILE_NAMES: [&str; 2] = [".gitignore", IGNORE_FILE_NAME];
+
+/// A line in an ignore file.
+struct Rule {
+    glob: Glob,
+    /// For `!` rules, which re-include paths that an earlier rule excluded.
+    negated: bool,
+    /// For rules with a trailing slash, which only match directories.
+    dir_only: bool,
+}
+
+/// A parsed ignore file, in the format of `.gitignore`.
+pub struct IgnoreFile {
+    /// The directory that contains the file, paths are relative to it.
+    dir: PathBuf,
+    rules: Vec<Rule>,
+}
+
+impl IgnoreFile {
+    /// Parse an ignore file that is located in `dir`.
+    pub fn parse(dir: PathBuf, input: &str) -> IgnoreFile {
+        let mut rules = Vec::new();
+        for line in input.lines() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (negated, pattern) = match line.strip_prefix('!') {
+                Some(pattern) => (true, pattern),
+                None => (false, line),
+            };
+            let (dir_only, pattern) = match pattern.strip_suffix('/') {
+                Some(pattern) => (true, pattern),
+                None => (false, pattern),
+            };
+            // A pattern without slash matches at any level, a pattern with a
+            // slash is relative to the directory of the ignore file.
+            let glob = if !pattern.contains('/') {
+                Glob::new(&format!("/**/{pattern}"))
+            } else if pattern.starts_with('/') {
+                Glob::new(pattern)
+            } else {
+                Glob::new(&format!("/{pattern}"))
+            };
+            rules.push(Rule {
+                glob,
+                negated,
+                dir_only,
+            });
+        }
+        IgnoreFile { dir, rules }
+    }
+
+    /// Return whether the rules ignore the path, or `None` if no rule matches.
+    fn is_ignored(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let relative = path.strip_prefix(&self.dir).ok()?;
+        let relative_str = format!("/{}", to_slash_path(relative));
+        // Later rules take precedence, so we search from the back.
+        self.rules
+            .iter()
+            .rev()
+            .filter(|rule| is_dir || !rule.dir_only)
+            .find(|rule| rule.glob.matches(&relative_str))
+            .map(|rule| !rule.negated)
+    }
+}
+
+/// Which files in a directory tree are documents.
+pub enum Select<'a> {
+    /// Files that have the `.rcl` extension.
+    RclFiles,
+    /// Files whose path relative to the root of the walk, prefixed with a
+    /// slash, matches the glob.
+    Glob(&'a Glob),
+}
+
+/// Split a path with wildcards into a directory to walk and a glob.
+///
+/// The directory is the longest prefix without wildcards, the glob applies to
+/// paths relative to it, see [`Select::Glob`]. Returns `None` when the path
+/// contains no wildcards.
+pub fn split_glob(pattern: &str) -> Option<(&str, Glob)> {
+    let is_wildcard = |c: char| matches!(c, '*' | '?' | '[' | '{');
+    let first_wildcard = pattern.find(is_wildcard)?;
+    let (dir, glob) = match pattern[..first_wildcard].rfind('/') {
+        Some(0) => ("/", &pattern[1..]),
+        Some(i) => (&pattern[..i], &pattern[i + 1..]),
+        None => ("", pattern),
+    };
+    Some((dir, Glob::new(&format!("/{glob}"))))
+}
+
+/// Walks a directory tree, and tracks the ignore files that apply.
+struct Walker<'a> {
+    select: Select<'a>,
+    use_ignore_files: bool,
+    /// The ignore files that apply to the current directory, outermost first.
+    ignore_files: Vec<IgnoreFile>,
+    /// The documents found so far, relative to the root of the walk.
+    documents: Vec<PathBuf>,
+    /// The canonical paths of the directories and documents visited so far.
+    visited: BTreeSet<PathBuf>,
+}
+
+impl<'a> Walker<'a> {
+    /// Push the ignore files in `dir`, return how many we pushed.
+    fn push_ignore_files(&mut self, dir: &Path) -> Result<usize> {
+        if !self.use_ignore_files {
+            return Ok(0);
+        }
+        let mut n = 0;
+        for name in IGNORE_FILE_NAMES {
+            let path = dir.join(name);
+            if !path.is_file() {
+                continue;
+            }
+            let data = std::fs::read_to_string(&path).map_err(|err| {
+                Error::new(concat! {
+                    "Failed to read '" Doc::path(path.clone()) "': " err.to_string()
+                })
+            })?;
+            self.ignore_files
+                .push(IgnoreFile::parse(dir.to_path_buf(), &data));
+            n += 1;
+        }
+        Ok(n)
+    }
+
+    fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        // Files in subdirectories take precedence, so we search from the back.
+        self.ignore_files
+            .iter()
+            .rev()
+            .find_map(|file| file.is_ignored(path, is_dir))
+            .unwrap_or(false)
+    }
+
+    fn walk(&mut self, dir: &Path, relative: &Path) -> Result<()> {
+        let n_pushed = self.push_ignore_files(dir)?;
+
+        let read_error = |err: std::io::Error| {
+            Error::new(concat! {
+                "Failed to read directory '" Doc::path(dir) "': " err.to_string()
+            })
+        };
+        let mut entries = Vec::new();
+        for entry in std::fs::read_dir(dir).map_err(read_error)? {
+            let entry = entry.map_err(read_error)?;
+            let file_type = entry.file_type().map_err(read_error)?;
+            entries.push((entry.file_name(), file_type));
+        }
+        // Sort the entries, so the order of the output is deterministic.
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+        for (name, file_type) in entries {
+            if name == ".git" {
+                continue;
+            }
+            let path = dir.join(&name);
+            let (is_dir, is_file) = if file_type.is_symlink() {
+                // Dangling symlinks are not an error, there is nothing to format.
+                match std::fs::metadata(&path) {
+                    Ok(target) => (target.is_dir(), target.is_file()),
+                    Err(..) => continue,
+                }
+            } else {
+                (file_type.is_dir(), file_type.is_file())
+            };
+            if !(is_dir || is_file) || self.is_ignored(&path, is_dir) {
+                continue;
+            }
+            let relative = relative.join(&name);
+            let is_selected = is_dir
+                || match self.select {
+                    Select::RclFiles => relative.extension().map_or(false, |ext| ext == "rcl"),
+                    Select::Glob(glob) => glob.matches(&format!("/{}", to_slash_path(&relative))),
+                };
+            if !is_selected {
+                continue;
+            }
+            // When we reach a directory or document a second time through a
+            // symlink, skip it. For directories, this prevents loops.
+            let canonical = std::fs::canonicalize(&path).map_err(read_error)?;
+            if !self.visited.insert(canonical) {
+                continue;
+            }
+            if is_dir {
+                self.walk(&path, &relative)?;
+            } else {
+                self.documents.push(relative);
+            }
+        }
+
+        self.ignore_files
+            .truncate(self.ignore_files.len() - n_pushed);
+        Ok(())
+    }
+}
+
+/// Render a relative path with `/` separators, to match globs against.
+fn to_slash_path(path: &Path) -> String {
+    let mut result = String::new();
+    for component in path.components() {
+        if !result.is_empty() {
+            result.push('/');
+        }
+        result.push_str(&component.as_os_str().to_string_lossy());
+    }
+    result
+}
+
+/// Find the selected files in the directory `root` and its subdirectories.
+///
+/// The results are relative to `root`, and sorted. With `use_ignore_files`,
+/// paths that `.gitignore` or [`IGNORE_FILE_NAME`] files exclude are skipped.
+/// This includes the ignore files in the parent directories of `root`, up to
+/// the root of the Git repository, if `root` is inside one.
+pub fn find_documents(root: &Path, select: Select, use_ignore_files: bool) -> Result<Vec<PathBuf>> {
+    let mut walker = Walker {
+        select,
+        use_ignore_files,
+        ignore_files: Vec::new(),
+        documents: Vec::new(),
+        visited: BTreeSet::new(),
+    };
+
+    let root = std::fs::canonicalize(root).map_err(|err| {
+        Error::new(concat! {
+            "Failed to access path '" Doc::path(root) "': " err.to_string()
+        })
+    })?;
+    let repository_root = root.ancestors().find(|dir| dir.join(".git").exists());
+    if let Some(repository_root) = repository_root {
+        let mut parents: Vec<&Path> = root
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| dir.starts_with(repository_root))
+            .collect();
+        parents.reverse();
+        for dir in parents {
+            walker.push_ignore_files(dir)?;
+        }
+    }
+
+    walker.visited.insert(root.clone());
+    walker.walk(&root, Path::new(""))?;
+    Ok(walker.documents)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{find_documents, split_glob, Glob, IgnoreFile, Select};
+    use std::path::{Path, PathBuf};
+
+    fn is_ignored(rules: &str, path: &str, is_dir: bool) -> Option<bool> {
+        let file = IgnoreFile::parse(PathBuf::from("/repo"), rules);
+        file.is_ignored(&Path::new("/repo").join(path), is_dir)
+    }
+
+    #[test]
+    fn ignore_file_matches_like_gitignore() {
+        let rules = "# Comment.\n*.gen.rcl\n/vendor\nbuild/\n!keep.gen.rcl\ndocs/*.rcl\n";
+        assert_eq!(is_ignored(rules, "a.gen.rcl", false), Some(true));
+        assert_eq!(is_ignored(rules, "sub/a.gen.rcl", false), Some(true));
+        assert_eq!(is_ignored(rules, "sub/keep.gen.rcl", false), Some(false));
+        assert_eq!(is_ignored(rules, "vendor", true), Some(true));
+        assert_eq!(is_ignored(rules, "sub/vendor", true), None);
+        assert_eq!(is_ignored(rules, "build", true), Some(true));
+        assert_eq!(is_ignored(rules, "build", false), None);
+        assert_eq!(is_ignored(rules, "docs/a.rcl", false), Some(true));
+        assert_eq!(is_ignored(rules, "docs/sub/a.rcl", false), None);
+        assert_eq!(is_ignored(rules, "a.rcl", false), None);
+    }
+
+    #[test]
+    fn split_glob_splits_at_last_literal_directory() {
+        let split = |pattern| split_glob(pattern).map(|(dir, _glob)| dir);
+        assert_eq!(split("configs/**/*.rcl"), Some("configs"));
+        assert_eq!(split("a/b/c*.rcl"), Some("a/b"));
+        assert_eq!(split("*.rcl"), Some(""));
+        assert_eq!(split("/srv/*.rcl"), Some("/srv"));
+        assert_eq!(split("/*.rcl"), Some("/"));
+        assert_eq!(split("a/b.rcl"), None);
+        let (_, glob) = split_glob("configs/**/*.rcl").unwrap();
+        assert!(glob.matches("/y.rcl"));
+        assert!(glob.matches("/x/y.rcl"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn find_documents_follows_symlinks_without_looping() {
+        let root = std::env::temp_dir().join(format!("rcl-walk-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("a/b")).unwrap();
+        std::fs::write(root.join("a/b/x.rcl"), "null").unwrap();
+        std::fs::write(root.join("a/y.json"), "null").unwrap();
+        std::os::unix::fs::symlink("..", root.join("a/b/up")).unwrap();
+        std::os::unix::fs::symlink("a/b", root.join("link")).unwrap();
+        std::os::unix::fs::symlink("missing", root.join("dangling.rcl")).unwrap();
+
+        let found = find_documents(&root, Select::RclFiles, false).unwrap();
+        assert_eq!(found, vec![PathBuf::from("a/b/x.rcl")]);
+
+        let glob = Glob::new("/**/*.json");
+        let found = find_documents(&root, Select::Glob(&glob), false).unwrap();
+        assert_eq!(found, vec![PathBuf::from("a/y.json")]);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,1289 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! A parser for YAML documents.
+//!
+//! YAML is a large language, this parser supports the subset that
+//! configuration files and inventories use in practice: block and flow
+//! collections, plain, quoted, and block scalars, comments, anchors, aliases,
+//! merge keys, and streams of multiple documents. A stream with more than one
+//! document becomes a list of the documents.
+//!
+//! Plain scalars are resolved conservatively, with the core schema of YAML 1.2
+//! rather than the YAML 1.1 rules: `yes` and `no` remain strings. Floats become
+//! decimal numbers. RCL cannot represent infinities and NaN, so these are an
+//! error, rather than silently becoming a string. Tags and complex keys are not
+//! supported, and neither are anchors on keys. See also
+//! <https://yaml.org/spec/1.2.2/>.
+
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+use crate::decimal::Decimal;
+use crate::error::{IntoError, Result};
+use crate::runtime::Value;
+use crate::source::{DocId, Span};
+
+/// Parse a YAML stream into a value.
+pub fn parse_yaml(doc: DocId, input: &str) -> Result<Value> {
+    let mut parser = Parser {
+        doc,
+        input,
+        pos: 0,
+        depth: 0,
+        anchors: HashMap::new(),
+    };
+    parser.parse_stream()
+}
+
+/// How to treat line breaks at the end of a block scalar.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Chomping {
+    /// Keep a single line break, the default.
+    Clip,
+    /// Remove all trailing line breaks, `-`.
+    Strip,
+    /// Keep all trailing line breaks, `+`.
+    Keep,
+}
+
+struct Parser<'a> {
+    doc: DocId,
+    input: &'a str,
+    pos: usize,
+    /// The nesting depth of nodes, to prevent stack overflow.
+    depth: u32,
+    /// The values of the anchors defined so far in the current document.
+    ///
+    /// While we parse the node that an anchor is attached to, its value is
+    /// `None`, so an alias to it inside that node is a cycle.
+    anchors: HashMap<&'a str, Option<Value>>,
+}
+
+impl<'a> Parser<'a> {
+    fn span(&self, start: usize, end: usize) -> Span {
+        Span::new(self.doc, start, end)
+    }
+
+    /// Return a span of the character at the cursor, or an empty one at the end.
+    fn span_here(&self) -> Span {
+        let n = self.input[self.pos..]
+            .chars()
+            .next()
+            .map_or(0, |ch| ch.len_utf8());
+        self.span(self.pos, self.pos + n)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<u8> {
+        self.input.as_bytes().get(self.pos + offset).copied()
+    }
+
+    fn is_end(&self) -> bool {
+        self.pos >= self.input.len()
+    }
+
+    /// Return the byte offset of the start of the line that the cursor is on.
+    fn line_start(&self) -> usize {
+        self.input[..self.pos].rfind('\n').map_or(0, |i| i + 1)
+    }
+
+    /// Return the column of the cursor, which is its indentation if it is at content.
+    fn column(&self) -> usize {
+        self.pos - self.line_start()
+    }
+
+    fn increase_depth(&mut self) -> Result<()> {
+        self.depth += 1;
+        if self.depth >= 100 {
+            return self
+                .span_here()
+                .error("Parser recursion limit reached, please reduce nesting.")
+                .err();
+        }
+        Ok(())
+    }
+
+    fn skip_inline_space(&mut self) {
+        while let Some(b' ' | b'\t') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    /// If the cursor is at a comment, move it to the end of the line.
+    fn skip_comment(&mut self) {
+        if self.peek() == Some(b'#') {
+            self.pos = self.input[self.pos..]
+                .find(['\r', '\n'])
+                .map_or(self.input.len(), |i| self.pos + i);
+        }
+    }
+
+    /// Whether the rest of the line is empty, apart from whitespace or a comment.
+    ///
+    /// This assumes that the caller already skipped whitespace.
+    fn at_line_end(&self) -> bool {
+        matches!(self.peek(), None | Some(b'\n' | b'\r' | b'#'))
+    }
+
+    /// Whether the byte at the given offset separates tokens.
+    fn is_blank_at(&self, offset: usize) -> bool {
+        matches!(
+            self.peek_at(offset),
+            None | Some(b' ' | b'\t' | b'\n' | b'\r')
+        )
+    }
+
+    /// Whether the cursor is at a `-` that starts a block sequence item.
+    fn is_sequence_indicator(&self) -> bool {
+        self.peek() == Some(b'-') && self.is_blank_at(1)
+    }
+
+    /// Whether the cursor is at a `---` or `...` line that starts or ends a document.
+    fn is_document_marker(&self) -> bool {
+        let rest = &self.input[self.pos..];
+        self.column() == 0
+            && (rest.starts_with("---") || rest.starts_with("..."))
+            && self.is_blank_at(3)
+    }
+
+    /// Skip whitespace, comments, and line breaks, up to the next content.
+    fn skip_to_content(&mut self) -> Result<()> {
+        loop {
+            self.skip_inline_space();
+            self.skip_comment();
+            match self.peek() {
+                Some(b'\n' | b'\r') => self.pos += 1,
+                _ => break,
+            }
+        }
+        if !self.is_end() {
+            let line_start = self.line_start();
+            if self.input[line_start..self.pos].contains('\t') {
+                return self
+                    .span(line_start, self.pos)
+                    .error("Tabs are not allowed for indentation in YAML.")
+                    .err();
+            }
+        }
+        Ok(())
+    }
+
+    /// Skip the remainder of a line after a value, and move to the next content.
+    fn finish_line(&mut self) -> Result<()> {
+        self.skip_inline_space();
+        self.skip_comment();
+        match self.peek() {
+            None | Some(b'\n' | b'\r') => self.skip_to_content(),
+            Some(b':') => self
+                .span_here()
+                .error("Unexpected ':'. A string that contains ': ' needs to be quoted.")
+                .err(),
+            Some(..) => self
+                .span_here()
+                .error("Expected a line break after the value.")
+                .err(),
+        }
+    }
+
+    fn parse_stream(&mut self) -> Result<Value> {
+        if self.input.starts_with('\u{feff}') {
+            self.pos = '\u{feff}'.len_utf8();
+        }
+        let mut documents = Vec::new();
+
+        loop {
+            self.skip_to_content()?;
+
+            // We don't interpret directives, but we do allow them.
+            while self.peek() == Some(b'%') && self.column() == 0 {
+                self.skip_rest_of_line();
+                self.skip_to_content()?;
+            }
+
+            if self.is_end() {
+                break;
+            }
+            if self.is_document_marker() && self.input[self.pos..].starts_with("...") {
+                self.pos += 3;
+                continue;
+            }
+
+            self.anchors.clear();
+            let value = if self.is_document_marker() {
+                self.pos += 3;
+                self.skip_inline_space();
+                if self.at_line_end() {
+                    self.skip_to_content()?;
+                    if self.is_end() || self.is_document_marker() {
+                        Value::Null
+                    } else {
+                        self.block_node(-1, true)?
+                    }
+                } else {
+                    self.block_node(-1, false)?
+                }
+            } else {
+                self.block_node(-1, true)?
+            };
+            documents.push(value);
+
+            if !self.is_end() && !self.is_document_marker() {
+                return self
+                    .span_here()
+                    .error("Expected the document to end after the value.")
+                    .err();
+            }
+        }
+
+        match documents.len() {
+            0 => Ok(Value::Null),
+            1 => Ok(documents.pop().expect("We have one document.")),
+            _ => Ok(Value::List(Rc::new(documents))),
+        }
+    }
+
+    /// Move the cursor to the end of the line, regardless of what is on it.
+    fn skip_rest_of_line(&mut self) {
+        self.pos = self.input[self.pos..]
+            .find(['\r', '\n'])
+            .map_or(self.input.len(), |i| self.pos + i);
+    }
+
+    /// Parse a node in block context.
+    ///
+    /// The node belongs to a collection at indentation `parent`, so lines that
+    /// continue the node must be indented further than that. When the node
+    /// starts on the same line as a mapping key, it cannot be a block
+    /// collection, and `allow_collection` is false. Afterwards, the cursor is
+    /// at the next content.
+    fn block_node(&mut self, parent: isize, allow_collection: bool) -> Result<Value> {
+        self.increase_depth()?;
+        let result = self.block_node_inner(parent, allow_collection);
+        self.depth -= 1;
+        result
+    }
+
+    fn block_node_inner(&mut self, parent: isize, allow_collection: bool) -> Result<Value> {
+        let column = self.column();
+        match self.peek() {
+            Some(b'&') => {
+                let name = self.anchor_name()?;
+                self.anchors.insert(name, None);
+                self.skip_inline_space();
+                let value = if self.at_line_end() {
+                    self.skip_to_content()?;
+                    let is_nested = !self.is_end()
+                        && !self.is_document_marker()
+                        && self.column() as isize > parent;
+                    if is_nested {
+                        self.block_node(parent, true)?
+                    } else {
+                        Value::Null
+                    }
+                } else {
+                    self.block_node(parent, allow_collection)?
+                };
+                self.anchors.insert(name, Some(value.clone()));
+                Ok(value)
+            }
+            Some(b'*') => {
+                let value = self.alias()?;
+                self.finish_line()?;
+                Ok(value)
+            }
+            Some(b'!') => self.error_tag(),
+            Some(b'?') if self.is_blank_at(1) => self.error_complex_key(),
+            Some(b'-') if self.is_sequence_indicator() => {
+                if !allow_collection {
+                    return self
+                        .span_here()
+                        .error("A block sequence cannot start on the same line as its key.")
+                        .err();
+                }
+                self.block_sequence(column)
+            }
+            Some(b'|' | b'>') => self.block_scalar(parent),
+            Some(b'[' | b'{') => {
+                let value = self.flow_node()?;
+                self.finish_line()?;
+                Ok(value)
+            }
+            _ if allow_collection && self.looks_like_key() => self.block_mapping(column),
+            Some(b'"') => {
+                let value = Value::String(self.double_quoted()?.into());
+                self.finish_line()?;
+                Ok(value)
+            }
+            Some(b'\'') => {
+                let value = Value::String(self.single_quoted()?.into());
+                self.finish_line()?;
+                Ok(value)
+            }
+            _ => self.plain_block(parent),
+        }
+    }
+
+    fn error_tag<T>(&self) -> Result<T> {
+        self.span_here()
+            .error("YAML tags are not supported.")
+            .with_help("For '!!str', put the value in quotes instead.")
+            .err()
+    }
+
+    fn error_complex_key<T>(&self) -> Result<T> {
+        self.span_here()
+            .error("Complex mapping keys are not supported.")
+            .err()
+    }
+
+    /// Whether the line at the cursor starts with a key, followed by `:`.
+    fn looks_like_key(&mut self) -> bool {
+        let start = self.pos;
+        let key_end = match self.peek() {
+            Some(b'"') => self.double_quoted().ok().map(|_| self.pos),
+            Some(b'\'') => self.single_quoted().ok().map(|_| self.pos),
+            Some(b'*') => self.alias().ok().map(|_| self.pos),
+            _ => Some(self.scan_plain_line(start, false)),
+        };
+        let is_key = match key_end {
+            Some(end) if !self.input[start..end].contains('\n') => {
+                self.pos = end;
+                self.skip_inline_space();
+                self.peek() == Some(b':') && self.is_blank_at(1)
+            }
+            _ => false,
+        };
+        self.pos = start;
+        is_key
+    }
+
+    /// Return the end of the plain scalar that starts at `start`, on this line.
+    ///
+    /// A plain scalar ends at `: ` and ` #`. In flow context it also ends at
+    /// flow indicators. Trailing whitespace is not part of the scalar.
+    fn scan_plain_line(&self, start: usize, is_flow: bool) -> usize {
+        let bytes = self.input.as_bytes();
+        let is_flow_indicator = |b: u8| matches!(b, b',' | b'[' | b']' | b'{' | b'}');
+        let mut i = start;
+        while i < bytes.len() {
+            match bytes[i] {
+                b'\n' | b'\r' => break,
+                b':' => {
+                    let next = bytes.get(i + 1).copied();
+                    let ends = match next {
+                        None | Some(b' ' | b'\t' | b'\n' | b'\r') => true,
+                        Some(b) => is_flow && is_flow_indicator(b),
+                    };
+                    if ends {
+                        break;
+                    }
+                }
+                b'#' if i > start && matches!(bytes[i - 1], b' ' | b'\t') => break,
+                b if is_flow && is_flow_indicator(b) => break,
+                _ => {}
+            }
+            i += 1;
+        }
+        while i > start && matches!(bytes[i - 1], b' ' | b'\t') {
+            i -= 1;
+        }
+        i
+    }
+
+    fn anchor_name(&mut self) -> Result<&'a str> {
+        let start = self.pos;
+        self.pos += 1;
+        while let Some(b) = self.peek() {
+            if matches!(
+                b,
+                b' ' | b'\t' | b'\n' | b'\r' | b',' | b'[' | b']' | b'{' | b'}'
+            ) {
+                break;
+            }
+            self.pos += 1;
+        }
+        if self.pos == start + 1 {
+            return self
+                .span(start, self.pos)
+                .error("Expected an anchor name.")
+                .err();
+        }
+        Ok(&self.input[start + 1..self.pos])
+    }
+
+    fn alias(&mut self) -> Result<Value> {
+        let start = self.pos;
+        let name = self.anchor_name()?;
+        match self.anchors.get(name) {
+            Some(Some(value)) => Ok(value.clone()),
+            Some(None) => self
+                .span(start, self.pos)
+                .error("This alias refers to an anchor that contains it, which would be a cycle.")
+                .err(),
+            None => self
+                .span(start, self.pos)
+                .error("This alias refers to an anchor that is not defined before it.")
+                .err(),
+        }
+    }
+
+    fn block_mapping(&mut self, column: usize) -> Result<Value> {
+        let mut entries = BTreeMap::new();
+        let mut merges = Vec::new();
+
+        loop {
+            let key_start = self.pos;
+            let (key, is_merge) = self.block_key()?;
+            let key_span = self.span(key_start, self.pos);
+            self.skip_inline_space();
+            if self.peek() != Some(b':') {
+                return self.span_here().error("Expected ':' after the key.").err();
+            }
+            self.pos += 1;
+            self.skip_inline_space();
+
+            let value = if self.at_line_end() {
+                self.skip_to_content()?;
+                if self.is_end() || self.is_document_marker() {
+                    Value::Null
+                } else if self.column() > column {
+                    self.block_node(column as isize, true)?
+                } else if self.column() == column && self.is_sequence_indicator() {
+                    // A sequence as the value of a key does not need to be
+                    // indented further than the key.
+                    self.increase_depth()?;
+                    let value = self.block_sequence(column)?;
+                    self.depth -= 1;
+                    value
+                } else {
+                    Value::Null
+                }
+            } else {
+                self.block_node(column as isize, false)?
+            };
+
+            if is_merge {
+                merges.push((key_span, value));
+            } else if entries.insert(key, value).is_some() {
+                return key_span
+                    .error("Duplicate key, keys in a mapping must be unique.")
+                    .err();
+            }
+
+            if self.is_end() || self.is_document_marker() || self.column() < column {
+                break;
+            }
+            if self.column() > column {
+                return self
+                    .span_here()
+                    .error("Unexpected indentation, expected a key at the same level as the preceding key.")
+                    .err();
+            }
+        }
+
+        self.apply_merges(&mut entries, merges)?;
+        Ok(Value::Dict(Rc::new(entries)))
+    }
+
+    /// Parse a mapping key in block context, and return whether it is a merge key.
+    fn block_key(&mut self) -> Result<(Value, bool)> {
+        match self.peek() {
+            Some(b'"') => Ok((Value::String(self.double_quoted()?.into()), false)),
+            Some(b'\'') => Ok((Value::String(self.single_quoted()?.into()), false)),
+            Some(b'*') => Ok((self.alias()?, false)),
+            Some(b'&') => self
+                .span_here()
+                .error("Anchors on mapping keys are not supported.")
+                .err(),
+            Some(b'!') => self.error_tag(),
+            Some(b'?') if self.is_blank_at(1) => self.error_complex_key(),
+            Some(b'[' | b'{') => self.error_complex_key(),
+            _ => {
+                let start = self.pos;
+                let end = self.scan_plain_line(start, false);
+                self.pos = end;
+                let text = &self.input[start..end];
+                let key = resolve_plain(text, self.span(start, end))?;
+                Ok((key, text == "<<"))
+            }
+        }
+    }
+
+    /// Insert the entries of merge keys (`<<`) that the mapping doesn't already have.
+    ///
+    /// The value of a merge key is a mapping, or a list of mappings, where the
+    /// earlier mappings take precedence.
+    fn apply_merges(
+        &mut self,
+        entries: &mut BTreeMap<Value, Value>,
+        merges: Vec<(Span, Value)>,
+    ) -> Result<()> {
+        for (span, value) in merges {
+            let sources = match value {
+                Value::List(sources) => sources,
+                single => Rc::new(vec![single]),
+            };
+            for source in sources.iter() {
+                match source {
+                    Value::Dict(kv) => {
+                        for (k, v) in kv.iter() {
+                            entries.entry(k.clone()).or_insert_with(|| v.clone());
+                        }
+                    }
+                    _ => return span
+                        .error("The value of a merge key must be a mapping, or a list of mappings.")
+                        .err(),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn block_sequence(&mut self, column: usize) -> Result<Value> {
+        let mut items = Vec::new();
+
+        loop {
+            // Skip over the `-`.
+            self.pos += 1;
+            self.skip_inline_space();
+
+            let item = if self.at_line_end() {
+                self.skip_to_content()?;
+                let is_nested =
+                    !self.is_end() && !self.is_document_marker() && self.column() > column;
+                if is_nested {
+                    self.block_node(column as isize, true)?
+                } else {
+                    Value::Null
+                }
+            } else {
+                // The item can be a collection that starts on this line, like
+                // `- key: value`, its own column is where the content starts.
+                self.block_node(column as isize, true)?
+            };
+            items.push(item);
+
+            if self.is_end() || self.is_document_marker() || self.column() != column {
+                break;
+            }
+            if !self.is_sequence_indicator() {
+                break;
+            }
+        }
+
+        Ok(Value::List(Rc::new(items)))
+    }
+
+    /// Parse a plain scalar in block context, which may span multiple lines.
+    fn plain_block(&mut self, parent: isize) -> Result<Value> {
+        let start = self.pos;
+        if let Some(b',' | b']' | b'}' | b'%' | b'@' | b'`') = self.peek() {
+            return self
+                .span_here()
+                .error("Unexpected character, a plain scalar cannot start with this.")
+                .with_help("Put the value in quotes.")
+                .err();
+        }
+        let mut end = self.scan_plain_line(start, false);
+        if end == start {
+            return self.span_here().error("Expected a value.").err();
+        }
+        let mut text = self.input[start..end].to_string();
+        let mut is_multiline = false;
+        self.pos = end;
+
+        // Continuation lines must be indented further than the parent, and
+        // line breaks between them are folded.
+        loop {
+            let line_end = self.pos;
+            self.skip_inline_space();
+            if !matches!(self.peek(), Some(b'\n' | b'\r')) {
+                self.pos = line_end;
+                break;
+            }
+            let mut breaks = 0;
+            while let Some(b'\n' | b'\r') = self.peek() {
+                if self.peek() == Some(b'\r') && self.peek_at(1) == Some(b'\n') {
+                    self.pos += 1;
+                }
+                self.pos += 1;
+                breaks += 1;
+                self.skip_inline_space();
+            }
+            let continues = !self.is_end()
+                && !self.is_document_marker()
+                && self.column() as isize > parent
+                && self.peek() != Some(b'#');
+            let next_end = self.scan_plain_line(self.pos, false);
+            if !continues || next_end == self.pos {
+                self.pos = line_end;
+                break;
+            }
+            match breaks {
+                1 => text.push(' '),
+                n => text.extend(std::iter::repeat('\n').take(n - 1)),
+            }
+            text.push_str(&self.input[self.pos..next_end]);
+            is_multiline = true;
+            end = next_end;
+            self.pos = next_end;
+        }
+
+        let value = if is_multiline {
+            Value::String(text.into())
+        } else {
+            resolve_plain(&text, self.span(start, end))?
+        };
+        self.finish_line()?;
+        Ok(value)
+    }
+
+    /// Parse a literal (`|`) or folded (`>`) block scalar.
+    fn block_scalar(&mut self, parent: isize) -> Result<Value> {
+        let is_literal = self.peek() == Some(b'|');
+        self.pos += 1;
+
+        let mut chomping = Chomping::Clip;
+        let mut explicit_indent = None;
+        for _ in 0..2 {
+            match self.peek() {
+                Some(b'-') => chomping = Chomping::Strip,
+                Some(b'+') => chomping = Chomping::Keep,
+                Some(b @ b'1'..=b'9') => explicit_indent = Some((b - b'0') as usize),
+                _ => break,
+            }
+            self.pos += 1;
+        }
+        self.skip_inline_space();
+        self.skip_comment();
+        match self.peek() {
+            None => {}
+            Some(b'\r') if self.peek_at(1) == Some(b'\n') => self.pos += 2,
+            Some(b'\n' | b'\r') => self.pos += 1,
+            Some(..) => {
+                return self
+                    .span_here()
+                    .error("Expected a line break after the block scalar header.")
+                    .err()
+            }
+        }
+
+        let min_indent = (parent + 1).max(0) as usize;
+        let indent = match explicit_indent {
+            Some(n) => parent.max(0) as usize + n,
+            // Without indicator, the first non-empty line determines the indentation.
+            None => self.input[self.pos..]
+                .lines()
+                .find(|line| {
+                    !line
+                        .trim_start_matches(' ')
+                        .trim_end_matches('\r')
+                        .is_empty()
+                })
+                .map_or(min_indent, |line| {
+                    line.len() - line.trim_start_matches(' ').len()
+                }),
+        };
+
+        let mut lines = Vec::new();
+        if indent >= min_indent {
+            while !self.is_end() {
+                let line_end = self.input[self.pos..]
+                    .find('\n')
+                    .map_or(self.input.len(), |i| self.pos + i);
+                let line = self.input[self.pos..line_end].trim_end_matches('\r');
+                let spaces = line.len() - line.trim_start_matches(' ').len();
+                if spaces == line.len() {
+                    lines.push(if spaces > indent { &line[indent..] } else { "" });
+                } else if spaces < indent || (indent == 0 && self.is_document_marker()) {
+                    break;
+                } else {
+                    lines.push(&line[indent..]);
+                }
+                self.pos = (line_end + 1).min(self.input.len());
+            }
+        }
+
+        // Trailing empty lines are subject to chomping, not part of the body.
+        let body_len = lines
+            .iter()
+            .rposition(|line| !line.is_empty())
+            .map_or(0, |i| i + 1);
+        let (body, trailing) = lines.split_at(body_len);
+        let mut text = if is_literal {
+            body.join("\n")
+        } else {
+            fold_lines(body)
+        };
+        match chomping {
+            Chomping::Strip => {}
+            Chomping::Clip if body.is_empty() => {}
+            Chomping::Clip => text.push('\n'),
+            Chomping::Keep => {
+                let n = trailing.len() + usize::from(!body.is_empty());
+                text.extend(std::iter::repeat('\n').take(n));
+            }
+        }
+
+        self.skip_to_content()?;
+        Ok(Value::String(text.into()))
+    }
+
+    /// Handle a line break inside a quoted scalar, the cursor is at the break.
+    ///
+    /// Whitespace around the break is removed, a single break becomes a space,
+    /// and otherwise every empty line becomes a line break. Whitespace before
+    /// `keep` in `out` came from escape sequences, so we don't remove that.
+    fn fold_quoted_break(&mut self, out: &mut String, keep: usize) {
+        let trimmed = out.trim_end_matches([' ', '\t']).len().max(keep);
+        out.truncate(trimmed);
+        let mut breaks = 0;
+        while let Some(b'\n' | b'\r') = self.peek() {
+            if self.peek() == Some(b'\r') && self.peek_at(1) == Some(b'\n') {
+                self.pos += 1;
+            }
+            self.pos += 1;
+            breaks += 1;
+            self.skip_inline_space();
+        }
+        match breaks {
+            1 => out.push(' '),
+            n => out.extend(std::iter::repeat('\n').take(n - 1)),
+        }
+    }
+
+    fn single_quoted(&mut self) -> Result<String> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            let run_start = self.pos;
+            while let Some(b) = self.peek() {
+                if matches!(b, b'\'' | b'\n' | b'\r') {
+                    break;
+                }
+                self.pos += 1;
+            }
+            out.push_str(&self.input[run_start..self.pos]);
+
+            match self.peek() {
+                Some(b'\'') if self.peek_at(1) == Some(b'\'') => {
+                    out.push('\'');
+                    self.pos += 2;
+                }
+                Some(b'\'') => {
+                    self.pos += 1;
+                    return Ok(out);
+                }
+                Some(..) => self.fold_quoted_break(&mut out, 0),
+                None => {
+                    return self
+                        .span(start, self.pos)
+                        .error("Unterminated string, expected \"'\".")
+                        .err()
+                }
+            }
+        }
+    }
+
+    /// Parse a fixed number of hex digits of an escape sequence.
+    fn parse_hex(&mut self, escape_start: usize, n: usize) -> Result<char> {
+        let hex = self.input.get(self.pos..self.pos + n).unwrap_or("");
+        let code_point = match u32::from_str_radix(hex, 16) {
+            Ok(code_point) if hex.bytes().all(|b| b.is_ascii_hexdigit()) => code_point,
+            _ => {
+                return self
+                    .span(escape_start, self.span_here().end())
+                    .error("Expected hex digits in escape sequence.")
+                    .err()
+            }
+        };
+        self.pos += n;
+        match char::from_u32(code_point) {
+            Some(ch) => Ok(ch),
+            None => self
+                .span(escape_start, self.pos)
+                .error("Invalid code point in escape sequence.")
+                .err(),
+        }
+    }
+
+    fn double_quoted(&mut self) -> Result<String> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut out = String::new();
+        // The length of `out` up to which whitespace came from escape sequences.
+        let mut keep = 0;
+        loop {
+            let run_start = self.pos;
+            while let Some(b) = self.peek() {
+                if matches!(b, b'"' | b'\\' | b'\n' | b'\r') {
+                    break;
+                }
+                self.pos += 1;
+            }
+            out.push_str(&self.input[run_start..self.pos]);
+
+            match self.peek() {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(out);
+                }
+                Some(b'\\') => {
+                    let escape_start = self.pos;
+                    self.pos += 1;
+                    let ch = match self.peek() {
+                        Some(b'0') => '\0',
+                        Some(b'a') => '\x07',
+                        Some(b'b') => '\x08',
+                        Some(b't' | b'\t') => '\t',
+                        Some(b'n') => '\n',
+                        Some(b'v') => '\x0b',
+                        Some(b'f') => '\x0c',
+                        Some(b'r') => '\r',
+                        Some(b'e') => '\x1b',
+                        Some(b' ') => ' ',
+                        Some(b'"') => '"',
+                        Some(b'/') => '/',
+                        Some(b'\\') => '\\',
+                        Some(b'N') => '\u{85}',
+                        Some(b'_') => '\u{a0}',
+                        Some(b'L') => '\u{2028}',
+                        Some(b'P') => '\u{2029}',
+                        Some(b @ (b'x' | b'u' | b'U')) => {
+                            self.pos += 1;
+                            let n = match b {
+                                b'x' => 2,
+                                b'u' => 4,
+                                _ => 8,
+                            };
+                            out.push(self.parse_hex(escape_start, n)?);
+                            keep = out.len();
+                            continue;
+                        }
+                        Some(b'\n' | b'\r') => {
+                            // An escaped line break joins the lines without a space.
+                            if self.peek() == Some(b'\r') && self.peek_at(1) == Some(b'\n') {
+                                self.pos += 1;
+                            }
+                            self.pos += 1;
+                            self.skip_inline_space();
+                            keep = out.len();
+                            continue;
+                        }
+                        _ => {
+                            return self
+                                .span(escape_start, self.span_here().end())
+                                .error("Invalid escape sequence.")
+                                .err()
+                        }
+                    };
+                    self.pos += 1;
+                    out.push(ch);
+                    keep = out.len();
+                }
+                Some(..) => self.fold_quoted_break(&mut out, keep),
+                None => {
+                    return self
+                        .span(start, self.pos)
+                        .error("Unterminated string, expected '\"'.")
+                        .err()
+                }
+            }
+        }
+    }
+
+    /// Skip whitespace, line breaks, and comments inside a flow collection.
+    fn skip_flow_space(&mut self) {
+        loop {
+            match self.peek() {
+                Some(b' ' | b'\t' | b'\n' | b'\r') => self.pos += 1,
+                Some(b'#') => self.skip_comment(),
+                _ => break,
+            }
+        }
+    }
+
+    /// Parse a node inside a flow collection, or a flow collection itself.
+    fn flow_node(&mut self) -> Result<Value> {
+        self.increase_depth()?;
+        let result = match self.peek() {
+            Some(b'[') => self.flow_sequence(),
+            Some(b'{') => self.flow_mapping(),
+            Some(b'"') => self.double_quoted().map(|s| Value::String(s.into())),
+            Some(b'\'') => self.single_quoted().map(|s| Value::String(s.into())),
+            Some(b'*') => self.alias(),
+            Some(b'&') => {
+                let name = self.anchor_name()?;
+                self.anchors.insert(name, None);
+                self.skip_flow_space();
+                let value = self.flow_node()?;
+                self.anchors.insert(name, Some(value.clone()));
+                Ok(value)
+            }
+            Some(b'!') => self.error_tag(),
+            Some(b'?') if self.is_blank_at(1) => self.error_complex_key(),
+            _ => {
+                let start = self.pos;
+                let end = self.scan_plain_line(start, true);
+                if end == start {
+                    return self.span_here().error("Expected a value.").err();
+                }
+                self.pos = end;
+                resolve_plain(&self.input[start..end], self.span(start, end))
+            }
+        };
+        self.depth -= 1;
+        result
+    }
+
+    fn flow_sequence(&mut self) -> Result<Value> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut items = Vec::new();
+        loop {
+            self.skip_flow_space();
+            if self.peek() == Some(b']') {
+                self.pos += 1;
+                break;
+            }
+            items.push(self.flow_node()?);
+            self.skip_flow_space();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    break;
+                }
+                Some(b':') => {
+                    return self
+                        .span_here()
+                        .error("Mappings inside flow sequences need braces.")
+                        .err()
+                }
+                None => {
+                    return self
+                        .span(start, start + 1)
+                        .error("Unterminated flow sequence, expected ']'.")
+                        .err()
+                }
+                Some(..) => return self.span_here().error("Expected ',' or ']'.").err(),
+            }
+        }
+        Ok(Value::List(Rc::new(items)))
+    }
+
+    fn flow_mapping(&mut self) -> Result<Value> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut entries = BTreeMap::new();
+        let mut merges = Vec::new();
+        loop {
+            self.skip_flow_space();
+            if self.peek() == Some(b'}') {
+                self.pos += 1;
+                break;
+            }
+            let key_start = self.pos;
+            if let Some(b'[' | b'{') = self.peek() {
+                return self.error_complex_key();
+            }
+            let key = self.flow_node()?;
+            let key_span = self.span(key_start, self.pos);
+            let is_merge = &self.input[key_start..self.pos] == "<<";
+            self.skip_flow_space();
+            let value = if self.peek() == Some(b':') {
+                self.pos += 1;
+                self.skip_flow_space();
+                match self.peek() {
+                    Some(b',' | b'}') => Value::Null,
+                    _ => self.flow_node()?,
+                }
+            } else {
+                Value::Null
+            };
+
+            if is_merge {
+                merges.push((key_span, value));
+            } else if entries.insert(key, value).is_some() {
+                return key_span
+                    .error("Duplicate key, keys in a mapping must be unique.")
+                    .err();
+            }
+
+            self.skip_flow_space();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    break;
+                }
+                None => {
+                    return self
+                        .span(start, start + 1)
+                        .error("Unterminated flow mapping, expected '}'.")
+                        .err()
+                }
+                Some(..) => return self.span_here().error("Expected ',' or '}'.").err(),
+            }
+        }
+        self.apply_merges(&mut entries, merges)?;
+        Ok(Value::Dict(Rc::new(entries)))
+    }
+}
+
+/// Fold the lines of a folded block scalar.
+///
+/// A line break between two lines of text becomes a space, empty lines become
+/// line breaks, and line breaks around more-indented lines are preserved.
+fn fold_lines(lines: &[&str]) -> String {
+    let mut out = String::new();
+    let mut prev_is_indented: Option<bool> = None;
+    let mut empty_lines = 0;
+    for line in lines {
+        if line.is_empty() {
+            empty_lines += 1;
+            continue;
+        }
+        let is_indented = line.starts_with([' ', '\t']);
+        let breaks = match prev_is_indented {
+            None => empty_lines,
+            Some(false) if !is_indented && empty_lines == 0 => {
+                out.push(' ');
+                0
+            }
+            Some(false) if !is_indented => empty_lines,
+            Some(..) => empty_lines + 1,
+        };
+        out.extend(std::iter::repeat('\n').take(breaks));
+        out.push_str(line);
+        prev_is_indented = Some(is_indented);
+        empty_lines = 0;
+    }
+    out
+}
+
+/// Return whether the plain scalar is a float in the YAML 1.2 core schema.
+fn is_float(s: &str) -> bool {
+    let unsigned = s.strip_prefix(['-', '+']).unwrap_or(s);
+    if matches!(unsigned, ".inf" | ".Inf" | ".INF") || matches!(s, ".nan" | ".NaN" | ".NAN") {
+        return true;
+    }
+    let (mantissa, exponent) = match unsigned.find(['e', 'E']) {
+        Some(i) => (&unsigned[..i], Some(&unsigned[i + 1..])),
+        None => (unsigned, None),
+    };
+    let (int_part, frac_part) = match mantissa.split_once('.') {
+        Some((int_part, frac_part)) => (int_part, Some(frac_part)),
+        None => (mantissa, None),
+    };
+    let is_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    let mantissa_ok = match frac_part {
+        None => !int_part.is_empty() && is_digits(int_part),
+        Some(frac) => {
+            is_digits(int_part) && is_digits(frac) && !(int_part.is_empty() && frac.is_empty())
+        }
+    };
+    let exponent_ok = match exponent {
+        None => frac_part.is_some(),
+        Some(e) => {
+            let e = e.strip_prefix(['-', '+']).unwrap_or(e);
+            !e.is_empty() && is_digits(e)
+        }
+    };
+    mantissa_ok && exponent_ok
+}
+
+/// Resolve a plain scalar to a value with the YAML 1.2 core schema.
+fn resolve_plain(s: &str, span: Span) -> Result<Value> {
+    match s {
+        "" | "~" | "null" | "Null" | "NULL" => return Ok(Value::Null),
+        "true" | "True" | "TRUE" => return Ok(Value::Bool(true)),
+        "false" | "False" | "FALSE" => return Ok(Value::Bool(false)),
+        _ => {}
+    }
+
+    let unsigned = s.strip_prefix(['-', '+']).unwrap_or(s);
+    let int = if let Some(hex) = s.strip_prefix("0x") {
+        (!hex.is_empty() && hex.bytes().all(|b| b.is_ascii_hexdigit()))
+            .then(|| i128::from_str_radix(hex, 16))
+    } else if let Some(oct) = s.strip_prefix("0o") {
+        (!oct.is_empty() && oct.bytes().all(|b| (b'0'..=b'7').contains(&b)))
+            .then(|| i128::from_str_radix(oct, 8))
+    } else {
+        (!unsigned.is_empty() && unsigned.bytes().all(|b| b.is_ascii_digit())).then(|| s.parse())
+    };
+    match int {
+        Some(Ok(n)) => return Ok(Value::Int(n)),
+        Some(Err(..)) => return span.error("Overflow in integer literal.").err(),
+        None => {}
+    }
+
+    if is_float(s) {
+        return match Decimal::parse(s) {
+            Some(d) => Ok(Value::Number(d)),
+            None if unsigned.ends_with(['f', 'F', 'n', 'N']) => span
+                .error("RCL numbers cannot be infinite or NaN.")
+                .with_help("To use this value as a string, put it in quotes.")
+                .err(),
+            None => span.error("Overflow in number literal.").err(),
+        };
+    }
+
+    Ok(Value::String(s.into()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse_yaml;
+    use crate::fmt_rcl::format_rcl;
+    use crate::pprint::Config;
+    use crate::source::DocId;
+
+    fn parse(input: &str) -> String {
+        let value = parse_yaml(DocId(0), input).unwrap();
+        let cfg = Config::default();
+        format_rcl(&value).println(&cfg).to_string_no_markup()
+    }
+
+    fn fail(input: &str) -> String {
+        let err = parse_yaml(DocId(0), input).err().unwrap();
+        let cfg = Config::default();
+        err.message.println(&cfg).to_string_no_markup()
+    }
+
+    #[test]
+    fn parse_yaml_block_collections() {
+        assert_eq!(
+            parse("a: 1\nb:\n  c: true\n  d: ~\n"),
+            "{ a = 1, b = { c = true, d = null } }\n"
+        );
+        assert_eq!(
+            parse("- a\n- - b\n  - c\n-\n"),
+            "[\"a\", [\"b\", \"c\"], null]\n"
+        );
+        assert_eq!(parse("k:\n- 1\n- 2\nl: x\n"), "{ k = [1, 2], l = \"x\" }\n");
+        assert_eq!(
+            parse("- name: a\n  port: 80\n- name: b\n"),
+            "[{ name = \"a\", port = 80 }, { name = \"b\" }]\n"
+        );
+        assert_eq!(
+            parse("# Comment\n\nkey: value # Comment\n"),
+            "{ key = \"value\" }\n"
+        );
+        assert_eq!(parse("1: one\n\"two\": 2\n"), "{ 1: \"one\", two = 2 }\n");
+        assert_eq!(
+            parse("[3.14, -.5, 1e3, 2.5E-8]"),
+            "[3.14, -0.5, 1000.0, 2.5e-8]\n"
+        );
+    }
+
+    #[test]
+    fn parse_yaml_scalars() {
+        assert_eq!(
+            parse("[yes, no, on, 0x1f, 0o17, -3, +4, 007, 1.2.3]"),
+            "[\"yes\", \"no\", \"on\", 31, 15, -3, 4, 7, \"1.2.3\"]\n"
+        );
+        assert_eq!(
+            parse("a: 'it''s'\nb: \"tab\\tq\\\"\\u00e9\"\n"),
+            "{ a = \"it's\", b = \"tab\\tq\\\"\u{e9}\" }\n"
+        );
+        assert_eq!(
+            parse("a: one\n  two\n\n  three\n"),
+            "{ a = \"one two\\nthree\" }\n"
+        );
+        assert_eq!(parse("\"a\n  b\\\n  c\""), "\"a bc\"\n");
+        assert_eq!(
+            parse("url: http://example.com:80/a#b\n"),
+            "{ url = \"http://example.com:80/a#b\" }\n"
+        );
+    }
+
+    #[test]
+    fn parse_yaml_block_scalars() {
+        assert_eq!(
+            parse("a: |\n  x\n   y\n\n  z\n\nb: 1\n"),
+            "{ a = \"x\\n y\\n\\nz\\n\", b = 1 }\n"
+        );
+        assert_eq!(parse("a: |-\n  x\n"), "{ a = \"x\" }\n");
+        assert_eq!(parse("a: |+\n  x\n\n"), "{ a = \"x\\n\\n\" }\n");
+        assert_eq!(
+            parse("a: >\n  x\n  y\n\n  z\n    w\n  v\n"),
+            "{ a = \"x y\\nz\\n  w\\nv\\n\" }\n"
+        );
+        assert_eq!(parse("- |2\n    x\n"), "[\"  x\\n\"]\n");
+    }
+
+    #[test]
+    fn parse_yaml_flow_collections() {
+        assert_eq!(
+            parse("{a: [1, 2,], \"b\":c, d}"),
+            "{ a = [1, 2], b = \"c\", d = null }\n"
+        );
+        assert_eq!(
+            parse("k: [a b, {x: y}]\n"),
+            "{ k = [\"a b\", { x = \"y\" }] }\n"
+        );
+    }
+
+    #[test]
+    fn parse_yaml_anchors_and_merges() {
+        assert_eq!(
+            parse("base: &b\n  x: 1\n  y: 2\nderived:\n  <<: *b\n  y: 3\n"),
+            "{ base = { x = 1, y = 2 }, derived = { x = 1, y = 3 } }\n",
+        );
+        assert_eq!(parse("- &a [1]\n- *a\n"), "[[1], [1]]\n");
+    }
+
+    #[test]
+    fn parse_yaml_documents() {
+        assert_eq!(parse(""), "null\n");
+        assert_eq!(parse("--- 1\n--- 2\n...\n"), "[1, 2]\n");
+        assert_eq!(parse("%YAML 1.2\n---\na: 1\n"), "{ a = 1 }\n");
+        assert_eq!(parse("---\n---\nx\n"), "[null, \"x\"]\n");
+    }
+
+    #[test]
+    fn parse_yaml_rejects_unsupported() {
+        assert_eq!(
+            fail("a: &a [*a]"),
+            "This alias refers to an anchor that contains it, which would be a cycle.\n"
+        );
+        assert_eq!(
+            fail("a: *b"),
+            "This alias refers to an anchor that is not defined before it.\n"
+        );
+        assert_eq!(
+            fail("a: 1\na: 2\n"),
+            "Duplicate key, keys in a mapping must be unique.\n"
+        );
+        assert_eq!(fail("a: .inf"), "RCL numbers cannot be infinite or NaN.\n");
+        assert_eq!(fail("a: .NaN"), "RCL numbers cannot be infinite or NaN.\n");
+        assert_eq!(
+            fail("a: b: c"),
+            "Unexpected ':'. A string that contains ': ' needs to be quoted.\n"
+        );
+        assert_eq!(fail("a: !!str 1"), "YAML tags are not supported.\n");
+        assert_eq!(
+            fail("a:\n\tb: 1"),
+            "Tabs are not allowed for indentation in YAML.\n"
+        );
+        assert_eq!(
+            fail("a:\n  b: 1\n c: 2\n"),
+            "Unexpected indentation, expected a key at the same level as the preceding key.\n"
+        );
+        assert_eq!(
+            fail("- a\nb: 1\n"),
+            "Expected the document to end after the value.\n"
+        );
+        assert_eq!(fail("[1, 2"), "Unterminated flow sequence, expected ']'.\n");
+    }
+}
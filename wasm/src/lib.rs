@@ -33,11 +33,15 @@ fn markup_class(markup: Markup) -> &'static str {
     match markup {
         Markup::Builtin => "builtin",
         Markup::Comment => "comment",
+        Markup::DiffAdd => "diff-add",
+        Markup::DiffChange => "diff-change",
+        Markup::DiffRemove => "diff-remove",
         Markup::Error => "error",
         Markup::Escape => "escape",
         Markup::Field => "field",
         Markup::Highlight => "highlight",
         Markup::Keyword => "keyword",
+        Markup::Link => "link",
         Markup::None => "text",
         Markup::Number => "number",
         Markup::String => "string",
@@ -84,7 +88,10 @@ fn print_markup(max_len: u32, markup_string: &MarkupString, out_node: &Node) {
 
 /// Pretty-print a document, append it as DOM nodes.
 fn pprint_doc(cfg: &PrintConfig, doc: Doc, out_node: &Node) {
-    let pprint_cfg = pprint::Config { width: cfg.width };
+    let pprint_cfg = pprint::Config {
+        width: cfg.width,
+        ..pprint::Config::default()
+    };
     let markup_string = doc.println(&pprint_cfg);
     print_markup(cfg.max_len, &markup_string, out_node);
 }
@@ -151,7 +158,7 @@ fn rcl_evaluate_query_impl<'a>(
     out_node: &Node,
 ) -> Result<()> {
     loader.set_filesystem(Box::new(VoidFilesystem));
-    let id = loader.load_string(query.to_string());
+    let id = loader.load_named_string("query", query.to_string());
     let mut tracer = VoidTracer;
     let mut evaluator = Evaluator::new(loader, &mut tracer);
     let mut type_env = rcl::typecheck::prelude();
@@ -190,6 +197,86 @@ pub fn rcl_evaluate_query(
     }
 }
 
+/// The result of [`rcl_evaluate`] or [`rcl_format`], for callers without a DOM.
+///
+/// Exactly one of the two is non-empty: the output if the call succeeded, and
+/// the error report otherwise.
+#[wasm_bindgen]
+pub struct Outcome {
+    output: String,
+    diagnostics: String,
+}
+
+#[wasm_bindgen]
+impl Outcome {
+    #[wasm_bindgen(getter)]
+    pub fn output(&self) -> String {
+        self.output.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn diagnostics(&self) -> String {
+        self.diagnostics.clone()
+    }
+}
+
+impl Outcome {
+    /// Print the result to plain strings, reporting errors against the loader.
+    fn new(loader: &Loader, width: u32, result: Result<Doc>) -> Outcome {
+        let cfg = pprint::Config {
+            width,
+            ..pprint::Config::default()
+        };
+        match result {
+            Ok(doc) => Outcome {
+                output: doc.println(&cfg).to_string_no_markup(),
+                diagnostics: String::new(),
+            },
+            Err(err) => Outcome {
+                output: String::new(),
+                diagnostics: err
+                    .report(&loader.as_inputs())
+                    .println(&cfg)
+                    .to_string_no_markup(),
+            },
+        }
+    }
+}
+
+/// Evaluate a document and format the result as RCL.
+///
+/// Unlike [`rcl_evaluate_json`], this returns strings rather than writing into
+/// a DOM node, for editor integrations that do their own rendering.
+#[wasm_bindgen]
+pub fn rcl_evaluate(input: &str, out_width: u32) -> Outcome {
+    let mut loader = Loader::new();
+    loader.set_filesystem(Box::new(VoidFilesystem));
+    let id = loader.load_string(input.to_string());
+    let mut tracer = VoidTracer;
+    let mut evaluator = Evaluator::new(&mut loader, &mut tracer);
+    let mut type_env = rcl::typecheck::prelude();
+    let mut value_env = rcl::runtime::prelude();
+    let result = evaluator.eval_doc(&mut type_env, &mut value_env, id);
+    let doc = result.map(|value| rcl::fmt_rcl::format_rcl(&value).into_owned());
+    Outcome::new(&loader, out_width, doc)
+}
+
+/// Format a document, like `rcl format` does.
+#[wasm_bindgen]
+pub fn rcl_format(input: &str, out_width: u32) -> Outcome {
+    let mut loader = Loader::new();
+    loader.set_filesystem(Box::new(VoidFilesystem));
+    let id = loader.load_string(input.to_string());
+    match loader.get_cst(id) {
+        Ok(cst) => {
+            let data = loader.get_doc(id).data;
+            let doc = rcl::fmt_cst::format_expr(data, &cst);
+            Outcome::new(&loader, out_width, Ok(doc))
+        }
+        Err(err) => Outcome::new(&loader, out_width, Err(err)),
+    }
+}
+
 /// An edit to transform a string `before` into `after`.
 ///
 /// An edit consists of a _delete_ followed by an _insert_:
@@ -337,7 +424,7 @@ pub fn rcl_highlight(input: &str, good_input: &str, out_node: &Node) -> bool {
 
 #[cfg(test)]
 mod wasm_test {
-    use super::{get_edit, Edit};
+    use super::{get_edit, rcl_evaluate, rcl_format, Edit};
     use rcl::source::{DocId, Span};
     const D: DocId = DocId(0);
 
@@ -445,5 +532,20 @@ mod wasm_test {
         assert_eq!(spans, expected_);
     }
 
+    #[test]
+    fn evaluate_and_format_return_strings() {
+        let outcome = rcl_evaluate("let x = 1; { a = [x, x + 1] }", 80);
+        assert_eq!(outcome.output(), "{ a = [1, 2] }\n");
+        assert_eq!(outcome.diagnostics(), "");
+
+        let outcome = rcl_evaluate("import \"a.rcl\"", 80);
+        assert_eq!(outcome.output(), "");
+        assert!(outcome.diagnostics().contains("Error:"));
+
+        let outcome = rcl_format("{a=1,\n}", 80);
+        assert_eq!(outcome.output(), "{\n  a = 1,\n}\n");
+        assert!(rcl_format("{", 80).diagnostics().contains("Error:"));
+    }
+
     // TODO: Write a fuzz test for this part.
 }